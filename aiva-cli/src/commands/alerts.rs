@@ -0,0 +1,170 @@
+use crate::output::{OutputFormat, OutputFormatter, print_info, print_success, write_output_file};
+use aiva_core::{
+    AivaError, Alert, AlertFilter, AlertSeverity, Config, LogStore, MONITORING_MEMORY_CAP,
+    MonitoringService, Result, VMManager, VMOrchestrator,
+};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+use tabled::Tabled;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SeverityArg {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl From<SeverityArg> for AlertSeverity {
+    fn from(severity: SeverityArg) -> Self {
+        match severity {
+            SeverityArg::Low => AlertSeverity::Low,
+            SeverityArg::Medium => AlertSeverity::Medium,
+            SeverityArg::High => AlertSeverity::High,
+            SeverityArg::Critical => AlertSeverity::Critical,
+        }
+    }
+}
+
+/// Command line filters of `aiva alerts`
+#[derive(Debug, Default)]
+pub struct AlertsQuery {
+    pub vm: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub severity: Option<SeverityArg>,
+    pub resolved: Option<bool>,
+}
+
+/// `--since`/`--until` value: a duration before now such as `30m`, `2h` or
+/// `7d`, or an RFC 3339 time
+pub(crate) fn parse_time_bound(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    time_bound_at(value, Utc::now())
+}
+
+pub(crate) fn time_bound_at(
+    value: &str,
+    now: DateTime<Utc>,
+) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let invalid =
+        || format!("'{value}' is neither a duration like 30m, 2h, 7d nor an RFC 3339 time");
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let ago = match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => return Err(invalid()),
+    }
+    .ok_or_else(invalid)?;
+    now.checked_sub_signed(ago).ok_or_else(invalid)
+}
+
+#[derive(Serialize, Tabled)]
+struct AlertRow {
+    id: Uuid,
+    vm: String,
+    severity: String,
+    #[tabled(rename = "type")]
+    #[serde(rename = "type")]
+    alert_type: String,
+    raised: String,
+    resolved: bool,
+    message: String,
+}
+
+impl AlertRow {
+    fn new(alert: Alert, vm_names: &HashMap<String, String>) -> Self {
+        let vm = alert.vm_id.map_or_else(
+            || "-".to_string(),
+            |id| vm_names.get(&id).cloned().unwrap_or(id),
+        );
+        Self {
+            id: alert.id,
+            vm,
+            severity: alert.severity.to_string(),
+            alert_type: alert.alert_type.to_string(),
+            raised: alert.timestamp.format("%Y-%m-%d %H:%M:%S").to_string(),
+            resolved: alert.resolved,
+            message: alert.message,
+        }
+    }
+}
+
+/// Alerts persisted by the monitoring service
+fn monitoring() -> MonitoringService {
+    MonitoringService::new(Box::new(aiva_core::DefaultMetricsCollector))
+        .with_overflow(LogStore::default_location(), MONITORING_MEMORY_CAP)
+}
+
+pub async fn list(query: AlertsQuery, _config: Config, format: OutputFormat) -> Result<()> {
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = VMOrchestrator::new(platform);
+    vm_manager.load_state().await?;
+
+    // Alerts name VMs by id; show names and accept them in --vm
+    let vm_names: HashMap<String, String> = vm_manager
+        .list_vms()
+        .await?
+        .into_iter()
+        .map(|vm| (vm.id.to_string(), vm.name))
+        .collect();
+    let vm_id = query.vm.map(|vm| {
+        vm_names
+            .iter()
+            .find(|(_, name)| **name == vm)
+            .map_or(vm, |(id, _)| id.clone())
+    });
+
+    let filter = AlertFilter {
+        vm_id,
+        since: query.since,
+        until: query.until,
+        min_severity: query.severity.map(AlertSeverity::from),
+        resolved: query.resolved,
+    };
+    let rows: Vec<AlertRow> = monitoring()
+        .get_alerts_filtered(&filter)
+        .await?
+        .into_iter()
+        .map(|alert| AlertRow::new(alert, &vm_names))
+        .collect();
+
+    write_output_file(&rows)?;
+    if rows.is_empty() && format == OutputFormat::Table {
+        print_info("No alerts match");
+        return Ok(());
+    }
+    println!("{}", format.format_table(rows));
+    Ok(())
+}
+
+pub async fn resolve(id: Uuid, _config: Config, _format: OutputFormat) -> Result<()> {
+    let monitoring = monitoring();
+    let alert = monitoring
+        .get_alerts(None)
+        .await?
+        .into_iter()
+        .find(|alert| alert.id == id)
+        .ok_or_else(|| AivaError::ConfigError(format!("No alert with id {id}")))?;
+
+    if alert.resolved {
+        print_info(&format!("Alert {id} is already resolved"));
+        return Ok(());
+    }
+    monitoring.resolve_alert(&id).await?;
+    print_success(&format!("Resolved alert {id}: {}", alert.message));
+    Ok(())
+}
@@ -0,0 +1,245 @@
+use crate::output::{OutputFormat, OutputFormatter, print_info, print_progress, print_warning};
+use crate::utils::get_vm_dir;
+use aiva_core::{
+    AivaError, BootTimings, Config, LatencyStats, Result, VMConfig, VMInstance, VMManager,
+    VMOrchestrator, VMState, time_phase,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tabled::Tabled;
+use uuid::Uuid;
+
+/// Command used to probe guest readiness and exec round-trips
+const PROBE_COMMAND: &str = "echo aiva-bench";
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Tabled)]
+struct BenchmarkRow {
+    phase: String,
+    samples: usize,
+    #[tabled(display_with = "format_ms")]
+    min_ms: f64,
+    #[tabled(display_with = "format_ms")]
+    p50_ms: f64,
+    #[tabled(display_with = "format_ms")]
+    p95_ms: f64,
+    #[tabled(display_with = "format_ms")]
+    max_ms: f64,
+    #[tabled(display_with = "format_ms")]
+    mean_ms: f64,
+}
+
+impl BenchmarkRow {
+    fn new(phase: &str, stats: LatencyStats) -> Self {
+        Self {
+            phase: phase.to_string(),
+            samples: stats.samples,
+            min_ms: stats.min_ms,
+            p50_ms: stats.p50_ms,
+            p95_ms: stats.p95_ms,
+            max_ms: stats.max_ms,
+            mean_ms: stats.mean_ms,
+        }
+    }
+}
+
+fn format_ms(value: &f64) -> String {
+    format!("{value:.1}")
+}
+
+pub async fn execute(
+    name: String,
+    iterations: usize,
+    skip_cold: bool,
+    _config: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    if iterations == 0 {
+        return Err(AivaError::ConfigError(
+            "Iterations must be at least 1".to_string(),
+        ));
+    }
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let vm = vm_manager
+        .get_vm_by_name(&name)
+        .await?
+        .ok_or_else(|| AivaError::VMError {
+            vm_name: name.clone(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+
+    let mut rows = Vec::new();
+
+    if !skip_cold {
+        print_progress(&format!(
+            "Measuring cold start over {iterations} iteration(s)..."
+        ));
+        let config = cold_start_config(&vm, load_vm_config(&name).ok())?;
+        let timings = measure_cold_start(&vm_manager, &name, &config, iterations).await?;
+        for (phase, samples) in [
+            (
+                "cold: create",
+                timings.iter().map(|t| t.create).collect::<Vec<_>>(),
+            ),
+            ("cold: start", timings.iter().map(|t| t.start).collect()),
+            ("cold: ready", timings.iter().map(|t| t.ready).collect()),
+            (
+                "cold: total",
+                timings.iter().map(BootTimings::total).collect(),
+            ),
+        ] {
+            if let Some(stats) = LatencyStats::from_samples(&samples) {
+                rows.push(BenchmarkRow::new(phase, stats));
+            }
+        }
+    }
+
+    // Snapshot restore is not available yet, so a warm restart is a stop/start
+    // cycle of the existing VM
+    print_progress(&format!(
+        "Measuring warm restart over {iterations} iteration(s)..."
+    ));
+    let samples = measure_warm_restart(&vm_manager, &vm.id, iterations).await?;
+    if let Some(stats) = LatencyStats::from_samples(&samples) {
+        rows.push(BenchmarkRow::new("warm restart", stats));
+    }
+
+    print_progress(&format!(
+        "Measuring command round-trip over {iterations} iteration(s)..."
+    ));
+    let mut samples = Vec::with_capacity(iterations);
+    for _ in 0..iterations {
+        let (result, elapsed) = time_phase(vm_manager.execute_command(&vm.id, PROBE_COMMAND)).await;
+        result?;
+        samples.push(elapsed);
+    }
+    if let Some(stats) = LatencyStats::from_samples(&samples) {
+        rows.push(BenchmarkRow::new("exec round-trip", stats));
+    }
+
+    println!("{}", format.format_table(rows));
+    Ok(())
+}
+
+/// Config of the throwaway VMs booted to measure cold start, taken from
+/// `config` or the stored one of `vm`. They share the guest IP of `vm`, so
+/// it has to be stopped; its port mappings are left out.
+pub(crate) fn cold_start_config(vm: &VMInstance, config: Option<VMConfig>) -> Result<VMConfig> {
+    if vm.state != VMState::Stopped {
+        return Err(AivaError::VMError {
+            vm_name: vm.name.clone(),
+            state: vm.state,
+            message: format!(
+                "Cold start clones reuse the guest IP of the VM; stop it first \
+                 (aiva stop {}) or pass --skip-cold",
+                vm.name
+            ),
+        });
+    }
+
+    let mut config = config.unwrap_or_else(|| vm.config.clone());
+    config.network.port_mappings.clear();
+    Ok(config)
+}
+
+async fn measure_cold_start(
+    vm_manager: &VMOrchestrator,
+    name: &str,
+    config: &VMConfig,
+    iterations: usize,
+) -> Result<Vec<BootTimings>> {
+    let mut timings = Vec::with_capacity(iterations);
+
+    for i in 0..iterations {
+        let bench_name = format!("{name}-bench-{i}");
+        let (created, create) =
+            time_phase(vm_manager.create_vm(bench_name.clone(), config.clone())).await;
+        let created = created?;
+
+        let result = async {
+            let (started, start) = time_phase(vm_manager.start_vm(&created.id)).await;
+            started?;
+            let (ready, ready_time) = time_phase(wait_until_ready(vm_manager, &created.id)).await;
+            ready?;
+            Ok::<_, AivaError>(BootTimings {
+                create,
+                start,
+                ready: ready_time,
+            })
+        }
+        .await;
+
+        // Always tear the throwaway VM down, even when a phase failed
+        if let Some(vm) = vm_manager.get_vm(&created.id).await?
+            && matches!(vm.state, VMState::Running | VMState::Paused)
+            && let Err(e) = vm_manager.stop_vm(&created.id, true).await
+        {
+            print_warning(&format!("Failed to stop benchmark VM {bench_name}: {e}"));
+        }
+        if let Err(e) = vm_manager.delete_vm(&created.id).await {
+            print_warning(&format!("Failed to delete benchmark VM {bench_name}: {e}"));
+        }
+
+        timings.push(result?);
+    }
+
+    Ok(timings)
+}
+
+async fn measure_warm_restart(
+    vm_manager: &VMOrchestrator,
+    id: &Uuid,
+    iterations: usize,
+) -> Result<Vec<Duration>> {
+    let mut samples = Vec::with_capacity(iterations);
+
+    for _ in 0..iterations {
+        if let Some(vm) = vm_manager.get_vm(id).await?
+            && vm.state == VMState::Running
+        {
+            vm_manager.stop_vm(id, false).await?;
+        }
+
+        let (started, start) = time_phase(vm_manager.start_vm(id)).await;
+        started?;
+        let (ready, ready_time) = time_phase(wait_until_ready(vm_manager, id)).await;
+        ready?;
+        samples.push(start + ready_time);
+    }
+
+    Ok(samples)
+}
+
+async fn wait_until_ready(vm_manager: &VMOrchestrator, id: &Uuid) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + READY_TIMEOUT;
+
+    loop {
+        match vm_manager.execute_command(id, PROBE_COMMAND).await {
+            Ok(output) if output.contains("aiva-bench") => return Ok(()),
+            Ok(_) | Err(_) if tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+            Ok(output) => {
+                return Err(AivaError::PlatformError {
+                    platform: "benchmark".to_string(),
+                    message: format!("Unexpected readiness probe output: {}", output.trim()),
+                    recoverable: true,
+                });
+            }
+            Err(e) => {
+                print_info("Guest did not become ready before the timeout");
+                return Err(e);
+            }
+        }
+    }
+}
+
+fn load_vm_config(name: &str) -> Result<VMConfig> {
+    aiva_core::read_vm_config(&get_vm_dir(name)?.join("config").join("config.json"))
+}
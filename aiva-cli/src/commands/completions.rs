@@ -0,0 +1,18 @@
+use crate::Cli;
+use crate::output::OutputFormat;
+use aiva_core::{Config, Result};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io::Write;
+
+pub async fn execute(shell: Shell, _config: Config, _format: OutputFormat) -> Result<()> {
+    generate_completions(shell, &mut std::io::stdout());
+    Ok(())
+}
+
+/// Write the completion script for `shell` covering the full command tree
+pub fn generate_completions(shell: Shell, out: &mut dyn Write) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, out);
+}
@@ -1,24 +1,38 @@
 use crate::commands::ConfigAction;
-use crate::output::{OutputFormat, print_error, print_info, print_success};
-use aiva_core::{Config, Result};
+use crate::commands::init::PreviousInit;
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_success, print_warning,
+};
+use crate::utils::{get_vm_dir, resolve_security_policy};
+use aiva_core::{
+    AivaError, Config, Result, RuntimeInfo, ScaleRequest, TemplateManager, VMConfig, VMDefinition,
+    VMManager, VMOrchestrator, VMState, VMTemplate,
+};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub async fn execute(action: ConfigAction, _config: Config, _format: OutputFormat) -> Result<()> {
+pub async fn execute(action: ConfigAction, config: Config, format: OutputFormat) -> Result<()> {
     match action {
         ConfigAction::Get { name, key } => {
             print_info(&format!("Getting config value '{key}' for VM '{name}'"));
 
-            // Get VM-specific configuration
-            let vm_config = get_vm_config(&name)?;
-
-            // Use dot notation to access nested config values
-            let value = get_config_value(&vm_config, &key)?;
+            let value = if RuntimeInfo::is_runtime_key(&key) {
+                // Runtime keys come from the orchestrator's live state, the
+                // on-disk config.json never holds them
+                get_runtime_value(&name, &key).await?
+            } else {
+                // Use dot notation to access nested config values
+                get_config_value(&get_vm_config(&name)?, &key)?
+            };
 
             match value {
                 Some(val) => {
                     println!("{key}: {val}");
                 }
+                None if RuntimeInfo::is_runtime_key(&key) => {
+                    println!("{key}: (not assigned, is the VM running?)");
+                }
                 None => {
                     print_error(&format!(
                         "Configuration key '{key}' not found for VM '{name}'"
@@ -26,7 +40,12 @@ pub async fn execute(action: ConfigAction, _config: Config, _format: OutputForma
                 }
             }
         }
-        ConfigAction::Set { name, key, value } => {
+        ConfigAction::Set {
+            name,
+            key,
+            value,
+            apply_now,
+        } => {
             print_info(&format!(
                 "Setting config '{key}' = '{value}' for VM '{name}'"
             ));
@@ -37,10 +56,32 @@ pub async fn execute(action: ConfigAction, _config: Config, _format: OutputForma
             // Set the configuration value
             set_config_value(&mut vm_config, &key, &value)?;
 
+            let platform = aiva_platform::get_current_platform()?;
+            let vm_manager = VMOrchestrator::new(platform);
+            vm_manager.load_state().await?;
+
+            // A bigger disk_gb only takes effect once the rootfs is grown
+            if key == "disk_gb" {
+                grow_vm_disk(&vm_manager, &name, vm_config.disk_gb).await?;
+            }
+
             // Save the updated configuration
             save_vm_config(&name, &vm_config)?;
 
             print_success(&format!("Config '{key}' set to '{value}' for VM '{name}'"));
+
+            match apply_to_running_vm(&vm_manager, &name, &key, &vm_config, apply_now).await? {
+                LiveOutcome::NotRunning | LiveOutcome::AlreadyLive => {}
+                LiveOutcome::Applied(message) => print_success(&message),
+                LiveOutcome::RestartRequired { live_capable } => {
+                    print_warning(&format!(
+                        "VM '{name}' is running, '{key}' takes effect after it is restarted"
+                    ));
+                    if live_capable && !apply_now {
+                        print_info("Pass --apply-now to push the change to the running VM");
+                    }
+                }
+            }
         }
         ConfigAction::List { name } => {
             print_info(&format!("Listing configuration for VM '{name}'"));
@@ -61,7 +102,15 @@ pub async fn execute(action: ConfigAction, _config: Config, _format: OutputForma
             println!("    Subnet: {}", vm_config.network.subnet);
             println!("    Gateway: {}", vm_config.network.gateway);
             println!("    DNS Servers: {:?}", vm_config.network.dns_servers);
+            println!("    DNS Search: {:?}", vm_config.network.dns_search);
+            for (domain, servers) in &vm_config.network.dns_overrides {
+                println!("    DNS Override: {domain} -> {}", servers.join(", "));
+            }
+            if let Some(hostname) = &vm_config.network.hostname {
+                println!("    Hostname: {hostname}");
+            }
             println!("    DHCP Enabled: {}", vm_config.network.dhcp_enabled);
+            println!("    Audit Egress: {}", vm_config.network.audit_egress);
 
             println!("  Storage:");
             println!("    Cache Strategy: {}", vm_config.storage.cache_strategy);
@@ -69,22 +118,174 @@ pub async fn execute(action: ConfigAction, _config: Config, _format: OutputForma
                 "    Additional Drives: {}",
                 vm_config.storage.additional_drives.len()
             );
+
+            println!("  Security:");
+            println!(
+                "    Policy: {}",
+                vm_config.security_policy.as_deref().unwrap_or("none")
+            );
+
+            println!("  Execution:");
+            println!(
+                "    Working Directory: {}",
+                aiva_core::ExecContext::from_config(&vm_config)
+                    .workdir()
+                    .display()
+            );
+            println!(
+                "    Run As User: {}",
+                vm_config
+                    .run_as_user
+                    .as_deref()
+                    .unwrap_or("(connection user)")
+            );
+            println!(
+                "    Transports: {}",
+                if vm_config.execution.transports.is_empty() {
+                    "(platform default)".to_string()
+                } else {
+                    vm_config
+                        .execution
+                        .transports
+                        .iter()
+                        .map(|transport| transport.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            );
+
+            println!("  Logging:");
+            println!("    Guest Paths: {:?}", vm_config.logging.paths);
+
+            if !vm_config.labels.is_empty() {
+                println!("  Labels:");
+                for (key, value) in &vm_config.labels {
+                    println!("    {key}: {value}");
+                }
+            }
+
+            if !vm_config.metadata.is_empty() {
+                println!("  Metadata:");
+                for (key, value) in &vm_config.metadata {
+                    println!("    {key}: {value}");
+                }
+            }
+        }
+        ConfigAction::Validate {
+            name,
+            allow_unsafe_cache,
+        } => {
+            print_info(&format!("Validating configuration for VM '{name}'"));
+
+            let vm_config = get_vm_config(&name)?;
+            let policy = match &vm_config.security_policy {
+                Some(policy_name) => Some(resolve_security_policy(policy_name).await?),
+                None => None,
+            };
+
+            if let Err(e) = aiva_security::validate_cache_strategy(
+                policy.as_ref(),
+                &vm_config.storage,
+                config.security.production,
+                allow_unsafe_cache,
+            ) {
+                print_error(&e.to_string());
+                return Err(e);
+            }
+
+            print_success(&format!("Configuration for VM '{name}' is valid"));
+        }
+        ConfigAction::Export { name } => {
+            // Only the document goes to stdout, so it can be redirected
+            println!("{}", format.format(export_definition(&name)?));
+        }
+        ConfigAction::Import { file, name } => {
+            print_info(&format!("Importing VM '{name}' from {}", file.display()));
+            let definition = VMDefinition::parse(&fs::read_to_string(&file)?)?;
+            import_definition(&name, definition).await?;
+            print_success(&format!(
+                "Created VM '{name}'; run 'aiva start {name}' to boot it"
+            ));
         }
     }
 
     Ok(())
 }
 
+/// Definition of the VM `name`, from its config.json and the template
+/// `init` recorded next to it
+pub(crate) fn export_definition(name: &str) -> Result<VMDefinition> {
+    let config = get_vm_config(name)?;
+    let template_path = get_vm_config_path(name)?.with_file_name("template.json");
+    let template = match fs::read_to_string(&template_path) {
+        Ok(content) => Some(serde_json::from_str::<VMTemplate>(&content)?.name),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(VMDefinition::new(&config, template))
+}
+
+/// Check that `definition` can be imported here: its template and security
+/// policy exist and the configuration is valid
+pub(crate) async fn validate_definition(
+    definition: VMDefinition,
+) -> Result<(VMConfig, Option<VMTemplate>)> {
+    let template = definition
+        .template
+        .as_deref()
+        .map(TemplateManager::get_template)
+        .transpose()?;
+    if let Some(policy) = &definition.security_policy {
+        resolve_security_policy(policy).await?;
+    }
+    Ok((definition.into_config()?, template))
+}
+
+/// Create the VM `name` from `definition`, writing the same files `init`
+/// does. An existing VM of that name is never replaced.
+async fn import_definition(name: &str, definition: VMDefinition) -> Result<()> {
+    let (vm_config, template) = validate_definition(definition).await?;
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = VMOrchestrator::new(platform);
+    vm_manager.load_state().await?;
+
+    let vm_dir = get_vm_dir(name)?;
+    let registered = vm_manager.get_vm_by_name(name).await?.is_some();
+    if PreviousInit::detect(&vm_dir, registered) != PreviousInit::None {
+        return Err(AivaError::VMError {
+            vm_name: name.to_string(),
+            state: VMState::Stopped,
+            message: "VM already exists; import it under another --name".to_string(),
+        });
+    }
+
+    fs::create_dir_all(vm_dir.join("data"))?;
+    fs::create_dir_all(vm_dir.join("logs"))?;
+    save_vm_config(name, &vm_config)?;
+    if let Some(template) = &template {
+        fs::write(
+            vm_dir.join("config").join("template.json"),
+            serde_json::to_string_pretty(template)?,
+        )?;
+        fs::write(vm_dir.join("setup.sh"), template.get_setup_script())?;
+    }
+
+    let vm = vm_manager.create_vm(name.to_string(), vm_config).await?;
+    print_info(&format!("Created VM instance with ID: {}", vm.id));
+    Ok(())
+}
+
 fn get_vm_config_path(name: &str) -> Result<PathBuf> {
     let home = dirs::home_dir().ok_or_else(|| {
         aiva_core::AivaError::ConfigError("Cannot determine home directory".to_string())
     })?;
 
     let vm_dir = home.join(".aiva").join("data").join("vms").join(name);
-    Ok(vm_dir.join("config.json"))
+    Ok(vm_dir.join("config").join("config.json"))
 }
 
-fn get_vm_config(name: &str) -> Result<aiva_core::VMConfig> {
+pub(crate) fn get_vm_config(name: &str) -> Result<aiva_core::VMConfig> {
     let config_path = get_vm_config_path(name)?;
 
     if !config_path.exists() {
@@ -93,14 +294,10 @@ fn get_vm_config(name: &str) -> Result<aiva_core::VMConfig> {
         )));
     }
 
-    let content = fs::read_to_string(&config_path)?;
-    let config: aiva_core::VMConfig = serde_json::from_str(&content)
-        .map_err(|e| aiva_core::AivaError::ConfigError(format!("Failed to parse config: {e}")))?;
-
-    Ok(config)
+    aiva_core::read_vm_config(&config_path)
 }
 
-fn save_vm_config(name: &str, config: &aiva_core::VMConfig) -> Result<()> {
+pub(crate) fn save_vm_config(name: &str, config: &aiva_core::VMConfig) -> Result<()> {
     let config_path = get_vm_config_path(name)?;
 
     // Ensure parent directory exists
@@ -129,13 +326,197 @@ fn get_config_value(config: &aiva_core::VMConfig, key: &str) -> Result<Option<St
         "network.subnet" => Ok(Some(config.network.subnet.clone())),
         "network.gateway" => Ok(Some(config.network.gateway.clone())),
         "network.dns_servers" => Ok(Some(config.network.dns_servers.join(","))),
+        "network.dns_search" => Ok(Some(config.network.dns_search.join(","))),
+        "network.dns_overrides" => Ok(Some(aiva_core::format_dns_overrides(
+            &config.network.dns_overrides,
+        ))),
+        "network.hostname" => Ok(config.network.hostname.clone()),
         "network.dhcp_enabled" => Ok(Some(config.network.dhcp_enabled.to_string())),
+        "network.audit_egress" => Ok(Some(config.network.audit_egress.to_string())),
         "storage.cache_strategy" => Ok(Some(config.storage.cache_strategy.to_string())),
-        _ => Ok(None),
+        "logging.paths" => Ok(Some(config.logging.paths.join(","))),
+        "security_policy" => Ok(config.security_policy.clone()),
+        "workdir" => Ok(Some(
+            aiva_core::ExecContext::from_config(config)
+                .workdir()
+                .display()
+                .to_string(),
+        )),
+        "run_as_user" => Ok(config.run_as_user.clone()),
+        "boot_args" => Ok(config.boot_args.clone()),
+        "extra_boot_args" => Ok(Some(config.extra_boot_args.join(" "))),
+        "cpu_template" => Ok(config.cpu_template.clone()),
+        "execution.transports" => Ok(Some(
+            config
+                .execution
+                .transports
+                .iter()
+                .map(|transport| transport.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        )),
+        "labels" => Ok(Some(
+            config
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{key}={value}"))
+                .collect::<Vec<_>>()
+                .join(","),
+        )),
+        _ => Ok(match key.strip_prefix("metadata.") {
+            Some(entry) => config.metadata.get(entry).cloned(),
+            None => key
+                .strip_prefix("labels.")
+                .and_then(|label| config.labels.get(label).cloned()),
+        }),
+    }
+}
+
+/// How a changed key reaches a VM that is already running
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LiveApply {
+    /// Only read when the VM starts
+    Restart,
+    /// Applied while setting it (disk_gb grows the rootfs right away)
+    Immediate,
+    /// Memory, through the balloon device
+    Balloon,
+    /// Resolver settings, rewritten through the guest agent
+    GuestDns,
+    /// Hostname and `/etc/hosts`, rewritten through the guest agent
+    GuestHostname,
+    /// Entries of the metadata service, published again
+    Metadata,
+}
+
+pub(crate) fn live_apply_mode(key: &str) -> LiveApply {
+    match key {
+        "disk_gb" => LiveApply::Immediate,
+        "memory_mb" => LiveApply::Balloon,
+        "network.dns_servers" | "network.dns_search" | "network.dns_overrides" => {
+            LiveApply::GuestDns
+        }
+        "network.hostname" => LiveApply::GuestHostname,
+        _ if key.starts_with("metadata.") => LiveApply::Metadata,
+        _ => LiveApply::Restart,
     }
 }
 
-fn set_config_value(config: &mut aiva_core::VMConfig, key: &str, value: &str) -> Result<()> {
+/// What happened to a running VM after `config set`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LiveOutcome {
+    NotRunning,
+    AlreadyLive,
+    Applied(String),
+    /// The change waits for a restart; `live_capable` when `--apply-now`
+    /// could have pushed it
+    RestartRequired {
+        live_capable: bool,
+    },
+}
+
+/// Push `key` of the saved `config` to VM `name` if it is running and
+/// `apply_now` is set, otherwise report that a restart is needed
+pub(crate) async fn apply_to_running_vm(
+    vm_manager: &VMOrchestrator,
+    name: &str,
+    key: &str,
+    config: &aiva_core::VMConfig,
+    apply_now: bool,
+) -> Result<LiveOutcome> {
+    let Some(vm) = vm_manager.get_vm_by_name(name).await? else {
+        return Ok(LiveOutcome::NotRunning);
+    };
+    if vm.state != VMState::Running {
+        return Ok(LiveOutcome::NotRunning);
+    }
+
+    let mode = live_apply_mode(key);
+    match mode {
+        LiveApply::Immediate => Ok(LiveOutcome::AlreadyLive),
+        LiveApply::Restart => Ok(LiveOutcome::RestartRequired {
+            live_capable: false,
+        }),
+        _ if !apply_now => Ok(LiveOutcome::RestartRequired { live_capable: true }),
+        LiveApply::Balloon => {
+            let request = ScaleRequest {
+                cpus: None,
+                memory_mb: Some(config.memory_mb),
+            };
+            let plan = vm_manager.scale_vm(&vm.id, request, false).await?;
+            if plan.restart_required() {
+                return Ok(LiveOutcome::RestartRequired {
+                    live_capable: false,
+                });
+            }
+            Ok(LiveOutcome::Applied(format!(
+                "Memory of running VM '{name}' set to {}MB",
+                plan.memory_mb
+            )))
+        }
+        LiveApply::GuestDns => {
+            aiva_core::apply_guest_dns(vm_manager, &vm, &config.network).await?;
+            Ok(LiveOutcome::Applied(format!(
+                "DNS settings pushed to running VM '{name}'"
+            )))
+        }
+        LiveApply::GuestHostname => {
+            let mut vm = vm;
+            vm.config.network.hostname = config.network.hostname.clone();
+            aiva_core::apply_guest_hostname(vm_manager, &vm).await?;
+            Ok(LiveOutcome::Applied(format!(
+                "Hostname of running VM '{name}' set to {}",
+                vm.hostname()
+            )))
+        }
+        LiveApply::Metadata => {
+            let mut vm = vm;
+            vm.config.metadata = config.metadata.clone();
+            vm_manager.publish_metadata(&vm).await?;
+            Ok(LiveOutcome::Applied(format!(
+                "Metadata of running VM '{name}' published"
+            )))
+        }
+    }
+}
+
+async fn get_runtime_value(name: &str, key: &str) -> Result<Option<String>> {
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let vm = vm_manager
+        .get_vm_by_name(name)
+        .await?
+        .ok_or_else(|| AivaError::ConfigError(format!("VM '{name}' not found")))?;
+
+    vm.runtime.get(key)
+}
+
+/// Grow the rootfs of an already created VM. VMs that were only initialized
+/// pick the new size up when they are created.
+async fn grow_vm_disk(vm_manager: &VMOrchestrator, name: &str, disk_gb: u64) -> Result<()> {
+    if let Some(vm) = vm_manager.get_vm_by_name(name).await?
+        && vm.config.disk_gb != disk_gb
+    {
+        vm_manager.resize_disk(&vm.id, disk_gb).await?;
+        print_info(&format!("Grew the disk of VM '{name}' to {disk_gb} GB"));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn set_config_value(
+    config: &mut aiva_core::VMConfig,
+    key: &str,
+    value: &str,
+) -> Result<()> {
+    if RuntimeInfo::is_runtime_key(key) {
+        return Err(AivaError::ConfigError(format!(
+            "'{key}' is read-only runtime state and cannot be set"
+        )));
+    }
+
     match key {
         "cpus" => {
             config.cpus = value
@@ -172,12 +553,39 @@ fn set_config_value(config: &mut aiva_core::VMConfig, key: &str, value: &str) ->
         }
         "network.dns_servers" => {
             config.network.dns_servers = value.split(',').map(|s| s.trim().to_string()).collect();
+            config.network.validate_dns()?;
+        }
+        "network.dns_search" => {
+            config.network.dns_search = value
+                .split(',')
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            config.network.validate_dns()?;
+        }
+        "network.dns_overrides" => {
+            config.network.dns_overrides = aiva_core::parse_dns_overrides(value)?;
+            config.network.validate_dns()?;
+        }
+        "network.hostname" => {
+            let hostname = value.trim().to_lowercase();
+            if hostname.is_empty() {
+                config.network.hostname = None;
+            } else {
+                aiva_core::validate_hostname(&hostname)?;
+                config.network.hostname = Some(hostname);
+            }
         }
         "network.dhcp_enabled" => {
             config.network.dhcp_enabled = value.parse().map_err(|_| {
                 aiva_core::AivaError::ConfigError("Invalid boolean value".to_string())
             })?;
         }
+        "network.audit_egress" => {
+            config.network.audit_egress = value.parse().map_err(|_| {
+                aiva_core::AivaError::ConfigError("Invalid boolean value".to_string())
+            })?;
+        }
         "storage.cache_strategy" => {
             config.storage.cache_strategy = match value.to_lowercase().as_str() {
                 "writeback" => aiva_core::CacheStrategy::Writeback,
@@ -189,6 +597,83 @@ fn set_config_value(config: &mut aiva_core::VMConfig, key: &str, value: &str) ->
                 }
             };
         }
+        "logging.paths" => {
+            config.logging.paths = value
+                .split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        "security_policy" => {
+            config.security_policy = match value {
+                "" | "none" => None,
+                name => Some(name.to_string()),
+            };
+        }
+        "workdir" => {
+            config.workdir = match value {
+                "" => None,
+                path if path.starts_with('/') => Some(PathBuf::from(path)),
+                _ => {
+                    return Err(aiva_core::AivaError::ConfigError(
+                        "Working directory must be an absolute guest path".to_string(),
+                    ));
+                }
+            };
+        }
+        "run_as_user" => {
+            config.run_as_user = match value {
+                "" => None,
+                user => Some(user.to_string()),
+            };
+        }
+        "boot_args" | "extra_boot_args" => {
+            let mut updated = config.clone();
+            if key == "boot_args" {
+                updated.boot_args = match value.trim() {
+                    "" => None,
+                    args => Some(args.to_string()),
+                };
+            } else {
+                updated.extra_boot_args = value.split_whitespace().map(str::to_string).collect();
+            }
+            updated.validate_boot_args()?;
+            *config = updated;
+        }
+        "cpu_template" => {
+            config.cpu_template = match value.trim() {
+                "" | "none" => None,
+                name => {
+                    aiva_platform::arch::check_host_machine_config(Some(name), false, config.cpus)?;
+                    Some(name.to_ascii_uppercase())
+                }
+            };
+        }
+        "execution.transports" => {
+            config.execution.transports = aiva_core::ExecutionConfig::parse_transports(value)?;
+        }
+        _ if key.starts_with("labels.") => {
+            let label = &key["labels.".len()..];
+            if value.is_empty() {
+                config.labels.remove(label);
+            } else {
+                let mut labels = config.labels.clone();
+                labels.insert(label.to_string(), value.to_string());
+                aiva_core::validate_labels(&labels)?;
+                config.labels = labels;
+            }
+        }
+        _ if key.starts_with("metadata.") => {
+            let entry = &key["metadata.".len()..];
+            if value.is_empty() {
+                config.metadata.remove(entry);
+            } else {
+                let mut metadata = config.metadata.clone();
+                metadata.insert(entry.to_string(), value.to_string());
+                aiva_core::validate_metadata(&metadata)?;
+                config.metadata = metadata;
+            }
+        }
         _ => {
             return Err(aiva_core::AivaError::ConfigError(format!(
                 "Unknown configuration key: {key}"
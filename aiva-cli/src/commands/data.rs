@@ -1,16 +1,101 @@
 use crate::commands::DataOperation;
-use crate::output::{OutputFormat, print_error, print_info, print_progress, print_success};
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_progress, print_success,
+};
+use crate::utils::get_data_dir;
 use aiva_core::{Config, Result, VMLogger, VMManager};
+use aiva_storage::{VolumeConfig, VolumeFormat, VolumeManager};
+use clap::ValueEnum;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+/// Volume formats `data create` offers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub(crate) enum VolumeFormatArg {
+    Raw,
+    Ext4,
+    Qcow2,
+}
+
+impl From<VolumeFormatArg> for VolumeFormat {
+    fn from(format: VolumeFormatArg) -> Self {
+        match format {
+            VolumeFormatArg::Raw => VolumeFormat::Raw,
+            VolumeFormatArg::Ext4 => VolumeFormat::Ext4,
+            VolumeFormatArg::Qcow2 => VolumeFormat::Qcow2,
+        }
+    }
+}
+
+/// Configuration `data create` asks for. Volumes are sparse unless
+/// `preallocate` is given.
+pub(crate) fn volume_config(
+    name: &str,
+    size: &str,
+    format: VolumeFormatArg,
+    preallocate: bool,
+    shared: bool,
+) -> Result<VolumeConfig> {
+    let size_mb = aiva_core::parse_memory_size(size).map_err(|_| {
+        aiva_core::AivaError::ConfigError(format!(
+            "Invalid volume size '{size}', use e.g. 512MB or 10GB"
+        ))
+    })?;
+    if size_mb == 0 {
+        return Err(aiva_core::AivaError::ConfigError(
+            "Volume size must be at least 1MB".to_string(),
+        ));
+    }
+
+    Ok(VolumeConfig {
+        name: name.to_string(),
+        size_mb,
+        format: format.into(),
+        sparse: !preallocate,
+        shared,
+        read_only: false,
+    })
+}
+
 pub async fn execute(
     operation: DataOperation,
     _config: Config,
-    _format: OutputFormat,
+    format: OutputFormat,
 ) -> Result<()> {
     match operation {
+        DataOperation::Create {
+            name,
+            size,
+            volume_format,
+            sparse: _,
+            preallocate,
+            shared,
+        } => {
+            let config = volume_config(&name, &size, volume_format, preallocate, shared)?;
+            if preallocate {
+                print_progress(&format!(
+                    "Creating volume '{name}' and reserving {}MB on disk",
+                    config.size_mb
+                ));
+            } else {
+                print_progress(&format!("Creating sparse volume '{name}'"));
+            }
+
+            let volumes = VolumeManager::new(get_data_dir()?)?;
+            volumes.init().await?;
+            let volume = volumes.create_volume(config).await?;
+
+            if !matches!(format, OutputFormat::Table) {
+                println!("{}", format.format(&volume));
+                return Ok(());
+            }
+            print_success(&format!(
+                "Created volume '{name}' ({}) at {}",
+                volume.id,
+                volume.path.display()
+            ));
+        }
         DataOperation::Sync { name, source, dest } => {
             print_progress(&format!("Syncing data for VM '{name}'"));
             print_progress(&format!("Source: {}", source.display()));
@@ -45,17 +130,17 @@ pub async fn execute(
                 }
 
                 // Create destination directory if it doesn't exist
-                if let Some(parent) = dest.parent() {
-                    if !parent.exists() {
-                        print_progress("Creating destination directory...");
-                        fs::create_dir_all(parent)?;
-                        logger
-                            .info(&format!(
-                                "Created destination directory: {}",
-                                parent.display()
-                            ))
-                            .await?;
-                    }
+                if let Some(parent) = dest.parent()
+                    && !parent.exists()
+                {
+                    print_progress("Creating destination directory...");
+                    fs::create_dir_all(parent)?;
+                    logger
+                        .info(&format!(
+                            "Created destination directory: {}",
+                            parent.display()
+                        ))
+                        .await?;
                 }
 
                 // Perform the sync operation
@@ -1,13 +1,49 @@
-use crate::output::{OutputFormat, print_error, print_progress, print_success};
-use aiva_core::{Config, Result, VMLogger, VMManager};
+use crate::output::{
+    OutputFormat, print_error, print_info, print_progress, print_success, write_output_file,
+};
+use crate::utils::{get_data_dir, get_vm_dir};
+use aiva_core::{AivaError, Config, LogStore, Result, VMInstance, VMLogger, VMManager};
+use aiva_storage::VolumeManager;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+/// Command line options for `aiva delete`
+pub struct DeleteOptions {
+    pub force: bool,
+    pub keep_data: bool,
+    pub purge: bool,
+    pub yes: bool,
+}
+
+/// Data a deleted VM left on disk
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct RetainedData {
+    /// Configuration and anything else under the VM's data directory
+    pub data_dir: Option<PathBuf>,
+    pub volumes: Vec<RetainedVolume>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct RetainedVolume {
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
 pub async fn execute(
     name: String,
-    force: bool,
+    options: DeleteOptions,
     _config: Config,
     _format: OutputFormat,
 ) -> Result<()> {
+    let DeleteOptions {
+        force,
+        keep_data,
+        purge,
+        yes,
+    } = options;
+
     print_progress(&format!("Deleting VM '{name}'"));
 
     // Get platform and VM manager
@@ -37,18 +73,44 @@ pub async fn execute(
             });
         }
 
+        if purge && !yes && !confirm_purge(&name)? {
+            print_info("Delete cancelled");
+            return Ok(());
+        }
+
         if force {
             logger.info("Force delete requested for running VM").await?;
         }
 
         logger
-            .info(&format!("Deleting VM (force: {force})"))
+            .info(&format!("Deleting VM (force: {force}, purge: {purge})"))
             .await?;
 
-        // Delete the VM
+        // Delete the VM. The platform releases the runtime resources: the
+        // hypervisor process, its workspace and the TAP device.
         vm_manager.delete_vm(&vm.id).await?;
 
         logger.info("VM deleted successfully").await?;
+
+        let volumes = VolumeManager::new(get_data_dir()?)?;
+        volumes.init().await?;
+        let vm_dir = get_vm_dir(&vm.name)?;
+
+        if purge {
+            purge_vm_volumes(&volumes, &vm).await?;
+            purge_vm_data(&vm, &vm_dir).await?;
+        } else {
+            let retained = retain_vm_data(&volumes, &vm, &vm_dir).await?;
+            if keep_data {
+                print_retained_data(&retained);
+            } else if retained.data_dir.is_some() || !retained.volumes.is_empty() {
+                print_info(
+                    "Data directory and volumes were kept; list them with --keep-data or remove them with --purge",
+                );
+            }
+            write_output_file(&retained)?;
+        }
+
         print_success(&format!("VM '{name}' deleted successfully"));
     } else {
         print_error(&format!("VM '{name}' not found"));
@@ -61,3 +123,96 @@ pub async fn execute(
 
     Ok(())
 }
+
+fn confirm_purge(name: &str) -> Result<bool> {
+    dialoguer::Confirm::new()
+        .with_prompt(format!(
+            "Permanently delete VM '{name}', its data directory and the volumes it owns?"
+        ))
+        .default(false)
+        .interact()
+        .map_err(|e| AivaError::Other(e.into()))
+}
+
+/// Volumes are attached by VM id, or by name for ones attached by hand
+fn volume_owners(vm: &VMInstance) -> [String; 2] {
+    [vm.id.to_string(), vm.name.clone()]
+}
+
+/// Detach the volumes of `vm` without deleting them, so they can be attached
+/// to a VM created later, and collect what stays on disk
+pub(crate) async fn retain_vm_data(
+    volumes: &VolumeManager,
+    vm: &VMInstance,
+    vm_dir: &Path,
+) -> Result<RetainedData> {
+    let mut retained = RetainedData {
+        data_dir: vm_dir.exists().then(|| vm_dir.to_path_buf()),
+        volumes: Vec::new(),
+    };
+
+    for owner in volume_owners(vm) {
+        for volume in volumes.release_vm_volumes(&owner).await? {
+            retained.volumes.push(RetainedVolume {
+                id: volume.id,
+                name: volume.name,
+                path: volume.path,
+            });
+        }
+    }
+
+    Ok(retained)
+}
+
+fn print_retained_data(retained: &RetainedData) {
+    if let Some(data_dir) = &retained.data_dir {
+        print_info(&format!("Kept data directory {}", data_dir.display()));
+    }
+    for volume in &retained.volumes {
+        print_info(&format!(
+            "Kept volume {} ({}) at {}",
+            volume.name,
+            volume.id,
+            volume.path.display()
+        ));
+    }
+}
+
+/// Delete the volumes `vm` owns exclusively. Shared and read-only volumes
+/// are detached but kept.
+pub(crate) async fn purge_vm_volumes(volumes: &VolumeManager, vm: &VMInstance) -> Result<()> {
+    for owner in volume_owners(vm) {
+        let purge = volumes.purge_vm_volumes(&owner).await?;
+        for volume in purge.deleted {
+            print_info(&format!("Deleted volume {} ({})", volume.name, volume.id));
+        }
+        for volume in purge.preserved {
+            print_info(&format!(
+                "Kept shared volume {} ({})",
+                volume.name, volume.id
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove everything else the VM leaves behind besides its runtime resources
+async fn purge_vm_data(vm: &VMInstance, vm_dir: &Path) -> Result<()> {
+    aiva_platform::command_pool::get_command_pool()
+        .unregister_vm(&vm.name)
+        .await?;
+
+    let log_path = LogStore::default_location().path_for(&vm.id.to_string());
+    if log_path.exists() {
+        std::fs::remove_file(&log_path)?;
+        print_info("Removed shipped guest logs");
+    }
+
+    if vm_dir.exists() {
+        std::fs::remove_dir_all(vm_dir)?;
+        print_info(&format!("Removed data directory {}", vm_dir.display()));
+    }
+
+    Ok(())
+}
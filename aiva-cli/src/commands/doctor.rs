@@ -0,0 +1,199 @@
+use crate::output::{OutputFormat, OutputFormatter, print_info, print_success, print_warning};
+use crate::utils::get_data_dir;
+use aiva_core::{
+    Config, FixStatus, Remediation, Result, VMInstance, VMManager, VMOrchestrator, fix_fleet,
+};
+use aiva_storage::{ToolPreflight, ToolStatus};
+use colored::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tabled::Tabled;
+
+#[derive(Serialize, Tabled)]
+struct CheckRow {
+    check: String,
+    status: String,
+    detail: String,
+}
+
+fn tool_row(status: ToolStatus) -> CheckRow {
+    let needed_for = status.needed_for.join(", ");
+    match status.path {
+        Some(path) => CheckRow {
+            check: status.tool.to_string(),
+            status: "ok".green().to_string(),
+            detail: path.display().to_string(),
+        },
+        None => CheckRow {
+            check: status.tool.to_string(),
+            status: "missing".red().to_string(),
+            detail: format!("needed for {needed_for}; install {}", status.package),
+        },
+    }
+}
+
+#[derive(Serialize, Tabled)]
+struct FixRow {
+    issue: String,
+    status: String,
+    detail: String,
+    undo: String,
+}
+
+impl From<Remediation> for FixRow {
+    fn from(remediation: Remediation) -> Self {
+        let status = match remediation.status {
+            FixStatus::Fixed => remediation.status.to_string().green(),
+            FixStatus::Failed => remediation.status.to_string().red(),
+            FixStatus::Manual => remediation.status.to_string().yellow(),
+        };
+        Self {
+            issue: remediation.issue,
+            status: status.to_string(),
+            detail: remediation.detail,
+            undo: remediation.undo.unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+/// Directories every VM in `vms` and the image cache expect under `data_dir`
+pub(crate) fn expected_data_dirs(data_dir: &Path, vms: &[VMInstance]) -> Vec<PathBuf> {
+    let mut dirs = vec![data_dir.join("images")];
+    for vm in vms {
+        let vm_dir = data_dir.join("vms").join(&vm.name);
+        dirs.push(vm_dir.join("data"));
+        dirs.push(vm_dir.join("logs"));
+    }
+    dirs
+}
+
+/// Create whichever of `dirs` is missing
+pub(crate) fn recreate_data_dirs(dirs: &[PathBuf]) -> Vec<Remediation> {
+    dirs.iter()
+        .filter(|dir| !dir.exists())
+        .map(|dir| {
+            let issue = format!("missing directory {}", dir.display());
+            match std::fs::create_dir_all(dir) {
+                Ok(()) => {
+                    Remediation::fixed(issue, "created", Some(format!("rmdir {}", dir.display())))
+                }
+                Err(e) => Remediation::failed(issue, e),
+            }
+        })
+        .collect()
+}
+
+/// Access to `/dev/kvm`, granted through sudo only when `grant_kvm` says so
+fn fix_kvm_access(grant_kvm: bool) -> Option<Remediation> {
+    if aiva_platform::detect_platform() != "linux" {
+        return None;
+    }
+    let issue = "no access to /dev/kvm";
+    let linux = match aiva_platform::LinuxPlatform::new() {
+        Ok(linux) => linux,
+        Err(e) => return Some(Remediation::failed(issue, e)),
+    };
+    if !linux.kvm_access_denied() {
+        return None;
+    }
+    if !grant_kvm {
+        return Some(Remediation::manual(
+            issue,
+            "run 'aiva doctor --fix --grant-kvm' to grant access through sudo",
+        ));
+    }
+    Some(match linux.grant_kvm_access() {
+        Ok(()) => Remediation::fixed(
+            issue,
+            "granted the current user access through sudo",
+            Some("sudo setfacl -x u:$USER /dev/kvm".to_string()),
+        ),
+        Err(e) => Remediation::failed(issue, e),
+    })
+}
+
+async fn remediate(grant_kvm: bool) -> Result<Vec<Remediation>> {
+    let mut remediations: Vec<Remediation> = fix_kvm_access(grant_kvm).into_iter().collect();
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = VMOrchestrator::new(platform);
+    vm_manager.load_state().await?;
+    remediations.extend(fix_fleet(&vm_manager).await?);
+
+    let vms = vm_manager.list_vms().await?;
+    remediations.extend(recreate_data_dirs(&expected_data_dirs(
+        &get_data_dir()?,
+        &vms,
+    )));
+    Ok(remediations)
+}
+
+pub async fn execute(
+    fix: bool,
+    grant_kvm: bool,
+    _config: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut rows = Vec::new();
+    let mut problems = 0;
+
+    let platform = aiva_platform::get_current_platform()?;
+    match platform.check_requirements().await {
+        Ok(()) => rows.push(CheckRow {
+            check: format!("{} platform", platform.name()),
+            status: "ok".green().to_string(),
+            detail: "requirements met".to_string(),
+        }),
+        Err(e) => {
+            problems += 1;
+            rows.push(CheckRow {
+                check: format!("{} platform", platform.name()),
+                status: "failed".red().to_string(),
+                detail: e.to_string(),
+            });
+        }
+    }
+
+    for status in ToolPreflight::global().report() {
+        if status.path.is_none() {
+            problems += 1;
+        }
+        rows.push(tool_row(status));
+    }
+
+    println!("{}", format.format_table(rows));
+    if problems == 0 {
+        print_success("Everything aiva needs is in place");
+    } else {
+        print_warning(&format!("{problems} check(s) need attention"));
+    }
+    if !fix {
+        return Ok(());
+    }
+
+    let remediations = remediate(grant_kvm).await?;
+    if remediations.is_empty() {
+        print_success("Nothing to fix");
+        return Ok(());
+    }
+    let count = |status| {
+        remediations
+            .iter()
+            .filter(|remediation| remediation.status == status)
+            .count()
+    };
+    let (fixed, failed, manual) = (
+        count(FixStatus::Fixed),
+        count(FixStatus::Failed),
+        count(FixStatus::Manual),
+    );
+
+    println!(
+        "{}",
+        format.format_table(remediations.into_iter().map(FixRow::from).collect())
+    );
+    print_info(&format!(
+        "{fixed} fixed, {failed} failed, {manual} need manual action"
+    ));
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use crate::commands::ImageAction;
+use crate::output::{OutputFormat, OutputFormatter, print_info, print_progress, print_success};
+use crate::utils::get_data_dir;
+use aiva_core::{AivaError, Config, Result};
+use aiva_storage::{
+    ImageAudit, ImageInfo, ImageManager, ImageSource, SourceAllowlist, audit_images,
+};
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use tabled::Tabled;
+
+#[derive(Serialize, Tabled)]
+struct ImageRow {
+    id: String,
+    name: String,
+    format: String,
+    size: String,
+    source: String,
+    created: String,
+}
+
+impl From<ImageInfo> for ImageRow {
+    fn from(image: ImageInfo) -> Self {
+        Self {
+            id: image.id,
+            name: image.name,
+            format: image.format.to_string(),
+            size: format!("{}MB", image.size_mb),
+            source: image.source.to_string(),
+            created: image.created_at.format("%Y-%m-%d %H:%M").to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Tabled)]
+struct AuditRow {
+    id: String,
+    name: String,
+    source: String,
+    findings: String,
+}
+
+impl From<&ImageAudit> for AuditRow {
+    fn from(audit: &ImageAudit) -> Self {
+        Self {
+            id: audit.id.clone(),
+            name: audit.name.clone(),
+            source: audit.source.clone(),
+            findings: audit
+                .findings
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+pub async fn execute(action: ImageAction, config: Config, format: OutputFormat) -> Result<()> {
+    let images = ImageManager::new(get_data_dir()?)?;
+    images.init().await?;
+
+    match action {
+        ImageAction::Pull { name, source } => {
+            let source = ImageSource::parse(&source)?;
+            print_progress(&format!("Pulling image '{name}' from {source}"));
+
+            let bar = ProgressBar::new_spinner();
+            let image = {
+                let bar = bar.clone();
+                images
+                    .pull_image_with_progress(&name, source, &move |written, total| {
+                        if let Some(total) = total
+                            && bar.length() != Some(total)
+                        {
+                            bar.set_length(total);
+                            bar.set_style(
+                                ProgressStyle::with_template(
+                                    "{bar:40.cyan/blue} {bytes}/{total_bytes} ({bytes_per_sec}, {eta})",
+                                )
+                                .unwrap_or_else(|_| ProgressStyle::default_bar()),
+                            );
+                        }
+                        bar.set_position(written);
+                    })
+                    .await
+            };
+            bar.finish_and_clear();
+            let image = image?;
+
+            print_success(&format!(
+                "Pulled image '{}' ({}, {}MB)",
+                image.name, image.id, image.size_mb
+            ));
+        }
+        ImageAction::Ls => {
+            let mut list = images.list_images().await?;
+            list.sort_by_key(|image| image.created_at);
+            let rows: Vec<ImageRow> = list.into_iter().map(ImageRow::from).collect();
+            println!("{}", format.format_table(rows));
+        }
+        ImageAction::Rm { id } => {
+            images.delete_image(&id).await?;
+            print_success(&format!("Image {id} deleted"));
+        }
+        ImageAction::Audit { allow_sources } => {
+            let allowlist = SourceAllowlist::new(
+                config
+                    .security
+                    .image_sources
+                    .iter()
+                    .cloned()
+                    .chain(allow_sources),
+            );
+            let list = images.list_images().await?;
+            let audits = audit_images(&list, &allowlist);
+
+            match format {
+                OutputFormat::Table if audits.is_empty() => {
+                    print_success(&format!("All {} images passed the audit", list.len()));
+                }
+                OutputFormat::Table => {
+                    let rows: Vec<AuditRow> = audits.iter().map(AuditRow::from).collect();
+                    println!("{}", format.format_table(rows));
+                }
+                _ => println!("{}", format.format(&audits)),
+            }
+            if allowlist.is_empty() && matches!(format, OutputFormat::Table) {
+                print_info(
+                    "No trusted sources configured, set security.image_sources to check them",
+                );
+            }
+
+            let untrusted = audits
+                .iter()
+                .filter(|audit| audit.fails_allowlist())
+                .count();
+            if untrusted > 0 {
+                return Err(AivaError::SecurityError(format!(
+                    "{untrusted} image(s) come from sources outside the allowlist"
+                )));
+            }
+        }
+        ImageAction::Inspect { id } => {
+            let image = images.get_image(&id).await?;
+            println!("{}", format.format(&image));
+            print_info(&format!(
+                "Stored at {}",
+                images.get_image_path(&id).await?.display()
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -1,15 +1,118 @@
-use crate::output::{OutputFormat, print_error, print_info, print_progress, print_success};
+use super::start::print_create_plan;
+use crate::output::{
+    OutputFormat, print_error, print_info, print_progress, print_success, print_warning,
+};
 use crate::utils::{get_images_dir, get_vm_dir};
-use aiva_core::{Config, Result, TemplateManager, VMConfigCustomizations, VMManager, VMTemplate};
+use aiva_core::{
+    AivaError, Config, HostCapacity, Result, TemplateManager, VMConfig, VMConfigCustomizations,
+    VMManager, VMState, VMTemplate,
+};
 use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
+/// Command line options for `aiva init`
+pub struct InitOptions {
+    pub template: Option<String>,
+    pub grant_kvm: bool,
+    pub dry_run: bool,
+    pub force: bool,
+    pub yes: bool,
+}
+
+/// Files `init` writes under the VM directory
+pub(crate) const INIT_FILES: [&str; 3] = ["config/config.json", "config/template.json", "setup.sh"];
+
+/// What an earlier `init` of the same name left behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PreviousInit {
+    None,
+    Complete,
+    /// Stopped part way: `found` are the files and the VM record that
+    /// exist, `missing` the rest
+    Partial {
+        found: Vec<String>,
+        missing: Vec<String>,
+    },
+}
+
+impl PreviousInit {
+    /// Inspect `vm_dir` and whether a VM of that name is `registered`
+    pub(crate) fn detect(vm_dir: &Path, registered: bool) -> Self {
+        let (mut found, mut missing): (Vec<String>, Vec<String>) = INIT_FILES
+            .iter()
+            .map(|file| file.to_string())
+            .partition(|file| vm_dir.join(file).exists());
+        if registered {
+            found.push("VM record".to_string());
+        } else {
+            missing.push("VM record".to_string());
+        }
+
+        if missing.is_empty() {
+            Self::Complete
+        } else if found.is_empty() {
+            Self::None
+        } else {
+            Self::Partial { found, missing }
+        }
+    }
+
+    /// Error that stops `init` of `name` without `--force`, if any
+    pub(crate) fn conflict(&self, name: &str) -> Option<AivaError> {
+        let message = match self {
+            Self::None => return None,
+            Self::Complete => "VM already exists; use --force to re-initialize it".to_string(),
+            Self::Partial { found, missing } => format!(
+                "A previous init did not finish ({} present, {} missing); use --force to re-initialize it",
+                found.join(", "),
+                missing.join(", ")
+            ),
+        };
+        Some(AivaError::VMError {
+            vm_name: name.to_string(),
+            state: VMState::Stopped,
+            message,
+        })
+    }
+}
+
+/// Remove the files an earlier `init` wrote to `vm_dir`. The `data` and
+/// `logs` directories are kept.
+pub(crate) fn clear_previous_init(vm_dir: &Path) -> Result<()> {
+    for file in INIT_FILES {
+        let path = vm_dir.join(file);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+fn confirm_reinit(name: &str) -> Result<bool> {
+    dialoguer::Confirm::new()
+        .with_prompt(format!(
+            "Re-initialize '{name}'? Its configuration and VM record are replaced; data and logs are kept"
+        ))
+        .default(false)
+        .interact()
+        .map_err(|e| AivaError::Other(e.into()))
+}
+
 pub async fn execute(
     name: String,
-    template: Option<String>,
+    options: InitOptions,
     config: Config,
-    _format: OutputFormat,
+    format: OutputFormat,
 ) -> Result<()> {
+    let InitOptions {
+        template,
+        grant_kvm,
+        dry_run,
+        force,
+        yes,
+    } = options;
+
     // Handle template selection
     let selected_template = if let Some(template_name) = template {
         print_progress(&format!("Using template: {template_name}"));
@@ -24,7 +127,7 @@ pub async fn execute(
                 for (name, desc) in VMTemplate::list_available_templates() {
                     print_info(&format!("  {name}: {desc}"));
                 }
-                return Err(aiva_core::AivaError::ConfigError(format!(
+                return Err(AivaError::ConfigError(format!(
                     "Unknown template: {template_name}"
                 )));
             }
@@ -47,24 +150,71 @@ pub async fn execute(
         selected_template.name
     ));
 
-    // Create directories
     let vm_dir = get_vm_dir(&name)?;
     let images_dir = get_images_dir()?;
 
-    print_progress("Creating directories...");
-    fs::create_dir_all(&vm_dir)?;
-    fs::create_dir_all(&images_dir)?;
-    fs::create_dir_all(vm_dir.join("data"))?;
-    fs::create_dir_all(vm_dir.join("logs"))?;
+    if dry_run {
+        return plan_init(&name, &selected_template, &config, format).await;
+    }
 
     print_progress("Checking platform requirements...");
 
+    if grant_kvm {
+        if aiva_platform::detect_platform() != "linux" {
+            return Err(AivaError::ConfigError(
+                "--grant-kvm is only supported on Linux".to_string(),
+            ));
+        }
+        print_progress("Granting access to /dev/kvm (requires sudo)...");
+        aiva_platform::LinuxPlatform::new()?.grant_kvm_access()?;
+        print_success("KVM access granted");
+    }
+
     // Check platform
     let platform = aiva_platform::get_current_platform()?;
     platform.check_requirements().await?;
 
     print_info(&format!("Detected platform: {}", platform.name()));
 
+    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    // Refuse to overwrite an earlier init, finished or not, unless forced
+    let existing = vm_manager.get_vm_by_name(&name).await?;
+    let previous = PreviousInit::detect(&vm_dir, existing.is_some());
+    if let Some(conflict) = previous.conflict(&name) {
+        if !force {
+            print_error(&conflict.to_string());
+            return Err(conflict);
+        }
+        if let Some(vm) = &existing
+            && matches!(vm.state, VMState::Running | VMState::Paused)
+        {
+            return Err(AivaError::VMError {
+                vm_name: name,
+                state: vm.state,
+                message: "Stop the VM before re-initializing it".to_string(),
+            });
+        }
+        if !yes && !confirm_reinit(&name)? {
+            print_info("Init cancelled");
+            return Ok(());
+        }
+
+        print_progress(&format!("Re-initializing '{name}'..."));
+        if let Some(vm) = existing {
+            vm_manager.delete_vm(&vm.id).await?;
+        }
+        clear_previous_init(&vm_dir)?;
+    }
+
+    // Create directories
+    print_progress("Creating directories...");
+    fs::create_dir_all(&vm_dir)?;
+    fs::create_dir_all(&images_dir)?;
+    fs::create_dir_all(vm_dir.join("data"))?;
+    fs::create_dir_all(vm_dir.join("logs"))?;
+
     // Download base images if needed
     let kernel_path = images_dir.join("vmlinux");
     let rootfs_path = images_dir.join("rootfs.ext4");
@@ -76,12 +226,7 @@ pub async fn execute(
     }
 
     // Generate VM configuration from template
-    let vm_config = selected_template.generate_vm_config(Some(VMConfigCustomizations {
-        cpus: Some(config.defaults.cpus),
-        memory_mb: Some(crate::utils::parse_memory_size(&config.defaults.memory)?),
-        disk_gb: Some(crate::utils::parse_disk_size(&config.defaults.disk)?),
-        additional_ports: None,
-    }));
+    let vm_config = default_vm_config(&selected_template, &config)?;
 
     // Create configuration directory
     let vm_config_dir = vm_dir.join("config");
@@ -101,20 +246,6 @@ pub async fn execute(
     let setup_script_file = vm_dir.join("setup.sh");
     fs::write(setup_script_file, selected_template.get_setup_script())?;
 
-    // Create VM instance using the VMManager
-    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
-    vm_manager.load_state().await?;
-
-    // Check if VM already exists
-    if vm_manager.get_vm_by_name(&name).await?.is_some() {
-        print_error(&format!("VM '{name}' already exists"));
-        return Err(aiva_core::AivaError::VMError {
-            vm_name: name,
-            state: aiva_core::VMState::Stopped,
-            message: "VM already exists".to_string(),
-        });
-    }
-
     // Create VM instance
     let vm_instance = vm_manager.create_vm(name.clone(), vm_config).await?;
     print_progress(&format!("Created VM instance with ID: {}", vm_instance.id));
@@ -126,10 +257,10 @@ pub async fn execute(
 
     // Display information about the template
     print_info(&format!("Runtime: {:?}", selected_template.runtime));
-    if selected_template.mcp_support.sse_enabled {
-        if let Some(port) = selected_template.mcp_support.default_port {
-            print_info(&format!("Default MCP port: {port} (SSE mode)"));
-        }
+    if selected_template.mcp_support.sse_enabled
+        && let Some(port) = selected_template.mcp_support.default_port
+    {
+        print_info(&format!("Default MCP port: {port} (SSE mode)"));
     }
     print_info(&format!(
         "Supported transports: {}",
@@ -147,3 +278,46 @@ pub async fn execute(
 
     Ok(())
 }
+
+fn default_vm_config(template: &VMTemplate, config: &Config) -> Result<VMConfig> {
+    // Explicit defaults win; whatever is unset scales with the host
+    let defaults = &config.defaults;
+    let host = HostCapacity::detect();
+    let cpus = defaults.cpus.unwrap_or_else(|| host.scaled_cpus());
+    let memory_mb = match &defaults.memory {
+        Some(memory) => crate::utils::parse_memory_size(memory)?,
+        None => host.scaled_memory_mb(),
+    };
+
+    Ok(template.generate_vm_config(Some(VMConfigCustomizations {
+        cpus: Some(cpus),
+        memory_mb: Some(memory_mb),
+        disk_gb: Some(crate::utils::parse_disk_size(&config.defaults.disk)?),
+        additional_ports: None,
+    })))
+}
+
+/// `init --dry-run`: report what would be created, writing nothing
+async fn plan_init(
+    name: &str,
+    template: &VMTemplate,
+    config: &Config,
+    format: OutputFormat,
+) -> Result<()> {
+    let platform = aiva_platform::get_current_platform()?;
+    if let Err(e) = platform.check_requirements().await {
+        print_warning(&format!("Platform requirements are not met: {e}"));
+    }
+
+    let vm_manager = aiva_core::VMOrchestrator::new(platform);
+    vm_manager.load_state().await?;
+
+    let vm_config = default_vm_config(template, config)?;
+    let plan = vm_manager
+        .plan_create(name, &vm_config)
+        .await?
+        .with_template(template);
+    print_create_plan(&plan, format);
+
+    Ok(())
+}
@@ -1,16 +1,23 @@
-use crate::output::{OutputFormat, print_error, print_info};
-use aiva_core::{Config, Result, VMManager};
-use std::path::PathBuf;
+use crate::output::{OutputFormat, print_error, print_info, print_warning};
+use aiva_core::{
+    AivaError, Config, DefaultMetricsCollector, LogEntry, LogStore, MonitoringService, Result,
+    VMInstance, VMManager, VMOrchestrator, VMState, tail_log,
+};
+use aiva_platform::command_pool::{ConnectionType, VsockExecutor};
+use aiva_platform::log_shipping::{GuestLogShipper, LogIngestor, ship_egress_log, ship_guest_logs};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, BufReader};
 use tokio::time::{Duration, sleep};
 
 pub async fn execute(
     name: String,
     follow: bool,
     tail: Option<usize>,
-    _config: Config,
+    guest: bool,
+    config: Config,
     _format: OutputFormat,
 ) -> Result<()> {
     print_info(&format!("Showing logs for AI agent/MCP server: {name}"));
@@ -24,6 +31,10 @@ pub async fn execute(
     let vm = vm_manager.get_vm_by_name(&name).await?;
 
     if let Some(vm) = vm {
+        if guest {
+            return show_guest_logs(&vm_manager, &vm, &config, follow, tail).await;
+        }
+
         let log_file = get_log_file(&vm.name);
 
         if !log_file.exists() {
@@ -52,46 +63,169 @@ fn get_log_file(vm_name: &str) -> PathBuf {
         .join(format!("{vm_name}.log"))
 }
 
-async fn show_logs(log_file: &PathBuf, tail: Option<usize>) -> Result<()> {
-    let content = fs::read_to_string(log_file).await?;
-    let lines: Vec<&str> = content.lines().collect();
-
-    let lines_to_show = if let Some(tail_lines) = tail {
-        let start = if lines.len() > tail_lines {
-            lines.len() - tail_lines
-        } else {
-            0
-        };
-        &lines[start..]
-    } else {
-        &lines
-    };
-
-    for line in lines_to_show {
-        println!("{line}");
+/// `--tail` value: a line count, with a clear error for negative ones
+pub(crate) fn parse_tail(value: &str) -> std::result::Result<usize, String> {
+    if value.trim_start().starts_with('-') {
+        return Err(format!(
+            "'{value}' is negative; --tail takes the number of lines to show"
+        ));
     }
+    value
+        .parse()
+        .map_err(|_| format!("'{value}' is not a number of lines"))
+}
 
-    Ok(())
+/// Print the log, or only its last `tail` lines. Either way the log is
+/// streamed rather than read into memory, and a tail longer than the log
+/// carries on into its rotated segments.
+async fn show_logs(log_file: &Path, tail: Option<usize>) -> Result<()> {
+    let log_file = log_file.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut stdout = std::io::stdout().lock();
+        match tail {
+            Some(lines) => tail_log(&log_file, lines, &mut stdout),
+            None => {
+                std::io::copy(&mut std::fs::File::open(&log_file)?, &mut stdout)?;
+                Ok(())
+            }
+        }
+    })
+    .await
+    .map_err(|e| AivaError::Other(e.into()))?
 }
 
-async fn follow_logs(log_file: &PathBuf, tail: Option<usize>) -> Result<()> {
+async fn follow_logs(log_file: &Path, tail: Option<usize>) -> Result<()> {
     // Show existing logs first
     if log_file.exists() {
         show_logs(log_file, tail).await?;
     }
 
-    // Follow new logs
+    // Then only what is appended from here on
+    let mut position = fs::metadata(log_file).await.map_or(0, |meta| meta.len());
     loop {
-        if log_file.exists() {
-            let file = fs::File::open(log_file).await?;
-            let reader = BufReader::new(file);
-            let mut lines = reader.lines();
-
-            while let Some(line) = lines.next_line().await? {
-                println!("{line}");
+        if let Ok(meta) = fs::metadata(log_file).await {
+            // Truncated or rotated away: start over on the new file
+            if meta.len() < position {
+                position = 0;
+            }
+            if meta.len() > position {
+                let mut file = fs::File::open(log_file).await?;
+                file.seek(SeekFrom::Start(position)).await?;
+                let mut lines = BufReader::new(file).lines();
+                while let Some(line) = lines.next_line().await? {
+                    println!("{line}");
+                }
+                // Read to the end, so nothing is left in the buffer
+                position = lines.into_inner().into_inner().stream_position().await?;
             }
         }
 
         sleep(Duration::from_millis(100)).await;
     }
 }
+
+async fn show_guest_logs(
+    vm_manager: &Arc<VMOrchestrator>,
+    vm: &VMInstance,
+    config: &Config,
+    follow: bool,
+    tail: Option<usize>,
+) -> Result<()> {
+    let store = LogStore::default_location();
+    let vm_id = vm.id.to_string();
+
+    // Connections the host logged since the last time the logs were read
+    if vm.config.network.audit_egress
+        && let Err(e) = ship_egress_log(vm, &store, false, |_| {}).await
+    {
+        print_warning(&format!("Could not read egress log: {e}"));
+    }
+
+    let entries = store.read(&vm_id).await?;
+    let start = tail.map_or(0, |n| entries.len().saturating_sub(n));
+    for entry in &entries[start..] {
+        print_guest_entry(entry);
+    }
+
+    if !follow {
+        return Ok(());
+    }
+
+    if vm.state != VMState::Running {
+        print_warning(&format!(
+            "VM '{}' is not running; guest logs are shipped from when it starts",
+            vm.name
+        ));
+    }
+    print_info(&format!(
+        "Following guest logs {:?} (press Ctrl+C to stop)...",
+        vm.config.logging.paths
+    ));
+
+    let egress = vm.config.network.audit_egress.then(|| {
+        let vm = vm.clone();
+        tokio::spawn(async move {
+            let store = LogStore::default_location();
+            if let Err(e) = ship_egress_log(&vm, &store, true, print_guest_entry).await {
+                print_warning(&format!("Stopped following egress log: {e}"));
+            }
+        })
+    });
+
+    // Shipping follows the VM's lifecycle in monitoring; this only echoes
+    // what it ships for this VM
+    let echoed = vm_id.clone();
+    let shipper = GuestLogShipper::new(store)
+        .with_jailer_config(config.platform.linux.jailer.clone())
+        .with_on_entry(move |entry| {
+            if entry.vm_id.as_deref() == Some(echoed.as_str()) {
+                print_guest_entry(entry);
+            }
+        });
+    let monitoring = Arc::new(
+        MonitoringService::new(Box::new(DefaultMetricsCollector))
+            .with_log_shipper(Arc::new(shipper)),
+    );
+    let follower = monitoring.follow(Arc::clone(vm_manager)).await?;
+
+    tokio::signal::ctrl_c()
+        .await
+        .map_err(|e| AivaError::Other(e.into()))?;
+    follower.abort();
+    if let Some(egress) = egress {
+        egress.abort();
+    }
+    Ok(())
+}
+
+/// Follow `paths` in the guest, shipping every line to the VM's log store
+/// and echoing it
+pub(crate) async fn follow_guest_files(
+    vm: &VMInstance,
+    paths: &[String],
+    config: &Config,
+) -> Result<()> {
+    let executor = Arc::new(VsockExecutor::new(
+        vm.name.clone(),
+        ConnectionType::guest_agent(vm, &config.platform.linux.jailer),
+    ));
+    let monitoring = Arc::new(MonitoringService::new(Box::new(DefaultMetricsCollector)));
+    let ingestor = LogIngestor::new(vm.id.to_string(), paths, monitoring)
+        .with_store(LogStore::default_location());
+
+    ship_guest_logs(executor, paths, ingestor, print_guest_entry).await
+}
+
+fn print_guest_entry(entry: &LogEntry) {
+    println!(
+        "{} [{}] [{}] {}",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S%.3f"),
+        entry.level,
+        entry
+            .metadata
+            .get("path")
+            .or_else(|| entry.metadata.get("source"))
+            .map_or("guest", String::as_str),
+        entry.message
+    );
+}
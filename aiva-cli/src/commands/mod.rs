@@ -1,16 +1,27 @@
-mod config;
-mod data;
-mod delete;
+pub(crate) mod alerts;
+pub(crate) mod benchmark;
+pub(crate) mod completions;
+pub(crate) mod config;
+pub(crate) mod data;
+pub(crate) mod delete;
 mod deploy;
-mod init;
-mod logs;
-mod run;
-mod start;
-mod status;
+pub(crate) mod doctor;
+mod image;
+pub(crate) mod init;
+pub(crate) mod logs;
+mod pause;
+mod recover;
+mod resume;
+pub(crate) mod run;
+mod scale;
+pub(crate) mod start;
+pub(crate) mod status;
 mod stop;
+mod top;
 
-use aiva_core::{Config as AivaConfig, Result};
+use aiva_core::{AivaError, Config as AivaConfig, Result};
 use clap::Subcommand;
+use clap_complete::Shell;
 use std::path::PathBuf;
 
 use crate::output::OutputFormat;
@@ -25,12 +36,36 @@ pub enum Command {
         /// Template to use
         #[arg(short, long)]
         template: Option<String>,
+
+        /// Give the current user access to /dev/kvm through sudo (Linux)
+        #[arg(long)]
+        grant_kvm: bool,
+
+        /// Show what would be created without writing any files
+        #[arg(long, conflicts_with = "grant_kvm")]
+        dry_run: bool,
+
+        /// Replace an existing or half-finished init of the same name
+        #[arg(long, conflicts_with = "dry_run")]
+        force: bool,
+
+        /// Do not ask for confirmation before re-initializing
+        #[arg(short, long, requires = "force")]
+        yes: bool,
     },
 
     /// Start an AI agent/MCP server instance
     Start {
         /// Name of the agent
-        name: String,
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+
+        /// Start every stopped agent, at most --concurrency at a time
+        #[arg(
+            long,
+            conflicts_with_all = ["name", "cpus", "memory", "disk", "profile", "port", "wait", "dry_run"]
+        )]
+        all: bool,
 
         /// Number of vCPUs
         #[arg(long)]
@@ -44,21 +79,87 @@ pub enum Command {
         #[arg(long)]
         disk: Option<String>,
 
-        /// Port mappings (format: host:guest)
+        /// Resource and security preset (e.g., dev, prod, untrusted);
+        /// --cpus, --memory and --disk take precedence over it
+        #[arg(long)]
+        profile: Option<String>,
+
+        /// Port mappings (format: [ip:]host:guest, binds 127.0.0.1 by default)
         #[arg(short, long)]
         port: Vec<String>,
+
+        /// Permit the unsafe cache strategy for hardened or production VMs
+        #[arg(long)]
+        allow_unsafe_cache: bool,
+
+        /// Wait until the guest agent answers and guest networking is up
+        #[arg(long)]
+        wait: bool,
+
+        /// Show what would be created without creating anything
+        #[arg(long, conflicts_with = "wait")]
+        dry_run: bool,
     },
 
     /// Stop an AI agent/MCP server instance
     Stop {
         /// Name of the agent
-        name: String,
+        #[arg(required_unless_present = "all")]
+        name: Option<String>,
+
+        /// Stop every running or paused agent, at most --concurrency at a time
+        #[arg(long, conflicts_with = "name")]
+        all: bool,
 
         /// Force stop
         #[arg(short, long)]
         force: bool,
     },
 
+    /// Pause an AI agent/MCP server instance
+    Pause {
+        /// Name of the agent
+        name: String,
+
+        /// Snapshot the VM to disk and free its memory until it is resumed
+        #[arg(long)]
+        hibernate: bool,
+    },
+
+    /// Resume a paused or hibernated AI agent/MCP server instance
+    Resume {
+        /// Name of the agent
+        name: String,
+    },
+
+    /// Change the vCPUs and memory of an instance, live where supported
+    Scale {
+        /// Name of the agent
+        name: String,
+
+        /// Number of vCPUs
+        #[arg(long)]
+        cpus: Option<u32>,
+
+        /// Memory size (e.g., 16GB)
+        #[arg(long)]
+        memory: Option<String>,
+
+        /// Restart the VM when a change cannot be applied live
+        #[arg(long)]
+        restart: bool,
+    },
+
+    /// Clean up a VM stuck in the Error state and reset it to Stopped
+    Recover {
+        /// Name of the agent
+        name: String,
+
+        /// Start the VM again once it has been recovered
+        #[arg(long)]
+        start: bool,
+    },
+
     /// Delete an AI agent/MCP server instance
     Delete {
         /// Name of the agent
@@ -67,12 +168,69 @@ pub enum Command {
         /// Force delete (delete running VMs)
         #[arg(short, long)]
         force: bool,
+
+        /// Keep the data directory and volumes and list where they are.
+        /// This is also the default; volumes are detached so a new VM can
+        /// attach them again
+        #[arg(long, conflicts_with = "purge")]
+        keep_data: bool,
+
+        /// Also delete owned volumes, the VM data directory and shipped logs
+        #[arg(long)]
+        purge: bool,
+
+        /// Do not ask for confirmation before purging
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Show status of AI agent/MCP server instances
     Status {
         /// Name of the agent (optional, shows all if not specified)
         name: Option<String>,
+
+        /// Correct stored states that disagree with the hypervisor, e.g. a
+        /// VM marked running whose process died
+        #[arg(long)]
+        reconcile: bool,
+    },
+
+    /// Show resource usage of running instances, busiest first
+    Top {
+        /// Resource to sort by
+        #[arg(long, value_enum, default_value = "cpu")]
+        sort: top::TopSort,
+    },
+
+    /// List the alerts raised by monitoring, or resolve one
+    #[command(args_conflicts_with_subcommands = true)]
+    Alerts {
+        #[command(subcommand)]
+        action: Option<AlertsAction>,
+
+        /// Only alerts of this agent
+        #[arg(long)]
+        vm: Option<String>,
+
+        /// Only alerts raised since: a duration ago (30m, 2h, 7d) or an RFC 3339 time
+        #[arg(long, value_parser = alerts::parse_time_bound)]
+        since: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Only alerts raised before: a duration ago or an RFC 3339 time
+        #[arg(long, value_parser = alerts::parse_time_bound)]
+        until: Option<chrono::DateTime<chrono::Utc>>,
+
+        /// Only alerts at least this severe
+        #[arg(long, value_enum)]
+        severity: Option<alerts::SeverityArg>,
+
+        /// Only resolved alerts
+        #[arg(long, conflicts_with = "unresolved")]
+        resolved: bool,
+
+        /// Only alerts still open
+        #[arg(long)]
+        unresolved: bool,
     },
 
     /// Deploy a new image to an AI agent/MCP server
@@ -98,9 +256,13 @@ pub enum Command {
         #[arg(short, long)]
         follow: bool,
 
-        /// Number of lines to show from the end
-        #[arg(short, long)]
+        /// Number of lines to show from the end, reaching into rotated logs
+        #[arg(short, long, value_parser = logs::parse_tail, allow_hyphen_values = true)]
         tail: Option<usize>,
+
+        /// Show log files shipped from the guest (see logging.paths)
+        #[arg(long)]
+        guest: bool,
     },
 
     /// Run an MCP server command in a VM
@@ -111,9 +273,58 @@ pub enum Command {
         /// Command to execute
         command: String,
 
-        /// Transport mode (sse, stdio)
-        #[arg(short, long, default_value = "sse")]
+        /// Arguments passed to the command as given, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+
+        /// Transport mode (sse, stdio); inferred from the command when omitted
+        #[arg(short, long)]
         transport: Option<String>,
+
+        /// Guest directory to run the command in (overrides the VM's workdir)
+        #[arg(short, long)]
+        workdir: Option<PathBuf>,
+
+        /// Guest user to run the command as (overrides the VM's run_as_user)
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// Port the sse server listens on (defaults to the template's port)
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Wait until the server accepts connections on its host port
+        /// (timeout in seconds, 30 when no value is given)
+        #[arg(long, value_name = "SECS", num_args = 0..=1, default_missing_value = "30")]
+        wait_ready: Option<u64>,
+
+        /// HTTP path polled by --wait-ready instead of a plain TCP connect
+        #[arg(long, requires = "wait_ready")]
+        health_path: Option<String>,
+
+        /// Stop an MCP server already running in the VM before starting this one
+        #[arg(long)]
+        restart_existing: bool,
+
+        /// Follow the server's log after it starts, until Ctrl+C; the server
+        /// keeps running
+        #[arg(long)]
+        attach_logs: bool,
+    },
+
+    /// Measure cold start, warm restart and command latency of a VM
+    Benchmark {
+        /// Name of the agent
+        name: String,
+
+        /// Number of iterations per measurement
+        #[arg(short = 'n', long, default_value_t = 5)]
+        iterations: usize,
+
+        /// Skip the cold start measurement, which boots throwaway clones of
+        /// the VM and needs it stopped
+        #[arg(long)]
+        skip_cold: bool,
     },
 
     /// Manage configuration
@@ -127,6 +338,33 @@ pub enum Command {
         #[command(subcommand)]
         operation: DataOperation,
     },
+
+    /// Manage rootfs images
+    Image {
+        #[command(subcommand)]
+        action: ImageAction,
+    },
+
+    /// Check the host for the platform requirements and tools aiva needs
+    Doctor {
+        /// Repair what is safe to repair: reset VMs stuck in a transition,
+        /// remove orphaned TAP devices and workspaces, recreate missing data
+        /// directories. Running VMs are never touched.
+        #[arg(long)]
+        fix: bool,
+
+        /// With --fix, also grant the current user access to /dev/kvm
+        /// through sudo
+        #[arg(long, requires = "fix")]
+        grant_kvm: bool,
+    },
+
+    /// Print a shell completion script to stdout
+    Completions {
+        /// Shell to generate completions for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -135,7 +373,9 @@ pub enum ConfigAction {
     Get {
         /// Name of the agent
         name: String,
-        /// Configuration key
+        /// Configuration key. `runtime.pid`, `runtime.api_socket`,
+        /// `runtime.tap_device`, `runtime.vsock_cid` and `runtime.mcp_pids`
+        /// are read-only and reflect the running VM
         key: String,
     },
 
@@ -147,6 +387,10 @@ pub enum ConfigAction {
         key: String,
         /// Configuration value
         value: String,
+        /// Push the change to the running VM where possible (memory_mb and
+        /// the network.dns_* keys) instead of waiting for a restart
+        #[arg(long)]
+        apply_now: bool,
     },
 
     /// List all configuration values
@@ -154,6 +398,77 @@ pub enum ConfigAction {
         /// Name of the agent
         name: String,
     },
+
+    /// Validate the configuration against the assigned security policy
+    Validate {
+        /// Name of the agent
+        name: String,
+
+        /// Permit the unsafe cache strategy for hardened or production VMs
+        #[arg(long)]
+        allow_unsafe_cache: bool,
+    },
+
+    /// Print the configuration, template, security policy and labels of a
+    /// VM as one document, YAML with --format yaml and JSON otherwise
+    Export {
+        /// Name of the agent
+        name: String,
+    },
+
+    /// Create a VM from a document written by 'aiva config export'. Only the
+    /// definition is recreated, no image or guest data
+    Import {
+        /// JSON or YAML definition
+        file: PathBuf,
+
+        /// Name of the new agent
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AlertsAction {
+    /// Mark an alert as resolved
+    Resolve {
+        /// Id of the alert, as listed by 'aiva alerts'
+        id: uuid::Uuid,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ImageAction {
+    /// Download an image from a URL, registry reference or local path
+    Pull {
+        /// Name to store the image under
+        name: String,
+        /// http(s) URL, registry reference (repo[:tag]) or local path
+        source: String,
+    },
+
+    /// List stored images
+    Ls,
+
+    /// Delete an image
+    Rm {
+        /// Image ID
+        id: String,
+    },
+
+    /// Show details of an image
+    Inspect {
+        /// Image ID
+        id: String,
+    },
+
+    /// List images with missing digests, unverified signatures or
+    /// untrusted sources
+    Audit {
+        /// Trusted source prefix, in addition to security.image_sources
+        #[arg(long = "allow-source")]
+        allow_sources: Vec<String>,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -177,35 +492,189 @@ pub enum DataOperation {
         /// Name of the agent
         name: String,
     },
+
+    /// Create a data volume
+    Create {
+        /// Name of the volume
+        name: String,
+
+        /// Size, e.g. 512MB or 10GB
+        #[arg(long)]
+        size: String,
+
+        /// Format of the volume
+        #[arg(long, value_enum, default_value = "ext4")]
+        volume_format: data::VolumeFormatArg,
+
+        /// Only allocate blocks as they are written (the default)
+        #[arg(long, conflicts_with = "preallocate")]
+        sparse: bool,
+
+        /// Reserve the whole size on disk now, so writes cannot fail later
+        /// because the host disk filled up
+        #[arg(long)]
+        preallocate: bool,
+
+        /// Keep the volume when VMs using it are purged
+        #[arg(long)]
+        shared: bool,
+    },
+}
+
+/// Name of a command that takes either a name or `--all`; clap makes sure
+/// one of them is given
+fn required_name(name: Option<String>) -> Result<String> {
+    name.ok_or_else(|| AivaError::ConfigError("Name the agent or pass --all".to_string()))
 }
 
 pub async fn execute(command: Command, config: AivaConfig, format: OutputFormat) -> Result<()> {
     match command {
-        Command::Init { name, template } => init::execute(name, template, config, format).await,
+        Command::Init {
+            name,
+            template,
+            grant_kvm,
+            dry_run,
+            force,
+            yes,
+        } => {
+            let options = init::InitOptions {
+                template,
+                grant_kvm,
+                dry_run,
+                force,
+                yes,
+            };
+            init::execute(name, options, config, format).await
+        }
+        Command::Start { all: true, .. } => start::start_all(config, format).await,
         Command::Start {
             name,
+            all: _,
             cpus,
             memory,
             disk,
+            profile,
             port,
-        } => start::execute(name, cpus, memory, disk, port, config, format).await,
-        Command::Stop { name, force } => stop::execute(name, force, config, format).await,
-        Command::Delete { name, force } => delete::execute(name, force, config, format).await,
-        Command::Status { name } => status::execute(name, config, format).await,
+            allow_unsafe_cache,
+            wait,
+            dry_run,
+        } => {
+            let options = start::StartOptions {
+                cpus,
+                memory,
+                disk,
+                profile,
+                ports: port,
+                allow_unsafe_cache,
+                wait,
+                dry_run,
+            };
+            start::execute(required_name(name)?, options, config, format).await
+        }
+        Command::Stop {
+            all: true, force, ..
+        } => stop::stop_all(force, config, format).await,
+        Command::Stop {
+            name,
+            all: _,
+            force,
+        } => stop::execute(required_name(name)?, force, config, format).await,
+        Command::Pause { name, hibernate } => pause::execute(name, hibernate, config, format).await,
+        Command::Resume { name } => resume::execute(name, config, format).await,
+        Command::Scale {
+            name,
+            cpus,
+            memory,
+            restart,
+        } => scale::execute(name, cpus, memory, restart, config, format).await,
+        Command::Recover { name, start } => recover::execute(name, start, config, format).await,
+        Command::Delete {
+            name,
+            force,
+            keep_data,
+            purge,
+            yes,
+        } => {
+            let options = delete::DeleteOptions {
+                force,
+                keep_data,
+                purge,
+                yes,
+            };
+            delete::execute(name, options, config, format).await
+        }
+        Command::Status { name, reconcile } => {
+            status::execute(name, reconcile, config, format).await
+        }
+        Command::Top { sort } => top::execute(sort, config, format).await,
+        Command::Alerts {
+            action: Some(AlertsAction::Resolve { id }),
+            ..
+        } => alerts::resolve(id, config, format).await,
+        Command::Alerts {
+            action: None,
+            vm,
+            since,
+            until,
+            severity,
+            resolved,
+            unresolved,
+        } => {
+            let query = alerts::AlertsQuery {
+                vm,
+                since,
+                until,
+                severity,
+                resolved: (resolved || unresolved).then_some(resolved),
+            };
+            alerts::list(query, config, format).await
+        }
         Command::Deploy {
             name,
             image_path,
             restart,
         } => deploy::execute(name, image_path, restart, config, format).await,
-        Command::Logs { name, follow, tail } => {
-            logs::execute(name, follow, tail, config, format).await
-        }
+        Command::Logs {
+            name,
+            follow,
+            tail,
+            guest,
+        } => logs::execute(name, follow, tail, guest, config, format).await,
         Command::Run {
             name,
             command,
+            args,
             transport,
-        } => run::execute(name, command, transport, config, format).await,
+            workdir,
+            user,
+            port,
+            wait_ready,
+            health_path,
+            restart_existing,
+            attach_logs,
+        } => {
+            let options = run::RunOptions {
+                args,
+                transport,
+                workdir,
+                user,
+                port,
+                wait_ready: wait_ready.map(std::time::Duration::from_secs),
+                health_path,
+                restart_existing,
+                attach_logs,
+            };
+            run::execute(name, command, options, config, format).await
+        }
+        Command::Benchmark {
+            name,
+            iterations,
+            skip_cold,
+        } => benchmark::execute(name, iterations, skip_cold, config, format).await,
         Command::Config { action } => config::execute(action, config, format).await,
         Command::Data { operation } => data::execute(operation, config, format).await,
+        Command::Image { action } => image::execute(action, config, format).await,
+        Command::Doctor { fix, grant_kvm } => doctor::execute(fix, grant_kvm, config, format).await,
+        Command::Completions { shell } => completions::execute(shell, config, format).await,
     }
 }
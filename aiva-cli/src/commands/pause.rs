@@ -0,0 +1,49 @@
+use crate::output::{OutputFormat, print_error, print_progress, print_success, print_warning};
+use aiva_core::{Config, PauseState, Result, VMManager, VMState};
+use std::sync::Arc;
+
+pub async fn execute(
+    name: String,
+    hibernate: bool,
+    _config: Config,
+    _format: OutputFormat,
+) -> Result<()> {
+    print_progress(&format!("Pausing AI agent/MCP server: {name}"));
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let Some(vm) = vm_manager.get_vm_by_name(&name).await? else {
+        print_error(&format!("VM '{name}' not found"));
+        return Err(aiva_core::AivaError::VMError {
+            vm_name: name,
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        });
+    };
+
+    if vm.state == VMState::Paused && !hibernate {
+        print_warning(&format!("VM '{name}' is already paused"));
+        return Ok(());
+    }
+
+    if hibernate {
+        print_progress("Snapshotting VM to disk...");
+    }
+
+    match vm_manager.pause_vm(&vm.id, hibernate).await? {
+        PauseState::InMemory => {
+            print_success(&format!("Paused AI agent/MCP server: {name}"));
+        }
+        PauseState::Hibernated { snapshot } => {
+            print_success(&format!(
+                "Hibernated AI agent/MCP server: {name} (snapshot in {})",
+                snapshot.state.parent().unwrap_or(&snapshot.state).display()
+            ));
+        }
+    }
+    print_progress(&format!("Resume it with 'aiva resume {name}'"));
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_progress, print_success,
+    print_warning,
+};
+use aiva_core::{Config, Result, VMManager};
+use std::sync::Arc;
+
+pub async fn execute(
+    name: String,
+    start: bool,
+    _config: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    print_progress(&format!("Recovering AI agent/MCP server: {name}"));
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let Some(vm) = vm_manager.get_vm_by_name(&name).await? else {
+        print_error(&format!("VM '{name}' not found"));
+        return Err(aiva_core::AivaError::VMError {
+            vm_name: name,
+            state: aiva_core::VMState::Stopped,
+            message: "VM not found".to_string(),
+        });
+    };
+
+    if vm.state != aiva_core::VMState::Error {
+        print_warning(&format!(
+            "VM '{}' is not in Error state (state: {:?}), nothing to recover",
+            name, vm.state
+        ));
+        return Ok(());
+    }
+
+    let report = vm_manager.recover_vm(&vm.id, start).await?;
+
+    if !matches!(format, OutputFormat::Table) {
+        println!("{}", format.format(&report));
+        return Ok(());
+    }
+
+    if report.cleaned.is_empty() {
+        print_info("No leftover resources found");
+    }
+    for resource in &report.cleaned {
+        print_info(&format!("Cleaned up: {resource}"));
+    }
+
+    if report.restarted {
+        print_success(&format!(
+            "Recovered and restarted AI agent/MCP server: {name}"
+        ));
+    } else {
+        print_success(&format!(
+            "Recovered AI agent/MCP server: {name} (now stopped, start it with 'aiva start {name}')"
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use crate::output::{OutputFormat, print_error, print_progress, print_success};
+use aiva_core::{Config, Result, VMManager, VMState};
+use std::sync::Arc;
+
+pub async fn execute(name: String, _config: Config, _format: OutputFormat) -> Result<()> {
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let Some(vm) = vm_manager.get_vm_by_name(&name).await? else {
+        print_error(&format!("VM '{name}' not found"));
+        return Err(aiva_core::AivaError::VMError {
+            vm_name: name,
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        });
+    };
+
+    if vm.runtime.is_hibernated() {
+        print_progress(&format!(
+            "Restoring AI agent/MCP server from snapshot: {name}"
+        ));
+    } else {
+        print_progress(&format!("Resuming AI agent/MCP server: {name}"));
+    }
+
+    vm_manager.resume_vm(&vm.id).await?;
+
+    print_success(&format!("Resumed AI agent/MCP server: {name}"));
+    Ok(())
+}
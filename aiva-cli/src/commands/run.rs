@@ -1,17 +1,107 @@
-use crate::output::{OutputFormat, print_error, print_info, print_progress, print_success};
-use aiva_core::{Config, Result, VMLogger, VMManager, VMTemplate};
+use super::logs::follow_guest_files;
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_progress, print_success,
+    print_warning, write_output_file,
+};
+use aiva_core::{
+    AivaError, Config, ExecContext, NetworkConfig, PortProbe, Result, RunPlan, RunResult,
+    ServerPidFile, VMLogger, VMManager, VMTemplate, command_line, replace_existing_server,
+    server_log_path,
+};
+use serde::Serialize;
 use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tabled::Tabled;
+
+/// Command line options for `aiva run`
+pub struct RunOptions {
+    /// Arguments after `--`, each passed to the command unchanged
+    pub args: Vec<String>,
+    pub transport: Option<String>,
+    pub workdir: Option<PathBuf>,
+    pub user: Option<String>,
+    /// Guest port for sse servers, overriding the template default
+    pub port: Option<u16>,
+    /// Poll the server's host port for this long before reporting success
+    pub wait_ready: Option<Duration>,
+    pub health_path: Option<String>,
+    /// Stop a server left running by an earlier `run` instead of failing
+    pub restart_existing: bool,
+    /// Follow the server's log after launch until Ctrl+C
+    pub attach_logs: bool,
+}
+
+#[derive(Serialize, Tabled)]
+struct RunResultRow {
+    transport: String,
+    host: String,
+    #[tabled(display_with = "display_option")]
+    port: Option<u16>,
+    #[tabled(display_with = "display_option")]
+    pid: Option<u32>,
+}
+
+impl From<&RunResult> for RunResultRow {
+    fn from(result: &RunResult) -> Self {
+        Self {
+            transport: result.transport.clone(),
+            host: result.host.clone(),
+            port: result.port,
+            pid: result.pid,
+        }
+    }
+}
+
+/// Where the server launched by `plan` is reached and where its output goes
+pub(crate) fn launch_result(
+    vm_name: &str,
+    plan: &RunPlan,
+    network: &NetworkConfig,
+    output: &str,
+) -> RunResult {
+    RunResult::new(plan, network, RunResult::pid_from_output(output))
+        .with_log_path(server_log_path(vm_name))
+}
+
+/// Guest files `--attach-logs` follows for a launched server
+pub(crate) fn attached_log_paths(result: &RunResult) -> Vec<String> {
+    result
+        .log_path
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+fn display_option<T: std::fmt::Display>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or_else(|| "-".to_string(), ToString::to_string)
+}
 
 pub async fn execute(
     name: String,
     command: String,
-    transport: Option<String>,
-    _config: Config,
-    _format: OutputFormat,
+    options: RunOptions,
+    config: Config,
+    format: OutputFormat,
 ) -> Result<()> {
-    let transport = transport.unwrap_or_else(|| "sse".to_string());
-    print_progress(&format!("Running MCP command in VM '{name}': {command}"));
+    let RunOptions {
+        args,
+        transport,
+        workdir,
+        user,
+        port,
+        wait_ready,
+        health_path,
+        restart_existing,
+        attach_logs,
+    } = options;
+    print_progress(&format!(
+        "Running MCP command in VM '{name}': {}",
+        command_line(&command, &args)
+    ));
 
     // Get platform and VM manager
     let platform = aiva_platform::get_current_platform()?;
@@ -25,6 +115,14 @@ pub async fn execute(
         let logger = VMLogger::new(vm.name.clone());
         logger.init().await?;
 
+        let mut context = ExecContext::from_config(&vm.config);
+        if workdir.is_some() {
+            context.workdir = workdir;
+        }
+        if user.is_some() {
+            context.user = user;
+        }
+
         // Check if VM is running
         if vm.state != aiva_core::VMState::Running {
             print_error(&format!(
@@ -57,9 +155,20 @@ pub async fn execute(
             })?
         } else {
             print_info("No template information found, using default command execution");
-            return execute_raw_command(&name, &command, &logger).await;
+            return execute_raw_command(
+                &name,
+                &context.wrap(&command_line(&command, &args)),
+                &logger,
+            )
+            .await;
         };
 
+        let choice = template.resolve_transport(transport.as_deref(), &command, &args);
+        if let Some(warning) = &choice.warning {
+            print_warning(warning);
+        }
+        let transport = choice.transport;
+
         logger
             .info(&format!(
                 "Executing MCP command with transport: {transport}"
@@ -72,8 +181,8 @@ pub async fn execute(
         print_info(&format!("Runtime: {:?}", template.runtime));
 
         // Generate the runtime-specific command
-        let full_command = match template.get_run_command(&command, &transport) {
-            Ok(cmd) => cmd,
+        let plan = match template.plan_run(&command, &args, &transport, port, &context) {
+            Ok(plan) => plan,
             Err(e) => {
                 print_error(&format!("Failed to generate command: {e}"));
                 logger
@@ -83,22 +192,46 @@ pub async fn execute(
             }
         };
 
-        print_info(&format!("Executing: {full_command}"));
+        print_info(&format!("Executing: {}", plan.command));
         logger
-            .info(&format!("Full command: {full_command}"))
+            .info(&format!("Full command: {}", plan.command))
             .await?;
 
+        let pid_file = ServerPidFile::for_vm(&vm.name);
+        let replaced = replace_existing_server(&pid_file, restart_existing, |command| {
+            let vm_manager = vm_manager.clone();
+            async move { vm_manager.execute_command(&vm.id, &command).await }
+        })
+        .await;
+        match replaced {
+            Ok(Some(pid)) => {
+                print_info(&format!(
+                    "Stopped the running MCP server (PID {pid}), starting the new one"
+                ));
+                logger
+                    .info(&format!("Replaced running MCP server with PID {pid}"))
+                    .await?;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                print_error(&e.to_string());
+                logger.error(&format!("Server collision: {e}")).await?;
+                return Err(e);
+            }
+        }
+
         print_progress("Executing command in VM...");
 
         // Execute the command in the VM using the platform integration
-        let execution_result = vm_manager.execute_command(&vm.id, &full_command).await;
+        let execution_result = vm_manager.run_server(&vm.id, &plan).await;
 
-        match execution_result {
+        let output = match execution_result {
             Ok(output) => {
                 logger
                     .info(&format!("Command execution successful: {}", output.trim()))
                     .await?;
                 print_info(&format!("Command output: {}", output.trim()));
+                output
             }
             Err(e) => {
                 logger
@@ -107,45 +240,76 @@ pub async fn execute(
                 print_error(&format!("Command execution failed: {e}"));
                 return Err(e);
             }
+        };
+
+        let result = launch_result(&vm.name, &plan, &vm.config.network, &output);
+
+        match (result.address(), wait_ready) {
+            (Some(addr), Some(timeout)) => {
+                let probe = match health_path {
+                    Some(path) => PortProbe::Http { path },
+                    None => PortProbe::Tcp,
+                };
+                print_progress(&format!(
+                    "Waiting for the server to accept connections on {addr}..."
+                ));
+                match aiva_core::wait_for_port(addr, &probe, timeout).await {
+                    Ok(waited) => {
+                        logger
+                            .info(&format!("Server ready on {addr} after {waited:?}"))
+                            .await?;
+                    }
+                    Err(e) => {
+                        logger
+                            .error(&format!("Server never became ready: {e}"))
+                            .await?;
+                        print_error(&format!(
+                            "MCP server is not accepting connections on {addr}"
+                        ));
+                        return Err(AivaError::NetworkError {
+                            operation: format!("waiting for the MCP server on {addr}"),
+                            cause: format!("{e}\nServer output:\n{}", output.trim()),
+                        });
+                    }
+                }
+            }
+            (None, Some(_)) => {
+                print_warning("--wait-ready has no effect with the stdio transport");
+            }
+            _ => {}
         }
 
-        // Check if this is an SSE mode command and provide connection info
-        if transport == "sse" {
-            // Extract port from command if it contains --port, otherwise use template default
-            let port = if let Some(port_pos) = command.find("--port ") {
-                command[port_pos + 7..]
-                    .split_whitespace()
-                    .next()
-                    .and_then(|p| p.parse::<u16>().ok())
-                    .unwrap_or_else(|| template.mcp_support.default_port.unwrap_or(3000))
-            } else {
-                template.mcp_support.default_port.unwrap_or(3000)
-            };
-
-            print_success("MCP server started successfully!");
-            print_info("MCP Server Details:");
-            print_info("  Transport: SSE");
-            print_info("  Host: 172.16.0.2 (VM internal)");
-            print_info(&format!("  Port: {port}"));
-            print_info(&format!("  URL: http://172.16.0.2:{port}"));
-
-            // Show the host URL (Lima forwards ports directly)
-            print_info(&format!("  Host URL: http://localhost:{port}"));
-            print_info(&format!("  Connect from host: localhost:{port}"));
-            print_info("Use the above URL to connect your MCP client to this server.");
-            print_info(&format!("Monitor logs: aiva logs {name} --follow"));
-        } else if transport == "stdio" {
-            print_success("MCP server ready for stdio communication!");
-            print_info("The server is running in stdio mode.");
-            print_info("Connect your MCP client using stdio transport.");
-            print_info(&format!("Monitor logs: aiva logs {name} --follow"));
+        match &result.url() {
+            Some(url) => {
+                print_success("MCP server started successfully!");
+                print_info(&format!("Connect your MCP client to {url}"));
+            }
+            None => {
+                print_success("MCP server ready for stdio communication!");
+                print_info("Connect your MCP client using stdio transport.");
+            }
         }
+        let rows = vec![RunResultRow::from(&result)];
+        write_output_file(&rows)?;
+        println!("{}", format.format_table(rows));
 
         logger
             .info(&format!(
                 "MCP command execution completed with transport: {transport}"
             ))
             .await?;
+
+        if attach_logs {
+            // The server keeps running after Ctrl+C; only the reader stops
+            let paths = attached_log_paths(&result);
+            print_info(&format!(
+                "Following server log {} (press Ctrl+C to stop)...",
+                paths.join(", ")
+            ));
+            follow_guest_files(&vm, &paths, &config).await?;
+        } else {
+            print_info(&format!("Monitor logs: aiva logs {name} --follow"));
+        }
     } else {
         print_error(&format!("VM '{name}' not found"));
         return Err(aiva_core::AivaError::VMError {
@@ -0,0 +1,103 @@
+use super::config::{get_vm_config, save_vm_config};
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_progress, print_success,
+    print_warning,
+};
+use crate::utils::parse_memory_size;
+use aiva_core::{AivaError, Config, Result, ScaleRequest, ScaleStep, VMManager, VMState};
+use std::sync::Arc;
+
+pub async fn execute(
+    name: String,
+    cpus: Option<u32>,
+    memory: Option<String>,
+    restart: bool,
+    _config: Config,
+    format: OutputFormat,
+) -> Result<()> {
+    if cpus.is_none() && memory.is_none() {
+        return Err(AivaError::ConfigError(
+            "Nothing to scale, pass --cpus and/or --memory".to_string(),
+        ));
+    }
+    let request = ScaleRequest {
+        cpus,
+        memory_mb: memory.as_deref().map(parse_memory_size).transpose()?,
+    };
+
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
+    vm_manager.load_state().await?;
+
+    let Some(vm) = vm_manager.get_vm_by_name(&name).await? else {
+        print_error(&format!("VM '{name}' not found"));
+        return Err(AivaError::VMError {
+            vm_name: name,
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        });
+    };
+
+    print_progress(&format!("Scaling AI agent/MCP server: {name}"));
+    let plan = vm_manager.scale_vm(&vm.id, request, restart).await?;
+
+    // Keep config.json in step so a recreated VM gets the same resources
+    if let Ok(mut vm_config) = get_vm_config(&name) {
+        vm_config.cpus = plan.cpus;
+        vm_config.memory_mb = plan.memory_mb;
+        save_vm_config(&name, &vm_config)?;
+    }
+
+    if !matches!(format, OutputFormat::Table) {
+        println!("{}", format.format(&plan));
+        return Ok(());
+    }
+
+    if plan.is_noop() {
+        print_info(&format!(
+            "VM '{name}' has {} vCPUs and {}MB{}",
+            plan.cpus,
+            plan.memory_mb,
+            if vm.state == VMState::Running {
+                ", nothing to change"
+            } else {
+                "; the new values apply on its next start"
+            }
+        ));
+        return Ok(());
+    }
+
+    for step in &plan.live {
+        match step {
+            ScaleStep::Balloon {
+                memory_mb,
+                balloon_mib,
+            } => print_success(&format!(
+                "Memory set to {memory_mb}MB without a restart (balloon holds {balloon_mib}MiB)"
+            )),
+            ScaleStep::HotplugCpus { cpus } => {
+                print_success(&format!("Hotplugged to {cpus} vCPUs"))
+            }
+        }
+    }
+
+    if plan.restart_required() {
+        if restart {
+            print_success(&format!(
+                "Restarted VM '{name}' with {} vCPUs and {}MB",
+                plan.cpus, plan.memory_mb
+            ));
+        } else {
+            print_warning("A restart is required, true hotplug is not available:");
+            for reason in &plan.restart_reasons {
+                println!("  - {reason}");
+            }
+            print_info(&format!(
+                "The new values are saved. Pass --restart, or run 'aiva stop {name}' and \
+                 'aiva start {name}', to apply them"
+            ));
+        }
+    }
+
+    Ok(())
+}
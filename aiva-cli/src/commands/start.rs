@@ -1,17 +1,75 @@
-use crate::output::{OutputFormat, print_error, print_progress, print_success};
-use crate::utils::{get_vm_dir, parse_disk_size, parse_memory_size, parse_port_mapping};
-use aiva_core::{Config, PortMapping, Protocol, Result, VMConfig, VMManager};
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_progress, print_success,
+    print_warning, write_output_file,
+};
+use crate::utils::{
+    fleet_manager, get_vm_dir, parse_disk_size, parse_memory_size, parse_port_mapping,
+    resolve_security_policy,
+};
+use aiva_core::{
+    AivaError, Config, CreatePlan, Result, VMConfig, VMInstance, VMManager, VMState, VMTemplate,
+};
+use aiva_security::{IOLimit, IOLimitStatus, parse_drive_rate_limiter, verify_io_limit};
+use serde::Serialize;
+use std::collections::HashMap;
 use std::fs;
 use std::sync::Arc;
+use std::time::Duration;
+use tabled::Tabled;
+use uuid::Uuid;
+
+/// Command line overrides for `aiva start`
+pub struct StartOptions {
+    pub cpus: Option<u32>,
+    pub memory: Option<String>,
+    pub disk: Option<String>,
+    /// Name of a profile from `Config::profile`
+    pub profile: Option<String>,
+    pub ports: Vec<String>,
+    pub allow_unsafe_cache: bool,
+    pub wait: bool,
+    /// Print what would be created instead of creating it
+    pub dry_run: bool,
+}
+
+/// Apply `--profile`, then the explicit resource flags and port mappings,
+/// to the stored configuration of the VM
+pub(crate) fn resolve_vm_config(
+    vm_config: &mut VMConfig,
+    config: &Config,
+    options: &StartOptions,
+) -> Result<()> {
+    if let Some(profile) = &options.profile {
+        config.profile(profile)?.apply(vm_config)?;
+    }
+
+    if let Some(cpus) = options.cpus {
+        vm_config.cpus = cpus;
+    }
+    if let Some(memory) = &options.memory {
+        vm_config.memory_mb = parse_memory_size(memory)?;
+    }
+    if let Some(disk) = &options.disk {
+        vm_config.disk_gb = parse_disk_size(disk)?;
+    }
+
+    for port in &options.ports {
+        vm_config
+            .network
+            .port_mappings
+            .push(parse_port_mapping(port)?);
+    }
+    Ok(())
+}
+
+/// How long `--wait` gives the guest agent to answer after boot
+const READY_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub async fn execute(
     name: String,
-    cpus: Option<u32>,
-    memory: Option<String>,
-    disk: Option<String>,
-    ports: Vec<String>,
-    _config: Config,
-    _format: OutputFormat,
+    options: StartOptions,
+    config: Config,
+    format: OutputFormat,
 ) -> Result<()> {
     print_progress(&format!("Starting AI agent/MCP server: {name}"));
 
@@ -28,45 +86,82 @@ pub async fn execute(
         )));
     }
 
-    let config_content = fs::read_to_string(&config_path)?;
-    let mut vm_config: VMConfig = serde_json::from_str(&config_content)?;
-
-    // Override with command line options
-    if let Some(cpus) = cpus {
-        vm_config.cpus = cpus;
-    }
-    if let Some(memory) = memory {
-        vm_config.memory_mb = parse_memory_size(&memory)?;
-    }
-    if let Some(disk) = disk {
-        vm_config.disk_gb = parse_disk_size(&disk)?;
+    let mut vm_config = aiva_core::read_vm_config(&config_path)?;
+    resolve_vm_config(&mut vm_config, &config, &options)?;
+    let StartOptions {
+        profile,
+        allow_unsafe_cache,
+        wait,
+        dry_run,
+        ..
+    } = options;
+    if let Some(profile) = &profile {
+        print_info(&format!(
+            "Profile '{profile}': {} vCPUs, {}MB memory, security policy {}",
+            vm_config.cpus,
+            vm_config.memory_mb,
+            vm_config.security_policy.as_deref().unwrap_or("none")
+        ));
     }
 
-    // Parse port mappings
-    for port in ports {
-        let (host_port, guest_port) = parse_port_mapping(&port)?;
-        vm_config.network.port_mappings.push(PortMapping {
-            host_port,
-            guest_port,
-            protocol: Protocol::Tcp,
-        });
+    vm_config.network.validate_dns()?;
+    if let Some(hostname) = &vm_config.network.hostname {
+        aiva_core::validate_hostname(hostname)?;
     }
+    let network = vm_config.network.clone();
+
+    let policy = match &vm_config.security_policy {
+        Some(policy_name) => Some(resolve_security_policy(policy_name).await?),
+        None => None,
+    };
+    aiva_security::validate_cache_strategy(
+        policy.as_ref(),
+        &vm_config.storage,
+        config.security.production,
+        allow_unsafe_cache,
+    )?;
 
     // Get platform and VM manager
     let platform = aiva_platform::get_current_platform()?;
     let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
     vm_manager.load_state().await?;
 
+    if dry_run {
+        if let Some(existing_vm) = vm_manager.get_vm_by_name(&name).await? {
+            print_info(&format!(
+                "VM '{name}' already exists ({:?}); start would boot it without creating anything",
+                existing_vm.state
+            ));
+            return Ok(());
+        }
+
+        let mut plan = vm_manager.plan_create(&name, &vm_config).await?;
+        let template_file = vm_dir.join("config").join("template.json");
+        if let Ok(content) = fs::read_to_string(template_file)
+            && let Ok(template) = serde_json::from_str::<VMTemplate>(&content)
+        {
+            plan = plan.with_template(&template);
+        }
+        print_create_plan(&plan, format);
+        return Ok(());
+    }
+
     // Check if VM already exists
-    if let Some(existing_vm) = vm_manager.get_vm_by_name(&name).await? {
+    let vm_id = if let Some(existing_vm) = vm_manager.get_vm_by_name(&name).await? {
         if existing_vm.state == aiva_core::VMState::Running {
             print_error(&format!("VM '{name}' is already running"));
             return Ok(());
         }
 
         // Start existing VM
+        if profile.is_some() {
+            print_warning(&format!(
+                "VM '{name}' already exists, --profile only applies when it is created"
+            ));
+        }
         print_progress("Starting existing VM...");
         vm_manager.start_vm(&existing_vm.id).await?;
+        existing_vm.id
     } else {
         // Create and start new VM
         print_progress("Creating new VM...");
@@ -74,6 +169,44 @@ pub async fn execute(
 
         print_progress("Starting VM...");
         vm_manager.start_vm(&vm.id).await?;
+        vm.id
+    };
+
+    if let Some(io_limit) = policy
+        .as_ref()
+        .and_then(|p| p.resource_limits.io_bandwidth.as_ref())
+        && let Some(vm) = vm_manager.get_vm(&vm_id).await?
+    {
+        enforce_io_limit(&vm, io_limit).await;
+    }
+
+    // Search domains and split DNS go through the guest agent, so they need
+    // it to be up even without --wait. The saved config is used so that
+    // 'aiva config set' changes reach existing VMs on their next start.
+    let custom_dns = network.has_custom_dns();
+    if (custom_dns || wait)
+        && let Some(mut vm) = vm_manager.get_vm(&vm_id).await?
+    {
+        print_progress("Waiting for the guest to become ready...");
+        aiva_core::wait_for_guest(vm_manager.as_ref(), &vm_id, READY_TIMEOUT).await?;
+        if custom_dns {
+            print_progress("Applying guest DNS settings...");
+            aiva_core::apply_guest_dns(vm_manager.as_ref(), &vm, &network).await?;
+        }
+
+        // With the agent up anyway, name the guest and list its neighbours
+        vm.config.network.hostname = network.hostname.clone();
+        if let Err(e) = aiva_core::apply_guest_hostname(vm_manager.as_ref(), &vm).await {
+            print_warning(&format!("Guest hostname not set: {e}"));
+        }
+    }
+
+    if wait && let Some(vm) = vm_manager.get_vm(&vm_id).await? {
+        let probe = aiva_core::check_guest_network(vm_manager.as_ref(), &vm).await?;
+        print_info(&format!(
+            "Guest network is up at {}",
+            probe.address.as_deref().unwrap_or("unknown")
+        ));
     }
 
     print_success(&format!("Successfully started AI agent/MCP server: {name}"));
@@ -82,3 +215,153 @@ pub async fn execute(
 
     Ok(())
 }
+
+/// Apply the policy's IO limit to the root drive and read it back, since
+/// older Firecracker releases silently drop rate limiter fields
+async fn enforce_io_limit(vm: &VMInstance, io_limit: &IOLimit) {
+    let Some(socket) = &vm.runtime.api_socket else {
+        print_info("IO limits cannot be verified on this platform");
+        return;
+    };
+
+    let result = async {
+        let client = aiva_platform::FirecrackerApiClient::new(socket.clone())?;
+        client
+            .update_drive_rate_limiter("rootfs", serde_json::to_value(io_limit.to_rate_limiter())?)
+            .await?;
+        let readback = parse_drive_rate_limiter(&client.get_vm_config().await?, "rootfs")?;
+        Ok::<_, aiva_core::AivaError>(verify_io_limit(io_limit, readback.as_ref()))
+    }
+    .await;
+
+    match result {
+        Ok(check) => match check.status {
+            IOLimitStatus::Enforced => print_info("IO limits verified on the root drive"),
+            IOLimitStatus::Missing => {
+                print_warning("IO limits were not applied: the root drive has no rate limiter")
+            }
+            IOLimitStatus::Drifted { differences } => print_warning(&format!(
+                "IO limits differ from the policy: {}",
+                differences.join("; ")
+            )),
+        },
+        Err(e) => print_warning(&format!("Could not verify IO limits: {e}")),
+    }
+}
+
+/// Show a dry-run plan, as prose for tables and structured otherwise
+pub(crate) fn print_create_plan(plan: &CreatePlan, format: OutputFormat) {
+    if !matches!(format, OutputFormat::Table) {
+        println!("{}", format.format(plan));
+        return;
+    }
+
+    print_info(&format!(
+        "Dry run: creating VM '{}' on {} would",
+        plan.name, plan.platform
+    ));
+    if let Some(template) = &plan.template {
+        println!("  use template {template}");
+    }
+    println!(
+        "  allocate {} vCPUs, {} MB memory and a {} GB disk",
+        plan.cpus, plan.memory_mb, plan.disk_gb
+    );
+    println!(
+        "  assign guest IP {} (host {}, gateway {})",
+        plan.guest_ip, plan.host_ip, plan.gateway
+    );
+    for mapping in &plan.port_mappings {
+        println!(
+            "  forward host port {} to guest port {} ({:?})",
+            mapping.host_port, mapping.guest_port, mapping.protocol
+        );
+    }
+    for copy in &plan.image_copies {
+        let size = copy.size_bytes.map_or_else(String::new, |bytes| {
+            format!(" ({} MB)", bytes / 1024 / 1024)
+        });
+        println!(
+            "  copy {} to {}{size}",
+            copy.source.display(),
+            copy.destination.display()
+        );
+    }
+    for resource in &plan.resources {
+        println!("  create {resource}");
+    }
+    println!(
+        "  apply security policy {}",
+        plan.security_policy.as_deref().unwrap_or("none")
+    );
+
+    for conflict in &plan.conflicts {
+        print_warning(conflict);
+    }
+}
+
+/// Outcome for one VM of `aiva start --all` or `aiva stop --all`
+#[derive(Serialize, Tabled)]
+pub(crate) struct BulkRow {
+    pub(crate) name: String,
+    pub(crate) result: String,
+}
+
+/// Print what a bulk `operation` (`done` in the past tense) did to each of
+/// `vms`, failing if any of them failed
+pub(crate) fn report_bulk(
+    (operation, done): (&str, &str),
+    vms: &[VMInstance],
+    mut results: HashMap<Uuid, Result<()>>,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut rows: Vec<BulkRow> = vms
+        .iter()
+        .map(|vm| BulkRow {
+            name: vm.name.clone(),
+            result: match results.remove(&vm.id) {
+                Some(Ok(())) => "ok".to_string(),
+                Some(Err(e)) => e.to_string(),
+                None => "not attempted".to_string(),
+            },
+        })
+        .collect();
+    rows.sort_by(|a, b| a.name.cmp(&b.name));
+
+    write_output_file(&rows)?;
+    let failed = rows.iter().filter(|row| row.result != "ok").count();
+    let total = rows.len();
+    println!("{}", format.format_table(rows));
+
+    if failed > 0 {
+        return Err(AivaError::Other(anyhow::anyhow!(
+            "{failed} of {total} agents failed to {operation}"
+        )));
+    }
+    print_success(&format!("All {total} agents {done}"));
+    Ok(())
+}
+
+/// Start every stopped VM, at most `config.concurrency` at a time
+pub async fn start_all(config: Config, format: OutputFormat) -> Result<()> {
+    let vm_manager = fleet_manager(&config).await?;
+    let stopped: Vec<VMInstance> = vm_manager
+        .list_vms()
+        .await?
+        .into_iter()
+        .filter(|vm| vm.state == VMState::Stopped)
+        .collect();
+    if stopped.is_empty() {
+        print_info("No stopped agents to start");
+        return Ok(());
+    }
+
+    print_progress(&format!(
+        "Starting {} agents, {} at a time",
+        stopped.len(),
+        vm_manager.concurrency()
+    ));
+    let ids: Vec<Uuid> = stopped.iter().map(|vm| vm.id).collect();
+    let results = vm_manager.start_vms(&ids).await;
+    report_bulk(("start", "started"), &stopped, results, format)
+}
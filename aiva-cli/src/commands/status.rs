@@ -1,4 +1,6 @@
-use crate::output::{OutputFormat, OutputFormatter, print_error, print_info};
+use crate::output::{
+    OutputFormat, OutputFormatter, print_error, print_info, print_warning, write_output_file,
+};
 use aiva_core::{Config, Result, VMInstance, VMManager};
 use colored::*;
 use serde::Serialize;
@@ -6,7 +8,7 @@ use std::sync::Arc;
 use tabled::Tabled;
 
 #[derive(Serialize, Tabled)]
-struct VMStatus {
+pub(crate) struct VMStatus {
     name: String,
     state: String,
     cpus: u32,
@@ -19,6 +21,9 @@ impl From<VMInstance> for VMStatus {
     fn from(vm: VMInstance) -> Self {
         let state = match vm.state {
             aiva_core::VMState::Running => "Running".green().to_string(),
+            aiva_core::VMState::Stopped if vm.runtime.is_hibernated() => {
+                "Hibernated".blue().to_string()
+            }
             aiva_core::VMState::Stopped => "Stopped".red().to_string(),
             aiva_core::VMState::Paused => "Paused".yellow().to_string(),
             aiva_core::VMState::Creating => "Creating".cyan().to_string(),
@@ -57,7 +62,12 @@ fn format_duration(duration: std::time::Duration) -> String {
     }
 }
 
-pub async fn execute(name: Option<String>, _config: Config, format: OutputFormat) -> Result<()> {
+pub async fn execute(
+    name: Option<String>,
+    reconcile: bool,
+    _config: Config,
+    format: OutputFormat,
+) -> Result<()> {
     // Get platform and VM manager
     let platform = aiva_platform::get_current_platform()?;
     let vm_manager = Arc::new(aiva_core::VMOrchestrator::new(platform));
@@ -76,15 +86,42 @@ pub async fn execute(name: Option<String>, _config: Config, format: OutputFormat
         }
     }
 
+    // Compare the stored states with the hypervisor
+    let divergences = vm_manager.reconcile_states(reconcile).await?;
+    for divergence in divergences
+        .iter()
+        .filter(|d| name.as_ref().is_none_or(|name| &d.vm_name == name))
+    {
+        if reconcile {
+            print_info(&format!(
+                "{}, marked it {:?}",
+                divergence.describe(),
+                divergence.corrected_state()
+            ));
+        } else {
+            print_warning(&format!(
+                "{}. Run 'aiva status --reconcile' to correct it",
+                divergence.describe()
+            ));
+        }
+    }
+
     if let Some(name) = name {
         // Show specific VM
         if let Some(vm) = vm_manager.get_vm_by_name(&name).await? {
             let status = VMStatus::from(vm.clone());
+            write_output_file(&status)?;
 
             match format {
                 OutputFormat::Table => {
                     println!("\n{}", format.format_table(vec![status]));
 
+                    if vm.state == aiva_core::VMState::Error {
+                        print_info(&format!(
+                            "Run 'aiva recover {name}' to clean up and reset this VM"
+                        ));
+                    }
+
                     // Show additional details for table format
                     if vm.state == aiva_core::VMState::Running {
                         println!("\nNetwork Configuration:");
@@ -95,7 +132,8 @@ pub async fn execute(name: Option<String>, _config: Config, format: OutputFormat
                             println!("\nPort Mappings:");
                             for mapping in &vm.config.network.port_mappings {
                                 println!(
-                                    "  {} -> {} ({})",
+                                    "  {}:{} -> {} ({})",
+                                    mapping.bind_address(),
                                     mapping.host_port,
                                     mapping.guest_port,
                                     match mapping.protocol {
@@ -142,10 +180,11 @@ pub async fn execute(name: Option<String>, _config: Config, format: OutputFormat
         // Show all VMs
         let vms = vm_manager.list_vms().await?;
 
-        if vms.is_empty() {
+        let statuses: Vec<VMStatus> = vms.into_iter().map(VMStatus::from).collect();
+        write_output_file(&statuses)?;
+        if statuses.is_empty() {
             print_info("No VMs found. Run 'aiva init <name>' to create a new VM.");
         } else {
-            let statuses: Vec<VMStatus> = vms.into_iter().map(VMStatus::from).collect();
             println!("{}", format.format_table(statuses));
         }
     }
@@ -1,6 +1,11 @@
-use crate::output::{OutputFormat, print_error, print_progress, print_success, print_warning};
-use aiva_core::{Config, Result, VMManager};
+use super::start::report_bulk;
+use crate::output::{
+    OutputFormat, print_error, print_info, print_progress, print_success, print_warning,
+};
+use crate::utils::fleet_manager;
+use aiva_core::{Config, Result, VMInstance, VMManager, VMState};
 use std::sync::Arc;
+use uuid::Uuid;
 
 pub async fn execute(
     name: String,
@@ -47,3 +52,27 @@ pub async fn execute(
 
     Ok(())
 }
+
+/// Stop every running or paused VM, at most `config.concurrency` at a time
+pub async fn stop_all(force: bool, config: Config, format: OutputFormat) -> Result<()> {
+    let vm_manager = fleet_manager(&config).await?;
+    let running: Vec<VMInstance> = vm_manager
+        .list_vms()
+        .await?
+        .into_iter()
+        .filter(|vm| matches!(vm.state, VMState::Running | VMState::Paused))
+        .collect();
+    if running.is_empty() {
+        print_info("No running agents to stop");
+        return Ok(());
+    }
+
+    print_progress(&format!(
+        "Stopping {} agents, {} at a time",
+        running.len(),
+        vm_manager.concurrency()
+    ));
+    let ids: Vec<Uuid> = running.iter().map(|vm| vm.id).collect();
+    let results = vm_manager.stop_vms(&ids, force).await;
+    report_bulk(("stop", "stopped"), &running, results, format)
+}
@@ -0,0 +1,116 @@
+use crate::output::{OutputFormat, OutputFormatter, print_info, print_warning, write_output_file};
+use aiva_core::{
+    AlertThresholds, Config, MetricsSortKey, Pressure, Result, VMManager, VMMetrics, VMOrchestrator,
+};
+use clap::ValueEnum;
+use colored::*;
+use serde::Serialize;
+use std::sync::Arc;
+use tabled::Tabled;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum TopSort {
+    Cpu,
+    Mem,
+    Net,
+}
+
+impl From<TopSort> for MetricsSortKey {
+    fn from(sort: TopSort) -> Self {
+        match sort {
+            TopSort::Cpu => MetricsSortKey::Cpu,
+            TopSort::Mem => MetricsSortKey::Memory,
+            TopSort::Net => MetricsSortKey::Network,
+        }
+    }
+}
+
+#[derive(Serialize, Tabled)]
+struct TopRow {
+    name: String,
+    #[tabled(display_with = "format_percent")]
+    cpu_percent: f64,
+    #[tabled(display_with = "format_percent")]
+    memory_percent: f64,
+    memory_used_mb: u64,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    pressure: String,
+    #[tabled(display_with = "format_hot")]
+    hot: Vec<String>,
+}
+
+impl TopRow {
+    fn new(name: String, metrics: &VMMetrics, thresholds: &AlertThresholds, color: bool) -> Self {
+        let pressure = thresholds.assess(metrics);
+        let label = pressure.level.to_string();
+        let label = match (color, pressure.level) {
+            (false, _) | (true, Pressure::Normal) => label,
+            (true, Pressure::Warning) => label.yellow().to_string(),
+            (true, Pressure::Critical) => label.red().bold().to_string(),
+        };
+
+        Self {
+            name,
+            cpu_percent: metrics.cpu_usage,
+            memory_percent: metrics.memory_percent(),
+            memory_used_mb: metrics.memory_usage.used_mb,
+            rx_bytes: metrics.network_io.rx_bytes,
+            tx_bytes: metrics.network_io.tx_bytes,
+            pressure: label,
+            hot: pressure.hot,
+        }
+    }
+}
+
+fn format_percent(value: &f64) -> String {
+    format!("{value:.1}%")
+}
+
+fn format_hot(hot: &[String]) -> String {
+    if hot.is_empty() {
+        "-".to_string()
+    } else {
+        hot.join(",")
+    }
+}
+
+pub async fn execute(sort: TopSort, config: Config, format: OutputFormat) -> Result<()> {
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager =
+        Arc::new(VMOrchestrator::new(platform).with_concurrency(config.metrics_concurrency()));
+    vm_manager.load_state().await?;
+
+    let mut metrics = vm_manager.batch_metrics(None).await;
+    let mut samples = Vec::new();
+    for vm in vm_manager.list_vms().await? {
+        match metrics.remove(&vm.id) {
+            Some(Ok(vm_metrics)) => samples.push((vm.name, vm_metrics)),
+            Some(Err(e)) => {
+                print_warning(&format!("Metrics of VM '{}' not available: {e}", vm.name))
+            }
+            None => {}
+        }
+    }
+
+    if samples.is_empty() {
+        write_output_file(&Vec::<TopRow>::new())?;
+        print_info("No running VMs with metrics. Start one with 'aiva start <name>'.");
+        return Ok(());
+    }
+
+    let key = MetricsSortKey::from(sort);
+    samples.sort_by(|(a_name, a), (b_name, b)| key.compare(a, b).then_with(|| a_name.cmp(b_name)));
+
+    // Color codes only belong in the table, JSON and YAML stay plain
+    let color = matches!(format, OutputFormat::Table);
+    let thresholds = AlertThresholds::default();
+    let rows: Vec<TopRow> = samples
+        .into_iter()
+        .map(|(name, metrics)| TopRow::new(name, &metrics, &thresholds, color))
+        .collect();
+    write_output_file(&rows)?;
+    println!("{}", format.format_table(rows));
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use tracing_subscriber::EnvFilter;
+
+/// Level of every target without an override. `--verbose` and `--quiet`
+/// conflict, so at most one of them is set.
+pub(crate) fn base_level(verbose: bool, quiet: bool) -> &'static str {
+    if quiet {
+        "error"
+    } else if verbose {
+        "debug"
+    } else {
+        "info"
+    }
+}
+
+/// Build the log filter from the base level and a `--log` spec such as
+/// `aiva-platform=debug,aiva-network=trace`. Crate names may be written
+/// with dashes; tracing targets use the module path with underscores.
+pub(crate) fn log_filter(
+    verbose: bool,
+    quiet: bool,
+    spec: Option<&str>,
+) -> Result<EnvFilter, String> {
+    let mut directives = vec![base_level(verbose, quiet).to_string()];
+    for directive in spec.unwrap_or_default().split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let directive = match directive.split_once('=') {
+            Some((target, level)) => format!("{}={level}", target.replace('-', "_")),
+            None => directive.replace('-', "_"),
+        };
+        directives.push(directive);
+    }
+
+    EnvFilter::try_new(directives.join(","))
+        .map_err(|e| format!("Invalid --log spec '{}': {e}", spec.unwrap_or_default()))
+}
@@ -1,10 +1,14 @@
 mod commands;
+mod logging;
 mod output;
 mod utils;
 
-use aiva_core::Config;
+#[cfg(test)]
+mod tests;
+
+use aiva_core::ConfigLayers;
 use clap::Parser;
-use tracing_subscriber::EnvFilter;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "aiva")]
@@ -17,9 +21,24 @@ struct Cli {
     #[arg(short, long, global = true, help = "Verbose output")]
     verbose: bool,
 
-    #[arg(short, long, global = true, help = "Quiet output")]
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Quiet output"
+    )]
     quiet: bool,
 
+    #[arg(
+        long,
+        global = true,
+        env = "AIVA_LOG",
+        value_name = "SPEC",
+        help = "Per-module log levels on top of the default (e.g., aiva-platform=debug,aiva-network=trace)"
+    )]
+    log: Option<String>,
+
     #[arg(
         long,
         global = true,
@@ -29,6 +48,26 @@ struct Cli {
     )]
     format: output::OutputFormat,
 
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        help = "Also write the result of status, run and top as JSON to this file"
+    )]
+    output_file: Option<PathBuf>,
+
+    #[arg(long, global = true, help = "Disable colored output")]
+    no_color: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "N",
+        value_parser = utils::parse_concurrency,
+        help = "VMs bulk start/stop and metrics collection act on at the same time (default: one per CPU, at most 16)"
+    )]
+    concurrency: Option<usize>,
+
     #[arg(
         long,
         global = true,
@@ -37,28 +76,52 @@ struct Cli {
     lima_config: Option<String>,
 }
 
+impl Cli {
+    /// Configuration layers with the global flags that override it on top
+    fn config_layers(&self, layers: ConfigLayers) -> ConfigLayers {
+        let mut layers = layers;
+        if let Some(limit) = self.concurrency {
+            // The flag bounds every operation, metrics collection included
+            layers = layers.flag("concurrency", "concurrency", limit).flag(
+                "concurrency",
+                "monitoring.metrics_concurrency",
+                None::<usize>,
+            );
+        }
+        if let Some(lima_config) = &self.lima_config {
+            layers = layers.flag(
+                "lima-config",
+                "platform.macos.lima_config",
+                lima_config.as_str(),
+            );
+        }
+        layers
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
 
-    // Initialize logging
-    let log_level = if cli.verbose {
-        "debug"
-    } else if cli.quiet {
-        "error"
-    } else {
-        "info"
-    };
+    let color = output::configure_color(cli.no_color, cli.format);
+    output::configure_output_file(cli.output_file.clone());
 
+    // Initialize logging
+    let filter = logging::log_filter(cli.verbose, cli.quiet, cli.log.as_deref())?;
     tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::new(log_level))
+        .with_env_filter(filter)
+        .with_ansi(color)
         .init();
 
-    // Load configuration
-    let config = Config::load()?;
+    // Defaults < config file < AIVA_* environment < flags
+    let resolved = cli.config_layers(ConfigLayers::new()?).resolve()?;
+    for (key, source) in &resolved.sources {
+        tracing::debug!("config {key} from {source}");
+    }
+    let config = resolved.config;
 
-    // Set Lima config environment variable if provided
-    if let Some(ref lima_config) = cli.lima_config {
+    // The macOS platform reads the Lima config from the environment
+    if let Some(lima_config) = &config.platform.macos.lima_config {
         unsafe {
             std::env::set_var("AIVA_LIMA_CONFIG", lima_config);
         }
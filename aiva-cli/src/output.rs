@@ -1,9 +1,13 @@
+use aiva_core::{AivaError, Result};
 use clap::ValueEnum;
 use colored::*;
 use serde::Serialize;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tabled::{Table, Tabled};
 
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum OutputFormat {
     Table,
     Json,
@@ -46,6 +50,112 @@ impl OutputFormatter for OutputFormat {
     }
 }
 
+/// Whether output may be colored. `--no-color`, a non-empty `NO_COLOR`, a
+/// stdout that is not a terminal and the JSON and YAML formats all turn
+/// color off.
+pub fn color_enabled(
+    no_color_flag: bool,
+    no_color_env: Option<&str>,
+    stdout_is_terminal: bool,
+    format: OutputFormat,
+) -> bool {
+    !no_color_flag
+        && no_color_env.is_none_or(str::is_empty)
+        && stdout_is_terminal
+        && format == OutputFormat::Table
+}
+
+/// Decide once for the whole process whether to color output, returns the
+/// decision for the log output
+pub fn configure_color(no_color_flag: bool, format: OutputFormat) -> bool {
+    let no_color_env = std::env::var("NO_COLOR").ok();
+    let enabled = color_enabled(
+        no_color_flag,
+        no_color_env.as_deref(),
+        std::io::stdout().is_terminal(),
+        format,
+    );
+    colored::control::set_override(enabled);
+    enabled
+}
+
+static OUTPUT_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set the `--output-file` for the whole process
+pub fn configure_output_file(path: Option<PathBuf>) {
+    if let Some(path) = path {
+        let _ = OUTPUT_FILE.set(path);
+    }
+}
+
+/// Write the structured result of a command to the `--output-file`, if one
+/// was given. The file always holds JSON, whatever `--format` says.
+pub fn write_output_file<T: Serialize>(data: &T) -> Result<()> {
+    match OUTPUT_FILE.get() {
+        Some(path) => write_json_file(path, data),
+        None => Ok(()),
+    }
+}
+
+/// Write `data` as pretty JSON to `path`, creating its directory. The JSON
+/// goes to a temporary file next to `path` first and is renamed over it, so
+/// readers never see a partial file. Color codes are stripped from strings.
+pub fn write_json_file<T: Serialize>(path: &Path, data: &T) -> Result<()> {
+    let mut value = serde_json::to_value(data)?;
+    strip_ansi_value(&mut value);
+    let content = serde_json::to_string_pretty(&value)?;
+
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::create_dir_all(dir)?;
+
+    let file_name = path.file_name().ok_or_else(|| {
+        AivaError::ConfigError(format!("Invalid output file: {}", path.display()))
+    })?;
+    let tmp = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+    std::fs::write(&tmp, content + "\n")?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })?;
+    Ok(())
+}
+
+fn strip_ansi_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => *s = strip_ansi(s),
+        serde_json::Value::Array(items) => items.iter_mut().for_each(strip_ansi_value),
+        serde_json::Value::Object(map) => map.values_mut().for_each(strip_ansi_value),
+        _ => {}
+    }
+}
+
+/// Remove the `ESC [ ... m` style sequences `colored` adds
+fn strip_ansi(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            if chars.clone().next() == Some('[') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
 pub fn print_success(message: &str) {
     println!("{} {}", "✓".green(), message);
 }
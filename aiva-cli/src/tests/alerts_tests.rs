@@ -0,0 +1,72 @@
+use crate::Cli;
+use crate::commands::alerts::{SeverityArg, time_bound_at};
+use crate::commands::{AlertsAction, Command};
+use chrono::{Duration, TimeZone, Utc};
+use clap::Parser;
+
+#[test]
+fn test_time_bounds_are_durations_ago_or_timestamps() {
+    let now = Utc.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap();
+
+    assert_eq!(time_bound_at("30m", now), Ok(now - Duration::minutes(30)));
+    assert_eq!(time_bound_at("2h", now), Ok(now - Duration::hours(2)));
+    assert_eq!(time_bound_at("7d", now), Ok(now - Duration::days(7)));
+    assert_eq!(time_bound_at("1w", now), Ok(now - Duration::weeks(1)));
+    assert_eq!(
+        time_bound_at("2026-03-09T08:30:00+02:00", now),
+        Ok(Utc.with_ymd_and_hms(2026, 3, 9, 6, 30, 0).unwrap())
+    );
+
+    for invalid in ["", "h", "10", "-5m", "5 minutes", "99999999999999999w"] {
+        assert!(time_bound_at(invalid, now).is_err(), "{invalid:?}");
+    }
+}
+
+#[test]
+fn test_alert_filters_parse() {
+    let cli = Cli::try_parse_from([
+        "aiva",
+        "alerts",
+        "--vm",
+        "web",
+        "--since",
+        "1h",
+        "--severity",
+        "high",
+        "--unresolved",
+    ])
+    .unwrap();
+    let Command::Alerts {
+        action: None,
+        vm,
+        since,
+        severity,
+        resolved,
+        unresolved,
+        ..
+    } = cli.command
+    else {
+        panic!("expected alerts");
+    };
+    assert_eq!(vm.as_deref(), Some("web"));
+    assert!(since.unwrap() < Utc::now());
+    assert_eq!(severity, Some(SeverityArg::High));
+    assert!(!resolved && unresolved);
+
+    assert!(Cli::try_parse_from(["aiva", "alerts", "--resolved", "--unresolved"]).is_err());
+}
+
+#[test]
+fn test_resolve_takes_an_alert_id() {
+    let id = uuid::Uuid::new_v4();
+    let cli = Cli::try_parse_from(["aiva", "alerts", "resolve", &id.to_string()]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Alerts {
+            action: Some(AlertsAction::Resolve { id: parsed }),
+            ..
+        } if parsed == id
+    ));
+
+    assert!(Cli::try_parse_from(["aiva", "alerts", "resolve", "not-an-id"]).is_err());
+}
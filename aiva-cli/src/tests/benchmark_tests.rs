@@ -0,0 +1,53 @@
+use crate::commands::benchmark::cold_start_config;
+use aiva_core::{
+    AivaError, PortMapping, Protocol, RuntimeInfo, SCHEMA_VERSION, VMInstance, VMState, VMTemplate,
+};
+
+fn vm(state: VMState) -> VMInstance {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.network.port_mappings = vec![PortMapping::new(8080, 3000, Protocol::Tcp)];
+    VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: "agent".to_string(),
+        state,
+        config,
+        runtime: RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: None,
+            tap_device: None,
+            mcp_pids: Some(Vec::new()),
+            paused: None,
+        },
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+#[test]
+fn test_cold_start_clones_leave_the_port_mappings_out() {
+    let vm = vm(VMState::Stopped);
+    let config = cold_start_config(&vm, None).unwrap();
+    assert!(config.network.port_mappings.is_empty());
+    assert_eq!(config.network.guest_ip, vm.config.network.guest_ip);
+
+    // The config on disk wins over the stored one
+    let mut on_disk = vm.config.clone();
+    on_disk.cpus = 4;
+    let config = cold_start_config(&vm, Some(on_disk)).unwrap();
+    assert_eq!(config.cpus, 4);
+    assert!(config.network.port_mappings.is_empty());
+}
+
+#[test]
+fn test_cold_start_needs_the_vm_stopped() {
+    for state in [VMState::Running, VMState::Paused] {
+        match cold_start_config(&vm(state), None) {
+            Err(AivaError::VMError { message, .. }) => {
+                assert!(message.contains("--skip-cold"), "{message}")
+            }
+            other => panic!("expected a VM error for {state:?}, got {other:?}"),
+        }
+    }
+}
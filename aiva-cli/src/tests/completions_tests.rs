@@ -0,0 +1,22 @@
+use crate::commands::completions::generate_completions;
+use clap::ValueEnum;
+use clap_complete::Shell;
+
+#[test]
+fn test_completions_generate_for_every_shell() {
+    for shell in Shell::value_variants() {
+        let mut out = Vec::new();
+        generate_completions(*shell, &mut out);
+
+        let script = String::from_utf8(out).unwrap();
+        assert!(
+            script.contains("aiva"),
+            "{shell} script does not mention aiva"
+        );
+        // Nested subcommands are part of the generated tree
+        assert!(
+            script.contains("validate"),
+            "{shell} script lacks config subcommands"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use crate::commands::config::{LiveOutcome, apply_to_running_vm, set_config_value};
+use aiva_core::{
+    AivaError, Platform, Result, ScaleCapabilities, ScaleStep, VMInstance, VMManager, VMMetadata,
+    VMMetrics, VMOrchestrator, VMTemplate,
+};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+/// Running-VM platform with a balloon that records what is pushed to it
+#[derive(Default)]
+struct LivePlatform {
+    commands: Mutex<Vec<String>>,
+    steps: Mutex<Vec<ScaleStep>>,
+    published: Mutex<Vec<VMMetadata>>,
+}
+
+#[async_trait]
+impl Platform for LivePlatform {
+    async fn create_vm(&self, instance: &VMInstance) -> Result<VMInstance> {
+        let mut created = instance.clone();
+        created.state = aiva_core::VMState::Stopped;
+        Ok(created)
+    }
+
+    async fn start_vm(&self, _instance: &VMInstance) -> Result<()> {
+        Ok(())
+    }
+
+    async fn stop_vm(&self, _instance: &VMInstance, _force: bool) -> Result<()> {
+        Ok(())
+    }
+
+    async fn delete_vm(&self, _instance: &VMInstance) -> Result<()> {
+        Ok(())
+    }
+
+    async fn get_vm_metrics(&self, _instance: &VMInstance) -> Result<VMMetrics> {
+        Err(AivaError::NotImplemented("metrics".to_string()))
+    }
+
+    async fn execute_command(&self, _instance: &VMInstance, command: &str) -> Result<String> {
+        self.commands.lock().unwrap().push(command.to_string());
+        Ok("dns=applied\n".to_string())
+    }
+
+    async fn check_requirements(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "live"
+    }
+
+    async fn scale_capabilities(&self, instance: &VMInstance) -> Result<ScaleCapabilities> {
+        Ok(ScaleCapabilities {
+            balloon_max_mb: Some(instance.config.memory_mb),
+            cpu_hotplug: false,
+        })
+    }
+
+    async fn apply_scale_step(&self, _instance: &VMInstance, step: &ScaleStep) -> Result<()> {
+        self.steps.lock().unwrap().push(*step);
+        Ok(())
+    }
+
+    async fn publish_metadata(&self, _instance: &VMInstance, metadata: &VMMetadata) -> Result<()> {
+        self.published.lock().unwrap().push(metadata.clone());
+        Ok(())
+    }
+}
+
+async fn running_vm(platform: Arc<LivePlatform>) -> Result<(VMOrchestrator, PathBuf)> {
+    let state_file =
+        std::env::temp_dir().join(format!("aiva-config-live-{}.json", uuid::Uuid::new_v4()));
+    let vm_manager = VMOrchestrator::new(platform).with_state_file(state_file.clone());
+    let vm = vm_manager
+        .create_vm(
+            "agent".to_string(),
+            VMTemplate::python3_uv().generate_vm_config(None),
+        )
+        .await?;
+    vm_manager.start_vm(&vm.id).await?;
+    Ok((vm_manager, state_file))
+}
+
+#[tokio::test]
+async fn test_running_vm_needs_restart_without_apply_now() -> Result<()> {
+    let platform = Arc::new(LivePlatform::default());
+    let (vm_manager, state_file) = running_vm(platform.clone()).await?;
+    let mut config = vm_manager.get_vm_by_name("agent").await?.unwrap().config;
+
+    set_config_value(&mut config, "cpus", "8")?;
+    assert_eq!(
+        apply_to_running_vm(&vm_manager, "agent", "cpus", &config, true).await?,
+        LiveOutcome::RestartRequired {
+            live_capable: false
+        }
+    );
+
+    set_config_value(&mut config, "network.dns_search", "corp.example.com")?;
+    assert_eq!(
+        apply_to_running_vm(&vm_manager, "agent", "network.dns_search", &config, false).await?,
+        LiveOutcome::RestartRequired { live_capable: true }
+    );
+    assert!(platform.commands.lock().unwrap().is_empty());
+
+    assert_eq!(
+        apply_to_running_vm(&vm_manager, "missing", "cpus", &config, true).await?,
+        LiveOutcome::NotRunning
+    );
+
+    let _ = std::fs::remove_file(state_file);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_now_pushes_live_keys() -> Result<()> {
+    let platform = Arc::new(LivePlatform::default());
+    let (vm_manager, state_file) = running_vm(platform.clone()).await?;
+    let mut config = vm_manager.get_vm_by_name("agent").await?.unwrap().config;
+    let boot_memory = config.memory_mb;
+
+    set_config_value(&mut config, "network.dns_search", "corp.example.com")?;
+    let outcome =
+        apply_to_running_vm(&vm_manager, "agent", "network.dns_search", &config, true).await?;
+    assert!(matches!(outcome, LiveOutcome::Applied(_)));
+    let commands = platform.commands.lock().unwrap().clone();
+    assert_eq!(commands.len(), 1);
+    assert!(commands[0].contains("search corp.example.com"));
+
+    set_config_value(&mut config, "memory_mb", &(boot_memory / 2).to_string())?;
+    let outcome = apply_to_running_vm(&vm_manager, "agent", "memory_mb", &config, true).await?;
+    assert!(matches!(outcome, LiveOutcome::Applied(_)));
+    assert_eq!(
+        *platform.steps.lock().unwrap(),
+        vec![ScaleStep::Balloon {
+            memory_mb: boot_memory / 2,
+            balloon_mib: boot_memory / 2
+        }]
+    );
+    assert_eq!(
+        vm_manager
+            .get_vm_by_name("agent")
+            .await?
+            .unwrap()
+            .config
+            .memory_mb,
+        boot_memory / 2
+    );
+
+    let _ = std::fs::remove_file(state_file);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_apply_now_publishes_metadata() -> Result<()> {
+    let platform = Arc::new(LivePlatform::default());
+    let (vm_manager, state_file) = running_vm(platform.clone()).await?;
+    let mut config = vm_manager.get_vm_by_name("agent").await?.unwrap().config;
+    platform.published.lock().unwrap().clear();
+
+    set_config_value(&mut config, "metadata.api_base", "https://example.com")?;
+    assert_eq!(
+        apply_to_running_vm(&vm_manager, "agent", "metadata.api_base", &config, false).await?,
+        LiveOutcome::RestartRequired { live_capable: true }
+    );
+    assert!(platform.published.lock().unwrap().is_empty());
+
+    let outcome =
+        apply_to_running_vm(&vm_manager, "agent", "metadata.api_base", &config, true).await?;
+    assert!(matches!(outcome, LiveOutcome::Applied(_)));
+    let published = platform.published.lock().unwrap().clone();
+    assert_eq!(published.len(), 1);
+    assert_eq!(published[0].metadata["api_base"], "https://example.com");
+
+    let _ = std::fs::remove_file(state_file);
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use crate::commands::config::set_config_value;
+use aiva_core::{AivaError, RuntimeInfo, VMTemplate};
+
+#[test]
+fn test_runtime_keys_are_read_only() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    for key in RuntimeInfo::KEYS {
+        match set_config_value(&mut config, key, "1234") {
+            Err(AivaError::ConfigError(message)) => assert!(message.contains("read-only")),
+            other => panic!("expected {key} to be rejected, got {other:?}"),
+        }
+    }
+
+    set_config_value(&mut config, "cpus", "4").unwrap();
+    assert_eq!(config.cpus, 4);
+}
+
+#[test]
+fn test_hostname_must_be_a_valid_label() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    set_config_value(&mut config, "network.hostname", "Web-1").unwrap();
+    assert_eq!(config.network.hostname.as_deref(), Some("web-1"));
+
+    for invalid in ["-web", "web_1", "web.example.com", &"a".repeat(64)] {
+        assert!(
+            matches!(
+                set_config_value(&mut config, "network.hostname", invalid),
+                Err(AivaError::ConfigError(_))
+            ),
+            "{invalid} should be rejected"
+        );
+    }
+    assert_eq!(config.network.hostname.as_deref(), Some("web-1"));
+
+    // Clearing it goes back to the VM name
+    set_config_value(&mut config, "network.hostname", "").unwrap();
+    assert_eq!(config.network.hostname, None);
+}
+
+#[test]
+fn test_labels_are_set_and_cleared_by_key() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    set_config_value(&mut config, "labels.team", "search").unwrap();
+    set_config_value(&mut config, "labels.env", "staging").unwrap();
+    assert_eq!(config.labels["team"], "search");
+    assert!(set_config_value(&mut config, "labels.bad key", "x").is_err());
+    assert!(!config.labels.contains_key("bad key"));
+
+    set_config_value(&mut config, "labels.env", "").unwrap();
+    assert_eq!(config.labels.keys().collect::<Vec<_>>(), ["team"]);
+}
+
+#[test]
+fn test_metadata_is_set_and_cleared_by_key() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    set_config_value(&mut config, "metadata.api_base", "https://example.com").unwrap();
+    set_config_value(&mut config, "metadata.region", "eu").unwrap();
+    assert_eq!(config.metadata["api_base"], "https://example.com");
+    assert!(set_config_value(&mut config, "metadata.a/b", "x").is_err());
+    assert!(!config.metadata.contains_key("a/b"));
+
+    set_config_value(&mut config, "metadata.region", "").unwrap();
+    assert_eq!(config.metadata.keys().collect::<Vec<_>>(), ["api_base"]);
+}
+
+#[tokio::test]
+async fn test_import_needs_a_known_template_and_policy() {
+    use crate::commands::config::validate_definition;
+    use aiva_core::VMDefinition;
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let mut definition = VMDefinition::new(&config, Some("python3-uv".to_string()));
+    definition.security_policy = Some("restricted".to_string());
+    definition
+        .labels
+        .insert("team".to_string(), "search".to_string());
+
+    let (imported, template) = validate_definition(definition.clone()).await.unwrap();
+    assert_eq!(template.unwrap().name, "python3-uv");
+    assert_eq!(imported.security_policy.as_deref(), Some("restricted"));
+    assert_eq!(imported.labels["team"], "search");
+
+    let mut unknown_template = definition.clone();
+    unknown_template.template = Some("cobol-85".to_string());
+    assert!(validate_definition(unknown_template).await.is_err());
+
+    let mut unknown_policy = definition;
+    unknown_policy.security_policy = Some("no-such-policy".to_string());
+    assert!(validate_definition(unknown_policy).await.is_err());
+}
+
+#[test]
+fn test_boot_args_replace_or_extend_the_default() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    set_config_value(&mut config, "extra_boot_args", "quiet  nomodeset").unwrap();
+    assert_eq!(config.extra_boot_args, ["quiet", "nomodeset"]);
+    assert_eq!(
+        config.kernel_command_line("console=ttyS0"),
+        "console=ttyS0 quiet nomodeset"
+    );
+
+    set_config_value(&mut config, "boot_args", "console=ttyS0 init=/bin/sh").unwrap();
+    assert_eq!(
+        config.kernel_command_line("console=ttyS0"),
+        "console=ttyS0 init=/bin/sh"
+    );
+
+    let long = "x".repeat(aiva_core::MAX_BOOT_ARGS_LEN + 1);
+    assert!(matches!(
+        set_config_value(&mut config, "boot_args", &long),
+        Err(AivaError::ConfigError(_))
+    ));
+    assert_eq!(
+        config.boot_args.as_deref(),
+        Some("console=ttyS0 init=/bin/sh")
+    );
+
+    set_config_value(&mut config, "boot_args", "").unwrap();
+    assert_eq!(config.boot_args, None);
+}
+
+#[test]
+fn test_unknown_cpu_template_is_rejected() {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.cpu_template = Some("T2".to_string());
+
+    let err = set_config_value(&mut config, "cpu_template", "Z9").unwrap_err();
+    assert!(matches!(err, AivaError::ConfigError(_)), "{err:?}");
+    assert!(err.to_string().contains("Z9"), "{err}");
+    assert_eq!(config.cpu_template.as_deref(), Some("T2"));
+
+    set_config_value(&mut config, "cpu_template", "").unwrap();
+    assert_eq!(config.cpu_template, None);
+}
@@ -0,0 +1,52 @@
+use crate::Cli;
+use crate::commands::data::{VolumeFormatArg, volume_config};
+use crate::commands::{Command, DataOperation};
+use aiva_storage::VolumeFormat;
+use clap::Parser;
+
+#[test]
+fn test_volumes_are_sparse_unless_preallocated() {
+    let cli = Cli::try_parse_from(["aiva", "data", "create", "models", "--size", "2GB"]).unwrap();
+    let Command::Data {
+        operation:
+            DataOperation::Create {
+                name,
+                size,
+                volume_format,
+                preallocate,
+                shared,
+                ..
+            },
+    } = cli.command
+    else {
+        panic!("expected data create");
+    };
+    assert!(!preallocate);
+
+    let config = volume_config(&name, &size, volume_format, preallocate, shared).unwrap();
+    assert!(config.sparse);
+    assert_eq!(config.size_mb, 2048);
+    assert_eq!(config.format, VolumeFormat::Ext4);
+
+    let config = volume_config("scratch", "512MB", VolumeFormatArg::Raw, true, false).unwrap();
+    assert!(!config.sparse);
+    assert_eq!(config.format, VolumeFormat::Raw);
+}
+
+#[test]
+fn test_sparse_and_preallocate_conflict() {
+    let result = Cli::try_parse_from([
+        "aiva",
+        "data",
+        "create",
+        "models",
+        "--size",
+        "1GB",
+        "--sparse",
+        "--preallocate",
+    ]);
+    assert!(result.is_err());
+
+    assert!(volume_config("models", "lots", VolumeFormatArg::Raw, true, false).is_err());
+    assert!(volume_config("models", "0MB", VolumeFormatArg::Raw, true, false).is_err());
+}
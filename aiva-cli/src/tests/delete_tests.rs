@@ -0,0 +1,106 @@
+use crate::Cli;
+use crate::commands::delete::{purge_vm_volumes, retain_vm_data};
+use aiva_core::{RuntimeInfo, SCHEMA_VERSION, VMInstance, VMState, VMTemplate};
+use aiva_storage::{VolumeConfig, VolumeFormat, VolumeManager};
+use clap::Parser;
+use std::path::PathBuf;
+
+fn deleted_vm(name: &str) -> VMInstance {
+    VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: name.to_string(),
+        state: VMState::Stopped,
+        config: VMTemplate::python3_uv().generate_vm_config(None),
+        runtime: RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: None,
+            tap_device: None,
+            mcp_pids: None,
+            paused: None,
+        },
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+fn scratch_volume(name: &str) -> VolumeConfig {
+    VolumeConfig {
+        name: name.to_string(),
+        size_mb: 1,
+        format: VolumeFormat::Raw,
+        sparse: false,
+        shared: false,
+        read_only: false,
+    }
+}
+
+fn scratch_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("aiva-delete-{}", uuid::Uuid::new_v4()))
+}
+
+#[tokio::test]
+async fn test_keep_data_leaves_volumes_and_data_dir_in_place() {
+    let dir = scratch_dir();
+    let volumes = VolumeManager::new(dir.join("data")).unwrap();
+    volumes.init().await.unwrap();
+    let vm = deleted_vm("keeper");
+    let vm_dir = dir.join("data").join("vms").join(&vm.name);
+    std::fs::create_dir_all(vm_dir.join("config")).unwrap();
+
+    let volume = volumes.create_volume(scratch_volume("work")).await.unwrap();
+    volumes
+        .attach_volume(&volume.id, &vm.id.to_string())
+        .await
+        .unwrap();
+
+    let retained = retain_vm_data(&volumes, &vm, &vm_dir).await.unwrap();
+
+    assert_eq!(retained.data_dir.as_deref(), Some(vm_dir.as_path()));
+    assert_eq!(retained.volumes.len(), 1);
+    assert_eq!(retained.volumes[0].path, volume.path);
+    assert!(volume.path.exists());
+    assert!(vm_dir.exists());
+
+    // Detached, so a re-created VM can attach it again
+    let kept = volumes.get_volume(&volume.id).await.unwrap();
+    assert!(kept.attached_to.is_none());
+    volumes.attach_volume(&volume.id, "new-vm").await.unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_purge_deletes_owned_volumes() {
+    let dir = scratch_dir();
+    let volumes = VolumeManager::new(dir.clone()).unwrap();
+    volumes.init().await.unwrap();
+    let vm = deleted_vm("purged");
+
+    let by_id = volumes.create_volume(scratch_volume("work")).await.unwrap();
+    let by_name = volumes
+        .create_volume(scratch_volume("cache"))
+        .await
+        .unwrap();
+    volumes
+        .attach_volume(&by_id.id, &vm.id.to_string())
+        .await
+        .unwrap();
+    volumes.attach_volume(&by_name.id, &vm.name).await.unwrap();
+
+    purge_vm_volumes(&volumes, &vm).await.unwrap();
+
+    for volume in [by_id, by_name] {
+        assert!(!volume.path.exists());
+        assert!(volumes.get_volume(&volume.id).await.is_err());
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_keep_data_and_purge_are_exclusive() {
+    assert!(Cli::try_parse_from(["aiva", "delete", "vm", "--keep-data"]).is_ok());
+    assert!(Cli::try_parse_from(["aiva", "delete", "vm", "--keep-data", "--purge"]).is_err());
+}
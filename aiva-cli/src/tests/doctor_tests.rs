@@ -0,0 +1,66 @@
+use crate::Cli;
+use crate::commands::Command;
+use crate::commands::doctor::{expected_data_dirs, recreate_data_dirs};
+use aiva_core::{FixStatus, RuntimeInfo, SCHEMA_VERSION, VMInstance, VMState, VMTemplate};
+use clap::Parser;
+
+fn vm(name: &str) -> VMInstance {
+    VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: name.to_string(),
+        state: VMState::Running,
+        config: VMTemplate::python3_uv().generate_vm_config(None),
+        runtime: RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: None,
+            tap_device: None,
+            mcp_pids: None,
+            paused: None,
+        },
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+#[test]
+fn test_missing_data_directories_are_recreated() {
+    let data_dir = std::env::temp_dir().join(format!("aiva-doctor-{}", uuid::Uuid::new_v4()));
+    let web_data = data_dir.join("vms").join("web").join("data");
+    std::fs::create_dir_all(&web_data).unwrap();
+
+    let dirs = expected_data_dirs(&data_dir, &[vm("web")]);
+    assert_eq!(
+        dirs,
+        vec![
+            data_dir.join("images"),
+            web_data.clone(),
+            data_dir.join("vms").join("web").join("logs"),
+        ]
+    );
+
+    let remediations = recreate_data_dirs(&dirs);
+    assert_eq!(remediations.len(), 2);
+    assert!(remediations.iter().all(|r| r.status == FixStatus::Fixed));
+    assert!(dirs.iter().all(|dir| dir.is_dir()));
+    let undo = remediations[0].undo.as_deref().unwrap();
+    assert_eq!(undo, format!("rmdir {}", data_dir.join("images").display()));
+
+    // Nothing left to do the second time
+    assert!(recreate_data_dirs(&dirs).is_empty());
+    let _ = std::fs::remove_dir_all(data_dir);
+}
+
+#[test]
+fn test_grant_kvm_requires_fix() {
+    let cli = Cli::try_parse_from(["aiva", "doctor", "--fix", "--grant-kvm"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Doctor {
+            fix: true,
+            grant_kvm: true
+        }
+    ));
+    assert!(Cli::try_parse_from(["aiva", "doctor", "--grant-kvm"]).is_err());
+}
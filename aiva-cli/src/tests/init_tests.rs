@@ -0,0 +1,109 @@
+use crate::Cli;
+use crate::commands::Command;
+use crate::commands::init::{PreviousInit, clear_previous_init};
+use aiva_core::AivaError;
+use clap::Parser;
+use std::path::{Path, PathBuf};
+
+fn scratch_vm_dir() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("aiva-init-{}", uuid::Uuid::new_v4()))
+        .join("vms")
+        .join("agent")
+}
+
+fn write_init_files(vm_dir: &Path, files: &[&str]) {
+    for file in files {
+        let path = vm_dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "{}").unwrap();
+    }
+}
+
+#[test]
+fn test_fresh_name_has_no_previous_init() {
+    let vm_dir = scratch_vm_dir();
+    assert_eq!(PreviousInit::detect(&vm_dir, false), PreviousInit::None);
+    assert!(PreviousInit::None.conflict("agent").is_none());
+}
+
+#[test]
+fn test_existing_init_is_refused_without_force() {
+    let vm_dir = scratch_vm_dir();
+    write_init_files(
+        &vm_dir,
+        &["config/config.json", "config/template.json", "setup.sh"],
+    );
+
+    let previous = PreviousInit::detect(&vm_dir, true);
+    assert_eq!(previous, PreviousInit::Complete);
+    let Some(AivaError::VMError { message, .. }) = previous.conflict("agent") else {
+        panic!("a complete init must conflict");
+    };
+    assert!(message.contains("--force"));
+
+    std::fs::remove_dir_all(vm_dir.parent().unwrap().parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_partial_init_reports_what_is_missing() {
+    let vm_dir = scratch_vm_dir();
+    // Config written, but the template and VM record never were
+    write_init_files(&vm_dir, &["config/config.json"]);
+
+    let previous = PreviousInit::detect(&vm_dir, false);
+    assert_eq!(
+        previous,
+        PreviousInit::Partial {
+            found: vec!["config/config.json".to_string()],
+            missing: vec![
+                "config/template.json".to_string(),
+                "setup.sh".to_string(),
+                "VM record".to_string(),
+            ],
+        }
+    );
+    let message = previous.conflict("agent").unwrap().to_string();
+    assert!(message.contains("did not finish"));
+    assert!(message.contains("config/template.json"));
+
+    // A VM record alone is a partial init too
+    let orphan = PreviousInit::detect(&scratch_vm_dir(), true);
+    assert!(matches!(orphan, PreviousInit::Partial { .. }));
+
+    std::fs::remove_dir_all(vm_dir.parent().unwrap().parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_force_clears_the_previous_init_but_keeps_data() {
+    let vm_dir = scratch_vm_dir();
+    write_init_files(
+        &vm_dir,
+        &["config/config.json", "config/template.json", "setup.sh"],
+    );
+    write_init_files(&vm_dir, &["data/state.db", "logs/vm.log"]);
+
+    clear_previous_init(&vm_dir).unwrap();
+
+    assert_eq!(PreviousInit::detect(&vm_dir, false), PreviousInit::None);
+    assert!(vm_dir.join("data/state.db").exists());
+    assert!(vm_dir.join("logs/vm.log").exists());
+    // Clearing twice is harmless
+    clear_previous_init(&vm_dir).unwrap();
+
+    std::fs::remove_dir_all(vm_dir.parent().unwrap().parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_force_flags_parse() {
+    let cli = Cli::try_parse_from(["aiva", "init", "agent", "-t", "python3-uv", "--force", "-y"])
+        .unwrap();
+    let Command::Init { force, yes, .. } = cli.command else {
+        panic!("expected init");
+    };
+    assert!(force && yes);
+
+    // Skipping the confirmation only makes sense when forcing
+    assert!(Cli::try_parse_from(["aiva", "init", "agent", "--yes"]).is_err());
+    assert!(Cli::try_parse_from(["aiva", "init", "agent", "--force", "--dry-run"]).is_err());
+}
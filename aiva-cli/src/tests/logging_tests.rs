@@ -0,0 +1,35 @@
+use crate::Cli;
+use crate::logging::{base_level, log_filter};
+use clap::Parser;
+
+#[test]
+fn test_verbose_and_quiet_conflict() {
+    assert!(Cli::try_parse_from(["aiva", "--verbose", "--quiet", "status"]).is_err());
+    assert!(Cli::try_parse_from(["aiva", "status", "-q"]).is_ok());
+
+    assert_eq!(base_level(false, false), "info");
+    assert_eq!(base_level(true, false), "debug");
+    assert_eq!(base_level(false, true), "error");
+}
+
+#[test]
+fn test_log_spec_adds_module_directives() {
+    let cli = Cli::try_parse_from([
+        "aiva",
+        "--log",
+        "aiva-platform=debug,aiva-network=trace",
+        "-q",
+        "status",
+    ])
+    .unwrap();
+    let filter = log_filter(cli.verbose, cli.quiet, cli.log.as_deref()).unwrap();
+
+    let mut directives: Vec<String> = filter.to_string().split(',').map(String::from).collect();
+    directives.sort();
+    assert_eq!(
+        directives,
+        ["aiva_network=trace", "aiva_platform=debug", "error"]
+    );
+
+    assert!(log_filter(false, false, Some("aiva-core=loud")).is_err());
+}
@@ -0,0 +1,37 @@
+use crate::Cli;
+use crate::commands::Command;
+use crate::commands::logs::parse_tail;
+use clap::Parser;
+
+fn tail_arg(args: &[&str]) -> Result<Option<usize>, clap::Error> {
+    let cli = Cli::try_parse_from(args)?;
+    let Command::Logs { tail, .. } = cli.command else {
+        panic!("expected logs");
+    };
+    Ok(tail)
+}
+
+#[test]
+fn test_tail_accepts_any_line_count() {
+    assert_eq!(tail_arg(&["aiva", "logs", "web"]).unwrap(), None);
+    assert_eq!(
+        tail_arg(&["aiva", "logs", "web", "--tail", "0"]).unwrap(),
+        Some(0)
+    );
+    // Huge counts are fine: the tail is read backward in bounded chunks
+    let huge = usize::MAX.to_string();
+    assert_eq!(
+        tail_arg(&["aiva", "logs", "web", "-t", &huge]).unwrap(),
+        Some(usize::MAX)
+    );
+}
+
+#[test]
+fn test_negative_tail_is_rejected_with_a_clear_error() {
+    let err = tail_arg(&["aiva", "logs", "web", "--tail", "-5"]).unwrap_err();
+    assert!(err.to_string().contains("is negative"), "{err}");
+
+    assert!(parse_tail("-0").is_err());
+    assert!(parse_tail("ten").unwrap_err().contains("not a number"));
+    assert_eq!(parse_tail("10"), Ok(10));
+}
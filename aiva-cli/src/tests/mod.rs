@@ -0,0 +1,28 @@
+#[cfg(test)]
+mod alerts_tests;
+#[cfg(test)]
+mod benchmark_tests;
+#[cfg(test)]
+mod completions_tests;
+#[cfg(test)]
+mod config_live_tests;
+#[cfg(test)]
+mod config_tests;
+#[cfg(test)]
+mod data_tests;
+#[cfg(test)]
+mod delete_tests;
+#[cfg(test)]
+mod doctor_tests;
+#[cfg(test)]
+mod init_tests;
+#[cfg(test)]
+mod logging_tests;
+#[cfg(test)]
+mod logs_tests;
+#[cfg(test)]
+mod output_tests;
+#[cfg(test)]
+mod run_tests;
+#[cfg(test)]
+mod start_tests;
@@ -0,0 +1,95 @@
+use crate::Cli;
+use crate::commands::status::VMStatus;
+use crate::output::{OutputFormat, OutputFormatter, color_enabled, write_json_file};
+use aiva_core::{RuntimeInfo, SCHEMA_VERSION, VMInstance, VMState, VMTemplate};
+use clap::Parser;
+
+fn sample_vm(name: &str, state: VMState) -> VMInstance {
+    VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: name.to_string(),
+        state,
+        config: VMTemplate::python3_uv().generate_vm_config(None),
+        runtime: RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: None,
+            tap_device: None,
+            mcp_pids: Some(Vec::new()),
+            paused: None,
+        },
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+#[test]
+fn test_color_only_on_a_terminal_without_opt_out() {
+    assert!(color_enabled(false, None, true, OutputFormat::Table));
+
+    // Piped or redirected stdout
+    assert!(!color_enabled(false, None, false, OutputFormat::Table));
+    // --no-color
+    assert!(!color_enabled(true, None, true, OutputFormat::Table));
+    // NO_COLOR is honoured unless it is empty
+    assert!(!color_enabled(false, Some("1"), true, OutputFormat::Table));
+    assert!(color_enabled(false, Some(""), true, OutputFormat::Table));
+    // Machine-readable formats never carry escape codes
+    assert!(!color_enabled(false, None, true, OutputFormat::Json));
+    assert!(!color_enabled(false, None, true, OutputFormat::Yaml));
+}
+
+#[test]
+fn test_no_color_is_a_global_flag() {
+    let cli = Cli::try_parse_from(["aiva", "status", "--no-color"]).unwrap();
+    assert!(cli.no_color);
+    let cli = Cli::try_parse_from(["aiva", "status"]).unwrap();
+    assert!(!cli.no_color);
+}
+
+#[test]
+fn test_output_file_matches_status_json() {
+    let vms = [
+        sample_vm("alpha", VMState::Running),
+        sample_vm("beta", VMState::Stopped),
+    ];
+    let rows = || vms.iter().cloned().map(VMStatus::from).collect::<Vec<_>>();
+
+    let stdout = OutputFormat::Json.format_table(rows());
+    let dir = std::env::temp_dir().join(format!("aiva-output-{}", uuid::Uuid::new_v4()));
+    let path = dir.join("nested").join("status.json");
+    write_json_file(&path, &rows()).unwrap();
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let printed: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(written, printed);
+    assert_eq!(written[0]["name"], "alpha");
+
+    // Only the result is left behind, no temporary file
+    let entries: Vec<_> = std::fs::read_dir(path.parent().unwrap()).unwrap().collect();
+    assert_eq!(entries.len(), 1);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_output_file_has_no_color_codes() {
+    let dir = std::env::temp_dir().join(format!("aiva-output-{}", uuid::Uuid::new_v4()));
+    let path = dir.join("colored.json");
+    write_json_file(&path, &vec!["\u{1b}[32mRunning\u{1b}[0m"]).unwrap();
+
+    let written: Vec<String> =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(written, ["Running"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_output_file_is_a_global_flag() {
+    let cli = Cli::try_parse_from(["aiva", "status", "--output-file", "/tmp/status.json"]).unwrap();
+    assert_eq!(
+        cli.output_file.as_deref(),
+        Some(std::path::Path::new("/tmp/status.json"))
+    );
+}
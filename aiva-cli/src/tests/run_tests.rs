@@ -0,0 +1,80 @@
+use crate::Cli;
+use crate::commands::Command;
+use crate::commands::run::{attached_log_paths, launch_result};
+use aiva_core::{ExecContext, VMTemplate, server_log_path};
+use aiva_platform::log_shipping::build_tail_command;
+use clap::Parser;
+
+#[test]
+fn test_args_after_double_dash_reach_the_remote_command() {
+    let cli = Cli::try_parse_from([
+        "aiva",
+        "run",
+        "vm",
+        "server",
+        "--",
+        "--port",
+        "9000",
+        "--verbose",
+    ])
+    .unwrap();
+    let Command::Run {
+        name,
+        command,
+        args,
+        port,
+        ..
+    } = cli.command
+    else {
+        panic!("expected the run command");
+    };
+
+    assert_eq!(name, "vm");
+    assert_eq!(command, "server");
+    assert_eq!(args, ["--port", "9000", "--verbose"]);
+    // Flags after `--` belong to the command, not to `aiva run`
+    assert_eq!(port, None);
+
+    let plan = VMTemplate::python3_uv()
+        .plan_run(&command, &args, "sse", port, &ExecContext::default())
+        .unwrap();
+    assert_eq!(
+        plan.command,
+        "cd '/opt/mcp' && uv run server --port 9000 --verbose"
+    );
+    assert_eq!(plan.port, Some(9000));
+}
+
+#[test]
+fn test_attach_logs_follows_the_log_captured_at_launch() {
+    let cli = Cli::try_parse_from(["aiva", "run", "web", "server", "--attach-logs"]).unwrap();
+    let Command::Run {
+        name,
+        command,
+        attach_logs,
+        ..
+    } = cli.command
+    else {
+        panic!("expected the run command");
+    };
+    assert!(attach_logs);
+
+    let template = VMTemplate::python3_uv();
+    let network = template.generate_vm_config(None).network;
+    let plan = template
+        .plan_run(&command, &[], "sse", None, &ExecContext::default())
+        .unwrap();
+    let result = launch_result(&name, &plan, &network, "MCP server started\nPID: 4242\n");
+
+    assert_eq!(result.pid, Some(4242));
+    assert_eq!(result.log_path, Some(server_log_path("web")));
+
+    let paths = attached_log_paths(&result);
+    assert_eq!(paths, ["/tmp/mcp-web.log"]);
+    // The follow reader tails exactly that file
+    assert!(
+        build_tail_command(&paths)
+            .unwrap()
+            .contains("/tmp/mcp-web.log")
+    );
+}
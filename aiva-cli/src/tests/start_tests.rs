@@ -0,0 +1,145 @@
+use crate::Cli;
+use crate::commands::Command;
+use crate::commands::start::{StartOptions, resolve_vm_config};
+use aiva_core::{CacheStrategy, Config, ConfigLayers, ResourceProfile, VMTemplate};
+use clap::Parser;
+
+fn start_options(args: &[&str]) -> StartOptions {
+    let cli = Cli::try_parse_from(["aiva", "start", "vm"].iter().chain(args)).unwrap();
+    let Command::Start {
+        cpus,
+        memory,
+        disk,
+        profile,
+        port,
+        allow_unsafe_cache,
+        wait,
+        dry_run,
+        ..
+    } = cli.command
+    else {
+        panic!("expected the start command");
+    };
+
+    StartOptions {
+        cpus,
+        memory,
+        disk,
+        profile,
+        ports: port,
+        allow_unsafe_cache,
+        wait,
+        dry_run,
+    }
+}
+
+#[test]
+fn test_untrusted_profile_assigns_isolated_policy_and_caps() {
+    let mut vm_config = VMTemplate::python3_uv().generate_vm_config(None);
+    vm_config.storage.cache_strategy = CacheStrategy::Unsafe;
+
+    resolve_vm_config(
+        &mut vm_config,
+        &Config::default(),
+        &start_options(&["--profile", "untrusted"]),
+    )
+    .unwrap();
+
+    assert_eq!(vm_config.security_policy.as_deref(), Some("isolated"));
+    assert_eq!(vm_config.cpus, 1);
+    assert_eq!(vm_config.memory_mb, 1024);
+    assert_eq!(vm_config.disk_gb, 10);
+    assert!(matches!(
+        vm_config.storage.cache_strategy,
+        CacheStrategy::Writeback
+    ));
+}
+
+#[test]
+fn test_flags_and_configured_profiles_take_precedence() {
+    let mut config = Config::default();
+    let mut untrusted = Config::resource_profiles()["untrusted"].clone();
+    untrusted.memory = "2GB".to_string();
+    config.profiles.insert("untrusted".to_string(), untrusted);
+    config.profiles.insert(
+        "ci".to_string(),
+        ResourceProfile {
+            security_policy: Some("restricted".to_string()),
+            ..Config::resource_profiles()["minimal"].clone()
+        },
+    );
+
+    let mut vm_config = VMTemplate::python3_uv().generate_vm_config(None);
+    resolve_vm_config(
+        &mut vm_config,
+        &config,
+        &start_options(&["--profile", "untrusted", "--cpus", "2"]),
+    )
+    .unwrap();
+    assert_eq!(vm_config.cpus, 2);
+    assert_eq!(vm_config.memory_mb, 2048);
+    assert_eq!(vm_config.security_policy.as_deref(), Some("isolated"));
+
+    let mut vm_config = VMTemplate::python3_uv().generate_vm_config(None);
+    resolve_vm_config(
+        &mut vm_config,
+        &config,
+        &start_options(&["--profile", "ci"]),
+    )
+    .unwrap();
+    assert_eq!(vm_config.security_policy.as_deref(), Some("restricted"));
+
+    let error = resolve_vm_config(
+        &mut vm_config,
+        &config,
+        &start_options(&["--profile", "missing"]),
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("ci, dev, minimal"));
+}
+
+#[test]
+fn test_start_all_takes_no_vm_name() {
+    let cli = Cli::try_parse_from(["aiva", "start", "--all"]).unwrap();
+    assert!(matches!(
+        cli.command,
+        Command::Start {
+            all: true,
+            name: None,
+            ..
+        }
+    ));
+
+    assert!(Cli::try_parse_from(["aiva", "start"]).is_err());
+    assert!(Cli::try_parse_from(["aiva", "start", "vm", "--all"]).is_err());
+    assert!(Cli::try_parse_from(["aiva", "start", "--all", "--cpus", "2"]).is_err());
+    assert!(Cli::try_parse_from(["aiva", "stop", "--all", "--force"]).is_ok());
+    assert!(Cli::try_parse_from(["aiva", "stop"]).is_err());
+}
+
+#[test]
+fn test_concurrency_flag_overrides_config() {
+    let layers = || {
+        ConfigLayers::new().unwrap().with_file(None).with_env([
+            ("AIVA_CONCURRENCY".to_string(), "8".to_string()),
+            (
+                "AIVA_MONITORING__METRICS_CONCURRENCY".to_string(),
+                "2".to_string(),
+            ),
+        ])
+    };
+
+    let cli = Cli::try_parse_from(["aiva", "--concurrency", "3", "stop", "--all"]).unwrap();
+    let config = cli.config_layers(layers()).resolve().unwrap().config;
+    assert_eq!(config.concurrency, 3);
+    assert_eq!(config.metrics_concurrency(), 3);
+
+    let cli = Cli::try_parse_from(["aiva", "stop", "--all"]).unwrap();
+    let config = cli.config_layers(layers()).resolve().unwrap().config;
+    assert_eq!(config.concurrency, 8);
+    assert_eq!(config.metrics_concurrency(), 2);
+
+    let error = Cli::try_parse_from(["aiva", "--concurrency", "0", "start", "--all"]).unwrap_err();
+    assert!(error.to_string().contains("at least 1"));
+    assert!(Cli::try_parse_from(["aiva", "--concurrency", "many", "start", "--all"]).is_err());
+}
@@ -1,60 +1,57 @@
-use aiva_core::{AivaError, Result};
+use aiva_core::{AivaError, Config, PortMapping, Protocol, Result, VMOrchestrator};
+use aiva_security::SecurityPolicy;
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-pub fn parse_memory_size(memory: &str) -> Result<u64> {
-    let memory = memory.to_uppercase();
-    let (value, unit) = if memory.ends_with("GB") {
-        let value = memory
-            .trim_end_matches("GB")
-            .parse::<u64>()
-            .map_err(|_| AivaError::ConfigError("Invalid memory size".to_string()))?;
-        (value, 1024)
-    } else if memory.ends_with("MB") {
-        let value = memory
-            .trim_end_matches("MB")
-            .parse::<u64>()
-            .map_err(|_| AivaError::ConfigError("Invalid memory size".to_string()))?;
-        (value, 1)
-    } else {
-        return Err(AivaError::ConfigError(
-            "Memory size must end with MB or GB".to_string(),
-        ));
-    };
+pub use aiva_core::{parse_disk_size, parse_memory_size};
 
-    Ok(value * unit)
-}
+/// Parse `host:guest` or `ip:host:guest` (IPv6 addresses in brackets).
+/// Without an address the mapping binds to 127.0.0.1.
+pub fn parse_port_mapping(port: &str) -> Result<PortMapping> {
+    let mut parts = port.rsplitn(3, ':');
+    let (guest, host, ip) = (parts.next(), parts.next(), parts.next());
 
-pub fn parse_disk_size(disk: &str) -> Result<u64> {
-    let disk = disk.to_uppercase();
-    if disk.ends_with("GB") {
-        let value = disk
-            .trim_end_matches("GB")
-            .parse::<u64>()
-            .map_err(|_| AivaError::ConfigError("Invalid disk size".to_string()))?;
-        Ok(value)
-    } else {
-        Err(AivaError::ConfigError(
-            "Disk size must end with GB".to_string(),
-        ))
-    }
-}
-
-pub fn parse_port_mapping(port: &str) -> Result<(u16, u16)> {
-    let parts: Vec<&str> = port.split(':').collect();
-    if parts.len() != 2 {
+    let (Some(guest), Some(host)) = (guest, host) else {
         return Err(AivaError::ConfigError(
-            "Port mapping must be in format host:guest".to_string(),
+            "Port mapping must be in format [ip:]host:guest".to_string(),
         ));
-    }
+    };
 
-    let host_port = parts[0]
+    let host_port = host
         .parse::<u16>()
         .map_err(|_| AivaError::ConfigError("Invalid host port".to_string()))?;
-    let guest_port = parts[1]
+    let guest_port = guest
         .parse::<u16>()
         .map_err(|_| AivaError::ConfigError("Invalid guest port".to_string()))?;
 
-    Ok((host_port, guest_port))
+    let mut mapping = PortMapping::new(host_port, guest_port, Protocol::Tcp);
+    if let Some(ip) = ip {
+        let ip = ip.trim_start_matches('[').trim_end_matches(']');
+        mapping.host_ip = Some(
+            ip.parse::<IpAddr>()
+                .map_err(|_| AivaError::ConfigError(format!("Invalid host address: {ip}")))?,
+        );
+    }
+
+    Ok(mapping)
+}
+
+/// `--concurrency` value: a number of VMs, at least 1
+pub fn parse_concurrency(value: &str) -> std::result::Result<usize, String> {
+    let limit = value
+        .parse::<usize>()
+        .map_err(|_| format!("'{value}' is not a number of VMs"))?;
+    aiva_core::validate_concurrency(limit).map_err(|e| e.to_string())
+}
+
+/// VM manager for operations over the whole fleet, acting on at most
+/// `config.concurrency` VMs at a time
+pub async fn fleet_manager(config: &Config) -> Result<Arc<VMOrchestrator>> {
+    let platform = aiva_platform::get_current_platform()?;
+    let vm_manager = VMOrchestrator::new(platform).with_concurrency(config.concurrency);
+    vm_manager.load_state().await?;
+    Ok(Arc::new(vm_manager))
 }
 
 pub fn get_data_dir() -> Result<PathBuf> {
@@ -72,3 +69,14 @@ pub fn get_vm_dir(vm_name: &str) -> Result<PathBuf> {
     let data_dir = get_data_dir()?;
     Ok(data_dir.join("vms").join(vm_name))
 }
+
+pub fn get_policies_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| AivaError::ConfigError("Cannot determine home directory".to_string()))?;
+    Ok(home.join(".aiva").join("policies"))
+}
+
+/// Look up a policy by name, preferring user-defined policies over the presets
+pub async fn resolve_security_policy(name: &str) -> Result<SecurityPolicy> {
+    aiva_security::resolve_policy(name, &get_policies_dir()?).await
+}
@@ -0,0 +1,92 @@
+//! Routing alerts to the channels that should see them.
+//!
+//! An [`AlertSink`] delivers alerts somewhere outside the process, such as a
+//! pager or a mailbox. [`AlertRoutes`] decides which registered sinks see an
+//! alert from its type and severity, so a critical VM crash can page someone
+//! while low disk space only sends an email. Without a table every sink sees
+//! every alert.
+
+use crate::Result;
+use crate::monitoring::{Alert, AlertSeverity, AlertType};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// Sink name in an [`AlertRoute`] that stands for every registered sink
+pub const ALL_SINKS: &str = "*";
+
+/// Delivers alerts raised by the
+/// [`MonitoringService`](crate::MonitoringService)
+#[async_trait]
+pub trait AlertSink: Send + Sync {
+    async fn send(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Sends alerts of one type, or of every type, that are at least as severe
+/// as `min_severity` to `sinks`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertRoute {
+    /// Matches every type when unset
+    #[serde(default)]
+    pub alert_type: Option<AlertType>,
+    pub min_severity: AlertSeverity,
+    /// Names the sinks were registered under, or [`ALL_SINKS`]
+    pub sinks: Vec<String>,
+}
+
+impl AlertRoute {
+    pub fn matches(&self, alert: &Alert) -> bool {
+        self.alert_type.is_none_or(|t| t == alert.alert_type)
+            && alert.severity.is_at_least(self.min_severity)
+    }
+}
+
+/// Routing table consulted for every alert. An alert goes to the sinks of
+/// all routes it matches, each sink once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlertRoutes {
+    pub routes: Vec<AlertRoute>,
+}
+
+impl AlertRoutes {
+    pub fn new(routes: Vec<AlertRoute>) -> Self {
+        Self { routes }
+    }
+
+    /// Every alert to every sink
+    pub fn route_all() -> Self {
+        Self::new(vec![AlertRoute {
+            alert_type: None,
+            min_severity: AlertSeverity::Low,
+            sinks: vec![ALL_SINKS.to_string()],
+        }])
+    }
+
+    /// Which of the `registered` sink names receive `alert`, in
+    /// registration order
+    pub fn sinks_for<'a>(&self, alert: &Alert, registered: &[&'a str]) -> Vec<&'a str> {
+        let matched: Vec<&AlertRoute> = self
+            .routes
+            .iter()
+            .filter(|route| route.matches(alert))
+            .collect();
+
+        registered
+            .iter()
+            .copied()
+            .filter(|name| {
+                matched.iter().any(|route| {
+                    route
+                        .sinks
+                        .iter()
+                        .any(|sink| sink == ALL_SINKS || sink == name)
+                })
+            })
+            .collect()
+    }
+}
+
+impl Default for AlertRoutes {
+    fn default() -> Self {
+        Self::route_all()
+    }
+}
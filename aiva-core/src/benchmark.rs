@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Wall-clock timings of the phases that make up a cold boot
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BootTimings {
+    pub create: Duration,
+    pub start: Duration,
+    pub ready: Duration,
+}
+
+impl BootTimings {
+    pub fn total(&self) -> Duration {
+        self.create + self.start + self.ready
+    }
+}
+
+/// Times a single async phase
+pub async fn time_phase<F, T>(phase: F) -> (T, Duration)
+where
+    F: std::future::Future<Output = T>,
+{
+    let started = Instant::now();
+    let output = phase.await;
+    (output, started.elapsed())
+}
+
+/// Summary statistics over a set of latency samples, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub samples: usize,
+    pub min_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+    pub mean_ms: f64,
+}
+
+impl LatencyStats {
+    /// Returns `None` when there are no samples to summarize
+    pub fn from_samples(samples: &[Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut millis: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        millis.sort_by(|a, b| a.total_cmp(b));
+
+        Some(Self {
+            samples: millis.len(),
+            min_ms: millis[0],
+            p50_ms: percentile(&millis, 50.0),
+            p95_ms: percentile(&millis, 95.0),
+            max_ms: millis[millis.len() - 1],
+            mean_ms: millis.iter().sum::<f64>() / millis.len() as f64,
+        })
+    }
+}
+
+/// Nearest-rank percentile over an already sorted, non-empty slice
+pub fn percentile(sorted: &[f64], pct: f64) -> f64 {
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
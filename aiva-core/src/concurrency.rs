@@ -0,0 +1,52 @@
+//! Running one operation per VM with a bound on how many run at once.
+//!
+//! Bulk start and stop and batch metrics collection all fan out over the
+//! fleet. [`run_bounded`] spawns every task up front but lets only `limit`
+//! of them past a semaphore, so a large fleet does not start dozens of
+//! Firecracker processes or API calls at the same moment. The limit comes
+//! from [`Config::concurrency`](crate::Config::concurrency) or `--concurrency`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::{JoinError, JoinSet};
+
+/// Run `tasks` with at most `limit` of them at a time and collect their
+/// results by key. A task that panics gets `on_panic` of its error instead,
+/// without affecting the others.
+pub async fn run_bounded<K, T, F>(
+    limit: usize,
+    tasks: impl IntoIterator<Item = (K, F)>,
+    on_panic: impl Fn(JoinError) -> T,
+) -> HashMap<K, T>
+where
+    K: Eq + Hash,
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let permits = Arc::new(Semaphore::new(limit.max(1)));
+    let mut running = JoinSet::new();
+    let mut keys = HashMap::new();
+    for (key, task) in tasks {
+        let permits = permits.clone();
+        let handle = running.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            task.await
+        });
+        keys.insert(handle.id(), key);
+    }
+
+    let mut results = HashMap::with_capacity(keys.len());
+    while let Some(joined) = running.join_next_with_id().await {
+        let (id, result) = match joined {
+            Ok((id, result)) => (id, result),
+            Err(e) => (e.id(), on_panic(e)),
+        };
+        if let Some(key) = keys.remove(&id) {
+            results.insert(key, result);
+        }
+    }
+    results
+}
@@ -1,3 +1,4 @@
+use crate::types::{CacheStrategy, VMConfig};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -8,12 +9,37 @@ pub struct Config {
     pub defaults: DefaultConfig,
     pub platform: PlatformConfig,
     pub networking: NetworkingConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub monitoring: MonitoringConfig,
+    /// VMs bulk and monitoring operations act on at the same time
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default)]
+    pub firecracker: FirecrackerSource,
+    #[serde(default = "ArtifactSource::default_kernel")]
+    pub kernel: ArtifactSource,
+    #[serde(default = "ArtifactSource::default_rootfs")]
+    pub rootfs: ArtifactSource,
+    /// Never download Firecracker, the kernel or the rootfs; the setup
+    /// scripts then expect them to be staged already
+    #[serde(default)]
+    pub skip_downloads: bool,
+    /// Profiles for `aiva start --profile`, overriding built-ins of the same name
+    #[serde(default)]
+    pub profiles: HashMap<String, ResourceProfile>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DefaultConfig {
-    pub cpus: u32,
-    pub memory: String,
+    /// vCPUs of new VMs; unset scales with the host, see
+    /// [`HostCapacity`](crate::HostCapacity)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<u32>,
+    /// Memory of new VMs (e.g. `8GB`); unset scales with the host
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
     pub disk: String,
     pub cache_strategy: String,
 }
@@ -29,6 +55,58 @@ pub struct PlatformConfig {
 pub struct LinuxConfig {
     pub firecracker_binary: PathBuf,
     pub jailer_binary: PathBuf,
+    #[serde(default)]
+    pub jailer: JailerConfig,
+    /// How long a guest gets to power off after Ctrl+Alt+Del before
+    /// Firecracker is sent SIGTERM, then SIGKILL
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    10
+}
+
+/// How the jailer confines Firecracker
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JailerConfig {
+    /// User Firecracker runs as. Unset, it is the user running aiva, or
+    /// the one who ran `sudo` when aiva runs as root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    /// Group Firecracker runs as; unset, the primary group of its user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gid: Option<u32>,
+    /// Directory holding a workspace per VM. It must be writable and not
+    /// mounted `noexec`, since the jailer runs Firecracker from inside it.
+    #[serde(default = "default_chroot_base_dir")]
+    pub chroot_base_dir: PathBuf,
+    /// cgroup hierarchy the jailer places Firecracker in, 1 or 2
+    #[serde(default = "default_cgroup_version")]
+    pub cgroup_version: u8,
+    /// NUMA node Firecracker's CPUs and memory are pinned to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numa_node: Option<u32>,
+}
+
+impl Default for JailerConfig {
+    fn default() -> Self {
+        Self {
+            uid: None,
+            gid: None,
+            chroot_base_dir: default_chroot_base_dir(),
+            cgroup_version: default_cgroup_version(),
+            numa_node: None,
+        }
+    }
+}
+
+fn default_chroot_base_dir() -> PathBuf {
+    PathBuf::from("/tmp/aiva-jailer")
+}
+
+fn default_cgroup_version() -> u8 {
+    2
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,12 +114,44 @@ pub struct MacOSConfig {
     pub lima_instance: String,
     pub lima_cpus: u32,
     pub lima_memory: String,
+    /// Lima configuration for the host VM instead of `./lima.yml` or the
+    /// built-in one. `--lima-config` or `AIVA_LIMA_CONFIG` also set it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lima_config: Option<PathBuf>,
+    /// Extra attempts made when the Lima host cannot be reached yet
+    #[serde(default = "default_lima_exec_retries")]
+    pub exec_retries: u32,
+    /// Longest wait between two attempts, in milliseconds
+    #[serde(default = "default_lima_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_lima_exec_retries() -> u32 {
+    4
+}
+
+fn default_lima_max_backoff_ms() -> u64 {
+    5000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowsConfig {
     pub wsl_distro: String,
     pub nested_virtualization: bool,
+    /// Per-attempt timeout for commands run inside WSL
+    #[serde(default = "default_wsl_exec_timeout_secs")]
+    pub exec_timeout_secs: u64,
+    /// Extra attempts made when WSL itself fails to start a command
+    #[serde(default = "default_wsl_exec_retries")]
+    pub exec_retries: u32,
+}
+
+fn default_wsl_exec_timeout_secs() -> u64 {
+    300
+}
+
+fn default_wsl_exec_retries() -> u32 {
+    2
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,28 +161,227 @@ pub struct NetworkingConfig {
     pub dns_servers: Vec<String>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Treat every VM as a production workload (stricter validation)
+    pub production: bool,
+    /// Trusted image source prefixes checked by `aiva image audit`
+    #[serde(default)]
+    pub image_sources: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MonitoringConfig {
+    /// VMs whose metrics are collected at the same time, see
+    /// [`VMManager::batch_metrics`](crate::VMManager::batch_metrics).
+    /// [`Config::concurrency`] when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics_concurrency: Option<usize>,
+}
+
+/// Most VMs the CPU-based default lets act at the same time
+pub const MAX_DEFAULT_CONCURRENCY: usize = 16;
+
+/// Default for [`Config::concurrency`]: one VM per CPU, up to
+/// [`MAX_DEFAULT_CONCURRENCY`]
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, |cpus| cpus.get())
+        .min(MAX_DEFAULT_CONCURRENCY)
+}
+
+/// Check a concurrency limit, which must let at least one VM proceed
+pub fn validate_concurrency(limit: usize) -> crate::Result<usize> {
+    if limit == 0 {
+        return Err(crate::AivaError::ConfigError(
+            "concurrency must be at least 1".to_string(),
+        ));
+    }
+    Ok(limit)
+}
+
+/// Placeholder in download URLs replaced by the guest architecture
+pub const ARCH_PLACEHOLDER: &str = "{arch}";
+
+const DEFAULT_FIRECRACKER_VERSION: &str = "v1.12.1";
+const DEFAULT_FIRECRACKER_DOWNLOAD_BASE: &str =
+    "https://github.com/firecracker-microvm/firecracker/releases/download";
+const DEFAULT_KERNEL_URL: &str =
+    "https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/{arch}/kernels/vmlinux.bin";
+const DEFAULT_ROOTFS_URL: &str =
+    "https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/{arch}/rootfs/bionic.rootfs.ext4";
+
+/// Firecracker release installed by the setup scripts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FirecrackerSource {
+    pub version: String,
+    /// Base URL laid out like the GitHub releases page, so a mirror only has
+    /// to serve `<base>/<version>/firecracker-<version>-<arch>.tgz`
+    pub download_base: String,
+    /// Expected SHA-256 of the release tarball. When unset the checksum file
+    /// published next to the tarball is used instead.
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+impl FirecrackerSource {
+    /// Release tag, accepting versions written with or without the `v`
+    pub fn tag(&self) -> String {
+        let version = self.version.trim();
+        if version.starts_with('v') {
+            version.to_string()
+        } else {
+            format!("v{version}")
+        }
+    }
+
+    /// URL of the release tarball for `arch`
+    pub fn release_url(&self, arch: &str) -> String {
+        let tag = self.tag();
+        format!(
+            "{}/{tag}/firecracker-{tag}-{arch}.tgz",
+            self.download_base.trim_end_matches('/')
+        )
+    }
+}
+
+impl Default for FirecrackerSource {
+    fn default() -> Self {
+        Self {
+            version: DEFAULT_FIRECRACKER_VERSION.to_string(),
+            download_base: DEFAULT_FIRECRACKER_DOWNLOAD_BASE.to_string(),
+            sha256: None,
+        }
+    }
+}
+
+/// A guest kernel or rootfs image downloaded by the setup scripts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactSource {
+    /// Download URL; `{arch}` is replaced by the guest architecture
+    pub url: String,
+    /// Expected SHA-256 of the download, verified when set
+    #[serde(default)]
+    pub sha256: Option<String>,
+}
+
+impl ArtifactSource {
+    pub fn default_kernel() -> Self {
+        Self {
+            url: DEFAULT_KERNEL_URL.to_string(),
+            sha256: None,
+        }
+    }
+
+    pub fn default_rootfs() -> Self {
+        Self {
+            url: DEFAULT_ROOTFS_URL.to_string(),
+            sha256: None,
+        }
+    }
+
+    pub fn url_for(&self, arch: &str) -> String {
+        self.url.replace(ARCH_PLACEHOLDER, arch)
+    }
+}
+
+/// Resources and security settings selected together with
+/// `aiva start --profile`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceProfile {
     pub cpus: u32,
     pub memory: String,
     pub disk: String,
     pub description: String,
+    /// Security policy assigned to the VM, kept as configured when unset
+    #[serde(default)]
+    pub security_policy: Option<String>,
+    #[serde(default)]
+    pub cache_strategy: Option<CacheStrategy>,
 }
 
-impl Config {
-    pub fn load() -> crate::Result<Self> {
-        let config_path = Self::config_path()?;
+impl ResourceProfile {
+    fn new(cpus: u32, memory: &str, disk: &str, description: &str) -> Self {
+        Self {
+            cpus,
+            memory: memory.to_string(),
+            disk: disk.to_string(),
+            description: description.to_string(),
+            security_policy: None,
+            cache_strategy: None,
+        }
+    }
 
-        if config_path.exists() {
-            let content = std::fs::read_to_string(&config_path)?;
-            let config: Config = serde_yaml::from_str(&content)
-                .map_err(|e| crate::AivaError::ConfigError(e.to_string()))?;
-            Ok(config)
-        } else {
-            Ok(Self::default())
+    fn with_policy(self, policy: &str) -> Self {
+        Self {
+            security_policy: Some(policy.to_string()),
+            cache_strategy: Some(CacheStrategy::Writeback),
+            ..self
         }
     }
 
+    /// Resolve the profile into `config`
+    pub fn apply(&self, config: &mut VMConfig) -> crate::Result<()> {
+        config.cpus = self.cpus;
+        config.memory_mb = parse_memory_size(&self.memory)?;
+        config.disk_gb = parse_disk_size(&self.disk)?;
+        if let Some(policy) = &self.security_policy {
+            config.security_policy = Some(policy.clone());
+        }
+        if let Some(cache_strategy) = self.cache_strategy {
+            config.storage.cache_strategy = cache_strategy;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a memory size such as `512MB` or `8GB` into megabytes
+pub fn parse_memory_size(memory: &str) -> crate::Result<u64> {
+    let memory = memory.to_uppercase();
+    let (value, unit) = if memory.ends_with("GB") {
+        let value = memory
+            .trim_end_matches("GB")
+            .parse::<u64>()
+            .map_err(|_| crate::AivaError::ConfigError("Invalid memory size".to_string()))?;
+        (value, 1024)
+    } else if memory.ends_with("MB") {
+        let value = memory
+            .trim_end_matches("MB")
+            .parse::<u64>()
+            .map_err(|_| crate::AivaError::ConfigError("Invalid memory size".to_string()))?;
+        (value, 1)
+    } else {
+        return Err(crate::AivaError::ConfigError(
+            "Memory size must end with MB or GB".to_string(),
+        ));
+    };
+
+    Ok(value * unit)
+}
+
+/// Parse a disk size such as `50GB` into gigabytes
+pub fn parse_disk_size(disk: &str) -> crate::Result<u64> {
+    let disk = disk.to_uppercase();
+    if disk.ends_with("GB") {
+        let value = disk
+            .trim_end_matches("GB")
+            .parse::<u64>()
+            .map_err(|_| crate::AivaError::ConfigError("Invalid disk size".to_string()))?;
+        Ok(value)
+    } else {
+        Err(crate::AivaError::ConfigError(
+            "Disk size must end with GB".to_string(),
+        ))
+    }
+}
+
+impl Config {
+    /// Defaults overridden by the config file and `AIVA_*` environment
+    /// variables, see [`ConfigLayers`](crate::ConfigLayers)
+    pub fn load() -> crate::Result<Self> {
+        Ok(crate::ConfigLayers::new()?.resolve()?.config)
+    }
+
     pub fn save(&self) -> crate::Result<()> {
         let config_path = Self::config_path()?;
         let config_dir = config_path.parent().unwrap();
@@ -86,7 +395,14 @@ impl Config {
         Ok(())
     }
 
-    fn config_path() -> crate::Result<PathBuf> {
+    /// Limit of [`VMManager::batch_metrics`](crate::VMManager::batch_metrics)
+    pub fn metrics_concurrency(&self) -> usize {
+        self.monitoring
+            .metrics_concurrency
+            .unwrap_or(self.concurrency)
+    }
+
+    pub(crate) fn config_path() -> crate::Result<PathBuf> {
         let home = dirs::home_dir().ok_or_else(|| {
             crate::AivaError::ConfigError("Cannot determine home directory".to_string())
         })?;
@@ -98,36 +414,55 @@ impl Config {
 
         profiles.insert(
             "minimal".to_string(),
-            ResourceProfile {
-                cpus: 2,
-                memory: "4GB".to_string(),
-                disk: "20GB".to_string(),
-                description: "Lightweight MCP server".to_string(),
-            },
+            ResourceProfile::new(2, "4GB", "20GB", "Lightweight MCP server"),
         );
-
         profiles.insert(
             "standard".to_string(),
-            ResourceProfile {
-                cpus: 4,
-                memory: "8GB".to_string(),
-                disk: "50GB".to_string(),
-                description: "Standard AI agent".to_string(),
-            },
+            ResourceProfile::new(4, "8GB", "50GB", "Standard AI agent"),
         );
-
         profiles.insert(
             "performance".to_string(),
-            ResourceProfile {
-                cpus: 8,
-                memory: "16GB".to_string(),
-                disk: "100GB".to_string(),
-                description: "Large model inference".to_string(),
-            },
+            ResourceProfile::new(8, "16GB", "100GB", "Large model inference"),
+        );
+        profiles.insert(
+            "dev".to_string(),
+            ResourceProfile::new(4, "8GB", "50GB", "Local development with trusted code")
+                .with_policy("trusted"),
+        );
+        profiles.insert(
+            "prod".to_string(),
+            ResourceProfile::new(4, "8GB", "50GB", "Production MCP server")
+                .with_policy("restricted"),
+        );
+        profiles.insert(
+            "untrusted".to_string(),
+            ResourceProfile::new(1, "1GB", "10GB", "Untrusted code, isolated and capped")
+                .with_policy("isolated"),
         );
 
         profiles
     }
+
+    /// The profile `name`, looked up in the configured profiles first
+    pub fn profile(&self, name: &str) -> crate::Result<ResourceProfile> {
+        if let Some(profile) = self.profiles.get(name) {
+            return Ok(profile.clone());
+        }
+
+        let mut builtin = Self::resource_profiles();
+        builtin.remove(name).ok_or_else(|| {
+            let mut names: Vec<String> = builtin
+                .into_keys()
+                .chain(self.profiles.keys().cloned())
+                .collect();
+            names.sort();
+            names.dedup();
+            crate::AivaError::ConfigError(format!(
+                "Unknown profile '{name}'. Available: {}",
+                names.join(", ")
+            ))
+        })
+    }
 }
 
 impl Default for Config {
@@ -135,8 +470,8 @@ impl Default for Config {
         Self {
             version: "1.0".to_string(),
             defaults: DefaultConfig {
-                cpus: 4,
-                memory: "8GB".to_string(),
+                cpus: None,
+                memory: None,
                 disk: "50GB".to_string(),
                 cache_strategy: "writeback".to_string(),
             },
@@ -144,15 +479,22 @@ impl Default for Config {
                 linux: LinuxConfig {
                     firecracker_binary: PathBuf::from("/usr/bin/firecracker"),
                     jailer_binary: PathBuf::from("/usr/bin/jailer"),
+                    jailer: JailerConfig::default(),
+                    shutdown_grace_secs: default_shutdown_grace_secs(),
                 },
                 macos: MacOSConfig {
                     lima_instance: "aiva-host".to_string(),
                     lima_cpus: 8,
                     lima_memory: "16GB".to_string(),
+                    lima_config: None,
+                    exec_retries: default_lima_exec_retries(),
+                    max_backoff_ms: default_lima_max_backoff_ms(),
                 },
                 windows: WindowsConfig {
                     wsl_distro: "aiva-wsl".to_string(),
                     nested_virtualization: true,
+                    exec_timeout_secs: default_wsl_exec_timeout_secs(),
+                    exec_retries: default_wsl_exec_retries(),
                 },
             },
             networking: NetworkingConfig {
@@ -160,6 +502,14 @@ impl Default for Config {
                 subnet: "172.16.0.0/24".to_string(),
                 dns_servers: vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()],
             },
+            security: SecurityConfig::default(),
+            monitoring: MonitoringConfig::default(),
+            concurrency: default_concurrency(),
+            firecracker: FirecrackerSource::default(),
+            kernel: ArtifactSource::default_kernel(),
+            rootfs: ArtifactSource::default_rootfs(),
+            skip_downloads: false,
+            profiles: HashMap::new(),
         }
     }
 }
@@ -0,0 +1,285 @@
+//! Where each configuration value comes from.
+//!
+//! The effective [`Config`] is built from four layers, each overriding the
+//! ones before it:
+//!
+//! 1. built-in defaults
+//! 2. `~/.aiva/config.yaml`
+//! 3. `AIVA_*` environment variables, `__` separating nested keys, so
+//!    `AIVA_DEFAULTS__CPUS=4` sets `defaults.cpus`
+//! 4. command line flags
+//!
+//! Layers are merged key by key, so a file that only sets `defaults.cpus`
+//! keeps every other default. [`ResolvedConfig::sources`] records which
+//! layer each value was taken from.
+
+use crate::config::{Config, validate_concurrency};
+use crate::error::{AivaError, Result};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Prefix of the environment variables read as configuration
+pub const ENV_PREFIX: &str = "AIVA_";
+/// Separates nested keys in an environment variable name
+const ENV_KEY_SEPARATOR: &str = "__";
+/// Variables with the prefix that are read elsewhere, not as configuration
+const RESERVED_ENV_VARS: &[&str] = &["AIVA_LOG"];
+/// Older variables kept working under their original names
+const ENV_ALIASES: &[(&str, &str)] = &[("AIVA_LIMA_CONFIG", "platform.macos.lima_config")];
+
+/// The layer a configuration value was taken from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    /// Environment variable of that name
+    Env(String),
+    /// Command line flag of that name
+    Flag(String),
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Default => f.write_str("default"),
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Env(var) => write!(f, "${var}"),
+            Self::Flag(flag) => write!(f, "--{flag}"),
+        }
+    }
+}
+
+/// The effective configuration and the source of each of its values
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    pub config: Config,
+    /// Source of every value by dotted key, e.g. `defaults.cpus`
+    pub sources: BTreeMap<String, ConfigSource>,
+}
+
+/// Builder for the layers of [`Config`]
+#[derive(Debug, Clone)]
+pub struct ConfigLayers {
+    file: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    flags: Vec<(String, String, Value)>,
+}
+
+impl ConfigLayers {
+    /// The user's config file and the environment of this process
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            file: Some(Config::config_path()?),
+            env: std::env::vars().collect(),
+            flags: Vec::new(),
+        })
+    }
+
+    /// Read the file at `path` instead, or no file at all
+    pub fn with_file(mut self, path: Option<PathBuf>) -> Self {
+        self.file = path;
+        self
+    }
+
+    /// Use `vars` as the environment
+    pub fn with_env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().collect();
+        self
+    }
+
+    /// Set `key` to `value` from the command line flag `flag`
+    pub fn flag(mut self, flag: &str, key: &str, value: impl Into<Value>) -> Self {
+        self.flags
+            .push((flag.to_string(), key.to_string(), value.into()));
+        self
+    }
+
+    /// Merge the layers into a validated [`Config`]
+    pub fn resolve(self) -> Result<ResolvedConfig> {
+        let mut merged = serde_json::to_value(Config::default())?;
+        let mut sources = BTreeMap::new();
+        record_leaves(&merged, "", &ConfigSource::Default, &mut sources);
+
+        if let Some(path) = self.file.filter(|path| path.exists()) {
+            let content = std::fs::read_to_string(&path)?;
+            let file: Value = serde_yaml::from_str(&content)
+                .map_err(|e| AivaError::ConfigError(format!("{}: {e}", path.display())))?;
+            merge(
+                &mut merged,
+                file,
+                "",
+                &ConfigSource::File(path),
+                &mut sources,
+            );
+        }
+
+        let mut env = self.env;
+        env.sort();
+        for (var, raw) in env {
+            let Some(key) = env_key(&var) else {
+                continue;
+            };
+            // Only keys the configuration has, not every AIVA_ variable
+            let top_level = key.split('.').next().unwrap_or_default();
+            if merged.get(top_level).is_none() {
+                continue;
+            }
+            let value = env_value(lookup(&merged, &key), &raw);
+            set(
+                &mut merged,
+                &key,
+                value,
+                &ConfigSource::Env(var),
+                &mut sources,
+            );
+        }
+
+        for (flag, key, value) in self.flags {
+            set(
+                &mut merged,
+                &key,
+                value,
+                &ConfigSource::Flag(flag),
+                &mut sources,
+            );
+        }
+
+        let config: Config = serde_json::from_value(merged)
+            .map_err(|e| AivaError::ConfigError(format!("Invalid configuration: {e}")))?;
+        validate_concurrency(config.concurrency)?;
+        if let Some(limit) = config.monitoring.metrics_concurrency {
+            validate_concurrency(limit)?;
+        }
+        Ok(ResolvedConfig { config, sources })
+    }
+}
+
+/// Dotted key an environment variable sets, `None` for variables that are
+/// not configuration
+pub fn env_key(var: &str) -> Option<String> {
+    if RESERVED_ENV_VARS.contains(&var) {
+        return None;
+    }
+    if let Some((_, key)) = ENV_ALIASES.iter().find(|(alias, _)| *alias == var) {
+        return Some(key.to_string());
+    }
+    let name = var.strip_prefix(ENV_PREFIX)?;
+    if name.is_empty() {
+        return None;
+    }
+    Some(name.to_lowercase().replace(ENV_KEY_SEPARATOR, "."))
+}
+
+/// Value of an environment variable for a key currently set to `current`.
+/// Strings stay strings; anything else is read as YAML, so numbers, booleans
+/// and lists such as `[8.8.8.8, 1.1.1.1]` work.
+fn env_value(current: Option<&Value>, raw: &str) -> Value {
+    if matches!(current, Some(Value::String(_))) {
+        return Value::String(raw.to_string());
+    }
+    serde_yaml::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+fn lookup<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    key.split('.')
+        .try_fold(value, |value, part| value.get(part))
+}
+
+/// Set the dotted `key` of `root` to `value`, creating objects on the way
+fn set(
+    root: &mut Value,
+    key: &str,
+    value: Value,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    let mut target = root;
+    for part in key.split('.') {
+        if !target.is_object() {
+            *target = Value::Object(Map::new());
+        }
+        target = target
+            .as_object_mut()
+            .expect("just made an object")
+            .entry(part)
+            .or_insert(Value::Null);
+    }
+    forget(sources, key);
+    record_leaves(&value, key, source, sources);
+    *target = value;
+}
+
+/// Merge `layer` into `base` key by key
+fn merge(
+    base: &mut Value,
+    layer: Value,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (part, value) in layer {
+                let key = join(prefix, &part);
+                match base.get_mut(&part) {
+                    Some(existing) if existing.is_object() && value.is_object() => {
+                        merge(existing, value, &key, source, sources)
+                    }
+                    _ => {
+                        forget(sources, &key);
+                        record_leaves(&value, &key, source, sources);
+                        base.insert(part, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => {
+            forget(sources, prefix);
+            record_leaves(&layer, prefix, source, sources);
+            *base = layer;
+        }
+    }
+}
+
+/// Record `source` for every value under `prefix`. Lists count as one value.
+fn record_leaves(
+    value: &Value,
+    prefix: &str,
+    source: &ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (part, value) in map {
+                record_leaves(value, &join(prefix, part), source, sources);
+            }
+        }
+        _ if !prefix.is_empty() => {
+            sources.insert(prefix.to_string(), source.clone());
+        }
+        _ => {}
+    }
+}
+
+/// Drop the sources of `key`, the values under it and the ones it replaces
+/// above it, such as an empty section that now has a value
+fn forget(sources: &mut BTreeMap<String, ConfigSource>, key: &str) {
+    sources.retain(|recorded, _| !is_under(recorded, key) && !is_under(key, recorded));
+}
+
+fn join(prefix: &str, part: &str) -> String {
+    if prefix.is_empty() {
+        part.to_string()
+    } else {
+        format!("{prefix}.{part}")
+    }
+}
+
+fn is_under(key: &str, prefix: &str) -> bool {
+    prefix.is_empty()
+        || key == prefix
+        || key
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('.'))
+}
@@ -0,0 +1,86 @@
+//! Portable VM definitions.
+//!
+//! A [`VMDefinition`] is everything needed to recreate a VM elsewhere
+//! except its disk contents: the [`VMConfig`], the template it was
+//! initialized from, its security policy and its labels. It is meant to be
+//! checked in and reviewed, so the policy and labels sit next to the config
+//! instead of inside it, and JSON and YAML are both accepted on import.
+
+use crate::error::{AivaError, Result};
+use crate::schema::migrate_config;
+use crate::types::VMConfig;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Layout version of definitions written by this build
+pub const DEFINITION_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VMDefinition {
+    pub version: u32,
+    /// Template the VM was initialized from
+    #[serde(default)]
+    pub template: Option<String>,
+    /// Security policy assigned to the VM
+    #[serde(default)]
+    pub security_policy: Option<String>,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// The configuration without its policy and labels
+    pub config: VMConfig,
+}
+
+impl VMDefinition {
+    /// Definition of a VM configured with `config`
+    pub fn new(config: &VMConfig, template: Option<String>) -> Self {
+        let mut config = config.clone();
+        Self {
+            version: DEFINITION_VERSION,
+            template,
+            security_policy: config.security_policy.take(),
+            labels: std::mem::take(&mut config.labels),
+            config,
+        }
+    }
+
+    /// Read a definition from JSON or YAML. The config is migrated like a
+    /// stored one, so definitions from older releases still import.
+    pub fn parse(text: &str) -> Result<Self> {
+        // YAML is a superset of JSON, one parser covers both
+        let mut value: Value = serde_yaml::from_str(text)
+            .map_err(|e| AivaError::ConfigError(format!("Invalid VM definition: {e}")))?;
+        let record = value.as_object_mut().ok_or_else(|| {
+            AivaError::ConfigError("VM definition is not a JSON or YAML object".to_string())
+        })?;
+
+        let version = record
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| AivaError::ConfigError("VM definition has no version".to_string()))?;
+        if version > u64::from(DEFINITION_VERSION) {
+            return Err(AivaError::ConfigError(format!(
+                "VM definition has version {version}, this aiva supports up to \
+                 {DEFINITION_VERSION}. Upgrade aiva to import it."
+            )));
+        }
+
+        let config = record
+            .remove("config")
+            .ok_or_else(|| AivaError::ConfigError("VM definition has no config".to_string()))?;
+        let (config, _) = migrate_config(config)?;
+        record.insert("config".to_string(), serde_json::to_value(config)?);
+
+        serde_json::from_value(value)
+            .map_err(|e| AivaError::ConfigError(format!("Invalid VM definition: {e}")))
+    }
+
+    /// The VM configuration with the policy and labels put back, validated
+    pub fn into_config(self) -> Result<VMConfig> {
+        let mut config = self.config;
+        config.security_policy = self.security_policy;
+        config.labels = self.labels;
+        config.validate()?;
+        Ok(config)
+    }
+}
@@ -0,0 +1,28 @@
+//! Sizing of VM root filesystems.
+//!
+//! Every VM boots from a copy of the base rootfs that is grown to `disk_gb`.
+//! ext4 images are only ever grown, so a disk smaller than the base image is
+//! rejected up front instead of leaving resize2fs to fail halfway.
+
+use crate::error::{AivaError, Result};
+
+pub const GIB: u64 = 1024 * 1024 * 1024;
+
+/// Smallest whole `disk_gb` that holds an image of `bytes`
+pub fn min_disk_gb(bytes: u64) -> u64 {
+    bytes.div_ceil(GIB)
+}
+
+/// Check that a disk of `disk_gb` can hold a rootfs image of `image_bytes`
+pub fn check_rootfs_fits(image_bytes: u64, disk_gb: u64) -> Result<()> {
+    if disk_gb.saturating_mul(GIB) >= image_bytes {
+        return Ok(());
+    }
+
+    Err(AivaError::ConfigError(format!(
+        "disk_gb is {disk_gb} GB but the rootfs image is {:.1} GB and cannot be shrunk; \
+         set disk_gb to at least {}",
+        image_bytes as f64 / GIB as f64,
+        min_disk_gb(image_bytes)
+    )))
+}
@@ -0,0 +1,212 @@
+//! Guest resolver configuration.
+//!
+//! Plain resolvers and search domains fit in `/etc/resolv.conf`. Split DNS
+//! does not: resolv.conf has no notion of per-domain servers, so overrides
+//! are served by a dnsmasq instance on the guest loopback that forwards each
+//! domain to its own resolvers and everything else to `dns_servers`.
+
+use crate::error::{AivaError, Result};
+use crate::types::{NetworkConfig, VMInstance, shell_quote};
+use crate::vm::VMManager;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use tracing::info;
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+const DNSMASQ_CONF: &str = "/etc/dnsmasq.d/aiva.conf";
+/// Address dnsmasq listens on; systemd-resolved keeps 127.0.0.53
+const DNSMASQ_LISTEN: &str = "127.0.0.1";
+const MAX_DOMAIN_LEN: usize = 253;
+const MAX_LABEL_LEN: usize = 63;
+
+/// Check that `domain` is a valid DNS name such as `corp.example.com`
+pub fn validate_domain(domain: &str) -> Result<()> {
+    let name = domain.strip_suffix('.').unwrap_or(domain);
+    let invalid = |reason: &str| {
+        Err(AivaError::ConfigError(format!(
+            "Invalid DNS domain '{domain}': {reason}"
+        )))
+    };
+
+    if name.is_empty() {
+        return invalid("empty name");
+    }
+    if name.len() > MAX_DOMAIN_LEN {
+        return invalid("longer than 253 characters");
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > MAX_LABEL_LEN {
+            return invalid("labels must be 1 to 63 characters");
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return invalid("labels cannot start or end with '-'");
+        }
+        if !label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        {
+            return invalid("only letters, digits and '-' are allowed");
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_servers(servers: &[String], context: &str) -> Result<()> {
+    for server in servers {
+        server.parse::<IpAddr>().map_err(|_| {
+            AivaError::ConfigError(format!(
+                "Invalid DNS server '{server}' in {context}: expected an IP address"
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+/// Parse `domain=ip[,ip];domain=ip` as accepted by
+/// `aiva config set network.dns_overrides`
+pub fn parse_dns_overrides(value: &str) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut overrides = BTreeMap::new();
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let (domain, servers) = entry.split_once('=').ok_or_else(|| {
+            AivaError::ConfigError(format!(
+                "Invalid DNS override '{entry}': expected domain=ip[,ip]"
+            ))
+        })?;
+        let servers = servers
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        overrides.insert(domain.trim().to_lowercase(), servers);
+    }
+    Ok(overrides)
+}
+
+/// Inverse of [`parse_dns_overrides`]
+pub fn format_dns_overrides(overrides: &BTreeMap<String, Vec<String>>) -> String {
+    overrides
+        .iter()
+        .map(|(domain, servers)| format!("{domain}={}", servers.join(",")))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+impl NetworkConfig {
+    /// Whether the guest needs more than the resolvers it boots with
+    pub fn has_custom_dns(&self) -> bool {
+        !self.dns_search.is_empty() || !self.dns_overrides.is_empty()
+    }
+
+    pub fn validate_dns(&self) -> Result<()> {
+        validate_servers(&self.dns_servers, "dns_servers")?;
+        for domain in &self.dns_search {
+            validate_domain(domain)?;
+        }
+        for (domain, servers) in &self.dns_overrides {
+            validate_domain(domain)?;
+            if servers.is_empty() {
+                return Err(AivaError::ConfigError(format!(
+                    "DNS override for '{domain}' lists no servers"
+                )));
+            }
+            validate_servers(servers, &format!("the override for '{domain}'"))?;
+        }
+        Ok(())
+    }
+
+    /// Contents of the guest `/etc/resolv.conf`
+    pub fn render_resolv_conf(&self) -> String {
+        let mut conf = String::from("# Generated by aiva\n");
+        if self.dns_overrides.is_empty() {
+            for server in &self.dns_servers {
+                conf.push_str(&format!("nameserver {server}\n"));
+            }
+        } else {
+            conf.push_str(&format!("nameserver {DNSMASQ_LISTEN}\n"));
+        }
+        if !self.dns_search.is_empty() {
+            conf.push_str(&format!("search {}\n", self.dns_search.join(" ")));
+        }
+        conf
+    }
+
+    /// dnsmasq configuration for split DNS, `None` without overrides
+    pub fn render_dnsmasq_conf(&self) -> Option<String> {
+        if self.dns_overrides.is_empty() {
+            return None;
+        }
+
+        let mut conf = format!(
+            "# Generated by aiva\nno-resolv\nlisten-address={DNSMASQ_LISTEN}\nbind-interfaces\n"
+        );
+        for (domain, servers) in &self.dns_overrides {
+            for server in servers {
+                conf.push_str(&format!("server=/{domain}/{server}\n"));
+            }
+        }
+        for server in &self.dns_servers {
+            conf.push_str(&format!("server={server}\n"));
+        }
+        Some(conf)
+    }
+
+    /// Guest shell command installing the resolver configuration. Prints
+    /// `dns=applied`, or `dns=missing-dnsmasq` when split DNS is configured
+    /// but the guest has no dnsmasq.
+    pub fn resolver_setup_command(&self) -> Result<String> {
+        self.validate_dns()?;
+
+        // resolv.conf is often a symlink into systemd-resolved; replace it
+        let write_resolv_conf = format!(
+            "rm -f {RESOLV_CONF} && printf '%s' {} > {RESOLV_CONF} && echo dns=applied",
+            shell_quote(&self.render_resolv_conf())
+        );
+
+        let command = match self.render_dnsmasq_conf() {
+            Some(dnsmasq) => format!(
+                "if ! command -v dnsmasq >/dev/null 2>&1; then echo dns=missing-dnsmasq; else \
+                 mkdir -p /etc/dnsmasq.d && printf '%s' {} > {DNSMASQ_CONF} && \
+                 (systemctl restart dnsmasq 2>/dev/null || service dnsmasq restart) && \
+                 {write_resolv_conf}; fi",
+                shell_quote(&dnsmasq)
+            ),
+            None => write_resolv_conf,
+        };
+        Ok(command)
+    }
+}
+
+/// Install the search domains and split-DNS resolvers of `network` in the
+/// guest of `vm` through its agent
+pub async fn apply_guest_dns(
+    vm_manager: &dyn VMManager,
+    vm: &VMInstance,
+    network: &NetworkConfig,
+) -> Result<()> {
+    let output = vm_manager
+        .execute_command(&vm.id, &network.resolver_setup_command()?)
+        .await?;
+
+    if output.contains("dns=applied") {
+        info!("Applied guest DNS settings to {}", vm.name);
+        return Ok(());
+    }
+
+    let (reason, hint) = if output.contains("dns=missing-dnsmasq") {
+        (
+            "split DNS needs dnsmasq in the guest".to_string(),
+            "Install dnsmasq in the image or clear network.dns_overrides".to_string(),
+        )
+    } else {
+        (
+            format!("unexpected output '{}'", output.trim()),
+            format!("Check {RESOLV_CONF} in the guest"),
+        )
+    };
+    Err(AivaError::GuestNetworkUnavailable {
+        vm_name: vm.name.clone(),
+        reason: format!("DNS settings could not be applied: {reason}"),
+        hint,
+    })
+}
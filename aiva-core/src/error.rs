@@ -18,6 +18,13 @@ pub enum AivaError {
     #[error("Network error during {operation}: {cause}")]
     NetworkError { operation: String, cause: String },
 
+    #[error("Guest network not ready on {vm_name}: {reason}. {hint}")]
+    GuestNetworkUnavailable {
+        vm_name: String,
+        reason: String,
+        hint: String,
+    },
+
     #[error("VM error for {vm_name} in state {state:?}: {message}")]
     VMError {
         vm_name: String,
@@ -0,0 +1,49 @@
+//! Lifecycle events published by [`VMOrchestrator`](crate::VMOrchestrator).
+//!
+//! Events are sent while the orchestrator still holds the lock on its VM
+//! map, so subscribers see changes to one VM in the order they happened.
+//! A subscriber that falls more than the channel capacity behind gets
+//! `RecvError::Lagged` and should resynchronize from `list_vms`.
+
+use crate::types::VMInstance;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Events buffered per subscriber before it lags
+const EVENT_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone)]
+pub enum VMEvent {
+    /// A VM was created or its state or configuration changed
+    Updated(Box<VMInstance>),
+    Deleted(Uuid),
+    /// The state file was loaded; carries every known VM
+    Loaded(Vec<VMInstance>),
+}
+
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<VMEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<VMEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Send `event` to the current subscribers; without any it is dropped
+    pub fn publish(&self, event: VMEvent) {
+        let _ = self.sender.send(event);
+    }
+}
@@ -0,0 +1,141 @@
+//! Guest hostnames and `/etc/hosts`.
+//!
+//! Every guest is named after its VM unless `network.hostname` says
+//! otherwise, so log lines from several agents can be told apart. VM names
+//! allow characters hostnames do not, so they go through
+//! [`sanitize_hostname`] first. `/etc/hosts` maps the guest's own name, the
+//! gateway and every other VM on the same subnet, letting agents reach each
+//! other by name without a DNS server.
+
+use crate::error::{AivaError, Result};
+use crate::types::{VMInstance, shell_quote};
+use crate::vm::VMManager;
+use tracing::info;
+
+const ETC_HOSTNAME: &str = "/etc/hostname";
+const ETC_HOSTS: &str = "/etc/hosts";
+/// Name the gateway gets in `/etc/hosts`
+pub const GATEWAY_HOSTNAME: &str = "aiva-gateway";
+/// Used when nothing of the VM name survives sanitizing
+const FALLBACK_HOSTNAME: &str = "aiva-vm";
+const MAX_HOSTNAME_LEN: usize = 63;
+
+/// Turn a VM name into a valid hostname: lower case letters, digits and
+/// single dashes, at most 63 characters
+pub fn sanitize_hostname(name: &str) -> String {
+    let mut hostname = String::with_capacity(name.len());
+    for c in name.chars().map(|c| c.to_ascii_lowercase()) {
+        if c.is_ascii_alphanumeric() {
+            hostname.push(c);
+        } else if !hostname.is_empty() && !hostname.ends_with('-') {
+            hostname.push('-');
+        }
+    }
+    hostname.truncate(MAX_HOSTNAME_LEN);
+    let hostname = hostname.trim_end_matches('-');
+
+    if hostname.is_empty() {
+        FALLBACK_HOSTNAME.to_string()
+    } else {
+        hostname.to_string()
+    }
+}
+
+/// Check that `hostname` is a single RFC 1123 label such as `web-1`
+pub fn validate_hostname(hostname: &str) -> Result<()> {
+    let invalid = |reason: &str| {
+        Err(AivaError::ConfigError(format!(
+            "Invalid hostname '{hostname}': {reason}"
+        )))
+    };
+
+    if hostname.is_empty() || hostname.len() > MAX_HOSTNAME_LEN {
+        return invalid("must be 1 to 63 characters");
+    }
+    if hostname.starts_with('-') || hostname.ends_with('-') {
+        return invalid("cannot start or end with '-'");
+    }
+    if !hostname
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    {
+        return invalid("only letters, digits and '-' are allowed");
+    }
+    Ok(())
+}
+
+impl VMInstance {
+    /// Hostname of the guest: `network.hostname`, or the sanitized VM name
+    pub fn hostname(&self) -> String {
+        self.config
+            .network
+            .hostname
+            .clone()
+            .unwrap_or_else(|| sanitize_hostname(&self.name))
+    }
+}
+
+/// Contents of the guest `/etc/hosts` of `vm`, listing the VMs of `fleet`
+/// on the same subnet by name
+pub fn render_hosts(vm: &VMInstance, fleet: &[VMInstance]) -> String {
+    let network = &vm.config.network;
+    let mut hosts = format!(
+        "# Generated by aiva\n\
+         127.0.0.1 localhost\n\
+         ::1 localhost ip6-localhost ip6-loopback\n\
+         {} {}\n\
+         {} {GATEWAY_HOSTNAME}\n",
+        network.guest_ip,
+        vm.hostname(),
+        network.gateway
+    );
+
+    let mut siblings: Vec<(String, &str)> = fleet
+        .iter()
+        .filter(|other| other.id != vm.id)
+        .filter(|other| other.config.network.subnet == network.subnet)
+        .filter(|other| other.config.network.guest_ip != network.guest_ip)
+        .map(|other| (other.hostname(), other.config.network.guest_ip.as_str()))
+        .collect();
+    siblings.sort();
+    for (hostname, ip) in siblings {
+        hosts.push_str(&format!("{ip} {hostname}\n"));
+    }
+    hosts
+}
+
+/// Guest shell command setting the hostname of `vm` and writing its
+/// `/etc/hosts`. Prints `hostname=applied`.
+pub fn hostname_setup_command(vm: &VMInstance, fleet: &[VMInstance]) -> Result<String> {
+    let hostname = vm.hostname();
+    validate_hostname(&hostname)?;
+
+    Ok(format!(
+        "printf '%s\\n' {name} > {ETC_HOSTNAME} && hostname {name} && \
+         printf '%s' {hosts} > {ETC_HOSTS} && echo hostname=applied",
+        name = shell_quote(&hostname),
+        hosts = shell_quote(&render_hosts(vm, fleet))
+    ))
+}
+
+/// Set the hostname of the guest of `vm` and seed its `/etc/hosts` with the
+/// other VMs `vm_manager` knows of
+pub async fn apply_guest_hostname(vm_manager: &dyn VMManager, vm: &VMInstance) -> Result<()> {
+    let fleet = vm_manager.list_vms().await?;
+    let output = vm_manager
+        .execute_command(&vm.id, &hostname_setup_command(vm, &fleet)?)
+        .await?;
+
+    if output.contains("hostname=applied") {
+        info!("Set hostname of {} to {}", vm.name, vm.hostname());
+        return Ok(());
+    }
+    Err(AivaError::GuestNetworkUnavailable {
+        vm_name: vm.name.clone(),
+        reason: format!(
+            "hostname could not be set: unexpected output '{}'",
+            output.trim()
+        ),
+        hint: format!("Check {ETC_HOSTNAME} and {ETC_HOSTS} in the guest"),
+    })
+}
@@ -1,15 +1,64 @@
+pub mod alert_routing;
+pub mod benchmark;
+pub mod concurrency;
 pub mod config;
+pub mod config_layers;
+pub mod definition;
+pub mod disk;
+pub mod dns;
 pub mod error;
+pub mod events;
+pub mod hostname;
+pub mod log_store;
+pub mod log_tail;
 pub mod logging;
+pub mod metadata;
+pub mod metrics_feed;
 pub mod monitoring;
+pub mod pause;
+pub mod plan;
+pub mod readiness;
+pub mod reconcile;
+pub mod remediation;
+pub mod scale;
+pub mod schema;
+pub mod server;
 pub mod templates;
 pub mod types;
 pub mod vm;
+pub mod vm_config;
+pub mod vsock;
 
+#[cfg(test)]
+mod tests;
+
+pub use alert_routing::*;
+pub use benchmark::*;
+pub use concurrency::run_bounded;
 pub use config::*;
+pub use config_layers::*;
+pub use definition::*;
+pub use disk::*;
+pub use dns::*;
 pub use error::*;
+pub use events::{EventBus, VMEvent};
+pub use hostname::*;
+pub use log_store::LogStore;
+pub use log_tail::{LogSegment, TAIL_CHUNK_SIZE, log_segments, tail_log};
 pub use logging::{LogLevel as VMLogLevel, VMLogger};
+pub use metadata::*;
+pub use metrics_feed::{METRICS_CAPACITY, MetricsFeed, MetricsSample, MetricsSubscriber};
 pub use monitoring::*;
+pub use pause::*;
+pub use plan::*;
+pub use readiness::*;
+pub use reconcile::*;
+pub use remediation::*;
+pub use scale::*;
+pub use schema::*;
+pub use server::*;
 pub use templates::*;
 pub use types::*;
 pub use vm::*;
+pub use vm_config::*;
+pub use vsock::*;
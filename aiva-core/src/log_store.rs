@@ -0,0 +1,117 @@
+use crate::Result;
+use crate::monitoring::{Alert, LogEntry};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+/// File holding alerts, next to the per-VM log files
+const ALERTS_FILE: &str = "alerts.jsonl";
+
+/// Append-only JSONL persistence for structured log entries, one file per VM
+#[derive(Debug, Clone)]
+pub struct LogStore {
+    dir: PathBuf,
+}
+
+impl LogStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Store rooted at ~/.aiva/logs, next to the plain-text VMLogger files
+    pub fn default_location() -> Self {
+        let dir = dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".aiva")
+            .join("logs");
+        Self::new(dir)
+    }
+
+    pub fn path_for(&self, vm_id: &str) -> PathBuf {
+        self.dir.join(format!("{vm_id}.jsonl"))
+    }
+
+    pub async fn append(&self, entry: &LogEntry) -> Result<()> {
+        let key = entry.vm_id.as_deref().unwrap_or("system");
+        self.append_line(&self.path_for(key), entry).await
+    }
+
+    /// Read back every entry persisted for a VM, skipping lines that fail to parse
+    pub async fn read(&self, vm_id: &str) -> Result<Vec<LogEntry>> {
+        read_lines(&self.path_for(vm_id)).await
+    }
+
+    /// Entries of every VM and of the host, each file in order
+    pub async fn read_all(&self) -> Result<Vec<LogEntry>> {
+        let mut entries = Vec::new();
+        if !self.dir.exists() {
+            return Ok(entries);
+        }
+
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            if path.extension().is_some_and(|ext| ext == "jsonl")
+                && path.file_name().is_some_and(|name| name != ALERTS_FILE)
+            {
+                entries.extend(read_lines(&path).await?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Persist `alert`; appending it again records a later change, such as
+    /// its resolution
+    pub async fn append_alert(&self, alert: &Alert) -> Result<()> {
+        self.append_line(&self.dir.join(ALERTS_FILE), alert).await
+    }
+
+    /// The latest persisted version of every alert, oldest first
+    pub async fn read_alerts(&self) -> Result<Vec<Alert>> {
+        let versions: Vec<Alert> = read_lines(&self.dir.join(ALERTS_FILE)).await?;
+
+        let mut order = Vec::new();
+        let mut latest = HashMap::new();
+        for alert in versions {
+            if !latest.contains_key(&alert.id) {
+                order.push(alert.id);
+            }
+            latest.insert(alert.id, alert);
+        }
+        Ok(order
+            .into_iter()
+            .filter_map(|id| latest.remove(&id))
+            .collect())
+    }
+
+    async fn append_line<T: Serialize>(&self, path: &Path, value: &T) -> Result<()> {
+        fs::create_dir_all(&self.dir).await?;
+
+        let mut line = serde_json::to_string(value)?;
+        line.push('\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+async fn read_lines<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(path).await?;
+    Ok(content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
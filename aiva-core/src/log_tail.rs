@@ -0,0 +1,231 @@
+//! Reading the end of a VM log without loading all of it.
+//!
+//! [`tail_log`] finds where the last lines start by reading backward from
+//! the end in fixed-size chunks, then streams them out, so memory stays at
+//! one chunk however many lines are asked for and however large the log is.
+//! When the current log holds fewer lines than that, the tail carries on into
+//! the rotated segments next to it: `vm.log.1`, then `vm.log.2.gz` and so on,
+//! compressed or not. Compressed segments cannot be read backward and are
+//! decompressed twice instead, once to count their lines and once to copy.
+
+use crate::Result;
+use flate2::read::GzDecoder;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes read at a time while scanning backward
+pub const TAIL_CHUNK_SIZE: usize = 64 * 1024;
+
+/// One file of a rotated log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogSegment {
+    pub path: PathBuf,
+    pub compressed: bool,
+}
+
+impl LogSegment {
+    fn plain(path: PathBuf) -> Self {
+        Self {
+            path,
+            compressed: false,
+        }
+    }
+}
+
+/// Segments of the log at `path` that exist, newest first: the log itself,
+/// then `<path>.1`, `<path>.2` and on, each either plain or `.gz`
+pub fn log_segments(path: &Path) -> Vec<LogSegment> {
+    let mut segments = Vec::new();
+    if path.exists() {
+        segments.push(LogSegment::plain(path.to_path_buf()));
+    }
+
+    for n in 1.. {
+        let rotated = PathBuf::from(format!("{}.{n}", path.display()));
+        let compressed = PathBuf::from(format!("{}.{n}.gz", path.display()));
+        if rotated.exists() {
+            segments.push(LogSegment::plain(rotated));
+        } else if compressed.exists() {
+            segments.push(LogSegment {
+                path: compressed,
+                compressed: true,
+            });
+        } else {
+            break;
+        }
+    }
+    segments
+}
+
+/// Write the last `lines` lines of the log at `path` to `out`, reading into
+/// rotated segments when the current log is too short
+pub fn tail_log(path: &Path, lines: usize, out: &mut impl Write) -> Result<()> {
+    tail_segments(&log_segments(path), lines, TAIL_CHUNK_SIZE, out)
+}
+
+/// Where in a segment the tail starts
+enum Start {
+    /// Byte offset in a plain segment
+    Byte(u64),
+    /// Lines to skip in a compressed one
+    Line(usize),
+}
+
+/// [`tail_log`] over `segments`, newest first, scanning `chunk` bytes at a
+/// time
+pub(crate) fn tail_segments(
+    segments: &[LogSegment],
+    lines: usize,
+    chunk: usize,
+    out: &mut impl Write,
+) -> Result<()> {
+    if lines == 0 || segments.is_empty() {
+        return Ok(());
+    }
+
+    // Walk back from the newest segment until enough lines are found
+    let mut remaining = lines;
+    let mut start = (segments.len() - 1, Start::Byte(0));
+    for (i, segment) in segments.iter().enumerate() {
+        if segment.compressed {
+            let count = count_lines(gz_reader(&segment.path)?)?;
+            if count >= remaining {
+                start = (i, Start::Line(count - remaining));
+                break;
+            }
+            remaining -= count;
+        } else {
+            let (offset, found) =
+                find_line_start(&mut File::open(&segment.path)?, remaining, chunk)?;
+            if found >= remaining {
+                start = (i, Start::Byte(offset));
+                break;
+            }
+            remaining -= found;
+        }
+    }
+
+    // Then copy forward, oldest first
+    let (first, from) = start;
+    for (i, segment) in segments[..=first].iter().enumerate().rev() {
+        let from = if i == first { &from } else { &Start::Byte(0) };
+        if segment.compressed {
+            let skip = match from {
+                Start::Line(skip) => *skip,
+                Start::Byte(_) => 0,
+            };
+            copy_lines(gz_reader(&segment.path)?, skip, out)?;
+        } else {
+            let mut file = File::open(&segment.path)?;
+            if let Start::Byte(offset) = from {
+                file.seek(SeekFrom::Start(*offset))?;
+            }
+            copy_lines(BufReader::with_capacity(chunk, file), 0, out)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Scan `file` backward for the start of its last `lines` lines. Returns the
+/// offset they start at and how many were found, fewer than `lines` when the
+/// whole file is shorter.
+fn find_line_start(file: &mut File, lines: usize, chunk: usize) -> Result<(u64, usize)> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok((0, 0));
+    }
+
+    let mut buffer = vec![0u8; chunk];
+    let mut end = len;
+    let mut found = 0;
+    // A newline ending the file closes the last line rather than starting one
+    let mut skip_trailing = true;
+    while end > 0 {
+        let begin = end.saturating_sub(chunk as u64);
+        let read = &mut buffer[..(end - begin) as usize];
+        file.seek(SeekFrom::Start(begin))?;
+        file.read_exact(read)?;
+
+        for (i, byte) in read.iter().enumerate().rev() {
+            let offset = begin + i as u64;
+            if *byte != b'\n' {
+                continue;
+            }
+            if skip_trailing && offset == len - 1 {
+                continue;
+            }
+            found += 1;
+            if found == lines {
+                return Ok((offset + 1, found));
+            }
+        }
+        skip_trailing = false;
+        end = begin;
+    }
+
+    // The first line starts at the beginning of the file
+    Ok((0, found + 1))
+}
+
+fn gz_reader(path: &Path) -> Result<BufReader<GzDecoder<File>>> {
+    Ok(BufReader::new(GzDecoder::new(File::open(path)?)))
+}
+
+fn count_lines(mut reader: impl BufRead) -> Result<usize> {
+    let mut count = 0;
+    let mut last = None;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        count += buffer.iter().filter(|byte| **byte == b'\n').count();
+        last = buffer.last().copied();
+        let consumed = buffer.len();
+        reader.consume(consumed);
+    }
+    // A last line without a newline still counts
+    if last.is_some_and(|byte| byte != b'\n') {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Copy `reader` to `out` after skipping `skip` lines, ending with a newline
+/// so the next segment starts on a line of its own
+fn copy_lines(mut reader: impl BufRead, mut skip: usize, out: &mut impl Write) -> Result<()> {
+    while skip > 0 {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        match buffer.iter().position(|byte| *byte == b'\n') {
+            Some(newline) => {
+                reader.consume(newline + 1);
+                skip -= 1;
+            }
+            None => {
+                let consumed = buffer.len();
+                reader.consume(consumed);
+            }
+        }
+    }
+
+    let mut last = None;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        out.write_all(buffer)?;
+        last = buffer.last().copied();
+        let consumed = buffer.len();
+        reader.consume(consumed);
+    }
+    if last.is_some_and(|byte| byte != b'\n') {
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
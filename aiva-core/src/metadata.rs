@@ -0,0 +1,96 @@
+//! Metadata a guest can read about its own VM.
+//!
+//! Agents inside a VM need their name, address, forwarded ports and
+//! resource limits without being told on the command line, and MCP servers
+//! the per-VM settings in [`VMConfig::metadata`](crate::VMConfig::metadata). The platform
+//! serves [`VMMetadata`] under the [`METADATA_KEY`] key of a link-local
+//! metadata service; on Linux that is the Firecracker MMDS, reachable from
+//! the guest at [`METADATA_ADDRESS`] (version 2, so a session token is
+//! needed first):
+//!
+//! ```text
+//! ip route add 169.254.169.254 dev eth0   # once, if there is no route yet
+//! TOKEN=$(curl -s -X PUT http://169.254.169.254/latest/api/token \
+//!     -H 'X-metadata-token-ttl-seconds: 300')
+//! curl -s -H "X-metadata-token: $TOKEN" -H 'Accept: application/json' \
+//!     http://169.254.169.254/aiva
+//! ```
+//!
+//! The document is published when the VM is created and again whenever
+//! [`VMOrchestrator`](crate::VMOrchestrator) changes the configuration of a
+//! running VM. New fields may be added; existing ones keep their meaning
+//! for a given [`METADATA_VERSION`].
+
+use crate::types::VMInstance;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// Link-local address the guest reaches the metadata service on
+pub const METADATA_ADDRESS: &str = "169.254.169.254";
+
+/// Top-level key of the document in the metadata service
+pub const METADATA_KEY: &str = "aiva";
+
+/// Layout version of [`VMMetadata`]
+pub const METADATA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VMMetadata {
+    pub version: u32,
+    pub id: Uuid,
+    pub name: String,
+    pub hostname: String,
+    pub guest_ip: String,
+    pub gateway: String,
+    pub port_mappings: Vec<MetadataPort>,
+    pub cpus: u32,
+    pub memory_mb: u64,
+    pub disk_gb: u64,
+    /// The VM's own entries, see [`VMConfig::metadata`](crate::VMConfig::metadata)
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A host port forwarded to the guest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataPort {
+    pub host_port: u16,
+    pub guest_port: u16,
+    /// `tcp` or `udp`
+    pub protocol: String,
+}
+
+impl VMMetadata {
+    pub fn from_instance(vm: &VMInstance) -> Self {
+        let config = &vm.config;
+        VMMetadata {
+            version: METADATA_VERSION,
+            id: vm.id,
+            name: vm.name.clone(),
+            hostname: vm.hostname(),
+            guest_ip: config.network.guest_ip.clone(),
+            gateway: config.network.gateway.clone(),
+            port_mappings: config
+                .network
+                .port_mappings
+                .iter()
+                .map(|mapping| MetadataPort {
+                    host_port: mapping.host_port,
+                    guest_port: mapping.guest_port,
+                    protocol: mapping.protocol.to_string(),
+                })
+                .collect(),
+            cpus: config.cpus,
+            memory_mb: config.memory_mb,
+            disk_gb: config.disk_gb,
+            metadata: config.metadata.clone(),
+        }
+    }
+
+    /// The whole data store of the metadata service, with the document
+    /// under [`METADATA_KEY`]
+    pub fn to_store(&self) -> serde_json::Value {
+        serde_json::json!({ METADATA_KEY: self })
+    }
+}
@@ -0,0 +1,128 @@
+//! Live metrics for streaming subscribers.
+//!
+//! [`MonitoringService`](crate::MonitoringService) publishes every sample it
+//! collects into a bounded broadcast channel. A subscriber that falls more
+//! than the capacity behind loses the oldest samples instead of making the
+//! channel grow: it skips ahead to the oldest retained sample and counts
+//! what it missed, so a client can tell it has gaps.
+
+use crate::monitoring::SystemMetrics;
+use crate::types::VMMetrics;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+/// Samples buffered per subscriber before the oldest are dropped
+pub const METRICS_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MetricsSample {
+    System(SystemMetrics),
+    Vm { vm_id: String, metrics: VMMetrics },
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsFeed {
+    sender: broadcast::Sender<MetricsSample>,
+    capacity: usize,
+    /// Samples dropped across all subscribers
+    dropped: Arc<AtomicU64>,
+}
+
+impl Default for MetricsFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MetricsFeed {
+    pub fn new() -> Self {
+        Self::with_capacity(METRICS_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            capacity,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn subscribe(&self) -> MetricsSubscriber {
+        MetricsSubscriber {
+            receiver: self.sender.subscribe(),
+            capacity: self.capacity,
+            dropped: 0,
+            feed_dropped: Arc::clone(&self.dropped),
+        }
+    }
+
+    /// Send `sample` to the current subscribers; without any it is dropped
+    /// and not counted
+    pub fn publish(&self, sample: MetricsSample) {
+        let _ = self.sender.send(sample);
+    }
+
+    /// Samples any subscriber missed because it fell behind
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+pub struct MetricsSubscriber {
+    receiver: broadcast::Receiver<MetricsSample>,
+    capacity: usize,
+    dropped: u64,
+    feed_dropped: Arc<AtomicU64>,
+}
+
+impl MetricsSubscriber {
+    /// The next sample, skipping over the ones dropped while this
+    /// subscriber lagged. `None` once the feed is gone.
+    pub async fn recv(&mut self) -> Option<MetricsSample> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(sample) => return Some(sample),
+                Err(RecvError::Lagged(missed)) => self.record_dropped(missed),
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Like [`recv`](Self::recv) without waiting; `None` when nothing is
+    /// buffered
+    pub fn try_recv(&mut self) -> Option<MetricsSample> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(sample) => return Some(sample),
+                Err(TryRecvError::Lagged(missed)) => self.record_dropped(missed),
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Samples this subscriber missed so far
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Samples still buffered for this subscriber, never more than the
+    /// capacity
+    pub fn pending(&self) -> usize {
+        // `len` also counts samples already overwritten while lagging
+        self.receiver.len().min(self.capacity)
+    }
+
+    fn record_dropped(&mut self, missed: u64) {
+        self.dropped += missed;
+        self.feed_dropped.fetch_add(missed, Ordering::Relaxed);
+    }
+}
@@ -1,11 +1,15 @@
-use crate::{Result, VMInstance, VMMetrics, VMState};
+use crate::alert_routing::{AlertRoutes, AlertSink};
+use crate::log_store::LogStore;
+use crate::metrics_feed::{MetricsFeed, MetricsSample};
+use crate::{Result, VMEvent, VMInstance, VMManager, VMMetrics, VMOrchestrator, VMState};
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::RwLock;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{RwLock, broadcast};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
@@ -15,6 +19,21 @@ pub trait MetricsCollector: Send + Sync {
     async fn collect_system_metrics(&self) -> Result<SystemMetrics>;
 }
 
+/// Streams the guest logs of a running VM into monitoring. Implemented by
+/// the platform layer, which owns the channel to the guest agent.
+#[async_trait]
+pub trait LogShipper: Send + Sync {
+    /// Ship the configured `logging.paths` of `vm` into `monitoring` until
+    /// the guest closes the stream
+    async fn ship(&self, vm: VMInstance, monitoring: Arc<MonitoringService>) -> Result<()>;
+}
+
+/// A running shipping task and the paths it follows
+struct ShippingTask {
+    paths: Vec<String>,
+    handle: tokio::task::JoinHandle<()>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
     pub cpu_usage: f64,
@@ -41,7 +60,7 @@ pub struct DiskUsage {
     pub usage_percent: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NetworkStats {
     pub rx_bytes: u64,
     pub tx_bytes: u64,
@@ -51,6 +70,17 @@ pub struct NetworkStats {
     pub drops: u64,
 }
 
+impl NetworkStats {
+    /// Errors and drops as a percentage of all packets, 0 without traffic
+    pub fn error_rate(&self) -> f64 {
+        let packets = self.rx_packets + self.tx_packets;
+        if packets == 0 {
+            return 0.0;
+        }
+        (self.errors + self.drops) as f64 / packets as f64 * 100.0
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogEntry {
     pub id: Uuid,
@@ -61,7 +91,22 @@ pub struct LogEntry {
     pub metadata: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl LogEntry {
+    pub fn new(vm_id: Option<String>, level: LogLevel, message: String) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            vm_id,
+            level,
+            message,
+            timestamp: Utc::now(),
+            metadata: HashMap::new(),
+        }
+    }
+}
+
+/// Severity of a log entry. Levels compare by severity, so `Error` is the
+/// greatest and `Trace` the least.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LogLevel {
     Error,
     Warn,
@@ -70,6 +115,30 @@ pub enum LogLevel {
     Trace,
 }
 
+impl LogLevel {
+    fn rank(self) -> u8 {
+        match self {
+            LogLevel::Trace => 0,
+            LogLevel::Debug => 1,
+            LogLevel::Info => 2,
+            LogLevel::Warn => 3,
+            LogLevel::Error => 4,
+        }
+    }
+}
+
+impl PartialOrd for LogLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LogLevel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Alert {
     pub id: Uuid,
@@ -82,7 +151,7 @@ pub struct Alert {
     pub resolved_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AlertType {
     ResourceExhaustion,
     HighCpuUsage,
@@ -94,7 +163,7 @@ pub enum AlertType {
     PerformanceDegradation,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum AlertSeverity {
     Critical,
     High,
@@ -102,12 +171,113 @@ pub enum AlertSeverity {
     Low,
 }
 
+impl AlertSeverity {
+    fn rank(self) -> u8 {
+        match self {
+            AlertSeverity::Low => 0,
+            AlertSeverity::Medium => 1,
+            AlertSeverity::High => 2,
+            AlertSeverity::Critical => 3,
+        }
+    }
+
+    pub fn is_at_least(self, min: AlertSeverity) -> bool {
+        self.rank() >= min.rank()
+    }
+}
+
+/// Narrows [`MonitoringService::get_alerts_filtered`]; unset fields match
+/// every alert
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilter {
+    pub vm_id: Option<String>,
+    /// Raised at or after
+    pub since: Option<DateTime<Utc>>,
+    /// Raised before
+    pub until: Option<DateTime<Utc>>,
+    /// Least severe alert included
+    pub min_severity: Option<AlertSeverity>,
+    pub resolved: Option<bool>,
+}
+
+impl AlertFilter {
+    pub fn matches(&self, alert: &Alert) -> bool {
+        self.vm_id
+            .as_ref()
+            .is_none_or(|vm_id| alert.vm_id.as_ref() == Some(vm_id))
+            && self.since.is_none_or(|since| alert.timestamp >= since)
+            && self.until.is_none_or(|until| alert.timestamp < until)
+            && self
+                .min_severity
+                .is_none_or(|min| alert.severity.is_at_least(min))
+            && self
+                .resolved
+                .is_none_or(|resolved| alert.resolved == resolved)
+    }
+}
+
 pub struct MonitoringService {
     metrics_collector: Box<dyn MetricsCollector>,
     alerts: Arc<RwLock<Vec<Alert>>>,
     logs: Arc<RwLock<Vec<LogEntry>>>,
     vm_instances: Arc<RwLock<HashMap<String, VMInstance>>>,
     alert_thresholds: AlertThresholds,
+    violation_limiter: Mutex<AlertRateLimiter>,
+    feed: MetricsFeed,
+    memory_cap: usize,
+    /// Receives what no longer fits in memory; without one it is dropped
+    overflow: Option<LogStore>,
+    alert_sinks: Vec<(String, Arc<dyn AlertSink>)>,
+    alert_routes: AlertRoutes,
+    log_shipper: Option<Arc<dyn LogShipper>>,
+    /// Guest log shipping of each tracked VM, by VM id
+    shipping: Mutex<HashMap<String, ShippingTask>>,
+}
+
+/// Default for how many log entries, and separately alerts, a
+/// [`MonitoringService`] holds in memory
+pub const MONITORING_MEMORY_CAP: usize = 10_000;
+
+/// Default window for [`MonitoringService::report_security_violation`]: a
+/// misbehaving guest can hit the same filtered syscall thousands of times a
+/// second, so repeats within the window are counted instead of alerted
+pub const SECURITY_ALERT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Allows one alert per key and window, counting what it suppresses
+#[derive(Debug)]
+pub struct AlertRateLimiter {
+    window: Duration,
+    seen: HashMap<String, (Instant, u64)>,
+}
+
+impl AlertRateLimiter {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Returns how many alerts for `key` were suppressed since the last one
+    /// if an alert may be raised at `now`, or `None` if it is rate limited
+    pub fn check(&mut self, key: &str, now: Instant) -> Option<u64> {
+        match self.seen.get_mut(key) {
+            Some((last, suppressed)) if now.duration_since(*last) < self.window => {
+                *suppressed += 1;
+                None
+            }
+            Some((last, suppressed)) => {
+                let count = *suppressed;
+                *last = now;
+                *suppressed = 0;
+                Some(count)
+            }
+            None => {
+                self.seen.insert(key.to_string(), (now, 0));
+                Some(0)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -137,6 +307,78 @@ impl Default for AlertThresholds {
     }
 }
 
+/// How close a VM is to the warning and critical thresholds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pressure {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Pressure of one VM and the resources that caused it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourcePressure {
+    pub level: Pressure,
+    /// `cpu` and/or `mem`, whichever crossed a threshold
+    pub hot: Vec<String>,
+}
+
+impl AlertThresholds {
+    /// Compare `metrics` against the warning and critical thresholds
+    pub fn assess(&self, metrics: &VMMetrics) -> ResourcePressure {
+        let checks = [
+            (
+                "cpu",
+                metrics.cpu_usage,
+                self.cpu_usage_warning,
+                self.cpu_usage_critical,
+            ),
+            (
+                "mem",
+                metrics.memory_percent(),
+                self.memory_usage_warning,
+                self.memory_usage_critical,
+            ),
+        ];
+
+        let mut level = Pressure::Normal;
+        let mut hot = Vec::new();
+        for (resource, value, warning, critical) in checks {
+            let resource_level = if value > critical {
+                Pressure::Critical
+            } else if value > warning {
+                Pressure::Warning
+            } else {
+                continue;
+            };
+            level = level.max(resource_level);
+            hot.push(resource.to_string());
+        }
+
+        ResourcePressure { level, hot }
+    }
+}
+
+/// Resource to rank VMs by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricsSortKey {
+    Cpu,
+    Memory,
+    Network,
+}
+
+impl MetricsSortKey {
+    /// Orders the busiest VM first
+    pub fn compare(self, a: &VMMetrics, b: &VMMetrics) -> Ordering {
+        match self {
+            MetricsSortKey::Cpu => b.cpu_usage.total_cmp(&a.cpu_usage),
+            MetricsSortKey::Memory => b.memory_percent().total_cmp(&a.memory_percent()),
+            MetricsSortKey::Network => b.network_bytes().cmp(&a.network_bytes()),
+        }
+    }
+}
+
 impl MonitoringService {
     pub fn new(metrics_collector: Box<dyn MetricsCollector>) -> Self {
         Self {
@@ -145,6 +387,74 @@ impl MonitoringService {
             logs: Arc::new(RwLock::new(Vec::new())),
             vm_instances: Arc::new(RwLock::new(HashMap::new())),
             alert_thresholds: AlertThresholds::default(),
+            violation_limiter: Mutex::new(AlertRateLimiter::new(SECURITY_ALERT_WINDOW)),
+            feed: MetricsFeed::new(),
+            memory_cap: MONITORING_MEMORY_CAP,
+            overflow: None,
+            alert_sinks: Vec::new(),
+            alert_routes: AlertRoutes::route_all(),
+            log_shipper: None,
+            shipping: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Deliver alerts to `sink` as well, under `name` in the routing table
+    pub fn with_alert_sink(mut self, name: &str, sink: Arc<dyn AlertSink>) -> Self {
+        self.alert_sinks.push((name.to_string(), sink));
+        self
+    }
+
+    /// Send alerts only to the sinks `routes` picks for them
+    pub fn with_alert_routes(self, routes: AlertRoutes) -> Self {
+        Self {
+            alert_routes: routes,
+            ..self
+        }
+    }
+
+    /// Keep at most `cap` log entries and `cap` alerts in memory. Once
+    /// either goes over, its oldest half is appended to `store` and dropped
+    /// from memory; [`get_logs`](Self::get_logs) and
+    /// [`get_alerts`](Self::get_alerts) read them back from there.
+    pub fn with_overflow(self, store: LogStore, cap: usize) -> Self {
+        Self {
+            memory_cap: cap,
+            overflow: Some(store),
+            ..self
+        }
+    }
+
+    /// Ship the guest logs of every tracked VM that is running and has
+    /// `logging.paths` configured, from when it starts until it stops or
+    /// is deleted
+    pub fn with_log_shipper(mut self, shipper: Arc<dyn LogShipper>) -> Self {
+        self.log_shipper = Some(shipper);
+        self
+    }
+
+    /// Log entries and alerts currently held in memory
+    pub async fn memory_usage(&self) -> (usize, usize) {
+        (self.logs.read().await.len(), self.alerts.read().await.len())
+    }
+
+    /// How many of `held` entries to move out of memory
+    fn overflow_count(&self, held: usize) -> usize {
+        if held > self.memory_cap {
+            held - self.memory_cap / 2
+        } else {
+            0
+        }
+    }
+
+    /// Every sample collected by [`start_monitoring`](Self::start_monitoring)
+    pub fn metrics_feed(&self) -> &MetricsFeed {
+        &self.feed
+    }
+
+    pub fn with_security_alert_window(self, window: Duration) -> Self {
+        Self {
+            violation_limiter: Mutex::new(AlertRateLimiter::new(window)),
+            ..self
         }
     }
 
@@ -165,6 +475,7 @@ impl MonitoringService {
         // Collect system metrics
         let system_metrics = self.metrics_collector.collect_system_metrics().await?;
         self.analyze_system_metrics(&system_metrics).await?;
+        self.feed.publish(MetricsSample::System(system_metrics));
 
         // Collect VM metrics
         let vm_instances = self.vm_instances.read().await;
@@ -173,6 +484,10 @@ impl MonitoringService {
                 match self.metrics_collector.collect_metrics(vm_id).await {
                     Ok(metrics) => {
                         self.analyze_vm_metrics(vm_id, &metrics).await?;
+                        self.feed.publish(MetricsSample::Vm {
+                            vm_id: vm_id.clone(),
+                            metrics,
+                        });
                     }
                     Err(e) => {
                         warn!("Failed to collect metrics for VM {}: {}", vm_id, e);
@@ -254,12 +569,30 @@ impl MonitoringService {
             .await?;
         }
 
+        // Check errors and drops on the VM network interfaces
+        let error_rate = metrics.network_stats.error_rate();
+        let severity = if error_rate > self.alert_thresholds.network_error_rate_critical {
+            Some(AlertSeverity::Critical)
+        } else if error_rate > self.alert_thresholds.network_error_rate_warning {
+            Some(AlertSeverity::Medium)
+        } else {
+            None
+        };
+        if let Some(severity) = severity {
+            self.create_alert(
+                None,
+                AlertType::NetworkConnectivity,
+                severity,
+                format!("{error_rate:.1}% of VM network packets had errors or were dropped"),
+            )
+            .await?;
+        }
+
         Ok(())
     }
 
-    async fn analyze_vm_metrics(&self, vm_id: &str, metrics: &VMMetrics) -> Result<()> {
-        let memory_usage_percent =
-            (metrics.memory_usage.used_mb as f64 / metrics.memory_usage.total_mb as f64) * 100.0;
+    pub(crate) async fn analyze_vm_metrics(&self, vm_id: &str, metrics: &VMMetrics) -> Result<()> {
+        let memory_usage_percent = metrics.memory_percent();
 
         // Check VM CPU usage
         if metrics.cpu_usage > self.alert_thresholds.cpu_usage_critical {
@@ -297,13 +630,13 @@ impl MonitoringService {
         Ok(())
     }
 
-    async fn create_alert(
+    pub(crate) async fn create_alert(
         &self,
         vm_id: Option<String>,
         alert_type: AlertType,
         severity: AlertSeverity,
         message: String,
-    ) -> Result<()> {
+    ) -> Result<Alert> {
         let alert = Alert {
             id: Uuid::new_v4(),
             vm_id,
@@ -315,7 +648,24 @@ impl MonitoringService {
             resolved_at: None,
         };
 
-        self.alerts.write().await.push(alert.clone());
+        {
+            let mut alerts = self.alerts.write().await;
+            alerts.push(alert.clone());
+            let excess = self.overflow_count(alerts.len());
+            if excess > 0 {
+                let flushed: Vec<Alert> = alerts.drain(..excess).collect();
+                if let Some(store) = &self.overflow {
+                    for (i, old) in flushed.iter().enumerate() {
+                        if let Err(e) = store.append_alert(old).await {
+                            alerts.splice(0..0, flushed[i..].iter().cloned());
+                            return Err(e);
+                        }
+                    }
+                } else {
+                    debug!("Dropped {} alerts over the in-memory cap", excess);
+                }
+            }
+        }
 
         // Log the alert
         match severity {
@@ -325,24 +675,91 @@ impl MonitoringService {
             AlertSeverity::Low => info!("LOW ALERT: {}", alert.message),
         }
 
-        Ok(())
+        self.dispatch_alert(&alert).await;
+
+        Ok(alert)
     }
 
-    pub async fn get_alerts(&self, vm_id: Option<&str>) -> Result<Vec<Alert>> {
-        let alerts = self.alerts.read().await;
-        let filtered_alerts: Vec<Alert> = alerts
+    /// Hand `alert` to the sinks the routing table picks. A failing sink
+    /// does not keep the alert from the others.
+    async fn dispatch_alert(&self, alert: &Alert) {
+        let names: Vec<&str> = self
+            .alert_sinks
             .iter()
-            .filter(|alert| {
-                if let Some(vm_id) = vm_id {
-                    alert.vm_id.as_ref() == Some(&vm_id.to_string())
-                } else {
-                    true
-                }
-            })
-            .cloned()
+            .map(|(name, _)| name.as_str())
             .collect();
+        let routed = self.alert_routes.sinks_for(alert, &names);
 
-        Ok(filtered_alerts)
+        for (name, sink) in &self.alert_sinks {
+            if !routed.contains(&name.as_str()) {
+                continue;
+            }
+            if let Err(e) = sink.send(alert).await {
+                warn!("Failed to send alert {} to sink {}: {}", alert.id, name, e);
+            }
+        }
+    }
+
+    /// Raise a [`AlertType::SecurityViolation`] for a syscall blocked or
+    /// logged by a VM's seccomp filter. Alerts are rate limited per VM and
+    /// syscall; returns `None` when this one was suppressed.
+    pub async fn report_security_violation(
+        &self,
+        vm_id: &str,
+        syscall: &str,
+        severity: AlertSeverity,
+        message: String,
+    ) -> Result<Option<Alert>> {
+        let suppressed = self
+            .violation_limiter
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .check(&format!("{vm_id}/{syscall}"), Instant::now());
+
+        let Some(suppressed) = suppressed else {
+            debug!("Rate limited security alert for {} ({})", vm_id, syscall);
+            return Ok(None);
+        };
+
+        let message = if suppressed > 0 {
+            format!("{message} ({suppressed} similar event(s) suppressed)")
+        } else {
+            message
+        };
+
+        self.create_alert(
+            Some(vm_id.to_string()),
+            AlertType::SecurityViolation,
+            severity,
+            message,
+        )
+        .await
+        .map(Some)
+    }
+
+    pub async fn get_alerts(&self, vm_id: Option<&str>) -> Result<Vec<Alert>> {
+        self.get_alerts_filtered(&AlertFilter {
+            vm_id: vm_id.map(str::to_string),
+            ..AlertFilter::default()
+        })
+        .await
+    }
+
+    /// Alerts matching `filter`, oldest first, including those flushed to
+    /// the overflow store
+    pub async fn get_alerts_filtered(&self, filter: &AlertFilter) -> Result<Vec<Alert>> {
+        // Held while reading the store so nothing is flushed in between
+        let alerts = self.alerts.read().await;
+        let mut merged = match &self.overflow {
+            Some(store) => store.read_alerts().await?,
+            None => Vec::new(),
+        };
+        let in_memory: HashSet<Uuid> = alerts.iter().map(|alert| alert.id).collect();
+        merged.retain(|alert| !in_memory.contains(&alert.id));
+        merged.extend(alerts.iter().cloned());
+
+        merged.retain(|alert| filter.matches(alert));
+        Ok(merged)
     }
 
     pub async fn resolve_alert(&self, alert_id: &Uuid) -> Result<()> {
@@ -351,23 +768,68 @@ impl MonitoringService {
             alert.resolved = true;
             alert.resolved_at = Some(Utc::now());
             info!("Resolved alert {}: {}", alert_id, alert.message);
+        } else if let Some(store) = &self.overflow
+            && let Some(mut alert) = store
+                .read_alerts()
+                .await?
+                .into_iter()
+                .find(|a| a.id == *alert_id)
+        {
+            alert.resolved = true;
+            alert.resolved_at = Some(Utc::now());
+            store.append_alert(&alert).await?;
+            info!("Resolved alert {}: {}", alert_id, alert.message);
         }
         Ok(())
     }
 
     pub async fn add_log_entry(&self, entry: LogEntry) -> Result<()> {
-        self.logs.write().await.push(entry);
+        let mut logs = self.logs.write().await;
+        logs.push(entry);
+
+        let excess = self.overflow_count(logs.len());
+        if excess == 0 {
+            return Ok(());
+        }
+        let flushed: Vec<LogEntry> = logs.drain(..excess).collect();
+        match &self.overflow {
+            Some(store) => {
+                for (i, old) in flushed.iter().enumerate() {
+                    // Keep what was not written; entries written twice are
+                    // told apart by id when read
+                    if let Err(e) = store.append(old).await {
+                        logs.splice(0..0, flushed[i..].iter().cloned());
+                        return Err(e);
+                    }
+                }
+            }
+            None => debug!("Dropped {} log entries over the in-memory cap", excess),
+        }
         Ok(())
     }
 
+    /// Entries of `vm_id`, or of all VMs, at `level` or more severe, oldest
+    /// first: `Warn` returns warnings and errors, `Trace` everything.
+    /// Includes entries flushed to the overflow store.
     pub async fn get_logs(
         &self,
         vm_id: Option<&str>,
         level: Option<LogLevel>,
     ) -> Result<Vec<LogEntry>> {
+        // Held while reading the store so nothing is flushed in between
         let logs = self.logs.read().await;
-        let filtered_logs: Vec<LogEntry> = logs
-            .iter()
+        let mut merged = match (&self.overflow, vm_id) {
+            (Some(store), Some(vm_id)) => store.read(vm_id).await?,
+            (Some(store), None) => store.read_all().await?,
+            (None, _) => Vec::new(),
+        };
+        merged.extend(logs.iter().cloned());
+        let mut seen = HashSet::new();
+        merged.retain(|log| seen.insert(log.id));
+        merged.sort_by_key(|log| log.timestamp);
+
+        let filtered_logs: Vec<LogEntry> = merged
+            .into_iter()
             .filter(|log| {
                 let vm_match = if let Some(vm_id) = vm_id {
                     log.vm_id.as_ref() == Some(&vm_id.to_string())
@@ -375,15 +837,10 @@ impl MonitoringService {
                     true
                 };
 
-                let level_match = if let Some(level) = level {
-                    log.level as u8 <= level as u8
-                } else {
-                    true
-                };
+                let level_match = level.is_none_or(|level| log.level >= level);
 
                 vm_match && level_match
             })
-            .cloned()
             .collect();
 
         Ok(filtered_logs)
@@ -411,6 +868,7 @@ impl MonitoringService {
 
     pub async fn unregister_vm(&self, vm_id: &str) -> Result<()> {
         self.vm_instances.write().await.remove(vm_id);
+        self.stop_log_shipping(vm_id);
 
         self.add_log_entry(LogEntry {
             id: Uuid::new_v4(),
@@ -425,6 +883,148 @@ impl MonitoringService {
         Ok(())
     }
 
+    /// Ids of the VMs currently monitored, sorted
+    pub async fn monitored_vms(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.vm_instances.read().await.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Ids of the VMs whose guest logs are being shipped, sorted
+    pub fn shipping_vms(&self) -> Vec<String> {
+        let shipping = self.shipping.lock().unwrap();
+        let mut ids: Vec<String> = shipping
+            .iter()
+            .filter(|(_, task)| !task.handle.is_finished())
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Start or stop shipping the guest logs of `vm` to match its state.
+    /// A stream the guest closed is restarted on the next update, and one
+    /// following other paths is replaced.
+    fn update_log_shipping(self: &Arc<Self>, vm: &VMInstance) {
+        let Some(shipper) = &self.log_shipper else {
+            return;
+        };
+        let vm_id = vm.id.to_string();
+        let paths = &vm.config.logging.paths;
+        if vm.state != VMState::Running || paths.is_empty() {
+            self.stop_log_shipping(&vm_id);
+            return;
+        }
+
+        let mut shipping = self.shipping.lock().unwrap();
+        if let Some(task) = shipping.get(&vm_id)
+            && !task.handle.is_finished()
+            && task.paths == *paths
+        {
+            return;
+        }
+        if let Some(old) = shipping.remove(&vm_id) {
+            old.handle.abort();
+        }
+
+        debug!("Shipping guest logs {:?} of VM {}", paths, vm.name);
+        let shipper = Arc::clone(shipper);
+        let monitoring = Arc::clone(self);
+        let vm = vm.clone();
+        let handle = tokio::spawn(async move {
+            let name = vm.name.clone();
+            if let Err(e) = shipper.ship(vm, monitoring).await {
+                warn!("Stopped shipping guest logs of VM {}: {}", name, e);
+            }
+        });
+        shipping.insert(
+            vm_id,
+            ShippingTask {
+                paths: paths.clone(),
+                handle,
+            },
+        );
+    }
+
+    fn stop_log_shipping(&self, vm_id: &str) {
+        if let Some(task) = self.shipping.lock().unwrap().remove(vm_id) {
+            task.handle.abort();
+        }
+    }
+
+    /// Keep the monitored set in line with an orchestrator event
+    pub async fn apply_vm_event(self: &Arc<Self>, event: VMEvent) -> Result<()> {
+        match event {
+            VMEvent::Updated(vm) => self.track_vm(*vm).await,
+            VMEvent::Deleted(id) => self.unregister_vm(&id.to_string()).await,
+            VMEvent::Loaded(vms) => self.sync_vms(vms).await,
+        }
+    }
+
+    /// Refresh a monitored VM, registering it when it is new
+    async fn track_vm(self: &Arc<Self>, vm: VMInstance) -> Result<()> {
+        self.update_log_shipping(&vm);
+        {
+            let mut instances = self.vm_instances.write().await;
+            if let Some(entry) = instances.get_mut(&vm.id.to_string()) {
+                *entry = vm;
+                return Ok(());
+            }
+        }
+        self.register_vm(vm).await
+    }
+
+    /// Make `vms` the monitored set, registering new VMs and unregistering
+    /// the ones that are gone
+    async fn sync_vms(self: &Arc<Self>, vms: Vec<VMInstance>) -> Result<()> {
+        let gone: Vec<String> = {
+            let instances = self.vm_instances.read().await;
+            instances
+                .keys()
+                .filter(|id| !vms.iter().any(|vm| vm.id.to_string() == **id))
+                .cloned()
+                .collect()
+        };
+        for vm_id in gone {
+            self.unregister_vm(&vm_id).await?;
+        }
+        for vm in vms {
+            self.track_vm(vm).await?;
+        }
+        Ok(())
+    }
+
+    /// Register every VM of `vm_manager` and follow its lifecycle events
+    /// until the orchestrator is dropped. Subscribing before the initial
+    /// listing means no change between the two is missed.
+    pub async fn follow(
+        self: &Arc<Self>,
+        vm_manager: Arc<VMOrchestrator>,
+    ) -> Result<tokio::task::JoinHandle<()>> {
+        let mut events = vm_manager.events().subscribe();
+        self.sync_vms(vm_manager.list_vms().await?).await?;
+
+        let monitoring = Arc::clone(self);
+        Ok(tokio::spawn(async move {
+            loop {
+                let result = match events.recv().await {
+                    Ok(event) => monitoring.apply_vm_event(event).await,
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Monitoring missed {} VM events, resynchronizing", missed);
+                        match vm_manager.list_vms().await {
+                            Ok(vms) => monitoring.sync_vms(vms).await,
+                            Err(e) => Err(e),
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if let Err(e) = result {
+                    error!("Failed to track VM event: {}", e);
+                }
+            }
+        }))
+    }
+
     pub async fn get_vm_metrics(&self, vm_id: &str) -> Result<VMMetrics> {
         self.metrics_collector.collect_metrics(vm_id).await
     }
@@ -461,6 +1061,16 @@ impl std::fmt::Display for AlertSeverity {
     }
 }
 
+impl std::fmt::Display for Pressure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pressure::Normal => write!(f, "normal"),
+            Pressure::Warning => write!(f, "warning"),
+            Pressure::Critical => write!(f, "critical"),
+        }
+    }
+}
+
 impl std::fmt::Display for AlertType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -529,14 +1139,7 @@ impl MetricsCollector for DefaultMetricsCollector {
                 available_gb: 500.0,
                 usage_percent: 0.0,
             },
-            network_stats: NetworkStats {
-                rx_bytes: 0,
-                tx_bytes: 0,
-                rx_packets: 0,
-                tx_packets: 0,
-                errors: 0,
-                drops: 0,
-            },
+            network_stats: NetworkStats::default(),
             active_vms: 0,
             timestamp: Utc::now(),
         })
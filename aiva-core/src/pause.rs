@@ -0,0 +1,117 @@
+//! Pausing VMs in memory or to disk.
+//!
+//! An in-memory pause stops the vCPUs but keeps the hypervisor process and
+//! the guest memory, so the VM sits in `Paused`. Hibernating writes a full
+//! snapshot of the VM and its memory to disk and ends the process; the VM
+//! moves on to `Stopped` and only holds disk space until it is resumed from
+//! the snapshot, with its processes, MCP servers included, as they were.
+//! [`RuntimeInfo::paused`] records which of the two a VM is in, so resuming
+//! takes the matching path.
+
+use crate::error::{AivaError, Result};
+use crate::types::{RuntimeInfo, VMInstance, VMState};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Snapshot written when a VM hibernates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFiles {
+    /// Device and vCPU state
+    pub state: PathBuf,
+    /// Guest memory
+    pub memory: PathBuf,
+}
+
+/// What a snapshot holds of guest memory, named as in Firecracker's API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SnapshotType {
+    /// All of it; the snapshot restores on its own
+    Full,
+    /// Only the pages dirtied since the previous snapshot, which it is
+    /// layered on. Needs dirty page tracking enabled in the VMM.
+    Diff,
+}
+
+/// How a paused VM is held
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum PauseState {
+    /// The process is alive with its vCPUs stopped
+    InMemory,
+    /// The process is gone; the VM lives on in `snapshot`
+    Hibernated { snapshot: SnapshotFiles },
+}
+
+impl PauseState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InMemory => "in_memory",
+            Self::Hibernated { .. } => "hibernated",
+        }
+    }
+
+    /// State of a VM paused this way
+    pub fn vm_state(&self) -> VMState {
+        match self {
+            Self::InMemory => VMState::Paused,
+            Self::Hibernated { .. } => VMState::Stopped,
+        }
+    }
+}
+
+/// How [`VMManager::resume_vm`](crate::VMManager::resume_vm) brings a VM back
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResumePath {
+    /// Let the vCPUs of the existing process run again
+    Resume,
+    /// Start a new process from the snapshot
+    Restore(SnapshotFiles),
+}
+
+/// Check that `vm` can be paused, or hibernated with `hibernate`. A VM
+/// paused in memory can still be hibernated.
+pub fn check_pause(vm: &VMInstance, hibernate: bool) -> Result<()> {
+    match (vm.state, &vm.runtime.paused) {
+        (VMState::Running, _) => Ok(()),
+        (VMState::Paused, Some(PauseState::InMemory) | None) if hibernate => Ok(()),
+        (VMState::Paused, _) => Err(AivaError::InvalidStateTransition(format!(
+            "VM '{}' is already paused; use --hibernate to move it to disk",
+            vm.name
+        ))),
+        (state, _) => Err(AivaError::InvalidStateTransition(format!(
+            "Cannot pause VM '{}' in state {state:?}",
+            vm.name
+        ))),
+    }
+}
+
+/// Path that resumes `vm`, which must have been paused or hibernated
+pub fn resume_path(vm: &VMInstance) -> Result<ResumePath> {
+    match (vm.state, &vm.runtime.paused) {
+        (VMState::Stopped, Some(PauseState::Hibernated { snapshot })) => {
+            Ok(ResumePath::Restore(snapshot.clone()))
+        }
+        // State written before pauses were tracked has no record
+        (VMState::Paused, Some(PauseState::InMemory) | None) => Ok(ResumePath::Resume),
+        (state, _) => Err(AivaError::InvalidStateTransition(format!(
+            "Cannot resume VM '{}' in state {state:?}; it is neither paused nor hibernated",
+            vm.name
+        ))),
+    }
+}
+
+impl RuntimeInfo {
+    /// Record that the VM was paused as `pause`. A hibernated VM has no
+    /// process or API socket left.
+    pub fn record_pause(&mut self, pause: PauseState) {
+        if matches!(pause, PauseState::Hibernated { .. }) {
+            self.pid = None;
+            self.api_socket = None;
+        }
+        self.paused = Some(pause);
+    }
+
+    pub fn is_hibernated(&self) -> bool {
+        matches!(self.paused, Some(PauseState::Hibernated { .. }))
+    }
+}
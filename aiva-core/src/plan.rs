@@ -0,0 +1,69 @@
+//! Side-effect free preview of VM creation.
+//!
+//! Creating a VM copies multi-gigabyte images, creates network devices and
+//! spawns processes. A [`CreatePlan`] describes all of that up front, along
+//! with anything that would collide with VMs that already exist.
+
+use crate::templates::VMTemplate;
+use crate::types::PortMapping;
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// An image the platform copies for a new VM
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageCopy {
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    /// Size of the source, when it exists yet
+    pub size_bytes: Option<u64>,
+}
+
+impl ImageCopy {
+    pub fn new(source: PathBuf, destination: PathBuf) -> Self {
+        let size_bytes = std::fs::metadata(&source).ok().map(|m| m.len());
+        Self {
+            source,
+            destination,
+            size_bytes,
+        }
+    }
+}
+
+/// What a platform would set up when creating a VM
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlatformPlan {
+    pub image_copies: Vec<ImageCopy>,
+    /// Host resources that would be allocated (devices, processes, ...)
+    pub resources: Vec<String>,
+}
+
+/// Everything `create_vm` would do for a VM, computed without doing it
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatePlan {
+    pub name: String,
+    pub template: Option<String>,
+    pub platform: String,
+    pub cpus: u32,
+    pub memory_mb: u64,
+    pub disk_gb: u64,
+    pub guest_ip: String,
+    pub host_ip: String,
+    pub gateway: String,
+    pub port_mappings: Vec<PortMapping>,
+    pub security_policy: Option<String>,
+    pub image_copies: Vec<ImageCopy>,
+    pub resources: Vec<String>,
+    /// Problems that would make the real creation fail or misbehave
+    pub conflicts: Vec<String>,
+}
+
+impl CreatePlan {
+    pub fn with_template(mut self, template: &VMTemplate) -> Self {
+        self.template = Some(template.name.clone());
+        self
+    }
+
+    pub fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+}
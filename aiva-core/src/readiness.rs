@@ -0,0 +1,262 @@
+//! Post-boot readiness checks.
+//!
+//! A rootfs without `ip` or a DHCP client boots fine but never brings its
+//! interface up, and the first symptom is usually a connection error from a
+//! much later command. These checks run through the guest agent right after
+//! boot and turn that situation into an actionable error. The same module
+//! polls host ports so `aiva run` can confirm a server is actually listening.
+
+use crate::error::*;
+use crate::types::*;
+use crate::vm::VMManager;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+use uuid::Uuid;
+
+/// Host name resolved by the guest to check DNS
+const DNS_PROBE_HOST: &str = "example.com";
+const AGENT_PROBE_COMMAND: &str = "echo aiva-ready";
+const AGENT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const PORT_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Upper bound for a single connection or HTTP attempt while polling a port
+const PORT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How [`wait_for_port`] decides that a server is ready
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortProbe {
+    /// The port accepts a TCP connection
+    Tcp,
+    /// `GET <path>` answers with a non-5xx status
+    Http { path: String },
+}
+
+/// Result of the guest network probe
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuestNetworkProbe {
+    /// Networking tools the rootfs does not provide
+    pub missing_tools: Vec<String>,
+    /// First global IPv4 address configured in the guest
+    pub address: Option<String>,
+    pub gateway_reachable: bool,
+    pub dns_resolves: bool,
+}
+
+impl GuestNetworkProbe {
+    /// Shell command the agent runs to collect the probe. Every line of its
+    /// output is a `key=value` pair understood by [`GuestNetworkProbe::parse`].
+    pub fn command(network: &NetworkConfig) -> String {
+        [
+            "command -v ip >/dev/null 2>&1 || echo missing=ip".to_string(),
+            "{ command -v dhclient || command -v udhcpc || command -v dhcpcd; } >/dev/null 2>&1 \
+             || echo missing=dhcp-client"
+                .to_string(),
+            "echo addr=$(ip -4 -o addr show scope global 2>/dev/null | awk '{print $4}' | head -n 1)"
+                .to_string(),
+            format!(
+                "ping -c 1 -W 2 {} >/dev/null 2>&1 && echo gateway=ok || echo gateway=fail",
+                shell_quote(&network.gateway)
+            ),
+            format!(
+                "{{ getent hosts {DNS_PROBE_HOST} || nslookup {DNS_PROBE_HOST}; }} >/dev/null 2>&1 \
+                 && echo dns=ok || echo dns=fail"
+            ),
+        ]
+        .join("; ")
+    }
+
+    pub fn parse(output: &str) -> Self {
+        let mut probe = Self::default();
+
+        for line in output.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            match key {
+                "missing" => probe.missing_tools.push(value.to_string()),
+                "addr" if !value.is_empty() => {
+                    // `ip -o` prints the address with its prefix length
+                    let address = value.split('/').next().unwrap_or(value);
+                    probe.address = Some(address.to_string());
+                }
+                "gateway" => probe.gateway_reachable = value == "ok",
+                "dns" => probe.dns_resolves = value == "ok",
+                _ => {}
+            }
+        }
+
+        probe
+    }
+
+    /// Explain why the guest network is not usable, if it isn't
+    pub fn diagnose(&self, vm_name: &str, network: &NetworkConfig) -> Result<()> {
+        let unavailable = |reason: String, hint: String| AivaError::GuestNetworkUnavailable {
+            vm_name: vm_name.to_string(),
+            reason,
+            hint,
+        };
+
+        let Some(address) = &self.address else {
+            // A static setup never needs a DHCP client
+            let missing: Vec<&str> = self
+                .missing_tools
+                .iter()
+                .map(String::as_str)
+                .filter(|tool| network.dhcp_enabled || *tool != "dhcp-client")
+                .collect();
+
+            let reason = if missing.is_empty() {
+                "the guest did not get an IPv4 address".to_string()
+            } else {
+                format!(
+                    "the guest did not get an IPv4 address and its rootfs is missing {}",
+                    missing.join(", ")
+                )
+            };
+
+            let hint = if network.dhcp_enabled {
+                "DHCP is enabled: make sure the rootfs ships iproute2 and a DHCP client \
+                 (dhclient, udhcpc or dhcpcd) and runs it on eth0 at boot"
+                    .to_string()
+            } else {
+                format!(
+                    "Static addressing is configured: the guest must assign {} on eth0 with \
+                     gateway {}, either from its init scripts or the kernel 'ip=' boot argument",
+                    network.guest_ip, network.gateway
+                )
+            };
+
+            return Err(unavailable(reason, hint));
+        };
+
+        if !self.gateway_reachable {
+            return Err(unavailable(
+                format!(
+                    "the guest has address {address} but cannot reach gateway {}",
+                    network.gateway
+                ),
+                format!(
+                    "Check that the address is in subnet {} and that the host TAP device is up",
+                    network.subnet
+                ),
+            ));
+        }
+
+        if !self.dns_resolves {
+            return Err(unavailable(
+                "the guest cannot resolve host names".to_string(),
+                format!(
+                    "Make sure /etc/resolv.conf in the guest lists {}",
+                    network.dns_servers.join(", ")
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Poll the guest agent until it answers or `timeout` elapses
+pub async fn wait_for_guest(
+    vm_manager: &dyn VMManager,
+    id: &Uuid,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        match vm_manager.execute_command(id, AGENT_PROBE_COMMAND).await {
+            Ok(output) if output.contains("aiva-ready") => return Ok(()),
+            Ok(_) | Err(_) if Instant::now() < deadline => {
+                tokio::time::sleep(AGENT_POLL_INTERVAL).await;
+            }
+            Ok(output) => {
+                return Err(AivaError::NetworkError {
+                    operation: "guest readiness".to_string(),
+                    cause: format!("unexpected agent response: {}", output.trim()),
+                });
+            }
+            Err(e) => {
+                debug!("Guest agent did not answer before the timeout: {}", e);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Run the network probe in a booted guest and fail with a
+/// [`AivaError::GuestNetworkUnavailable`] when networking is not usable
+pub async fn check_guest_network(
+    vm_manager: &dyn VMManager,
+    vm: &VMInstance,
+) -> Result<GuestNetworkProbe> {
+    let network = &vm.config.network;
+    let output = vm_manager
+        .execute_command(&vm.id, &GuestNetworkProbe::command(network))
+        .await?;
+
+    let probe = GuestNetworkProbe::parse(&output);
+    debug!("Guest network probe for {}: {:?}", vm.name, probe);
+    probe.diagnose(&vm.name, network)?;
+
+    Ok(probe)
+}
+
+/// Poll `addr` until it passes `probe`, returning how long that took.
+/// Fails with a [`AivaError::NetworkError`] once `timeout` elapses.
+pub async fn wait_for_port(
+    addr: SocketAddr,
+    probe: &PortProbe,
+    timeout: Duration,
+) -> Result<Duration> {
+    let started = Instant::now();
+    let deadline = started + timeout;
+    let client = reqwest::Client::builder()
+        .timeout(PORT_ATTEMPT_TIMEOUT)
+        .build()
+        .map_err(|e| AivaError::Other(e.into()))?;
+
+    loop {
+        let last_error = match probe_port(&client, addr, probe).await {
+            Ok(()) => return Ok(started.elapsed()),
+            Err(e) => e,
+        };
+
+        if Instant::now() >= deadline {
+            return Err(AivaError::NetworkError {
+                operation: format!("waiting for {addr}"),
+                cause: format!("not ready after {}s: {last_error}", timeout.as_secs_f64()),
+            });
+        }
+        tokio::time::sleep(PORT_POLL_INTERVAL).await;
+    }
+}
+
+async fn probe_port(
+    client: &reqwest::Client,
+    addr: SocketAddr,
+    probe: &PortProbe,
+) -> std::result::Result<(), String> {
+    match probe {
+        PortProbe::Tcp => {
+            tokio::time::timeout(PORT_ATTEMPT_TIMEOUT, tokio::net::TcpStream::connect(addr))
+                .await
+                .map_err(|_| "connection attempt timed out".to_string())?
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        }
+        PortProbe::Http { path } => {
+            let path = path.trim_start_matches('/');
+            let response = client
+                .get(format!("http://{addr}/{path}"))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?;
+            if response.status().is_server_error() {
+                return Err(format!("health check returned {}", response.status()));
+            }
+            Ok(())
+        }
+    }
+}
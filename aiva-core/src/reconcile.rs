@@ -0,0 +1,79 @@
+//! Agreement between the stored VM state and the hypervisor.
+//!
+//! `vm_state.json` is only updated by aiva itself. A Firecracker process that
+//! crashes leaves a VM marked `Running`, and one started or left behind by
+//! hand runs under a VM marked `Stopped`. `aiva status` compares the two and
+//! can correct the stored state.
+
+use crate::types::{VMInstance, VMState};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// What the platform observed about the hypervisor process of a VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Liveness {
+    Alive,
+    Dead,
+    /// The platform cannot tell, e.g. the VM runs inside Lima or WSL
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// Marked running or paused, but the hypervisor process is gone
+    ProcessDead,
+    /// Marked stopped, but the hypervisor process still runs
+    ProcessAlive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateDivergence {
+    pub vm_id: Uuid,
+    pub vm_name: String,
+    pub stored: VMState,
+    pub kind: DivergenceKind,
+}
+
+impl StateDivergence {
+    /// The divergence of `vm`, if any, given what the platform observed
+    pub fn check(vm: &VMInstance, liveness: Liveness) -> Option<Self> {
+        detect_divergence(vm.state, liveness).map(|kind| StateDivergence {
+            vm_id: vm.id,
+            vm_name: vm.name.clone(),
+            stored: vm.state,
+            kind,
+        })
+    }
+
+    /// State that matches what was observed. A VM whose process died is
+    /// put in `Error` so `aiva recover` releases what it left behind.
+    pub fn corrected_state(&self) -> VMState {
+        match self.kind {
+            DivergenceKind::ProcessDead => VMState::Error,
+            DivergenceKind::ProcessAlive => VMState::Running,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let observed = match self.kind {
+            DivergenceKind::ProcessDead => "process dead",
+            DivergenceKind::ProcessAlive => "process running",
+        };
+        format!(
+            "VM '{}': state={:?} but {observed}",
+            self.vm_name, self.stored
+        )
+    }
+}
+
+/// Compare `stored` with `liveness`. VMs in a transitional state or in
+/// `Error` are left alone, stuck transitions are reset separately.
+pub fn detect_divergence(stored: VMState, liveness: Liveness) -> Option<DivergenceKind> {
+    match (stored, liveness) {
+        (VMState::Running | VMState::Paused, Liveness::Dead) => Some(DivergenceKind::ProcessDead),
+        (VMState::Stopped, Liveness::Alive) => Some(DivergenceKind::ProcessAlive),
+        _ => None,
+    }
+}
@@ -0,0 +1,129 @@
+//! Repairs made by `aiva doctor --fix`.
+//!
+//! Only what nobody is using is touched: VMs whose `Creating` or `Stopping`
+//! transition stalled, and host resources that no VM in the state file owns.
+//! A running VM keeps its state and its resources. Every repair is logged
+//! and reported together with how to undo it, where that is possible, and
+//! problems that need a person are reported as manual steps instead.
+
+use crate::error::Result;
+use crate::vm::{VMManager, VMOrchestrator};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+/// A host resource that looks like aiva's but belongs to no known VM, such
+/// as the TAP device or jailer workspace of a VM deleted by hand
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanedResource {
+    TapDevice(String),
+    Workspace(PathBuf),
+}
+
+impl std::fmt::Display for OrphanedResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TapDevice(name) => write!(f, "TAP device {name}"),
+            Self::Workspace(path) => write!(f, "jailer workspace {}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixStatus {
+    Fixed,
+    Failed,
+    /// Needs someone to act, `detail` says what to do
+    Manual,
+}
+
+impl std::fmt::Display for FixStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Fixed => "fixed",
+            Self::Failed => "failed",
+            Self::Manual => "manual",
+        })
+    }
+}
+
+/// One problem `aiva doctor --fix` found and what became of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Remediation {
+    pub issue: String,
+    pub status: FixStatus,
+    /// What was done, why it failed, or what to do by hand
+    pub detail: String,
+    /// How to reverse the fix, `None` where it cannot be
+    pub undo: Option<String>,
+}
+
+impl Remediation {
+    pub fn fixed(
+        issue: impl Into<String>,
+        detail: impl Into<String>,
+        undo: Option<String>,
+    ) -> Self {
+        let remediation = Self {
+            issue: issue.into(),
+            status: FixStatus::Fixed,
+            detail: detail.into(),
+            undo,
+        };
+        info!("Fixed {}: {}", remediation.issue, remediation.detail);
+        remediation
+    }
+
+    pub fn failed(issue: impl Into<String>, error: impl std::fmt::Display) -> Self {
+        let remediation = Self {
+            issue: issue.into(),
+            status: FixStatus::Failed,
+            detail: error.to_string(),
+            undo: None,
+        };
+        warn!(
+            "Could not fix {}: {}",
+            remediation.issue, remediation.detail
+        );
+        remediation
+    }
+
+    pub fn manual(issue: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            issue: issue.into(),
+            status: FixStatus::Manual,
+            detail: hint.into(),
+            undo: None,
+        }
+    }
+}
+
+/// Reset VMs stuck in a transition and remove orphaned host resources.
+/// Running VMs are left as they are.
+pub async fn fix_fleet(vm_manager: &VMOrchestrator) -> Result<Vec<Remediation>> {
+    let mut remediations = Vec::new();
+
+    for (id, stuck_in) in vm_manager.reset_stuck_vms().await? {
+        let name = vm_manager
+            .get_vm(&id)
+            .await?
+            .map_or_else(|| id.to_string(), |vm| vm.name);
+        remediations.push(Remediation::fixed(
+            format!("VM '{name}' stuck in {stuck_in:?}"),
+            "reset to Stopped",
+            None,
+        ));
+    }
+
+    for (resource, removed) in vm_manager.remove_orphans().await? {
+        let issue = format!("orphaned {resource}");
+        remediations.push(match removed {
+            Ok(()) => Remediation::fixed(issue, "removed", None),
+            Err(e) => Remediation::failed(issue, e),
+        });
+    }
+
+    Ok(remediations)
+}
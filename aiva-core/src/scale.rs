@@ -0,0 +1,126 @@
+//! Changing the vCPUs and memory of existing VMs.
+//!
+//! Firecracker cannot hotplug vCPUs or add memory to a running guest. What it
+//! can do is inflate a balloon device to take memory back from the guest and
+//! deflate it again, up to the memory the VM booted with. [`plan_scale`]
+//! decides which part of a request can be applied live and which part needs
+//! the VM to restart.
+
+use crate::error::{AivaError, Result};
+use crate::types::VMConfig;
+use serde::{Deserialize, Serialize};
+
+/// Smallest memory size a VM may be scaled to
+pub const MIN_SCALE_MEMORY_MB: u64 = 128;
+
+/// Target resources of `aiva scale`; `None` keeps the current value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScaleRequest {
+    pub cpus: Option<u32>,
+    pub memory_mb: Option<u64>,
+}
+
+/// What a running VM supports changing without a restart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScaleCapabilities {
+    /// Memory the VM booted with, when it has a balloon device. Memory can
+    /// be resized live anywhere up to this amount.
+    pub balloon_max_mb: Option<u64>,
+    pub cpu_hotplug: bool,
+}
+
+/// A change applied to a running VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScaleStep {
+    /// Inflate the balloon to `balloon_mib`, leaving the guest `memory_mb`
+    Balloon {
+        memory_mb: u64,
+        balloon_mib: u64,
+    },
+    HotplugCpus {
+        cpus: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScalePlan {
+    pub cpus: u32,
+    pub memory_mb: u64,
+    /// Steps that take effect on the running VM
+    pub live: Vec<ScaleStep>,
+    /// Why the rest only takes effect after a restart
+    pub restart_reasons: Vec<String>,
+}
+
+impl ScalePlan {
+    pub fn restart_required(&self) -> bool {
+        !self.restart_reasons.is_empty()
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.live.is_empty() && self.restart_reasons.is_empty()
+    }
+}
+
+/// Decide how to move a VM from `current` to `request`. Stopped VMs pick
+/// the new values up on their next start, so they never need a restart.
+pub fn plan_scale(
+    current: &VMConfig,
+    running: bool,
+    request: ScaleRequest,
+    capabilities: &ScaleCapabilities,
+) -> Result<ScalePlan> {
+    let cpus = request.cpus.unwrap_or(current.cpus);
+    let memory_mb = request.memory_mb.unwrap_or(current.memory_mb);
+    if cpus == 0 {
+        return Err(AivaError::ConfigError(
+            "A VM needs at least one vCPU".to_string(),
+        ));
+    }
+    if memory_mb < MIN_SCALE_MEMORY_MB {
+        return Err(AivaError::ConfigError(format!(
+            "A VM needs at least {MIN_SCALE_MEMORY_MB}MB of memory"
+        )));
+    }
+
+    let mut plan = ScalePlan {
+        cpus,
+        memory_mb,
+        live: Vec::new(),
+        restart_reasons: Vec::new(),
+    };
+    if !running {
+        return Ok(plan);
+    }
+
+    if cpus != current.cpus {
+        if capabilities.cpu_hotplug {
+            plan.live.push(ScaleStep::HotplugCpus { cpus });
+        } else {
+            plan.restart_reasons.push(format!(
+                "vCPUs cannot be hotplugged, {} -> {cpus} needs a restart",
+                current.cpus
+            ));
+        }
+    }
+
+    if memory_mb != current.memory_mb {
+        match capabilities.balloon_max_mb {
+            Some(max) if memory_mb <= max => plan.live.push(ScaleStep::Balloon {
+                memory_mb,
+                balloon_mib: max - memory_mb,
+            }),
+            Some(max) => plan.restart_reasons.push(format!(
+                "the balloon can only give back memory up to the {max}MB the VM booted with, \
+                 {memory_mb}MB needs a restart"
+            )),
+            None => plan.restart_reasons.push(format!(
+                "the VM has no balloon device, {}MB -> {memory_mb}MB needs a restart",
+                current.memory_mb
+            )),
+        }
+    }
+
+    Ok(plan)
+}
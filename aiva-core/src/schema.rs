@@ -0,0 +1,127 @@
+//! Versioning of the stored VM records.
+//!
+//! `vm_state.json` and the per-VM `config.json` carry a `schema_version`.
+//! Records without one are version 0. On load, each record is upgraded step
+//! by step to [`SCHEMA_VERSION`] on its JSON form, the remaining new fields
+//! take their serde defaults, and callers write the migrated record back so
+//! the file on disk has the current shape.
+
+use crate::error::{AivaError, Result};
+use crate::types::{VMConfig, VMInstance};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::info;
+use uuid::Uuid;
+
+/// Version of the VM records written by this build
+pub const SCHEMA_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// Steps upgrading a VM config, index `n` goes from version `n` to `n + 1`
+const CONFIG_MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [config_v0_to_v1];
+
+/// Port mappings predate `host_ip`, and the default of a missing one is
+/// loopback. Pin it so a later change of the default cannot expose them.
+fn config_v0_to_v1(config: &mut Map<String, Value>) {
+    let mappings = config
+        .get_mut("network")
+        .and_then(|network| network.get_mut("port_mappings"))
+        .and_then(Value::as_array_mut);
+    for mapping in mappings.into_iter().flatten() {
+        if let Some(mapping) = mapping.as_object_mut() {
+            mapping
+                .entry("host_ip")
+                .or_insert_with(|| Value::from("127.0.0.1"));
+        }
+    }
+}
+
+fn record_version(record: &Map<String, Value>, what: &str) -> Result<u32> {
+    let version = match record.get("schema_version") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| {
+                AivaError::ConfigError(format!("Invalid schema_version {value} in {what}"))
+            })?,
+    };
+    if version > SCHEMA_VERSION {
+        return Err(AivaError::ConfigError(format!(
+            "{what} has schema version {version}, this aiva supports up to {SCHEMA_VERSION}. \
+             Upgrade aiva to use it."
+        )));
+    }
+    Ok(version)
+}
+
+fn as_record<'a>(value: &'a mut Value, what: &str) -> Result<&'a mut Map<String, Value>> {
+    value
+        .as_object_mut()
+        .ok_or_else(|| AivaError::ConfigError(format!("{what} is not a JSON object")))
+}
+
+fn upgrade_config(value: &mut Value, what: &str) -> Result<bool> {
+    let config = as_record(value, what)?;
+    let version = record_version(config, what)?;
+    for migration in &CONFIG_MIGRATIONS[version as usize..] {
+        migration(config);
+    }
+    config.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    Ok(version < SCHEMA_VERSION)
+}
+
+/// Upgrade a VM config in its JSON form. Returns the config and whether it
+/// was migrated.
+pub fn migrate_config(mut value: Value) -> Result<(VMConfig, bool)> {
+    let migrated = upgrade_config(&mut value, "VM config")?;
+    Ok((serde_json::from_value(value)?, migrated))
+}
+
+/// Upgrade a stored VM instance, including its config
+pub fn migrate_instance(mut value: Value) -> Result<(VMInstance, bool)> {
+    let what = "VM state record";
+    let record = as_record(&mut value, what)?;
+    let version = record_version(record, what)?;
+    let config_migrated = match record.get_mut("config") {
+        Some(config) => upgrade_config(config, "VM config in the state file")?,
+        None => false,
+    };
+    record.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+
+    let instance = serde_json::from_value(value)?;
+    Ok((instance, config_migrated || version < SCHEMA_VERSION))
+}
+
+/// Parse the contents of `vm_state.json`, upgrading every record
+pub fn migrate_state(content: &str) -> Result<(HashMap<Uuid, VMInstance>, bool)> {
+    let records: HashMap<Uuid, Value> = serde_json::from_str(content)?;
+    let mut vms = HashMap::with_capacity(records.len());
+    let mut migrated = false;
+
+    for (id, record) in records {
+        let (instance, upgraded) = migrate_instance(record)?;
+        migrated |= upgraded;
+        vms.insert(id, instance);
+    }
+    Ok((vms, migrated))
+}
+
+/// Read a per-VM `config.json`, writing it back when it had to be migrated
+pub fn read_vm_config(path: &Path) -> Result<VMConfig> {
+    let value: Value = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| AivaError::ConfigError(format!("Failed to parse config: {e}")))?;
+    let (config, migrated) = migrate_config(value)?;
+
+    if migrated {
+        std::fs::write(path, serde_json::to_string_pretty(&config)?)?;
+        info!(
+            "Migrated {} to schema version {}",
+            path.display(),
+            SCHEMA_VERSION
+        );
+    }
+    Ok(config)
+}
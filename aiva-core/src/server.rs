@@ -0,0 +1,172 @@
+//! Tracking of MCP servers launched by `aiva run`.
+//!
+//! The launcher records the server's PID in a file inside the guest. Before
+//! another server is started, that file is checked so a second `run` neither
+//! starts a duplicate nor fights the first one for its port. The PID is also
+//! kept in the VM's runtime information, so tearing the VM down stops
+//! exactly the servers it started.
+
+use crate::error::*;
+use crate::types::{VMInstance, VMState, shell_quote};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// How long a server gets to exit after SIGTERM before it is killed
+const STOP_GRACE_TICKS: u32 = 25;
+const STOP_TICK: &str = "0.2";
+
+/// Guest file the launcher redirects the output of the MCP server of
+/// `vm_name` to
+pub fn server_log_path(vm_name: &str) -> PathBuf {
+    PathBuf::from(format!("/tmp/mcp-{vm_name}.log"))
+}
+
+/// PID file of the MCP server of one VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerPidFile {
+    vm_name: String,
+    path: PathBuf,
+}
+
+impl ServerPidFile {
+    /// The guest path the launcher writes to
+    pub fn for_vm(vm_name: &str) -> Self {
+        Self::at(vm_name, PathBuf::from(format!("/tmp/mcp-{vm_name}.pid")))
+    }
+
+    pub fn at(vm_name: &str, path: PathBuf) -> Self {
+        Self {
+            vm_name: vm_name.to_string(),
+            path,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Shell command printing `running=<pid>` when the recorded process is
+    /// alive. A stale file is removed and nothing is printed.
+    pub fn probe_command(&self) -> String {
+        let path = shell_quote(&self.path.to_string_lossy());
+        format!(
+            "if [ -f {path} ]; then pid=$(cat {path}); \
+             if [ -n \"$pid\" ] && kill -0 \"$pid\" 2>/dev/null; then echo running=$pid; \
+             else rm -f {path}; fi; fi"
+        )
+    }
+
+    pub fn parse_probe(output: &str) -> Option<u32> {
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("running="))
+            .and_then(|pid| pid.parse().ok())
+    }
+
+    /// Shell command that stops `pid` with SIGTERM, falls back to SIGKILL
+    /// after a grace period and removes the PID file. Prints `stopped=term`,
+    /// `stopped=killed` or `stopped=failed`.
+    pub fn stop_command(&self, pid: u32) -> String {
+        let path = shell_quote(&self.path.to_string_lossy());
+        format!(
+            "kill {pid} 2>/dev/null; i=0; \
+             while kill -0 {pid} 2>/dev/null && [ $i -lt {STOP_GRACE_TICKS} ]; do sleep {STOP_TICK}; i=$((i+1)); done; \
+             if ! kill -0 {pid} 2>/dev/null; then echo stopped=term; rm -f {path}; \
+             else kill -9 {pid} 2>/dev/null; sleep {STOP_TICK}; \
+             if kill -0 {pid} 2>/dev/null; then echo stopped=failed; else echo stopped=killed; rm -f {path}; fi; fi"
+        )
+    }
+}
+
+/// How the MCP servers of a VM are found when it is torn down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerTeardown {
+    /// The PIDs recorded by `aiva run`, possibly none
+    Pids(Vec<u32>),
+    /// The VM comes from state without a PID record: stop the process in
+    /// the PID file and anything whose command line matches
+    Pattern(String),
+}
+
+impl ServerTeardown {
+    pub fn for_instance(instance: &VMInstance) -> Self {
+        Self::select(&instance.name, instance.runtime.mcp_pids.as_deref())
+    }
+
+    pub fn select(vm_name: &str, recorded_pids: Option<&[u32]>) -> Self {
+        match recorded_pids {
+            Some(pids) => Self::Pids(pids.to_vec()),
+            None => Self::Pattern(format!("mcp.*{vm_name}")),
+        }
+    }
+
+    /// Shell command stopping the servers where they run. It never fails,
+    /// servers that are already gone are skipped.
+    pub fn command(&self, pid_file: &ServerPidFile) -> String {
+        match self {
+            Self::Pids(pids) if pids.is_empty() => "true".to_string(),
+            Self::Pids(pids) => pids
+                .iter()
+                .map(|pid| pid_file.stop_command(*pid))
+                .collect::<Vec<_>>()
+                .join("; "),
+            Self::Pattern(pattern) => {
+                let path = shell_quote(&pid_file.path.to_string_lossy());
+                format!(
+                    "if [ -f {path} ]; then kill -9 $(cat {path}) 2>/dev/null; rm -f {path}; fi; \
+                     pkill -f {} 2>/dev/null || true",
+                    shell_quote(pattern)
+                )
+            }
+        }
+    }
+}
+
+/// Check for a server that is still running from an earlier `run`. With
+/// `restart` it is stopped and its PID returned, otherwise the collision is
+/// an error. `exec` runs a shell command where the server lives.
+pub async fn replace_existing_server<F, Fut>(
+    pid_file: &ServerPidFile,
+    restart: bool,
+    mut exec: F,
+) -> Result<Option<u32>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    let Some(pid) = ServerPidFile::parse_probe(&exec(pid_file.probe_command()).await?) else {
+        return Ok(None);
+    };
+
+    if !restart {
+        return Err(AivaError::VMError {
+            vm_name: pid_file.vm_name.clone(),
+            state: VMState::Running,
+            message: format!(
+                "An MCP server is already running (PID {pid}). Pass --restart-existing to \
+                 replace it, or stop the VM with 'aiva stop {}'",
+                pid_file.vm_name
+            ),
+        });
+    }
+
+    let output = exec(pid_file.stop_command(pid)).await?;
+    let outcome = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("stopped="))
+        .unwrap_or("failed");
+    if outcome == "failed" {
+        return Err(AivaError::VMError {
+            vm_name: pid_file.vm_name.clone(),
+            state: VMState::Running,
+            message: format!("Failed to stop the running MCP server (PID {pid})"),
+        });
+    }
+
+    info!(
+        "Stopped MCP server {} of VM {} ({})",
+        pid, pid_file.vm_name, outcome
+    );
+    Ok(Some(pid))
+}
@@ -1,9 +1,6 @@
-use crate::{
-    AivaError, CacheStrategy, NetworkConfig, PortMapping, Protocol, Result, StorageConfig, VMConfig,
-};
+use crate::{AivaError, ExecContext, PortMapping, Protocol, Result, VMConfig, command_line};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VMTemplate {
@@ -130,30 +127,10 @@ impl VMTemplate {
     }
 
     fn default_vm_config_with_port(default_port: u16) -> VMConfig {
-        VMConfig {
-            cpus: 2,
-            memory_mb: 4096,
-            disk_gb: 20,
-            kernel_path: PathBuf::from("/opt/aiva/images/vmlinux"),
-            rootfs_path: PathBuf::from("/opt/aiva/images/rootfs.ext4"),
-            network: NetworkConfig {
-                guest_ip: "172.16.0.2".to_string(),
-                host_ip: "172.16.0.1".to_string(),
-                subnet: "172.16.0.0/24".to_string(),
-                gateway: "172.16.0.1".to_string(),
-                dns_servers: vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()],
-                dhcp_enabled: false,
-                port_mappings: vec![PortMapping {
-                    host_port: default_port,
-                    guest_port: default_port,
-                    protocol: Protocol::Tcp,
-                }],
-            },
-            storage: StorageConfig {
-                cache_strategy: CacheStrategy::Writeback,
-                additional_drives: vec![],
-            },
-        }
+        VMConfig::builder()
+            .port(default_port, default_port, Protocol::Tcp)
+            .build()
+            .expect("built-in template config is valid")
     }
 
     pub fn get_all_templates() -> Vec<VMTemplate> {
@@ -199,11 +176,10 @@ impl VMTemplate {
             }
             if let Some(additional_ports) = custom.additional_ports {
                 for port in additional_ports {
-                    config.network.port_mappings.push(PortMapping {
-                        host_port: port,
-                        guest_port: port,
-                        protocol: Protocol::Tcp,
-                    });
+                    config
+                        .network
+                        .port_mappings
+                        .push(PortMapping::new(port, port, Protocol::Tcp));
                 }
             }
         }
@@ -216,8 +192,93 @@ impl VMTemplate {
         self.setup_scripts.join("\n")
     }
 
-    /// Get the command to run a specific MCP server
-    pub fn get_run_command(&self, mcp_command: &str, transport: &str) -> Result<String> {
+    /// Get the command to run a specific MCP server in the given guest context.
+    /// `args` are quoted individually instead of being re-split by the shell.
+    pub fn get_run_command(
+        &self,
+        mcp_command: &str,
+        args: &[String],
+        transport: &str,
+        context: &ExecContext,
+    ) -> Result<String> {
+        Ok(self
+            .plan_run(mcp_command, args, transport, None, context)?
+            .command)
+    }
+
+    /// Transport `aiva run` uses when neither the flag nor the command names
+    /// one: the first the template supports
+    pub fn preferred_transport(&self) -> &str {
+        self.mcp_support
+            .supported_transports
+            .first()
+            .map(String::as_str)
+            .unwrap_or("sse")
+    }
+
+    /// Pick the transport for an MCP command. An explicit `--transport` wins,
+    /// then a transport named in the command itself, then the template's
+    /// preferred one. A transport inferred from the command that the template
+    /// does not support is replaced by the preferred one, with a warning.
+    pub fn resolve_transport(
+        &self,
+        explicit: Option<&str>,
+        mcp_command: &str,
+        args: &[String],
+    ) -> TransportChoice {
+        if let Some(transport) = explicit {
+            return TransportChoice {
+                transport: transport.to_string(),
+                source: TransportSource::Flag,
+                warning: None,
+            };
+        }
+
+        let preferred = self.preferred_transport().to_string();
+        match infer_transport(&command_line(mcp_command, args)) {
+            Some(inferred)
+                if self
+                    .mcp_support
+                    .supported_transports
+                    .iter()
+                    .any(|t| t == inferred) =>
+            {
+                TransportChoice {
+                    transport: inferred.to_string(),
+                    source: TransportSource::Command,
+                    warning: None,
+                }
+            }
+            Some(inferred) => TransportChoice {
+                warning: Some(format!(
+                    "The command looks like a {inferred} server, but template '{}' only supports {}; using {preferred}",
+                    self.name,
+                    self.mcp_support.supported_transports.join(", ")
+                )),
+                transport: preferred,
+                source: TransportSource::Template,
+            },
+            None => TransportChoice {
+                transport: preferred,
+                source: TransportSource::Template,
+                warning: None,
+            },
+        }
+    }
+
+    /// Resolve an MCP command and its arguments into what will run in the
+    /// guest and the port it listens on. `port` comes from `aiva run --port`;
+    /// without it a `--port` already in the command wins over the template
+    /// default.
+    pub fn plan_run(
+        &self,
+        mcp_command: &str,
+        args: &[String],
+        transport: &str,
+        port: Option<u16>,
+        context: &ExecContext,
+    ) -> Result<RunPlan> {
+        let mcp_command = &command_line(mcp_command, args);
         if !self
             .mcp_support
             .supported_transports
@@ -252,38 +313,119 @@ impl VMTemplate {
             RuntimeType::Custom { .. } => mcp_command.to_string(),
         };
 
-        let full_command = match transport {
-            "sse" => {
-                // Check if the command already includes --port
-                if base_cmd.contains("--port") {
-                    // Command already has port specified, use as-is
-                    format!("cd /opt/mcp && {base_cmd}")
-                } else {
-                    // Add default port
-                    let port = self.mcp_support.default_port.unwrap_or(3000);
-                    // Check if the command already includes the transport mode
-                    if mcp_command.contains(" sse") || mcp_command.contains(" stdio") {
-                        format!("cd /opt/mcp && {base_cmd} --port {port}")
-                    } else {
-                        format!("cd /opt/mcp && {base_cmd} {transport} --port {port}")
-                    }
+        let has_transport = mcp_command.contains(" sse") || mcp_command.contains(" stdio");
+
+        let (full_command, port) = match transport {
+            "sse" => match (port, port_flag(mcp_command)?) {
+                (Some(requested), Some(in_command)) if requested != in_command => {
+                    return Err(AivaError::ConfigError(format!(
+                        "Port {requested} conflicts with '--port {in_command}' in the command"
+                    )));
                 }
-            }
-            "stdio" => {
-                // stdio doesn't use ports, just check for transport mode
-                if mcp_command.contains(" sse") || mcp_command.contains(" stdio") {
-                    format!("cd /opt/mcp && {base_cmd}")
-                } else {
-                    format!("cd /opt/mcp && {base_cmd} {transport}")
+                // The command already listens on the right port
+                (_, Some(in_command)) => (base_cmd, Some(in_command)),
+                (requested, None) => {
+                    let port = requested.or(self.mcp_support.default_port).unwrap_or(3000);
+                    let command = if has_transport {
+                        format!("{base_cmd} --port {port}")
+                    } else {
+                        format!("{base_cmd} {transport} --port {port}")
+                    };
+                    (command, Some(port))
                 }
-            }
-            _ => {
-                format!("cd /opt/mcp && {base_cmd}")
-            }
+            },
+            // stdio doesn't use ports, just check for transport mode
+            "stdio" if has_transport => (base_cmd, None),
+            "stdio" => (format!("{base_cmd} {transport}"), None),
+            _ => (base_cmd, None),
+        };
+
+        Ok(RunPlan {
+            command: context.wrap(&full_command),
+            transport: transport.to_string(),
+            port,
+        })
+    }
+}
+
+/// Value of a `--port N` or `--port=N` argument in an MCP command
+fn port_flag(command: &str) -> Result<Option<u16>> {
+    let mut args = command.split_whitespace();
+
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--port") {
+            Some("") => args.next(),
+            Some(rest) if rest.starts_with('=') => Some(&rest[1..]),
+            _ => continue,
         };
 
-        Ok(full_command)
+        return value
+            .and_then(|v| v.parse::<u16>().ok())
+            .map(Some)
+            .ok_or_else(|| {
+                AivaError::ConfigError(format!("Invalid '--port' value in command: {command}"))
+            });
+    }
+
+    Ok(None)
+}
+
+/// Transport an MCP command names: a bare `sse`/`stdio` subcommand, a
+/// `--sse`/`--stdio` flag or `--transport X`. `None` when it names none or
+/// both.
+pub fn infer_transport(command: &str) -> Option<&'static str> {
+    let mut found = None;
+    let mut args = command.split_whitespace();
+
+    while let Some(arg) = args.next() {
+        let name = match arg {
+            "--transport" => args.next(),
+            _ => match arg.strip_prefix("--transport=") {
+                Some(value) => Some(value),
+                None => Some(arg.trim_start_matches("--")),
+            },
+        };
+        let transport = match name {
+            Some("sse") => "sse",
+            Some("stdio") => "stdio",
+            _ => continue,
+        };
+        match found {
+            Some(previous) if previous != transport => return None,
+            _ => found = Some(transport),
+        }
     }
+
+    found
+}
+
+/// Where the transport of an `aiva run` came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportSource {
+    /// `aiva run --transport`
+    Flag,
+    /// Named in the MCP command
+    Command,
+    /// The template's preferred transport
+    Template,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransportChoice {
+    pub transport: String,
+    pub source: TransportSource,
+    /// Set when a transport named in the command had to be overridden
+    pub warning: Option<String>,
+}
+
+/// A resolved `aiva run` invocation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunPlan {
+    /// Command to execute in the guest, already wrapped in its exec context
+    pub command: String,
+    pub transport: String,
+    /// Guest port the server listens on; `None` for stdio
+    pub port: Option<u16>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
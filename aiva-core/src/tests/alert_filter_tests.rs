@@ -0,0 +1,95 @@
+use crate::{
+    Alert, AlertFilter, AlertSeverity, AlertType, DefaultMetricsCollector, LogStore,
+    MonitoringService, Result,
+};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+fn noon() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 10, 12, 0, 0).unwrap()
+}
+
+fn alert(vm: &str, severity: AlertSeverity, hours_ago: i64, resolved: bool) -> Alert {
+    Alert {
+        id: uuid::Uuid::new_v4(),
+        vm_id: Some(vm.to_string()),
+        alert_type: AlertType::HighCpuUsage,
+        severity,
+        message: format!("{vm} {severity} {hours_ago}h ago"),
+        timestamp: noon() - Duration::hours(hours_ago),
+        resolved,
+        resolved_at: None,
+    }
+}
+
+/// Alerts spread over a day, persisted the way earlier runs leave them
+async fn monitoring_with_history() -> Result<(MonitoringService, std::path::PathBuf)> {
+    let dir = std::env::temp_dir().join(format!("aiva-alert-filter-{}", uuid::Uuid::new_v4()));
+    let store = LogStore::new(dir.clone());
+    for alert in [
+        alert("web", AlertSeverity::Critical, 20, false),
+        alert("web", AlertSeverity::Low, 5, false),
+        alert("web", AlertSeverity::High, 3, true),
+        alert("db", AlertSeverity::Critical, 2, false),
+        alert("web", AlertSeverity::Medium, 1, false),
+        alert("web", AlertSeverity::Critical, 0, false),
+    ] {
+        store.append_alert(&alert).await?;
+    }
+
+    let monitoring =
+        MonitoringService::new(Box::new(DefaultMetricsCollector)).with_overflow(store, 100);
+    Ok((monitoring, dir))
+}
+
+fn messages(alerts: &[Alert]) -> Vec<&str> {
+    alerts.iter().map(|alert| alert.message.as_str()).collect()
+}
+
+#[tokio::test]
+async fn test_time_window_and_severity_combine() -> Result<()> {
+    let (monitoring, dir) = monitoring_with_history().await?;
+
+    // The last 6 hours, High or worse
+    let filter = AlertFilter {
+        since: Some(noon() - Duration::hours(6)),
+        until: Some(noon()),
+        min_severity: Some(AlertSeverity::High),
+        ..AlertFilter::default()
+    };
+    let alerts = monitoring.get_alerts_filtered(&filter).await?;
+    assert_eq!(
+        messages(&alerts),
+        ["web HIGH 3h ago", "db CRITICAL 2h ago"],
+        "the window is half open and excludes the 20h old alert"
+    );
+
+    // Narrowed to one VM and its open alerts
+    let filter = AlertFilter {
+        vm_id: Some("web".to_string()),
+        resolved: Some(false),
+        ..filter
+    };
+    assert!(monitoring.get_alerts_filtered(&filter).await?.is_empty());
+
+    let filter = AlertFilter {
+        until: None,
+        ..filter
+    };
+    assert_eq!(
+        messages(&monitoring.get_alerts_filtered(&filter).await?),
+        ["web CRITICAL 0h ago"]
+    );
+
+    // No filter is everything, and get_alerts still filters by VM only
+    assert_eq!(
+        monitoring
+            .get_alerts_filtered(&AlertFilter::default())
+            .await?
+            .len(),
+        6
+    );
+    assert_eq!(monitoring.get_alerts(Some("db")).await?.len(), 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use crate::{
+    Alert, AlertRoute, AlertRoutes, AlertSeverity, AlertSink, AlertType, DefaultMetricsCollector,
+    MonitoringService, Result,
+};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+/// Keeps every alert it is sent
+#[derive(Default)]
+struct RecordingSink {
+    received: Mutex<Vec<Alert>>,
+}
+
+impl RecordingSink {
+    fn types(&self) -> Vec<(AlertType, AlertSeverity)> {
+        self.received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|alert| (alert.alert_type, alert.severity))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl AlertSink for RecordingSink {
+    async fn send(&self, alert: &Alert) -> Result<()> {
+        self.received.lock().unwrap().push(alert.clone());
+        Ok(())
+    }
+}
+
+fn route(alert_type: AlertType, min_severity: AlertSeverity, sink: &str) -> AlertRoute {
+    AlertRoute {
+        alert_type: Some(alert_type),
+        min_severity,
+        sinks: vec![sink.to_string()],
+    }
+}
+
+#[tokio::test]
+async fn test_critical_crash_pages_and_low_disk_emails() -> Result<()> {
+    let pager = Arc::new(RecordingSink::default());
+    let email = Arc::new(RecordingSink::default());
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector))
+        .with_alert_sink("pager", pager.clone())
+        .with_alert_sink("email", email.clone())
+        .with_alert_routes(AlertRoutes::new(vec![
+            route(AlertType::VMCrash, AlertSeverity::Critical, "pager"),
+            route(AlertType::DiskSpaceLow, AlertSeverity::Low, "email"),
+        ]));
+
+    monitoring
+        .create_alert(
+            Some("vm-1".to_string()),
+            AlertType::VMCrash,
+            AlertSeverity::Critical,
+            "VM vm-1 crashed".to_string(),
+        )
+        .await?;
+    monitoring
+        .create_alert(
+            None,
+            AlertType::DiskSpaceLow,
+            AlertSeverity::Medium,
+            "Disk usage at 85%".to_string(),
+        )
+        .await?;
+    // Below the pager route's severity and matched by no other route
+    monitoring
+        .create_alert(
+            Some("vm-2".to_string()),
+            AlertType::VMCrash,
+            AlertSeverity::High,
+            "VM vm-2 crashed".to_string(),
+        )
+        .await?;
+
+    assert_eq!(
+        pager.types(),
+        vec![(AlertType::VMCrash, AlertSeverity::Critical)]
+    );
+    assert_eq!(
+        email.types(),
+        vec![(AlertType::DiskSpaceLow, AlertSeverity::Medium)]
+    );
+
+    // Routing only picks sinks; every alert is still recorded
+    assert_eq!(monitoring.get_alerts(None).await?.len(), 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_every_sink_sees_every_alert_by_default() -> Result<()> {
+    let pager = Arc::new(RecordingSink::default());
+    let email = Arc::new(RecordingSink::default());
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector))
+        .with_alert_sink("pager", pager.clone())
+        .with_alert_sink("email", email.clone());
+
+    monitoring
+        .report_security_violation("vm-1", "ptrace", AlertSeverity::Low, "blocked".to_string())
+        .await?;
+
+    let expected = vec![(AlertType::SecurityViolation, AlertSeverity::Low)];
+    assert_eq!(pager.types(), expected);
+    assert_eq!(email.types(), expected);
+    Ok(())
+}
+
+#[test]
+fn test_sinks_are_picked_once_in_registration_order() {
+    let routes = AlertRoutes::new(vec![
+        route(AlertType::VMCrash, AlertSeverity::Low, "email"),
+        AlertRoute {
+            alert_type: None,
+            min_severity: AlertSeverity::High,
+            sinks: vec!["pager".to_string(), "email".to_string()],
+        },
+    ]);
+    let alert = Alert {
+        id: uuid::Uuid::new_v4(),
+        vm_id: None,
+        alert_type: AlertType::VMCrash,
+        severity: AlertSeverity::Critical,
+        message: String::new(),
+        timestamp: chrono::Utc::now(),
+        resolved: false,
+        resolved_at: None,
+    };
+
+    assert_eq!(
+        routes.sinks_for(&alert, &["pager", "chat", "email"]),
+        vec!["pager", "email"]
+    );
+    assert!(AlertSeverity::Critical.is_at_least(AlertSeverity::High));
+    assert!(!AlertSeverity::Medium.is_at_least(AlertSeverity::High));
+}
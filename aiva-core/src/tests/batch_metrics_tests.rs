@@ -0,0 +1,112 @@
+use super::{MockOp, MockPlatform, TestState};
+use crate::{
+    AivaError, DiskIOMetrics, MemoryMetrics, NetworkIOMetrics, Result, VMInstance, VMManager,
+    VMMetrics, VMOrchestrator, VMState, VMTemplate,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn metrics(cpu: f64) -> VMMetrics {
+    VMMetrics {
+        cpu_usage: cpu,
+        memory_usage: MemoryMetrics {
+            total_mb: 1024,
+            used_mb: 256,
+            available_mb: 768,
+            cache_mb: 0,
+        },
+        disk_io: DiskIOMetrics {
+            read_bytes: 0,
+            write_bytes: 0,
+            read_ops: 0,
+            write_ops: 0,
+        },
+        network_io: NetworkIOMetrics {
+            rx_bytes: 0,
+            tx_bytes: 0,
+            rx_packets: 0,
+            tx_packets: 0,
+        },
+        uptime: Duration::from_secs(1),
+    }
+}
+
+async fn orchestrator(
+    names: &[&str],
+    concurrency: usize,
+) -> Result<(
+    Arc<MockPlatform>,
+    VMOrchestrator,
+    Vec<VMInstance>,
+    TestState,
+)> {
+    let platform = Arc::new(
+        MockPlatform::new()
+            .created(VMState::Running)
+            .slow(Duration::from_millis(20))
+            .failing(MockOp::Metrics, "broken", "metrics endpoint unreachable")
+            .metrics(|vm| metrics(vm.name.len() as f64)),
+    );
+    let state = TestState::new();
+    let vm_manager = state
+        .orchestrator(platform.clone())
+        .with_concurrency(concurrency);
+
+    let mut vms = Vec::new();
+    for name in names {
+        let config = VMTemplate::python3_uv().generate_vm_config(None);
+        vms.push(vm_manager.create_vm(name.to_string(), config).await?);
+    }
+    Ok((platform, vm_manager, vms, state))
+}
+
+#[tokio::test]
+async fn test_one_failing_vm_does_not_poison_the_batch() -> Result<()> {
+    let (_, vm_manager, vms, _state) = orchestrator(&["web", "broken", "worker"], 4).await?;
+
+    let results = vm_manager.batch_metrics(None).await;
+    assert_eq!(results.len(), 3);
+    for vm in &vms {
+        match (vm.name.as_str(), &results[&vm.id]) {
+            ("broken", Err(AivaError::PlatformError { message, .. })) => {
+                assert!(message.contains("unreachable"))
+            }
+            ("broken", other) => panic!("expected an error for broken, got {other:?}"),
+            (name, result) => {
+                assert_eq!(result.as_ref().unwrap().cpu_usage, name.len() as f64)
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batch_of_selected_ids_reports_unknown_ones() -> Result<()> {
+    let (_, vm_manager, vms, _state) = orchestrator(&["web", "worker"], 4).await?;
+    let unknown = uuid::Uuid::new_v4();
+
+    let results = vm_manager.batch_metrics(Some(&[vms[1].id, unknown])).await;
+    assert_eq!(results.len(), 2);
+    assert!(results[&vms[1].id].is_ok());
+    assert!(matches!(
+        results[&unknown],
+        Err(AivaError::VMError { ref message, .. }) if message == "VM not found"
+    ));
+
+    // Stopped VMs are left out unless asked for
+    vm_manager.stop_vm(&vms[0].id, false).await?;
+    let results = vm_manager.batch_metrics(None).await;
+    assert_eq!(results.keys().collect::<Vec<_>>(), vec![&vms[1].id]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_batch_concurrency_is_bounded() -> Result<()> {
+    let names = ["a", "b", "c", "d", "e", "f"];
+    let (platform, vm_manager, _, _state) = orchestrator(&names, 2).await?;
+
+    let results = vm_manager.batch_metrics(None).await;
+    assert_eq!(results.len(), names.len());
+    assert_eq!(platform.peak(), 2);
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use crate::benchmark::{LatencyStats, percentile};
+use std::time::Duration;
+
+#[test]
+fn test_percentiles_over_synthetic_sample() {
+    // 1ms..=100ms, shuffled so the stats must sort first
+    let mut samples: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+    samples.reverse();
+    samples.swap(3, 71);
+
+    let stats = LatencyStats::from_samples(&samples).unwrap();
+
+    assert_eq!(stats.samples, 100);
+    assert_eq!(stats.min_ms, 1.0);
+    assert_eq!(stats.p50_ms, 50.0);
+    assert_eq!(stats.p95_ms, 95.0);
+    assert_eq!(stats.max_ms, 100.0);
+    assert!((stats.mean_ms - 50.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_percentiles_small_sample() {
+    let sorted = [10.0, 20.0, 30.0];
+
+    assert_eq!(percentile(&sorted, 50.0), 20.0);
+    assert_eq!(percentile(&sorted, 95.0), 30.0);
+    assert_eq!(percentile(&sorted, 0.0), 10.0);
+}
+
+#[test]
+fn test_empty_sample_has_no_stats() {
+    assert!(LatencyStats::from_samples(&[]).is_none());
+}
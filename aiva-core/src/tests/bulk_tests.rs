@@ -0,0 +1,90 @@
+use super::{MockOp, MockPlatform, TestState};
+use crate::{
+    AivaError, Result, VMInstance, VMManager, VMOrchestrator, VMState, VMTemplate,
+    default_concurrency, validate_concurrency,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn fleet(
+    names: &[&str],
+    concurrency: usize,
+) -> Result<(
+    Arc<MockPlatform>,
+    Arc<VMOrchestrator>,
+    Vec<VMInstance>,
+    TestState,
+)> {
+    let platform = Arc::new(MockPlatform::new().slow(Duration::from_millis(20)).failing(
+        MockOp::Start,
+        "broken",
+        "kernel not found",
+    ));
+    let state = TestState::new();
+    let vm_manager = state
+        .orchestrator(platform.clone())
+        .with_concurrency(concurrency);
+
+    let mut vms = Vec::new();
+    for name in names {
+        let config = VMTemplate::python3_uv().generate_vm_config(None);
+        vms.push(vm_manager.create_vm(name.to_string(), config).await?);
+    }
+    Ok((platform, Arc::new(vm_manager), vms, state))
+}
+
+#[tokio::test]
+async fn test_bulk_start_runs_at_most_concurrency_starts() -> Result<()> {
+    let names = ["a", "b", "c", "d", "e", "f", "g"];
+    let (platform, vm_manager, vms, _state) = fleet(&names, 3).await?;
+    assert_eq!(vm_manager.concurrency(), 3);
+
+    let ids: Vec<_> = vms.iter().map(|vm| vm.id).collect();
+    let results = vm_manager.start_vms(&ids).await;
+    assert_eq!(results.len(), names.len());
+    assert!(results.values().all(Result::is_ok));
+    assert_eq!(platform.peak(), 3);
+
+    let results = vm_manager.stop_vms(&ids, false).await;
+    assert!(results.values().all(Result::is_ok));
+    for vm in vm_manager.list_vms().await? {
+        assert_eq!(vm.state, VMState::Stopped, "{}", vm.name);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bulk_start_failure_stays_with_its_vm() -> Result<()> {
+    let (_, vm_manager, vms, _state) = fleet(&["web", "broken", "worker"], 2).await?;
+    let unknown = uuid::Uuid::new_v4();
+
+    let mut ids: Vec<_> = vms.iter().map(|vm| vm.id).collect();
+    ids.push(unknown);
+    let results = vm_manager.start_vms(&ids).await;
+
+    for vm in &vms {
+        let state = vm_manager.get_vm(&vm.id).await?.unwrap().state;
+        if vm.name == "broken" {
+            assert!(results[&vm.id].is_err());
+            assert_eq!(state, VMState::Error);
+        } else {
+            assert!(results[&vm.id].is_ok(), "{:?}", results[&vm.id]);
+            assert_eq!(state, VMState::Running);
+        }
+    }
+    assert!(matches!(
+        results[&unknown],
+        Err(AivaError::VMError { ref message, .. }) if message == "VM not found"
+    ));
+    Ok(())
+}
+
+#[test]
+fn test_concurrency_must_be_positive() {
+    assert!(default_concurrency() >= 1);
+    assert_eq!(validate_concurrency(4).unwrap(), 4);
+    assert!(matches!(
+        validate_concurrency(0),
+        Err(AivaError::ConfigError(message)) if message.contains("at least 1")
+    ));
+}
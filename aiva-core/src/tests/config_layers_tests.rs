@@ -0,0 +1,122 @@
+use crate::{ConfigLayers, ConfigSource, env_key};
+use std::path::PathBuf;
+
+fn config_file(content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("aiva-config-{}.yaml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+    vars.iter()
+        .map(|(var, value)| (var.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_flag_wins_over_every_other_layer() -> crate::Result<()> {
+    let file = config_file("concurrency: 4\n");
+    let layers = || {
+        ConfigLayers::new()
+            .unwrap()
+            .with_file(Some(file.clone()))
+            .with_env(env(&[("AIVA_CONCURRENCY", "6")]))
+    };
+
+    let resolved = layers().flag("concurrency", "concurrency", 3).resolve()?;
+    assert_eq!(resolved.config.concurrency, 3);
+    assert_eq!(
+        resolved.sources["concurrency"],
+        ConfigSource::Flag("concurrency".to_string())
+    );
+
+    // Each layer in turn, once the ones above it are gone
+    let resolved = layers().resolve()?;
+    assert_eq!(resolved.config.concurrency, 6);
+    assert_eq!(
+        resolved.sources["concurrency"],
+        ConfigSource::Env("AIVA_CONCURRENCY".to_string())
+    );
+    let resolved = layers().with_env(Vec::new()).resolve()?;
+    assert_eq!(resolved.config.concurrency, 4);
+    assert_eq!(
+        resolved.sources["concurrency"],
+        ConfigSource::File(file.clone())
+    );
+    let resolved = layers().with_env(Vec::new()).with_file(None).resolve()?;
+    assert_eq!(resolved.config.concurrency, crate::default_concurrency());
+    assert_eq!(resolved.sources["concurrency"], ConfigSource::Default);
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
+
+#[test]
+fn test_partial_file_keeps_the_other_defaults() -> crate::Result<()> {
+    let file = config_file("defaults:\n  cpus: 2\nnetworking:\n  subnet: 10.9.0.0/24\n");
+    let resolved = ConfigLayers::new()?
+        .with_file(Some(file.clone()))
+        .with_env(env(&[
+            ("AIVA_DEFAULTS__MEMORY", "4GB"),
+            ("AIVA_NETWORKING__DNS_SERVERS", "[9.9.9.9]"),
+            ("AIVA_MONITORING__METRICS_CONCURRENCY", "2"),
+            ("AIVA_LIMA_CONFIG", "/etc/aiva/lima.yml"),
+            ("AIVA_LOG", "debug"),
+            ("AIVA_UNRELATED", "x"),
+        ]))
+        .resolve()?;
+
+    let config = &resolved.config;
+    assert_eq!(config.defaults.cpus, Some(2));
+    assert_eq!(config.defaults.disk, "50GB");
+    assert_eq!(config.defaults.memory.as_deref(), Some("4GB"));
+    assert_eq!(config.networking.subnet, "10.9.0.0/24");
+    assert_eq!(config.networking.bridge_name, "aiva-br0");
+    assert_eq!(config.networking.dns_servers, vec!["9.9.9.9"]);
+    assert_eq!(config.monitoring.metrics_concurrency, Some(2));
+    assert_eq!(
+        config.platform.macos.lima_config,
+        Some(PathBuf::from("/etc/aiva/lima.yml"))
+    );
+
+    assert_eq!(
+        resolved.sources["defaults.cpus"],
+        ConfigSource::File(file.clone())
+    );
+    assert_eq!(resolved.sources["defaults.disk"], ConfigSource::Default);
+    assert_eq!(
+        resolved.sources["platform.macos.lima_config"],
+        ConfigSource::Env("AIVA_LIMA_CONFIG".to_string())
+    );
+    // The empty section was replaced by the value set in it
+    assert!(!resolved.sources.contains_key("monitoring"));
+    assert!(!resolved.sources.contains_key("unrelated"));
+
+    std::fs::remove_file(file)?;
+    Ok(())
+}
+
+#[test]
+fn test_invalid_layers_are_rejected() -> crate::Result<()> {
+    let layers = || ConfigLayers::new().unwrap().with_file(None);
+    assert!(
+        layers()
+            .with_env(env(&[("AIVA_CONCURRENCY", "0")]))
+            .resolve()
+            .is_err()
+    );
+    assert!(
+        layers()
+            .with_env(env(&[("AIVA_CONCURRENCY", "many")]))
+            .resolve()
+            .is_err()
+    );
+
+    assert_eq!(
+        env_key("AIVA_DEFAULTS__CPUS").as_deref(),
+        Some("defaults.cpus")
+    );
+    assert_eq!(env_key("AIVA_LOG"), None);
+    assert_eq!(env_key("HOME"), None);
+    Ok(())
+}
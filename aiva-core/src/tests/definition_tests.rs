@@ -0,0 +1,80 @@
+use crate::{AivaError, DEFINITION_VERSION, Result, VMConfig, VMDefinition};
+
+fn labelled_config() -> VMConfig {
+    VMConfig::builder()
+        .cpus(4)
+        .memory_mb(2048)
+        .security_policy("restricted")
+        .label("team", "search")
+        .label("env", "staging")
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn test_export_lists_config_template_policy_and_labels() -> Result<()> {
+    let definition = VMDefinition::new(&labelled_config(), Some("python3-uv".to_string()));
+    let document = serde_json::to_value(&definition)?;
+
+    assert_eq!(document["version"], DEFINITION_VERSION);
+    assert_eq!(document["template"], "python3-uv");
+    assert_eq!(document["security_policy"], "restricted");
+    assert_eq!(document["labels"]["team"], "search");
+    assert_eq!(document["labels"]["env"], "staging");
+    assert_eq!(document["config"]["cpus"], 4);
+    assert_eq!(document["config"]["memory_mb"], 2048);
+    // Policy and labels appear once, next to the config rather than in it
+    assert!(document["config"]["security_policy"].is_null());
+    assert!(document["config"].get("labels").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_import_reconstructs_the_config_from_json_and_yaml() -> Result<()> {
+    let config = labelled_config();
+    let definition = VMDefinition::new(&config, Some("python3-uv".to_string()));
+
+    for text in [
+        serde_json::to_string_pretty(&definition)?,
+        serde_yaml::to_string(&definition).unwrap(),
+    ] {
+        let parsed = VMDefinition::parse(&text)?;
+        assert_eq!(parsed.template.as_deref(), Some("python3-uv"));
+
+        let imported = parsed.into_config()?;
+        assert_eq!(imported.cpus, 4);
+        assert_eq!(imported.memory_mb, 2048);
+        assert_eq!(imported.security_policy.as_deref(), Some("restricted"));
+        assert_eq!(imported.labels, config.labels);
+        assert_eq!(imported.network.guest_ip, config.network.guest_ip);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_import_validates_the_definition() -> Result<()> {
+    let mut definition = VMDefinition::new(&labelled_config(), None);
+    definition.config.cpus = 0;
+    assert!(matches!(
+        definition.into_config(),
+        Err(AivaError::ConfigError(message)) if message.contains("cpus")
+    ));
+
+    let mut definition = VMDefinition::new(&labelled_config(), None);
+    definition
+        .labels
+        .insert("no spaces".to_string(), "x".to_string());
+    assert!(matches!(
+        definition.into_config(),
+        Err(AivaError::ConfigError(message)) if message.contains("no spaces")
+    ));
+
+    let mut newer = serde_json::to_value(VMDefinition::new(&labelled_config(), None))?;
+    newer["version"] = (DEFINITION_VERSION + 1).into();
+    let err = VMDefinition::parse(&newer.to_string()).unwrap_err();
+    assert!(err.to_string().contains("Upgrade aiva"), "{err}");
+
+    assert!(VMDefinition::parse("version: 1\n").is_err());
+    assert!(VMDefinition::parse("- not\n- a definition\n").is_err());
+    Ok(())
+}
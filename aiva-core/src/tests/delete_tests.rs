@@ -0,0 +1,51 @@
+use super::{MockPlatform, TestState};
+use crate::{AivaError, Liveness, Result, VMManager, VMTemplate};
+use std::sync::Arc;
+
+/// Platform whose VMs have hypervisor process 4242, alive or not
+fn platform(liveness: Liveness) -> Arc<MockPlatform> {
+    Arc::new(MockPlatform::new().with_process(4242).liveness(liveness))
+}
+
+#[tokio::test]
+async fn test_delete_confirms_the_process_is_gone_before_teardown() -> Result<()> {
+    let platform = platform(Liveness::Dead);
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform.clone());
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("gone".to_string(), config).await?;
+
+    vm_manager.delete_vm(&vm.id).await?;
+
+    assert_eq!(platform.calls(), ["liveness", "delete"]);
+    assert!(vm_manager.get_vm(&vm.id).await?.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_lingering_process_blocks_delete() -> Result<()> {
+    let platform = platform(Liveness::Alive);
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform.clone());
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager
+        .create_vm("lingering".to_string(), config)
+        .await?;
+
+    let err = vm_manager.delete_vm(&vm.id).await.unwrap_err();
+    assert!(
+        matches!(&err, AivaError::VMError { vm_name, .. } if vm_name == "lingering"),
+        "{err:?}"
+    );
+    let message = err.to_string();
+    assert!(
+        message.contains("process 4242 is still running"),
+        "{message}"
+    );
+    assert!(message.contains("aiva stop lingering --force"), "{message}");
+
+    // Nothing was torn down and the VM is still known
+    assert_eq!(platform.calls(), ["liveness"]);
+    assert!(vm_manager.get_vm(&vm.id).await?.is_some());
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, GIB, Result, VMManager, VMOrchestrator, VMTemplate, check_rootfs_fits, min_disk_gb,
+};
+use std::sync::Arc;
+
+/// Platform whose rootfs images are `image_bytes` large, on an orchestrator
+/// of its own
+fn orchestrator(image_bytes: u64) -> (VMOrchestrator, Arc<MockPlatform>, TestState) {
+    let platform = Arc::new(MockPlatform::new().image_bytes(image_bytes));
+    let state = TestState::new();
+    (state.orchestrator(platform.clone()), platform, state)
+}
+
+#[test]
+fn test_rootfs_larger_than_disk_is_rejected() {
+    let image = 5 * GIB + 1;
+
+    let err = check_rootfs_fits(image, 4).unwrap_err();
+    match err {
+        AivaError::ConfigError(message) => {
+            assert!(message.contains("disk_gb is 4 GB"), "{message}");
+            assert!(message.contains("at least 6"), "{message}");
+        }
+        other => panic!("unexpected error: {other:?}"),
+    }
+
+    assert!(check_rootfs_fits(image, 6).is_ok());
+    assert!(check_rootfs_fits(4 * GIB, 4).is_ok());
+    assert!(check_rootfs_fits(GIB, 0).is_err());
+}
+
+#[test]
+fn test_min_disk_gb_rounds_up() {
+    assert_eq!(min_disk_gb(0), 0);
+    assert_eq!(min_disk_gb(GIB), 1);
+    assert_eq!(min_disk_gb(GIB + 1), 2);
+}
+
+#[tokio::test]
+async fn test_create_fails_when_base_image_exceeds_disk() -> Result<()> {
+    let (vm_manager, _, _state) = orchestrator(30 * GIB);
+
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.disk_gb = 20;
+    let err = vm_manager
+        .create_vm("small".to_string(), config)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AivaError::ConfigError(_)), "{err:?}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resize_disk_grows_and_persists() -> Result<()> {
+    let (vm_manager, platform, state) = orchestrator(2 * GIB);
+
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.disk_gb = 10;
+    let vm = vm_manager.create_vm("grow".to_string(), config).await?;
+
+    let resized = vm_manager.resize_disk(&vm.id, 25).await?;
+    assert_eq!(resized.config.disk_gb, 25);
+    assert_eq!(platform.resized(), vec![25]);
+
+    // Same size is a no-op, shrinking is refused before the platform is asked
+    vm_manager.resize_disk(&vm.id, 25).await?;
+    assert!(matches!(
+        vm_manager.resize_disk(&vm.id, 5).await,
+        Err(AivaError::ConfigError(_))
+    ));
+    assert_eq!(platform.resized().len(), 1);
+
+    let reloaded = state.orchestrator(platform.clone());
+    reloaded.load_state().await?;
+    assert_eq!(reloaded.get_vm(&vm.id).await?.unwrap().config.disk_gb, 25);
+
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use crate::{AivaError, NetworkConfig, format_dns_overrides, parse_dns_overrides, validate_domain};
+
+fn split_dns() -> NetworkConfig {
+    NetworkConfig {
+        dns_servers: vec!["1.1.1.1".to_string()],
+        dns_search: vec!["corp.example.com".to_string(), "svc.local".to_string()],
+        dns_overrides: parse_dns_overrides(
+            "corp.example.com=10.0.0.53,10.0.0.54;lab.internal=192.168.7.1",
+        )
+        .unwrap(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_split_dns_resolver_configuration() {
+    let network = split_dns();
+
+    assert_eq!(
+        network.render_resolv_conf(),
+        "# Generated by aiva\nnameserver 127.0.0.1\nsearch corp.example.com svc.local\n"
+    );
+    assert_eq!(
+        network.render_dnsmasq_conf().unwrap(),
+        "# Generated by aiva\n\
+         no-resolv\n\
+         listen-address=127.0.0.1\n\
+         bind-interfaces\n\
+         server=/corp.example.com/10.0.0.53\n\
+         server=/corp.example.com/10.0.0.54\n\
+         server=/lab.internal/192.168.7.1\n\
+         server=1.1.1.1\n"
+    );
+
+    let command = network.resolver_setup_command().unwrap();
+    assert!(command.contains("command -v dnsmasq"));
+    assert!(command.contains("/etc/dnsmasq.d/aiva.conf"));
+    assert!(command.ends_with("echo dns=applied; fi"));
+}
+
+#[test]
+fn test_search_domains_without_overrides_skip_dnsmasq() {
+    let network = NetworkConfig {
+        dns_search: vec!["corp.example.com".to_string()],
+        ..Default::default()
+    };
+
+    assert!(network.has_custom_dns());
+    assert!(network.render_dnsmasq_conf().is_none());
+    assert_eq!(
+        network.render_resolv_conf(),
+        "# Generated by aiva\nnameserver 8.8.8.8\nnameserver 1.1.1.1\nsearch corp.example.com\n"
+    );
+    assert!(
+        !network
+            .resolver_setup_command()
+            .unwrap()
+            .contains("dnsmasq")
+    );
+    assert!(!NetworkConfig::default().has_custom_dns());
+}
+
+#[test]
+fn test_invalid_domains_and_servers_are_rejected() {
+    assert!(validate_domain("corp.example.com").is_ok());
+    assert!(validate_domain("corp.example.com.").is_ok());
+    for bad in [
+        "",
+        "-corp.example",
+        "corp..example",
+        "corp_example.com",
+        "a b",
+    ] {
+        assert!(validate_domain(bad).is_err(), "{bad:?} should be rejected");
+    }
+    assert!(validate_domain(&format!("{}.com", "a".repeat(64))).is_err());
+
+    let mut network = split_dns();
+    network
+        .dns_overrides
+        .insert("lab.internal".to_string(), vec!["not-an-ip".to_string()]);
+    assert!(matches!(
+        network.validate_dns(),
+        Err(AivaError::ConfigError(message)) if message.contains("not-an-ip")
+    ));
+
+    let mut network = split_dns();
+    network
+        .dns_overrides
+        .insert("empty.example".to_string(), vec![]);
+    assert!(network.resolver_setup_command().is_err());
+
+    assert!(parse_dns_overrides("corp.example.com").is_err());
+}
+
+#[test]
+fn test_dns_overrides_round_trip() {
+    let overrides = split_dns().dns_overrides;
+    let formatted = format_dns_overrides(&overrides);
+    assert_eq!(
+        formatted,
+        "corp.example.com=10.0.0.53,10.0.0.54;lab.internal=192.168.7.1"
+    );
+    assert_eq!(parse_dns_overrides(&formatted).unwrap(), overrides);
+}
@@ -0,0 +1,97 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, BlockDevice, Result, VMInstance, VMManager, VMOrchestrator, VMState, VMTemplate,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn volume(name: &str) -> BlockDevice {
+    BlockDevice {
+        path: PathBuf::from(format!("/var/lib/aiva/volumes/{name}.img")),
+        size_mb: 1024,
+        read_only: false,
+    }
+}
+
+/// Platform whose VMs run right after create
+fn platform() -> MockPlatform {
+    MockPlatform::new().created(VMState::Running)
+}
+
+async fn running_vm(
+    platform: Arc<MockPlatform>,
+) -> Result<(VMOrchestrator, VMInstance, TestState)> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform);
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("data".to_string(), config).await?;
+    Ok((vm_manager, vm, state))
+}
+
+#[tokio::test]
+async fn test_drives_of_a_stopped_vm_are_kept_for_its_next_start() -> Result<()> {
+    let (vm_manager, vm, state) = running_vm(Arc::new(platform())).await?;
+    vm_manager.stop_vm(&vm.id, false).await?;
+
+    assert_eq!(
+        vm_manager.attach_drive(&vm.id, volume("models")).await?,
+        "drive1"
+    );
+    assert_eq!(
+        vm_manager.attach_drive(&vm.id, volume("cache")).await?,
+        "drive2"
+    );
+
+    // The same volume twice would be two drives writing one file
+    let err = vm_manager
+        .attach_drive(&vm.id, volume("models"))
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AivaError::ConfigError(_)), "{err:?}");
+
+    let reloaded = state.orchestrator(Arc::new(platform()));
+    reloaded.load_state().await?;
+    let stored = reloaded.get_vm(&vm.id).await?.unwrap();
+    let drives: Vec<(String, PathBuf)> = stored
+        .config
+        .storage
+        .drives()
+        .map(|(id, drive)| (id, drive.path.clone()))
+        .collect();
+    assert_eq!(
+        drives,
+        [
+            ("drive1".to_string(), volume("models").path),
+            ("drive2".to_string(), volume("cache").path),
+        ]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_running_vm_keeps_its_drives_when_the_platform_refuses() -> Result<()> {
+    let (vm_manager, vm, _state) = running_vm(Arc::new(platform())).await?;
+
+    let err = vm_manager
+        .attach_drive(&vm.id, volume("models"))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("cannot attach"), "{err}");
+    let unchanged = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert!(unchanged.config.storage.additional_drives.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_running_vm_gets_the_drive_live() -> Result<()> {
+    let platform = Arc::new(platform().live_drives());
+    let (vm_manager, vm, _state) = running_vm(platform.clone()).await?;
+
+    let drive_id = vm_manager.attach_drive(&vm.id, volume("models")).await?;
+    assert_eq!(drive_id, "drive1");
+    let updated = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(updated.config.storage.additional_drives.len(), 1);
+    assert_eq!(platform.attached(), ["drive1"]);
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use crate::{ExecContext, VMTemplate, shell_arg};
+use std::path::PathBuf;
+
+#[test]
+fn test_default_context_runs_in_opt_mcp() {
+    let template = VMTemplate::python3_uv();
+    let command = template
+        .get_run_command("server.py", &[], "stdio", &ExecContext::default())
+        .unwrap();
+
+    assert_eq!(command, "cd '/opt/mcp' && uv run server.py stdio");
+}
+
+#[test]
+fn test_configured_workdir_and_user_are_used() {
+    let mut config = VMTemplate::nodejs22_npx().generate_vm_config(None);
+    config.workdir = Some(PathBuf::from("/srv/agent"));
+    config.run_as_user = Some("mcp".to_string());
+
+    let context = ExecContext::from_config(&config);
+    let command = VMTemplate::nodejs22_npx()
+        .get_run_command("my-server --port 8080", &[], "sse", &context)
+        .unwrap();
+
+    assert_eq!(
+        command,
+        r"sudo -u 'mcp' -H sh -c 'cd '\''/srv/agent'\'' && npx my-server --port 8080'"
+    );
+}
+
+#[test]
+fn test_workdir_with_spaces_is_quoted() {
+    let context = ExecContext {
+        workdir: Some(PathBuf::from("/home/it's here")),
+        user: None,
+    };
+
+    assert_eq!(context.wrap("ls"), r"cd '/home/it'\''s here' && ls");
+}
+
+#[test]
+fn test_trailing_args_are_quoted_individually() {
+    let args: Vec<String> = ["--name", "my server", "--greeting=it's", "$HOME"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let command = VMTemplate::python3_uv()
+        .get_run_command("server.py", &args, "stdio", &ExecContext::default())
+        .unwrap();
+
+    assert_eq!(
+        command,
+        r"cd '/opt/mcp' && uv run server.py --name 'my server' '--greeting=it'\''s' '$HOME' stdio"
+    );
+    assert_eq!(shell_arg(""), "''");
+}
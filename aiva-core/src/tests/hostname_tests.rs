@@ -0,0 +1,95 @@
+use crate::{
+    AivaError, RuntimeInfo, SCHEMA_VERSION, VMInstance, VMMetadata, VMState, VMTemplate,
+    hostname_setup_command, render_hosts, sanitize_hostname, validate_hostname,
+};
+
+fn vm(name: &str, guest_ip: &str, subnet: &str) -> VMInstance {
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.network.guest_ip = guest_ip.to_string();
+    config.network.subnet = subnet.to_string();
+    VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: name.to_string(),
+        state: VMState::Running,
+        config,
+        runtime: RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: None,
+            tap_device: None,
+            mcp_pids: None,
+            paused: None,
+        },
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        schema_version: SCHEMA_VERSION,
+    }
+}
+
+#[test]
+fn test_vm_names_are_sanitized_into_hostnames() {
+    assert_eq!(sanitize_hostname("web"), "web");
+    assert_eq!(sanitize_hostname("My_Agent.v2"), "my-agent-v2");
+    assert_eq!(sanitize_hostname("--code  review--"), "code-review");
+    assert_eq!(sanitize_hostname("émoji🤖bot"), "moji-bot");
+    assert_eq!(sanitize_hostname("___"), "aiva-vm");
+
+    // Cut to 63 characters without leaving a trailing dash
+    let long = format!("{}-tail", "a".repeat(62));
+    let hostname = sanitize_hostname(&long);
+    assert_eq!(hostname, "a".repeat(62));
+
+    for name in ["My_Agent.v2", "--code  review--", "___", long.as_str()] {
+        validate_hostname(&sanitize_hostname(name)).unwrap();
+    }
+}
+
+#[test]
+fn test_hostnames_follow_rfc_1123() {
+    validate_hostname("web-1").unwrap();
+    validate_hostname("1agent").unwrap();
+    validate_hostname(&"a".repeat(63)).unwrap();
+
+    for invalid in ["", "-web", "web-", "web_1", "web.local", &"a".repeat(64)] {
+        assert!(
+            matches!(validate_hostname(invalid), Err(AivaError::ConfigError(_))),
+            "{invalid:?} should be rejected"
+        );
+    }
+}
+
+#[test]
+fn test_hosts_list_the_gateway_and_siblings_on_the_subnet() {
+    let web = vm("Web_Server", "172.16.0.2", "172.16.0.0/24");
+    let mut worker = vm("worker", "172.16.0.3", "172.16.0.0/24");
+    worker.config.network.hostname = Some("crawler".to_string());
+    let db = vm("db", "172.16.0.4", "172.16.0.0/24");
+    let isolated = vm("isolated", "10.0.0.2", "10.0.0.0/24");
+    let fleet = vec![web.clone(), worker, db, isolated];
+
+    assert_eq!(
+        render_hosts(&web, &fleet),
+        "# Generated by aiva\n\
+         127.0.0.1 localhost\n\
+         ::1 localhost ip6-localhost ip6-loopback\n\
+         172.16.0.2 web-server\n\
+         172.16.0.1 aiva-gateway\n\
+         172.16.0.3 crawler\n\
+         172.16.0.4 db\n"
+    );
+
+    // Nothing shares the other subnet
+    let hosts = render_hosts(&fleet[3], &fleet);
+    assert!(hosts.ends_with("10.0.0.2 isolated\n172.16.0.1 aiva-gateway\n"));
+
+    let command = hostname_setup_command(&web, &fleet).unwrap();
+    assert!(command.contains("hostname 'web-server'"));
+    assert!(command.contains("> /etc/hosts"));
+    assert!(command.ends_with("echo hostname=applied"));
+}
+
+#[test]
+fn test_metadata_carries_the_hostname() {
+    let web = vm("Web_Server", "172.16.0.2", "172.16.0.0/24");
+    assert_eq!(VMMetadata::from_instance(&web).hostname, "web-server");
+}
@@ -0,0 +1,67 @@
+use crate::{DefaultMetricsCollector, LogEntry, LogLevel, MonitoringService, Result};
+
+const LEVELS: [LogLevel; 5] = [
+    LogLevel::Trace,
+    LogLevel::Debug,
+    LogLevel::Info,
+    LogLevel::Warn,
+    LogLevel::Error,
+];
+
+/// One entry per level for `vm-1`, least severe first
+async fn monitoring_with_every_level() -> Result<MonitoringService> {
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector));
+    for level in LEVELS {
+        monitoring
+            .add_log_entry(LogEntry::new(
+                Some("vm-1".to_string()),
+                level,
+                level.to_string(),
+            ))
+            .await?;
+    }
+    Ok(monitoring)
+}
+
+async fn messages(monitoring: &MonitoringService, level: LogLevel) -> Result<Vec<String>> {
+    Ok(monitoring
+        .get_logs(Some("vm-1"), Some(level))
+        .await?
+        .into_iter()
+        .map(|log| log.message)
+        .collect())
+}
+
+#[test]
+fn test_levels_order_by_severity() {
+    assert!(LogLevel::Error > LogLevel::Warn);
+    assert!(LogLevel::Warn > LogLevel::Info);
+    assert!(LogLevel::Info > LogLevel::Debug);
+    assert!(LogLevel::Debug > LogLevel::Trace);
+    assert!(LEVELS.is_sorted());
+}
+
+#[tokio::test]
+async fn test_each_level_returns_itself_and_more_severe() -> Result<()> {
+    let monitoring = monitoring_with_every_level().await?;
+
+    assert_eq!(messages(&monitoring, LogLevel::Error).await?, ["ERROR"]);
+    assert_eq!(
+        messages(&monitoring, LogLevel::Warn).await?,
+        ["WARN", "ERROR"]
+    );
+    assert_eq!(
+        messages(&monitoring, LogLevel::Info).await?,
+        ["INFO", "WARN", "ERROR"]
+    );
+    assert_eq!(
+        messages(&monitoring, LogLevel::Debug).await?,
+        ["DEBUG", "INFO", "WARN", "ERROR"]
+    );
+    assert_eq!(
+        messages(&monitoring, LogLevel::Trace).await?,
+        ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"]
+    );
+    assert_eq!(monitoring.get_logs(Some("vm-1"), None).await?.len(), 5);
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use crate::log_tail::tail_segments;
+use crate::{LogSegment, log_segments, tail_log};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("aiva-tail-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn numbered(range: std::ops::RangeInclusive<usize>) -> String {
+    range.map(|i| format!("line {i}\n")).collect()
+}
+
+fn write_gz(path: &Path, content: &str) {
+    let mut encoder = GzEncoder::new(std::fs::File::create(path).unwrap(), Compression::fast());
+    encoder.write_all(content.as_bytes()).unwrap();
+    encoder.finish().unwrap();
+}
+
+/// `vm.log` with lines 13-16, `vm.log.1` with 9-12 and no final newline,
+/// then compressed `vm.log.2.gz` (5-8) and `vm.log.3.gz` (1-4)
+fn rotated_log(dir: &Path) -> PathBuf {
+    let log = dir.join("vm.log");
+    std::fs::write(&log, numbered(13..=16)).unwrap();
+    std::fs::write(dir.join("vm.log.1"), numbered(9..=12).trim_end()).unwrap();
+    write_gz(&dir.join("vm.log.2.gz"), &numbered(5..=8));
+    write_gz(&dir.join("vm.log.3.gz"), &numbered(1..=4));
+    log
+}
+
+fn tail(segments: &[LogSegment], lines: usize, chunk: usize) -> String {
+    let mut out = Vec::new();
+    tail_segments(segments, lines, chunk, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn test_segments_are_listed_newest_first() {
+    let dir = scratch_dir();
+    let log = rotated_log(&dir);
+    // Segments after a gap are not part of the log
+    std::fs::write(dir.join("vm.log.5"), "stray\n").unwrap();
+
+    let segments = log_segments(&log);
+    let names: Vec<String> = segments
+        .iter()
+        .map(|s| s.path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["vm.log", "vm.log.1", "vm.log.2.gz", "vm.log.3.gz"]);
+    assert_eq!(
+        segments.iter().map(|s| s.compressed).collect::<Vec<_>>(),
+        [false, false, true, true]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tail_reads_backward_across_rotated_segments() {
+    let dir = scratch_dir();
+    let segments = log_segments(&rotated_log(&dir));
+
+    // Chunks smaller than a line exercise the backward scan across reads
+    for chunk in [1, 3, 7, 4096] {
+        assert_eq!(tail(&segments, 0, chunk), "");
+        assert_eq!(tail(&segments, 2, chunk), numbered(15..=16));
+        assert_eq!(tail(&segments, 4, chunk), numbered(13..=16));
+        // Into the uncompressed rotated segment
+        assert_eq!(tail(&segments, 6, chunk), numbered(11..=16));
+        // Into the compressed ones
+        assert_eq!(tail(&segments, 10, chunk), numbered(7..=16));
+        assert_eq!(tail(&segments, 14, chunk), numbered(3..=16));
+        // More than the whole log is all of it, oldest first
+        assert_eq!(tail(&segments, 16, chunk), numbered(1..=16));
+        assert_eq!(tail(&segments, usize::MAX, chunk), numbered(1..=16));
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tail_of_a_large_log_reads_only_its_end() {
+    let dir = scratch_dir();
+    let log = dir.join("vm.log");
+    std::fs::write(&log, numbered(1..=50_000)).unwrap();
+
+    let mut out = Vec::new();
+    tail_log(&log, 3, &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), numbered(49_998..=50_000));
+
+    // A missing log has no tail
+    let mut out = Vec::new();
+    tail_log(&dir.join("missing.log"), 10, &mut out).unwrap();
+    assert!(out.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,77 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    METADATA_KEY, METADATA_VERSION, PortMapping, Protocol, Result, ScaleRequest, VMManager,
+    VMMetadata, VMOrchestrator, VMState, VMTemplate,
+};
+use std::sync::Arc;
+
+/// An orchestrator whose VMs run right after create
+fn orchestrator() -> (VMOrchestrator, Arc<MockPlatform>, TestState) {
+    let platform = Arc::new(MockPlatform::new().created(VMState::Running));
+    let state = TestState::new();
+    let orchestrator = state.orchestrator(platform.clone());
+    (orchestrator, platform, state)
+}
+
+#[tokio::test]
+async fn test_metadata_document_describes_the_vm() -> Result<()> {
+    let (orchestrator, _, _state) = orchestrator();
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.cpus = 2;
+    config.memory_mb = 2048;
+    config.network.guest_ip = "172.16.0.7".to_string();
+    config.network.port_mappings = vec![PortMapping::new(8080, 3000, Protocol::Tcp)];
+    config
+        .metadata
+        .insert("api_base".to_string(), "https://example.com".to_string());
+    let vm = orchestrator.create_vm("agent".to_string(), config).await?;
+
+    let document = VMMetadata::from_instance(&vm).to_store();
+    let metadata = &document[METADATA_KEY];
+    assert_eq!(metadata["version"], METADATA_VERSION);
+    assert_eq!(metadata["name"], "agent");
+    assert_eq!(metadata["id"], vm.id.to_string());
+    assert_eq!(metadata["guest_ip"], "172.16.0.7");
+    assert_eq!(metadata["cpus"], 2);
+    assert_eq!(metadata["memory_mb"], 2048);
+    assert_eq!(metadata["port_mappings"][0]["host_port"], 8080);
+    assert_eq!(metadata["port_mappings"][0]["guest_port"], 3000);
+    assert_eq!(metadata["port_mappings"][0]["protocol"], "tcp");
+    assert_eq!(metadata["metadata"]["api_base"], "https://example.com");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_config_changes_of_running_vm_republish_metadata() -> Result<()> {
+    let (orchestrator, platform, _state) = orchestrator();
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let disk_gb = config.disk_gb;
+    let vm = orchestrator.create_vm("agent".to_string(), config).await?;
+
+    orchestrator.resize_disk(&vm.id, disk_gb + 5).await?;
+    let request = ScaleRequest {
+        cpus: None,
+        memory_mb: Some(1024),
+    };
+    orchestrator.scale_vm(&vm.id, request, false).await?;
+
+    let published = platform.published();
+    assert_eq!(published.len(), 2);
+    assert_eq!(published[0].disk_gb, disk_gb + 5);
+    assert_eq!(published[1].memory_mb, 1024);
+    assert_eq!(published[1].disk_gb, disk_gb + 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stopped_vm_metadata_is_not_published() -> Result<()> {
+    let (orchestrator, platform, _state) = orchestrator();
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let disk_gb = config.disk_gb;
+    let vm = orchestrator.create_vm("agent".to_string(), config).await?;
+    orchestrator.stop_vm(&vm.id, false).await?;
+
+    orchestrator.resize_disk(&vm.id, disk_gb + 1).await?;
+    assert!(platform.published().is_empty());
+    Ok(())
+}
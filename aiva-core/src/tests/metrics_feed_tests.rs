@@ -0,0 +1,79 @@
+use crate::{
+    DefaultMetricsCollector, MetricsCollector, MetricsFeed, MetricsSample, MonitoringService,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn vm_sample(vm_id: &str) -> MetricsSample {
+    MetricsSample::Vm {
+        vm_id: vm_id.to_string(),
+        metrics: DefaultMetricsCollector
+            .collect_metrics(vm_id)
+            .await
+            .unwrap(),
+    }
+}
+
+fn vm_id(sample: &MetricsSample) -> &str {
+    match sample {
+        MetricsSample::Vm { vm_id, .. } => vm_id,
+        MetricsSample::System(_) => panic!("expected a VM sample"),
+    }
+}
+
+#[tokio::test]
+async fn test_slow_subscriber_drops_oldest_samples() {
+    let feed = MetricsFeed::with_capacity(8);
+    let mut slow = feed.subscribe();
+
+    for i in 0..100 {
+        feed.publish(vm_sample(&format!("vm-{i}")).await);
+        // The backlog is bounded no matter how far behind the subscriber is
+        assert!(slow.pending() <= 8);
+    }
+
+    // The first sample received is the oldest one still buffered
+    let first = slow.recv().await.unwrap();
+    assert_eq!(vm_id(&first), "vm-92");
+    assert_eq!(slow.dropped(), 92);
+    assert_eq!(feed.dropped(), 92);
+
+    let mut rest = Vec::new();
+    while let Some(sample) = slow.try_recv() {
+        rest.push(vm_id(&sample).to_string());
+    }
+    assert_eq!(rest.len(), 7);
+    assert_eq!(rest.last().map(String::as_str), Some("vm-99"));
+    assert_eq!(slow.dropped(), 92);
+}
+
+#[tokio::test]
+async fn test_subscriber_that_keeps_up_drops_nothing() {
+    let feed = MetricsFeed::with_capacity(4);
+    let mut fast = feed.subscribe();
+
+    for i in 0..20 {
+        feed.publish(vm_sample(&format!("vm-{i}")).await);
+        let sample = fast.try_recv().unwrap();
+        assert_eq!(vm_id(&sample), format!("vm-{i}"));
+    }
+    assert_eq!(fast.dropped(), 0);
+    assert_eq!(feed.dropped(), 0);
+}
+
+#[tokio::test]
+async fn test_monitoring_publishes_collected_samples() {
+    let monitoring = Arc::new(MonitoringService::new(Box::new(DefaultMetricsCollector)));
+    let mut subscriber = monitoring.metrics_feed().subscribe();
+
+    let running = Arc::clone(&monitoring);
+    let task =
+        tokio::spawn(async move { running.start_monitoring(Duration::from_millis(10)).await });
+
+    let sample = tokio::time::timeout(Duration::from_secs(5), subscriber.recv())
+        .await
+        .expect("no sample within 5s")
+        .unwrap();
+    assert!(matches!(sample, MetricsSample::System(_)));
+    task.abort();
+}
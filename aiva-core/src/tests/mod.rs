@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod alert_filter_tests;
+#[cfg(test)]
+mod alert_routing_tests;
+#[cfg(test)]
+mod batch_metrics_tests;
+#[cfg(test)]
+mod benchmark_tests;
+#[cfg(test)]
+mod bulk_tests;
+#[cfg(test)]
+mod config_layers_tests;
+#[cfg(test)]
+mod definition_tests;
+#[cfg(test)]
+mod delete_tests;
+#[cfg(test)]
+mod disk_tests;
+#[cfg(test)]
+mod dns_tests;
+#[cfg(test)]
+mod drive_tests;
+#[cfg(test)]
+mod exec_context_tests;
+#[cfg(test)]
+mod hostname_tests;
+#[cfg(test)]
+mod log_filter_tests;
+#[cfg(test)]
+mod log_tail_tests;
+#[cfg(test)]
+mod metadata_tests;
+#[cfg(test)]
+mod metrics_feed_tests;
+#[cfg(test)]
+mod monitoring_overflow_tests;
+#[cfg(test)]
+mod monitoring_sync_tests;
+#[cfg(test)]
+mod pause_tests;
+#[cfg(test)]
+mod plan_tests;
+#[cfg(test)]
+mod pressure_tests;
+#[cfg(test)]
+mod readiness_tests;
+#[cfg(test)]
+mod reconcile_tests;
+#[cfg(test)]
+mod recovery_tests;
+#[cfg(test)]
+mod remediation_tests;
+#[cfg(test)]
+mod run_result_tests;
+#[cfg(test)]
+mod runtime_info_tests;
+#[cfg(test)]
+mod scale_tests;
+#[cfg(test)]
+mod schema_tests;
+#[cfg(test)]
+mod server_tests;
+#[cfg(test)]
+mod transport_tests;
+#[cfg(test)]
+mod vm_config_tests;
+#[cfg(test)]
+mod vsock_tests;
+
+mod test_support;
+
+use test_support::{MockOp, MockPlatform, TestState};
@@ -0,0 +1,97 @@
+use crate::{
+    AlertSeverity, AlertType, DefaultMetricsCollector, LogEntry, LogLevel, LogStore,
+    MonitoringService, Result,
+};
+use std::path::PathBuf;
+
+fn scratch_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("aiva-overflow-{}", uuid::Uuid::new_v4()))
+}
+
+fn entry(vm_id: &str, i: usize) -> LogEntry {
+    LogEntry::new(Some(vm_id.to_string()), LogLevel::Info, format!("line {i}"))
+}
+
+#[tokio::test]
+async fn test_logs_over_the_cap_are_flushed_oldest_first() -> Result<()> {
+    let dir = scratch_dir();
+    let store = LogStore::new(dir.clone());
+    let monitoring =
+        MonitoringService::new(Box::new(DefaultMetricsCollector)).with_overflow(store.clone(), 10);
+
+    for i in 0..100 {
+        monitoring.add_log_entry(entry("vm-1", i)).await?;
+        assert!(monitoring.memory_usage().await.0 <= 10);
+    }
+    monitoring.add_log_entry(entry("vm-2", 0)).await?;
+
+    // The oldest entries went to disk, the newest are still in memory
+    let flushed = store.read("vm-1").await?;
+    assert!(!flushed.is_empty());
+    assert_eq!(flushed[0].message, "line 0");
+    assert!(flushed.len() < 100);
+
+    let logs = monitoring.get_logs(Some("vm-1"), None).await?;
+    let messages: Vec<String> = logs.into_iter().map(|log| log.message).collect();
+    let expected: Vec<String> = (0..100).map(|i| format!("line {i}")).collect();
+    assert_eq!(messages, expected);
+
+    let all = monitoring.get_logs(None, None).await?;
+    assert_eq!(all.len(), 101);
+    assert!(
+        monitoring
+            .get_logs(None, Some(LogLevel::Error))
+            .await?
+            .is_empty()
+    );
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_flushed_alerts_are_still_listed_and_resolvable() -> Result<()> {
+    let dir = scratch_dir();
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector))
+        .with_overflow(LogStore::new(dir.clone()), 4);
+
+    let mut ids = Vec::new();
+    for i in 0..20 {
+        let alert = monitoring
+            .report_security_violation(
+                "vm-1",
+                &format!("syscall-{i}"),
+                AlertSeverity::Medium,
+                format!("alert {i}"),
+            )
+            .await?
+            .expect("distinct syscalls are not rate limited");
+        assert!(matches!(alert.alert_type, AlertType::SecurityViolation));
+        ids.push(alert.id);
+        assert!(monitoring.memory_usage().await.1 <= 4);
+    }
+
+    let alerts = monitoring.get_alerts(Some("vm-1")).await?;
+    assert_eq!(alerts.iter().map(|alert| alert.id).collect::<Vec<_>>(), ids);
+
+    // The first alert was flushed long ago
+    monitoring.resolve_alert(&ids[0]).await?;
+    let alerts = monitoring.get_alerts(None).await?;
+    assert_eq!(alerts.len(), 20);
+    assert!(alerts[0].resolved);
+    assert!(alerts[1..].iter().all(|alert| !alert.resolved));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_without_a_store_memory_is_still_bounded() -> Result<()> {
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector));
+    for i in 0..crate::MONITORING_MEMORY_CAP + 1 {
+        monitoring.add_log_entry(entry("vm-1", i)).await?;
+    }
+    let (logs, _) = monitoring.memory_usage().await;
+    assert!(logs <= crate::MONITORING_MEMORY_CAP);
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    DefaultMetricsCollector, LogEntry, LogLevel, LogShipper, MonitoringService, Result, VMInstance,
+    VMManager, VMOrchestrator, VMTemplate,
+};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Ships one line per VM, then keeps the stream open like a guest would
+struct OneLineShipper;
+
+#[async_trait]
+impl LogShipper for OneLineShipper {
+    async fn ship(&self, vm: VMInstance, monitoring: Arc<MonitoringService>) -> Result<()> {
+        monitoring
+            .add_log_entry(LogEntry::new(
+                Some(vm.id.to_string()),
+                LogLevel::Info,
+                "guest line".to_string(),
+            ))
+            .await?;
+        std::future::pending().await
+    }
+}
+
+fn orchestrator(state: &TestState) -> Arc<VMOrchestrator> {
+    Arc::new(state.orchestrator(Arc::new(MockPlatform::new())))
+}
+
+async fn vm_ids(vm_manager: &VMOrchestrator) -> Result<Vec<String>> {
+    let mut ids: Vec<String> = vm_manager
+        .list_vms()
+        .await?
+        .iter()
+        .map(|vm| vm.id.to_string())
+        .collect();
+    ids.sort();
+    Ok(ids)
+}
+
+/// Wait for the follower task to catch up with the orchestrator
+async fn wait_in_sync(monitoring: &MonitoringService, vm_manager: &VMOrchestrator) -> Result<()> {
+    let expected = vm_ids(vm_manager).await?;
+    for _ in 0..200 {
+        if monitoring.monitored_vms().await == expected {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(monitoring.monitored_vms().await, expected);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_delete_cycle_keeps_monitoring_in_sync() -> Result<()> {
+    let state = TestState::new();
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+
+    // A VM left over from an earlier run is registered on startup
+    let earlier = orchestrator(&state);
+    let existing = earlier
+        .create_vm("existing".to_string(), config.clone())
+        .await?;
+    drop(earlier);
+
+    let vm_manager = orchestrator(&state);
+    vm_manager.load_state().await?;
+    let monitoring = Arc::new(MonitoringService::new(Box::new(DefaultMetricsCollector)));
+    let follower = monitoring.follow(vm_manager.clone()).await?;
+    assert_eq!(
+        monitoring.monitored_vms().await,
+        vec![existing.id.to_string()]
+    );
+
+    let first = vm_manager
+        .create_vm("first".to_string(), config.clone())
+        .await?;
+    let second = vm_manager.create_vm("second".to_string(), config).await?;
+    vm_manager.start_vm(&second.id).await?;
+    wait_in_sync(&monitoring, &vm_manager).await?;
+    assert_eq!(monitoring.monitored_vms().await.len(), 3);
+
+    vm_manager.delete_vm(&first.id).await?;
+    vm_manager.delete_vm(&existing.id).await?;
+    vm_manager.stop_vm(&second.id, false).await?;
+    vm_manager.delete_vm(&second.id).await?;
+    wait_in_sync(&monitoring, &vm_manager).await?;
+    assert!(monitoring.monitored_vms().await.is_empty());
+
+    // Reloading the state file replaces the monitored set as a whole
+    let other = orchestrator(&state);
+    let reloaded = other
+        .create_vm(
+            "reloaded".to_string(),
+            VMTemplate::python3_uv().generate_vm_config(None),
+        )
+        .await?;
+    vm_manager.load_state().await?;
+    wait_in_sync(&monitoring, &vm_manager).await?;
+    assert_eq!(
+        monitoring.monitored_vms().await,
+        vec![reloaded.id.to_string()]
+    );
+
+    follower.abort();
+    Ok(())
+}
+
+/// Wait until shipping runs for exactly `expected`
+async fn wait_shipping(monitoring: &MonitoringService, expected: &[String]) {
+    for _ in 0..200 {
+        if monitoring.shipping_vms() == expected {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    assert_eq!(monitoring.shipping_vms(), expected);
+}
+
+#[tokio::test]
+async fn test_guest_logs_are_shipped_while_the_vm_runs() -> Result<()> {
+    let state = TestState::new();
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.logging.paths = vec!["/var/log/app.log".to_string()];
+
+    let vm_manager = orchestrator(&state);
+    let monitoring = Arc::new(
+        MonitoringService::new(Box::new(DefaultMetricsCollector))
+            .with_log_shipper(Arc::new(OneLineShipper)),
+    );
+    let follower = monitoring.follow(vm_manager.clone()).await?;
+
+    let shipped = vm_manager
+        .create_vm("shipped".to_string(), config.clone())
+        .await?;
+    config.logging.paths.clear();
+    let unconfigured = vm_manager.create_vm("quiet".to_string(), config).await?;
+    wait_in_sync(&monitoring, &vm_manager).await?;
+    assert!(monitoring.shipping_vms().is_empty());
+
+    // Shipping starts with the VM, without anyone following its logs
+    vm_manager.start_vm(&shipped.id).await?;
+    vm_manager.start_vm(&unconfigured.id).await?;
+    let id = shipped.id.to_string();
+    wait_shipping(&monitoring, std::slice::from_ref(&id)).await;
+    let logs = monitoring.get_logs(Some(&id), None).await?;
+    assert!(logs.iter().any(|entry| entry.message == "guest line"));
+
+    vm_manager.stop_vm(&shipped.id, false).await?;
+    wait_shipping(&monitoring, &[]).await;
+
+    vm_manager.start_vm(&shipped.id).await?;
+    wait_shipping(&monitoring, std::slice::from_ref(&id)).await;
+    vm_manager.stop_vm(&shipped.id, false).await?;
+    vm_manager.delete_vm(&shipped.id).await?;
+    wait_shipping(&monitoring, &[]).await;
+
+    follower.abort();
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, PauseState, Result, ResumePath, SnapshotFiles, VMInstance, VMManager,
+    VMOrchestrator, VMState, VMTemplate, check_pause, resume_path,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+fn snapshot() -> SnapshotFiles {
+    SnapshotFiles {
+        state: PathBuf::from("/tmp/aiva-jailer/web/root/snapshot"),
+        memory: PathBuf::from("/tmp/aiva-jailer/web/root/memory"),
+    }
+}
+
+/// A running VM with process 100. Restored VMs come back with process 200.
+async fn running_vm() -> Result<(Arc<MockPlatform>, VMOrchestrator, VMInstance, TestState)> {
+    let platform = Arc::new(
+        MockPlatform::new()
+            .created(VMState::Running)
+            .with_process(100),
+    );
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform.clone());
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("web".to_string(), config).await?;
+    Ok((platform, vm_manager, vm, state))
+}
+
+#[tokio::test]
+async fn test_in_memory_pause_keeps_the_process() -> Result<()> {
+    let (platform, vm_manager, vm, _state) = running_vm().await?;
+
+    let pause = vm_manager.pause_vm(&vm.id, false).await?;
+    assert_eq!(pause, PauseState::InMemory);
+
+    let paused = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(paused.state, VMState::Paused);
+    assert_eq!(paused.runtime.paused, Some(PauseState::InMemory));
+    assert_eq!(paused.runtime.pid, Some(100));
+    assert!(!paused.runtime.is_hibernated());
+
+    vm_manager.resume_vm(&vm.id).await?;
+    let resumed = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(resumed.state, VMState::Running);
+    assert_eq!(resumed.runtime.paused, None);
+    assert_eq!(resumed.runtime.pid, Some(100));
+    assert_eq!(platform.calls(), vec!["pause", "resume"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hibernate_stops_the_vm_and_resume_restores_it() -> Result<()> {
+    let (platform, vm_manager, vm, _state) = running_vm().await?;
+
+    let pause = vm_manager.pause_vm(&vm.id, true).await?;
+    assert_eq!(
+        pause,
+        PauseState::Hibernated {
+            snapshot: snapshot()
+        }
+    );
+
+    let hibernated = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(hibernated.state, VMState::Stopped);
+    assert!(hibernated.runtime.is_hibernated());
+    assert_eq!(hibernated.runtime.pid, None);
+    assert_eq!(hibernated.runtime.api_socket, None);
+    // The TAP device stays for the restored VM to use
+    assert_eq!(hibernated.runtime.tap_device.as_deref(), Some("tap-web"));
+
+    vm_manager.resume_vm(&vm.id).await?;
+    let restored = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(restored.state, VMState::Running);
+    assert_eq!(restored.runtime.paused, None);
+    assert_eq!(restored.runtime.pid, Some(200));
+    assert_eq!(
+        restored.runtime.api_socket,
+        Some(PathBuf::from("/tmp/fc-200.socket"))
+    );
+    assert_eq!(
+        platform.calls(),
+        vec!["hibernate", "restore /tmp/aiva-jailer/web/root/snapshot"]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_hibernated_state_survives_a_reload() -> Result<()> {
+    let (platform, vm_manager, vm, state) = running_vm().await?;
+    vm_manager.pause_vm(&vm.id, true).await?;
+
+    let reloaded = state.orchestrator(platform.clone());
+    reloaded.load_state().await?;
+    let stored = reloaded.get_vm(&vm.id).await?.unwrap();
+    assert!(stored.runtime.is_hibernated());
+    assert_eq!(
+        stored.runtime.get("runtime.paused")?.as_deref(),
+        Some("hibernated")
+    );
+
+    // Starting a hibernated VM restores it instead of booting afresh
+    reloaded.start_vm(&vm.id).await?;
+    assert_eq!(
+        platform.calls().last().map(String::as_str),
+        Some("restore /tmp/aiva-jailer/web/root/snapshot")
+    );
+    assert!(!platform.calls().contains(&"start".to_string()));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_paused_vm_can_still_be_hibernated() -> Result<()> {
+    let (platform, vm_manager, vm, _state) = running_vm().await?;
+
+    vm_manager.pause_vm(&vm.id, false).await?;
+    assert!(matches!(
+        vm_manager.pause_vm(&vm.id, false).await,
+        Err(AivaError::InvalidStateTransition(_))
+    ));
+
+    vm_manager.pause_vm(&vm.id, true).await?;
+    let hibernated = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(hibernated.state, VMState::Stopped);
+    assert!(hibernated.runtime.is_hibernated());
+    assert_eq!(platform.calls(), vec!["pause", "hibernate"]);
+
+    // Nothing is left to pause once it is on disk
+    assert!(matches!(
+        vm_manager.pause_vm(&vm.id, true).await,
+        Err(AivaError::InvalidStateTransition(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resume_path_follows_the_pause_record() -> Result<()> {
+    let (_platform, vm_manager, vm, _state) = running_vm().await?;
+    let mut vm = vm_manager.get_vm(&vm.id).await?.unwrap();
+
+    // Running VMs have nothing to resume
+    assert!(resume_path(&vm).is_err());
+    assert!(check_pause(&vm, false).is_ok());
+
+    vm.state = VMState::Paused;
+    vm.runtime.record_pause(PauseState::InMemory);
+    assert_eq!(resume_path(&vm)?, ResumePath::Resume);
+
+    // Paused before pauses were recorded
+    vm.runtime.paused = None;
+    assert_eq!(resume_path(&vm)?, ResumePath::Resume);
+
+    vm.state = VMState::Stopped;
+    assert!(resume_path(&vm).is_err());
+    assert!(check_pause(&vm, true).is_err());
+
+    vm.runtime.record_pause(PauseState::Hibernated {
+        snapshot: snapshot(),
+    });
+    assert_eq!(resume_path(&vm)?, ResumePath::Restore(snapshot()));
+    assert!(vm.runtime.pid.is_none());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_disallowed_transitions_leave_the_vm_alone() -> Result<()> {
+    let (platform, vm_manager, vm, _state) = running_vm().await?;
+
+    // Running VMs have nothing to resume
+    assert!(matches!(
+        vm_manager.resume_vm(&vm.id).await,
+        Err(AivaError::InvalidStateTransition(_))
+    ));
+
+    // A stopped VM cannot be paused, nor resumed without a snapshot
+    vm_manager.stop_vm(&vm.id, false).await?;
+    for hibernate in [false, true] {
+        assert!(matches!(
+            vm_manager.pause_vm(&vm.id, hibernate).await,
+            Err(AivaError::InvalidStateTransition(_))
+        ));
+    }
+    assert!(matches!(
+        vm_manager.resume_vm(&vm.id).await,
+        Err(AivaError::InvalidStateTransition(_))
+    ));
+
+    assert_eq!(platform.calls(), vec!["stop"]);
+    let stopped = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(stopped.state, VMState::Stopped);
+    assert_eq!(stopped.runtime.paused, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_paused_vm_can_be_stopped() -> Result<()> {
+    let (_platform, vm_manager, vm, _state) = running_vm().await?;
+
+    vm_manager.pause_vm(&vm.id, false).await?;
+    vm_manager.stop_vm(&vm.id, false).await?;
+    let stopped = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(stopped.state, VMState::Stopped);
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use super::{MockPlatform, TestState};
+use crate::{PortMapping, Protocol, Result, VMManager, VMTemplate};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Platform that copies the rootfs into a per-VM workspace on create
+fn copying(workspace: &Path) -> Arc<MockPlatform> {
+    Arc::new(MockPlatform::new().copying_to(workspace.to_path_buf()))
+}
+
+#[tokio::test]
+async fn test_plan_lists_allocations_without_side_effects() -> Result<()> {
+    let state = TestState::new();
+    let workspace = state.path().with_file_name("workspace");
+    let vm_manager = state.orchestrator(copying(&workspace));
+
+    let template = VMTemplate::python3_uv();
+    let mut config = template.generate_vm_config(None);
+    config.network.port_mappings = vec![PortMapping::new(8080, 3000, Protocol::Tcp)];
+
+    let plan = vm_manager
+        .plan_create("preview", &config)
+        .await?
+        .with_template(&template);
+
+    assert_eq!(plan.name, "preview");
+    assert_eq!(plan.template.as_deref(), Some(template.name.as_str()));
+    assert_eq!(plan.guest_ip, config.network.guest_ip);
+    assert_eq!(plan.port_mappings.len(), 1);
+    assert_eq!(plan.port_mappings[0].host_port, 8080);
+    assert_eq!(plan.port_mappings[0].guest_port, 3000);
+    assert_eq!(plan.image_copies.len(), 1);
+    assert!(plan.image_copies[0].destination.starts_with(&workspace));
+    assert_eq!(plan.resources, ["TAP device for preview"]);
+    assert!(!plan.has_conflicts());
+
+    // Nothing was created: no state, no workspace, no registered VM
+    assert!(!state.path().exists());
+    assert!(!workspace.exists());
+    assert!(vm_manager.list_vms().await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_reports_conflicts_with_existing_vms() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(copying(&state.path().with_file_name("workspace")));
+
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.network.port_mappings = vec![PortMapping::new(8080, 3000, Protocol::Tcp)];
+    vm_manager
+        .create_vm("first".to_string(), config.clone())
+        .await?;
+
+    let plan = vm_manager.plan_create("second", &config).await?;
+    assert!(plan.conflicts.iter().any(|c| c.contains("Guest IP")));
+    assert!(plan.conflicts.iter().any(|c| c.contains("Host port 8080")));
+
+    let plan = vm_manager.plan_create("first", &config).await?;
+    assert_eq!(plan.conflicts, ["VM 'first' already exists"]);
+
+    Ok(())
+}
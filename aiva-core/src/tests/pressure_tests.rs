@@ -0,0 +1,138 @@
+use crate::{
+    AlertThresholds, DefaultMetricsCollector, DiskIOMetrics, MemoryMetrics, MetricsSortKey,
+    MonitoringService, NetworkIOMetrics, Pressure, Result, VMMetrics,
+};
+use std::time::Duration;
+
+fn metrics(cpu: f64, used_mb: u64, net_bytes: u64) -> VMMetrics {
+    VMMetrics {
+        cpu_usage: cpu,
+        memory_usage: MemoryMetrics {
+            total_mb: 1000,
+            used_mb,
+            available_mb: 1000 - used_mb,
+            cache_mb: 0,
+        },
+        disk_io: DiskIOMetrics {
+            read_bytes: 0,
+            write_bytes: 0,
+            read_ops: 0,
+            write_ops: 0,
+        },
+        network_io: NetworkIOMetrics {
+            rx_bytes: net_bytes,
+            tx_bytes: net_bytes / 2,
+            rx_packets: 0,
+            tx_packets: 0,
+        },
+        uptime: Duration::from_secs(60),
+    }
+}
+
+fn sorted(samples: &[(&str, VMMetrics)], key: MetricsSortKey) -> Vec<String> {
+    let mut samples = samples.to_vec();
+    samples.sort_by(|(_, a), (_, b)| key.compare(a, b));
+    samples
+        .into_iter()
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+#[test]
+fn test_sort_orders_busiest_first() {
+    let samples = [
+        ("idle", metrics(2.0, 100, 10)),
+        ("compute", metrics(97.5, 300, 500)),
+        ("cache", metrics(40.0, 900, 20)),
+        ("proxy", metrics(15.0, 200, 90_000)),
+    ];
+
+    assert_eq!(
+        sorted(&samples, MetricsSortKey::Cpu),
+        ["compute", "cache", "proxy", "idle"]
+    );
+    assert_eq!(
+        sorted(&samples, MetricsSortKey::Memory),
+        ["cache", "compute", "proxy", "idle"]
+    );
+    assert_eq!(
+        sorted(&samples, MetricsSortKey::Network),
+        ["proxy", "compute", "cache", "idle"]
+    );
+}
+
+#[test]
+fn test_sort_is_stable_for_ties() {
+    let samples = [("a", metrics(50.0, 100, 0)), ("b", metrics(50.0, 100, 0))];
+    assert_eq!(sorted(&samples, MetricsSortKey::Cpu), ["a", "b"]);
+}
+
+#[test]
+fn test_assess_flags_threshold_crossings() {
+    let thresholds = AlertThresholds::default();
+
+    let calm = thresholds.assess(&metrics(30.0, 500, 0));
+    assert_eq!(calm.level, Pressure::Normal);
+    assert!(calm.hot.is_empty());
+
+    // Exactly at the threshold is not over it
+    assert_eq!(
+        thresholds.assess(&metrics(80.0, 800, 0)).level,
+        Pressure::Normal
+    );
+
+    let warm = thresholds.assess(&metrics(85.0, 500, 0));
+    assert_eq!(warm.level, Pressure::Warning);
+    assert_eq!(warm.hot, ["cpu"]);
+
+    let hot = thresholds.assess(&metrics(85.0, 990, 0));
+    assert_eq!(hot.level, Pressure::Critical);
+    assert_eq!(hot.hot, ["cpu", "mem"]);
+}
+
+#[test]
+fn test_assess_ignores_unknown_memory_total() {
+    let mut sample = metrics(10.0, 0, 0);
+    sample.memory_usage.total_mb = 0;
+    sample.memory_usage.available_mb = 0;
+
+    assert_eq!(sample.memory_percent(), 0.0);
+    assert_eq!(
+        AlertThresholds::default().assess(&sample).level,
+        Pressure::Normal
+    );
+}
+
+#[test]
+fn test_memory_from_kb_never_underflows() {
+    // RSS read just after VmSize can be the larger of the two
+    let memory = MemoryMetrics::from_kb(1024 * 1024, 1024 * 1024 + 4096);
+    assert_eq!((memory.total_mb, memory.used_mb), (1024, 1028));
+    assert_eq!(memory.available_mb, 0);
+
+    let memory = MemoryMetrics::from_kb(0, 2048);
+    assert_eq!((memory.total_mb, memory.available_mb), (0, 0));
+
+    let memory = MemoryMetrics::from_kb(4096 * 1024, 1024 * 1024);
+    assert_eq!(memory.available_mb, 3072);
+}
+
+#[tokio::test]
+async fn test_analyze_handles_pathological_memory() -> Result<()> {
+    let monitoring = MonitoringService::new(Box::new(DefaultMetricsCollector));
+
+    let mut unknown_total = metrics(10.0, 0, 0);
+    unknown_total.memory_usage = MemoryMetrics::from_kb(0, 512 * 1024);
+    monitoring
+        .analyze_vm_metrics("vm-1", &unknown_total)
+        .await?;
+    assert!(monitoring.get_alerts(Some("vm-1")).await?.is_empty());
+
+    let mut overcommitted = metrics(10.0, 0, 0);
+    overcommitted.memory_usage = MemoryMetrics::from_kb(1024 * 1024, 2048 * 1024);
+    monitoring
+        .analyze_vm_metrics("vm-2", &overcommitted)
+        .await?;
+    assert_eq!(monitoring.get_alerts(Some("vm-2")).await?.len(), 1);
+    Ok(())
+}
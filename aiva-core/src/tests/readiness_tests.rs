@@ -0,0 +1,130 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, GuestNetworkProbe, PortProbe, Result, VMManager, VMTemplate, check_guest_network,
+    wait_for_guest, wait_for_port,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// Platform whose guest agent answers, but whose rootfs has no DHCP client
+/// and therefore never configures an address
+fn no_dhcp() -> Arc<MockPlatform> {
+    Arc::new(
+        MockPlatform::new().exec(|command| match command.strip_prefix("echo ") {
+            Some(echoed) => echoed.to_string(),
+            None => "missing=dhcp-client\naddr=\ngateway=fail\ndns=fail\n".to_string(),
+        }),
+    )
+}
+
+#[test]
+fn test_probe_parse() {
+    let probe = GuestNetworkProbe::parse("addr=172.16.0.2/24\ngateway=ok\ndns=fail\nnoise\n");
+
+    assert_eq!(probe.address.as_deref(), Some("172.16.0.2"));
+    assert!(probe.missing_tools.is_empty());
+    assert!(probe.gateway_reachable);
+    assert!(!probe.dns_resolves);
+}
+
+#[tokio::test]
+async fn test_guest_without_address_fails_readiness_with_network_error() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(no_dhcp());
+
+    let mut config = VMTemplate::python3_uv().generate_vm_config(None);
+    config.network.dhcp_enabled = true;
+    let vm = vm_manager.create_vm("offline".to_string(), config).await?;
+    vm_manager.start_vm(&vm.id).await?;
+
+    // The agent itself is reachable, only guest networking is broken
+    wait_for_guest(&vm_manager, &vm.id, Duration::from_secs(1)).await?;
+
+    let vm = vm_manager.get_vm(&vm.id).await?.unwrap();
+    match check_guest_network(&vm_manager, &vm).await {
+        Err(AivaError::GuestNetworkUnavailable {
+            vm_name,
+            reason,
+            hint,
+        }) => {
+            assert_eq!(vm_name, "offline");
+            assert!(reason.contains("did not get an IPv4 address"));
+            assert!(reason.contains("dhcp-client"));
+            assert!(hint.contains("DHCP is enabled"));
+        }
+        other => panic!("expected a guest network error, got {other:?}"),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_static_config_ignores_missing_dhcp_client() {
+    let mut network = VMTemplate::python3_uv().generate_vm_config(None).network;
+    network.dhcp_enabled = false;
+
+    let probe = GuestNetworkProbe {
+        missing_tools: vec!["dhcp-client".to_string()],
+        address: Some(network.guest_ip.clone()),
+        gateway_reachable: true,
+        dns_resolves: true,
+    };
+    assert!(probe.diagnose("static", &network).is_ok());
+
+    let unconfigured = GuestNetworkProbe {
+        address: None,
+        ..probe
+    };
+    let err = unconfigured.diagnose("static", &network).unwrap_err();
+    assert!(err.to_string().contains("Static addressing"));
+    assert!(!err.to_string().contains("dhcp-client"));
+}
+
+/// Reserve a free local port and release it so a listener can bind it later
+fn free_port() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_wait_for_port_succeeds_once_listener_opens() -> Result<()> {
+    let addr = free_port();
+    let listener = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+        // Keep accepting so both probes below get a connection
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let _ = socket
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await;
+        }
+    });
+
+    let waited = wait_for_port(addr, &PortProbe::Tcp, Duration::from_secs(5)).await?;
+    assert!(waited >= Duration::from_millis(300));
+
+    let probe = PortProbe::Http {
+        path: "/health".to_string(),
+    };
+    wait_for_port(addr, &probe, Duration::from_secs(5)).await?;
+
+    listener.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wait_for_port_times_out_without_listener() {
+    let addr = free_port();
+
+    match wait_for_port(addr, &PortProbe::Tcp, Duration::from_millis(300)).await {
+        Err(AivaError::NetworkError { operation, .. }) => {
+            assert!(operation.contains(&addr.to_string()));
+        }
+        other => panic!("expected a timeout, got {other:?}"),
+    }
+}
@@ -0,0 +1,65 @@
+use super::{MockPlatform, TestState};
+use crate::{DivergenceKind, Liveness, Result, VMManager, VMState, VMTemplate, detect_divergence};
+use std::sync::Arc;
+
+#[test]
+fn test_divergence_matrix() {
+    use DivergenceKind::*;
+    use Liveness::*;
+    use VMState::*;
+
+    let cases = [
+        (Running, Alive, None),
+        (Running, Dead, Some(ProcessDead)),
+        (Running, Unknown, None),
+        (Paused, Dead, Some(ProcessDead)),
+        (Paused, Alive, None),
+        (Stopped, Alive, Some(ProcessAlive)),
+        (Stopped, Dead, None),
+        (Stopped, Unknown, None),
+        (Creating, Dead, None),
+        (Creating, Alive, None),
+        (Stopping, Dead, None),
+        (Stopping, Alive, None),
+        (Error, Dead, None),
+        (Error, Alive, None),
+    ];
+    for (stored, liveness, expected) in cases {
+        assert_eq!(
+            detect_divergence(stored, liveness),
+            expected,
+            "{stored:?} with {liveness:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_reconcile_marks_dead_vms_as_error_only_when_asked() -> Result<()> {
+    // Every hypervisor process has died
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(Arc::new(MockPlatform::new().liveness(Liveness::Dead)));
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let running = vm_manager
+        .create_vm("running".to_string(), config.clone())
+        .await?;
+    vm_manager.start_vm(&running.id).await?;
+    vm_manager.create_vm("stopped".to_string(), config).await?;
+
+    let reported = vm_manager.reconcile_states(false).await?;
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].vm_name, "running");
+    assert_eq!(
+        reported[0].describe(),
+        "VM 'running': state=Running but process dead"
+    );
+    let vm = vm_manager.get_vm(&running.id).await?.unwrap();
+    assert_eq!(vm.state, VMState::Running);
+
+    let corrected = vm_manager.reconcile_states(true).await?;
+    assert_eq!(corrected, reported);
+    let vm = vm_manager.get_vm(&running.id).await?.unwrap();
+    assert_eq!(vm.state, VMState::Error);
+
+    assert!(vm_manager.reconcile_states(true).await?.is_empty());
+    Ok(())
+}
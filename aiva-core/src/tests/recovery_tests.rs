@@ -0,0 +1,69 @@
+use super::{MockPlatform, TestState};
+use crate::{AivaError, Result, VMManager, VMState, VMTemplate};
+use std::sync::Arc;
+
+/// Platform whose first `failing_starts` starts fail after leaving a process
+/// and TAP device behind
+fn flaky(failing_starts: usize) -> Arc<MockPlatform> {
+    Arc::new(
+        MockPlatform::new()
+            .with_process(4242)
+            .failing_starts(failing_starts),
+    )
+}
+
+#[tokio::test]
+async fn test_failed_start_can_be_recovered_and_restarted() -> Result<()> {
+    let platform = flaky(1);
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform.clone());
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("broken".to_string(), config).await?;
+
+    assert!(vm_manager.start_vm(&vm.id).await.is_err());
+    let errored = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(errored.state, VMState::Error);
+
+    let report = vm_manager.recover_vm(&vm.id, true).await?;
+    assert_eq!(report.vm_name, "broken");
+    assert_eq!(
+        report.cleaned,
+        vec!["killed process 4242", "deleted TAP device tap-broken"]
+    );
+    assert!(report.restarted);
+    assert_eq!(platform.cleaned(), report.cleaned);
+
+    let recovered = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(recovered.state, VMState::Running);
+    assert!(recovered.runtime.pid.is_none());
+    assert!(recovered.runtime.tap_device.is_none());
+    assert!(recovered.runtime.api_socket.is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_recover_without_restart_leaves_vm_stopped() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(flaky(1));
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("broken".to_string(), config).await?;
+    let _ = vm_manager.start_vm(&vm.id).await;
+
+    let report = vm_manager.recover_vm(&vm.id, false).await?;
+    assert!(!report.restarted);
+    assert_eq!(
+        vm_manager.get_vm(&vm.id).await?.unwrap().state,
+        VMState::Stopped
+    );
+
+    // A healthy VM has nothing to recover from
+    assert!(matches!(
+        vm_manager.recover_vm(&vm.id, false).await,
+        Err(AivaError::InvalidStateTransition(_))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use super::{MockPlatform, TestState};
+use crate::{FixStatus, OrphanedResource, Result, VMManager, VMState, VMTemplate, fix_fleet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn orphans() -> Vec<OrphanedResource> {
+    vec![
+        OrphanedResource::TapDevice("aiva-tap-gone".to_string()),
+        OrphanedResource::Workspace(PathBuf::from("/tmp/aiva-jailer/busy")),
+    ]
+}
+
+/// Make every VM in `state_file` look last updated ten minutes ago
+fn age_state(state_file: &Path) -> Result<()> {
+    let mut state: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(state_file)?)?;
+    let then = chrono::Utc::now() - chrono::Duration::minutes(10);
+    for vm in state.as_object_mut().unwrap().values_mut() {
+        vm["updated_at"] = serde_json::to_value(then)?;
+    }
+    std::fs::write(state_file, serde_json::to_string(&state)?)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_fix_resets_stuck_vms_and_removes_orphans_only() -> Result<()> {
+    // Two orphaned resources, one of which will not go away
+    let platform = Arc::new(
+        MockPlatform::new()
+            .created(VMState::Creating)
+            .orphans(orphans(), vec![orphans()[1].clone()]),
+    );
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform.clone());
+    for name in ["web", "stuck"] {
+        let config = VMTemplate::python3_uv().generate_vm_config(None);
+        vm_manager.create_vm(name.to_string(), config).await?;
+    }
+    let web = vm_manager.get_vm_by_name("web").await?.unwrap();
+    vm_manager
+        .force_reset_vm_state(&web.id, VMState::Running)
+        .await?;
+    age_state(&state.path())?;
+
+    let vm_manager = state.orchestrator(platform.clone());
+    vm_manager.load_state().await?;
+    let remediations = fix_fleet(&vm_manager).await?;
+
+    let stuck = vm_manager.get_vm_by_name("stuck").await?.unwrap();
+    assert_eq!(stuck.state, VMState::Stopped);
+    let web = vm_manager.get_vm_by_name("web").await?.unwrap();
+    assert_eq!(web.state, VMState::Running);
+
+    // Every known VM, the running one included, is excluded from cleanup
+    assert_eq!(platform.known(), vec!["stuck", "web"]);
+    assert_eq!(platform.removed(), orphans());
+
+    let outcomes: Vec<(&str, FixStatus)> = remediations
+        .iter()
+        .map(|remediation| (remediation.issue.as_str(), remediation.status))
+        .collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            ("VM 'stuck' stuck in Creating", FixStatus::Fixed),
+            ("orphaned TAP device aiva-tap-gone", FixStatus::Fixed),
+            (
+                "orphaned jailer workspace /tmp/aiva-jailer/busy",
+                FixStatus::Failed
+            ),
+        ]
+    );
+    assert!(remediations[2].detail.contains("busy"));
+    Ok(())
+}
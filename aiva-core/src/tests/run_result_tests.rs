@@ -0,0 +1,79 @@
+use crate::{AivaError, ExecContext, PortMapping, Protocol, RunResult, VMTemplate};
+
+#[test]
+fn test_sse_without_port_uses_template_default() {
+    let template = VMTemplate::python3_uv();
+    let network = template.generate_vm_config(None).network;
+
+    let plan = template
+        .plan_run("server.py", &[], "sse", None, &ExecContext::default())
+        .unwrap();
+    assert_eq!(
+        plan.command,
+        "cd '/opt/mcp' && uv run server.py sse --port 3000"
+    );
+    assert_eq!(plan.port, Some(3000));
+
+    let result = RunResult::new(&plan, &network, None);
+    assert_eq!(result.transport, "sse");
+    assert_eq!(result.host, "127.0.0.1");
+    assert_eq!(result.port, Some(3000));
+    assert_eq!(result.url().as_deref(), Some("http://127.0.0.1:3000"));
+}
+
+#[test]
+fn test_sse_with_explicit_port_follows_port_mapping() {
+    let template = VMTemplate::nodejs22_npx();
+    let mut network = template.generate_vm_config(None).network;
+    let mut mapping = PortMapping::new(9090, 8080, Protocol::Tcp);
+    mapping.host_ip = None;
+    network.port_mappings = vec![mapping];
+
+    let plan = template
+        .plan_run("my-server", &[], "sse", Some(8080), &ExecContext::default())
+        .unwrap();
+    assert!(plan.command.ends_with("npx my-server sse --port 8080"));
+
+    // An all-interfaces mapping is still reached through loopback
+    let result = RunResult::new(&plan, &network, Some(4242));
+    assert_eq!(result.host, "127.0.0.1");
+    assert_eq!(result.port, Some(9090));
+    assert_eq!(result.pid, Some(4242));
+
+    // A port already in the command must agree with the flag
+    assert!(matches!(
+        template.plan_run(
+            "my-server --port=7000",
+            &[],
+            "sse",
+            Some(8080),
+            &ExecContext::default()
+        ),
+        Err(AivaError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_stdio_has_no_port() {
+    let template = VMTemplate::python3_uv();
+    let network = template.generate_vm_config(None).network;
+
+    for port in [None, Some(8080)] {
+        let plan = template
+            .plan_run("server.py", &[], "stdio", port, &ExecContext::default())
+            .unwrap();
+        let result = RunResult::new(&plan, &network, None);
+
+        assert_eq!(result.transport, "stdio");
+        assert_eq!(result.host, network.guest_ip);
+        assert_eq!(result.port, None);
+        assert!(result.url().is_none());
+    }
+}
+
+#[test]
+fn test_pid_from_platform_output() {
+    let output = "MCP server started on port 3000\nPID: 1234\n\nServer is running";
+    assert_eq!(RunResult::pid_from_output(output), Some(1234));
+    assert_eq!(RunResult::pid_from_output("no pid here"), None);
+}
@@ -0,0 +1,47 @@
+use super::{MockPlatform, TestState};
+use crate::{AivaError, Result, RuntimeInfo, VMManager, VMState, VMTemplate};
+use std::sync::Arc;
+
+/// Platform that assigns runtime resources the way Firecracker does on create
+fn runtime_platform() -> Arc<MockPlatform> {
+    Arc::new(MockPlatform::new().with_process(4242))
+}
+
+#[tokio::test]
+async fn test_runtime_pid_is_read_from_live_state() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(runtime_platform());
+
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("live".to_string(), config).await?;
+    vm_manager.start_vm(&vm.id).await?;
+
+    // A fresh orchestrator sees the same values through the persisted state
+    let reloaded = state.orchestrator(runtime_platform());
+    reloaded.load_state().await?;
+    let vm = reloaded.get_vm_by_name("live").await?.unwrap();
+    assert_eq!(vm.state, VMState::Running);
+
+    assert_eq!(vm.runtime.get("runtime.pid")?.as_deref(), Some("4242"));
+    assert_eq!(
+        vm.runtime.get("runtime.tap_device")?.as_deref(),
+        Some("tap-live")
+    );
+    assert_eq!(vm.runtime.get("runtime.vsock_cid")?.as_deref(), Some("3"));
+    assert!(matches!(
+        vm.runtime.get("runtime.memory"),
+        Err(AivaError::ConfigError(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn test_runtime_key_detection() {
+    assert!(
+        RuntimeInfo::KEYS
+            .iter()
+            .all(|key| RuntimeInfo::is_runtime_key(key))
+    );
+    assert!(!RuntimeInfo::is_runtime_key("network.guest_ip"));
+}
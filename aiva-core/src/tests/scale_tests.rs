@@ -0,0 +1,182 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, Result, ScaleCapabilities, ScaleRequest, ScaleStep, VMConfig, VMInstance, VMManager,
+    VMOrchestrator, VMState, plan_scale,
+};
+use std::sync::Arc;
+
+fn config(cpus: u32, memory_mb: u64) -> VMConfig {
+    VMConfig::builder()
+        .cpus(cpus)
+        .memory_mb(memory_mb)
+        .build()
+        .unwrap()
+}
+
+fn request(cpus: Option<u32>, memory_mb: Option<u64>) -> ScaleRequest {
+    ScaleRequest { cpus, memory_mb }
+}
+
+const BALLOON: ScaleCapabilities = ScaleCapabilities {
+    balloon_max_mb: Some(8192),
+    cpu_hotplug: false,
+};
+
+#[test]
+fn test_memory_within_boot_size_is_ballooned_live() {
+    let plan = plan_scale(&config(2, 8192), true, request(None, Some(4096)), &BALLOON).unwrap();
+
+    assert_eq!(
+        plan.live,
+        vec![ScaleStep::Balloon {
+            memory_mb: 4096,
+            balloon_mib: 4096
+        }]
+    );
+    assert!(!plan.restart_required());
+
+    // Growing back to the boot size deflates the balloon completely
+    let plan = plan_scale(&config(2, 4096), true, request(None, Some(8192)), &BALLOON).unwrap();
+    assert_eq!(
+        plan.live,
+        vec![ScaleStep::Balloon {
+            memory_mb: 8192,
+            balloon_mib: 0
+        }]
+    );
+}
+
+#[test]
+fn test_growing_past_boot_memory_requires_restart() {
+    let plan = plan_scale(&config(2, 8192), true, request(None, Some(16384)), &BALLOON).unwrap();
+
+    assert!(plan.live.is_empty());
+    assert!(plan.restart_required());
+    assert!(plan.restart_reasons[0].contains("8192MB"));
+    assert_eq!(plan.memory_mb, 16384);
+}
+
+#[test]
+fn test_missing_capabilities_require_restart() {
+    let none = ScaleCapabilities::default();
+    let plan = plan_scale(&config(2, 4096), true, request(Some(4), Some(2048)), &none).unwrap();
+
+    assert!(plan.live.is_empty());
+    assert_eq!(plan.restart_reasons.len(), 2);
+    assert!(plan.restart_reasons[0].contains("vCPUs"));
+    assert!(plan.restart_reasons[1].contains("no balloon"));
+
+    let hotplug = ScaleCapabilities {
+        cpu_hotplug: true,
+        ..BALLOON
+    };
+    let plan = plan_scale(&config(2, 4096), true, request(Some(4), None), &hotplug).unwrap();
+    assert_eq!(plan.live, vec![ScaleStep::HotplugCpus { cpus: 4 }]);
+    assert!(!plan.restart_required());
+}
+
+#[test]
+fn test_stopped_vm_only_updates_config() {
+    let plan = plan_scale(
+        &config(2, 4096),
+        false,
+        request(Some(8), Some(32768)),
+        &ScaleCapabilities::default(),
+    )
+    .unwrap();
+
+    assert_eq!((plan.cpus, plan.memory_mb), (8, 32768));
+    assert!(plan.is_noop());
+}
+
+#[test]
+fn test_invalid_targets_are_rejected() {
+    let current = config(2, 4096);
+    assert!(matches!(
+        plan_scale(&current, true, request(Some(0), None), &BALLOON),
+        Err(AivaError::ConfigError(_))
+    ));
+    assert!(matches!(
+        plan_scale(&current, true, request(None, Some(64)), &BALLOON),
+        Err(AivaError::ConfigError(_))
+    ));
+}
+
+/// A VM that runs right after create on a platform booting it with
+/// `capabilities`
+async fn running_vm(
+    capabilities: ScaleCapabilities,
+) -> Result<(VMOrchestrator, Arc<MockPlatform>, VMInstance, TestState)> {
+    let platform = Arc::new(
+        MockPlatform::new()
+            .created(VMState::Running)
+            .scalable(capabilities),
+    );
+    let state = TestState::new();
+    let orchestrator = state.orchestrator(platform.clone());
+    let vm = orchestrator
+        .create_vm("agent".to_string(), config(2, 8192))
+        .await?;
+    Ok((orchestrator, platform, vm, state))
+}
+
+#[tokio::test]
+async fn test_balloon_target_squeezes_a_running_vm() -> Result<()> {
+    let (orchestrator, platform, vm, _state) = running_vm(BALLOON).await?;
+
+    orchestrator.set_balloon_target(&vm.id, 4096).await?;
+    orchestrator.set_balloon_target(&vm.id, 0).await?;
+
+    assert_eq!(
+        platform.applied(),
+        vec![
+            ScaleStep::Balloon {
+                memory_mb: 4096,
+                balloon_mib: 4096
+            },
+            ScaleStep::Balloon {
+                memory_mb: 8192,
+                balloon_mib: 0
+            },
+        ]
+    );
+    // Ballooning is not a resize; the next boot gets the full memory again
+    let vm = orchestrator.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(vm.config.memory_mb, 8192);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_balloon_target_needs_a_balloon_device() -> Result<()> {
+    let (orchestrator, platform, vm, _state) = running_vm(ScaleCapabilities::default()).await?;
+
+    let err = orchestrator
+        .set_balloon_target(&vm.id, 1024)
+        .await
+        .unwrap_err();
+    match err {
+        AivaError::PlatformError {
+            message,
+            recoverable,
+            ..
+        } => {
+            assert!(message.contains("without a balloon device"), "{message}");
+            assert!(recoverable);
+        }
+        other => panic!("unexpected error: {other}"),
+    }
+    assert!(platform.applied().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_balloon_target_leaves_the_guest_enough_memory() -> Result<()> {
+    let (orchestrator, platform, vm, _state) = running_vm(BALLOON).await?;
+
+    assert!(matches!(
+        orchestrator.set_balloon_target(&vm.id, 8192).await,
+        Err(AivaError::ConfigError(_))
+    ));
+    assert!(platform.applied().is_empty());
+    Ok(())
+}
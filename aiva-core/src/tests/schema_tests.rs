@@ -0,0 +1,96 @@
+use super::{MockPlatform, TestState};
+use crate::{AivaError, Result, SCHEMA_VERSION, VMManager, VMState, migrate_config, migrate_state};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+
+const VM_ID: &str = "6f1c2d3e-4a5b-4c6d-8e7f-901234567890";
+
+/// A VM record as written before any field was added to the baseline layout
+fn v0_state() -> String {
+    format!(
+        r#"{{
+  "{VM_ID}": {{
+    "id": "{VM_ID}",
+    "name": "legacy",
+    "state": "Stopped",
+    "config": {{
+      "cpus": 2,
+      "memory_mb": 2048,
+      "disk_gb": 10,
+      "kernel_path": "/opt/aiva/images/vmlinux",
+      "rootfs_path": "/opt/aiva/images/rootfs.ext4",
+      "network": {{
+        "guest_ip": "172.16.0.2",
+        "host_ip": "172.16.0.1",
+        "subnet": "172.16.0.0/24",
+        "gateway": "172.16.0.1",
+        "dns_servers": ["8.8.8.8"],
+        "dhcp_enabled": false,
+        "port_mappings": [{{"host_port": 3000, "guest_port": 3000, "protocol": "Tcp"}}]
+      }},
+      "storage": {{"cache_strategy": "Writeback", "additional_drives": []}}
+    }},
+    "runtime": {{"pid": null, "api_socket": null, "vsock_cid": null, "tap_device": null}},
+    "created_at": "2025-01-01T00:00:00Z",
+    "updated_at": "2025-01-01T00:00:00Z"
+  }}
+}}"#
+    )
+}
+
+#[test]
+fn test_v0_state_is_upgraded_with_defaults() -> Result<()> {
+    let (vms, migrated) = migrate_state(&v0_state())?;
+    assert!(migrated);
+
+    let vm = &vms[&VM_ID.parse().unwrap()];
+    assert_eq!(vm.name, "legacy");
+    assert_eq!(vm.state, VMState::Stopped);
+    assert_eq!(vm.schema_version, SCHEMA_VERSION);
+    assert_eq!(vm.config.schema_version, SCHEMA_VERSION);
+    assert!(vm.config.network.dns_search.is_empty());
+    assert!(vm.config.logging.paths.is_empty());
+    assert_eq!(vm.config.workdir, None);
+    assert_eq!(
+        vm.config.network.port_mappings[0].host_ip,
+        Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+    );
+
+    // Migrating the current shape again changes nothing
+    let current = serde_json::to_string(&vms)?;
+    let (_, migrated) = migrate_state(&current)?;
+    assert!(!migrated);
+    Ok(())
+}
+
+#[test]
+fn test_newer_schema_is_refused() {
+    let mut config =
+        serde_json::to_value(crate::VMTemplate::python3_uv().generate_vm_config(None)).unwrap();
+    config["schema_version"] = (SCHEMA_VERSION + 1).into();
+
+    assert!(matches!(
+        migrate_config(config),
+        Err(AivaError::ConfigError(message)) if message.contains("Upgrade aiva")
+    ));
+}
+
+#[tokio::test]
+async fn test_load_state_writes_back_migrated_state() -> Result<()> {
+    let state = TestState::new();
+    std::fs::write(state.path(), v0_state())?;
+
+    let vm_manager = state.orchestrator(Arc::new(MockPlatform::new()));
+    vm_manager.load_state().await?;
+    assert!(vm_manager.get_vm_by_name("legacy").await?.is_some());
+
+    let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(state.path())?)?;
+    let record = &written[VM_ID];
+    assert_eq!(record["schema_version"], SCHEMA_VERSION);
+    assert_eq!(record["config"]["schema_version"], SCHEMA_VERSION);
+    assert_eq!(
+        record["config"]["network"]["port_mappings"][0]["host_ip"],
+        "127.0.0.1"
+    );
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use super::{MockPlatform, TestState};
+use crate::{
+    AivaError, Result, RunPlan, ServerPidFile, ServerTeardown, VMManager, VMTemplate,
+    replace_existing_server,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Run a command the way the guest agent would
+async fn sh(command: String) -> Result<String> {
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .await?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn temp_pid_file() -> PathBuf {
+    std::env::temp_dir().join(format!("aiva-mcp-{}.pid", uuid::Uuid::new_v4()))
+}
+
+/// Start a stand-in server and record it in `path`. The child is reaped in
+/// the background so it disappears as soon as it is killed.
+fn spawn_fake_server(path: &PathBuf) -> u32 {
+    let mut child = tokio::process::Command::new("sleep")
+        .arg("30")
+        .spawn()
+        .unwrap();
+    let pid = child.id().unwrap();
+    std::fs::write(path, pid.to_string()).unwrap();
+    tokio::spawn(async move { child.wait().await });
+    pid
+}
+
+#[tokio::test]
+async fn test_running_server_is_a_collision_without_restart() -> Result<()> {
+    let path = temp_pid_file();
+    let pid = spawn_fake_server(&path);
+    let pid_file = ServerPidFile::at("agent", path.clone());
+
+    match replace_existing_server(&pid_file, false, sh).await {
+        Err(AivaError::VMError { message, .. }) => {
+            assert!(message.contains(&format!("PID {pid}")));
+            assert!(message.contains("--restart-existing"));
+        }
+        other => panic!("expected a collision, got {other:?}"),
+    }
+    // Nothing was touched
+    assert!(path.exists());
+
+    replace_existing_server(&pid_file, true, sh).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_restart_existing_stops_the_recorded_server() -> Result<()> {
+    let path = temp_pid_file();
+    let pid = spawn_fake_server(&path);
+    let pid_file = ServerPidFile::at("agent", path.clone());
+
+    assert_eq!(
+        ServerPidFile::parse_probe(&sh(pid_file.probe_command()).await?),
+        Some(pid)
+    );
+
+    let replaced = replace_existing_server(&pid_file, true, sh).await?;
+    assert_eq!(replaced, Some(pid));
+    assert!(!path.exists());
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stale_pid_file_is_not_a_collision() -> Result<()> {
+    let path = temp_pid_file();
+    // PIDs are capped well below this on Linux
+    std::fs::write(&path, "99999999")?;
+    let pid_file = ServerPidFile::at("agent", path.clone());
+
+    assert_eq!(replace_existing_server(&pid_file, false, sh).await?, None);
+    assert!(!path.exists());
+
+    // No file at all
+    assert_eq!(replace_existing_server(&pid_file, false, sh).await?, None);
+    Ok(())
+}
+
+/// Platform whose launcher reports the server PID like the Lima one does
+fn launcher() -> Arc<MockPlatform> {
+    Arc::new(
+        MockPlatform::new().exec(|_| "MCP server started on port 3000\nPID: 4242\n".to_string()),
+    )
+}
+
+#[tokio::test]
+async fn test_run_records_server_pid_until_stop() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(launcher());
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    let vm = vm_manager.create_vm("agent".to_string(), config).await?;
+    vm_manager.start_vm(&vm.id).await?;
+
+    let plan = RunPlan {
+        command: "server".to_string(),
+        transport: "sse".to_string(),
+        port: Some(3000),
+    };
+    vm_manager.run_server(&vm.id, &plan).await?;
+    vm_manager.run_server(&vm.id, &plan).await?;
+
+    let reloaded = state.orchestrator(launcher());
+    reloaded.load_state().await?;
+    let running = reloaded.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(running.runtime.mcp_pids, Some(vec![4242]));
+    assert_eq!(
+        ServerTeardown::for_instance(&running),
+        ServerTeardown::Pids(vec![4242])
+    );
+
+    vm_manager.stop_vm(&vm.id, false).await?;
+    let stopped = vm_manager.get_vm(&vm.id).await?.unwrap();
+    assert_eq!(stopped.runtime.mcp_pids, Some(Vec::new()));
+    // Nothing left to stop, and no reason to guess
+    assert_eq!(
+        ServerTeardown::for_instance(&stopped),
+        ServerTeardown::Pids(Vec::new())
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_teardown_stops_exactly_the_recorded_pids() -> Result<()> {
+    let path = temp_pid_file();
+    let recorded = spawn_fake_server(&path);
+    let bystander = spawn_fake_server(&temp_pid_file());
+    let pid_file = ServerPidFile::at("agent", path.clone());
+
+    let teardown = ServerTeardown::select("agent", Some(&[recorded]));
+    let command = teardown.command(&pid_file);
+    assert!(!command.contains("pkill"));
+    sh(command).await?;
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!std::path::Path::new(&format!("/proc/{recorded}")).exists());
+    assert!(std::path::Path::new(&format!("/proc/{bystander}")).exists());
+    assert!(!path.exists());
+
+    sh(format!("kill -9 {bystander}")).await?;
+    Ok(())
+}
+
+#[test]
+fn test_teardown_falls_back_to_pattern_without_pid_record() {
+    let teardown = ServerTeardown::select("agent", None);
+    assert_eq!(teardown, ServerTeardown::Pattern("mcp.*agent".to_string()));
+
+    let command = teardown.command(&ServerPidFile::for_vm("agent"));
+    assert!(command.contains("pkill -f 'mcp.*agent'"), "{command}");
+    assert!(command.contains("/tmp/mcp-agent.pid"), "{command}");
+}
@@ -0,0 +1,479 @@
+//! Mock platform and state files shared by the orchestrator tests
+
+use crate::{
+    AivaError, BlockDevice, ImageCopy, Liveness, OrphanedResource, Platform, PlatformPlan, Result,
+    ScaleCapabilities, ScaleStep, SnapshotFiles, VMInstance, VMMetadata, VMMetrics, VMOrchestrator,
+    VMState, check_rootfs_fits,
+};
+use async_trait::async_trait;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Calls of [`MockPlatform`] that can be made to fail for one VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockOp {
+    Create,
+    Start,
+    Metrics,
+}
+
+impl fmt::Display for MockOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockOp::Create => write!(f, "create"),
+            MockOp::Start => write!(f, "start"),
+            MockOp::Metrics => write!(f, "metrics"),
+        }
+    }
+}
+
+type ExecHook = Box<dyn Fn(&str) -> String + Send + Sync>;
+type MetricsHook = Box<dyn Fn(&VMInstance) -> VMMetrics + Send + Sync>;
+
+/// Platform standing in for Firecracker in orchestrator tests. Out of the
+/// box every call succeeds and VMs are created stopped; the builder methods
+/// set up what a test needs. The calls it gets are recorded.
+pub struct MockPlatform {
+    created_state: VMState,
+    pid: Option<u32>,
+    failures: Vec<(MockOp, String, String)>,
+    failing_starts: AtomicUsize,
+    delay: Duration,
+    exec: Option<ExecHook>,
+    metrics: Option<MetricsHook>,
+    liveness: Liveness,
+    scale: ScaleCapabilities,
+    live_drives: bool,
+    image_bytes: Option<u64>,
+    workspace: Option<PathBuf>,
+    orphans: Vec<OrphanedResource>,
+    stuck_orphans: Vec<OrphanedResource>,
+    in_flight: AtomicUsize,
+    peak: AtomicUsize,
+    calls: Mutex<Vec<String>>,
+    resized: Mutex<Vec<u64>>,
+    attached: Mutex<Vec<String>>,
+    applied: Mutex<Vec<ScaleStep>>,
+    published: Mutex<Vec<VMMetadata>>,
+    cleaned: Mutex<Vec<String>>,
+    known: Mutex<Vec<String>>,
+    removed: Mutex<Vec<OrphanedResource>>,
+}
+
+impl Default for MockPlatform {
+    fn default() -> Self {
+        Self {
+            created_state: VMState::Stopped,
+            pid: None,
+            failures: Vec::new(),
+            failing_starts: AtomicUsize::new(0),
+            delay: Duration::ZERO,
+            exec: None,
+            metrics: None,
+            liveness: Liveness::Unknown,
+            scale: ScaleCapabilities::default(),
+            live_drives: false,
+            image_bytes: None,
+            workspace: None,
+            orphans: Vec::new(),
+            stuck_orphans: Vec::new(),
+            in_flight: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+            calls: Mutex::new(Vec::new()),
+            resized: Mutex::new(Vec::new()),
+            attached: Mutex::new(Vec::new()),
+            applied: Mutex::new(Vec::new()),
+            published: Mutex::new(Vec::new()),
+            cleaned: Mutex::new(Vec::new()),
+            known: Mutex::new(Vec::new()),
+            removed: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl MockPlatform {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// State VMs are in once created
+    pub fn created(mut self, state: VMState) -> Self {
+        self.created_state = state;
+        self
+    }
+
+    /// Give created VMs hypervisor process `pid`, its API socket and a TAP
+    /// device named after the VM. A restored VM gets process `pid + 100`.
+    pub fn with_process(mut self, pid: u32) -> Self {
+        self.pid = Some(pid);
+        self
+    }
+
+    /// Fail `op` for the VM named `vm_name` with `message`
+    pub fn failing(mut self, op: MockOp, vm_name: &str, message: &str) -> Self {
+        self.failures
+            .push((op, vm_name.to_string(), message.to_string()));
+        self
+    }
+
+    /// Fail the first `count` starts, whatever the VM
+    pub fn failing_starts(self, count: usize) -> Self {
+        self.failing_starts.store(count, Ordering::SeqCst);
+        self
+    }
+
+    /// Make starts and metrics take `delay`, tracking how many run at once
+    pub fn slow(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Answer guest commands with `exec` instead of empty output
+    pub fn exec(mut self, exec: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.exec = Some(Box::new(exec));
+        self
+    }
+
+    /// Report `metrics` for each VM instead of not implementing them
+    pub fn metrics(
+        mut self,
+        metrics: impl Fn(&VMInstance) -> VMMetrics + Send + Sync + 'static,
+    ) -> Self {
+        self.metrics = Some(Box::new(metrics));
+        self
+    }
+
+    pub fn liveness(mut self, liveness: Liveness) -> Self {
+        self.liveness = liveness;
+        self
+    }
+
+    /// What running VMs can change without a restart
+    pub fn scalable(mut self, capabilities: ScaleCapabilities) -> Self {
+        self.scale = capabilities;
+        self
+    }
+
+    /// Accept drives attached to running VMs
+    pub fn live_drives(mut self) -> Self {
+        self.live_drives = true;
+        self
+    }
+
+    /// Rootfs images are `image_bytes` large, so smaller disks are refused
+    pub fn image_bytes(mut self, image_bytes: u64) -> Self {
+        self.image_bytes = Some(image_bytes);
+        self
+    }
+
+    /// Plan a copy of the rootfs into a per-VM directory of `workspace`
+    pub fn copying_to(mut self, workspace: PathBuf) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Report `orphans`, failing to remove those also in `stuck`
+    pub fn orphans(mut self, orphans: Vec<OrphanedResource>, stuck: Vec<OrphanedResource>) -> Self {
+        self.orphans = orphans;
+        self.stuck_orphans = stuck;
+        self
+    }
+
+    /// Lifecycle calls in order: `start`, `stop`, `delete`, `liveness`,
+    /// `pause`, `resume`, `hibernate` and `restore <snapshot state>`
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Disk sizes VMs were resized to, in GB
+    pub fn resized(&self) -> Vec<u64> {
+        self.resized.lock().unwrap().clone()
+    }
+
+    /// Ids of the drives attached to running VMs
+    pub fn attached(&self) -> Vec<String> {
+        self.attached.lock().unwrap().clone()
+    }
+
+    /// Live scale steps applied
+    pub fn applied(&self) -> Vec<ScaleStep> {
+        self.applied.lock().unwrap().clone()
+    }
+
+    /// Metadata documents published
+    pub fn published(&self) -> Vec<VMMetadata> {
+        self.published.lock().unwrap().clone()
+    }
+
+    /// What recovery cleaned up
+    pub fn cleaned(&self) -> Vec<String> {
+        self.cleaned.lock().unwrap().clone()
+    }
+
+    /// Sorted names of the VMs the last orphan scan was told about
+    pub fn known(&self) -> Vec<String> {
+        self.known.lock().unwrap().clone()
+    }
+
+    /// Orphans removal was attempted for
+    pub fn removed(&self) -> Vec<OrphanedResource> {
+        self.removed.lock().unwrap().clone()
+    }
+
+    /// Most starts and metrics calls that ran at once
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::SeqCst)
+    }
+
+    fn record(&self, call: impl Into<String>) {
+        self.calls.lock().unwrap().push(call.into());
+    }
+
+    fn check(&self, op: MockOp, instance: &VMInstance) -> Result<()> {
+        match self
+            .failures
+            .iter()
+            .find(|(failing, name, _)| *failing == op && *name == instance.name)
+        {
+            Some((_, _, message)) => Err(AivaError::PlatformError {
+                platform: self.name().to_string(),
+                message: message.clone(),
+                recoverable: true,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    async fn take_time(&self) {
+        if self.delay.is_zero() {
+            return;
+        }
+        let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl Platform for MockPlatform {
+    async fn create_vm(&self, instance: &VMInstance) -> Result<VMInstance> {
+        self.check(MockOp::Create, instance)?;
+        if let Some(image_bytes) = self.image_bytes {
+            check_rootfs_fits(image_bytes, instance.config.disk_gb)?;
+        }
+        let mut created = instance.clone();
+        created.state = self.created_state;
+        if let Some(pid) = self.pid {
+            created.runtime.pid = Some(pid);
+            created.runtime.api_socket = Some(PathBuf::from(format!("/tmp/fc-{pid}.socket")));
+            created.runtime.tap_device = Some(format!("tap-{}", instance.name));
+        }
+        Ok(created)
+    }
+
+    async fn start_vm(&self, instance: &VMInstance) -> Result<()> {
+        self.record("start");
+        self.take_time().await;
+        let failing = self
+            .failing_starts
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if failing.is_ok() {
+            return Err(AivaError::PlatformError {
+                platform: self.name().to_string(),
+                message: "boot failed".to_string(),
+                recoverable: true,
+            });
+        }
+        self.check(MockOp::Start, instance)
+    }
+
+    async fn stop_vm(&self, _instance: &VMInstance, _force: bool) -> Result<()> {
+        self.record("stop");
+        Ok(())
+    }
+
+    async fn delete_vm(&self, _instance: &VMInstance) -> Result<()> {
+        self.record("delete");
+        Ok(())
+    }
+
+    async fn get_vm_metrics(&self, instance: &VMInstance) -> Result<VMMetrics> {
+        self.take_time().await;
+        self.check(MockOp::Metrics, instance)?;
+        match &self.metrics {
+            Some(metrics) => Ok(metrics(instance)),
+            None => Err(AivaError::NotImplemented("metrics".to_string())),
+        }
+    }
+
+    async fn execute_command(&self, _instance: &VMInstance, command: &str) -> Result<String> {
+        Ok(self
+            .exec
+            .as_ref()
+            .map(|exec| exec(command))
+            .unwrap_or_default())
+    }
+
+    async fn check_requirements(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn plan_create(&self, instance: &VMInstance) -> PlatformPlan {
+        let Some(workspace) = &self.workspace else {
+            return PlatformPlan::default();
+        };
+        PlatformPlan {
+            image_copies: vec![ImageCopy::new(
+                instance.config.rootfs_path.clone(),
+                workspace.join(instance.id.to_string()).join("rootfs.ext4"),
+            )],
+            resources: vec![format!("TAP device for {}", instance.name)],
+        }
+    }
+
+    async fn resize_disk(&self, _instance: &VMInstance, disk_gb: u64) -> Result<()> {
+        if let Some(image_bytes) = self.image_bytes {
+            check_rootfs_fits(image_bytes, disk_gb)?;
+        }
+        self.resized.lock().unwrap().push(disk_gb);
+        Ok(())
+    }
+
+    async fn attach_drive(
+        &self,
+        instance: &VMInstance,
+        drive_id: &str,
+        _drive: &BlockDevice,
+    ) -> Result<()> {
+        if !self.live_drives {
+            return Err(AivaError::PlatformError {
+                platform: self.name().to_string(),
+                message: format!("cannot attach to running VM {}", instance.name),
+                recoverable: true,
+            });
+        }
+        self.attached.lock().unwrap().push(drive_id.to_string());
+        Ok(())
+    }
+
+    async fn scale_capabilities(&self, _instance: &VMInstance) -> Result<ScaleCapabilities> {
+        Ok(self.scale)
+    }
+
+    async fn apply_scale_step(&self, _instance: &VMInstance, step: &ScaleStep) -> Result<()> {
+        self.applied.lock().unwrap().push(*step);
+        Ok(())
+    }
+
+    async fn pause_vm(&self, _instance: &VMInstance) -> Result<()> {
+        self.record("pause");
+        Ok(())
+    }
+
+    async fn resume_vm(&self, _instance: &VMInstance) -> Result<()> {
+        self.record("resume");
+        Ok(())
+    }
+
+    async fn hibernate_vm(&self, instance: &VMInstance) -> Result<SnapshotFiles> {
+        self.record("hibernate");
+        let root = Path::new("/tmp/aiva-jailer")
+            .join(&instance.name)
+            .join("root");
+        Ok(SnapshotFiles {
+            state: root.join("snapshot"),
+            memory: root.join("memory"),
+        })
+    }
+
+    async fn restore_vm(
+        &self,
+        instance: &VMInstance,
+        snapshot: &SnapshotFiles,
+    ) -> Result<VMInstance> {
+        self.record(format!("restore {}", snapshot.state.display()));
+        let mut restored = instance.clone();
+        if let Some(pid) = self.pid {
+            let pid = pid + 100;
+            restored.runtime.pid = Some(pid);
+            restored.runtime.api_socket = Some(PathBuf::from(format!("/tmp/fc-{pid}.socket")));
+        }
+        Ok(restored)
+    }
+
+    async fn publish_metadata(&self, _instance: &VMInstance, metadata: &VMMetadata) -> Result<()> {
+        self.published.lock().unwrap().push(metadata.clone());
+        Ok(())
+    }
+
+    async fn vm_liveness(&self, _instance: &VMInstance) -> Liveness {
+        self.record("liveness");
+        self.liveness
+    }
+
+    async fn orphaned_resources(&self, known: &[VMInstance]) -> Result<Vec<OrphanedResource>> {
+        let mut names: Vec<String> = known.iter().map(|vm| vm.name.clone()).collect();
+        names.sort();
+        *self.known.lock().unwrap() = names;
+        Ok(self.orphans.clone())
+    }
+
+    async fn remove_orphan(&self, resource: &OrphanedResource) -> Result<()> {
+        self.removed.lock().unwrap().push(resource.clone());
+        if self.stuck_orphans.contains(resource) {
+            return Err(AivaError::IoError(std::io::Error::other(
+                "device or resource busy",
+            )));
+        }
+        Ok(())
+    }
+
+    async fn cleanup_failed_vm(&self, instance: &VMInstance) -> Result<Vec<String>> {
+        let mut cleaned = Vec::new();
+        if let Some(pid) = instance.runtime.pid {
+            cleaned.push(format!("killed process {pid}"));
+        }
+        if let Some(tap) = &instance.runtime.tap_device {
+            cleaned.push(format!("deleted TAP device {tap}"));
+        }
+        self.cleaned.lock().unwrap().extend(cleaned.clone());
+        Ok(cleaned)
+    }
+}
+
+/// Orchestrator state file in a directory removed once this is dropped.
+/// Orchestrators built on the same one share their VMs.
+pub struct TestState {
+    dir: tempfile::TempDir,
+}
+
+impl TestState {
+    pub fn new() -> Self {
+        Self {
+            dir: tempfile::tempdir().expect("create state directory"),
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.dir.path().join("vms.json")
+    }
+
+    /// An orchestrator of `platform` keeping its VMs in this state file
+    pub fn orchestrator(&self, platform: Arc<MockPlatform>) -> VMOrchestrator {
+        VMOrchestrator::new(platform).with_state_file(self.path())
+    }
+}
+
+impl Default for TestState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
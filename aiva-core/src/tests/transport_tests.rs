@@ -0,0 +1,60 @@
+use crate::{TransportSource, VMTemplate, infer_transport};
+
+#[test]
+fn infers_stdio_from_command() {
+    assert_eq!(infer_transport("mcp-server-fetch stdio"), Some("stdio"));
+    assert_eq!(infer_transport("server.py --stdio"), Some("stdio"));
+    assert_eq!(infer_transport("server --transport stdio"), Some("stdio"));
+    assert_eq!(infer_transport("server --transport=stdio"), Some("stdio"));
+}
+
+#[test]
+fn infers_sse_from_command() {
+    assert_eq!(
+        infer_transport("mcp-server-fetch sse --port 8080"),
+        Some("sse")
+    );
+    assert_eq!(infer_transport("server --transport=sse"), Some("sse"));
+}
+
+#[test]
+fn infers_nothing_without_or_with_both_transports() {
+    assert_eq!(infer_transport("mcp-server-fetch --port 8080"), None);
+    assert_eq!(infer_transport("ssehub stdiofile"), None);
+    assert_eq!(infer_transport("server stdio --sse"), None);
+}
+
+#[test]
+fn resolve_prefers_explicit_flag() {
+    let template = VMTemplate::python3_uv();
+    let choice = template.resolve_transport(Some("sse"), "mcp-server-fetch stdio", &[]);
+    assert_eq!(choice.transport, "sse");
+    assert_eq!(choice.source, TransportSource::Flag);
+    assert!(choice.warning.is_none());
+}
+
+#[test]
+fn resolve_uses_command_then_template() {
+    let template = VMTemplate::python3_uv();
+
+    let args = vec!["stdio".to_string()];
+    let choice = template.resolve_transport(None, "mcp-server-fetch", &args);
+    assert_eq!(choice.transport, "stdio");
+    assert_eq!(choice.source, TransportSource::Command);
+
+    let choice = template.resolve_transport(None, "mcp-server-fetch", &[]);
+    assert_eq!(choice.transport, template.preferred_transport());
+    assert_eq!(choice.source, TransportSource::Template);
+    assert!(choice.warning.is_none());
+}
+
+#[test]
+fn resolve_warns_when_inferred_transport_is_unsupported() {
+    let mut template = VMTemplate::python3_uv();
+    template.mcp_support.supported_transports = vec!["sse".to_string()];
+
+    let choice = template.resolve_transport(None, "mcp-server-fetch stdio", &[]);
+    assert_eq!(choice.transport, "sse");
+    assert_eq!(choice.source, TransportSource::Template);
+    assert!(choice.warning.unwrap().contains("stdio"));
+}
@@ -0,0 +1,241 @@
+use crate::{
+    AivaError, HostCapacity, MAX_BOOT_ARGS_LEN, MAX_CPUS, MAX_METADATA_BYTES, PortMapping,
+    Protocol, SCALED_CPUS, SCALED_MEMORY_MB, SCHEMA_VERSION, VMConfig, VMTemplate,
+};
+
+fn config_error(result: crate::Result<VMConfig>) -> String {
+    match result {
+        Err(AivaError::ConfigError(message)) => message,
+        other => panic!("expected a config error, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_builder_applies_defaults() {
+    let config = VMConfig::builder().cpus(4).memory_mb(2048).build().unwrap();
+    let defaults = VMConfig::default();
+
+    assert_eq!(config.cpus, 4);
+    assert_eq!(config.memory_mb, 2048);
+    assert_eq!(config.disk_gb, defaults.disk_gb);
+    assert_eq!(config.kernel_path, defaults.kernel_path);
+    assert_eq!(config.network.guest_ip, "172.16.0.2");
+    assert!(config.network.port_mappings.is_empty());
+    assert!(config.security_policy.is_none());
+    assert_eq!(config.schema_version, SCHEMA_VERSION);
+    defaults.validate().unwrap();
+}
+
+#[test]
+fn test_builder_sets_network_and_ports() {
+    let config = VMConfig::builder()
+        .addresses("10.0.5.2", "10.0.5.1", "10.0.5.0/24")
+        .port(8080, 3000, Protocol::Tcp)
+        .port(5353, 53, Protocol::Udp)
+        .workdir("/srv/agent")
+        .build()
+        .unwrap();
+
+    assert_eq!(config.network.gateway, "10.0.5.1");
+    assert_eq!(config.network.port_mappings.len(), 2);
+    assert_eq!(config.workdir.unwrap().to_str(), Some("/srv/agent"));
+}
+
+#[test]
+fn test_builder_rejects_bad_resources() {
+    assert!(config_error(VMConfig::builder().cpus(0).build()).contains("cpus"));
+    assert!(config_error(VMConfig::builder().cpus(MAX_CPUS + 1).build()).contains("cpus"));
+    assert!(config_error(VMConfig::builder().memory_mb(64).build()).contains("memory_mb"));
+    assert!(config_error(VMConfig::builder().disk_gb(0).build()).contains("disk_gb"));
+    assert!(config_error(VMConfig::builder().workdir("relative").build()).contains("workdir"));
+}
+
+#[test]
+fn test_builder_checks_metadata_keys_and_size() {
+    let config = VMConfig::builder()
+        .metadata("api_base", "https://example.com")
+        .build()
+        .unwrap();
+    assert_eq!(config.metadata["api_base"], "https://example.com");
+
+    for key in ["", "a/b", "has space"] {
+        let message = config_error(VMConfig::builder().metadata(key, "x").build());
+        assert!(message.contains("Metadata key"), "{key}: {message}");
+    }
+
+    let large = VMConfig::builder()
+        .metadata("a", "x".repeat(MAX_METADATA_BYTES / 2))
+        .metadata("b", "x".repeat(MAX_METADATA_BYTES / 2))
+        .build();
+    assert!(config_error(large).contains("bytes"));
+}
+
+#[test]
+fn test_boot_args_replace_the_default_or_follow_it() {
+    let default = "console=ttyS0 reboot=k";
+    assert_eq!(VMConfig::default().kernel_command_line(default), default);
+
+    let extended = VMConfig::builder()
+        .extra_boot_arg("quiet")
+        .extra_boot_arg("init=/sbin/custom-init")
+        .build()
+        .unwrap();
+    assert_eq!(
+        extended.kernel_command_line(default),
+        "console=ttyS0 reboot=k quiet init=/sbin/custom-init"
+    );
+
+    // Extra arguments only extend the default
+    let replaced = VMConfig::builder()
+        .boot_args("console=ttyS1")
+        .extra_boot_arg("quiet")
+        .build()
+        .unwrap();
+    assert_eq!(replaced.kernel_command_line(default), "console=ttyS1");
+
+    let long = VMConfig::builder()
+        .boot_args("x".repeat(MAX_BOOT_ARGS_LEN + 1))
+        .build();
+    assert!(config_error(long).contains("Boot arguments"));
+    let multiline = VMConfig::builder()
+        .extra_boot_arg("quiet\ninit=/bin/sh")
+        .build();
+    assert!(config_error(multiline).contains("single line"));
+}
+
+#[test]
+fn test_builder_rejects_bad_network_combinations() {
+    let outside = VMConfig::builder()
+        .addresses("10.0.6.2", "10.0.5.1", "10.0.5.0/24")
+        .build();
+    assert!(config_error(outside).contains("outside network.subnet"));
+
+    let same = VMConfig::builder()
+        .addresses("10.0.5.1", "10.0.5.1", "10.0.5.0/24")
+        .build();
+    assert!(config_error(same).contains("both"));
+
+    let bad_subnet = VMConfig::builder()
+        .addresses("10.0.5.2", "10.0.5.1", "10.0.5.0")
+        .build();
+    assert!(config_error(bad_subnet).contains("CIDR"));
+
+    let duplicate = VMConfig::builder()
+        .port(8080, 3000, Protocol::Tcp)
+        .port(8080, 3001, Protocol::Tcp)
+        .build();
+    assert!(config_error(duplicate).contains("more than once"));
+
+    // The same port on another protocol or address is fine
+    let mut public = PortMapping::new(8080, 3001, Protocol::Tcp);
+    public.host_ip = None;
+    VMConfig::builder()
+        .port(8080, 3000, Protocol::Tcp)
+        .port(8080, 3000, Protocol::Udp)
+        .port_mapping(public)
+        .build()
+        .unwrap();
+}
+
+#[test]
+fn test_templates_build_valid_configs() {
+    for template in VMTemplate::get_all_templates() {
+        template.generate_vm_config(None).validate().unwrap();
+    }
+}
+
+#[test]
+fn test_host_scaled_defaults_take_a_quarter_of_the_host() {
+    let host = HostCapacity {
+        cpus: 16,
+        memory_mb: 32 * 1024,
+    };
+    assert_eq!(host.scaled_cpus(), 4);
+    assert_eq!(host.scaled_memory_mb(), 8 * 1024);
+
+    // Rounded down to whole 256 MB steps
+    let host = HostCapacity {
+        cpus: 12,
+        memory_mb: 7000,
+    };
+    assert_eq!(host.scaled_cpus(), 3);
+    assert_eq!(host.scaled_memory_mb(), 1536);
+}
+
+#[test]
+fn test_host_scaled_defaults_are_clamped() {
+    let small = HostCapacity {
+        cpus: 2,
+        memory_mb: 1024,
+    };
+    assert_eq!(small.scaled_cpus(), SCALED_CPUS.0);
+    assert_eq!(small.scaled_memory_mb(), SCALED_MEMORY_MB.0);
+
+    let large = HostCapacity {
+        cpus: 256,
+        memory_mb: 1024 * 1024,
+    };
+    assert_eq!(large.scaled_cpus(), SCALED_CPUS.1);
+    assert_eq!(large.scaled_memory_mb(), SCALED_MEMORY_MB.1);
+
+    // Whatever the host, the result passes validation
+    for host in [small, large] {
+        VMConfig::builder()
+            .cpus(host.scaled_cpus())
+            .memory_mb(host.scaled_memory_mb())
+            .build()
+            .unwrap();
+    }
+}
+
+#[test]
+fn test_default_config_leaves_resources_to_the_host() {
+    let defaults = crate::Config::default().defaults;
+    assert_eq!(defaults.cpus, None);
+    assert_eq!(defaults.memory, None);
+
+    // Explicit values in an existing config file are kept
+    let defaults: crate::DefaultConfig = serde_json::from_str(
+        r#"{"cpus": 6, "memory": "12GB", "disk": "50GB", "cache_strategy": "writeback"}"#,
+    )
+    .unwrap();
+    assert_eq!(defaults.cpus, Some(6));
+    assert_eq!(defaults.memory.as_deref(), Some("12GB"));
+}
+
+#[test]
+fn test_execution_transports_are_parsed_and_validated() {
+    use crate::{ExecTransport, ExecutionConfig};
+
+    assert_eq!(
+        ExecutionConfig::parse_transports("vsock, SSH").unwrap(),
+        vec![ExecTransport::Vsock, ExecTransport::Ssh]
+    );
+    assert!(ExecutionConfig::parse_transports("").unwrap().is_empty());
+    assert!(ExecutionConfig::parse_transports("vsock,carrier-pigeon").is_err());
+    assert!(ExecutionConfig::parse_transports("ssh,ssh").is_err());
+
+    let message = config_error(
+        VMConfig::builder()
+            .transports([ExecTransport::Network, ExecTransport::Network])
+            .build(),
+    );
+    assert!(message.contains("more than once"));
+
+    // Stored as `"execution": {"transports": ["vsock", "ssh"]}`
+    let execution: ExecutionConfig =
+        serde_json::from_str(r#"{"transports": ["vsock", "ssh"]}"#).unwrap();
+    assert_eq!(
+        execution.transports,
+        vec![ExecTransport::Vsock, ExecTransport::Ssh]
+    );
+
+    let supported = [ExecTransport::Network, ExecTransport::Wsl];
+    assert_eq!(
+        ExecutionConfig::default()
+            .transport_order(&supported)
+            .unwrap(),
+        supported
+    );
+    assert!(execution.transport_order(&supported).is_err());
+}
@@ -0,0 +1,87 @@
+use super::{MockOp, MockPlatform, TestState};
+use crate::{
+    FIRST_GUEST_CID, Result, VMInstance, VMManager, VMOrchestrator, VMTemplate, VsockCidAllocator,
+};
+use std::sync::Arc;
+
+/// Platform that refuses to create the VM named `broken`
+fn platform() -> Arc<MockPlatform> {
+    Arc::new(MockPlatform::new().failing(MockOp::Create, "broken", "no kernel"))
+}
+
+async fn create(vm_manager: &VMOrchestrator, name: &str) -> Result<VMInstance> {
+    let config = VMTemplate::python3_uv().generate_vm_config(None);
+    vm_manager.create_vm(name.to_string(), config).await
+}
+
+#[test]
+fn test_allocator_hands_out_the_lowest_free_cid() -> Result<()> {
+    let mut cids = VsockCidAllocator::default();
+    assert_eq!(cids.allocate()?, FIRST_GUEST_CID);
+    assert_eq!(cids.allocate()?, 4);
+    assert!(cids.is_used(4));
+
+    cids.release(FIRST_GUEST_CID);
+    assert!(!cids.is_used(FIRST_GUEST_CID));
+    assert_eq!(cids.allocate()?, FIRST_GUEST_CID);
+    assert_eq!(cids.allocate()?, 5);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_creates_get_distinct_cids_and_delete_frees_them() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform());
+
+    let mut cids = Vec::new();
+    for name in ["a", "b", "c"] {
+        cids.push(create(&vm_manager, name).await?.runtime.vsock_cid);
+    }
+    assert_eq!(cids, vec![Some(3), Some(4), Some(5)]);
+
+    let b = vm_manager.get_vm_by_name("b").await?.unwrap();
+    vm_manager.delete_vm(&b.id).await?;
+    assert_eq!(create(&vm_manager, "d").await?.runtime.vsock_cid, Some(4));
+
+    // A failed create does not hold on to its CID
+    assert!(create(&vm_manager, "broken").await.is_err());
+    assert_eq!(create(&vm_manager, "e").await?.runtime.vsock_cid, Some(6));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cids_survive_reloading_state() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = state.orchestrator(platform());
+    create(&vm_manager, "a").await?;
+    create(&vm_manager, "b").await?;
+
+    let reloaded = state.orchestrator(platform());
+    reloaded.load_state().await?;
+    let a = reloaded.get_vm_by_name("a").await?.unwrap();
+    assert_eq!(a.runtime.vsock_cid, Some(FIRST_GUEST_CID));
+    assert_eq!(create(&reloaded, "c").await?.runtime.vsock_cid, Some(5));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_creates_never_share_a_cid() -> Result<()> {
+    let state = TestState::new();
+    let vm_manager = Arc::new(state.orchestrator(platform()));
+
+    let creates: Vec<_> = (0..16)
+        .map(|i| {
+            let vm_manager = vm_manager.clone();
+            tokio::spawn(async move { create(&vm_manager, &format!("vm-{i}")).await })
+        })
+        .collect();
+    let mut cids = Vec::new();
+    for create in creates {
+        cids.push(create.await.unwrap()?.runtime.vsock_cid.unwrap());
+    }
+
+    cids.sort_unstable();
+    let expected: Vec<u32> = (FIRST_GUEST_CID..FIRST_GUEST_CID + 16).collect();
+    assert_eq!(cids, expected);
+    Ok(())
+}
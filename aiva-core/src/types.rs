@@ -1,5 +1,8 @@
+use crate::error::{AivaError, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::path::PathBuf;
 use uuid::Uuid;
 
@@ -12,6 +15,9 @@ pub struct VMInstance {
     pub runtime: RuntimeInfo,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Layout version of the stored record, see [`crate::schema`]
+    #[serde(default)]
+    pub schema_version: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +29,112 @@ pub struct VMConfig {
     pub rootfs_path: PathBuf,
     pub network: NetworkConfig,
     pub storage: StorageConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    /// Name of the security policy assigned to this VM
+    #[serde(default)]
+    pub security_policy: Option<String>,
+    /// Guest directory commands run in, `/opt/mcp` when unset
+    #[serde(default)]
+    pub workdir: Option<PathBuf>,
+    /// Guest user commands run as, the connection user when unset
+    #[serde(default)]
+    pub run_as_user: Option<String>,
+    /// How commands reach the guest
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+    /// Free-form `key=value` tags such as `team=search`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Served to the guest by the metadata service under
+    /// `/aiva/metadata/<key>`, see [`crate::metadata`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Kernel command line replacing the platform's default, see
+    /// [`VMConfig::kernel_command_line`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_args: Option<String>,
+    /// Appended to the platform's default command line, e.g. `quiet`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_boot_args: Vec<String>,
+    /// Firecracker CPU template such as `T2`, hiding CPU features so
+    /// snapshots restore on hosts with other CPUs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_template: Option<String>,
+    /// Layout version of the stored config, see [`crate::schema`]
+    #[serde(default)]
+    pub schema_version: u32,
+}
+
+/// Guest directory used when a VM does not configure one
+pub const DEFAULT_WORKDIR: &str = "/opt/mcp";
+
+/// Where and as whom a command runs inside the guest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecContext {
+    pub workdir: Option<PathBuf>,
+    pub user: Option<String>,
+}
+
+impl ExecContext {
+    pub fn from_config(config: &VMConfig) -> Self {
+        Self {
+            workdir: config.workdir.clone(),
+            user: config.run_as_user.clone(),
+        }
+    }
+
+    pub fn workdir(&self) -> PathBuf {
+        self.workdir
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_WORKDIR))
+    }
+
+    /// Prefix `command` with the `cd` and, for another user, run it through
+    /// `sudo -u` so the whole chain executes as that user
+    pub fn wrap(&self, command: &str) -> String {
+        let workdir = self.workdir();
+        let chained = format!(
+            "cd {} && {command}",
+            shell_quote(&workdir.to_string_lossy())
+        );
+
+        match &self.user {
+            Some(user) => format!(
+                "sudo -u {} -H sh -c {}",
+                shell_quote(user),
+                shell_quote(&chained)
+            ),
+            None => chained,
+        }
+    }
+}
+
+/// Quote a string for POSIX `sh` using single quotes
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quote a single argument for `sh`, leaving plain words such as `--port`
+/// or `9000` readable
+pub fn shell_arg(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"-_./=:,@%+".contains(&b));
+    if plain {
+        value.to_string()
+    } else {
+        shell_quote(value)
+    }
+}
+
+/// `command` followed by `args`, each of which reaches the program as one
+/// argument. `command` itself is passed to the shell as written.
+pub fn command_line(command: &str, args: &[String]) -> String {
+    args.iter().fold(command.to_string(), |line, arg| {
+        format!("{line} {}", shell_arg(arg))
+    })
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -35,12 +147,153 @@ pub enum VMState {
     Error,
 }
 
+/// What it took to stop a VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopMethod {
+    /// The guest powered off when asked to
+    Graceful,
+    /// The VMM had to be sent SIGTERM
+    Terminated,
+    /// The VMM had to be sent SIGKILL
+    Killed,
+}
+
+impl std::fmt::Display for StopMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Graceful => write!(f, "graceful shutdown"),
+            Self::Terminated => write!(f, "SIGTERM"),
+            Self::Killed => write!(f, "SIGKILL"),
+        }
+    }
+}
+
+/// What `VMOrchestrator::recover_vm` did to bring a VM out of `Error`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    pub vm_name: String,
+    pub cleaned: Vec<String>,
+    pub restarted: bool,
+}
+
+/// Where an MCP server started by `aiva run` can be reached from the host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub transport: String,
+    /// Host address for sse servers, the guest address for stdio ones
+    pub host: String,
+    /// Host port for sse servers; stdio servers have none
+    pub port: Option<u16>,
+    pub pid: Option<u32>,
+    /// Guest file the server's output goes to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_path: Option<PathBuf>,
+}
+
+impl RunResult {
+    pub fn new(plan: &crate::RunPlan, network: &NetworkConfig, pid: Option<u32>) -> Self {
+        let (host, port) = match plan.port {
+            Some(guest_port) => {
+                let addr = network.host_address(guest_port);
+                (addr.ip().to_string(), Some(addr.port()))
+            }
+            None => (network.guest_ip.clone(), None),
+        };
+
+        Self {
+            transport: plan.transport.clone(),
+            host,
+            port,
+            pid,
+            log_path: None,
+        }
+    }
+
+    pub fn with_log_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log_path = Some(path.into());
+        self
+    }
+
+    /// Socket address clients connect to, for servers listening on a port
+    pub fn address(&self) -> Option<SocketAddr> {
+        let ip: IpAddr = self.host.parse().ok()?;
+        Some(SocketAddr::new(ip, self.port?))
+    }
+
+    pub fn url(&self) -> Option<String> {
+        self.address().map(|addr| format!("http://{addr}"))
+    }
+
+    /// Server PID reported by the platform as a `PID: <n>` line
+    pub fn pid_from_output(output: &str) -> Option<u32> {
+        output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("PID:"))
+            .and_then(|pid| pid.trim().parse().ok())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RuntimeInfo {
     pub pid: Option<u32>,
     pub api_socket: Option<PathBuf>,
     pub vsock_cid: Option<u32>,
     pub tap_device: Option<String>,
+    /// PIDs of the MCP servers `aiva run` launched, where the platform runs
+    /// them. Emptied when the VM stops; `None` in state written before the
+    /// PIDs were tracked.
+    #[serde(default)]
+    pub mcp_pids: Option<Vec<u32>>,
+    /// How the VM is paused, `None` while it is not
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paused: Option<crate::pause::PauseState>,
+}
+
+impl RuntimeInfo {
+    /// Prefix of the read-only keys `aiva config get` resolves from live state
+    pub const KEY_PREFIX: &'static str = "runtime.";
+    pub const KEYS: [&'static str; 6] = [
+        "runtime.pid",
+        "runtime.api_socket",
+        "runtime.tap_device",
+        "runtime.vsock_cid",
+        "runtime.mcp_pids",
+        "runtime.paused",
+    ];
+
+    pub fn is_runtime_key(key: &str) -> bool {
+        key.starts_with(Self::KEY_PREFIX)
+    }
+
+    /// Value of a `runtime.*` key, `None` while it is not assigned
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        match key {
+            "runtime.pid" => Ok(self.pid.map(|pid| pid.to_string())),
+            "runtime.api_socket" => Ok(self
+                .api_socket
+                .as_ref()
+                .map(|path| path.display().to_string())),
+            "runtime.tap_device" => Ok(self.tap_device.clone()),
+            "runtime.vsock_cid" => Ok(self.vsock_cid.map(|cid| cid.to_string())),
+            "runtime.mcp_pids" => {
+                Ok(self
+                    .mcp_pids
+                    .as_ref()
+                    .filter(|pids| !pids.is_empty())
+                    .map(|pids| {
+                        pids.iter()
+                            .map(u32::to_string)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    }))
+            }
+            "runtime.paused" => Ok(self.paused.as_ref().map(|pause| pause.name().to_string())),
+            _ => Err(AivaError::ConfigError(format!(
+                "Unknown runtime key '{key}', expected one of: {}",
+                Self::KEYS.join(", ")
+            ))),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -50,18 +303,76 @@ pub struct NetworkConfig {
     pub subnet: String,
     pub gateway: String,
     pub dns_servers: Vec<String>,
+    /// Domains appended to unqualified guest lookups
+    #[serde(default)]
+    pub dns_search: Vec<String>,
+    /// Resolvers used instead of `dns_servers` for a domain and its
+    /// subdomains (split DNS)
+    #[serde(default)]
+    pub dns_overrides: BTreeMap<String, Vec<String>>,
+    /// Guest hostname; unset uses the sanitized VM name, see
+    /// [`sanitize_hostname`](crate::sanitize_hostname)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
     pub dhcp_enabled: bool,
     pub port_mappings: Vec<PortMapping>,
+    /// Log every connection the guest opens to the host kernel log, see
+    /// `aiva_network::egress_log_rule_args`
+    #[serde(default)]
+    pub audit_egress: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PortMapping {
+    /// Host address the forward is bound to; `None` listens on all interfaces
+    #[serde(default = "default_port_host_ip")]
+    pub host_ip: Option<IpAddr>,
     pub host_port: u16,
     pub guest_port: u16,
     pub protocol: Protocol,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+fn default_port_host_ip() -> Option<IpAddr> {
+    Some(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+impl NetworkConfig {
+    /// Host address a guest port is reachable on: the bind address of a
+    /// matching port mapping, or loopback when the mapping listens on every
+    /// interface or there is no mapping
+    pub fn host_address(&self, guest_port: u16) -> SocketAddr {
+        self.port_mappings
+            .iter()
+            .find(|m| m.guest_port == guest_port)
+            .map(|m| {
+                let ip = match m.bind_address() {
+                    ip if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                    ip => ip,
+                };
+                SocketAddr::new(ip, m.host_port)
+            })
+            .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), guest_port))
+    }
+}
+
+impl PortMapping {
+    /// A mapping bound to the loopback address
+    pub fn new(host_port: u16, guest_port: u16, protocol: Protocol) -> Self {
+        Self {
+            host_ip: default_port_host_ip(),
+            host_port,
+            guest_port,
+            protocol,
+        }
+    }
+
+    /// Address to bind on the host, with `None` meaning every interface
+    pub fn bind_address(&self) -> IpAddr {
+        self.host_ip.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Protocol {
     Tcp,
     Udp,
@@ -79,11 +390,29 @@ impl std::fmt::Display for Protocol {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StorageConfig {
     pub cache_strategy: CacheStrategy,
+    /// Drives attached next to the root filesystem, in attach order
     pub additional_drives: Vec<BlockDevice>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+impl StorageConfig {
+    /// The additional drives with the Firecracker drive id of each:
+    /// `drive1`, `drive2` and so on
+    pub fn drives(&self) -> impl Iterator<Item = (String, &BlockDevice)> {
+        self.additional_drives
+            .iter()
+            .enumerate()
+            .map(|(i, drive)| (format!("drive{}", i + 1), drive))
+    }
+
+    /// Drive id the next attached drive gets
+    pub fn next_drive_id(&self) -> String {
+        format!("drive{}", self.additional_drives.len() + 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
 pub enum CacheStrategy {
+    #[default]
     Writeback,
     Unsafe,
 }
@@ -97,6 +426,112 @@ impl std::fmt::Display for CacheStrategy {
     }
 }
 
+/// Ways the host can run commands in a guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecTransport {
+    /// The guest agent over virtio-vsock
+    Vsock,
+    /// The guest agent over the VM's network
+    Network,
+    /// SSH through the first forwarded port
+    Ssh,
+    /// Straight into the WSL distribution hosting the VM
+    Wsl,
+}
+
+impl ExecTransport {
+    pub const ALL: [Self; 4] = [Self::Vsock, Self::Network, Self::Ssh, Self::Wsl];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|transport| transport.as_str() == name)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vsock => "vsock",
+            Self::Network => "network",
+            Self::Ssh => "ssh",
+            Self::Wsl => "wsl",
+        }
+    }
+}
+
+impl std::fmt::Display for ExecTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How commands reach the guest of a VM
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionConfig {
+    /// Transports to try, in order. Empty uses the platform's own order.
+    #[serde(default)]
+    pub transports: Vec<ExecTransport>,
+}
+
+impl ExecutionConfig {
+    /// Parse a comma-separated list such as `vsock,ssh`
+    pub fn parse_transports(value: &str) -> Result<Vec<ExecTransport>> {
+        let transports = value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                ExecTransport::from_name(&name.to_lowercase()).ok_or_else(|| {
+                    AivaError::ConfigError(format!(
+                        "Unknown execution transport '{name}', expected one of: {}",
+                        ExecTransport::ALL.map(|t| t.as_str()).join(", ")
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let execution = Self { transports };
+        execution.validate()?;
+        Ok(execution.transports)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for (i, transport) in self.transports.iter().enumerate() {
+            if self.transports[..i].contains(transport) {
+                return Err(AivaError::ConfigError(format!(
+                    "execution.transports lists '{transport}' more than once"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Transports to try on a platform that supports `supported`, in its
+    /// preferred order. Fails if the configuration names one it lacks.
+    pub fn transport_order(&self, supported: &[ExecTransport]) -> Result<Vec<ExecTransport>> {
+        if self.transports.is_empty() {
+            return Ok(supported.to_vec());
+        }
+        if let Some(unsupported) = self.transports.iter().find(|t| !supported.contains(t)) {
+            return Err(AivaError::ConfigError(format!(
+                "Execution transport '{unsupported}' is not supported here, use {}",
+                supported
+                    .iter()
+                    .map(ExecTransport::as_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+        Ok(self.transports.clone())
+    }
+}
+
+/// Guest log files shipped to the host by the agent
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    pub paths: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockDevice {
     pub path: PathBuf,
@@ -120,6 +555,23 @@ pub struct VMMetrics {
     pub uptime: std::time::Duration,
 }
 
+impl VMMetrics {
+    /// Share of guest memory in use, 0 when the total is unknown
+    pub fn memory_percent(&self) -> f64 {
+        if self.memory_usage.total_mb == 0 {
+            return 0.0;
+        }
+        self.memory_usage.used_mb as f64 / self.memory_usage.total_mb as f64 * 100.0
+    }
+
+    /// Bytes received and sent since boot
+    pub fn network_bytes(&self) -> u64 {
+        self.network_io
+            .rx_bytes
+            .saturating_add(self.network_io.tx_bytes)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryMetrics {
     pub total_mb: u64,
@@ -128,6 +580,20 @@ pub struct MemoryMetrics {
     pub cache_mb: u64,
 }
 
+impl MemoryMetrics {
+    /// Figures from kB counters as `/proc` reports them. Counters read at
+    /// slightly different times can show more in use than in total; nothing
+    /// is available then.
+    pub fn from_kb(total_kb: u64, used_kb: u64) -> Self {
+        Self {
+            total_mb: total_kb / 1024,
+            used_mb: used_kb / 1024,
+            available_mb: total_kb.saturating_sub(used_kb) / 1024,
+            cache_mb: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DiskIOMetrics {
     pub read_bytes: u64,
@@ -181,8 +647,12 @@ impl Default for NetworkConfig {
             subnet: "172.16.0.0/24".to_string(),
             gateway: "172.16.0.1".to_string(),
             dns_servers: vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()],
+            dns_search: vec![],
+            dns_overrides: BTreeMap::new(),
+            hostname: None,
             dhcp_enabled: false,
             port_mappings: vec![],
+            audit_egress: false,
         }
     }
 }
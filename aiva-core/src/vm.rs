@@ -1,5 +1,19 @@
+use crate::concurrency::run_bounded;
+use crate::config::default_concurrency;
 use crate::error::*;
+use crate::events::{EventBus, VMEvent};
+use crate::metadata::VMMetadata;
+use crate::pause::{PauseState, ResumePath, SnapshotFiles, SnapshotType, check_pause, resume_path};
+use crate::plan::{CreatePlan, PlatformPlan};
+use crate::reconcile::{Liveness, StateDivergence};
+use crate::remediation::OrphanedResource;
+use crate::scale::{
+    MIN_SCALE_MEMORY_MB, ScaleCapabilities, ScalePlan, ScaleRequest, ScaleStep, plan_scale,
+};
+use crate::schema::{SCHEMA_VERSION, migrate_state};
+use crate::templates::RunPlan;
 use crate::types::*;
+use crate::vsock::VsockCidAllocator;
 use async_trait::async_trait;
 use chrono::Utc;
 use std::collections::HashMap;
@@ -7,6 +21,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::fs;
 use tokio::sync::RwLock;
+use tracing::{info, warn};
 use uuid::Uuid;
 
 #[async_trait]
@@ -20,15 +35,30 @@ pub trait VMManager: Send + Sync {
     async fn list_vms(&self) -> Result<Vec<VMInstance>>;
     async fn update_vm_state(&self, id: &Uuid, state: VMState) -> Result<()>;
     async fn get_vm_metrics(&self, id: &Uuid) -> Result<VMMetrics>;
+    /// Metrics of the VMs in `ids`, or of every running VM, collected
+    /// concurrently. A VM that fails only has an error in its own entry.
+    async fn batch_metrics(&self, ids: Option<&[Uuid]>) -> HashMap<Uuid, Result<VMMetrics>>;
     async fn execute_command(&self, id: &Uuid, command: &str) -> Result<String>;
+    /// Launch the MCP server described by `plan` in a running VM
+    async fn run_server(&self, id: &Uuid, plan: &RunPlan) -> Result<String>;
     async fn force_reset_vm_state(&self, id: &Uuid, state: VMState) -> Result<()>;
     async fn reset_stuck_vms(&self) -> Result<Vec<(Uuid, VMState)>>;
+    /// Pause a running VM. In memory it moves to `Paused`; with `hibernate`
+    /// it is snapshotted to disk and moves to `Stopped` until
+    /// [`VMManager::resume_vm`] restores it.
+    async fn pause_vm(&self, id: &Uuid, hibernate: bool) -> Result<PauseState>;
+    /// Bring back a VM paused by [`VMManager::pause_vm`], resuming it in
+    /// place or restoring it from its snapshot
+    async fn resume_vm(&self, id: &Uuid) -> Result<()>;
 }
 
 pub struct VMOrchestrator {
     vms: Arc<RwLock<HashMap<Uuid, VMInstance>>>,
     platform: Arc<dyn Platform>,
     state_file: PathBuf,
+    events: EventBus,
+    /// VMs bulk operations and batch metrics act on at the same time
+    concurrency: usize,
 }
 
 impl VMOrchestrator {
@@ -42,18 +72,517 @@ impl VMOrchestrator {
             vms: Arc::new(RwLock::new(HashMap::new())),
             platform,
             state_file,
+            events: EventBus::new(),
+            concurrency: default_concurrency(),
         }
     }
 
+    /// Act on at most `limit` VMs at a time in [`start_vms`](Self::start_vms),
+    /// [`stop_vms`](Self::stop_vms) and
+    /// [`batch_metrics`](VMManager::batch_metrics)
+    pub fn with_concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    pub fn concurrency(&self) -> usize {
+        self.concurrency
+    }
+
+    /// Start the VMs in `ids`, at most [`concurrency`](Self::concurrency) at
+    /// a time. A VM that fails to start only has an error in its own entry.
+    pub async fn start_vms(self: &Arc<Self>, ids: &[Uuid]) -> HashMap<Uuid, Result<()>> {
+        let tasks = ids.iter().map(|id| {
+            let manager = self.clone();
+            let id = *id;
+            (id, async move { manager.start_vm(&id).await })
+        });
+        run_bounded(self.concurrency, tasks, |e| Err(bulk_panic("start", e))).await
+    }
+
+    /// Stop the VMs in `ids` like [`start_vms`](Self::start_vms) starts them
+    pub async fn stop_vms(
+        self: &Arc<Self>,
+        ids: &[Uuid],
+        force: bool,
+    ) -> HashMap<Uuid, Result<()>> {
+        let tasks = ids.iter().map(|id| {
+            let manager = self.clone();
+            let id = *id;
+            (id, async move { manager.stop_vm(&id, force).await })
+        });
+        run_bounded(self.concurrency, tasks, |e| Err(bulk_panic("stop", e))).await
+    }
+
+    /// Lifecycle events of the VMs managed here
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Describe what creating `name` with `config` would allocate, without
+    /// touching the platform, the filesystem or the stored state
+    pub async fn plan_create(&self, name: &str, config: &VMConfig) -> Result<CreatePlan> {
+        let now = Utc::now();
+        let instance = VMInstance {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            state: VMState::Creating,
+            config: config.clone(),
+            runtime: RuntimeInfo {
+                pid: None,
+                api_socket: None,
+                vsock_cid: None,
+                tap_device: None,
+                mcp_pids: Some(Vec::new()),
+                paused: None,
+            },
+            created_at: now,
+            updated_at: now,
+            schema_version: SCHEMA_VERSION,
+        };
+        let PlatformPlan {
+            image_copies,
+            resources,
+        } = self.platform.plan_create(&instance);
+
+        let mut conflicts = Vec::new();
+        let network = &config.network;
+        for vm in self.vms.read().await.values() {
+            if vm.name == name {
+                conflicts.push(format!("VM '{name}' already exists"));
+                continue;
+            }
+            if vm.config.network.guest_ip == network.guest_ip {
+                conflicts.push(format!(
+                    "Guest IP {} is already used by VM '{}'",
+                    network.guest_ip, vm.name
+                ));
+            }
+            for mapping in &network.port_mappings {
+                if vm.config.network.port_mappings.iter().any(|other| {
+                    other.host_port == mapping.host_port && other.protocol == mapping.protocol
+                }) {
+                    conflicts.push(format!(
+                        "Host port {} is already forwarded to VM '{}'",
+                        mapping.host_port, vm.name
+                    ));
+                }
+            }
+        }
+        for (i, mapping) in network.port_mappings.iter().enumerate() {
+            if network.port_mappings[..i].iter().any(|other| {
+                other.host_port == mapping.host_port && other.protocol == mapping.protocol
+            }) {
+                conflicts.push(format!(
+                    "Host port {} is mapped more than once",
+                    mapping.host_port
+                ));
+            }
+        }
+
+        Ok(CreatePlan {
+            name: name.to_string(),
+            template: None,
+            platform: self.platform.name().to_string(),
+            cpus: config.cpus,
+            memory_mb: config.memory_mb,
+            disk_gb: config.disk_gb,
+            guest_ip: network.guest_ip.clone(),
+            host_ip: network.host_ip.clone(),
+            gateway: network.gateway.clone(),
+            port_mappings: network.port_mappings.clone(),
+            security_policy: config.security_policy.clone(),
+            image_copies,
+            resources,
+            conflicts,
+        })
+    }
+
+    /// Compare the stored state of every VM with what the platform observes.
+    /// With `correct`, diverging VMs are moved to the state that matches.
+    pub async fn reconcile_states(&self, correct: bool) -> Result<Vec<StateDivergence>> {
+        let mut divergences = Vec::new();
+        for vm in self.list_vms().await? {
+            let liveness = self.platform.vm_liveness(&vm).await;
+            if let Some(divergence) = StateDivergence::check(&vm, liveness) {
+                divergences.push(divergence);
+            }
+        }
+
+        if correct {
+            for divergence in &divergences {
+                info!(
+                    "Reconciling {}: {:?} -> {:?}",
+                    divergence.vm_name,
+                    divergence.stored,
+                    divergence.corrected_state()
+                );
+                self.force_reset_vm_state(&divergence.vm_id, divergence.corrected_state())
+                    .await?;
+            }
+        }
+        Ok(divergences)
+    }
+
+    /// Change the runtime information of a VM and persist it
+    async fn update_runtime(&self, id: &Uuid, update: impl FnOnce(&mut RuntimeInfo)) -> Result<()> {
+        {
+            let mut vms = self.vms.write().await;
+            if let Some(vm) = vms.get_mut(id) {
+                update(&mut vm.runtime);
+                vm.updated_at = Utc::now();
+                self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
+            }
+        }
+        self.save_state().await
+    }
+
+    /// Serve the metadata of `vm`, which is running, to its guest, replacing
+    /// what the guest saw before
+    pub async fn publish_metadata(&self, vm: &VMInstance) -> Result<()> {
+        self.platform
+            .publish_metadata(vm, &VMMetadata::from_instance(vm))
+            .await
+    }
+
+    /// Republish the metadata of `vm` after its configuration changed. The
+    /// change itself already happened, so a failure is only logged.
+    async fn refresh_metadata(&self, vm: &VMInstance) {
+        if vm.state != VMState::Running {
+            return;
+        }
+        if let Err(e) = self.publish_metadata(vm).await {
+            warn!("Failed to refresh the metadata of VM {}: {}", vm.name, e);
+        }
+    }
+
+    async fn running_vm(&self, id: &Uuid) -> Result<VMInstance> {
+        let vm = self
+            .vms
+            .read()
+            .await
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AivaError::VMError {
+                vm_name: id.to_string(),
+                state: VMState::Stopped,
+                message: "VM not found".to_string(),
+            })?;
+
+        if vm.state != VMState::Running {
+            return Err(AivaError::VMError {
+                vm_name: vm.name,
+                state: vm.state,
+                message: "VM must be running to execute commands".to_string(),
+            });
+        }
+
+        Ok(vm)
+    }
+
     pub async fn load_state(&self) -> Result<()> {
         if !self.state_file.exists() {
             return Ok(());
         }
 
         let content = fs::read_to_string(&self.state_file).await?;
-        let vms: HashMap<Uuid, VMInstance> = serde_json::from_str(&content)?;
+        let (vms, migrated) = migrate_state(&content)?;
 
-        *self.vms.write().await = vms;
+        {
+            let mut loaded = self.vms.write().await;
+            *loaded = vms;
+            self.events
+                .publish(VMEvent::Loaded(loaded.values().cloned().collect()));
+        }
+        if migrated {
+            info!(
+                "Migrated {} to schema version {}",
+                self.state_file.display(),
+                SCHEMA_VERSION
+            );
+            self.save_state().await?;
+        }
+        Ok(())
+    }
+
+    /// Remove the host resources the platform finds that no VM here owns,
+    /// with the outcome of each removal. Resources of known VMs, running or
+    /// not, are never touched.
+    pub async fn remove_orphans(&self) -> Result<Vec<(OrphanedResource, Result<()>)>> {
+        let known: Vec<VMInstance> = self.vms.read().await.values().cloned().collect();
+        let mut removed = Vec::new();
+        for resource in self.platform.orphaned_resources(&known).await? {
+            let result = self.platform.remove_orphan(&resource).await;
+            removed.push((resource, result));
+        }
+        Ok(removed)
+    }
+
+    /// Use a different state file, e.g. to keep tests away from ~/.aiva
+    pub fn with_state_file(mut self, state_file: PathBuf) -> Self {
+        self.state_file = state_file;
+        self
+    }
+
+    /// Bring a VM out of `VMState::Error`: release whatever the failed
+    /// operation left behind, reset it to `Stopped` and optionally start it
+    /// again.
+    pub async fn recover_vm(&self, id: &Uuid, retry_start: bool) -> Result<RecoveryReport> {
+        let vm = {
+            let vms = self.vms.read().await;
+            vms.get(id).cloned()
+        };
+
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+
+        if vm.state != VMState::Error {
+            return Err(AivaError::InvalidStateTransition(format!(
+                "Cannot recover VM in state {:?}; only VMs in Error state need recovery",
+                vm.state
+            )));
+        }
+
+        let cleaned = self.platform.cleanup_failed_vm(&vm).await?;
+        for resource in &cleaned {
+            info!("Recovered VM {}: cleaned {}", vm.name, resource);
+        }
+
+        {
+            let mut vms = self.vms.write().await;
+            if let Some(vm) = vms.get_mut(id) {
+                vm.runtime.pid = None;
+                vm.runtime.api_socket = None;
+                vm.runtime.tap_device = None;
+                vm.runtime.mcp_pids = Some(Vec::new());
+                vm.runtime.paused = None;
+                vm.state = VMState::Stopped;
+                vm.updated_at = Utc::now();
+            }
+        }
+        self.save_state().await?;
+
+        let mut report = RecoveryReport {
+            vm_name: vm.name,
+            cleaned,
+            restarted: false,
+        };
+
+        if retry_start {
+            self.start_vm(id).await?;
+            report.restarted = true;
+        }
+
+        Ok(report)
+    }
+
+    /// Grow the root disk of a VM to `disk_gb`. Running VMs are resized
+    /// online where the platform supports it; shrinking is never supported.
+    pub async fn resize_disk(&self, id: &Uuid, disk_gb: u64) -> Result<VMInstance> {
+        let vm = {
+            let vms = self.vms.read().await;
+            vms.get(id).cloned()
+        };
+
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+
+        if disk_gb < vm.config.disk_gb {
+            return Err(AivaError::ConfigError(format!(
+                "Cannot shrink the disk of VM '{}' from {} GB to {disk_gb} GB",
+                vm.name, vm.config.disk_gb
+            )));
+        }
+        if disk_gb == vm.config.disk_gb {
+            return Ok(vm);
+        }
+
+        self.platform.resize_disk(&vm, disk_gb).await?;
+        info!(
+            "Resized disk of VM {} from {} GB to {} GB",
+            vm.name, vm.config.disk_gb, disk_gb
+        );
+
+        let updated = {
+            let mut vms = self.vms.write().await;
+            let entry = vms.get_mut(id).ok_or_else(|| AivaError::VMError {
+                vm_name: vm.name.clone(),
+                state: vm.state,
+                message: "VM disappeared during resize".to_string(),
+            })?;
+            entry.config.disk_gb = disk_gb;
+            entry.updated_at = Utc::now();
+            self.events
+                .publish(VMEvent::Updated(Box::new(entry.clone())));
+            entry.clone()
+        };
+        self.save_state().await?;
+        self.refresh_metadata(&updated).await;
+
+        Ok(updated)
+    }
+
+    /// Add `drive` to the additional drives of a VM and return its drive
+    /// id. A stopped VM gets it on its next start; a running one only if
+    /// the platform can attach drives live, and otherwise nothing changes.
+    pub async fn attach_drive(&self, id: &Uuid, drive: BlockDevice) -> Result<String> {
+        let vm = self.vms.read().await.get(id).cloned();
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+        if vm
+            .config
+            .storage
+            .additional_drives
+            .iter()
+            .any(|attached| attached.path == drive.path)
+        {
+            return Err(AivaError::ConfigError(format!(
+                "{} is already attached to VM '{}'",
+                drive.path.display(),
+                vm.name
+            )));
+        }
+
+        let drive_id = vm.config.storage.next_drive_id();
+        match vm.state {
+            VMState::Stopped => {}
+            VMState::Running => self.platform.attach_drive(&vm, &drive_id, &drive).await?,
+            state => {
+                return Err(AivaError::InvalidStateTransition(format!(
+                    "Cannot attach a drive to VM '{}' in state {state:?}",
+                    vm.name
+                )));
+            }
+        }
+        info!(
+            "Attached {} to VM {} as {}",
+            drive.path.display(),
+            vm.name,
+            drive_id
+        );
+
+        {
+            let mut vms = self.vms.write().await;
+            if let Some(vm) = vms.get_mut(id) {
+                vm.config.storage.additional_drives.push(drive);
+                vm.updated_at = Utc::now();
+                self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
+            }
+        }
+        self.save_state().await?;
+        Ok(drive_id)
+    }
+
+    /// Change the vCPUs and memory of a VM. What the platform can change
+    /// live is applied right away; the rest is stored and, with `restart`,
+    /// applied by stopping and starting the VM.
+    pub async fn scale_vm(
+        &self,
+        id: &Uuid,
+        request: ScaleRequest,
+        restart: bool,
+    ) -> Result<ScalePlan> {
+        let vm = {
+            let vms = self.vms.read().await;
+            vms.get(id).cloned()
+        };
+
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+
+        let running = vm.state == VMState::Running;
+        let capabilities = if running {
+            self.platform.scale_capabilities(&vm).await?
+        } else {
+            ScaleCapabilities::default()
+        };
+        let plan = plan_scale(&vm.config, running, request, &capabilities)?;
+
+        for step in &plan.live {
+            self.platform.apply_scale_step(&vm, step).await?;
+            info!("Scaled VM {} live: {:?}", vm.name, step);
+        }
+
+        let updated = {
+            let mut vms = self.vms.write().await;
+            vms.get_mut(id).map(|vm| {
+                vm.config.cpus = plan.cpus;
+                vm.config.memory_mb = plan.memory_mb;
+                vm.updated_at = Utc::now();
+                self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
+                vm.clone()
+            })
+        };
+        self.save_state().await?;
+        if let Some(updated) = &updated {
+            self.refresh_metadata(updated).await;
+        }
+
+        if plan.restart_required() && restart {
+            info!("Restarting VM {} to apply new resources", vm.name);
+            self.stop_vm(id, false).await?;
+            self.start_vm(id).await?;
+        }
+
+        Ok(plan)
+    }
+
+    /// Inflate the balloon of a running VM to `mib`, taking that much
+    /// memory back from the guest, or deflate it to give memory back. The
+    /// VM's configured memory is left alone, so a restart undoes it. Fails
+    /// with a recoverable error when the VM booted without a balloon device.
+    pub async fn set_balloon_target(&self, id: &Uuid, mib: u64) -> Result<()> {
+        let vm = self.vms.read().await.get(id).cloned();
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+        if vm.state != VMState::Running {
+            return Err(AivaError::VMError {
+                vm_name: vm.name.clone(),
+                state: vm.state,
+                message: "Only a running VM's balloon can be resized".to_string(),
+            });
+        }
+
+        let capabilities = self.platform.scale_capabilities(&vm).await?;
+        let Some(max) = capabilities.balloon_max_mb else {
+            return Err(AivaError::PlatformError {
+                platform: self.platform.name().to_string(),
+                message: format!(
+                    "VM {} was booted without a balloon device; restart it to add one",
+                    vm.name
+                ),
+                recoverable: true,
+            });
+        };
+        let memory_mb = max.saturating_sub(mib);
+        if memory_mb < MIN_SCALE_MEMORY_MB {
+            return Err(AivaError::ConfigError(format!(
+                "A {mib}MiB balloon leaves VM {} less than {MIN_SCALE_MEMORY_MB}MB of its {max}MB",
+                vm.name
+            )));
+        }
+
+        let step = ScaleStep::Balloon {
+            memory_mb,
+            balloon_mib: mib,
+        };
+        self.platform.apply_scale_step(&vm, &step).await?;
+        info!("Set balloon of VM {} to {}MiB", vm.name, mib);
         Ok(())
     }
 
@@ -69,13 +598,18 @@ impl VMOrchestrator {
     }
 }
 
+/// Error of a bulk `operation` task that panicked
+fn bulk_panic(operation: &str, error: tokio::task::JoinError) -> AivaError {
+    AivaError::Other(anyhow::anyhow!("Bulk {operation} task failed: {error}"))
+}
+
 #[async_trait]
 impl VMManager for VMOrchestrator {
     async fn create_vm(&self, name: String, config: VMConfig) -> Result<VMInstance> {
         let id = Uuid::new_v4();
         let now = Utc::now();
 
-        let instance = VMInstance {
+        let mut instance = VMInstance {
             id,
             name: name.clone(),
             state: VMState::Creating,
@@ -85,14 +619,20 @@ impl VMManager for VMOrchestrator {
                 api_socket: None,
                 vsock_cid: None,
                 tap_device: None,
+                mcp_pids: Some(Vec::new()),
+                paused: None,
             },
             created_at: now,
             updated_at: now,
+            schema_version: SCHEMA_VERSION,
         };
 
-        // Store the instance
+        // Store the instance, taking its CID under the same lock so two
+        // creates cannot pick the same one
         {
             let mut vms = self.vms.write().await;
+            let cid = VsockCidAllocator::from_vms(vms.values()).allocate()?;
+            instance.runtime.vsock_cid = Some(cid);
             vms.insert(id, instance.clone());
         }
 
@@ -102,6 +642,8 @@ impl VMManager for VMOrchestrator {
                 {
                     let mut vms = self.vms.write().await;
                     vms.insert(id, updated_instance.clone());
+                    self.events
+                        .publish(VMEvent::Updated(Box::new(updated_instance.clone())));
                 }
                 self.save_state().await?;
                 Ok(updated_instance)
@@ -136,8 +678,16 @@ impl VMManager for VMOrchestrator {
                 vm.state
             )));
         }
+        if vm.runtime.is_hibernated() {
+            return self.resume_vm(id).await;
+        }
 
-        self.platform.start_vm(&vm).await?;
+        if let Err(e) = self.platform.start_vm(&vm).await {
+            // Leave the VM in Error so `aiva recover` can clean up after it
+            warn!("Failed to start VM {}: {}", vm.name, e);
+            self.update_vm_state(id, VMState::Error).await?;
+            return Err(e);
+        }
         self.update_vm_state(id, VMState::Running).await?;
 
         Ok(())
@@ -167,12 +717,26 @@ impl VMManager for VMOrchestrator {
         // Use timeout to prevent hanging indefinitely
         let result = tokio::time::timeout(
             std::time::Duration::from_secs(30),
-            self.platform.stop_vm(&vm, force),
+            self.platform.shutdown_vm(&vm, force),
         )
         .await;
 
         match result {
-            Ok(Ok(())) => {
+            Ok(Ok(method)) => {
+                if force || method == StopMethod::Graceful {
+                    info!("Stopped VM {} ({})", vm.name, method);
+                } else {
+                    warn!(
+                        "VM {} did not power off in time and was stopped with {}",
+                        vm.name, method
+                    );
+                }
+                // The servers stopped with the VM
+                self.update_runtime(id, |runtime| {
+                    runtime.mcp_pids = Some(Vec::new());
+                    runtime.paused = None;
+                })
+                .await?;
                 self.update_vm_state(id, VMState::Stopped).await?;
                 Ok(())
             }
@@ -212,14 +776,35 @@ impl VMManager for VMOrchestrator {
             )));
         }
 
+        // A hypervisor that outlived its stop may recreate files in the
+        // workspace or hold on to the TAP device while they are removed
+        if self.platform.vm_liveness(&vm).await == Liveness::Alive {
+            return Err(AivaError::VMError {
+                vm_name: vm.name.clone(),
+                state: vm.state,
+                message: format!(
+                    "hypervisor process{} is still running; run `aiva status --reconcile` \
+                     and `aiva stop {} --force` before deleting",
+                    vm.runtime
+                        .pid
+                        .map(|pid| format!(" {pid}"))
+                        .unwrap_or_default(),
+                    vm.name
+                ),
+            });
+        }
+
         self.platform.delete_vm(&vm).await?;
 
+        // State goes last, so a failed teardown can be retried
         {
             let mut vms = self.vms.write().await;
             vms.remove(id);
+            self.events.publish(VMEvent::Deleted(*id));
         }
 
         self.save_state().await?;
+        info!("Deleted VM {}", vm.name);
 
         Ok(())
     }
@@ -246,6 +831,7 @@ impl VMManager for VMOrchestrator {
             if let Some(vm) = vms.get_mut(id) {
                 vm.state = state;
                 vm.updated_at = Utc::now();
+                self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
             } else {
                 return Err(AivaError::VMError {
                     vm_name: id.to_string(),
@@ -273,29 +859,79 @@ impl VMManager for VMOrchestrator {
         self.platform.get_vm_metrics(&vm).await
     }
 
-    async fn execute_command(&self, id: &Uuid, command: &str) -> Result<String> {
-        let vm = {
+    async fn batch_metrics(&self, ids: Option<&[Uuid]>) -> HashMap<Uuid, Result<VMMetrics>> {
+        let mut results = HashMap::new();
+        let targets: Vec<VMInstance> = {
             let vms = self.vms.read().await;
-            vms.get(id).cloned()
+            match ids {
+                Some(ids) => ids
+                    .iter()
+                    .filter_map(|id| {
+                        let vm = vms.get(id).cloned();
+                        if vm.is_none() {
+                            results.insert(
+                                *id,
+                                Err(AivaError::VMError {
+                                    vm_name: id.to_string(),
+                                    state: VMState::Stopped,
+                                    message: "VM not found".to_string(),
+                                }),
+                            );
+                        }
+                        vm
+                    })
+                    .collect(),
+                None => vms
+                    .values()
+                    .filter(|vm| vm.state == VMState::Running)
+                    .cloned()
+                    .collect(),
+            }
         };
 
-        let vm = vm.ok_or_else(|| AivaError::VMError {
-            vm_name: id.to_string(),
-            state: VMState::Stopped,
-            message: "VM not found".to_string(),
-        })?;
-
-        if vm.state != VMState::Running {
-            return Err(AivaError::VMError {
-                vm_name: vm.name,
-                state: vm.state,
-                message: "VM must be running to execute commands".to_string(),
-            });
-        }
+        let platform_name = self.platform.name().to_string();
+        let tasks = targets.into_iter().map(|vm| {
+            let platform = self.platform.clone();
+            (vm.id, async move { platform.get_vm_metrics(&vm).await })
+        });
+        // A panicking platform call fails only its own VM
+        results.extend(
+            run_bounded(self.concurrency, tasks, |e| {
+                Err(AivaError::PlatformError {
+                    platform: platform_name.clone(),
+                    message: format!("Metrics collection failed: {e}"),
+                    recoverable: true,
+                })
+            })
+            .await,
+        );
+        results
+    }
 
+    async fn execute_command(&self, id: &Uuid, command: &str) -> Result<String> {
+        let vm = self.running_vm(id).await?;
         self.platform.execute_command(&vm, command).await
     }
 
+    async fn run_server(&self, id: &Uuid, plan: &RunPlan) -> Result<String> {
+        let vm = self.running_vm(id).await?;
+        let output = self
+            .platform
+            .run_server(&vm, &plan.command, plan.port)
+            .await?;
+
+        if let Some(pid) = RunResult::pid_from_output(&output) {
+            self.update_runtime(id, |runtime| {
+                let pids = runtime.mcp_pids.get_or_insert_with(Vec::new);
+                if !pids.contains(&pid) {
+                    pids.push(pid);
+                }
+            })
+            .await?;
+        }
+        Ok(output)
+    }
+
     /// Force reset a VM's state - use with caution
     async fn force_reset_vm_state(&self, id: &Uuid, state: VMState) -> Result<()> {
         {
@@ -303,6 +939,7 @@ impl VMManager for VMOrchestrator {
             if let Some(vm) = vms.get_mut(id) {
                 vm.state = state;
                 vm.updated_at = Utc::now();
+                self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
             } else {
                 return Err(AivaError::VMError {
                     vm_name: id.to_string(),
@@ -315,6 +952,63 @@ impl VMManager for VMOrchestrator {
         Ok(())
     }
 
+    /// Pause a running VM in memory or, with `hibernate`, snapshot it to
+    /// disk and release its memory
+    async fn pause_vm(&self, id: &Uuid, hibernate: bool) -> Result<PauseState> {
+        let vm = self.vms.read().await.get(id).cloned();
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+        check_pause(&vm, hibernate)?;
+
+        let pause = if hibernate {
+            let snapshot = self.platform.hibernate_vm(&vm).await?;
+            info!("Hibernated VM {} to {}", vm.name, snapshot.memory.display());
+            PauseState::Hibernated { snapshot }
+        } else {
+            self.platform.pause_vm(&vm).await?;
+            info!("Paused VM {}", vm.name);
+            PauseState::InMemory
+        };
+
+        let state = pause.vm_state();
+        let recorded = pause.clone();
+        self.update_runtime(id, |runtime| runtime.record_pause(recorded))
+            .await?;
+        self.update_vm_state(id, state).await?;
+        Ok(pause)
+    }
+
+    async fn resume_vm(&self, id: &Uuid) -> Result<()> {
+        let vm = self.vms.read().await.get(id).cloned();
+        let vm = vm.ok_or_else(|| AivaError::VMError {
+            vm_name: id.to_string(),
+            state: VMState::Stopped,
+            message: "VM not found".to_string(),
+        })?;
+
+        match resume_path(&vm)? {
+            ResumePath::Resume => {
+                self.platform.resume_vm(&vm).await?;
+                self.update_runtime(id, |runtime| runtime.paused = None)
+                    .await?;
+            }
+            ResumePath::Restore(snapshot) => {
+                let restored = self.platform.restore_vm(&vm, &snapshot).await?;
+                info!("Restored VM {} from its snapshot", vm.name);
+                self.update_runtime(id, |runtime| {
+                    runtime.pid = restored.runtime.pid;
+                    runtime.api_socket = restored.runtime.api_socket;
+                    runtime.paused = None;
+                })
+                .await?;
+            }
+        }
+        self.update_vm_state(id, VMState::Running).await
+    }
+
     /// Reset VMs that are stuck in transitional states
     async fn reset_stuck_vms(&self) -> Result<Vec<(Uuid, VMState)>> {
         let mut reset_vms = Vec::new();
@@ -334,6 +1028,7 @@ impl VMManager for VMOrchestrator {
                             _ => old_state,
                         };
                         vm.updated_at = now;
+                        self.events.publish(VMEvent::Updated(Box::new(vm.clone())));
                         reset_vms.push((*id, old_state));
                     }
                 }
@@ -358,4 +1053,162 @@ pub trait Platform: Send + Sync {
     async fn execute_command(&self, instance: &VMInstance, command: &str) -> Result<String>;
     async fn check_requirements(&self) -> Result<()>;
     fn name(&self) -> &str;
+
+    /// Stop `instance` and report what it took. Platforms that can tell
+    /// whether the guest powered off on its own override this.
+    async fn shutdown_vm(&self, instance: &VMInstance, force: bool) -> Result<StopMethod> {
+        self.stop_vm(instance, force).await?;
+        Ok(if force {
+            StopMethod::Killed
+        } else {
+            StopMethod::Graceful
+        })
+    }
+
+    /// Launch a long-running MCP server. `port` is the guest port it will
+    /// listen on, if any; platforms that forward ports themselves use it
+    /// instead of guessing from the command line.
+    async fn run_server(
+        &self,
+        instance: &VMInstance,
+        command: &str,
+        _port: Option<u16>,
+    ) -> Result<String> {
+        self.execute_command(instance, command).await
+    }
+
+    /// Describe the images and host resources `create_vm` would set up for
+    /// `instance`. Must not have side effects.
+    fn plan_create(&self, _instance: &VMInstance) -> PlatformPlan {
+        PlatformPlan::default()
+    }
+
+    /// Grow the root filesystem of an existing VM to `disk_gb`. Callers have
+    /// already checked that this is not a shrink.
+    async fn resize_disk(&self, _instance: &VMInstance, _disk_gb: u64) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "disk resize on {}",
+            self.name()
+        )))
+    }
+
+    /// Attach `drive` to `instance`, which is running, as `drive_id`
+    async fn attach_drive(
+        &self,
+        _instance: &VMInstance,
+        _drive_id: &str,
+        _drive: &BlockDevice,
+    ) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "drive attach on {}",
+            self.name()
+        )))
+    }
+
+    /// What `instance`, which is running, can change without a restart
+    async fn scale_capabilities(&self, _instance: &VMInstance) -> Result<ScaleCapabilities> {
+        Ok(ScaleCapabilities::default())
+    }
+
+    /// Apply a live step chosen from [`Platform::scale_capabilities`]
+    async fn apply_scale_step(&self, _instance: &VMInstance, step: &ScaleStep) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "{step:?} on {}",
+            self.name()
+        )))
+    }
+
+    /// Stop the vCPUs of `instance`, keeping its process and memory
+    async fn pause_vm(&self, _instance: &VMInstance) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "pause on {}",
+            self.name()
+        )))
+    }
+
+    /// Let the vCPUs of a VM paused by [`Platform::pause_vm`] run again
+    async fn resume_vm(&self, _instance: &VMInstance) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "resume on {}",
+            self.name()
+        )))
+    }
+
+    /// Write a `snapshot_type` snapshot of `instance`, which must be paused,
+    /// to disk. The VM stays paused; [`Platform::restore_vm`] starts a copy
+    /// of it from the snapshot.
+    async fn snapshot_vm(
+        &self,
+        _instance: &VMInstance,
+        _snapshot_type: SnapshotType,
+    ) -> Result<SnapshotFiles> {
+        Err(AivaError::NotImplemented(format!(
+            "snapshot on {}",
+            self.name()
+        )))
+    }
+
+    /// Snapshot `instance`, which is running or paused, to disk and end its
+    /// process
+    async fn hibernate_vm(&self, _instance: &VMInstance) -> Result<SnapshotFiles> {
+        Err(AivaError::NotImplemented(format!(
+            "hibernate on {}",
+            self.name()
+        )))
+    }
+
+    /// Start a new process for `instance` from `snapshot` and return the
+    /// instance with its new runtime information
+    async fn restore_vm(
+        &self,
+        _instance: &VMInstance,
+        _snapshot: &SnapshotFiles,
+    ) -> Result<VMInstance> {
+        Err(AivaError::NotImplemented(format!(
+            "restore from snapshot on {}",
+            self.name()
+        )))
+    }
+
+    /// Serve `metadata` to the guest of `instance`, replacing what it saw
+    /// before. Platforms without a metadata service ignore it.
+    async fn publish_metadata(&self, _instance: &VMInstance, _metadata: &VMMetadata) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the hypervisor process of `instance` is running
+    async fn vm_liveness(&self, _instance: &VMInstance) -> Liveness {
+        Liveness::Unknown
+    }
+
+    /// Host resources that look like this platform's but belong to none of
+    /// `known`. Resources a VM may still be setting up are not reported.
+    async fn orphaned_resources(&self, _known: &[VMInstance]) -> Result<Vec<OrphanedResource>> {
+        Ok(Vec::new())
+    }
+
+    /// Remove a resource reported by [`Platform::orphaned_resources`]
+    async fn remove_orphan(&self, resource: &OrphanedResource) -> Result<()> {
+        Err(AivaError::NotImplemented(format!(
+            "removing {resource} on {}",
+            self.name()
+        )))
+    }
+
+    /// Release resources a failed operation may have left behind (processes,
+    /// network devices, workspaces) and describe each one that was cleaned.
+    /// The default forcibly stops the VM and ignores failures, since a VM in
+    /// `Error` may well have no process left.
+    async fn cleanup_failed_vm(&self, instance: &VMInstance) -> Result<Vec<String>> {
+        match self.stop_vm(instance, true).await {
+            Ok(()) => Ok(vec!["force-stopped VM process".to_string()]),
+            Err(e) => {
+                warn!(
+                    "Forced stop during recovery of {} failed: {}",
+                    instance.name, e
+                );
+                Ok(Vec::new())
+            }
+        }
+    }
 }
@@ -0,0 +1,447 @@
+//! Defaults, validation and a builder for [`VMConfig`].
+//!
+//! `VMConfig::builder().cpus(2).memory_mb(4096).build()` starts from
+//! [`VMConfig::default`], so callers only spell out what differs, and
+//! rejects combinations the platforms cannot boot.
+
+use crate::error::{AivaError, Result};
+use crate::schema::SCHEMA_VERSION;
+use crate::types::{
+    CacheStrategy, ExecTransport, ExecutionConfig, LoggingConfig, NetworkConfig, PortMapping,
+    Protocol, StorageConfig, VMConfig,
+};
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+/// Most vCPUs a Firecracker microVM can have
+pub const MAX_CPUS: u32 = 32;
+
+/// Longest label key or value
+pub const MAX_LABEL_LEN: usize = 63;
+
+/// Most bytes of keys and values in [`VMConfig::metadata`], well within
+/// what the metadata service stores
+pub const MAX_METADATA_BYTES: usize = 16 * 1024;
+
+/// Longest kernel command line, the limit of the x86 boot protocol
+pub const MAX_BOOT_ARGS_LEN: usize = 2048;
+
+/// Least memory a guest kernel boots with
+pub const MIN_MEMORY_MB: u64 = 128;
+
+/// Share of the host's cores and memory a VM gets when the configuration
+/// leaves `cpus` or `memory` unset
+pub const HOST_SHARE_PERCENT: u64 = 25;
+
+/// Bounds of the host-scaled defaults; a tiny host still gets a usable VM
+/// and a large one does not hand a single agent most of the machine
+pub const SCALED_CPUS: (u32, u32) = (1, 8);
+pub const SCALED_MEMORY_MB: (u64, u64) = (512, 16 * 1024);
+
+/// Cores and memory of the machine VMs run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostCapacity {
+    pub cpus: u32,
+    pub memory_mb: u64,
+}
+
+impl HostCapacity {
+    pub fn detect() -> Self {
+        let system = sysinfo::System::new_with_specifics(
+            sysinfo::RefreshKind::nothing()
+                .with_memory(sysinfo::MemoryRefreshKind::nothing().with_ram()),
+        );
+        let cpus = std::thread::available_parallelism().map_or(1, |n| n.get() as u32);
+        Self {
+            cpus,
+            memory_mb: system.total_memory() / (1024 * 1024),
+        }
+    }
+
+    /// [`HOST_SHARE_PERCENT`] of the cores, clamped to [`SCALED_CPUS`]
+    pub fn scaled_cpus(&self) -> u32 {
+        let share = (u64::from(self.cpus) * HOST_SHARE_PERCENT / 100) as u32;
+        share.clamp(SCALED_CPUS.0, SCALED_CPUS.1)
+    }
+
+    /// [`HOST_SHARE_PERCENT`] of the memory in whole 256 MB steps, clamped
+    /// to [`SCALED_MEMORY_MB`]
+    pub fn scaled_memory_mb(&self) -> u64 {
+        let share = self.memory_mb * HOST_SHARE_PERCENT / 100 / 256 * 256;
+        share.clamp(SCALED_MEMORY_MB.0, SCALED_MEMORY_MB.1)
+    }
+}
+
+impl Default for VMConfig {
+    fn default() -> Self {
+        Self {
+            cpus: 2,
+            memory_mb: 4096,
+            disk_gb: 20,
+            kernel_path: PathBuf::from("/opt/aiva/images/vmlinux"),
+            rootfs_path: PathBuf::from("/opt/aiva/images/rootfs.ext4"),
+            network: NetworkConfig::default(),
+            storage: StorageConfig::default(),
+            logging: LoggingConfig::default(),
+            security_policy: None,
+            workdir: None,
+            run_as_user: None,
+            execution: ExecutionConfig::default(),
+            labels: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            boot_args: None,
+            extra_boot_args: Vec::new(),
+            cpu_template: None,
+            schema_version: SCHEMA_VERSION,
+        }
+    }
+}
+
+impl VMConfig {
+    pub fn builder() -> VMConfigBuilder {
+        VMConfigBuilder::default()
+    }
+
+    /// Check that the resources, addresses and port mappings fit together
+    pub fn validate(&self) -> Result<()> {
+        if self.cpus == 0 || self.cpus > MAX_CPUS {
+            return Err(AivaError::ConfigError(format!(
+                "cpus must be between 1 and {MAX_CPUS}, got {}",
+                self.cpus
+            )));
+        }
+        if self.memory_mb < MIN_MEMORY_MB {
+            return Err(AivaError::ConfigError(format!(
+                "memory_mb must be at least {MIN_MEMORY_MB}, got {}",
+                self.memory_mb
+            )));
+        }
+        if self.disk_gb == 0 {
+            return Err(AivaError::ConfigError(
+                "disk_gb must be at least 1".to_string(),
+            ));
+        }
+        if let Some(workdir) = &self.workdir
+            && !workdir.is_absolute()
+        {
+            return Err(AivaError::ConfigError(format!(
+                "workdir must be an absolute guest path, got {}",
+                workdir.display()
+            )));
+        }
+
+        self.validate_boot_args()?;
+        self.execution.validate()?;
+        validate_labels(&self.labels)?;
+        validate_metadata(&self.metadata)?;
+        validate_addresses(&self.network)?;
+        self.network.validate_dns()?;
+        if let Some(hostname) = &self.network.hostname {
+            crate::validate_hostname(hostname)?;
+        }
+        validate_port_mappings(&self.network.port_mappings)
+    }
+}
+
+impl VMConfig {
+    /// Kernel command line the VM boots with: `boot_args` when set, and
+    /// otherwise the platform's `default` followed by `extra_boot_args`
+    pub fn kernel_command_line(&self, default: &str) -> String {
+        kernel_command_line(self.boot_args.as_deref(), &self.extra_boot_args, default)
+    }
+
+    /// The configured arguments fit in [`MAX_BOOT_ARGS_LEN`] and are a
+    /// single line
+    pub fn validate_boot_args(&self) -> Result<()> {
+        let configured = self.kernel_command_line("");
+        if configured.len() > MAX_BOOT_ARGS_LEN {
+            return Err(AivaError::ConfigError(format!(
+                "Boot arguments take {} bytes, at most {MAX_BOOT_ARGS_LEN} are allowed",
+                configured.len()
+            )));
+        }
+        if configured.contains(['\n', '\r', '\0']) {
+            return Err(AivaError::ConfigError(
+                "Boot arguments must be a single line".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// `boot_args` if given, otherwise `default` followed by `extra_boot_args`
+pub fn kernel_command_line(
+    boot_args: Option<&str>,
+    extra_boot_args: &[String],
+    default: &str,
+) -> String {
+    if let Some(boot_args) = boot_args {
+        return boot_args.to_string();
+    }
+    std::iter::once(default)
+        .chain(extra_boot_args.iter().map(String::as_str))
+        .filter(|args| !args.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Label keys are letters, digits, `-`, `_`, `.` and `/`; values may be
+/// empty. Both are at most [`MAX_LABEL_LEN`] characters.
+pub fn validate_labels(labels: &BTreeMap<String, String>) -> Result<()> {
+    for (key, value) in labels {
+        if key.is_empty() || key.len() > MAX_LABEL_LEN {
+            return Err(AivaError::ConfigError(format!(
+                "Label key '{key}' must be 1 to {MAX_LABEL_LEN} characters"
+            )));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+        {
+            return Err(AivaError::ConfigError(format!(
+                "Label key '{key}' may only contain letters, digits, '-', '_', '.' and '/'"
+            )));
+        }
+        if value.len() > MAX_LABEL_LEN {
+            return Err(AivaError::ConfigError(format!(
+                "Value of label '{key}' is longer than {MAX_LABEL_LEN} characters"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Metadata keys are letters, digits, `-`, `_` and `.`, at most
+/// [`MAX_LABEL_LEN`] characters, as each is a path segment in the metadata
+/// service. Keys and values together take at most [`MAX_METADATA_BYTES`].
+pub fn validate_metadata(metadata: &BTreeMap<String, String>) -> Result<()> {
+    let mut size = 0;
+    for (key, value) in metadata {
+        if key.is_empty() || key.len() > MAX_LABEL_LEN {
+            return Err(AivaError::ConfigError(format!(
+                "Metadata key '{key}' must be 1 to {MAX_LABEL_LEN} characters"
+            )));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        {
+            return Err(AivaError::ConfigError(format!(
+                "Metadata key '{key}' may only contain letters, digits, '-', '_' and '.'"
+            )));
+        }
+        size += key.len() + value.len();
+    }
+    if size > MAX_METADATA_BYTES {
+        return Err(AivaError::ConfigError(format!(
+            "Metadata takes {size} bytes, at most {MAX_METADATA_BYTES} are allowed"
+        )));
+    }
+    Ok(())
+}
+
+fn parse_ipv4(value: &str, field: &str) -> Result<Ipv4Addr> {
+    value.parse().map_err(|_| {
+        AivaError::ConfigError(format!("network.{field} '{value}' is not an IPv4 address"))
+    })
+}
+
+fn validate_addresses(network: &NetworkConfig) -> Result<()> {
+    let guest_ip = parse_ipv4(&network.guest_ip, "guest_ip")?;
+    let host_ip = parse_ipv4(&network.host_ip, "host_ip")?;
+    parse_ipv4(&network.gateway, "gateway")?;
+
+    if guest_ip == host_ip {
+        return Err(AivaError::ConfigError(format!(
+            "network.guest_ip and network.host_ip are both {guest_ip}"
+        )));
+    }
+
+    let (subnet, prefix) = network
+        .subnet
+        .split_once('/')
+        .and_then(|(address, prefix)| {
+            Some((
+                address.parse::<Ipv4Addr>().ok()?,
+                prefix.parse::<u32>().ok()?,
+            ))
+        })
+        .filter(|(_, prefix)| *prefix <= 32)
+        .ok_or_else(|| {
+            AivaError::ConfigError(format!(
+                "network.subnet '{}' is not a CIDR range such as 172.16.0.0/24",
+                network.subnet
+            ))
+        })?;
+    let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+    for (field, ip) in [("guest_ip", guest_ip), ("host_ip", host_ip)] {
+        if u32::from(ip) & mask != u32::from(subnet) & mask {
+            return Err(AivaError::ConfigError(format!(
+                "network.{field} {ip} is outside network.subnet {}",
+                network.subnet
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn validate_port_mappings(mappings: &[PortMapping]) -> Result<()> {
+    for (i, mapping) in mappings.iter().enumerate() {
+        if mapping.host_port == 0 || mapping.guest_port == 0 {
+            return Err(AivaError::ConfigError(format!(
+                "Port mapping {}:{} uses port 0",
+                mapping.host_port, mapping.guest_port
+            )));
+        }
+        if mappings[..i].iter().any(|other| {
+            other.host_port == mapping.host_port
+                && other.protocol == mapping.protocol
+                && other.bind_address() == mapping.bind_address()
+        }) {
+            return Err(AivaError::ConfigError(format!(
+                "Host port {}/{} is mapped more than once",
+                mapping.host_port, mapping.protocol
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Builds a [`VMConfig`] on top of the defaults, validating on
+/// [`build`](Self::build)
+#[derive(Debug, Clone, Default)]
+pub struct VMConfigBuilder {
+    config: VMConfig,
+}
+
+impl VMConfigBuilder {
+    pub fn cpus(mut self, cpus: u32) -> Self {
+        self.config.cpus = cpus;
+        self
+    }
+
+    pub fn memory_mb(mut self, memory_mb: u64) -> Self {
+        self.config.memory_mb = memory_mb;
+        self
+    }
+
+    pub fn disk_gb(mut self, disk_gb: u64) -> Self {
+        self.config.disk_gb = disk_gb;
+        self
+    }
+
+    pub fn kernel_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.kernel_path = path.into();
+        self
+    }
+
+    pub fn rootfs_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.config.rootfs_path = path.into();
+        self
+    }
+
+    pub fn network(mut self, network: NetworkConfig) -> Self {
+        self.config.network = network;
+        self
+    }
+
+    /// Guest and host addresses on `subnet`, with the host as gateway
+    pub fn addresses(mut self, guest_ip: &str, host_ip: &str, subnet: &str) -> Self {
+        let network = &mut self.config.network;
+        network.guest_ip = guest_ip.to_string();
+        network.host_ip = host_ip.to_string();
+        network.gateway = host_ip.to_string();
+        network.subnet = subnet.to_string();
+        self
+    }
+
+    pub fn dns_servers<S: Into<String>>(mut self, servers: impl IntoIterator<Item = S>) -> Self {
+        self.config.network.dns_servers = servers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Forward `host_port` on the loopback address to `guest_port`
+    pub fn port(mut self, host_port: u16, guest_port: u16, protocol: Protocol) -> Self {
+        self.config
+            .network
+            .port_mappings
+            .push(PortMapping::new(host_port, guest_port, protocol));
+        self
+    }
+
+    pub fn port_mapping(mut self, mapping: PortMapping) -> Self {
+        self.config.network.port_mappings.push(mapping);
+        self
+    }
+
+    pub fn cache_strategy(mut self, cache_strategy: CacheStrategy) -> Self {
+        self.config.storage.cache_strategy = cache_strategy;
+        self
+    }
+
+    pub fn storage(mut self, storage: StorageConfig) -> Self {
+        self.config.storage = storage;
+        self
+    }
+
+    pub fn logging(mut self, logging: LoggingConfig) -> Self {
+        self.config.logging = logging;
+        self
+    }
+
+    pub fn security_policy(mut self, policy: impl Into<String>) -> Self {
+        self.config.security_policy = Some(policy.into());
+        self
+    }
+
+    pub fn workdir(mut self, workdir: impl Into<PathBuf>) -> Self {
+        self.config.workdir = Some(workdir.into());
+        self
+    }
+
+    pub fn run_as_user(mut self, user: impl Into<String>) -> Self {
+        self.config.run_as_user = Some(user.into());
+        self
+    }
+
+    /// Boot with `boot_args` instead of the platform's command line
+    pub fn boot_args(mut self, boot_args: impl Into<String>) -> Self {
+        self.config.boot_args = Some(boot_args.into());
+        self
+    }
+
+    /// Append `arg` to the platform's kernel command line
+    pub fn extra_boot_arg(mut self, arg: impl Into<String>) -> Self {
+        self.config.extra_boot_args.push(arg.into());
+        self
+    }
+
+    /// Mask the guest's CPU features with the Firecracker template `name`
+    pub fn cpu_template(mut self, name: impl Into<String>) -> Self {
+        self.config.cpu_template = Some(name.into());
+        self
+    }
+
+    pub fn label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.labels.insert(key.into(), value.into());
+        self
+    }
+
+    /// Serve `value` to the guest under `/aiva/metadata/<key>`
+    pub fn metadata(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.config.metadata.insert(key.into(), value.into());
+        self
+    }
+
+    /// Try only `transports`, in this order, to run commands in the guest
+    pub fn transports(mut self, transports: impl IntoIterator<Item = ExecTransport>) -> Self {
+        self.config.execution.transports = transports.into_iter().collect();
+        self
+    }
+
+    pub fn build(self) -> Result<VMConfig> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
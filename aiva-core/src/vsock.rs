@@ -0,0 +1,57 @@
+//! Guest CIDs for virtio-vsock.
+//!
+//! Every VM with a vsock device needs a context ID of its own, or commands
+//! meant for one guest reach another. CIDs 0 to 2 are reserved for the
+//! hypervisor, loopback and the host, so guests are numbered from
+//! [`FIRST_GUEST_CID`]. A VM keeps its CID in
+//! [`RuntimeInfo::vsock_cid`](crate::RuntimeInfo::vsock_cid) for as long as
+//! it exists; deleting it makes the CID free for the next create.
+
+use crate::error::{AivaError, ResourceType, Result};
+use crate::types::VMInstance;
+use std::collections::BTreeSet;
+
+/// Lowest CID a guest can have
+pub const FIRST_GUEST_CID: u32 = 3;
+/// `VMADDR_CID_ANY`, never a valid guest CID
+const CID_ANY: u32 = u32::MAX;
+
+/// Guest CIDs in use. Built from the stored VMs, so a VM reloaded after a
+/// restart keeps its CID and the next create passes over it.
+#[derive(Debug, Clone, Default)]
+pub struct VsockCidAllocator {
+    used: BTreeSet<u32>,
+}
+
+impl VsockCidAllocator {
+    /// Allocator holding the CIDs of `vms`
+    pub fn from_vms<'a>(vms: impl IntoIterator<Item = &'a VMInstance>) -> Self {
+        Self {
+            used: vms
+                .into_iter()
+                .filter_map(|vm| vm.runtime.vsock_cid)
+                .collect(),
+        }
+    }
+
+    /// Take the lowest free CID
+    pub fn allocate(&mut self) -> Result<u32> {
+        let cid = (FIRST_GUEST_CID..CID_ANY)
+            .find(|cid| !self.used.contains(cid))
+            .ok_or_else(|| AivaError::ResourceError {
+                resource_type: ResourceType::Network,
+                message: "no free vsock CID left".to_string(),
+            })?;
+        self.used.insert(cid);
+        Ok(cid)
+    }
+
+    /// Make `cid` free for the next allocation
+    pub fn release(&mut self, cid: u32) {
+        self.used.remove(&cid);
+    }
+
+    pub fn is_used(&self, cid: u32) -> bool {
+        self.used.contains(&cid)
+    }
+}
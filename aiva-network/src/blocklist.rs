@@ -0,0 +1,123 @@
+//! Enforcement of the blocked address ranges of a VM's network policy.
+//!
+//! New connections from the VM's TAP device to a blocked range are dropped
+//! on the FORWARD chain. Addresses the VM must always reach are accepted
+//! ahead of the drops, so a broad range cannot cut it off from its gateway.
+//! Every rule carries a comment naming the TAP device, which lets
+//! [`remove_ip_block_rules`] find them again without the policy they were
+//! built from.
+
+use aiva_core::{AivaError, Result};
+use std::net::IpAddr;
+use std::process::Command;
+use tracing::debug;
+
+/// Prefix of the comment on every block rule, followed by the TAP device
+pub const IP_BLOCK_COMMENT_PREFIX: &str = "aiva-block:";
+
+/// Build the rules blocking `blocked` for `tap_device`, in the order they are
+/// evaluated: the `allowed` addresses first, then the blocked ranges. Only
+/// IPv4 is filtered, as with the rest of the iptables setup.
+pub fn ip_block_rule_args(
+    action: &str,
+    tap_device: &str,
+    allowed: &[IpAddr],
+    blocked: &[String],
+) -> Vec<Vec<String>> {
+    let comment = format!("{IP_BLOCK_COMMENT_PREFIX}{tap_device}");
+    let rule = |dest: &str, target: &str| -> Vec<String> {
+        [
+            action,
+            "FORWARD",
+            "-i",
+            tap_device,
+            "-d",
+            dest,
+            "-m",
+            "comment",
+            "--comment",
+            &comment,
+            "-j",
+            target,
+        ]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect()
+    };
+
+    let allowed = allowed
+        .iter()
+        .filter(|ip| ip.is_ipv4() && !ip.is_loopback())
+        .map(|ip| rule(&ip.to_string(), "ACCEPT"));
+    let blocked = blocked
+        .iter()
+        .filter(|range| !range.contains(':'))
+        .map(|range| rule(range, "DROP"));
+    allowed.chain(blocked).collect()
+}
+
+/// Insert `rules`, built by [`ip_block_rule_args`] with `-I`, at the top of
+/// the FORWARD chain in the order they are to be evaluated
+pub fn add_ip_block_rules(rules: &[Vec<String>]) -> Result<()> {
+    for args in rules.iter().rev() {
+        let output =
+            Command::new("iptables")
+                .args(args)
+                .output()
+                .map_err(|e| AivaError::NetworkError {
+                    operation: "add IP block rule".to_string(),
+                    cause: e.to_string(),
+                })?;
+        if !output.status.success() {
+            return Err(AivaError::NetworkError {
+                operation: "add IP block rule".to_string(),
+                cause: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove every block rule of `tap_device`
+pub fn remove_ip_block_rules(tap_device: &str) -> Result<()> {
+    let output = Command::new("iptables")
+        .args(["-S", "FORWARD"])
+        .output()
+        .map_err(|e| AivaError::NetworkError {
+            operation: "list IP block rules".to_string(),
+            cause: e.to_string(),
+        })?;
+
+    for args in ip_block_rules_in(&String::from_utf8_lossy(&output.stdout), tap_device) {
+        debug!("Removing IP block rule: {}", args.join(" "));
+        Command::new("iptables")
+            .args(&args)
+            .output()
+            .map_err(|e| AivaError::NetworkError {
+                operation: "remove IP block rule".to_string(),
+                cause: e.to_string(),
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Deletion arguments for the block rules of `tap_device` found in
+/// `iptables -S FORWARD` output
+pub(crate) fn ip_block_rules_in(rules: &str, tap_device: &str) -> Vec<Vec<String>> {
+    let comment = format!("{IP_BLOCK_COMMENT_PREFIX}{tap_device}");
+    rules
+        .lines()
+        .filter_map(|line| line.strip_prefix("-A "))
+        .map(|rule| {
+            std::iter::once("-D".to_string())
+                .chain(
+                    rule.split_whitespace()
+                        .map(|arg| arg.trim_matches('"').to_string()),
+                )
+                .collect::<Vec<_>>()
+        })
+        .filter(|args| args.contains(&comment))
+        .collect()
+}
@@ -2,7 +2,7 @@ use aiva_core::{AivaError, Result};
 use std::process::Command;
 use tracing::{debug, info};
 
-const BRIDGE_NAME: &str = "aiva-br0";
+pub(crate) const BRIDGE_NAME: &str = "aiva-br0";
 
 pub fn create_bridge() -> Result<()> {
     info!("Creating bridge: {}", BRIDGE_NAME);
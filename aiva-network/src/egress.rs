@@ -0,0 +1,199 @@
+//! Audit logging of the connections a VM opens to the outside.
+//!
+//! With `network.audit_egress` set, a `LOG` rule on the FORWARD chain matches
+//! the first packet of every connection arriving from the VM's TAP device.
+//! The kernel writes one line per match:
+//!
+//! ```text
+//! aiva-egress: IN=aiva-tap-web OUT=eth0 SRC=172.16.0.2 DST=93.184.216.34 ... PROTO=TCP SPT=40312 DPT=443 ...
+//! ```
+//!
+//! The rule is rate limited so a guest opening connections in a loop cannot
+//! flood the kernel log. [`parse_egress_log_line`] turns such a line, as read
+//! back from the journal or written by ulogd for an `NFLOG` rule, into an
+//! [`EgressRecord`].
+
+use aiva_core::{AivaError, LogEntry, LogLevel, Result};
+use chrono::{DateTime, Utc};
+use std::net::IpAddr;
+use std::process::Command;
+use tracing::debug;
+
+/// Prefix of the kernel log lines written by the egress rule
+pub const EGRESS_LOG_PREFIX: &str = "aiva-egress: ";
+
+/// Sustained rate of logged connections per VM
+pub const EGRESS_LOG_LIMIT: &str = "10/second";
+
+/// Connections logged in a burst before [`EGRESS_LOG_LIMIT`] applies
+pub const EGRESS_LOG_BURST: u32 = 20;
+
+/// Build the rule logging new connections from `tap_device`.
+///
+/// `LOG` does not stop the packet, but the subnet's FORWARD `ACCEPT` does, so
+/// the rule is inserted at the top of the chain rather than appended.
+pub fn egress_log_rule_args(action: &str, tap_device: &str, guest_ip: &str) -> Vec<String> {
+    let chain_action = match action {
+        "-A" => "-I",
+        other => other,
+    };
+
+    [
+        chain_action,
+        "FORWARD",
+        "-i",
+        tap_device,
+        "-s",
+        guest_ip,
+        "-m",
+        "conntrack",
+        "--ctstate",
+        "NEW",
+        "-m",
+        "limit",
+        "--limit",
+        EGRESS_LOG_LIMIT,
+        "--limit-burst",
+        &EGRESS_LOG_BURST.to_string(),
+        "-j",
+        "LOG",
+        "--log-prefix",
+        EGRESS_LOG_PREFIX,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+pub fn add_egress_log_rule(tap_device: &str, guest_ip: &str) -> Result<()> {
+    debug!("Logging egress of {} ({})", tap_device, guest_ip);
+
+    let output = Command::new("iptables")
+        .args(egress_log_rule_args("-A", tap_device, guest_ip))
+        .output()
+        .map_err(|e| AivaError::NetworkError {
+            operation: "add egress log rule".to_string(),
+            cause: e.to_string(),
+        })?;
+
+    if !output.status.success() {
+        return Err(AivaError::NetworkError {
+            operation: "add egress log rule".to_string(),
+            cause: String::from_utf8_lossy(&output.stderr).to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn remove_egress_log_rule(tap_device: &str, guest_ip: &str) -> Result<()> {
+    Command::new("iptables")
+        .args(egress_log_rule_args("-D", tap_device, guest_ip))
+        .output()
+        .map_err(|e| AivaError::NetworkError {
+            operation: "remove egress log rule".to_string(),
+            cause: e.to_string(),
+        })?;
+
+    Ok(())
+}
+
+/// A connection a VM opened, as logged by the egress rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EgressRecord {
+    pub timestamp: DateTime<Utc>,
+    /// TAP device the connection came in on
+    pub interface: String,
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    /// `None` for protocols without ports, such as ICMP
+    pub dst_port: Option<u16>,
+    /// Lowercase protocol name, e.g. `tcp`
+    pub protocol: String,
+}
+
+/// Parse one kernel or ulogd log line written for the egress rule.
+///
+/// The line may start with an ISO 8601 timestamp (`journalctl -o
+/// short-iso-precise`); otherwise the record is stamped with the current
+/// time. Lines of other rules and lines missing an address are skipped.
+pub fn parse_egress_log_line(line: &str) -> Option<EgressRecord> {
+    let (head, fields) = line.split_once(EGRESS_LOG_PREFIX)?;
+
+    let mut interface = None;
+    let mut src_ip = None;
+    let mut dst_ip = None;
+    let mut dst_port = None;
+    let mut protocol = None;
+    for (key, value) in fields
+        .split_whitespace()
+        .filter_map(|field| field.split_once('='))
+    {
+        match key {
+            "IN" => interface = Some(value.to_string()),
+            "SRC" => src_ip = value.parse().ok(),
+            "DST" => dst_ip = value.parse().ok(),
+            "DPT" => dst_port = value.parse().ok(),
+            "PROTO" => protocol = Some(value.to_lowercase()),
+            _ => {}
+        }
+    }
+
+    let timestamp = head
+        .split_whitespace()
+        .next()
+        .and_then(parse_timestamp)
+        .unwrap_or_else(Utc::now);
+
+    Some(EgressRecord {
+        timestamp,
+        interface: interface.filter(|name| !name.is_empty())?,
+        src_ip: src_ip?,
+        dst_ip: dst_ip?,
+        dst_port,
+        protocol: protocol?,
+    })
+}
+
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .ok()
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+impl EgressRecord {
+    /// Destination as `ip:port`, or just the address without a port
+    pub fn destination(&self) -> String {
+        match (self.dst_port, self.dst_ip) {
+            (Some(port), IpAddr::V6(ip)) => format!("[{ip}]:{port}"),
+            (Some(port), ip) => format!("{ip}:{port}"),
+            (None, ip) => ip.to_string(),
+        }
+    }
+
+    /// Monitoring log entry for the connection, tagged with `vm_id`
+    pub fn to_log_entry(&self, vm_id: &str) -> LogEntry {
+        let mut entry = LogEntry::new(
+            Some(vm_id.to_string()),
+            LogLevel::Info,
+            format!("egress {} {}", self.protocol, self.destination()),
+        );
+        entry.timestamp = self.timestamp;
+        entry
+            .metadata
+            .insert("source".to_string(), "egress".to_string());
+        entry
+            .metadata
+            .insert("dst_ip".to_string(), self.dst_ip.to_string());
+        if let Some(port) = self.dst_port {
+            entry
+                .metadata
+                .insert("dst_port".to_string(), port.to_string());
+        }
+        entry
+            .metadata
+            .insert("protocol".to_string(), self.protocol.clone());
+        entry
+    }
+}
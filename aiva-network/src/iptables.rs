@@ -1,4 +1,7 @@
-use aiva_core::{AivaError, NetworkConfig, Result};
+use crate::bridge::BRIDGE_NAME;
+use aiva_core::{AivaError, NetworkConfig, PortMapping, Result};
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::process::Command;
 use tracing::{debug, info};
 
@@ -45,11 +48,11 @@ pub fn setup_nat_rules(config: &NetworkConfig) -> Result<()> {
             ])
             .output();
 
-        if let Ok(check_output) = check {
-            if check_output.status.success() {
-                debug!("NAT rule already exists");
-                return Ok(());
-            }
+        if let Ok(check_output) = check
+            && check_output.status.success()
+        {
+            debug!("NAT rule already exists");
+            return Ok(());
         }
 
         return Err(AivaError::NetworkError {
@@ -61,14 +64,15 @@ pub fn setup_nat_rules(config: &NetworkConfig) -> Result<()> {
     // Add FORWARD rules
     add_forward_rule("ACCEPT", &config.subnet)?;
 
+    // DNAT of loopback traffic is dropped by the kernel unless the bridge
+    // the guests sit behind routes it
+    if config.port_mappings.iter().any(binds_loopback) {
+        enable_loopback_forwarding()?;
+    }
+
     // Add port forwarding rules
     for mapping in &config.port_mappings {
-        add_port_forward_rule(
-            &config.guest_ip,
-            mapping.host_port,
-            mapping.guest_port,
-            &mapping.protocol.to_string().to_lowercase(),
-        )?;
+        add_port_forward_rule(&config.guest_ip, mapping)?;
     }
 
     Ok(())
@@ -102,17 +106,137 @@ pub fn cleanup_nat_rules(config: &NetworkConfig) -> Result<()> {
 
     // Remove port forwarding rules
     for mapping in &config.port_mappings {
-        let _ = remove_port_forward_rule(
-            &config.guest_ip,
-            mapping.host_port,
-            mapping.guest_port,
-            &mapping.protocol.to_string().to_lowercase(),
-        );
+        let _ = remove_port_forward_rule(&config.guest_ip, mapping);
+    }
+
+    // Other VMs may still forward loopback ports through the bridge
+    if config.port_mappings.iter().any(binds_loopback) {
+        let remaining = Command::new("iptables")
+            .args(["-t", "nat", "-S", "OUTPUT"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned());
+        if let Ok(rules) = remaining
+            && !has_loopback_forwards(&rules)
+        {
+            disable_loopback_forwarding()?;
+        }
     }
 
     Ok(())
 }
 
+fn binds_loopback(mapping: &PortMapping) -> bool {
+    mapping.host_ip.is_some_and(|ip| ip.is_loopback())
+}
+
+/// The `route_localnet` switch of one interface
+pub(crate) fn route_localnet_path(device: &str) -> PathBuf {
+    PathBuf::from(format!("/proc/sys/net/ipv4/conf/{device}/route_localnet"))
+}
+
+/// Rules a loopback forward needs besides its DNAT, shared by all VMs:
+///
+/// - a filter rule dropping new connections from the guests to the host's
+///   loopback addresses, which `route_localnet` would otherwise let through.
+///   Replies to forwarded connections are established and still pass.
+/// - a MASQUERADE rule for loopback sources leaving through the bridge.
+///   DNAT only rewrites the destination, and a guest cannot reply to
+///   127.0.0.1.
+pub(crate) fn loopback_rule_args(action: &str) -> Vec<Vec<String>> {
+    let guard = [
+        action,
+        "INPUT",
+        "-i",
+        BRIDGE_NAME,
+        "-d",
+        "127.0.0.0/8",
+        "-m",
+        "conntrack",
+        "!",
+        "--ctstate",
+        "RELATED,ESTABLISHED",
+        "-j",
+        "DROP",
+    ];
+    let snat = [
+        "-t",
+        "nat",
+        action,
+        "POSTROUTING",
+        "-s",
+        "127.0.0.0/8",
+        "-o",
+        BRIDGE_NAME,
+        "-j",
+        "MASQUERADE",
+    ];
+    [&guard[..], &snat[..]]
+        .iter()
+        .map(|rule| rule.iter().map(|arg| arg.to_string()).collect())
+        .collect()
+}
+
+/// Whether `iptables -t nat -S OUTPUT` output still has a DNAT rule for a
+/// loopback address
+pub(crate) fn has_loopback_forwards(rules: &str) -> bool {
+    rules.lines().any(|rule| {
+        rule.contains("-j DNAT")
+            && rule
+                .split_whitespace()
+                .skip_while(|arg| *arg != "-d")
+                .nth(1)
+                .is_some_and(|dest| dest.starts_with("127."))
+    })
+}
+
+/// Route loopback-addressed traffic over the bridge only, masquerade it so
+/// the guests can reply, and keep them from reaching host services on
+/// loopback
+fn enable_loopback_forwarding() -> Result<()> {
+    for (check, insert) in loopback_rule_args("-C")
+        .into_iter()
+        .zip(loopback_rule_args("-I"))
+    {
+        let present = Command::new("iptables")
+            .args(&check)
+            .output()
+            .is_ok_and(|output| output.status.success());
+        if present {
+            continue;
+        }
+        let output = Command::new("iptables")
+            .args(&insert)
+            .output()
+            .map_err(|e| AivaError::NetworkError {
+                operation: "add loopback forwarding rule".to_string(),
+                cause: e.to_string(),
+            })?;
+        if !output.status.success() {
+            return Err(AivaError::NetworkError {
+                operation: "add loopback forwarding rule".to_string(),
+                cause: String::from_utf8_lossy(&output.stderr).to_string(),
+            });
+        }
+    }
+
+    std::fs::write(route_localnet_path(BRIDGE_NAME), "1").map_err(|e| AivaError::NetworkError {
+        operation: "enable route_localnet".to_string(),
+        cause: e.to_string(),
+    })
+}
+
+fn disable_loopback_forwarding() -> Result<()> {
+    debug!("No loopback port forwards left, disabling route_localnet");
+    std::fs::write(route_localnet_path(BRIDGE_NAME), "0").map_err(|e| AivaError::NetworkError {
+        operation: "disable route_localnet".to_string(),
+        cause: e.to_string(),
+    })?;
+    for args in loopback_rule_args("-D") {
+        let _ = Command::new("iptables").args(&args).output();
+    }
+    Ok(())
+}
+
 fn add_forward_rule(action: &str, subnet: &str) -> Result<()> {
     // Allow forwarding from subnet
     Command::new("iptables")
@@ -135,67 +259,94 @@ fn add_forward_rule(action: &str, subnet: &str) -> Result<()> {
     Ok(())
 }
 
-fn add_port_forward_rule(
+/// Build the nat-table rules forwarding `mapping` to the guest.
+///
+/// The DNAT is scoped to the mapping's host address with `-d`. PREROUTING only
+/// sees traffic arriving from other hosts, so an equivalent OUTPUT rule covers
+/// connections made from the host itself (including to 127.0.0.1).
+pub fn port_forward_rule_args(
+    action: &str,
     guest_ip: &str,
-    host_port: u16,
-    guest_port: u16,
-    protocol: &str,
-) -> Result<()> {
+    mapping: &PortMapping,
+) -> Result<Vec<Vec<String>>> {
+    let host_ip = match mapping.host_ip {
+        Some(IpAddr::V6(ip)) => {
+            return Err(AivaError::NetworkError {
+                operation: "add port forward rule".to_string(),
+                cause: format!("IPv6 host address {ip} is not supported by iptables forwarding"),
+            });
+        }
+        Some(IpAddr::V4(ip)) => Some(ip),
+        None => None,
+    };
+
+    let protocol = mapping.protocol.to_string().to_lowercase();
+    let chains: &[&str] = match host_ip {
+        Some(_) => &["PREROUTING", "OUTPUT"],
+        // Unscoped forwards keep the historical PREROUTING-only behaviour
+        None => &["PREROUTING"],
+    };
+
+    Ok(chains
+        .iter()
+        .map(|chain| {
+            let mut args = vec![
+                "-t".to_string(),
+                "nat".to_string(),
+                action.to_string(),
+                chain.to_string(),
+                "-p".to_string(),
+                protocol.clone(),
+            ];
+            if let Some(ip) = host_ip {
+                args.extend(["-d".to_string(), ip.to_string()]);
+            }
+            args.extend([
+                "--dport".to_string(),
+                mapping.host_port.to_string(),
+                "-j".to_string(),
+                "DNAT".to_string(),
+                "--to-destination".to_string(),
+                format!("{guest_ip}:{}", mapping.guest_port),
+            ]);
+            args
+        })
+        .collect())
+}
+
+fn add_port_forward_rule(guest_ip: &str, mapping: &PortMapping) -> Result<()> {
     debug!(
-        "Adding port forward: {}:{} -> {}:{}",
-        protocol, host_port, guest_ip, guest_port
+        "Adding port forward: {}:{}:{} -> {}:{}",
+        mapping.protocol,
+        mapping.bind_address(),
+        mapping.host_port,
+        guest_ip,
+        mapping.guest_port
     );
 
-    Command::new("iptables")
-        .args([
-            "-t",
-            "nat",
-            "-A",
-            "PREROUTING",
-            "-p",
-            protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{guest_ip}:{guest_port}"),
-        ])
-        .output()
-        .map_err(|e| AivaError::NetworkError {
-            operation: "add port forward rule".to_string(),
-            cause: e.to_string(),
-        })?;
+    for args in port_forward_rule_args("-A", guest_ip, mapping)? {
+        Command::new("iptables")
+            .args(&args)
+            .output()
+            .map_err(|e| AivaError::NetworkError {
+                operation: "add port forward rule".to_string(),
+                cause: e.to_string(),
+            })?;
+    }
 
     Ok(())
 }
 
-fn remove_port_forward_rule(
-    guest_ip: &str,
-    host_port: u16,
-    guest_port: u16,
-    protocol: &str,
-) -> Result<()> {
-    Command::new("iptables")
-        .args([
-            "-t",
-            "nat",
-            "-D",
-            "PREROUTING",
-            "-p",
-            protocol,
-            "--dport",
-            &host_port.to_string(),
-            "-j",
-            "DNAT",
-            "--to-destination",
-            &format!("{guest_ip}:{guest_port}"),
-        ])
-        .output()
-        .map_err(|e| AivaError::NetworkError {
-            operation: "remove port forward rule".to_string(),
-            cause: e.to_string(),
-        })?;
+fn remove_port_forward_rule(guest_ip: &str, mapping: &PortMapping) -> Result<()> {
+    for args in port_forward_rule_args("-D", guest_ip, mapping)? {
+        Command::new("iptables")
+            .args(&args)
+            .output()
+            .map_err(|e| AivaError::NetworkError {
+                operation: "remove port forward rule".to_string(),
+                cause: e.to_string(),
+            })?;
+    }
 
     Ok(())
 }
@@ -1,10 +1,22 @@
+mod blocklist;
 mod bridge;
+mod egress;
 mod iptables;
 mod tap;
 
+#[cfg(test)]
+mod tests;
+
+pub use blocklist::{
+    IP_BLOCK_COMMENT_PREFIX, add_ip_block_rules, ip_block_rule_args, remove_ip_block_rules,
+};
 pub use bridge::{configure_bridge, create_bridge, delete_bridge};
-pub use iptables::{cleanup_nat_rules, setup_nat_rules};
-pub use tap::{configure_tap_device, create_tap_device, delete_tap_device};
+pub use egress::{
+    EGRESS_LOG_BURST, EGRESS_LOG_LIMIT, EGRESS_LOG_PREFIX, EgressRecord, add_egress_log_rule,
+    egress_log_rule_args, parse_egress_log_line, remove_egress_log_rule,
+};
+pub use iptables::{cleanup_nat_rules, port_forward_rule_args, setup_nat_rules};
+pub use tap::{configure_tap_device, create_tap_device, delete_tap_device, tap_device_name};
 
 use aiva_core::{NetworkConfig, NetworkInfo, Result, VMInstance};
 
@@ -17,6 +29,9 @@ pub async fn setup_network(instance: &VMInstance) -> Result<NetworkInfo> {
 
     // 3. Set up iptables rules
     setup_nat_rules(&instance.config.network)?;
+    if instance.config.network.audit_egress {
+        add_egress_log_rule(&tap_device, &instance.config.network.guest_ip)?;
+    }
 
     // 4. Configure DHCP (if enabled)
     if instance.config.network.dhcp_enabled {
@@ -34,6 +49,9 @@ pub async fn cleanup_network(instance: &VMInstance) -> Result<()> {
     if let Some(tap_device) = &instance.runtime.tap_device {
         // Clean up iptables rules
         cleanup_nat_rules(&instance.config.network)?;
+        if instance.config.network.audit_egress {
+            remove_egress_log_rule(tap_device, &instance.config.network.guest_ip)?;
+        }
 
         // Delete TAP device
         delete_tap_device(tap_device)?;
@@ -2,8 +2,13 @@ use aiva_core::{AivaError, Result};
 use std::process::Command;
 use tracing::{debug, info};
 
+/// Name of the TAP device created for the VM `name`
+pub fn tap_device_name(name: &str) -> String {
+    format!("aiva-tap-{}", &name[..8.min(name.len())])
+}
+
 pub fn create_tap_device(name: &str) -> Result<String> {
-    let tap_name = format!("aiva-tap-{}", &name[..8.min(name.len())]);
+    let tap_name = tap_device_name(name);
 
     info!("Creating TAP device: {}", tap_name);
 
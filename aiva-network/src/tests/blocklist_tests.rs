@@ -0,0 +1,53 @@
+use crate::blocklist::ip_block_rules_in;
+use crate::ip_block_rule_args;
+use std::net::IpAddr;
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn test_allowed_addresses_come_before_the_drops() {
+    let rules = ip_block_rule_args(
+        "-I",
+        "aiva-tap-web",
+        &[ip("127.0.0.1"), ip("::1"), ip("172.16.0.1")],
+        &["10.0.0.0/8".to_string(), "fd00::/8".to_string()],
+    );
+
+    assert_eq!(rules.len(), 2);
+    assert_eq!(
+        rules[0],
+        [
+            "-I",
+            "FORWARD",
+            "-i",
+            "aiva-tap-web",
+            "-d",
+            "172.16.0.1",
+            "-m",
+            "comment",
+            "--comment",
+            "aiva-block:aiva-tap-web",
+            "-j",
+            "ACCEPT",
+        ]
+    );
+    assert_eq!(rules[1][5], "10.0.0.0/8");
+    assert_eq!(rules[1].last().unwrap(), "DROP");
+}
+
+#[test]
+fn test_only_the_rules_of_one_device_are_removed() {
+    let listing = "-P FORWARD ACCEPT\n\
+        -A FORWARD -d 172.16.0.1/32 -i aiva-tap-web -m comment --comment \"aiva-block:aiva-tap-web\" -j ACCEPT\n\
+        -A FORWARD -d 10.0.0.0/8 -i aiva-tap-web -m comment --comment aiva-block:aiva-tap-web -j DROP\n\
+        -A FORWARD -d 10.0.0.0/8 -i aiva-tap-db -m comment --comment aiva-block:aiva-tap-db -j DROP\n\
+        -A FORWARD -s 172.16.0.0/24 -j ACCEPT\n";
+
+    let rules = ip_block_rules_in(listing, "aiva-tap-web");
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0][..3], ["-D", "FORWARD", "-d"]);
+    assert!(rules[0].contains(&"aiva-block:aiva-tap-web".to_string()));
+    assert_eq!(rules[1].last().unwrap(), "DROP");
+}
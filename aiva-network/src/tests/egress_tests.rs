@@ -0,0 +1,107 @@
+use crate::{EGRESS_LOG_PREFIX, egress_log_rule_args, parse_egress_log_line};
+use aiva_core::LogLevel;
+use chrono::{TimeZone, Utc};
+use std::net::IpAddr;
+
+const KERNEL_LINE: &str = "2026-10-16T09:41:07.250113+00:00 host kernel: aiva-egress: \
+    IN=aiva-tap-web OUT=eth0 MAC=02:fc:00:00:00:01:06:00:ac:10:00:02:08:00 \
+    SRC=172.16.0.2 DST=93.184.216.34 LEN=60 TOS=0x00 PREC=0x00 TTL=63 ID=4242 DF \
+    PROTO=TCP SPT=40312 DPT=443 WINDOW=64240 RES=0x00 SYN URGP=0";
+
+#[test]
+fn test_log_rule_is_inserted_rate_limited_for_new_connections() {
+    let rule = egress_log_rule_args("-A", "aiva-tap-web", "172.16.0.2");
+    assert_eq!(
+        rule,
+        [
+            "-I",
+            "FORWARD",
+            "-i",
+            "aiva-tap-web",
+            "-s",
+            "172.16.0.2",
+            "-m",
+            "conntrack",
+            "--ctstate",
+            "NEW",
+            "-m",
+            "limit",
+            "--limit",
+            "10/second",
+            "--limit-burst",
+            "20",
+            "-j",
+            "LOG",
+            "--log-prefix",
+            EGRESS_LOG_PREFIX,
+        ]
+    );
+
+    // Deleting matches the same rule specification
+    let delete = egress_log_rule_args("-D", "aiva-tap-web", "172.16.0.2");
+    assert_eq!(delete[0], "-D");
+    assert_eq!(delete[1..], rule[1..]);
+}
+
+#[test]
+fn test_kernel_log_line_parses_into_record() {
+    let record = parse_egress_log_line(KERNEL_LINE).unwrap();
+
+    assert_eq!(
+        record.timestamp,
+        Utc.with_ymd_and_hms(2026, 10, 16, 9, 41, 7).unwrap()
+            + chrono::Duration::microseconds(250_113)
+    );
+    assert_eq!(record.interface, "aiva-tap-web");
+    assert_eq!(record.src_ip, "172.16.0.2".parse::<IpAddr>().unwrap());
+    assert_eq!(record.dst_ip, "93.184.216.34".parse::<IpAddr>().unwrap());
+    assert_eq!(record.dst_port, Some(443));
+    assert_eq!(record.protocol, "tcp");
+    assert_eq!(record.destination(), "93.184.216.34:443");
+}
+
+#[test]
+fn test_nflog_entry_becomes_log_record_tagged_with_vm() {
+    // ulogd LOGEMU output for an NFLOG rule, without a parsable timestamp
+    let line = "Oct 16 09:41:07 host aiva-egress: IN=aiva-tap-web OUT=eth0 \
+        MAC=02:fc:00:00:00:01:06:00:ac:10:00:02:08:00 SRC=172.16.0.2 DST=1.1.1.1 \
+        LEN=64 TOS=00 PREC=0x00 TTL=63 ID=7 PROTO=UDP SPT=51000 DPT=53 LEN=44 MARK=0";
+    let before = Utc::now();
+    let record = parse_egress_log_line(line).unwrap();
+    assert!(record.timestamp >= before);
+
+    let entry = record.to_log_entry("vm-1");
+    assert_eq!(entry.vm_id.as_deref(), Some("vm-1"));
+    assert!(matches!(entry.level, LogLevel::Info));
+    assert_eq!(entry.message, "egress udp 1.1.1.1:53");
+    assert_eq!(entry.timestamp, record.timestamp);
+    assert_eq!(entry.metadata["source"], "egress");
+    assert_eq!(entry.metadata["dst_ip"], "1.1.1.1");
+    assert_eq!(entry.metadata["dst_port"], "53");
+    assert_eq!(entry.metadata["protocol"], "udp");
+}
+
+#[test]
+fn test_portless_protocols_keep_the_address_only() {
+    let line = "aiva-egress: IN=aiva-tap-web OUT=eth0 SRC=172.16.0.2 DST=8.8.8.8 \
+        LEN=84 PROTO=ICMP TYPE=8 CODE=0 ID=1 SEQ=1";
+    let record = parse_egress_log_line(line).unwrap();
+    assert_eq!(record.dst_port, None);
+    assert_eq!(record.destination(), "8.8.8.8");
+    assert!(
+        !record
+            .to_log_entry("vm-1")
+            .metadata
+            .contains_key("dst_port")
+    );
+}
+
+#[test]
+fn test_unrelated_and_incomplete_lines_are_skipped() {
+    assert!(parse_egress_log_line("kernel: IN=eth0 SRC=1.2.3.4 DST=5.6.7.8 PROTO=TCP").is_none());
+    assert!(parse_egress_log_line("aiva-egress: IN= OUT=eth0 SRC=172.16.0.2 PROTO=TCP").is_none());
+    assert!(
+        parse_egress_log_line("aiva-egress: IN=aiva-tap-web SRC=172.16.0.2 DST=bogus PROTO=TCP")
+            .is_none()
+    );
+}
@@ -0,0 +1,155 @@
+use crate::iptables::{has_loopback_forwards, loopback_rule_args, route_localnet_path};
+use crate::port_forward_rule_args;
+use aiva_core::{PortMapping, Protocol};
+use std::net::IpAddr;
+
+#[test]
+fn test_default_mapping_binds_loopback() {
+    let mapping = PortMapping::new(3000, 3000, Protocol::Tcp);
+    let rules = port_forward_rule_args("-A", "172.16.0.2", &mapping).unwrap();
+
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0][3], "PREROUTING");
+    assert_eq!(rules[1][3], "OUTPUT");
+    for rule in &rules {
+        let d = rule.iter().position(|arg| arg == "-d").unwrap();
+        assert_eq!(rule[d + 1], "127.0.0.1");
+        assert_eq!(rule.last().unwrap(), "172.16.0.2:3000");
+    }
+}
+
+#[test]
+fn test_rule_binds_to_configured_address() {
+    let mut mapping = PortMapping::new(8080, 80, Protocol::Udp);
+    mapping.host_ip = Some("192.168.1.10".parse::<IpAddr>().unwrap());
+
+    let rules = port_forward_rule_args("-D", "172.16.0.2", &mapping).unwrap();
+    assert_eq!(
+        rules[0],
+        [
+            "-t",
+            "nat",
+            "-D",
+            "PREROUTING",
+            "-p",
+            "udp",
+            "-d",
+            "192.168.1.10",
+            "--dport",
+            "8080",
+            "-j",
+            "DNAT",
+            "--to-destination",
+            "172.16.0.2:80",
+        ]
+    );
+}
+
+#[test]
+fn test_unscoped_mapping_listens_on_all_interfaces() {
+    let mut mapping = PortMapping::new(8080, 80, Protocol::Tcp);
+    mapping.host_ip = None;
+
+    let rules = port_forward_rule_args("-A", "172.16.0.2", &mapping).unwrap();
+    assert_eq!(rules.len(), 1);
+    assert!(!rules[0].contains(&"-d".to_string()));
+}
+
+#[test]
+fn test_ipv6_host_address_is_rejected() {
+    let mut mapping = PortMapping::new(8080, 80, Protocol::Tcp);
+    mapping.host_ip = Some("::1".parse::<IpAddr>().unwrap());
+
+    assert!(port_forward_rule_args("-A", "172.16.0.2", &mapping).is_err());
+}
+
+#[test]
+fn test_missing_host_ip_deserializes_to_loopback() {
+    let mapping: PortMapping =
+        serde_json::from_str(r#"{"host_port":1,"guest_port":2,"protocol":"Tcp"}"#).unwrap();
+    assert_eq!(mapping.host_ip, Some("127.0.0.1".parse().unwrap()));
+}
+
+#[test]
+fn test_loopback_forwarding_is_scoped_to_the_bridge() {
+    assert_eq!(
+        route_localnet_path("aiva-br0").to_str(),
+        Some("/proc/sys/net/ipv4/conf/aiva-br0/route_localnet")
+    );
+
+    let rules = loopback_rule_args("-I");
+    let guard = &rules[0];
+    assert_eq!(guard[..4], ["-I", "INPUT", "-i", "aiva-br0"]);
+    assert!(guard.contains(&"127.0.0.0/8".to_string()));
+    assert_eq!(guard.last().unwrap(), "DROP");
+}
+
+#[test]
+fn test_loopback_mapping_rule_set() {
+    let mapping = PortMapping::new(3000, 8000, Protocol::Tcp);
+    let mut rules = port_forward_rule_args("-A", "172.16.0.2", &mapping).unwrap();
+    rules.extend(loopback_rule_args("-I"));
+
+    let dnat = |chain: &'static str| {
+        vec![
+            "-t",
+            "nat",
+            "-A",
+            chain,
+            "-p",
+            "tcp",
+            "-d",
+            "127.0.0.1",
+            "--dport",
+            "3000",
+            "-j",
+            "DNAT",
+            "--to-destination",
+            "172.16.0.2:8000",
+        ]
+    };
+    let expected = vec![
+        dnat("PREROUTING"),
+        dnat("OUTPUT"),
+        vec![
+            "-I",
+            "INPUT",
+            "-i",
+            "aiva-br0",
+            "-d",
+            "127.0.0.0/8",
+            "-m",
+            "conntrack",
+            "!",
+            "--ctstate",
+            "RELATED,ESTABLISHED",
+            "-j",
+            "DROP",
+        ],
+        vec![
+            "-t",
+            "nat",
+            "-I",
+            "POSTROUTING",
+            "-s",
+            "127.0.0.0/8",
+            "-o",
+            "aiva-br0",
+            "-j",
+            "MASQUERADE",
+        ],
+    ];
+    assert_eq!(rules, expected);
+}
+
+#[test]
+fn test_remaining_loopback_forwards_are_detected() {
+    let scoped = "-P OUTPUT ACCEPT\n\
+        -A OUTPUT -d 192.168.1.10/32 -p tcp -m tcp --dport 8080 -j DNAT --to-destination 172.16.0.2:80\n";
+    assert!(!has_loopback_forwards(scoped));
+
+    let loopback = format!(
+        "{scoped}-A OUTPUT -d 127.0.0.1/32 -p tcp -m tcp --dport 3000 -j DNAT --to-destination 172.16.0.3:3000\n"
+    );
+    assert!(has_loopback_forwards(&loopback));
+}
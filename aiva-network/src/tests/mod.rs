@@ -0,0 +1,6 @@
+#[cfg(test)]
+mod blocklist_tests;
+#[cfg(test)]
+mod egress_tests;
+#[cfg(test)]
+mod iptables_tests;
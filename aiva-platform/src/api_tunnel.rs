@@ -0,0 +1,181 @@
+//! Host-side access to a Firecracker API socket inside Lima or WSL.
+//!
+//! On macOS and Windows Firecracker runs inside a Linux host, so its API
+//! socket is not reachable from aiva directly. For Lima a tunnel process
+//! forwards it to a socket of this user with SSH's Unix socket forwarding
+//! (`-L local.sock:remote.sock`). WSL2 cannot share a Unix socket with
+//! Windows, and a loopback port would be open to every local user, so each
+//! connection runs socat in the distro instead, bridging its stdio to the
+//! socket. [`FirecrackerApiClient`] then talks to either exactly as it talks
+//! to a local socket on Linux.
+
+use crate::firecracker::{ApiEndpoint, FirecrackerApiClient};
+use aiva_core::{AivaError, Result};
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::process::{Child, Command};
+use tracing::debug;
+
+/// How long the tunnel and the VMM behind it get to answer
+const TUNNEL_READY_TIMEOUT: Duration = Duration::from_secs(10);
+const TUNNEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// `ssh` arguments forwarding `local` to the socket `remote` in the Lima
+/// instance `lima_instance`
+pub(crate) fn lima_tunnel_args(
+    ssh_config: &Path,
+    lima_instance: &str,
+    local: &Path,
+    remote: &Path,
+) -> Vec<String> {
+    vec![
+        "-F".to_string(),
+        ssh_config.display().to_string(),
+        "-o".to_string(),
+        "LogLevel=ERROR".to_string(),
+        "-o".to_string(),
+        "ExitOnForwardFailure=yes".to_string(),
+        // Replace a socket left behind by an earlier tunnel
+        "-o".to_string(),
+        "StreamLocalBindUnlink=yes".to_string(),
+        "-N".to_string(),
+        "-L".to_string(),
+        format!("{}:{}", local.display(), remote.display()),
+        format!("lima-{lima_instance}"),
+    ]
+}
+
+/// `wsl` arguments connecting stdio to the socket `remote` in the distro
+/// `distro`
+pub(crate) fn wsl_tunnel_args(distro: &str, remote: &Path) -> Vec<String> {
+    vec![
+        "-d".to_string(),
+        distro.to_string(),
+        "sudo".to_string(),
+        "socat".to_string(),
+        "STDIO".to_string(),
+        format!("UNIX-CONNECT:{}", remote.display()),
+    ]
+}
+
+/// A reachable Firecracker API, with the tunnel process forwarding it if it
+/// needs one; dropping it closes the tunnel
+pub struct ApiTunnel {
+    child: Option<Child>,
+    endpoint: ApiEndpoint,
+}
+
+impl ApiTunnel {
+    /// Tunnel to the Firecracker of VM `vm_name` in the Lima instance
+    /// `lima_instance`
+    pub(crate) async fn lima(
+        ssh_config: &Path,
+        lima_instance: &str,
+        vm_name: &str,
+        remote: &Path,
+    ) -> Result<Self> {
+        // Short, since macOS limits socket paths to 104 bytes
+        let local = std::env::temp_dir().join(format!("aiva-fc-{vm_name}.sock"));
+        Self::open(
+            "ssh",
+            &lima_tunnel_args(ssh_config, lima_instance, &local, remote),
+            ApiEndpoint::Unix(local),
+        )
+        .await
+    }
+
+    /// Tunnel to a Firecracker in the WSL distro `distro`
+    pub(crate) async fn wsl(distro: &str, remote: &Path) -> Result<Self> {
+        let mut tunnel = Self {
+            child: None,
+            endpoint: ApiEndpoint::Pipe {
+                program: "wsl".to_string(),
+                args: wsl_tunnel_args(distro, remote),
+            },
+        };
+        tunnel.wait_ready().await?;
+        Ok(tunnel)
+    }
+
+    /// Run `program` and wait until the VMM answers through `endpoint`
+    pub async fn open(program: &str, args: &[String], endpoint: ApiEndpoint) -> Result<Self> {
+        debug!("Opening Firecracker API tunnel: {} {:?}", program, args);
+
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| tunnel_error(format!("Failed to start {program}: {e}")))?;
+
+        let mut tunnel = Self {
+            child: Some(child),
+            endpoint,
+        };
+        tunnel.wait_ready().await?;
+        Ok(tunnel)
+    }
+
+    pub fn endpoint(&self) -> &ApiEndpoint {
+        &self.endpoint
+    }
+
+    pub fn client(&self) -> Result<FirecrackerApiClient> {
+        FirecrackerApiClient::with_endpoint(self.endpoint.clone())
+    }
+
+    async fn wait_ready(&mut self) -> Result<()> {
+        let client = self.client()?;
+        let deadline = tokio::time::Instant::now() + TUNNEL_READY_TIMEOUT;
+
+        loop {
+            if let Some(child) = self.child.as_mut()
+                && let Some(status) = child.try_wait()?
+            {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    let _ = pipe.read_to_string(&mut stderr).await;
+                }
+                return Err(tunnel_error(format!(
+                    "Tunnel exited with {status}: {}",
+                    stderr.trim()
+                )));
+            }
+
+            if client.ping().await.is_ok() {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(tunnel_error(format!(
+                    "Firecracker API not reachable through {:?} after {}s",
+                    self.endpoint,
+                    TUNNEL_READY_TIMEOUT.as_secs()
+                )));
+            }
+            tokio::time::sleep(TUNNEL_POLL_INTERVAL).await;
+        }
+    }
+}
+
+impl Drop for ApiTunnel {
+    fn drop(&mut self) {
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.start_kill();
+        }
+        if let ApiEndpoint::Unix(path) = &self.endpoint {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn tunnel_error(message: String) -> AivaError {
+    AivaError::PlatformError {
+        platform: "firecracker".to_string(),
+        message,
+        recoverable: true,
+    }
+}
@@ -0,0 +1,246 @@
+//! Architecture checks of the Firecracker binary and the guest kernel.
+//!
+//! Firecracker only boots kernels built for the host's architecture, and a
+//! mismatched binary does not run at all. Both would otherwise surface as an
+//! opaque failure at boot, so [`check_architecture`] reads the file header
+//! up front: the ELF machine type for the binary and `vmlinux`, or the arm64
+//! `Image` magic for the uncompressed kernels Firecracker boots on aarch64.
+
+use aiva_core::{AivaError, Result};
+use std::fmt;
+use std::io::Read;
+use std::path::Path;
+
+/// `e_machine` of x86_64 ELF files
+const EM_X86_64: u16 = 62;
+/// `e_machine` of aarch64 ELF files
+const EM_AARCH64: u16 = 183;
+
+/// Offset of the `ARM\x64` magic in an arm64 `Image` header
+const ARM64_IMAGE_MAGIC_OFFSET: usize = 56;
+const ARM64_IMAGE_MAGIC: &[u8; 4] = b"ARM\x64";
+
+/// Bytes read from a file to identify it
+const HEADER_LEN: usize = 64;
+
+/// CPU architectures Firecracker supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Architecture {
+    X86_64,
+    Aarch64,
+}
+
+impl Architecture {
+    /// Architecture this binary was built for, `None` where Firecracker
+    /// does not run
+    pub fn host() -> Option<Self> {
+        Self::from_name(std::env::consts::ARCH)
+    }
+
+    /// Parse `uname -m` style names
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "x86_64" | "amd64" => Some(Self::X86_64),
+            "aarch64" | "arm64" => Some(Self::Aarch64),
+            _ => None,
+        }
+    }
+
+    pub fn from_elf_machine(machine: u16) -> Option<Self> {
+        match machine {
+            EM_X86_64 => Some(Self::X86_64),
+            EM_AARCH64 => Some(Self::Aarch64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::X86_64 => "x86_64",
+            Self::Aarch64 => "aarch64",
+        }
+    }
+}
+
+impl fmt::Display for Architecture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Identify the architecture from the first bytes of an ELF file or arm64
+/// kernel `Image`
+pub fn header_architecture(header: &[u8]) -> std::result::Result<Architecture, String> {
+    if header.starts_with(b"\x7fELF") {
+        if header.len() < 20 {
+            return Err("truncated ELF header".to_string());
+        }
+        let bytes = [header[18], header[19]];
+        // EI_DATA: 1 is little endian, 2 big endian
+        let machine = match header[5] {
+            1 => u16::from_le_bytes(bytes),
+            2 => u16::from_be_bytes(bytes),
+            other => return Err(format!("unknown ELF byte order {other}")),
+        };
+        return Architecture::from_elf_machine(machine)
+            .ok_or_else(|| format!("unsupported ELF machine type {machine}"));
+    }
+
+    if header.get(ARM64_IMAGE_MAGIC_OFFSET..ARM64_IMAGE_MAGIC_OFFSET + 4)
+        == Some(ARM64_IMAGE_MAGIC.as_slice())
+    {
+        return Ok(Architecture::Aarch64);
+    }
+
+    Err("neither an ELF file nor an arm64 kernel Image".to_string())
+}
+
+/// Architecture of the binary or kernel image at `path`
+pub fn file_architecture(path: &Path) -> Result<Architecture> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    std::fs::File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    header_architecture(&header).map_err(|reason| AivaError::PlatformError {
+        platform: "firecracker".to_string(),
+        message: format!(
+            "Cannot tell the architecture of {}: {reason}",
+            path.display()
+        ),
+        recoverable: false,
+    })
+}
+
+/// Fail unless the `what` at `path` (the Firecracker binary, the kernel
+/// image) is built for `expected`
+pub fn check_architecture(what: &str, path: &Path, expected: Architecture) -> Result<()> {
+    let actual = file_architecture(path)?;
+    if actual != expected {
+        return Err(AivaError::PlatformError {
+            platform: "firecracker".to_string(),
+            message: format!(
+                "{what} {} is built for {actual} but the host is {expected}; install the {expected} build",
+                path.display()
+            ),
+            recoverable: true,
+        });
+    }
+    Ok(())
+}
+
+/// Makers of x86_64 CPUs that Firecracker's CPU templates target
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuVendor {
+    Intel,
+    Amd,
+}
+
+impl CpuVendor {
+    /// Vendor of this host's CPUs, `None` when unknown or not x86_64
+    pub fn host() -> Option<Self> {
+        let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").ok()?;
+        cpuinfo
+            .lines()
+            .find_map(|line| line.strip_prefix("vendor_id"))
+            .and_then(|rest| Self::from_vendor_id(rest.trim_start_matches([' ', '\t', ':'])))
+    }
+
+    /// Parse the `vendor_id` of `/proc/cpuinfo`
+    pub fn from_vendor_id(vendor_id: &str) -> Option<Self> {
+        match vendor_id.trim() {
+            "GenuineIntel" => Some(Self::Intel),
+            "AuthenticAMD" => Some(Self::Amd),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CpuVendor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Intel => "Intel",
+            Self::Amd => "AMD",
+        })
+    }
+}
+
+/// Static CPU templates Firecracker accepts, with the architecture and
+/// CPU vendor each one masks CPUID (or the arm64 ID registers) for
+const CPU_TEMPLATES: &[(&str, Architecture, Option<CpuVendor>)] = &[
+    ("C3", Architecture::X86_64, Some(CpuVendor::Intel)),
+    ("T2", Architecture::X86_64, Some(CpuVendor::Intel)),
+    ("T2S", Architecture::X86_64, Some(CpuVendor::Intel)),
+    ("T2CL", Architecture::X86_64, Some(CpuVendor::Intel)),
+    ("T2A", Architecture::X86_64, Some(CpuVendor::Amd)),
+    ("V1N1", Architecture::Aarch64, None),
+];
+
+/// CPU templates Firecracker accepts on `arch`
+pub fn cpu_templates(arch: Architecture) -> Vec<&'static str> {
+    CPU_TEMPLATES
+        .iter()
+        .filter(|(_, template_arch, _)| *template_arch == arch)
+        .map(|(name, _, _)| *name)
+        .collect()
+}
+
+/// Fail unless Firecracker accepts `cpu_template` and `smt` for a VM with
+/// `vcpus` on a host with `arch` and, when known, `vendor` CPUs
+pub fn check_machine_config(
+    arch: Architecture,
+    vendor: Option<CpuVendor>,
+    cpu_template: Option<&str>,
+    smt: bool,
+    vcpus: u32,
+) -> Result<()> {
+    if let Some(template) = cpu_template {
+        let Some((name, template_arch, template_vendor)) = CPU_TEMPLATES
+            .iter()
+            .find(|(name, _, _)| name.eq_ignore_ascii_case(template))
+        else {
+            return Err(AivaError::ConfigError(format!(
+                "Unknown CPU template {template}; {arch} hosts support {}",
+                cpu_templates(arch).join(", ")
+            )));
+        };
+        if *template_arch != arch {
+            return Err(AivaError::ConfigError(format!(
+                "CPU template {name} is for {template_arch} hosts, this one is {arch}; use one of {}",
+                cpu_templates(arch).join(", ")
+            )));
+        }
+        if let (Some(expected), Some(vendor)) = (template_vendor, vendor)
+            && *expected != vendor
+        {
+            return Err(AivaError::ConfigError(format!(
+                "CPU template {name} is for {expected} CPUs, this host has {vendor}"
+            )));
+        }
+    }
+
+    if smt {
+        if arch == Architecture::Aarch64 {
+            return Err(AivaError::ConfigError(
+                "Firecracker does not support SMT on aarch64".to_string(),
+            ));
+        }
+        if vcpus > 1 && !vcpus.is_multiple_of(2) {
+            return Err(AivaError::ConfigError(format!(
+                "With SMT the vCPU count must be 1 or even, got {vcpus}"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// [`check_machine_config`] for this host
+pub fn check_host_machine_config(cpu_template: Option<&str>, smt: bool, vcpus: u32) -> Result<()> {
+    let arch = Architecture::host().ok_or_else(|| {
+        AivaError::ConfigError(format!(
+            "Firecracker does not support the {} architecture",
+            std::env::consts::ARCH
+        ))
+    })?;
+    check_machine_config(arch, CpuVendor::host(), cpu_template, smt, vcpus)
+}
@@ -1,33 +1,98 @@
-use aiva_core::{AivaError, Result};
+use aiva_core::{AivaError, ExecTransport, Result, VMInstance};
+use async_trait::async_trait;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-pub use crate::vsock_executor::{ConnectionType, VsockExecutor};
+pub use crate::vsock_executor::{ConnectionType, VSOCK_COMMAND_PORT, VsockExecutor};
 
-/// Manages command executors for VMs
+/// How long a connection may go unused before the pool checks it again
+pub const DEFAULT_IDLE_TTL: Duration = Duration::from_secs(600);
+
+/// A registered VM: its executor, how it was reached and when it last
+/// answered
+struct PooledConnection {
+    executor: Arc<VsockExecutor>,
+    connection_type: ConnectionType,
+    last_used: Mutex<Instant>,
+}
+
+impl PooledConnection {
+    fn new(vm_name: &str, connection_type: ConnectionType) -> Self {
+        Self {
+            executor: Arc::new(VsockExecutor::new(
+                vm_name.to_string(),
+                connection_type.clone(),
+            )),
+            connection_type,
+            last_used: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_used.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    fn idle_for(&self) -> Duration {
+        self.last_used
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed()
+    }
+}
+
+/// Connections of a [`CommandPool`] at one moment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Registered VMs
+    pub connections: usize,
+    pub vsock: usize,
+    pub network: usize,
+    pub ssh: usize,
+    /// Of the registered VMs, those unused for longer than the idle TTL
+    pub idle: usize,
+    /// Connections re-established after a failed command
+    pub reconnects: u64,
+}
+
+/// Manages command executors for VMs. A command failing on the network is
+/// retried once over a freshly checked connection, so a guest that
+/// rebooted or a dropped link does not fail commands until restart.
 pub struct CommandPool {
-    executors: Arc<RwLock<HashMap<String, Arc<VsockExecutor>>>>,
+    executors: Arc<RwLock<HashMap<String, Arc<PooledConnection>>>>,
+    idle_ttl: Duration,
+    reconnects: AtomicU64,
 }
 
 impl CommandPool {
     pub fn new() -> Self {
         Self {
             executors: Arc::new(RwLock::new(HashMap::new())),
+            idle_ttl: DEFAULT_IDLE_TTL,
+            reconnects: AtomicU64::new(0),
         }
     }
 
+    /// Treat connections unused for longer than `idle_ttl` as gone
+    pub fn with_idle_ttl(mut self, idle_ttl: Duration) -> Self {
+        self.idle_ttl = idle_ttl;
+        self
+    }
+
     /// Register a VM with its executor
     pub async fn register_vm(
         &self,
         vm_name: String,
         connection_type: ConnectionType,
     ) -> Result<()> {
-        let executor = Arc::new(VsockExecutor::new(vm_name.clone(), connection_type));
+        let connection = Arc::new(PooledConnection::new(&vm_name, connection_type));
 
         // Test connection before registering
-        if !executor.check_connection().await? {
+        if !connection.executor.check_connection().await? {
             return Err(AivaError::NetworkError {
                 operation: "register_vm".to_string(),
                 cause: format!("Failed to establish connection to VM {vm_name}"),
@@ -35,24 +100,82 @@ impl CommandPool {
         }
 
         let mut executors = self.executors.write().await;
-        executors.insert(vm_name.clone(), executor);
+        executors.insert(vm_name.clone(), connection);
 
         info!("Registered VM {} in command pool", vm_name);
         Ok(())
     }
 
-    /// Execute a command on a specific VM
+    /// Execute a command on a specific VM. A network failure re-establishes
+    /// the connection and runs the command once more.
     pub async fn execute_command(&self, vm_name: &str, command: &str) -> Result<String> {
-        let executors = self.executors.read().await;
-
-        let executor = executors.get(vm_name).ok_or_else(|| AivaError::VMError {
-            vm_name: vm_name.to_string(),
-            state: aiva_core::VMState::Stopped,
-            message: "VM not registered in command pool".to_string(),
-        })?;
+        let connection = self.connection(vm_name).await?;
 
         debug!("Executing command on VM {}: {}", vm_name, command);
-        executor.execute_command(command).await
+        match connection.executor.execute_command(command).await {
+            Ok(output) => {
+                connection.touch();
+                Ok(output)
+            }
+            Err(e @ AivaError::NetworkError { .. }) => {
+                warn!("Command on VM {} failed, reconnecting: {}", vm_name, e);
+                let Some(connection) = self.reconnect(vm_name, &connection).await else {
+                    return Err(e);
+                };
+                let output = connection.executor.execute_command(command).await?;
+                connection.touch();
+                Ok(output)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Ping the guest agent of `vm_name`, refreshing its last use when it
+    /// answers
+    pub async fn health_check(&self, vm_name: &str) -> Result<bool> {
+        let connection = self.connection(vm_name).await?;
+        let healthy = connection.executor.check_connection().await?;
+        if healthy {
+            connection.touch();
+        }
+        Ok(healthy)
+    }
+
+    /// Drop the connections unused for longer than the idle TTL and return
+    /// their VMs. They are registered again on their next command.
+    pub async fn evict_idle(&self) -> Vec<String> {
+        let mut executors = self.executors.write().await;
+        let idle: Vec<String> = executors
+            .iter()
+            .filter(|(_, connection)| connection.idle_for() >= self.idle_ttl)
+            .map(|(vm_name, _)| vm_name.clone())
+            .collect();
+        for vm_name in &idle {
+            executors.remove(vm_name);
+            debug!("Evicted idle connection to VM {}", vm_name);
+        }
+        idle
+    }
+
+    /// Live connections by kind
+    pub async fn stats(&self) -> PoolStats {
+        let executors = self.executors.read().await;
+        let mut stats = PoolStats {
+            connections: executors.len(),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            ..PoolStats::default()
+        };
+        for connection in executors.values() {
+            match connection.connection_type {
+                ConnectionType::Vsock { .. } => stats.vsock += 1,
+                ConnectionType::Network { .. } => stats.network += 1,
+                ConnectionType::Ssh { .. } => stats.ssh += 1,
+            }
+            if connection.idle_for() >= self.idle_ttl {
+                stats.idle += 1;
+            }
+        }
+        stats
     }
 
     /// Remove a VM from the pool
@@ -64,8 +187,11 @@ impl CommandPool {
         Ok(())
     }
 
-    /// Check if a VM is registered
+    /// Check if a VM is registered. A connection idle beyond the TTL is
+    /// evicted and reported as not registered, so callers register the VM
+    /// again and thereby check the guest is still there.
     pub async fn is_registered(&self, vm_name: &str) -> bool {
+        self.evict_idle().await;
         let executors = self.executors.read().await;
         executors.contains_key(vm_name)
     }
@@ -75,6 +201,128 @@ impl CommandPool {
         let executors = self.executors.read().await;
         executors.keys().cloned().collect()
     }
+
+    async fn connection(&self, vm_name: &str) -> Result<Arc<PooledConnection>> {
+        let executors = self.executors.read().await;
+        executors
+            .get(vm_name)
+            .cloned()
+            .ok_or_else(|| AivaError::VMError {
+                vm_name: vm_name.to_string(),
+                state: aiva_core::VMState::Stopped,
+                message: "VM not registered in command pool".to_string(),
+            })
+    }
+
+    /// Replace the connection `failed` of `vm_name` by a new one over the
+    /// same transport. `None` if the guest does not answer on it either,
+    /// leaving the VM unregistered so the next command tries every
+    /// transport again.
+    async fn reconnect(
+        &self,
+        vm_name: &str,
+        failed: &PooledConnection,
+    ) -> Option<Arc<PooledConnection>> {
+        let connection = Arc::new(PooledConnection::new(
+            vm_name,
+            failed.connection_type.clone(),
+        ));
+        if !matches!(connection.executor.check_connection().await, Ok(true)) {
+            warn!("VM {} does not answer after reconnecting", vm_name);
+            self.executors.write().await.remove(vm_name);
+            return None;
+        }
+
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+        let mut executors = self.executors.write().await;
+        executors.insert(vm_name.to_string(), connection.clone());
+        info!("Reconnected to VM {}", vm_name);
+        Some(connection)
+    }
+}
+
+/// Somewhere VMs are registered for command execution. Lets the fallback
+/// order be exercised without connecting to a guest.
+#[async_trait]
+pub trait ExecutorRegistry: Send + Sync {
+    /// Register `vm_name`, failing if `connection_type` does not connect
+    async fn register_vm(&self, vm_name: String, connection_type: ConnectionType) -> Result<()>;
+
+    /// Register `vm_name` with the first of `candidates` that connects,
+    /// trying them in order, and return the transport that did
+    async fn register_in_order(
+        &self,
+        vm_name: &str,
+        candidates: Vec<(ExecTransport, ConnectionType)>,
+    ) -> Result<ExecTransport> {
+        let mut failures = Vec::new();
+        for (transport, connection_type) in candidates {
+            match self.register_vm(vm_name.to_string(), connection_type).await {
+                Ok(()) => {
+                    debug!("Registered VM {} over {}", vm_name, transport);
+                    return Ok(transport);
+                }
+                Err(e) => {
+                    warn!("Transport {} to VM {} failed: {}", transport, vm_name, e);
+                    failures.push(format!("{transport}: {e}"));
+                }
+            }
+        }
+
+        Err(AivaError::NetworkError {
+            operation: "register_vm".to_string(),
+            cause: if failures.is_empty() {
+                format!("No configured transport is available for VM {vm_name}")
+            } else {
+                format!(
+                    "No transport reached VM {vm_name} ({})",
+                    failures.join("; ")
+                )
+            },
+        })
+    }
+}
+
+#[async_trait]
+impl ExecutorRegistry for CommandPool {
+    async fn register_vm(&self, vm_name: String, connection_type: ConnectionType) -> Result<()> {
+        CommandPool::register_vm(self, vm_name, connection_type).await
+    }
+}
+
+/// Pool connection for `transport` to `instance`, `None` where it does not
+/// go through the pool (WSL) or cannot be used (no vsock socket `vsock_uds`
+/// on the platform or no CID assigned, no forwarded port for SSH)
+pub fn connection_for(
+    transport: ExecTransport,
+    instance: &VMInstance,
+    vsock_uds: Option<PathBuf>,
+) -> Option<ConnectionType> {
+    match transport {
+        // The CID assigned when the VM was created; older VMs have no vsock
+        ExecTransport::Vsock => instance
+            .runtime
+            .vsock_cid
+            .zip(vsock_uds)
+            .map(|(cid, uds_path)| ConnectionType::Vsock { cid, uds_path }),
+        ExecTransport::Network => Some(ConnectionType::Network {
+            host: instance.config.network.guest_ip.clone(),
+            port: VSOCK_COMMAND_PORT as u16,
+        }),
+        ExecTransport::Ssh => {
+            instance
+                .config
+                .network
+                .port_mappings
+                .first()
+                .map(|port| ConnectionType::Ssh {
+                    host: "localhost".to_string(),
+                    port: port.host_port,
+                    key_path: None,
+                })
+        }
+        ExecTransport::Wsl => None,
+    }
 }
 
 impl Default for CommandPool {
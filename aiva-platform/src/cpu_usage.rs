@@ -0,0 +1,181 @@
+//! Current CPU usage of a process from `/proc`.
+//!
+//! `/proc/<pid>/stat` only holds the CPU time a process used since it
+//! started, so usage over a window is the difference between two readings.
+//! As `top` does, it is measured against the time all CPUs spent in the same
+//! window (the `cpu` line of `/proc/stat`) and scaled by the CPU count: a
+//! process keeping one core busy reads 100%, one keeping two busy 200%.
+//!
+//! The last reading of every process is kept, so a caller polling metrics
+//! gets the usage since its previous call without waiting. Only the first
+//! call for a process, or one made before any time has passed, sleeps for
+//! a short window. Readings are dropped when the VM stops or the process is
+//! gone, and carry the process start time, so a PID the OS hands out again
+//! is never compared with its previous owner.
+
+use aiva_core::{AivaError, Result};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Window sampled when there is no earlier reading to compare with
+pub(crate) const CPU_SAMPLE_WINDOW: Duration = Duration::from_millis(200);
+
+/// Where readings come from; [`HostProc`] outside of tests
+pub(crate) trait ProcSource: Send + Sync {
+    /// Contents of `/proc/<pid>/stat`
+    fn process_stat(&self, pid: u32) -> std::io::Result<String>;
+    /// Contents of `/proc/stat`
+    fn system_stat(&self) -> std::io::Result<String>;
+}
+
+/// The real `/proc` of this host
+pub(crate) struct HostProc;
+
+impl ProcSource for HostProc {
+    fn process_stat(&self, pid: u32) -> std::io::Result<String> {
+        std::fs::read_to_string(format!("/proc/{pid}/stat"))
+    }
+
+    fn system_stat(&self) -> std::io::Result<String> {
+        std::fs::read_to_string("/proc/stat")
+    }
+}
+
+/// CPU time counters at one moment, in clock ticks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct CpuSample {
+    /// User and system time of the process
+    pub process_ticks: u64,
+    /// Time of all CPUs together, idle included
+    pub system_ticks: u64,
+    pub cpus: u32,
+    /// When the process started, in ticks after boot
+    pub start_time: u64,
+}
+
+/// `utime + stime` of a `/proc/<pid>/stat` line. The command name in
+/// parentheses may contain spaces, so fields are counted after it.
+pub(crate) fn parse_process_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // utime and stime are fields 14 and 15, the first after the name is 3
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// `starttime` of a `/proc/<pid>/stat` line
+pub(crate) fn parse_start_time(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    // Field 22
+    rest.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// Total ticks of the `cpu` line of `/proc/stat` and the number of CPUs
+/// listed under it
+pub(crate) fn parse_system_ticks(stat: &str) -> Option<(u64, u32)> {
+    let mut lines = stat.lines();
+    let total = lines
+        .next()?
+        .strip_prefix("cpu ")?
+        .split_whitespace()
+        .filter_map(|field| field.parse::<u64>().ok())
+        .sum();
+    let cpus = lines
+        .take_while(|line| line.starts_with("cpu"))
+        .filter(|line| line[3..].starts_with(|c: char| c.is_ascii_digit()))
+        .count();
+    Some((total, cpus.max(1) as u32))
+}
+
+/// Usage in percent of one CPU between two samples of the same process,
+/// `None` if no time passed between them or the process was replaced
+pub(crate) fn cpu_percent(earlier: CpuSample, later: CpuSample) -> Option<f64> {
+    if earlier.start_time != later.start_time {
+        return None;
+    }
+    let system = later.system_ticks.checked_sub(earlier.system_ticks)?;
+    let process = later.process_ticks.checked_sub(earlier.process_ticks)?;
+    if system == 0 {
+        return None;
+    }
+    Some(process as f64 / system as f64 * f64::from(later.cpus) * 100.0)
+}
+
+/// CPU usage of processes, remembering the last sample of each
+pub(crate) struct CpuUsageTracker {
+    source: Box<dyn ProcSource>,
+    window: Duration,
+    samples: Mutex<HashMap<u32, CpuSample>>,
+}
+
+impl CpuUsageTracker {
+    pub(crate) fn new(source: Box<dyn ProcSource>, window: Duration) -> Self {
+        Self {
+            source,
+            window,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Usage of `pid` since the previous call for it, or over the sample
+    /// window the first time
+    pub(crate) async fn cpu_usage(&self, pid: u32) -> Result<f64> {
+        let now = match self.sample(pid) {
+            Ok(sample) => sample,
+            Err(e) => {
+                self.forget(pid);
+                return Err(e);
+            }
+        };
+        let previous = self.remember(pid, now);
+        if let Some(usage) = previous.and_then(|previous| cpu_percent(previous, now)) {
+            return Ok(usage);
+        }
+
+        tokio::time::sleep(self.window).await;
+        let later = self.sample(pid)?;
+        self.remember(pid, later);
+        Ok(cpu_percent(now, later).unwrap_or(0.0))
+    }
+
+    fn sample(&self, pid: u32) -> Result<CpuSample> {
+        let stat = self.source.process_stat(pid)?;
+        let unreadable_stat = || unreadable(&format!("/proc/{pid}/stat"));
+        let process_ticks = parse_process_ticks(&stat).ok_or_else(unreadable_stat)?;
+        let start_time = parse_start_time(&stat).ok_or_else(unreadable_stat)?;
+        let (system_ticks, cpus) = parse_system_ticks(&self.source.system_stat()?)
+            .ok_or_else(|| unreadable("/proc/stat"))?;
+        Ok(CpuSample {
+            process_ticks,
+            system_ticks,
+            cpus,
+            start_time,
+        })
+    }
+
+    /// Store `sample` as the latest of `pid`, returning the one it replaces
+    fn remember(&self, pid: u32, sample: CpuSample) -> Option<CpuSample> {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(pid, sample)
+    }
+
+    /// Drop the sample of `pid`, whose VM stopped
+    pub(crate) fn forget(&self, pid: u32) {
+        self.samples
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&pid);
+    }
+}
+
+fn unreadable(path: &str) -> AivaError {
+    AivaError::PlatformError {
+        platform: "linux".to_string(),
+        message: format!("Cannot parse CPU times in {path}"),
+        recoverable: true,
+    }
+}
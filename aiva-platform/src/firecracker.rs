@@ -1,22 +1,177 @@
-use aiva_core::{AivaError, Result};
+use crate::firecracker_vm::FirecrackerVMConfig;
+use aiva_core::{AivaError, CacheStrategy, Result, SnapshotType};
 use http_body_util::BodyExt;
 use hyper::{Method, Request};
 use hyper_util::client::legacy::Client;
+use hyper_util::client::legacy::connect::{Connected, Connection};
 use hyper_util::rt::TokioIo;
 use serde::{Deserialize, Serialize};
+use std::io;
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::UnixStream;
+use tokio::process::{Child, ChildStdin, ChildStdout};
 use tracing::{debug, error};
 
-type UnixClient = Client<UnixConnector, String>;
+type ApiClient = Client<ApiConnector, String>;
+
+/// Where the Firecracker API is reached from this process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApiEndpoint {
+    /// The API socket itself, or a local socket forwarded to it
+    Unix(PathBuf),
+    /// A command whose stdin and stdout are connected to the API socket, run
+    /// once per connection. For hosts that cannot forward a socket to this
+    /// user alone, such as WSL.
+    Pipe { program: String, args: Vec<String> },
+}
+
+/// Balloon statistics from `GET /balloon/statistics`. Pages are 4 KiB; the
+/// memory figures are in bytes and only present when the guest reports them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalloonStats {
+    /// Pages the balloon was asked to hold
+    pub target_pages: u64,
+    /// Pages the balloon holds, i.e. taken from the guest
+    pub actual_pages: u64,
+    pub target_mib: u64,
+    pub actual_mib: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub free_memory: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub available_memory: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_memory: Option<u64>,
+}
+
+/// Firecracker's `cache_type` for a cache strategy
+pub(crate) fn firecracker_cache_type(strategy: CacheStrategy) -> &'static str {
+    match strategy {
+        CacheStrategy::Writeback => "Writeback",
+        CacheStrategy::Unsafe => "Unsafe",
+    }
+}
+
+/// Body of `PUT /machine-config`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct MachineConfig {
+    pub vcpu_count: u32,
+    pub mem_size_mib: u64,
+    /// Simultaneous multithreading, two vCPUs per core
+    pub smt: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_template: Option<String>,
+}
+
+impl MachineConfig {
+    /// Template names are sent the way Firecracker spells them, `T2`
+    pub(crate) fn new(
+        vcpu_count: u32,
+        mem_size_mib: u64,
+        cpu_template: Option<&str>,
+        smt: bool,
+    ) -> Self {
+        Self {
+            vcpu_count,
+            mem_size_mib,
+            smt,
+            cpu_template: cpu_template.map(str::to_ascii_uppercase),
+        }
+    }
+}
+
+/// Connection to either kind of [`ApiEndpoint`]
+enum ApiStream {
+    Unix(UnixStream),
+    Pipe(PipeStream),
+}
+
+/// The process behind an [`ApiEndpoint::Pipe`] connection, killed when the
+/// connection is dropped
+struct PipeStream {
+    _child: Child,
+    stdin: ChildStdin,
+    stdout: ChildStdout,
+}
+
+impl PipeStream {
+    fn spawn(program: &str, args: &[String]) -> io::Result<Self> {
+        let mut child = tokio::process::Command::new(program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(io::Error::other("pipe command has no stdio"));
+        };
+        Ok(Self {
+            _child: child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl AsyncRead for ApiStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ApiStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+            ApiStream::Pipe(pipe) => Pin::new(&mut pipe.stdout).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ApiStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ApiStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+            ApiStream::Pipe(pipe) => Pin::new(&mut pipe.stdin).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ApiStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+            ApiStream::Pipe(pipe) => Pin::new(&mut pipe.stdin).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ApiStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+            ApiStream::Pipe(pipe) => Pin::new(&mut pipe.stdin).poll_shutdown(cx),
+        }
+    }
+}
+
+impl Connection for ApiStream {
+    fn connected(&self) -> Connected {
+        match self {
+            ApiStream::Unix(stream) => stream.connected(),
+            ApiStream::Pipe(_) => Connected::new(),
+        }
+    }
+}
 
 #[derive(Clone)]
-struct UnixConnector {
-    socket_path: PathBuf,
+struct ApiConnector {
+    endpoint: ApiEndpoint,
 }
 
-impl tower::Service<hyper::Uri> for UnixConnector {
-    type Response = TokioIo<UnixStream>;
+impl tower::Service<hyper::Uri> for ApiConnector {
+    type Response = TokioIo<ApiStream>;
     type Error = Box<dyn std::error::Error + Send + Sync>;
     type Future = std::pin::Pin<
         Box<
@@ -33,34 +188,43 @@ impl tower::Service<hyper::Uri> for UnixConnector {
     }
 
     fn call(&mut self, _uri: hyper::Uri) -> Self::Future {
-        let socket_path = self.socket_path.clone();
+        let endpoint = self.endpoint.clone();
         Box::pin(async move {
-            let stream = UnixStream::connect(&socket_path)
-                .await
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)?;
+            let stream = match endpoint {
+                ApiEndpoint::Unix(path) => ApiStream::Unix(UnixStream::connect(&path).await?),
+                ApiEndpoint::Pipe { program, args } => {
+                    ApiStream::Pipe(PipeStream::spawn(&program, &args)?)
+                }
+            };
             Ok(TokioIo::new(stream))
         })
     }
 }
 
-#[allow(dead_code)]
 pub struct FirecrackerApiClient {
-    socket_path: PathBuf,
-    client: UnixClient,
+    endpoint: ApiEndpoint,
+    client: ApiClient,
 }
 
 impl FirecrackerApiClient {
     pub fn new(socket_path: PathBuf) -> Result<Self> {
-        let connector = UnixConnector {
-            socket_path: socket_path.clone(),
+        Self::with_endpoint(ApiEndpoint::Unix(socket_path))
+    }
+
+    /// Client for an API socket forwarded out of Lima or WSL, see
+    /// [`ApiTunnel`](crate::api_tunnel::ApiTunnel)
+    pub fn with_endpoint(endpoint: ApiEndpoint) -> Result<Self> {
+        let connector = ApiConnector {
+            endpoint: endpoint.clone(),
         };
 
         let client = Client::builder(hyper_util::rt::TokioExecutor::new()).build(connector);
 
-        Ok(Self {
-            socket_path,
-            client,
-        })
+        Ok(Self { endpoint, client })
+    }
+
+    pub fn endpoint(&self) -> &ApiEndpoint {
+        &self.endpoint
     }
 
     async fn make_request<T: Serialize, R: for<'de> Deserialize<'de>>(
@@ -176,23 +340,58 @@ impl FirecrackerApiClient {
         Ok(Some(result))
     }
 
-    pub async fn configure_machine(&self, vcpu_count: u32, mem_size_mib: u64) -> Result<()> {
-        #[derive(Serialize)]
-        struct MachineConfig {
-            vcpu_count: u32,
-            mem_size_mib: u64,
-            ht_enabled: bool,
-        }
+    /// Succeeds once the VMM answers API requests
+    pub async fn ping(&self) -> Result<()> {
+        self.make_request::<(), serde_json::Value>("GET", "/", None)
+            .await?;
+        Ok(())
+    }
 
-        let config = MachineConfig {
-            vcpu_count,
-            mem_size_mib,
-            ht_enabled: false,
-        };
+    /// Configure machine, kernel, root drive and network of a VM that runs
+    /// in a Lima or WSL host, then start it
+    pub async fn boot(&self, config: &FirecrackerVMConfig) -> Result<()> {
+        self.configure_machine(
+            config.vcpu_count,
+            config.mem_size_mib,
+            config.cpu_template.as_deref(),
+            false,
+        )
+        .await?;
+        self.configure_boot_source(&config.kernel_path, &config.boot_args())
+            .await?;
+        self.configure_drive(
+            "rootfs",
+            &config.rootfs_path,
+            false,
+            firecracker_cache_type(config.cache_strategy),
+        )
+        .await?;
+        self.configure_network(
+            &config.network_interface,
+            &config.tap_device,
+            Some(&config.guest_ip),
+        )
+        .await?;
+        self.start_instance().await
+    }
+
+    /// Set the vCPUs and memory of the VM. `cpu_template` masks the CPU
+    /// features the guest sees so its snapshots restore on other hosts;
+    /// callers check it with [`crate::arch::check_machine_config`].
+    pub async fn configure_machine(
+        &self,
+        vcpu_count: u32,
+        mem_size_mib: u64,
+        cpu_template: Option<&str>,
+        smt: bool,
+    ) -> Result<()> {
+        let config = MachineConfig::new(vcpu_count, mem_size_mib, cpu_template, smt);
 
         debug!(
-            "Configuring machine: {} vCPUs, {} MiB RAM",
-            vcpu_count, mem_size_mib
+            "Configuring machine: {} vCPUs, {} MiB RAM, CPU template {}",
+            vcpu_count,
+            mem_size_mib,
+            cpu_template.unwrap_or("none")
         );
 
         self.make_request::<_, serde_json::Value>("PUT", "/machine-config", Some(config))
@@ -294,6 +493,240 @@ impl FirecrackerApiClient {
         Ok(())
     }
 
+    /// Enable the metadata service on `network_ifaces`, at
+    /// [`METADATA_ADDRESS`](aiva_core::METADATA_ADDRESS). `version` is `V1`
+    /// or `V2`, which requires a session token. Must happen after the
+    /// interfaces are attached and before the instance starts.
+    pub async fn configure_mmds(&self, version: &str, network_ifaces: &[String]) -> Result<()> {
+        #[derive(Serialize)]
+        struct MmdsConfig<'a> {
+            version: &'a str,
+            network_interfaces: &'a [String],
+            ipv4_address: &'a str,
+        }
+
+        if !matches!(version, "V1" | "V2") {
+            return Err(AivaError::PlatformError {
+                platform: "firecracker".to_string(),
+                message: format!("Unknown MMDS version {version}, expected V1 or V2"),
+                recoverable: false,
+            });
+        }
+
+        let config = MmdsConfig {
+            version,
+            network_interfaces: network_ifaces,
+            ipv4_address: aiva_core::METADATA_ADDRESS,
+        };
+
+        debug!("Enabling MMDS {} on {:?}", version, network_ifaces);
+
+        self.make_request::<_, serde_json::Value>("PUT", "/mmds/config", Some(config))
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the whole MMDS data store with `data`
+    pub async fn set_mmds_data(&self, data: serde_json::Value) -> Result<()> {
+        debug!("Replacing MMDS contents");
+
+        self.make_request::<_, serde_json::Value>("PUT", "/mmds", Some(data))
+            .await?;
+        Ok(())
+    }
+
+    /// The whole MMDS data store
+    pub async fn get_mmds_data(&self) -> Result<serde_json::Value> {
+        Ok(self
+            .make_request::<(), serde_json::Value>("GET", "/mmds", None)
+            .await?
+            .unwrap_or_default())
+    }
+
+    /// Full machine configuration as the VMM currently sees it
+    pub async fn get_vm_config(&self) -> Result<serde_json::Value> {
+        self.make_request::<(), serde_json::Value>("GET", "/vm/config", None)
+            .await?
+            .ok_or_else(|| AivaError::PlatformError {
+                platform: "firecracker".to_string(),
+                message: "Empty response from /vm/config".to_string(),
+                recoverable: true,
+            })
+    }
+
+    /// Point an attached drive at `path` again, which makes Firecracker
+    /// pick up a changed backing file size and notify the guest
+    pub async fn rescan_drive(&self, drive_id: &str, path: &Path) -> Result<()> {
+        #[derive(Serialize)]
+        struct PartialDrive {
+            drive_id: String,
+            path_on_host: String,
+        }
+
+        let drive = PartialDrive {
+            drive_id: drive_id.to_string(),
+            path_on_host: path.to_string_lossy().to_string(),
+        };
+
+        debug!("Rescanning drive {}: {:?}", drive_id, path);
+
+        self.make_request::<_, serde_json::Value>(
+            "PATCH",
+            &format!("/drives/{drive_id}"),
+            Some(drive),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Replace the rate limiter of an attached drive
+    pub async fn update_drive_rate_limiter(
+        &self,
+        drive_id: &str,
+        rate_limiter: serde_json::Value,
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        struct PartialDrive {
+            drive_id: String,
+            rate_limiter: serde_json::Value,
+        }
+
+        let drive = PartialDrive {
+            drive_id: drive_id.to_string(),
+            rate_limiter,
+        };
+
+        debug!("Updating rate limiter of drive {}", drive_id);
+
+        self.make_request::<_, serde_json::Value>(
+            "PATCH",
+            &format!("/drives/{drive_id}"),
+            Some(drive),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Attach a balloon device inflated to `amount_mib`. Must happen before
+    /// the instance starts. With `deflate_on_oom` the balloon gives memory
+    /// back to a guest under OOM; a `stats_polling_interval_s` of 0 turns
+    /// [`get_balloon_stats`](Self::get_balloon_stats) off.
+    pub async fn configure_balloon(
+        &self,
+        amount_mib: u64,
+        deflate_on_oom: bool,
+        stats_polling_interval_s: u32,
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        struct Balloon {
+            amount_mib: u64,
+            deflate_on_oom: bool,
+            stats_polling_interval_s: u32,
+        }
+
+        let balloon = Balloon {
+            amount_mib,
+            deflate_on_oom,
+            stats_polling_interval_s,
+        };
+
+        debug!("Configuring balloon device at {}MiB", amount_mib);
+
+        self.make_request::<_, serde_json::Value>("PUT", "/balloon", Some(balloon))
+            .await?;
+        Ok(())
+    }
+
+    /// Attach a vsock device with guest CID `guest_cid`. Host connections go
+    /// through the Unix socket at `uds_path`. Must happen before the
+    /// instance starts.
+    pub async fn configure_vsock(&self, guest_cid: u32, uds_path: &Path) -> Result<()> {
+        #[derive(Serialize)]
+        struct Vsock {
+            guest_cid: u32,
+            uds_path: String,
+        }
+
+        let vsock = Vsock {
+            guest_cid,
+            uds_path: uds_path.to_string_lossy().to_string(),
+        };
+
+        debug!("Configuring vsock device with CID {}", guest_cid);
+
+        self.make_request::<_, serde_json::Value>("PUT", "/vsock", Some(vsock))
+            .await?;
+        Ok(())
+    }
+
+    /// Inflate or deflate the balloon of a running VM to `amount_mib`
+    pub async fn update_balloon(&self, amount_mib: u64) -> Result<()> {
+        #[derive(Serialize)]
+        struct BalloonUpdate {
+            amount_mib: u64,
+        }
+
+        debug!("Setting balloon to {}MiB", amount_mib);
+
+        self.make_request::<_, serde_json::Value>(
+            "PATCH",
+            "/balloon",
+            Some(BalloonUpdate { amount_mib }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Balloon statistics the guest last reported. Needs a balloon
+    /// configured with a non-zero statistics polling interval.
+    pub async fn get_balloon_stats(&self) -> Result<BalloonStats> {
+        debug!("Reading balloon statistics");
+
+        self.make_request::<(), BalloonStats>("GET", "/balloon/statistics", None)
+            .await?
+            .ok_or_else(|| AivaError::PlatformError {
+                platform: "firecracker".to_string(),
+                message: "Empty balloon statistics".to_string(),
+                recoverable: true,
+            })
+    }
+
+    /// Have the VMM write its metrics to `metrics_path`, as seen inside the
+    /// jail. Must happen before the instance starts.
+    pub async fn configure_metrics(&self, metrics_path: &Path) -> Result<()> {
+        #[derive(Serialize)]
+        struct Metrics {
+            metrics_path: String,
+        }
+
+        let metrics = Metrics {
+            metrics_path: metrics_path.to_string_lossy().to_string(),
+        };
+
+        debug!("Configuring metrics at {}", metrics_path.display());
+
+        self.make_request::<_, serde_json::Value>("PUT", "/metrics", Some(metrics))
+            .await?;
+        Ok(())
+    }
+
+    /// Write the metrics gathered since the last flush right away instead
+    /// of at the next periodic flush
+    pub async fn flush_metrics(&self) -> Result<()> {
+        #[derive(Serialize)]
+        struct FlushMetrics {
+            action_type: String,
+        }
+
+        let action = FlushMetrics {
+            action_type: "FlushMetrics".to_string(),
+        };
+
+        self.make_request::<_, serde_json::Value>("PUT", "/actions", Some(action))
+            .await?;
+        Ok(())
+    }
+
     pub async fn start_instance(&self) -> Result<()> {
         #[derive(Serialize)]
         struct InstanceStart {
@@ -311,33 +744,120 @@ impl FirecrackerApiClient {
         Ok(())
     }
 
-    #[allow(dead_code)]
+    /// Stop the vCPUs; the process and guest memory stay
     pub async fn pause_vm(&self) -> Result<()> {
+        self.set_vm_state("Paused").await
+    }
+
+    pub async fn resume_vm(&self) -> Result<()> {
+        self.set_vm_state("Resumed").await
+    }
+
+    async fn set_vm_state(&self, state: &str) -> Result<()> {
         #[derive(Serialize)]
-        struct VmAction {
-            action_type: String,
+        struct VmState<'a> {
+            state: &'a str,
         }
 
-        let action = VmAction {
-            action_type: "Pause".to_string(),
+        debug!("Setting VM state to {}", state);
+
+        self.make_request::<_, serde_json::Value>("PATCH", "/vm", Some(VmState { state }))
+            .await?;
+        Ok(())
+    }
+
+    /// State of the instance as the VMM reports it: `Not started`,
+    /// `Running` or `Paused`
+    pub async fn instance_state(&self) -> Result<String> {
+        #[derive(Deserialize)]
+        struct InstanceInfo {
+            state: String,
+        }
+
+        let info = self
+            .make_request::<(), InstanceInfo>("GET", "/", None)
+            .await?
+            .ok_or_else(|| AivaError::PlatformError {
+                platform: "firecracker".to_string(),
+                message: "Empty instance info".to_string(),
+                recoverable: true,
+            })?;
+        Ok(info.state)
+    }
+
+    /// Write a snapshot of the VM, which must be paused. Paths are as the
+    /// VMM sees them, i.e. inside the jail.
+    pub async fn create_snapshot(
+        &self,
+        snapshot_type: SnapshotType,
+        mem_file_path: &Path,
+        snapshot_path: &Path,
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        struct SnapshotCreate {
+            snapshot_type: SnapshotType,
+            snapshot_path: String,
+            mem_file_path: String,
+        }
+
+        // A running VM would change its memory while it is written out
+        let state = self.instance_state().await?;
+        if state != "Paused" {
+            return Err(AivaError::PlatformError {
+                platform: "firecracker".to_string(),
+                message: format!(
+                    "Cannot create a {snapshot_type:?} snapshot of a VM that is {state}; pause it first"
+                ),
+                recoverable: false,
+            });
+        }
+
+        let snapshot = SnapshotCreate {
+            snapshot_type,
+            snapshot_path: snapshot_path.to_string_lossy().to_string(),
+            mem_file_path: mem_file_path.to_string_lossy().to_string(),
         };
 
-        self.make_request::<_, serde_json::Value>("PATCH", "/vm", Some(action))
+        debug!("Creating {:?} snapshot: {:?}", snapshot_type, snapshot_path);
+
+        self.make_request::<_, serde_json::Value>("PUT", "/snapshot/create", Some(snapshot))
             .await?;
         Ok(())
     }
 
-    pub async fn resume_vm(&self) -> Result<()> {
+    /// Load a snapshot into a fresh VMM, resuming it if `resume` is set and
+    /// leaving it paused otherwise
+    pub async fn load_snapshot(
+        &self,
+        snapshot_path: &Path,
+        mem_file_path: &Path,
+        resume: bool,
+    ) -> Result<()> {
         #[derive(Serialize)]
-        struct VmAction {
-            action_type: String,
+        struct MemBackend {
+            backend_type: String,
+            backend_path: String,
         }
 
-        let action = VmAction {
-            action_type: "Resume".to_string(),
+        #[derive(Serialize)]
+        struct SnapshotLoad {
+            snapshot_path: String,
+            mem_backend: MemBackend,
+            resume_vm: bool,
+        }
+
+        let snapshot = SnapshotLoad {
+            snapshot_path: snapshot_path.to_string_lossy().to_string(),
+            mem_backend: MemBackend {
+                backend_type: "File".to_string(),
+                backend_path: mem_file_path.to_string_lossy().to_string(),
+            },
+            resume_vm: resume,
         };
 
-        self.make_request::<_, serde_json::Value>("PATCH", "/vm", Some(action))
+        debug!("Loading snapshot: {:?}", snapshot_path);
+
+        self.make_request::<_, serde_json::Value>("PUT", "/snapshot/load", Some(snapshot))
             .await?;
         Ok(())
     }
@@ -1,5 +1,5 @@
-use crate::firecracker::FirecrackerApiClient;
-use aiva_core::{AivaError, Result, VMState, VMTemplate};
+use crate::firecracker::{FirecrackerApiClient, firecracker_cache_type};
+use aiva_core::{AivaError, CacheStrategy, Result, VMState, VMTemplate};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
@@ -17,6 +17,37 @@ pub struct FirecrackerVMConfig {
     pub tap_device: String,
     pub guest_ip: String,
     pub network_interface: String,
+    /// Kernel command line replacing the default one
+    #[serde(default)]
+    pub custom_boot_args: Option<String>,
+    /// Appended to the default kernel command line
+    #[serde(default)]
+    pub extra_boot_args: Vec<String>,
+    /// Firecracker CPU template, see [`crate::arch::check_machine_config`]
+    #[serde(default)]
+    pub cpu_template: Option<String>,
+    /// How the root drive caches writes
+    #[serde(default)]
+    pub cache_strategy: CacheStrategy,
+}
+
+/// Gateway of the guest, the TAP device's address in the Lima or WSL host
+const GUEST_GATEWAY: &str = "172.16.0.1";
+
+impl FirecrackerVMConfig {
+    /// Kernel command line bringing up `eth0` with the static guest
+    /// address, unless the VM configures its own
+    pub fn boot_args(&self) -> String {
+        let default = format!(
+            "console=ttyS0 reboot=k panic=1 pci=off init=/sbin/init ip={}::{GUEST_GATEWAY}:255.255.255.0::{}:off",
+            self.guest_ip, self.network_interface
+        );
+        aiva_core::kernel_command_line(
+            self.custom_boot_args.as_deref(),
+            &self.extra_boot_args,
+            &default,
+        )
+    }
 }
 
 pub struct FirecrackerVM {
@@ -75,6 +106,9 @@ impl FirecrackerVM {
     async fn resize_rootfs(&self, rootfs_path: &Path, size_gb: u64) -> Result<()> {
         debug!("Resizing rootfs to {}GB", size_gb);
 
+        let current_size = tokio::fs::metadata(rootfs_path).await?.len();
+        aiva_core::check_rootfs_fits(current_size, size_gb)?;
+
         let output = Command::new("truncate")
             .args(["-s", &format!("{size_gb}G")])
             .arg(rootfs_path)
@@ -368,21 +402,27 @@ impl FirecrackerVM {
 
         // Configure machine
         client
-            .configure_machine(self.config.vcpu_count, self.config.mem_size_mib)
+            .configure_machine(
+                self.config.vcpu_count,
+                self.config.mem_size_mib,
+                self.config.cpu_template.as_deref(),
+                false,
+            )
             .await?;
 
         // Configure boot source
-        let boot_args = format!(
-            "console=ttyS0 reboot=k panic=1 pci=off ip={}::172.16.0.1:255.255.255.0::eth0:off",
-            self.config.guest_ip
-        );
         client
-            .configure_boot_source(&self.config.kernel_path, &boot_args)
+            .configure_boot_source(&self.config.kernel_path, &self.config.boot_args())
             .await?;
 
         // Configure root drive
         client
-            .configure_drive("rootfs", &self.config.rootfs_path, false, "Unsafe")
+            .configure_drive(
+                "rootfs",
+                &self.config.rootfs_path,
+                false,
+                firecracker_cache_type(self.config.cache_strategy),
+            )
             .await?;
 
         // Configure network interface
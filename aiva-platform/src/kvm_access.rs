@@ -0,0 +1,104 @@
+//! Diagnosis of `/dev/kvm` access problems.
+//!
+//! Most distributions ship `/dev/kvm` as `root:kvm 0660`, so a user who is not
+//! in the `kvm` group cannot start VMs. The decision here is kept separate from
+//! the filesystem so the suggested fix can be picked for any mode and group
+//! combination.
+
+use aiva_core::AivaError;
+use std::path::Path;
+
+/// Mode and ownership of the KVM device node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvmDeviceInfo {
+    pub mode: u32,
+    pub owner_uid: u32,
+    pub group_gid: u32,
+    /// Name of the owning group, if it resolves
+    pub group_name: Option<String>,
+}
+
+/// The user that wants to open the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvmUser {
+    pub uid: u32,
+    pub name: String,
+    /// Groups of the current process
+    pub active_gids: Vec<u32>,
+    /// Whether the user is listed in the device group, which can differ from
+    /// `active_gids` until the next login
+    pub in_device_group: bool,
+}
+
+/// What it takes for [`KvmUser`] to get read/write access to the device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KvmAccess {
+    Granted,
+    /// The user was added to the group but this session predates it
+    ReloginRequired {
+        group: String,
+    },
+    /// The group can use the device, the user is not a member
+    JoinGroup {
+        group: String,
+    },
+    /// Neither the group nor others may read and write the device
+    FixPermissions,
+}
+
+const OWNER_RW: u32 = 0o600;
+const GROUP_RW: u32 = 0o060;
+const OTHER_RW: u32 = 0o006;
+
+pub fn decide_kvm_access(device: &KvmDeviceInfo, user: &KvmUser) -> KvmAccess {
+    let group = device
+        .group_name
+        .clone()
+        .unwrap_or_else(|| device.group_gid.to_string());
+    let group_rw = device.mode & GROUP_RW == GROUP_RW;
+
+    if user.uid == 0
+        || device.mode & OTHER_RW == OTHER_RW
+        || (user.uid == device.owner_uid && device.mode & OWNER_RW == OWNER_RW)
+        || (group_rw && user.active_gids.contains(&device.group_gid))
+    {
+        KvmAccess::Granted
+    } else if group_rw && user.in_device_group {
+        KvmAccess::ReloginRequired { group }
+    } else if group_rw {
+        KvmAccess::JoinGroup { group }
+    } else {
+        KvmAccess::FixPermissions
+    }
+}
+
+impl KvmAccess {
+    /// Recoverable error describing how to fix the access problem, `None`
+    /// when access is granted
+    pub fn to_error(&self, device: &Path, user: &str) -> Option<AivaError> {
+        let device = device.display();
+        let message = match self {
+            KvmAccess::Granted => return None,
+            KvmAccess::ReloginRequired { group } => format!(
+                "User '{user}' is in the '{group}' group but this session is not yet. \
+                 Log out and back in, or run 'newgrp {group}', to use {device}."
+            ),
+            KvmAccess::JoinGroup { group } => format!(
+                "User '{user}' cannot access {device}, which is restricted to the '{group}' group. \
+                 Run 'sudo usermod -aG {group} $USER' and log in again, \
+                 or 'aiva init <name> --grant-kvm' for immediate access."
+            ),
+            KvmAccess::FixPermissions => format!(
+                "{device} is not readable and writable by its group. \
+                 Run 'sudo chgrp kvm {device} && sudo chmod 660 {device}', \
+                 or 'aiva init <name> --grant-kvm' for immediate access."
+            ),
+        };
+
+        Some(AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message,
+            recoverable: true,
+        })
+    }
+}
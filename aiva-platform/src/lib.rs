@@ -1,8 +1,16 @@
+mod api_tunnel;
+pub mod arch;
 pub mod command_pool;
+mod cpu_usage;
 mod firecracker;
 mod firecracker_vm;
+pub mod kvm_access;
+mod lima;
 mod linux;
+pub mod log_shipping;
 mod macos;
+pub mod metrics;
+mod setup_sources;
 mod vsock_executor;
 mod windows;
 
@@ -12,24 +20,42 @@ mod tests;
 use aiva_core::{Platform, Result};
 use std::sync::Arc;
 
+pub use api_tunnel::ApiTunnel;
+pub use arch::Architecture;
+pub use firecracker::{ApiEndpoint, BalloonStats, FirecrackerApiClient};
+pub use kvm_access::{KvmAccess, KvmDeviceInfo, KvmUser, decide_kvm_access};
 pub use linux::LinuxPlatform;
-pub use macos::MacOSPlatform;
-pub use windows::WindowsPlatform;
+pub use macos::{LimaRetryPolicy, MacOSPlatform};
+pub use metrics::{InterfaceCounters, PlatformMetricsCollector};
+pub use windows::{WindowsPlatform, WslExecPolicy};
 
 pub fn get_current_platform() -> Result<Arc<dyn Platform>> {
     #[cfg(target_os = "linux")]
     {
-        Ok(Arc::new(LinuxPlatform::new()?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        Ok(Arc::new(
+            LinuxPlatform::new()?
+                .with_jailer_config(config.platform.linux.jailer)
+                .with_shutdown_grace(std::time::Duration::from_secs(
+                    config.platform.linux.shutdown_grace_secs,
+                )),
+        ))
     }
 
     #[cfg(target_os = "macos")]
     {
-        Ok(Arc::new(MacOSPlatform::new()?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        Ok(Arc::new(MacOSPlatform::new()?.with_retry_policy(
+            LimaRetryPolicy::from(&config.platform.macos),
+        )))
     }
 
     #[cfg(target_os = "windows")]
     {
-        Ok(Arc::new(WindowsPlatform::new()?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        Ok(Arc::new(WindowsPlatform::new()?.with_exec_policy(
+            WslExecPolicy::from(&config.platform.windows),
+        )))
     }
 
     #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
@@ -45,21 +71,35 @@ pub fn get_current_platform() -> Result<Arc<dyn Platform>> {
 pub fn get_platform_with_config(_lima_config: Option<String>) -> Result<Arc<dyn Platform>> {
     #[cfg(target_os = "linux")]
     {
-        Ok(Arc::new(LinuxPlatform::new()?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        Ok(Arc::new(
+            LinuxPlatform::new()?
+                .with_jailer_config(config.platform.linux.jailer)
+                .with_shutdown_grace(std::time::Duration::from_secs(
+                    config.platform.linux.shutdown_grace_secs,
+                )),
+        ))
     }
 
     #[cfg(target_os = "macos")]
     {
-        if let Some(config_path) = _lima_config {
-            Ok(Arc::new(MacOSPlatform::with_config(config_path)?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        let platform = if let Some(config_path) = _lima_config {
+            MacOSPlatform::with_config(config_path)?
         } else {
-            Ok(Arc::new(MacOSPlatform::new()?))
-        }
+            MacOSPlatform::new()?
+        };
+        Ok(Arc::new(platform.with_retry_policy(LimaRetryPolicy::from(
+            &config.platform.macos,
+        ))))
     }
 
     #[cfg(target_os = "windows")]
     {
-        Ok(Arc::new(WindowsPlatform::new()?))
+        let config = aiva_core::Config::load().unwrap_or_default();
+        Ok(Arc::new(WindowsPlatform::new()?.with_exec_policy(
+            WslExecPolicy::from(&config.platform.windows),
+        )))
     }
 
     #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
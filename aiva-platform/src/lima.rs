@@ -0,0 +1,59 @@
+//! Typed view of `limactl list --format json`.
+//!
+//! Depending on the Lima version the command prints either a JSON array or
+//! one JSON object per line; both are accepted.
+
+use aiva_core::{AivaError, Result};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum LimaStatus {
+    Running,
+    Stopped,
+    Broken,
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+/// One instance reported by `limactl list`. Only the fields aiva uses are
+/// read; the rest of Lima's output is ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimaInstance {
+    pub name: String,
+    #[serde(default)]
+    pub status: LimaStatus,
+    #[serde(default)]
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub ssh_local_port: Option<u16>,
+}
+
+pub fn parse_lima_list(output: &str) -> Result<Vec<LimaInstance>> {
+    let parse_error = |e: serde_json::Error| AivaError::PlatformError {
+        platform: "macos".to_string(),
+        message: format!("Failed to parse 'limactl list' output: {e}"),
+        recoverable: false,
+    };
+
+    let output = output.trim();
+    if output.starts_with('[') {
+        return serde_json::from_str(output).map_err(parse_error);
+    }
+
+    output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_str(line).map_err(parse_error))
+        .collect()
+}
+
+/// The instance called exactly `name`
+pub fn find_lima_instance<'a>(
+    instances: &'a [LimaInstance],
+    name: &str,
+) -> Option<&'a LimaInstance> {
+    instances.iter().find(|instance| instance.name == name)
+}
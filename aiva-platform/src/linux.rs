@@ -1,16 +1,68 @@
-use aiva_core::{AivaError, Platform, Result, VMInstance, VMLogger, VMMetrics, VMState};
+use aiva_core::{
+    AivaError, BlockDevice, ExecTransport, ImageCopy, JailerConfig, Liveness, OrphanedResource,
+    Platform, PlatformPlan, Result, ScaleCapabilities, ScaleStep, SnapshotFiles, SnapshotType,
+    StopMethod, VMConfig, VMInstance, VMLogger, VMMetadata, VMMetrics, VMState,
+};
+use aiva_security::{
+    NetworkPolicy, OpenFilesCheck, OpenFilesLimit, ResourceLimits, SecurityPolicy,
+    parse_open_files_limit,
+};
 use async_trait::async_trait;
+use nix::sys::resource::{Resource, getrlimit, setrlimit};
+use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
-use crate::command_pool::{ConnectionType, get_command_pool};
-use crate::vsock_executor::VSOCK_COMMAND_PORT;
+use crate::arch::{Architecture, check_architecture, check_host_machine_config};
+use crate::command_pool::{ExecutorRegistry, connection_for, get_command_pool};
+use crate::cpu_usage::{CPU_SAMPLE_WINDOW, CpuUsageTracker, HostProc};
+use crate::firecracker::firecracker_cache_type;
+use crate::kvm_access::{KvmDeviceInfo, KvmUser, decide_kvm_access};
+
+/// Grows the mounted root filesystem after its block device got bigger
+const ONLINE_RESIZE_COMMAND: &str = "resize2fs /dev/vda";
+
+/// Command transports in the order tried when a VM does not configure one
+const LINUX_TRANSPORTS: &[ExecTransport] = &[
+    ExecTransport::Vsock,
+    ExecTransport::Network,
+    ExecTransport::Ssh,
+];
+
+/// Snapshot files of a hibernated VM, relative to the jail root
+const SNAPSHOT_STATE_FILE: &str = "snapshot";
+const SNAPSHOT_MEMORY_FILE: &str = "memory";
+
+/// Firecracker's metrics FIFO, relative to the jail root
+const METRICS_FIFO_FILE: &str = "metrics.fifo";
+/// Device totals summed from the metrics FIFO, in the jailer workspace
+const METRICS_TOTALS_FILE: &str = "metrics.json";
+
+/// How often the guest reports balloon statistics, in seconds
+const BALLOON_STATS_INTERVAL_S: u32 = 5;
+
+/// Kernel command line of VMs that do not configure their own
+const DEFAULT_BOOT_ARGS: &str = "console=ttyS0 reboot=k panic=1 pci=off";
+/// User and group Firecracker runs as when aiva runs as root outside sudo
+const DEFAULT_JAILER_ID: u32 = 1000;
+/// A workspace younger than this may belong to a create still in progress
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(120);
 
 pub struct LinuxPlatform {
     firecracker_path: PathBuf,
     jailer_path: PathBuf,
     kvm_device: PathBuf,
+    /// Kernel new VMs boot unless their configuration names another one
+    kernel_path: PathBuf,
+    /// User-defined security policies, consulted before the presets
+    policies_dir: PathBuf,
+    /// Last CPU time readings of the Firecracker processes
+    cpu_usage: CpuUsageTracker,
+    jailer: JailerConfig,
+    /// How long a guest gets to power off before Firecracker is signalled
+    shutdown_grace: Duration,
 }
 
 impl LinuxPlatform {
@@ -24,14 +76,120 @@ impl LinuxPlatform {
             which::which("jailer").unwrap_or_else(|_| PathBuf::from("/usr/bin/jailer"));
 
         let kvm_device = PathBuf::from("/dev/kvm");
+        let kernel_path = VMConfig::default().kernel_path;
+
+        let policies_dir = dirs::home_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(".aiva")
+            .join("policies");
 
         Ok(Self {
             firecracker_path,
             jailer_path,
             kvm_device,
+            kernel_path,
+            policies_dir,
+            cpu_usage: CpuUsageTracker::new(Box::new(HostProc), CPU_SAMPLE_WINDOW),
+            jailer: JailerConfig::default(),
+            shutdown_grace: Duration::from_secs(10),
+        })
+    }
+
+    /// Confine Firecracker as `jailer` says, e.g. `platform.linux.jailer`
+    /// of the main configuration
+    pub fn with_jailer_config(mut self, jailer: JailerConfig) -> Self {
+        self.jailer = jailer;
+        self
+    }
+
+    /// Give guests `grace` to power off on a graceful stop, e.g.
+    /// `platform.linux.shutdown_grace_secs` of the main configuration. The
+    /// whole stop must fit the orchestrator's timeout, so it is capped at
+    /// [`MAX_SHUTDOWN_GRACE`].
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        if grace > MAX_SHUTDOWN_GRACE {
+            warn!(
+                "Shutdown grace of {}s is longer than the {}s allowed, using {}s",
+                grace.as_secs(),
+                MAX_SHUTDOWN_GRACE.as_secs(),
+                MAX_SHUTDOWN_GRACE.as_secs()
+            );
+        }
+        self.shutdown_grace = grace.min(MAX_SHUTDOWN_GRACE);
+        self
+    }
+
+    /// User and group Firecracker runs as
+    pub(crate) fn jailer_owner(&self) -> (u32, u32) {
+        jailer_owner(&self.jailer, invoking_user(), |uid| {
+            nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .map(|user| user.gid.as_raw())
         })
     }
 
+    /// Per-VM directory the jailer chroots Firecracker into
+    pub(crate) fn jailer_workspace(&self, vm: &VMInstance) -> PathBuf {
+        self.jailer.chroot_base_dir.join(vm.id.to_string())
+    }
+
+    /// Unix socket on the host side of the vsock device of `vm`
+    pub(crate) fn vsock_uds_path(&self, vm: &VMInstance) -> PathBuf {
+        crate::vsock_executor::vsock_uds_path(&self.jailer, vm)
+    }
+
+    /// Check the jailer can run Firecracker as configured: its user and
+    /// group exist, and the chroot base is a writable directory that allows
+    /// executing files
+    pub(crate) fn check_jailer(&self) -> Result<()> {
+        let invalid = |message: String| AivaError::ConfigError(message);
+        if !matches!(self.jailer.cgroup_version, 1 | 2) {
+            return Err(invalid(format!(
+                "platform.linux.jailer.cgroup_version must be 1 or 2, not {}",
+                self.jailer.cgroup_version
+            )));
+        }
+
+        let (uid, gid) = self.jailer_owner();
+        if !matches!(
+            nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid)),
+            Ok(Some(_))
+        ) {
+            return Err(invalid(format!(
+                "Jailer user {uid} does not exist; set platform.linux.jailer.uid to an existing user"
+            )));
+        }
+        if !matches!(
+            nix::unistd::Group::from_gid(nix::unistd::Gid::from_raw(gid)),
+            Ok(Some(_))
+        ) {
+            return Err(invalid(format!(
+                "Jailer group {gid} does not exist; set platform.linux.jailer.gid to an existing group"
+            )));
+        }
+
+        let base = &self.jailer.chroot_base_dir;
+        let unusable = |reason: String| {
+            invalid(format!(
+                "Jailer chroot base {} {reason}; set platform.linux.jailer.chroot_base_dir to another directory",
+                base.display()
+            ))
+        };
+        std::fs::create_dir_all(base).map_err(|e| unusable(format!("cannot be created: {e}")))?;
+        nix::unistd::access(base, nix::unistd::AccessFlags::W_OK)
+            .map_err(|e| unusable(format!("is not writable: {e}")))?;
+        let mount = nix::sys::statvfs::statvfs(base)
+            .map_err(|e| unusable(format!("cannot be inspected: {e}")))?;
+        if mount
+            .flags()
+            .contains(nix::sys::statvfs::FsFlags::ST_NOEXEC)
+        {
+            return Err(unusable("is on a filesystem mounted noexec".to_string()));
+        }
+        Ok(())
+    }
+
     pub fn check_vsock_support(&self) -> bool {
         // Check if vsock kernel module is loaded
         Path::new("/dev/vsock").exists() || Path::new("/dev/vhost-vsock").exists()
@@ -46,66 +204,281 @@ impl LinuxPlatform {
             });
         }
 
-        // Check KVM permissions
-        use std::os::unix::fs::PermissionsExt;
-        let metadata = std::fs::metadata(&self.kvm_device)?;
-        let mode = metadata.permissions().mode();
-
-        if mode & 0o666 != 0o666 {
-            return Err(AivaError::PlatformError {
+        // Opening the device is the only reliable check, it also honours ACLs
+        let open_error = match self.open_kvm_device() {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        let (device, user) = self.kvm_access_context()?;
+        match decide_kvm_access(&device, &user).to_error(&self.kvm_device, &user.name) {
+            Some(error) => Err(error),
+            // The mode bits allow access, so something else (SELinux, a
+            // seccomp sandbox, a read-only /dev) is in the way
+            None => Err(AivaError::PlatformError {
                 platform: "linux".to_string(),
                 message: format!(
-                    "Insufficient permissions for {}. Run 'sudo chmod 666 /dev/kvm' or add user to kvm group.",
-                    self.kvm_device.display()
+                    "Cannot open {} although its permissions allow it: {}",
+                    self.kvm_device.display(),
+                    open_error
                 ),
-                recoverable: true,
-            });
+                recoverable: false,
+            }),
         }
+    }
 
-        Ok(())
+    fn open_kvm_device(&self) -> std::io::Result<()> {
+        std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.kvm_device)
+            .map(|_| ())
+    }
+
+    fn kvm_access_context(&self) -> Result<(KvmDeviceInfo, KvmUser)> {
+        use nix::unistd::{Gid, Group, User, getgroups, getuid};
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = std::fs::metadata(&self.kvm_device)?;
+        let group = Group::from_gid(Gid::from_raw(metadata.gid()))
+            .ok()
+            .flatten();
+
+        let uid = getuid();
+        let user = User::from_uid(uid).ok().flatten();
+        let name = user
+            .as_ref()
+            .map(|user| user.name.clone())
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| uid.to_string());
+        let in_device_group = match (&user, &group) {
+            (Some(user), Some(group)) => user.gid == group.gid || group.mem.contains(&user.name),
+            _ => false,
+        };
+
+        let device = KvmDeviceInfo {
+            mode: metadata.mode() & 0o777,
+            owner_uid: metadata.uid(),
+            group_gid: metadata.gid(),
+            group_name: group.map(|group| group.name),
+        };
+        let user = KvmUser {
+            uid: uid.as_raw(),
+            name,
+            active_gids: getgroups()
+                .unwrap_or_default()
+                .into_iter()
+                .chain([nix::unistd::getegid()])
+                .map(|gid| gid.as_raw())
+                .collect(),
+            in_device_group,
+        };
+
+        Ok((device, user))
+    }
+
+    /// Whether the KVM device exists but the current user cannot open it,
+    /// which [`grant_kvm_access`](Self::grant_kvm_access) may fix
+    pub fn kvm_access_denied(&self) -> bool {
+        self.kvm_device.exists() && self.open_kvm_device().is_err()
+    }
+
+    /// Give the current user access to the KVM device through sudo: an ACL
+    /// for immediate access plus group membership so it survives a reboot.
+    /// Fails unless the device can be opened afterwards.
+    pub fn grant_kvm_access(&self) -> Result<()> {
+        if !self.kvm_device.exists() {
+            return self.check_kvm_available();
+        }
+        if self.open_kvm_device().is_ok() {
+            info!("{} is already accessible", self.kvm_device.display());
+            return Ok(());
+        }
+
+        let (device, user) = self.kvm_access_context()?;
+        let device_path = self.kvm_device.display().to_string();
+
+        if device.mode & 0o060 != 0o060 {
+            run_sudo(&["chmod", "g+rw", &device_path])?;
+        }
+        run_sudo(&[
+            "setfacl",
+            "-m",
+            &format!("u:{}:rw", user.name),
+            &device_path,
+        ])?;
+        if let Some(group) = &device.group_name
+            && !user.in_device_group
+        {
+            run_sudo(&["usermod", "-aG", group, &user.name])?;
+        }
+
+        self.open_kvm_device()
+            .map_err(|e| AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: format!(
+                    "Still cannot open {} after granting access: {}",
+                    device_path, e
+                ),
+                recoverable: false,
+            })
     }
 
     async fn prepare_jailer_workspace(&self, vm: &VMInstance) -> Result<PathBuf> {
-        let workspace = PathBuf::from("/tmp")
-            .join("aiva-jailer")
-            .join(vm.id.to_string());
+        self.check_jailer()?;
+        let workspace = self.jailer_workspace(vm);
         std::fs::create_dir_all(&workspace)?;
 
         // Create required directories
         let root_dir = workspace.join("root");
         std::fs::create_dir_all(&root_dir)?;
 
+        // A socket left by an earlier attempt would pass for the new one
+        let stale_socket = root_dir.join("firecracker.socket");
+        if stale_socket.exists() {
+            std::fs::remove_file(&stale_socket)?;
+        }
+
+        // Refuse a disk smaller than the base image before copying anything
+        let base_size = std::fs::metadata(&vm.config.rootfs_path)?.len();
+        aiva_core::check_rootfs_fits(base_size, vm.config.disk_gb)?;
+
         // Copy kernel and rootfs
         let kernel_dest = root_dir.join("vmlinux");
         let rootfs_dest = root_dir.join("rootfs.ext4");
 
         std::fs::copy(&vm.config.kernel_path, &kernel_dest)?;
         std::fs::copy(&vm.config.rootfs_path, &rootfs_dest)?;
+        grow_rootfs(&rootfs_dest, vm.config.disk_gb, true)?;
+        for (drive_id, drive) in vm.config.storage.drives() {
+            link_drive_into_jail(&root_dir, &drive_id, drive)?;
+        }
 
         Ok(workspace)
     }
 
+    /// Jailer command line for `vm`, enforcing `limits` through the cgroup
+    /// the jailer creates
+    pub(crate) fn jailer_args(
+        &self,
+        workspace: &Path,
+        vm: &VMInstance,
+        limits: Option<&ResourceLimits>,
+    ) -> Result<Vec<OsString>> {
+        let socket_path = workspace.join("root").join("firecracker.socket");
+
+        let (uid, gid) = self.jailer_owner();
+
+        let mut args: Vec<OsString> = vec![
+            "--id".into(),
+            vm.id.to_string().into(),
+            "--exec-file".into(),
+            self.firecracker_path.clone().into(),
+            "--uid".into(),
+            uid.to_string().into(),
+            "--gid".into(),
+            gid.to_string().into(),
+            "--chroot-base-dir".into(),
+            workspace.into(),
+        ];
+        let cgroup_args = match limits {
+            Some(limits) => limits.jailer_cgroup_args(vm.config.cpus)?,
+            None => Vec::new(),
+        };
+        // The limits are written as cgroup v2 files
+        if !cgroup_args.is_empty() && self.jailer.cgroup_version != 2 {
+            return Err(AivaError::ConfigError(format!(
+                "The resource limits of VM '{}' need cgroup v2, but platform.linux.jailer.cgroup_version is {}",
+                vm.name, self.jailer.cgroup_version
+            )));
+        }
+        if let Some(node) = self.jailer.numa_node {
+            if cgroup_args.is_empty() {
+                args.extend([
+                    "--cgroup-version".into(),
+                    self.jailer.cgroup_version.to_string().into(),
+                ]);
+            }
+            args.extend(["--node".into(), node.to_string().into()]);
+        }
+        args.extend(cgroup_args.into_iter().map(OsString::from));
+        if let Some(limits) = limits {
+            args.extend(
+                limits
+                    .jailer_rlimit_args(host_open_files_hard_limit()?)?
+                    .into_iter()
+                    .map(OsString::from),
+            );
+        }
+        args.extend(["--".into(), "--api-sock".into(), socket_path.into()]);
+
+        Ok(args)
+    }
+
+    /// Firecracker arguments installing the syscall filter of `policy`,
+    /// after writing the compiled filter into the workspace. A filter that
+    /// would weaken Firecracker's built-in one is left out.
+    pub(crate) fn seccomp_args(
+        &self,
+        workspace: &Path,
+        policy: Option<&SecurityPolicy>,
+    ) -> Result<Vec<OsString>> {
+        let Some(filter) = policy.and_then(|policy| policy.syscall_filter.as_ref()) else {
+            return Ok(Vec::new());
+        };
+        if !aiva_security::replaces_firecracker_filter(filter) {
+            info!(
+                "Keeping Firecracker's built-in seccomp filter, the policy's {:?} default is weaker",
+                filter.default_action
+            );
+            return Ok(Vec::new());
+        }
+        let program = aiva_security::compile_filter(filter, aiva_security::host_arch()?)?;
+        let path = workspace
+            .join("root")
+            .join(aiva_security::SECCOMP_FILTER_FILE);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        aiva_security::write_firecracker_filter(&program, &path)?;
+        Ok(vec!["--seccomp-filter".into(), path.into()])
+    }
+
+    /// The security policy `vm` is configured with, if any
+    async fn vm_policy(&self, vm: &VMInstance) -> Result<Option<SecurityPolicy>> {
+        match &vm.config.security_policy {
+            Some(name) => Ok(Some(
+                aiva_security::resolve_policy(name, &self.policies_dir).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
     async fn spawn_firecracker(
         &self,
         workspace: &Path,
         vm: &VMInstance,
+        policy: Option<&SecurityPolicy>,
     ) -> Result<std::process::Child> {
         let socket_path = workspace.join("root").join("firecracker.socket");
 
+        let limits = policy.map(|policy| &policy.resource_limits);
+
+        let metrics_fifo = create_metrics_fifo(workspace, self.jailer_owner())
+            .inspect_err(|e| warn!("No metrics FIFO for VM {}: {}", vm.name, e))
+            .ok();
+
         let mut cmd = Command::new(&self.jailer_path);
-        cmd.arg("--id")
-            .arg(vm.id.to_string())
-            .arg("--exec-file")
-            .arg(&self.firecracker_path)
-            .arg("--uid")
-            .arg("1000")
-            .arg("--gid")
-            .arg("1000")
-            .arg("--chroot-base-dir")
-            .arg(workspace)
-            .arg("--")
-            .arg("--api-sock")
-            .arg(&socket_path);
+        cmd.args(self.jailer_args(workspace, vm, limits)?);
+        cmd.args(self.seccomp_args(workspace, policy)?);
+
+        let open_files = match limits {
+            Some(limits) => limits.open_files_limit(host_open_files_hard_limit()?)?,
+            None => None,
+        };
+        if let Some(limit) = open_files {
+            limit_open_files_on_exec(&mut cmd, limit);
+        }
 
         info!("Starting Firecracker with jailer: {:?}", cmd);
 
@@ -131,69 +504,100 @@ impl LinuxPlatform {
             });
         }
 
+        if let Some(fifo) = metrics_fifo {
+            let configured = match crate::firecracker::FirecrackerApiClient::new(socket_path) {
+                Ok(api_client) => {
+                    api_client
+                        .configure_metrics(&Path::new("/").join(METRICS_FIFO_FILE))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = configured {
+                // Without a FIFO, metrics fall back to the process figures
+                warn!("Firecracker metrics unavailable for VM {}: {}", vm.name, e);
+                let _ = std::fs::remove_file(fifo);
+            }
+        }
+
+        if let Some(intended) = limits.and_then(|limits| limits.open_files) {
+            let check = OpenFilesCheck {
+                intended,
+                readback: read_open_files_limit(child.id()),
+            };
+            if !check.is_enforced() {
+                warn!(
+                    "Open files limit of {} is not in effect for VM {}: {:?}",
+                    intended, vm.name, check.readback
+                );
+            }
+        }
+
         Ok(child)
     }
 
-    async fn get_process_cpu_usage(&self, pid: u32) -> Result<f64> {
-        // Read process stat
-        let stat_path = format!("/proc/{pid}/stat");
-        let stat_content = tokio::fs::read_to_string(&stat_path).await?;
-
-        // Parse CPU time from stat file
-        let fields: Vec<&str> = stat_content.split_whitespace().collect();
-        if fields.len() > 14 {
-            let utime: u64 = fields[13].parse().unwrap_or(0);
-            let stime: u64 = fields[14].parse().unwrap_or(0);
-            let total_time = utime + stime;
+    /// Everything [`Platform::create_vm`] does, recording in `acquired`
+    /// what has to be released if a step fails
+    async fn create_steps(
+        &self,
+        instance: &VMInstance,
+        acquired: &mut CreateRollback,
+    ) -> std::result::Result<VMInstance, StepFailure> {
+        let workspace = self.jailer_workspace(instance);
+        acquired.workspace = Some(workspace.clone());
+        self.prepare_jailer_workspace(instance)
+            .await
+            .map_err(|e| StepFailure::at(CreateStep::PrepareWorkspace, e))?;
+
+        let policy = self
+            .vm_policy(instance)
+            .await
+            .map_err(|e| StepFailure::at(CreateStep::SpawnFirecracker, e))?;
+        let child = self
+            .spawn_firecracker(&workspace, instance, policy.as_ref())
+            .await
+            .map_err(|e| StepFailure::at(CreateStep::SpawnFirecracker, e))?;
+        let pid = child.id();
+        acquired.child = Some(child);
 
-            // Get system uptime
-            let uptime_content = tokio::fs::read_to_string("/proc/uptime").await?;
-            let uptime: f64 = uptime_content
-                .split_whitespace()
-                .next()
-                .and_then(|s| s.parse().ok())
-                .unwrap_or(1.0);
+        let socket_path = workspace.join("root").join("firecracker.socket");
+        let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())
+            .map_err(|e| StepFailure::at(CreateStep::SpawnFirecracker, e))?;
+        let network_policy = policy.as_ref().map(|policy| &policy.network_policy);
+        let tap_device =
+            configure_and_boot(&api_client, instance, network_policy, acquired).await?;
 
-            // Calculate CPU usage percentage
-            let hz = 100.0; // Typical USER_HZ value
-            let cpu_usage = (total_time as f64 / hz / uptime) * 100.0;
+        let mut updated_instance = instance.clone();
+        updated_instance.runtime.pid = Some(pid);
+        updated_instance.runtime.api_socket = Some(socket_path);
+        updated_instance.runtime.tap_device = Some(tap_device);
+        updated_instance.state = VMState::Running;
+        Ok(updated_instance)
+    }
 
-            Ok(cpu_usage.min(100.0))
-        } else {
-            Ok(0.0)
-        }
+    /// API client of the running Firecracker process of `instance`
+    fn api_client(
+        &self,
+        instance: &VMInstance,
+    ) -> Result<crate::firecracker::FirecrackerApiClient> {
+        let socket_path =
+            instance
+                .runtime
+                .api_socket
+                .clone()
+                .ok_or_else(|| AivaError::VMError {
+                    vm_name: instance.name.clone(),
+                    state: instance.state,
+                    message: "No Firecracker API socket recorded".to_string(),
+                })?;
+        crate::firecracker::FirecrackerApiClient::new(socket_path)
     }
 
     async fn get_process_memory_usage(&self, pid: u32) -> Result<aiva_core::MemoryMetrics> {
         // Read process status for memory info
         let status_path = format!("/proc/{pid}/status");
         let status_content = tokio::fs::read_to_string(&status_path).await?;
-
-        let mut vm_size_kb = 0u64;
-        let mut vm_rss_kb = 0u64;
-
-        for line in status_content.lines() {
-            if line.starts_with("VmSize:") {
-                vm_size_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-            } else if line.starts_with("VmRSS:") {
-                vm_rss_kb = line
-                    .split_whitespace()
-                    .nth(1)
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0);
-            }
-        }
-
-        Ok(aiva_core::MemoryMetrics {
-            total_mb: vm_size_kb / 1024,
-            used_mb: vm_rss_kb / 1024,
-            available_mb: (vm_size_kb - vm_rss_kb) / 1024,
-            cache_mb: 0,
-        })
+        Ok(parse_process_memory(&status_content))
     }
 
     async fn get_process_uptime(&self, pid: u32) -> Result<std::time::Duration> {
@@ -222,39 +626,32 @@ impl LinuxPlatform {
         }
     }
 
+    /// Device totals from the metrics FIFO of `instance`, `None` when it
+    /// has none or it cannot be read
+    async fn firecracker_counters(
+        &self,
+        instance: &VMInstance,
+    ) -> Option<crate::metrics::FirecrackerCounters> {
+        let workspace = self.jailer_workspace(instance);
+        let fifo = workspace.join("root").join(METRICS_FIFO_FILE);
+        if !fifo.exists() {
+            return None;
+        }
+        // Flushing first makes the totals current rather than up to a
+        // minute old
+        if let Err(e) = self.api_client(instance).ok()?.flush_metrics().await {
+            debug!("Could not flush metrics of VM {}: {}", instance.name, e);
+        }
+        crate::metrics::drain_firecracker_metrics(&fifo, &workspace.join(METRICS_TOTALS_FILE))
+            .inspect_err(|e| warn!("Cannot read metrics of VM {}: {}", instance.name, e))
+            .ok()
+    }
+
     async fn get_tap_device_metrics(
         &self,
         tap_device: &str,
     ) -> Result<aiva_core::NetworkIOMetrics> {
-        // Read network statistics from /proc/net/dev
-        let net_dev_content = tokio::fs::read_to_string("/proc/net/dev").await?;
-
-        for line in net_dev_content.lines() {
-            if line.contains(tap_device) {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 10 {
-                    // Format: interface: rx_bytes rx_packets ... tx_bytes tx_packets ...
-                    let rx_bytes = parts[1].parse().unwrap_or(0);
-                    let rx_packets = parts[2].parse().unwrap_or(0);
-                    let tx_bytes = parts[9].parse().unwrap_or(0);
-                    let tx_packets = parts[10].parse().unwrap_or(0);
-
-                    return Ok(aiva_core::NetworkIOMetrics {
-                        rx_bytes,
-                        tx_bytes,
-                        rx_packets,
-                        tx_packets,
-                    });
-                }
-            }
-        }
-
-        Ok(aiva_core::NetworkIOMetrics {
-            rx_bytes: 0,
-            tx_bytes: 0,
-            rx_packets: 0,
-            tx_packets: 0,
-        })
+        Ok(crate::metrics::interface_counters(tap_device).await?.into())
     }
 }
 
@@ -262,121 +659,203 @@ impl LinuxPlatform {
 impl Platform for LinuxPlatform {
     async fn create_vm(&self, instance: &VMInstance) -> Result<VMInstance> {
         self.check_kvm_available()?;
+        check_host_machine_config(
+            instance.config.cpu_template.as_deref(),
+            false,
+            instance.config.cpus,
+        )?;
 
         info!("Creating VM: {}", instance.name);
 
-        // Prepare jailer workspace
-        let workspace = self.prepare_jailer_workspace(instance).await?;
+        // Whatever a failed step leaves half set up is released again, so
+        // creating the VM can simply be retried
+        let mut acquired = CreateRollback::default();
+        match self.create_steps(instance, &mut acquired).await {
+            Ok(created) => {
+                info!("VM created successfully: {}", instance.name);
+                Ok(created)
+            }
+            Err(failure) => Err(acquired.fail(&instance.name, failure)),
+        }
+    }
 
-        // Spawn Firecracker process
-        let child = self.spawn_firecracker(&workspace, instance).await?;
-        let pid = child.id();
+    async fn start_vm(&self, instance: &VMInstance) -> Result<()> {
+        debug!("Starting VM: {}", instance.name);
 
-        // Configure VM through Firecracker API
-        let api_client = crate::firecracker::FirecrackerApiClient::new(
-            workspace.join("root").join("firecracker.socket"),
-        )?;
+        // For Linux/Firecracker, VMs are created in running state
+        // This would be used to resume a paused VM
 
-        // Configure machine
-        api_client
-            .configure_machine(instance.config.cpus, instance.config.memory_mb)
-            .await?;
+        if let Some(socket_path) = &instance.runtime.api_socket {
+            let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+            api_client.resume_vm().await?;
+        }
 
-        // Configure boot source
-        api_client
-            .configure_boot_source(
-                &PathBuf::from("/vmlinux"),
-                "console=ttyS0 reboot=k panic=1 pci=off",
-            )
-            .await?;
+        Ok(())
+    }
 
-        // Configure root drive
-        api_client
-            .configure_drive("rootfs", &PathBuf::from("/rootfs.ext4"), false, "Writeback")
-            .await?;
+    async fn pause_vm(&self, instance: &VMInstance) -> Result<()> {
+        self.api_client(instance)?.pause_vm().await
+    }
 
-        // Configure network
-        let tap_device = aiva_network::create_tap_device(&instance.name)?;
-        api_client
-            .configure_network("eth0", &tap_device, Some(&instance.config.network.guest_ip))
-            .await?;
+    async fn resume_vm(&self, instance: &VMInstance) -> Result<()> {
+        self.api_client(instance)?.resume_vm().await
+    }
 
-        // Start VM
-        api_client.start_instance().await?;
+    async fn snapshot_vm(
+        &self,
+        instance: &VMInstance,
+        snapshot_type: SnapshotType,
+    ) -> Result<SnapshotFiles> {
+        self.api_client(instance)?
+            .create_snapshot(
+                snapshot_type,
+                &Path::new("/").join(SNAPSHOT_MEMORY_FILE),
+                &Path::new("/").join(SNAPSHOT_STATE_FILE),
+            )
+            .await?;
 
-        // Update instance with runtime info
-        let mut updated_instance = instance.clone();
-        updated_instance.runtime.pid = Some(pid);
-        updated_instance.runtime.api_socket =
-            Some(workspace.join("root").join("firecracker.socket"));
-        updated_instance.runtime.tap_device = Some(tap_device);
-        updated_instance.state = VMState::Running;
+        let root = self.jailer_workspace(instance).join("root");
+        Ok(SnapshotFiles {
+            state: root.join(SNAPSHOT_STATE_FILE),
+            memory: root.join(SNAPSHOT_MEMORY_FILE),
+        })
+    }
 
-        info!("VM created successfully: {}", instance.name);
+    async fn hibernate_vm(&self, instance: &VMInstance) -> Result<SnapshotFiles> {
+        // Snapshots can only be taken of a paused VM; pausing twice is harmless
+        self.api_client(instance)?.pause_vm().await?;
+        let snapshot = self.snapshot_vm(instance, SnapshotType::Full).await?;
 
-        Ok(updated_instance)
+        // Everything the VM needs is on disk now
+        self.stop_vm(instance, true).await?;
+        Ok(snapshot)
     }
 
-    async fn start_vm(&self, instance: &VMInstance) -> Result<()> {
-        debug!("Starting VM: {}", instance.name);
+    async fn restore_vm(
+        &self,
+        instance: &VMInstance,
+        snapshot: &SnapshotFiles,
+    ) -> Result<VMInstance> {
+        self.check_kvm_available()?;
 
-        // For Linux/Firecracker, VMs are created in running state
-        // This would be used to resume a paused VM
+        info!(
+            "Restoring VM {} from {}",
+            instance.name,
+            snapshot.state.display()
+        );
 
-        if let Some(socket_path) = &instance.runtime.api_socket {
-            let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
-            api_client.resume_vm().await?;
+        let workspace = self.jailer_workspace(instance);
+        let socket_path = workspace.join("root").join("firecracker.socket");
+        // The killed VMM left its socket behind, which would pass for the new one
+        if socket_path.exists() {
+            std::fs::remove_file(&socket_path)?;
         }
 
-        Ok(())
+        let policy = self.vm_policy(instance).await?;
+        let child = self
+            .spawn_firecracker(&workspace, instance, policy.as_ref())
+            .await?;
+        let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+        api_client
+            .load_snapshot(
+                &Path::new("/").join(SNAPSHOT_STATE_FILE),
+                &Path::new("/").join(SNAPSHOT_MEMORY_FILE),
+                true,
+            )
+            .await?;
+
+        let mut restored = instance.clone();
+        restored.runtime.pid = Some(child.id());
+        restored.runtime.api_socket = Some(socket_path);
+        restored.state = VMState::Running;
+        Ok(restored)
     }
 
     async fn stop_vm(&self, instance: &VMInstance, force: bool) -> Result<()> {
+        self.shutdown_vm(instance, force).await?;
+        if let Some(pid) = instance.runtime.pid {
+            self.cpu_usage.forget(pid);
+        }
+        // The vsock socket goes away with the process
+        get_command_pool().unregister_vm(&instance.name).await
+    }
+
+    async fn shutdown_vm(&self, instance: &VMInstance, force: bool) -> Result<StopMethod> {
         debug!("Stopping VM: {} (force: {})", instance.name, force);
 
-        if let Some(socket_path) = &instance.runtime.api_socket {
-            let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+        let Some(pid) = instance.runtime.pid else {
+            // Nothing to wait for; ask the guest and trust it
+            if let Some(socket_path) = &instance.runtime.api_socket {
+                let api_client =
+                    crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+                api_client.shutdown_vm().await?;
+            }
+            return Ok(StopMethod::Graceful);
+        };
 
-            if force {
-                // Force shutdown
-                if let Some(pid) = instance.runtime.pid {
-                    use nix::sys::signal::{self, Signal};
-                    use nix::unistd::Pid;
-
-                    signal::kill(Pid::from_raw(pid as i32), Signal::SIGKILL).map_err(|e| {
-                        AivaError::PlatformError {
-                            platform: "linux".to_string(),
-                            message: format!("Failed to kill process: {e}"),
-                            recoverable: false,
-                        }
-                    })?;
+        if force {
+            send_signal(pid, nix::sys::signal::Signal::SIGKILL)?;
+            wait_for_exit(pid, PROCESS_EXIT_GRACE).await?;
+            return Ok(StopMethod::Killed);
+        }
+
+        let mut grace = self.shutdown_grace;
+        let asked = match &instance.runtime.api_socket {
+            Some(socket_path) => {
+                match crate::firecracker::FirecrackerApiClient::new(socket_path.clone()) {
+                    Ok(api_client) => api_client.shutdown_vm().await,
+                    Err(e) => Err(e),
                 }
-            } else {
-                // Graceful shutdown
-                api_client.shutdown_vm().await?;
             }
+            None => Err(AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: "no API socket recorded".to_string(),
+                recoverable: true,
+            }),
+        };
+        if let Err(e) = asked {
+            // Nobody is going to power the guest off, so don't wait for it
+            warn!("Failed to send Ctrl+Alt+Del to VM {}: {}", instance.name, e);
+            grace = Duration::ZERO;
         }
 
-        Ok(())
+        escalate_stop(pid, grace, PROCESS_EXIT_GRACE, firecracker_alive).await
     }
 
     async fn delete_vm(&self, instance: &VMInstance) -> Result<()> {
         debug!("Deleting VM: {}", instance.name);
 
-        // Remove jailer workspace
-        let workspace = PathBuf::from("/tmp")
-            .join("aiva-jailer")
-            .join(instance.id.to_string());
-        if workspace.exists() {
-            std::fs::remove_dir_all(&workspace)?;
+        // MCP servers run inside the guest and went away when it stopped,
+        // so there are no recorded PIDs to terminate on the host
+        for step in delete_steps(instance, &self.jailer_workspace(instance)) {
+            debug!("Delete {}: {}", instance.name, step);
+            match &step {
+                DeleteStep::ConfirmExited(pid) => {
+                    wait_for_exit(*pid, PROCESS_EXIT_GRACE).await?;
+                }
+                DeleteStep::RemoveTap(tap_device) => {
+                    if let Err(e) = aiva_network::remove_ip_block_rules(tap_device) {
+                        warn!("Failed to remove IP block rules: {}", e);
+                    }
+                    if instance.config.network.audit_egress
+                        && let Err(e) = aiva_network::remove_egress_log_rule(
+                            tap_device,
+                            &instance.config.network.guest_ip,
+                        )
+                    {
+                        warn!("Failed to remove egress log rule: {}", e);
+                    }
+                    aiva_network::delete_tap_device(tap_device)?;
+                }
+                DeleteStep::RemoveWorkspace(workspace) => remove_workspace(workspace)?,
+            }
         }
 
-        // Remove TAP device
-        if let Some(tap_device) = &instance.runtime.tap_device {
-            aiva_network::delete_tap_device(tap_device)?;
+        if let Some(pid) = instance.runtime.pid {
+            self.cpu_usage.forget(pid);
         }
-
-        Ok(())
+        // A VM created later under this name gets another CID and socket
+        get_command_pool().unregister_vm(&instance.name).await
     }
 
     async fn get_vm_metrics(&self, instance: &VMInstance) -> Result<VMMetrics> {
@@ -388,54 +867,55 @@ impl Platform for LinuxPlatform {
             instance.name
         );
 
-        // Get metrics from Firecracker API if available
-        if let Some(socket_path) = &instance.runtime.api_socket {
-            if socket_path.exists() {
-                let _api_client =
-                    crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
-
-                // Try to get metrics from Firecracker
-                // Note: This would need actual Firecracker metrics API implementation
-                // For now, we'll collect host-level metrics for the VM process
-
-                if let Some(pid) = instance.runtime.pid {
-                    // Get process metrics
-                    let cpu_usage = self.get_process_cpu_usage(pid).await?;
-                    let memory_metrics = self.get_process_memory_usage(pid).await?;
-                    let uptime = self.get_process_uptime(pid).await?;
-
-                    // Get network metrics if TAP device exists
-                    let network_metrics = if let Some(tap) = &instance.runtime.tap_device {
-                        self.get_tap_device_metrics(tap).await?
-                    } else {
-                        aiva_core::NetworkIOMetrics {
-                            rx_bytes: 0,
-                            tx_bytes: 0,
-                            rx_packets: 0,
-                            tx_packets: 0,
-                        }
-                    };
-
-                    logger
-                        .info(&format!(
-                            "Metrics collected: CPU {:.1}%, Memory {}/{} MB",
-                            cpu_usage, memory_metrics.used_mb, memory_metrics.total_mb
-                        ))
-                        .await?;
-
-                    return Ok(VMMetrics {
-                        cpu_usage,
-                        memory_usage: memory_metrics,
-                        disk_io: aiva_core::DiskIOMetrics {
+        if let Some(socket_path) = &instance.runtime.api_socket
+            && socket_path.exists()
+        {
+            // Device I/O as the VMM counts it; CPU and memory still come
+            // from the host process
+            let devices = self.firecracker_counters(instance).await;
+
+            if let Some(pid) = instance.runtime.pid {
+                // Get process metrics
+                let cpu_usage = self.cpu_usage.cpu_usage(pid).await?;
+                let memory_metrics = self.get_process_memory_usage(pid).await?;
+                let uptime = self.get_process_uptime(pid).await?;
+
+                let (disk_io, network_io) = match devices {
+                    Some(devices) => (devices.disk_io(), devices.network_io()),
+                    None => {
+                        let network_io = match &instance.runtime.tap_device {
+                            Some(tap) => self.get_tap_device_metrics(tap).await?,
+                            None => aiva_core::NetworkIOMetrics {
+                                rx_bytes: 0,
+                                tx_bytes: 0,
+                                rx_packets: 0,
+                                tx_packets: 0,
+                            },
+                        };
+                        let disk_io = aiva_core::DiskIOMetrics {
                             read_bytes: 0,
                             write_bytes: 0,
                             read_ops: 0,
                             write_ops: 0,
-                        },
-                        network_io: network_metrics,
-                        uptime,
-                    });
-                }
+                        };
+                        (disk_io, network_io)
+                    }
+                };
+
+                logger
+                    .info(&format!(
+                        "Metrics collected: CPU {:.1}%, Memory {}/{} MB",
+                        cpu_usage, memory_metrics.used_mb, memory_metrics.total_mb
+                    ))
+                    .await?;
+
+                return Ok(VMMetrics {
+                    cpu_usage,
+                    memory_usage: memory_metrics,
+                    disk_io,
+                    network_io,
+                    uptime,
+                });
             }
         }
 
@@ -493,42 +973,21 @@ impl Platform for LinuxPlatform {
 
         // If not registered, register it now
         if !command_pool.is_registered(&instance.name).await {
-            // Try to use vsock first, then fallback to network
-            let connection_type = if self.check_vsock_support() {
-                // For vsock, we need the VM's context ID (CID)
-                // In a real implementation, this would be obtained from Firecracker
-                let cid = 3; // Default guest CID, in production this would be dynamic
-                ConnectionType::Vsock { cid }
-            } else {
-                // Use network connection through guest IP
-                ConnectionType::Network {
-                    host: instance.config.network.guest_ip.clone(),
-                    port: VSOCK_COMMAND_PORT as u16,
-                }
-            };
-
-            // Register the VM with the command pool
-            if let Err(e) = command_pool
-                .register_vm(instance.name.clone(), connection_type)
-                .await
-            {
-                warn!("Failed to register VM in command pool: {}", e);
-
-                // Fallback to SSH if available
-                if let Some(port) = instance.config.network.port_mappings.first() {
-                    let ssh_connection = ConnectionType::Ssh {
-                        host: "localhost".to_string(),
-                        port: port.host_port,
-                        key_path: None,
-                    };
-
-                    command_pool
-                        .register_vm(instance.name.clone(), ssh_connection)
-                        .await?;
-                } else {
-                    return Err(e);
-                }
-            }
+            let vsock_uds = self.vsock_uds_path(instance);
+            let candidates = instance
+                .config
+                .execution
+                .transport_order(LINUX_TRANSPORTS)?
+                .into_iter()
+                .filter_map(|transport| {
+                    connection_for(transport, instance, Some(vsock_uds.clone()))
+                        .map(|connection| (transport, connection))
+                })
+                .collect();
+
+            command_pool
+                .register_in_order(&instance.name, candidates)
+                .await?;
         }
 
         // Execute the command through the command pool
@@ -545,6 +1004,102 @@ impl Platform for LinuxPlatform {
         Ok(output)
     }
 
+    async fn resize_disk(&self, instance: &VMInstance, disk_gb: u64) -> Result<()> {
+        let rootfs = self
+            .jailer_workspace(instance)
+            .join("root")
+            .join("rootfs.ext4");
+        if !rootfs.exists() {
+            // Nothing created yet, the next create_vm uses the new size
+            return Ok(());
+        }
+
+        let running = instance.state == VMState::Running;
+        grow_rootfs(&rootfs, disk_gb, !running)?;
+
+        if running && let Some(socket_path) = &instance.runtime.api_socket {
+            let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+            api_client
+                .rescan_drive("rootfs", &PathBuf::from("/rootfs.ext4"))
+                .await?;
+            self.execute_command(instance, ONLINE_RESIZE_COMMAND)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn attach_drive(
+        &self,
+        instance: &VMInstance,
+        drive_id: &str,
+        drive: &BlockDevice,
+    ) -> Result<()> {
+        let Some(socket_path) = &instance.runtime.api_socket else {
+            return Err(AivaError::VMError {
+                vm_name: instance.name.clone(),
+                state: instance.state,
+                message: "No API socket to attach a drive through".to_string(),
+            });
+        };
+        let root_dir = self.jailer_workspace(instance).join("root");
+        link_drive_into_jail(&root_dir, drive_id, drive)?;
+
+        let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+        let attached = api_client
+            .configure_drive(
+                drive_id,
+                &jailed_drive_path(drive_id),
+                drive.read_only,
+                firecracker_cache_type(instance.config.storage.cache_strategy),
+            )
+            .await;
+        if let Err(e) = attached {
+            let _ = std::fs::remove_file(root_dir.join(format!("{drive_id}.img")));
+            // Firecracker only takes new drives before boot
+            return Err(AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: format!(
+                    "Firecracker cannot attach {} to running VM {}: {}; stop the VM and attach it again",
+                    drive.path.display(),
+                    instance.name,
+                    e
+                ),
+                recoverable: true,
+            });
+        }
+        Ok(())
+    }
+
+    async fn publish_metadata(&self, instance: &VMInstance, metadata: &VMMetadata) -> Result<()> {
+        let Some(socket_path) = &instance.runtime.api_socket else {
+            return Ok(());
+        };
+        let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+        api_client.set_mmds_data(metadata.to_store()).await
+    }
+
+    async fn scale_capabilities(&self, instance: &VMInstance) -> Result<ScaleCapabilities> {
+        let Some(socket_path) = &instance.runtime.api_socket else {
+            return Ok(ScaleCapabilities::default());
+        };
+        let api_client = crate::firecracker::FirecrackerApiClient::new(socket_path.clone())?;
+        Ok(scale_capabilities_from_vm_config(
+            &api_client.get_vm_config().await?,
+        ))
+    }
+
+    async fn apply_scale_step(&self, instance: &VMInstance, step: &ScaleStep) -> Result<()> {
+        let ScaleStep::Balloon { balloon_mib, .. } = step else {
+            return Err(AivaError::NotImplemented(
+                "vCPU hotplug with Firecracker".to_string(),
+            ));
+        };
+        self.api_client(instance)?
+            .update_balloon(*balloon_mib)
+            .await
+    }
+
     async fn check_requirements(&self) -> Result<()> {
         // Check KVM
         self.check_kvm_available()?;
@@ -573,6 +1128,21 @@ impl Platform for LinuxPlatform {
             });
         }
 
+        // A binary or kernel for another architecture only fails at boot
+        let host = Architecture::host().ok_or_else(|| AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message: format!(
+                "Firecracker does not support the {} architecture",
+                std::env::consts::ARCH
+            ),
+            recoverable: false,
+        })?;
+        check_architecture("Firecracker binary", &self.firecracker_path, host)?;
+        // The kernel may not be installed yet when checking a fresh host
+        if self.kernel_path.exists() {
+            check_architecture("Kernel image", &self.kernel_path, host)?;
+        }
+
         info!("Linux platform requirements satisfied");
 
         Ok(())
@@ -581,4 +1151,799 @@ impl Platform for LinuxPlatform {
     fn name(&self) -> &str {
         "linux"
     }
+
+    fn plan_create(&self, instance: &VMInstance) -> PlatformPlan {
+        let root = self.jailer_workspace(instance).join("root");
+        let config = &instance.config;
+
+        PlatformPlan {
+            image_copies: vec![
+                ImageCopy::new(config.kernel_path.clone(), root.join("vmlinux")),
+                ImageCopy::new(config.rootfs_path.clone(), root.join("rootfs.ext4")),
+            ],
+            resources: vec![
+                format!(
+                    "TAP device {}",
+                    aiva_network::tap_device_name(&instance.name)
+                ),
+                format!(
+                    "Firecracker process under jailer ({} vCPUs, {} MB)",
+                    config.cpus, config.memory_mb
+                ),
+                format!("API socket {}", root.join("firecracker.socket").display()),
+            ],
+        }
+    }
+
+    async fn vm_liveness(&self, instance: &VMInstance) -> Liveness {
+        match instance.runtime.pid {
+            Some(pid) if firecracker_alive(pid) => Liveness::Alive,
+            Some(_) => Liveness::Dead,
+            None => Liveness::Unknown,
+        }
+    }
+
+    async fn cleanup_failed_vm(&self, instance: &VMInstance) -> Result<Vec<String>> {
+        let mut cleaned = Vec::new();
+
+        // A Firecracker process can outlive a failed API call
+        if let Some(pid) = instance.runtime.pid
+            && PathBuf::from(format!("/proc/{pid}")).exists()
+        {
+            use nix::sys::signal::{self, Signal};
+            use nix::unistd::Pid;
+
+            match signal::kill(Pid::from_raw(pid as i32), Signal::SIGKILL) {
+                Ok(()) => cleaned.push(format!("killed dangling Firecracker process {pid}")),
+                Err(e) => warn!("Failed to kill Firecracker process {}: {}", pid, e),
+            }
+        }
+
+        // The rules are added before the TAP device is recorded in the runtime
+        let tap_name = aiva_network::tap_device_name(&instance.name);
+        let _ = aiva_network::remove_ip_block_rules(&tap_name);
+        if instance.config.network.audit_egress {
+            let _ =
+                aiva_network::remove_egress_log_rule(&tap_name, &instance.config.network.guest_ip);
+        }
+
+        if let Some(tap_device) = &instance.runtime.tap_device {
+            match aiva_network::delete_tap_device(tap_device) {
+                Ok(()) => cleaned.push(format!("deleted TAP device {tap_device}")),
+                Err(e) => warn!("Failed to delete TAP device {}: {}", tap_device, e),
+            }
+        }
+
+        let workspace = self.jailer_workspace(instance);
+        if workspace.exists() {
+            std::fs::remove_dir_all(&workspace)?;
+            cleaned.push(format!("removed jailer workspace {}", workspace.display()));
+        }
+
+        Ok(cleaned)
+    }
+
+    async fn orphaned_resources(&self, known: &[VMInstance]) -> Result<Vec<OrphanedResource>> {
+        // A TAP device with a carrier has a Firecracker process attached
+        let taps = host_tap_devices()?
+            .into_iter()
+            .filter(|tap| !tap_in_use(tap))
+            .collect();
+        let workspaces = settled_workspaces(&self.jailer.chroot_base_dir, ORPHAN_MIN_AGE)?;
+        Ok(find_orphans(known, taps, workspaces))
+    }
+
+    async fn remove_orphan(&self, resource: &OrphanedResource) -> Result<()> {
+        match resource {
+            OrphanedResource::TapDevice(tap_device) => aiva_network::delete_tap_device(tap_device),
+            OrphanedResource::Workspace(path) => remove_workspace(path),
+        }
+    }
+}
+
+/// Whether `pid` is a live Firecracker or jailer process. The recorded PID
+/// outlives the VM, so a process that merely reuses it does not count.
+fn firecracker_alive(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/comm"))
+        .map(|comm| matches!(comm.trim(), "firecracker" | "jailer"))
+        .unwrap_or(false)
+        && process_running(pid)
+}
+
+/// Whether `pid` exists and has not exited. A child of this process that
+/// exited stays in `/proc` as a zombie until it is reaped.
+pub(crate) fn process_running(pid: u32) -> bool {
+    std::fs::read_to_string(format!("/proc/{pid}/stat"))
+        .ok()
+        .and_then(|stat| {
+            let (_, rest) = stat.rsplit_once(')')?;
+            rest.split_whitespace()
+                .next()
+                .map(|state| state.to_string())
+        })
+        .is_some_and(|state| !matches!(state.as_str(), "Z" | "X"))
+}
+
+/// Hard `RLIMIT_NOFILE` of this process, the most a VMM it spawns can get
+pub(crate) fn host_open_files_hard_limit() -> Result<u64> {
+    let (_, hard) = getrlimit(Resource::RLIMIT_NOFILE).map_err(|e| AivaError::PlatformError {
+        platform: "linux".to_string(),
+        message: format!("Failed to read the open files limit: {e}"),
+        recoverable: false,
+    })?;
+    Ok(hard)
+}
+
+/// Give the process `command` spawns `limit` as its `RLIMIT_NOFILE`, set
+/// between fork and exec so it holds from the first instruction
+pub(crate) fn limit_open_files_on_exec(command: &mut Command, limit: OpenFilesLimit) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: the hook only calls setrlimit, which is async-signal-safe, and
+    // allocates nothing
+    unsafe {
+        command.pre_exec(move || {
+            setrlimit(Resource::RLIMIT_NOFILE, limit.soft, limit.hard).map_err(std::io::Error::from)
+        });
+    }
+}
+
+/// `RLIMIT_NOFILE` of a running process, `None` if it cannot be read
+pub(crate) fn read_open_files_limit(pid: u32) -> Option<OpenFilesLimit> {
+    let limits = std::fs::read_to_string(format!("/proc/{pid}/limits")).ok()?;
+    parse_open_files_limit(&limits)
+}
+
+/// Step of creating a VM, named in the error when it fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CreateStep {
+    PrepareWorkspace,
+    SpawnFirecracker,
+    ConfigureMachine,
+    ConfigureBootSource,
+    ConfigureDrive,
+    ConfigureBalloon,
+    ConfigureVsock,
+    CreateTap,
+    ConfigureNetwork,
+    ConfigureMmds,
+    StartInstance,
+}
+
+impl std::fmt::Display for CreateStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::PrepareWorkspace => "prepare jailer workspace",
+            Self::SpawnFirecracker => "spawn Firecracker",
+            Self::ConfigureMachine => "configure machine",
+            Self::ConfigureBootSource => "configure boot source",
+            Self::ConfigureDrive => "configure drive",
+            Self::ConfigureBalloon => "configure balloon",
+            Self::ConfigureVsock => "configure vsock device",
+            Self::CreateTap => "create TAP device",
+            Self::ConfigureNetwork => "configure network",
+            Self::ConfigureMmds => "configure metadata service",
+            Self::StartInstance => "start instance",
+        })
+    }
+}
+
+/// A create step and the error it failed with
+#[derive(Debug)]
+pub(crate) struct StepFailure {
+    pub(crate) step: CreateStep,
+    pub(crate) error: AivaError,
+}
+
+impl StepFailure {
+    fn at(step: CreateStep, error: AivaError) -> Self {
+        Self { step, error }
+    }
+}
+
+/// Host resources a create has acquired so far
+#[derive(Debug, Default)]
+pub(crate) struct CreateRollback {
+    pub(crate) child: Option<std::process::Child>,
+    pub(crate) tap_device: Option<String>,
+    /// TAP device and guest address the egress log rule was added for
+    pub(crate) egress_rule: Option<(String, String)>,
+    /// TAP device the policy's IP block rules were added for
+    pub(crate) ip_block_rules: Option<String>,
+    pub(crate) workspace: Option<PathBuf>,
+}
+
+impl CreateRollback {
+    /// Release everything acquired, the process first so it cannot hold the
+    /// TAP device or write to the workspace, and turn `failure` into the
+    /// error create returns. Resources that are already gone are skipped.
+    pub(crate) fn fail(mut self, vm_name: &str, failure: StepFailure) -> AivaError {
+        warn!(
+            "Creating VM {} failed at {}, rolling back: {}",
+            vm_name, failure.step, failure.error
+        );
+        let mut leftovers = Vec::new();
+
+        if let Some(mut child) = self.child.take() {
+            let pid = child.id();
+            // Killing a process that already exited fails; reaping it does not
+            let _ = child.kill();
+            match child.wait() {
+                Ok(_) => info!("Rollback: stopped Firecracker process {}", pid),
+                Err(e) => {
+                    warn!("Rollback: Firecracker process {} did not exit: {}", pid, e);
+                    leftovers.push(format!("Firecracker process {pid}"));
+                }
+            }
+        }
+
+        if let Some((tap_device, guest_ip)) = self.egress_rule.take()
+            && let Err(e) = aiva_network::remove_egress_log_rule(&tap_device, &guest_ip)
+        {
+            warn!("Rollback: failed to remove egress log rule: {}", e);
+            leftovers.push(format!("egress log rule of {tap_device}"));
+        }
+
+        if let Some(tap_device) = self.ip_block_rules.take()
+            && let Err(e) = aiva_network::remove_ip_block_rules(&tap_device)
+        {
+            warn!("Rollback: failed to remove IP block rules: {}", e);
+            leftovers.push(format!("IP block rules of {tap_device}"));
+        }
+
+        if let Some(tap_device) = self.tap_device.take() {
+            match aiva_network::delete_tap_device(&tap_device) {
+                Ok(()) => info!("Rollback: deleted TAP device {}", tap_device),
+                Err(e) => {
+                    warn!(
+                        "Rollback: failed to delete TAP device {}: {}",
+                        tap_device, e
+                    );
+                    leftovers.push(format!("TAP device {tap_device}"));
+                }
+            }
+        }
+
+        if let Some(workspace) = self.workspace.take()
+            && let Err(e) = remove_workspace(&workspace)
+        {
+            warn!("Rollback: {}", e);
+            leftovers.push(format!("jailer workspace {}", workspace.display()));
+        }
+
+        let mut message = format!(
+            "Creating VM {vm_name} failed at {}: {}",
+            failure.step, failure.error
+        );
+        if !leftovers.is_empty() {
+            message.push_str(&format!("; left behind: {}", leftovers.join(", ")));
+        }
+        AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message,
+            // Nothing is left in the way of creating the VM again
+            recoverable: leftovers.is_empty(),
+        }
+    }
+}
+
+/// Host side of the vsock device, relative to the jail root
+pub(crate) const VSOCK_UDS_PATH: &str = "/vsock.sock";
+
+/// iptables rules enforcing the blocked ranges of `policy` for `instance`,
+/// which decide with the VM's own network what stays reachable
+pub(crate) fn ip_block_rules(
+    instance: &VMInstance,
+    policy: &NetworkPolicy,
+    tap_device: &str,
+) -> Result<Vec<Vec<String>>> {
+    let plan = aiva_security::plan_network_policy(policy, Some(&instance.config.network))?;
+    for conflict in &plan.conflicts {
+        warn!(
+            "Blocked range {} for VM {}; keeping it reachable",
+            conflict, instance.name
+        );
+    }
+    Ok(aiva_network::ip_block_rule_args(
+        "-I",
+        tap_device,
+        &plan.always_allowed,
+        &plan.blocked,
+    ))
+}
+
+/// Configure the Firecracker behind `api_client` for `instance` and boot it,
+/// enforcing the blocked ranges of `network_policy` on its TAP device.
+/// Returns the TAP device, which is recorded in `acquired` once created.
+/// Every call may be repeated on a fresh process, so the sequence is safe to
+/// run again after a rollback.
+pub(crate) async fn configure_and_boot(
+    api_client: &crate::firecracker::FirecrackerApiClient,
+    instance: &VMInstance,
+    network_policy: Option<&NetworkPolicy>,
+    acquired: &mut CreateRollback,
+) -> std::result::Result<String, StepFailure> {
+    let config = &instance.config;
+    let at = |step| move |e| StepFailure::at(step, e);
+
+    api_client
+        .configure_machine(
+            config.cpus,
+            config.memory_mb,
+            config.cpu_template.as_deref(),
+            false,
+        )
+        .await
+        .map_err(at(CreateStep::ConfigureMachine))?;
+    api_client
+        .configure_boot_source(
+            &PathBuf::from("/vmlinux"),
+            &config.kernel_command_line(DEFAULT_BOOT_ARGS),
+        )
+        .await
+        .map_err(at(CreateStep::ConfigureBootSource))?;
+    let cache_type = firecracker_cache_type(config.storage.cache_strategy);
+    api_client
+        .configure_drive("rootfs", &PathBuf::from("/rootfs.ext4"), false, cache_type)
+        .await
+        .map_err(at(CreateStep::ConfigureDrive))?;
+    for (drive_id, drive) in config.storage.drives() {
+        api_client
+            .configure_drive(
+                &drive_id,
+                &jailed_drive_path(&drive_id),
+                drive.read_only,
+                cache_type,
+            )
+            .await
+            .map_err(at(CreateStep::ConfigureDrive))?;
+    }
+    // Balloon for resizing memory while running (aiva scale)
+    api_client
+        .configure_balloon(0, true, BALLOON_STATS_INTERVAL_S)
+        .await
+        .map_err(at(CreateStep::ConfigureBalloon))?;
+    // VMs created before CIDs were assigned go without vsock
+    if let Some(cid) = instance.runtime.vsock_cid {
+        api_client
+            .configure_vsock(cid, Path::new(VSOCK_UDS_PATH))
+            .await
+            .map_err(at(CreateStep::ConfigureVsock))?;
+    }
+
+    let tap_device =
+        aiva_network::create_tap_device(&instance.name).map_err(at(CreateStep::CreateTap))?;
+    acquired.tap_device = Some(tap_device.clone());
+    if let Some(policy) = network_policy.filter(|policy| !policy.blocked_ips.is_empty()) {
+        let rules =
+            ip_block_rules(instance, policy, &tap_device).map_err(at(CreateStep::CreateTap))?;
+        // Drop the rules of an earlier attempt rather than add them twice
+        let _ = aiva_network::remove_ip_block_rules(&tap_device);
+        aiva_network::add_ip_block_rules(&rules).map_err(at(CreateStep::CreateTap))?;
+        acquired.ip_block_rules = Some(tap_device.clone());
+    }
+    if config.network.audit_egress {
+        // Drop the rule of an earlier attempt rather than log twice
+        let _ = aiva_network::remove_egress_log_rule(&tap_device, &config.network.guest_ip);
+        aiva_network::add_egress_log_rule(&tap_device, &config.network.guest_ip)
+            .map_err(at(CreateStep::CreateTap))?;
+        acquired.egress_rule = Some((tap_device.clone(), config.network.guest_ip.clone()));
+    }
+    api_client
+        .configure_network("eth0", &tap_device, Some(&config.network.guest_ip))
+        .await
+        .map_err(at(CreateStep::ConfigureNetwork))?;
+
+    // Metadata the guest reads about itself, see aiva_core::metadata
+    api_client
+        .configure_mmds("V2", &["eth0".to_string()])
+        .await
+        .map_err(at(CreateStep::ConfigureMmds))?;
+    api_client
+        .set_mmds_data(VMMetadata::from_instance(instance).to_store())
+        .await
+        .map_err(at(CreateStep::ConfigureMmds))?;
+
+    api_client
+        .start_instance()
+        .await
+        .map_err(at(CreateStep::StartInstance))?;
+    Ok(tap_device)
+}
+
+/// How long a stopped Firecracker gets to exit before delete gives up, and
+/// how long a signalled one gets before the next signal
+const PROCESS_EXIT_GRACE: Duration = Duration::from_secs(2);
+
+/// Longest a guest may take to power off. With both signals on top it
+/// keeps a stop within the orchestrator's 30 second timeout.
+pub(crate) const MAX_SHUTDOWN_GRACE: Duration = Duration::from_secs(25);
+
+/// One step of deleting a VM. Steps run in the order [`delete_steps`]
+/// returns them and each tolerates its resource being gone already.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum DeleteStep {
+    /// The Firecracker process must be gone, or it may recreate files in
+    /// the workspace or keep the TAP device open
+    ConfirmExited(u32),
+    RemoveTap(String),
+    RemoveWorkspace(PathBuf),
+}
+
+impl std::fmt::Display for DeleteStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConfirmExited(pid) => write!(f, "confirm Firecracker process {pid} exited"),
+            Self::RemoveTap(tap_device) => write!(f, "remove TAP device {tap_device}"),
+            Self::RemoveWorkspace(path) => write!(f, "remove jailer workspace {}", path.display()),
+        }
+    }
+}
+
+/// Steps deleting `instance`: the process first, then the TAP device, then
+/// the workspace
+pub(crate) fn delete_steps(instance: &VMInstance, workspace: &Path) -> Vec<DeleteStep> {
+    let mut steps = Vec::new();
+    if let Some(pid) = instance.runtime.pid {
+        steps.push(DeleteStep::ConfirmExited(pid));
+    }
+    if let Some(tap_device) = &instance.runtime.tap_device {
+        steps.push(DeleteStep::RemoveTap(tap_device.clone()));
+    }
+    steps.push(DeleteStep::RemoveWorkspace(workspace.to_path_buf()));
+    steps
+}
+
+/// Wait up to `grace` for the Firecracker process `pid` to exit
+pub(crate) async fn wait_for_exit(pid: u32, grace: Duration) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + grace;
+    while firecracker_alive(pid) {
+        if tokio::time::Instant::now() >= deadline {
+            return Err(AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: format!(
+                    "Firecracker process {pid} is still running; stop the VM with --force \
+                     before deleting it"
+                ),
+                recoverable: true,
+            });
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+}
+
+/// Stop `pid`, which was asked to exit: wait `grace` for it, then send
+/// SIGTERM and wait `signal_grace`, then SIGKILL and wait again. `alive`
+/// tells whether it is still running. Returns the step that worked.
+pub(crate) async fn escalate_stop(
+    pid: u32,
+    grace: Duration,
+    signal_grace: Duration,
+    alive: fn(u32) -> bool,
+) -> Result<StopMethod> {
+    use nix::sys::signal::Signal;
+
+    if exited_within(pid, grace, alive).await {
+        return Ok(StopMethod::Graceful);
+    }
+    info!(
+        "Process {} still running after {}ms, sending SIGTERM",
+        pid,
+        grace.as_millis()
+    );
+    send_signal(pid, Signal::SIGTERM)?;
+    if exited_within(pid, signal_grace, alive).await {
+        return Ok(StopMethod::Terminated);
+    }
+    warn!("Process {} ignored SIGTERM, sending SIGKILL", pid);
+    send_signal(pid, Signal::SIGKILL)?;
+    if exited_within(pid, signal_grace, alive).await {
+        return Ok(StopMethod::Killed);
+    }
+    Err(AivaError::PlatformError {
+        platform: "linux".to_string(),
+        message: format!("Process {pid} is still running after SIGKILL"),
+        recoverable: false,
+    })
+}
+
+/// Whether `alive` stops holding for `pid` within `grace`
+async fn exited_within(pid: u32, grace: Duration, alive: fn(u32) -> bool) -> bool {
+    let deadline = tokio::time::Instant::now() + grace;
+    while alive(pid) {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    true
+}
+
+/// Send `signal` to `pid`; one that already exited is not an error
+fn send_signal(pid: u32, signal: nix::sys::signal::Signal) -> Result<()> {
+    use nix::errno::Errno;
+    use nix::unistd::Pid;
+
+    match nix::sys::signal::kill(Pid::from_raw(pid as i32), signal) {
+        Ok(()) | Err(Errno::ESRCH) => Ok(()),
+        Err(e) => Err(AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message: format!("Failed to send {signal} to process {pid}: {e}"),
+            recoverable: false,
+        }),
+    }
+}
+
+/// Remove the jailer workspace at `path`, which may be gone already
+pub(crate) fn remove_workspace(path: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => {
+            info!("Removed jailer workspace {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            debug!("Jailer workspace {} already removed", path.display());
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// User and group Firecracker runs as under `config`, defaulting to the
+/// user running aiva, `invoking`, and to the primary group of a configured
+/// user
+pub(crate) fn jailer_owner(
+    config: &JailerConfig,
+    invoking: (u32, u32),
+    primary_gid: impl Fn(u32) -> Option<u32>,
+) -> (u32, u32) {
+    let uid = config.uid.unwrap_or(invoking.0);
+    let gid = match (config.gid, config.uid) {
+        (Some(gid), _) => gid,
+        (None, Some(uid)) => primary_gid(uid).unwrap_or(uid),
+        (None, None) => invoking.1,
+    };
+    (uid, gid)
+}
+
+/// The user running aiva, or under sudo the user who ran it
+fn invoking_user() -> (u32, u32) {
+    let uid = nix::unistd::getuid();
+    if !uid.is_root() {
+        return (uid.as_raw(), nix::unistd::getgid().as_raw());
+    }
+    let from_env = |name| std::env::var(name).ok().and_then(|id| id.parse().ok());
+    match (from_env("SUDO_UID"), from_env("SUDO_GID")) {
+        (Some(uid), Some(gid)) => (uid, gid),
+        _ => (DEFAULT_JAILER_ID, DEFAULT_JAILER_ID),
+    }
+}
+
+/// Path Firecracker sees additional drive `drive_id` at inside the jail
+fn jailed_drive_path(drive_id: &str) -> PathBuf {
+    PathBuf::from(format!("/{drive_id}.img"))
+}
+
+/// Hard-link `drive` into the jail root `root_dir`. A link rather than a
+/// copy keeps the guest writing to the volume itself, which requires the
+/// volume to be on the same filesystem as the jail.
+fn link_drive_into_jail(root_dir: &Path, drive_id: &str, drive: &BlockDevice) -> Result<()> {
+    let link = root_dir.join(format!("{drive_id}.img"));
+    // Left by an earlier start, possibly for another volume
+    match std::fs::remove_file(&link) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    std::fs::hard_link(&drive.path, &link).map_err(|e| AivaError::PlatformError {
+        platform: "linux".to_string(),
+        message: format!(
+            "Cannot link {} into the jail at {}: {}; drives must be on the same filesystem as the jail",
+            drive.path.display(),
+            link.display(),
+            e
+        ),
+        recoverable: false,
+    })
+}
+
+/// Memory of a process from its `/proc/<pid>/status`: the virtual size as
+/// total and the resident set as used
+pub(crate) fn parse_process_memory(status: &str) -> aiva_core::MemoryMetrics {
+    let mut vm_size_kb = 0u64;
+    let mut vm_rss_kb = 0u64;
+
+    for line in status.lines() {
+        if line.starts_with("VmSize:") {
+            vm_size_kb = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+        } else if line.starts_with("VmRSS:") {
+            vm_rss_kb = line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0);
+        }
+    }
+
+    aiva_core::MemoryMetrics::from_kb(vm_size_kb, vm_rss_kb)
+}
+
+/// Make the metrics FIFO in the jail root of `workspace`, replacing one an
+/// earlier process left, and hand it to `owner`, whom Firecracker runs as
+fn create_metrics_fifo(workspace: &Path, (uid, gid): (u32, u32)) -> Result<PathBuf> {
+    let root = workspace.join("root");
+    std::fs::create_dir_all(&root)?;
+    let fifo = root.join(METRICS_FIFO_FILE);
+    if fifo.exists() {
+        std::fs::remove_file(&fifo)?;
+    }
+    let io_error = |e: nix::Error| AivaError::IoError(std::io::Error::from(e));
+    nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::from_bits_truncate(0o600))
+        .map_err(io_error)?;
+    let owner = nix::unistd::chown(
+        &fifo,
+        Some(nix::unistd::Uid::from_raw(uid)),
+        Some(nix::unistd::Gid::from_raw(gid)),
+    );
+    if let Err(e) = owner {
+        let _ = std::fs::remove_file(&fifo);
+        return Err(io_error(e));
+    }
+    Ok(fifo)
+}
+
+/// aiva's TAP devices on the host
+fn host_tap_devices() -> Result<Vec<String>> {
+    let prefix = aiva_network::tap_device_name("");
+    let entries = match std::fs::read_dir("/sys/class/net") {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with(&prefix))
+        .collect())
+}
+
+fn tap_in_use(tap_device: &str) -> bool {
+    std::fs::read_to_string(format!("/sys/class/net/{tap_device}/carrier"))
+        .is_ok_and(|carrier| carrier.trim() == "1")
+}
+
+/// Workspaces under `root` left untouched for at least `min_age`
+pub(crate) fn settled_workspaces(root: &Path, min_age: Duration) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut workspaces = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let age = metadata.modified()?.elapsed().unwrap_or(Duration::ZERO);
+        if metadata.is_dir() && age >= min_age {
+            workspaces.push(entry.path());
+        }
+    }
+    workspaces.sort();
+    Ok(workspaces)
+}
+
+/// The TAP devices and workspaces among `taps` and `workspaces` that none of
+/// `known` owns
+pub(crate) fn find_orphans(
+    known: &[VMInstance],
+    taps: Vec<String>,
+    workspaces: Vec<PathBuf>,
+) -> Vec<OrphanedResource> {
+    let owned_taps: Vec<String> = known
+        .iter()
+        .flat_map(|vm| {
+            [
+                vm.runtime.tap_device.clone(),
+                Some(aiva_network::tap_device_name(&vm.name)),
+            ]
+        })
+        .flatten()
+        .collect();
+    let owned_workspaces: Vec<String> = known.iter().map(|vm| vm.id.to_string()).collect();
+
+    let orphaned_taps = taps
+        .into_iter()
+        .filter(|tap| !owned_taps.contains(tap))
+        .map(OrphanedResource::TapDevice);
+    let orphaned_workspaces = workspaces
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_none_or(|name| !owned_workspaces.iter().any(|id| id == name))
+        })
+        .map(OrphanedResource::Workspace);
+    orphaned_taps.chain(orphaned_workspaces).collect()
+}
+
+/// Live scaling support of a VM, from Firecracker's `GET /vm/config`. Memory
+/// can move up to the boot size when a balloon device is attached; vCPUs are
+/// fixed at boot.
+pub(crate) fn scale_capabilities_from_vm_config(config: &serde_json::Value) -> ScaleCapabilities {
+    let has_balloon = config.get("balloon").is_some_and(|b| !b.is_null());
+    let boot_memory = config
+        .pointer("/machine-config/mem_size_mib")
+        .and_then(serde_json::Value::as_u64);
+
+    ScaleCapabilities {
+        balloon_max_mb: boot_memory.filter(|_| has_balloon),
+        cpu_hotplug: false,
+    }
+}
+
+/// Grow a rootfs image to `disk_gb`. The filesystem is resized right away
+/// when `offline`; a mounted one is grown from inside the guest instead.
+pub(crate) fn grow_rootfs(path: &Path, disk_gb: u64, offline: bool) -> Result<()> {
+    let size = std::fs::metadata(path)?.len();
+    aiva_core::check_rootfs_fits(size, disk_gb)?;
+
+    let target = disk_gb * aiva_core::GIB;
+    if target == size {
+        return Ok(());
+    }
+
+    debug!("Growing {} to {}GB", path.display(), disk_gb);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(target)?;
+
+    if !offline {
+        return Ok(());
+    }
+
+    // e2fsck exits with 1 when it corrected errors, which is fine here
+    let fsck = Command::new("e2fsck")
+        .args(["-f", "-y"])
+        .arg(path)
+        .output()?;
+    if fsck.status.code().is_none_or(|code| code > 1) {
+        return Err(resize_error("e2fsck", &fsck.stderr));
+    }
+
+    let resize = Command::new("resize2fs").arg(path).output()?;
+    if !resize.status.success() {
+        return Err(resize_error("resize2fs", &resize.stderr));
+    }
+
+    Ok(())
+}
+
+fn resize_error(tool: &str, stderr: &[u8]) -> AivaError {
+    AivaError::PlatformError {
+        platform: "linux".to_string(),
+        message: format!(
+            "{tool} failed while growing the rootfs: {}",
+            String::from_utf8_lossy(stderr).trim()
+        ),
+        recoverable: false,
+    }
+}
+
+fn run_sudo(args: &[&str]) -> Result<()> {
+    info!("Running: sudo {}", args.join(" "));
+    let status = Command::new("sudo").args(args).status()?;
+
+    if !status.success() {
+        return Err(AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message: format!("'sudo {}' failed with {}", args.join(" "), status),
+            recoverable: true,
+        });
+    }
+
+    Ok(())
 }
@@ -0,0 +1,312 @@
+//! Host side of guest log shipping.
+//!
+//! The guest agent runs `tail -n 0 -F <paths...>` and streams its stdout back
+//! over the command channel. When more than one file is followed, tail marks
+//! each switch of source with a `==> <path> <==` header; the decoder uses those
+//! headers to tag every line with the file it came from.
+//!
+//! [`GuestLogShipper`] plugs this into a [`MonitoringService`], which ships
+//! the configured paths of each VM for as long as it runs.
+//!
+//! Connections logged by the host for VMs with `network.audit_egress` are
+//! read back from the kernel log by [`ship_egress_log`].
+
+use crate::vsock_executor::{ConnectionType, VsockExecutor};
+use aiva_core::{
+    AivaError, JailerConfig, LogEntry, LogLevel, LogShipper, LogStore, MonitoringService, Result,
+    VMInstance, shell_quote,
+};
+use aiva_network::{EGRESS_LOG_PREFIX, parse_egress_log_line};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{debug, warn};
+
+/// A single log line received from the guest, tagged with its source file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShippedLine {
+    pub path: String,
+    pub line: String,
+}
+
+/// Splits the agent's tail stream into per-file log lines
+pub struct LogFrameDecoder {
+    current_path: Option<String>,
+}
+
+impl LogFrameDecoder {
+    pub fn new(paths: &[String]) -> Self {
+        // tail emits no headers when following a single file
+        let current_path = match paths {
+            [only] => Some(only.clone()),
+            _ => None,
+        };
+        Self { current_path }
+    }
+
+    /// Decode one raw line of the stream. Headers and blank separator lines
+    /// update decoder state and yield nothing.
+    pub fn decode(&mut self, raw: &str) -> Option<ShippedLine> {
+        let raw = raw.trim_end_matches('\r');
+
+        if let Some(path) = raw
+            .strip_prefix("==> ")
+            .and_then(|rest| rest.strip_suffix(" <=="))
+        {
+            self.current_path = Some(path.to_string());
+            return None;
+        }
+
+        if raw.is_empty() {
+            return None;
+        }
+
+        Some(ShippedLine {
+            path: self
+                .current_path
+                .clone()
+                .unwrap_or_else(|| "unknown".to_string()),
+            line: raw.to_string(),
+        })
+    }
+}
+
+/// Build the command the guest agent runs to follow the configured files
+pub fn build_tail_command(paths: &[String]) -> Result<String> {
+    if paths.is_empty() {
+        return Err(AivaError::ConfigError(
+            "No guest log paths configured. Set them with 'aiva config set <name> logging.paths <path,...>'"
+                .to_string(),
+        ));
+    }
+
+    let quoted: Vec<String> = paths.iter().map(|path| shell_quote(path)).collect();
+
+    Ok(format!("tail -n 0 -F {} 2>/dev/null", quoted.join(" ")))
+}
+
+/// Best-effort severity detection for free-form guest log lines
+pub fn detect_level(line: &str) -> LogLevel {
+    let upper = line.to_uppercase();
+    if upper.contains("ERROR") || upper.contains("FATAL") || upper.contains("PANIC") {
+        LogLevel::Error
+    } else if upper.contains("WARN") {
+        LogLevel::Warn
+    } else if upper.contains("DEBUG") {
+        LogLevel::Debug
+    } else if upper.contains("TRACE") {
+        LogLevel::Trace
+    } else {
+        LogLevel::Info
+    }
+}
+
+/// Turns shipped lines into monitoring log entries and persists them
+pub struct LogIngestor {
+    vm_id: String,
+    decoder: LogFrameDecoder,
+    monitoring: Arc<MonitoringService>,
+    store: Option<LogStore>,
+}
+
+impl LogIngestor {
+    pub fn new(vm_id: String, paths: &[String], monitoring: Arc<MonitoringService>) -> Self {
+        Self {
+            vm_id,
+            decoder: LogFrameDecoder::new(paths),
+            monitoring,
+            store: None,
+        }
+    }
+
+    pub fn with_store(mut self, store: LogStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Feed one raw line from the agent stream
+    pub async fn ingest(&mut self, raw: &str) -> Result<Option<LogEntry>> {
+        let Some(shipped) = self.decoder.decode(raw) else {
+            return Ok(None);
+        };
+
+        let mut entry = LogEntry::new(
+            Some(self.vm_id.clone()),
+            detect_level(&shipped.line),
+            shipped.line,
+        );
+        entry
+            .metadata
+            .insert("source".to_string(), "guest".to_string());
+        entry.metadata.insert("path".to_string(), shipped.path);
+
+        if let Some(store) = &self.store
+            && let Err(e) = store.append(&entry).await
+        {
+            warn!("Failed to persist shipped log line: {}", e);
+        }
+
+        self.monitoring.add_log_entry(entry.clone()).await?;
+        Ok(Some(entry))
+    }
+}
+
+/// Follow the configured guest log files until the agent closes the stream.
+/// Each ingested entry is also passed to `on_entry` so callers can echo it.
+pub async fn ship_guest_logs<F>(
+    executor: Arc<VsockExecutor>,
+    paths: &[String],
+    mut ingestor: LogIngestor,
+    mut on_entry: F,
+) -> Result<()>
+where
+    F: FnMut(&LogEntry),
+{
+    let command = build_tail_command(paths)?;
+    let (tx, mut rx) = tokio::sync::mpsc::channel(256);
+
+    let stream = tokio::spawn(async move { executor.stream_command(&command, tx).await });
+
+    while let Some(raw) = rx.recv().await {
+        if let Some(entry) = ingestor.ingest(&raw).await? {
+            on_entry(&entry);
+        }
+    }
+
+    debug!("Guest log stream closed");
+    stream.await.map_err(|e| AivaError::Other(e.into()))?
+}
+
+/// Callback receiving each entry a [`GuestLogShipper`] ships
+pub type ShippedEntryHook = Arc<dyn Fn(&LogEntry) + Send + Sync>;
+
+/// Ships guest logs over the agent's command channel, persisting them to
+/// `store`
+pub struct GuestLogShipper {
+    store: LogStore,
+    jailer: JailerConfig,
+    on_entry: Option<ShippedEntryHook>,
+}
+
+impl GuestLogShipper {
+    pub fn new(store: LogStore) -> Self {
+        Self {
+            store,
+            jailer: JailerConfig::default(),
+            on_entry: None,
+        }
+    }
+
+    /// Find the VMs' vsock sockets where `jailer` puts them, e.g.
+    /// `platform.linux.jailer` of the main configuration
+    pub fn with_jailer_config(mut self, jailer: JailerConfig) -> Self {
+        self.jailer = jailer;
+        self
+    }
+
+    /// Also pass every shipped entry, of any VM, to `on_entry`
+    pub fn with_on_entry(mut self, on_entry: impl Fn(&LogEntry) + Send + Sync + 'static) -> Self {
+        self.on_entry = Some(Arc::new(on_entry));
+        self
+    }
+}
+
+#[async_trait]
+impl LogShipper for GuestLogShipper {
+    async fn ship(&self, vm: VMInstance, monitoring: Arc<MonitoringService>) -> Result<()> {
+        let executor = Arc::new(VsockExecutor::new(
+            vm.name.clone(),
+            ConnectionType::guest_agent(&vm, &self.jailer),
+        ));
+        let paths = &vm.config.logging.paths;
+        let ingestor =
+            LogIngestor::new(vm.id.to_string(), paths, monitoring).with_store(self.store.clone());
+
+        ship_guest_logs(executor, paths, ingestor, |entry| {
+            if let Some(on_entry) = &self.on_entry {
+                on_entry(entry);
+            }
+        })
+        .await
+    }
+}
+
+/// `journalctl` arguments selecting the egress rule's kernel log lines
+/// since `since`
+pub fn egress_journal_args(since: DateTime<Utc>, follow: bool) -> Vec<String> {
+    let mut args = vec![
+        "-k".to_string(),
+        "--no-pager".to_string(),
+        "-o".to_string(),
+        "short-iso-precise".to_string(),
+        "--grep".to_string(),
+        EGRESS_LOG_PREFIX.trim_end().to_string(),
+        "--since".to_string(),
+        format!("@{}", since.timestamp()),
+    ];
+    if follow {
+        args.push("-f".to_string());
+    }
+    args
+}
+
+/// Copy the connections the kernel logged for `vm` since the last one in
+/// `store` into it, returning how many were added. With `follow` it keeps
+/// copying new ones until journalctl exits.
+pub async fn ship_egress_log<F>(
+    vm: &VMInstance,
+    store: &LogStore,
+    follow: bool,
+    mut on_entry: F,
+) -> Result<usize>
+where
+    F: FnMut(&LogEntry),
+{
+    let Some(tap_device) = &vm.runtime.tap_device else {
+        return Ok(0);
+    };
+    let vm_id = vm.id.to_string();
+
+    let last = store
+        .read(&vm_id)
+        .await?
+        .iter()
+        .filter(|entry| entry.metadata.get("source").is_some_and(|s| s == "egress"))
+        .map(|entry| entry.timestamp)
+        .max();
+
+    let mut child = tokio::process::Command::new("journalctl")
+        .args(egress_journal_args(last.unwrap_or(vm.created_at), follow))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| AivaError::PlatformError {
+            platform: "linux".to_string(),
+            message: format!("Failed to read the kernel log: {e}"),
+            recoverable: false,
+        })?;
+    let stdout = child.stdout.take().expect("journalctl stdout is piped");
+
+    let mut shipped = 0;
+    let mut lines = BufReader::new(stdout).lines();
+    while let Some(line) = lines.next_line().await? {
+        let Some(record) = parse_egress_log_line(&line) else {
+            continue;
+        };
+        // --since has whole-second precision
+        if record.interface != *tap_device || last.is_some_and(|last| record.timestamp <= last) {
+            continue;
+        }
+
+        let entry = record.to_log_entry(&vm_id);
+        store.append(&entry).await?;
+        on_entry(&entry);
+        shipped += 1;
+    }
+
+    child.wait().await?;
+    Ok(shipped)
+}
@@ -0,0 +1,294 @@
+//! Host-level metrics backed by the platform and the VM tap interfaces.
+//!
+//! Every VM gets its own tap device, so the traffic of all guests is the sum
+//! of the tap counters in `/proc/net/dev`. Hosts without that file (macOS,
+//! Windows) report no network traffic.
+//!
+//! Per-device I/O of a Linux VM comes from Firecracker's own metrics, which
+//! it writes to a FIFO as one JSON object per flush. Each object holds how
+//! much a counter grew since the previous flush, so [`FirecrackerCounters`]
+//! adds them up and keeps the totals in a file next to the jail.
+
+use aiva_core::{
+    AivaError, DefaultMetricsCollector, DiskIOMetrics, MetricsCollector, NetworkIOMetrics,
+    NetworkStats, Result, SystemMetrics, VMManager, VMMetrics, VMState,
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+const NET_DEV: &str = "/proc/net/dev";
+
+/// Counters of one interface in `/proc/net/dev`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterfaceCounters {
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_drops: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_drops: u64,
+}
+
+impl From<InterfaceCounters> for NetworkIOMetrics {
+    fn from(counters: InterfaceCounters) -> Self {
+        NetworkIOMetrics {
+            rx_bytes: counters.rx_bytes,
+            tx_bytes: counters.tx_bytes,
+            rx_packets: counters.rx_packets,
+            tx_packets: counters.tx_packets,
+        }
+    }
+}
+
+/// Parse the contents of `/proc/net/dev` into counters per interface
+pub fn parse_net_dev(content: &str) -> HashMap<String, InterfaceCounters> {
+    let mut interfaces = HashMap::new();
+    // The first two lines are headers. Large counters can run into the
+    // colon after the name, so split on it rather than on whitespace.
+    for line in content.lines().skip(2) {
+        let Some((name, fields)) = line.split_once(':') else {
+            continue;
+        };
+        let fields: Vec<u64> = fields
+            .split_whitespace()
+            .map(|field| field.parse().unwrap_or(0))
+            .collect();
+        if fields.len() < 12 {
+            continue;
+        }
+        interfaces.insert(
+            name.trim().to_string(),
+            InterfaceCounters {
+                rx_bytes: fields[0],
+                rx_packets: fields[1],
+                rx_errors: fields[2],
+                rx_drops: fields[3],
+                tx_bytes: fields[8],
+                tx_packets: fields[9],
+                tx_errors: fields[10],
+                tx_drops: fields[11],
+            },
+        );
+    }
+    interfaces
+}
+
+/// Counters of `interface` on this host, zero when it does not exist
+pub(crate) async fn interface_counters(interface: &str) -> Result<InterfaceCounters> {
+    let content = tokio::fs::read_to_string(NET_DEV).await?;
+    Ok(parse_net_dev(&content)
+        .remove(interface)
+        .unwrap_or_default())
+}
+
+/// Sum the counters of `taps` into host-level network statistics. Taps
+/// missing from `interfaces` are skipped.
+pub fn aggregate_network_stats<'a>(
+    interfaces: &HashMap<String, InterfaceCounters>,
+    taps: impl IntoIterator<Item = &'a str>,
+) -> NetworkStats {
+    let mut stats = NetworkStats::default();
+    for tap in taps {
+        if let Some(counters) = interfaces.get(tap) {
+            stats.rx_bytes += counters.rx_bytes;
+            stats.tx_bytes += counters.tx_bytes;
+            stats.rx_packets += counters.rx_packets;
+            stats.tx_packets += counters.tx_packets;
+            stats.errors += counters.rx_errors + counters.tx_errors;
+            stats.drops += counters.rx_drops + counters.tx_drops;
+        }
+    }
+    stats
+}
+
+/// Block and network totals of a VM from Firecracker's metrics, summed over
+/// all its drives and interfaces
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirecrackerCounters {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_ops: u64,
+    pub write_ops: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+}
+
+/// The parts of a Firecracker metrics flush aiva reads. `block` and `net`
+/// aggregate every device; per-device entries such as `block_rootfs` are
+/// ignored.
+#[derive(Deserialize)]
+struct MetricsFlush {
+    #[serde(default)]
+    block: BlockFlush,
+    #[serde(default)]
+    net: NetFlush,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct BlockFlush {
+    read_bytes: u64,
+    write_bytes: u64,
+    read_count: u64,
+    write_count: u64,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct NetFlush {
+    rx_bytes_count: u64,
+    tx_bytes_count: u64,
+    rx_packets_count: u64,
+    tx_packets_count: u64,
+}
+
+impl FirecrackerCounters {
+    /// Add every flush in `content`, one JSON object per line. A line that
+    /// does not parse, such as one cut off by a full FIFO, is skipped.
+    pub fn add_flushes(&mut self, content: &str) {
+        for line in content.lines() {
+            let Ok(flush) = serde_json::from_str::<MetricsFlush>(line) else {
+                debug!("Skipping unreadable Firecracker metrics line");
+                continue;
+            };
+            self.read_bytes += flush.block.read_bytes;
+            self.write_bytes += flush.block.write_bytes;
+            self.read_ops += flush.block.read_count;
+            self.write_ops += flush.block.write_count;
+            self.rx_bytes += flush.net.rx_bytes_count;
+            self.tx_bytes += flush.net.tx_bytes_count;
+            self.rx_packets += flush.net.rx_packets_count;
+            self.tx_packets += flush.net.tx_packets_count;
+        }
+    }
+
+    pub fn disk_io(&self) -> DiskIOMetrics {
+        DiskIOMetrics {
+            read_bytes: self.read_bytes,
+            write_bytes: self.write_bytes,
+            read_ops: self.read_ops,
+            write_ops: self.write_ops,
+        }
+    }
+
+    pub fn network_io(&self) -> NetworkIOMetrics {
+        NetworkIOMetrics {
+            rx_bytes: self.rx_bytes,
+            tx_bytes: self.tx_bytes,
+            rx_packets: self.rx_packets,
+            tx_packets: self.tx_packets,
+        }
+    }
+}
+
+/// Drain the flushes waiting in the metrics FIFO at `fifo` into the totals
+/// kept at `totals`, and return the new totals
+pub(crate) fn drain_firecracker_metrics(fifo: &Path, totals: &Path) -> Result<FirecrackerCounters> {
+    let mut counters: FirecrackerCounters = match std::fs::read(totals) {
+        Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+            warn!(
+                "Ignoring unreadable metrics totals {}: {}",
+                totals.display(),
+                e
+            );
+            FirecrackerCounters::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => FirecrackerCounters::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    counters.add_flushes(&read_available(fifo)?);
+    std::fs::write(totals, serde_json::to_vec(&counters)?)?;
+    Ok(counters)
+}
+
+/// Everything that can be read from the FIFO at `path` without waiting.
+/// Firecracker keeps its end open, so an empty FIFO reports `WouldBlock`
+/// rather than end of file.
+fn read_available(path: &Path) -> Result<String> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut fifo = std::fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(nix::libc::O_NONBLOCK)
+        .open(path)?;
+    let mut content = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        match fifo.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => content.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// [`MetricsCollector`] reading VM metrics from the platform and summing the
+/// tap interfaces of running VMs into the system network statistics. CPU,
+/// memory and disk figures come from the `host` collector.
+pub struct PlatformMetricsCollector {
+    vm_manager: Arc<dyn VMManager>,
+    host: Box<dyn MetricsCollector>,
+}
+
+impl PlatformMetricsCollector {
+    pub fn new(vm_manager: Arc<dyn VMManager>) -> Self {
+        Self {
+            vm_manager,
+            host: Box::new(DefaultMetricsCollector),
+        }
+    }
+
+    pub fn with_host_collector(self, host: Box<dyn MetricsCollector>) -> Self {
+        Self { host, ..self }
+    }
+}
+
+#[async_trait]
+impl MetricsCollector for PlatformMetricsCollector {
+    async fn collect_metrics(&self, vm_id: &str) -> Result<VMMetrics> {
+        let id = Uuid::parse_str(vm_id)
+            .map_err(|e| AivaError::ConfigError(format!("Invalid VM id '{vm_id}': {e}")))?;
+        self.vm_manager.get_vm_metrics(&id).await
+    }
+
+    async fn collect_system_metrics(&self) -> Result<SystemMetrics> {
+        let mut metrics = self.host.collect_system_metrics().await?;
+
+        let running: Vec<_> = self
+            .vm_manager
+            .list_vms()
+            .await?
+            .into_iter()
+            .filter(|vm| vm.state == VMState::Running)
+            .collect();
+        metrics.active_vms = running.len() as u32;
+
+        let interfaces = match tokio::fs::read_to_string(NET_DEV).await {
+            Ok(content) => parse_net_dev(&content),
+            Err(e) => {
+                debug!("No interface counters from {}: {}", NET_DEV, e);
+                HashMap::new()
+            }
+        };
+        metrics.network_stats = aggregate_network_stats(
+            &interfaces,
+            running
+                .iter()
+                .filter_map(|vm| vm.runtime.tap_device.as_deref()),
+        );
+        Ok(metrics)
+    }
+}
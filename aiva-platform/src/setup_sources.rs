@@ -0,0 +1,43 @@
+//! Download sources rendered into the Firecracker setup scripts.
+
+use aiva_core::Config;
+
+/// Shell expression the setup scripts set to the guest architecture
+const SHELL_ARCH: &str = "${FC_ARCH}";
+
+/// Template context for the Firecracker, kernel and rootfs downloads. URLs
+/// keep `${FC_ARCH}` so the script resolves the architecture at run time;
+/// an empty checksum means the download is not pinned.
+#[derive(Debug, Clone)]
+pub(crate) struct DownloadSources {
+    /// Fail instead of downloading anything that is not staged
+    pub skip_downloads: bool,
+    pub firecracker_tag: String,
+    pub firecracker_url: String,
+    pub firecracker_sha256: String,
+    pub kernel_url: String,
+    pub kernel_sha256: String,
+    pub rootfs_url: String,
+    pub rootfs_sha256: String,
+}
+
+impl DownloadSources {
+    pub(crate) fn from_config(config: &Config) -> Self {
+        Self {
+            skip_downloads: config.skip_downloads,
+            firecracker_tag: config.firecracker.tag(),
+            firecracker_url: config.firecracker.release_url(SHELL_ARCH),
+            firecracker_sha256: config.firecracker.sha256.clone().unwrap_or_default(),
+            kernel_url: config.kernel.url_for(SHELL_ARCH),
+            kernel_sha256: config.kernel.sha256.clone().unwrap_or_default(),
+            rootfs_url: config.rootfs.url_for(SHELL_ARCH),
+            rootfs_sha256: config.rootfs.sha256.clone().unwrap_or_default(),
+        }
+    }
+}
+
+impl Default for DownloadSources {
+    fn default() -> Self {
+        Self::from_config(&Config::default())
+    }
+}
@@ -0,0 +1,444 @@
+use crate::api_tunnel::{ApiTunnel, lima_tunnel_args, wsl_tunnel_args};
+use crate::firecracker::{ApiEndpoint, FirecrackerApiClient};
+use crate::firecracker_vm::FirecrackerVMConfig;
+use aiva_core::CacheStrategy;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Request received by [`MockFirecracker`]: method, path and body
+type Recorded = Arc<Mutex<Vec<(String, String, String)>>>;
+
+/// Answers the Firecracker API on a Unix socket the way the VMM does:
+/// `GET /` with instance info, `GET /mmds` with what was last put there,
+/// `GET /balloon/statistics` with a balloon that holds its whole target,
+/// everything else with 204. Starting, pausing, resuming and loading
+/// snapshots change the reported state; changing a balloon that was never
+/// configured, or putting a drive once started, fails.
+pub(crate) struct MockFirecracker {
+    pub(crate) socket: PathBuf,
+    requests: Recorded,
+}
+
+impl MockFirecracker {
+    pub(crate) fn start() -> Self {
+        Self::failing_on(None)
+    }
+
+    /// Like [`start`](Self::start), but requests to paths starting with
+    /// `failing` are answered with 400
+    pub(crate) fn failing_on(failing: Option<&'static str>) -> Self {
+        let socket = temp_socket("fc");
+        let listener = UnixListener::bind(&socket).unwrap();
+        let requests = Recorded::default();
+
+        let recorded = requests.clone();
+        let vmm = Arc::new(Mutex::new(VmmState {
+            state: "Not started",
+            mmds: "{}".to_string(),
+            balloon_mib: None,
+        }));
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, recorded.clone(), vmm.clone(), failing));
+            }
+        });
+
+        Self { socket, requests }
+    }
+
+    pub(crate) fn requests(&self) -> Vec<(String, String, String)> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockFirecracker {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+/// What [`MockFirecracker`] remembers between requests
+struct VmmState {
+    state: &'static str,
+    mmds: String,
+    balloon_mib: Option<u64>,
+}
+
+fn temp_socket(kind: &str) -> PathBuf {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    std::env::temp_dir().join(format!("aiva-{kind}-{}.sock", &id[..8]))
+}
+
+/// Serve keep-alive HTTP/1.1 requests on one connection
+async fn serve(
+    mut stream: UnixStream,
+    recorded: Recorded,
+    vmm: Arc<Mutex<VmmState>>,
+    failing: Option<&str>,
+) {
+    let mut buffer = Vec::new();
+    loop {
+        let header_end = loop {
+            if let Some(end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break end + 4;
+            }
+            let mut chunk = [0u8; 1024];
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        };
+
+        let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+        let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+        let method = request_line.next().unwrap_or_default().to_string();
+        let path = request_line.next().unwrap_or_default().to_string();
+        let content_length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        while buffer.len() < header_end + content_length {
+            let mut chunk = [0u8; 1024];
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => buffer.extend_from_slice(&chunk[..n]),
+            }
+        }
+        let body =
+            String::from_utf8_lossy(&buffer[header_end..header_end + content_length]).to_string();
+        buffer.drain(..header_end + content_length);
+
+        let response = if method == "GET" && path == "/" {
+            let info = format!(r#"{{"id":"mock","state":"{}"}}"#, vmm.lock().unwrap().state);
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{info}",
+                info.len()
+            )
+        } else if method == "GET" && path == "/mmds" {
+            let data = vmm.lock().unwrap().mmds.clone();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{data}",
+                data.len()
+            )
+        } else if failing.is_some_and(|prefix| path.starts_with(prefix)) {
+            bad_request("injected failure")
+        } else if method == "PUT"
+            && path.starts_with("/drives/")
+            && vmm.lock().unwrap().state != "Not started"
+        {
+            bad_request("The requested operation is not supported after starting the microVM.")
+        } else if path.starts_with("/balloon") && method != "PUT" {
+            let balloon_mib = vmm.lock().unwrap().balloon_mib;
+            match (method.as_str(), balloon_mib) {
+                (_, None) => bad_request("balloon device not configured"),
+                ("GET", Some(mib)) => {
+                    let stats = format!(
+                        r#"{{"target_pages":{pages},"actual_pages":{pages},"target_mib":{mib},"actual_mib":{mib}}}"#,
+                        pages = mib * 256
+                    );
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{stats}",
+                        stats.len()
+                    )
+                }
+                _ => {
+                    vmm.lock().unwrap().balloon_mib = Some(balloon_amount(&body));
+                    "HTTP/1.1 204 No Content\r\n\r\n".to_string()
+                }
+            }
+        } else {
+            let next = match (method.as_str(), path.as_str()) {
+                ("PUT", "/actions") if body.contains("InstanceStart") => Some("Running"),
+                ("PATCH", "/vm") if body.contains("Paused") => Some("Paused"),
+                ("PATCH", "/vm") if body.contains("Resumed") => Some("Running"),
+                ("PUT", "/snapshot/load") if body.contains(r#""resume_vm":true"#) => {
+                    Some("Running")
+                }
+                ("PUT", "/snapshot/load") => Some("Paused"),
+                _ => None,
+            };
+            let mut vmm = vmm.lock().unwrap();
+            if let Some(next) = next {
+                vmm.state = next;
+            }
+            if method == "PUT" && path == "/mmds" {
+                vmm.mmds = body.clone();
+            }
+            if method == "PUT" && path == "/balloon" {
+                vmm.balloon_mib = Some(balloon_amount(&body));
+            }
+            "HTTP/1.1 204 No Content\r\n\r\n".to_string()
+        };
+        recorded.lock().unwrap().push((method, path, body));
+
+        if stream.write_all(response.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn bad_request(fault: &str) -> String {
+    let fault = format!(r#"{{"fault_message":"{fault}"}}"#);
+    format!(
+        "HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{fault}",
+        fault.len()
+    )
+}
+
+/// `amount_mib` of a balloon request body
+fn balloon_amount(body: &str) -> u64 {
+    serde_json::from_str::<serde_json::Value>(body).unwrap()["amount_mib"]
+        .as_u64()
+        .unwrap()
+}
+
+/// Copy bytes both ways between a forwarded connection and the VMM socket
+async fn pipe<S>(mut client: S, remote: PathBuf)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    if let Ok(mut upstream) = UnixStream::connect(&remote).await {
+        let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+    }
+}
+
+/// Stand-in for `ssh -L local.sock:remote.sock`
+fn forward_unix(remote: &Path) -> PathBuf {
+    let local = temp_socket("fwd");
+    let listener = UnixListener::bind(&local).unwrap();
+    let remote = remote.to_path_buf();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(pipe(stream, remote.clone()));
+        }
+    });
+    local
+}
+
+fn vm_config() -> FirecrackerVMConfig {
+    FirecrackerVMConfig {
+        vm_id: "web".to_string(),
+        socket_path: PathBuf::from("/var/lib/firecracker/web/firecracker.sock"),
+        kernel_path: PathBuf::from("/opt/aiva/images/vmlinux"),
+        rootfs_path: PathBuf::from("/var/lib/firecracker/web/web.rootfs.ext4"),
+        vcpu_count: 2,
+        mem_size_mib: 1024,
+        tap_device: "tap-web".to_string(),
+        guest_ip: "172.16.0.2".to_string(),
+        network_interface: "eth0".to_string(),
+        custom_boot_args: None,
+        extra_boot_args: Vec::new(),
+        cpu_template: None,
+        cache_strategy: CacheStrategy::Writeback,
+    }
+}
+
+fn body_json(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_client_configures_machine_through_forwarded_socket() {
+    let vmm = MockFirecracker::start();
+    let local = forward_unix(&vmm.socket);
+
+    let client = FirecrackerApiClient::with_endpoint(ApiEndpoint::Unix(local.clone())).unwrap();
+    client.ping().await.unwrap();
+    client
+        .configure_machine(4, 2048, None, false)
+        .await
+        .unwrap();
+
+    let requests = vmm.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        (requests[0].0.as_str(), requests[0].1.as_str()),
+        ("GET", "/")
+    );
+    let (method, path, body) = &requests[1];
+    assert_eq!((method.as_str(), path.as_str()), ("PUT", "/machine-config"));
+    let body = body_json(body);
+    assert_eq!(body["vcpu_count"], 4);
+    assert_eq!(body["mem_size_mib"], 2048);
+
+    let _ = std::fs::remove_file(local);
+}
+
+#[tokio::test]
+async fn test_boot_sends_the_linux_configuration_sequence() {
+    let vmm = MockFirecracker::start();
+    let local = forward_unix(&vmm.socket);
+    let config = vm_config();
+
+    let client = FirecrackerApiClient::with_endpoint(ApiEndpoint::Unix(local.clone())).unwrap();
+    client.boot(&config).await.unwrap();
+
+    let requests = vmm.requests();
+    let paths: Vec<&str> = requests.iter().map(|(_, path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "/machine-config",
+            "/boot-source",
+            "/drives/rootfs",
+            "/network-interfaces/eth0",
+            "/actions",
+        ]
+    );
+    assert!(requests.iter().all(|(method, _, _)| method == "PUT"));
+
+    let boot_source = body_json(&requests[1].2);
+    assert_eq!(boot_source["kernel_image_path"], "/opt/aiva/images/vmlinux");
+    assert_eq!(boot_source["boot_args"], config.boot_args());
+    assert!(
+        config
+            .boot_args()
+            .contains("ip=172.16.0.2::172.16.0.1:255.255.255.0::eth0:off")
+    );
+
+    let drive = body_json(&requests[2].2);
+    assert_eq!(
+        drive["path_on_host"],
+        "/var/lib/firecracker/web/web.rootfs.ext4"
+    );
+    assert_eq!(drive["is_root_device"], true);
+    assert_eq!(drive["cache_type"], "Writeback");
+
+    let network = body_json(&requests[3].2);
+    assert_eq!(network["host_dev_name"], "tap-web");
+
+    assert_eq!(body_json(&requests[4].2)["action_type"], "InstanceStart");
+
+    let _ = std::fs::remove_file(local);
+}
+
+#[tokio::test]
+async fn test_boot_uses_the_configured_rootfs_cache_strategy() {
+    let vmm = MockFirecracker::start();
+    let local = forward_unix(&vmm.socket);
+    let config = FirecrackerVMConfig {
+        cache_strategy: CacheStrategy::Unsafe,
+        ..vm_config()
+    };
+
+    let client = FirecrackerApiClient::with_endpoint(ApiEndpoint::Unix(local.clone())).unwrap();
+    client.boot(&config).await.unwrap();
+
+    let requests = vmm.requests();
+    let (_, _, body) = requests
+        .iter()
+        .find(|(_, path, _)| path == "/drives/rootfs")
+        .unwrap();
+    assert_eq!(body_json(body)["cache_type"], "Unsafe");
+
+    let _ = std::fs::remove_file(local);
+}
+
+#[tokio::test]
+async fn test_client_talks_through_a_pipe_command() {
+    // Stand-in for socat in WSL: note the request line, answer it, then
+    // swallow the rest of the connection
+    let seen = std::env::temp_dir().join(format!("aiva-pipe-{}", uuid::Uuid::new_v4()));
+    let script = format!(
+        "read line; echo \"$line\" > {}; printf 'HTTP/1.1 204 No Content\\r\\n\\r\\n'; cat > /dev/null",
+        seen.display()
+    );
+    let endpoint = ApiEndpoint::Pipe {
+        program: "sh".to_string(),
+        args: vec!["-c".to_string(), script],
+    };
+
+    let client = FirecrackerApiClient::with_endpoint(endpoint).unwrap();
+    client.configure_machine(1, 512, None, false).await.unwrap();
+
+    let line = std::fs::read_to_string(&seen).unwrap();
+    assert_eq!(line.trim_end(), "PUT /machine-config HTTP/1.1");
+    let _ = std::fs::remove_file(seen);
+}
+
+#[tokio::test]
+async fn test_tunnel_opens_once_the_vmm_answers() {
+    let vmm = MockFirecracker::start();
+    let local = forward_unix(&vmm.socket);
+
+    // The forward is already up, so any long-running process stands in for ssh
+    let tunnel = ApiTunnel::open(
+        "sleep",
+        &["30".to_string()],
+        ApiEndpoint::Unix(local.clone()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(tunnel.endpoint(), &ApiEndpoint::Unix(local.clone()));
+
+    tunnel
+        .client()
+        .unwrap()
+        .configure_machine(2, 1024, None, false)
+        .await
+        .unwrap();
+    assert!(
+        vmm.requests()
+            .iter()
+            .any(|(_, path, _)| path == "/machine-config")
+    );
+
+    // Closing the tunnel removes the forwarded socket
+    drop(tunnel);
+    assert!(!local.exists());
+}
+
+#[tokio::test]
+async fn test_tunnel_fails_when_the_forward_exits() {
+    let endpoint = ApiEndpoint::Unix(temp_socket("missing"));
+    let result = ApiTunnel::open("false", &[], endpoint).await;
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_lima_tunnel_forwards_the_socket_over_ssh() {
+    let args = lima_tunnel_args(
+        Path::new("/Users/dev/.lima/aiva-host/ssh.config"),
+        "aiva-host",
+        Path::new("/tmp/aiva-fc-web.sock"),
+        Path::new("/var/lib/firecracker/web/firecracker.socket"),
+    );
+
+    assert_eq!(&args[..2], ["-F", "/Users/dev/.lima/aiva-host/ssh.config"]);
+    assert!(args.contains(&"ExitOnForwardFailure=yes".to_string()));
+    assert!(args.contains(&"StreamLocalBindUnlink=yes".to_string()));
+    assert_eq!(
+        &args[args.len() - 4..],
+        [
+            "-N",
+            "-L",
+            "/tmp/aiva-fc-web.sock:/var/lib/firecracker/web/firecracker.socket",
+            "lima-aiva-host",
+        ]
+    );
+}
+
+#[test]
+fn test_wsl_tunnel_bridges_stdio_without_listening() {
+    let args = wsl_tunnel_args(
+        "Ubuntu",
+        Path::new("/var/lib/firecracker/web/firecracker.sock"),
+    );
+
+    assert_eq!(
+        args,
+        vec![
+            "-d",
+            "Ubuntu",
+            "sudo",
+            "socat",
+            "STDIO",
+            "UNIX-CONNECT:/var/lib/firecracker/web/firecracker.sock",
+        ]
+    );
+}
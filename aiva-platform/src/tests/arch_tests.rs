@@ -0,0 +1,136 @@
+use crate::arch::{
+    Architecture, CpuVendor, check_architecture, check_machine_config, cpu_templates,
+    file_architecture, header_architecture,
+};
+use aiva_core::AivaError;
+use std::path::PathBuf;
+
+/// Start of a 64-bit ELF header with the given byte order and machine type
+fn elf_header(little_endian: bool, machine: u16) -> Vec<u8> {
+    let mut header = vec![0u8; 64];
+    header[..4].copy_from_slice(b"\x7fELF");
+    header[4] = 2; // ELFCLASS64
+    header[5] = if little_endian { 1 } else { 2 };
+    header[6] = 1; // EV_CURRENT
+    let machine = if little_endian {
+        machine.to_le_bytes()
+    } else {
+        machine.to_be_bytes()
+    };
+    header[18..20].copy_from_slice(&machine);
+    header
+}
+
+fn write_temp(header: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("aiva-arch-{}", uuid::Uuid::new_v4()));
+    std::fs::write(&path, header).unwrap();
+    path
+}
+
+#[test]
+fn test_elf_machine_type_is_detected() {
+    assert_eq!(
+        header_architecture(&elf_header(true, 62)),
+        Ok(Architecture::X86_64)
+    );
+    assert_eq!(
+        header_architecture(&elf_header(true, 183)),
+        Ok(Architecture::Aarch64)
+    );
+    assert_eq!(
+        header_architecture(&elf_header(false, 183)),
+        Ok(Architecture::Aarch64)
+    );
+}
+
+#[test]
+fn test_arm64_kernel_image_is_detected() {
+    let mut header = vec![0u8; 64];
+    header[56..60].copy_from_slice(b"ARM\x64");
+    assert_eq!(header_architecture(&header), Ok(Architecture::Aarch64));
+}
+
+#[test]
+fn test_unknown_headers_are_rejected() {
+    // EM_RISCV
+    let error = header_architecture(&elf_header(true, 243)).unwrap_err();
+    assert!(error.contains("243"));
+
+    assert!(header_architecture(b"\x7fELF\x02\x01").is_err());
+    assert!(header_architecture(b"#!/bin/sh\necho hi\n").is_err());
+}
+
+#[test]
+fn test_mismatch_names_file_and_both_architectures() {
+    let path = write_temp(&elf_header(true, 62));
+    assert_eq!(file_architecture(&path).unwrap(), Architecture::X86_64);
+    check_architecture("Kernel image", &path, Architecture::X86_64).unwrap();
+
+    let message = check_architecture("Kernel image", &path, Architecture::Aarch64)
+        .unwrap_err()
+        .to_string();
+    assert!(message.contains("Kernel image"));
+    assert!(message.contains(&path.display().to_string()));
+    assert!(message.contains("built for x86_64 but the host is aarch64"));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_architecture_names() {
+    assert_eq!(Architecture::from_name("amd64"), Some(Architecture::X86_64));
+    assert_eq!(
+        Architecture::from_name("arm64"),
+        Some(Architecture::Aarch64)
+    );
+    assert_eq!(Architecture::from_name("riscv64"), None);
+    assert_eq!(Architecture::Aarch64.to_string(), "aarch64");
+}
+
+#[test]
+fn test_cpu_templates_must_match_the_host() {
+    let x86 = Architecture::X86_64;
+    let arm = Architecture::Aarch64;
+    assert_eq!(cpu_templates(arm), ["V1N1"]);
+    assert!(cpu_templates(x86).contains(&"T2"));
+
+    check_machine_config(x86, Some(CpuVendor::Intel), Some("t2"), false, 2).unwrap();
+    check_machine_config(x86, Some(CpuVendor::Amd), Some("T2A"), false, 2).unwrap();
+    // Without a known vendor only the architecture is checked
+    check_machine_config(x86, None, Some("C3"), false, 2).unwrap();
+    check_machine_config(arm, None, Some("V1N1"), false, 2).unwrap();
+    check_machine_config(arm, None, None, false, 3).unwrap();
+
+    for (arch, vendor, template, expected) in [
+        (x86, None, "Z9", "Unknown CPU template Z9"),
+        (arm, None, "T2", "is for x86_64 hosts"),
+        (x86, None, "V1N1", "is for aarch64 hosts"),
+        (x86, Some(CpuVendor::Amd), "T2", "is for Intel CPUs"),
+        (x86, Some(CpuVendor::Intel), "T2A", "is for AMD CPUs"),
+    ] {
+        let err = check_machine_config(arch, vendor, Some(template), false, 2).unwrap_err();
+        assert!(matches!(err, AivaError::ConfigError(_)), "{err:?}");
+        assert!(err.to_string().contains(expected), "{err}");
+    }
+}
+
+#[test]
+fn test_smt_needs_x86_and_an_even_vcpu_count() {
+    check_machine_config(Architecture::X86_64, None, None, true, 1).unwrap();
+    check_machine_config(Architecture::X86_64, None, Some("T2"), true, 4).unwrap();
+    assert!(check_machine_config(Architecture::X86_64, None, None, true, 3).is_err());
+    assert!(check_machine_config(Architecture::Aarch64, None, None, true, 2).is_err());
+}
+
+#[test]
+fn test_cpu_vendor_is_read_from_cpuinfo() {
+    assert_eq!(
+        CpuVendor::from_vendor_id("GenuineIntel"),
+        Some(CpuVendor::Intel)
+    );
+    assert_eq!(
+        CpuVendor::from_vendor_id("AuthenticAMD\n"),
+        Some(CpuVendor::Amd)
+    );
+    assert_eq!(CpuVendor::from_vendor_id("HygonGenuine"), None);
+}
@@ -1,5 +1,5 @@
-use crate::command_pool::{CommandPool, ConnectionType};
-use aiva_core::Result;
+use crate::command_pool::{CommandPool, ConnectionType, ExecutorRegistry, connection_for};
+use aiva_core::{AivaError, ExecTransport, Result};
 
 #[tokio::test]
 async fn test_command_pool_creation() {
@@ -44,7 +44,10 @@ async fn test_vm_unregistration() -> Result<()> {
 #[tokio::test]
 async fn test_connection_types() {
     // Test different connection type creations
-    let _vsock = ConnectionType::Vsock { cid: 3 };
+    let _vsock = ConnectionType::Vsock {
+        cid: 3,
+        uds_path: "/srv/jailer/web/root/vsock.sock".into(),
+    };
 
     let _network = ConnectionType::Network {
         host: "192.168.1.100".to_string(),
@@ -71,3 +74,262 @@ async fn test_global_command_pool() {
 
     assert_eq!(vms1.len(), vms2.len());
 }
+
+/// Registry whose connections only succeed over `reachable` transports
+struct MockRegistry {
+    reachable: Vec<&'static str>,
+    attempts: std::sync::Mutex<Vec<&'static str>>,
+}
+
+impl MockRegistry {
+    fn new(reachable: &[&'static str]) -> Self {
+        Self {
+            reachable: reachable.to_vec(),
+            attempts: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    fn attempts(&self) -> Vec<&'static str> {
+        self.attempts.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl ExecutorRegistry for MockRegistry {
+    async fn register_vm(&self, _vm_name: String, connection_type: ConnectionType) -> Result<()> {
+        let kind = match connection_type {
+            ConnectionType::Vsock { .. } => "vsock",
+            ConnectionType::Network { .. } => "network",
+            ConnectionType::Ssh { .. } => "ssh",
+        };
+        self.attempts.lock().unwrap().push(kind);
+        if self.reachable.contains(&kind) {
+            Ok(())
+        } else {
+            Err(AivaError::NetworkError {
+                operation: "connect".to_string(),
+                cause: format!("{kind} refused"),
+            })
+        }
+    }
+}
+
+fn vm_with_transports(transports: &[ExecTransport]) -> aiva_core::VMInstance {
+    let config = aiva_core::VMConfig::builder()
+        .port(2222, 22, aiva_core::Protocol::Tcp)
+        .transports(transports.iter().copied())
+        .build()
+        .unwrap();
+    let now = chrono::Utc::now();
+    aiva_core::VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: "web".to_string(),
+        state: aiva_core::VMState::Running,
+        config,
+        runtime: aiva_core::RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: Some(7),
+            tap_device: None,
+            mcp_pids: None,
+            paused: None,
+        },
+        created_at: now,
+        updated_at: now,
+        schema_version: aiva_core::SCHEMA_VERSION,
+    }
+}
+
+/// Candidates the Linux platform would try for `vm`
+fn linux_candidates(
+    vm: &aiva_core::VMInstance,
+    vsock_supported: bool,
+) -> Result<Vec<(ExecTransport, ConnectionType)>> {
+    let supported = [
+        ExecTransport::Vsock,
+        ExecTransport::Network,
+        ExecTransport::Ssh,
+    ];
+    Ok(vm
+        .config
+        .execution
+        .transport_order(&supported)?
+        .into_iter()
+        .filter_map(|t| {
+            let vsock_uds = vsock_supported.then(|| "/srv/jailer/web/root/vsock.sock".into());
+            connection_for(t, vm, vsock_uds).map(|c| (t, c))
+        })
+        .collect())
+}
+
+#[tokio::test]
+async fn test_default_order_falls_back_from_vsock_to_ssh() -> Result<()> {
+    let vm = vm_with_transports(&[]);
+    let registry = MockRegistry::new(&["ssh"]);
+
+    let used = registry
+        .register_in_order("web", linux_candidates(&vm, true)?)
+        .await?;
+    assert_eq!(used, ExecTransport::Ssh);
+    assert_eq!(registry.attempts(), vec!["vsock", "network", "ssh"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_configured_order_is_tried_as_written() -> Result<()> {
+    let vm = vm_with_transports(&[ExecTransport::Ssh, ExecTransport::Vsock]);
+    let registry = MockRegistry::new(&["vsock", "ssh"]);
+
+    // Forcing SSH skips vsock even though it would connect
+    let used = registry
+        .register_in_order("web", linux_candidates(&vm, true)?)
+        .await?;
+    assert_eq!(used, ExecTransport::Ssh);
+    assert_eq!(registry.attempts(), vec!["ssh"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_vsock_only_never_falls_back_to_the_network() -> Result<()> {
+    let vm = vm_with_transports(&[ExecTransport::Vsock]);
+    let registry = MockRegistry::new(&["network", "ssh"]);
+
+    let err = registry
+        .register_in_order("web", linux_candidates(&vm, true)?)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("vsock refused"));
+    assert_eq!(registry.attempts(), vec!["vsock"]);
+
+    // Without vsock on the host nothing is left to try
+    let registry = MockRegistry::new(&["network", "ssh"]);
+    let err = registry
+        .register_in_order("web", linux_candidates(&vm, false)?)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("No configured transport"));
+    assert!(registry.attempts().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_transport_is_rejected() {
+    let vm = vm_with_transports(&[ExecTransport::Wsl]);
+    assert!(matches!(
+        linux_candidates(&vm, true),
+        Err(AivaError::ConfigError(_))
+    ));
+}
+
+#[test]
+fn test_vsock_connects_to_the_assigned_cid() -> Result<()> {
+    let mut vm = vm_with_transports(&[]);
+    let candidates = linux_candidates(&vm, true)?;
+    assert!(matches!(
+        candidates.first(),
+        Some((ExecTransport::Vsock, ConnectionType::Vsock { cid: 7, .. }))
+    ));
+
+    // A VM without a CID has no vsock device to reach
+    vm.runtime.vsock_cid = None;
+    let transports: Vec<_> = linux_candidates(&vm, true)?
+        .into_iter()
+        .map(|(transport, _)| transport)
+        .collect();
+    assert_eq!(transports, vec![ExecTransport::Network, ExecTransport::Ssh]);
+    Ok(())
+}
+
+/// Guest agent on a loopback port answering `ran: <command>`, except that
+/// the connections numbered in `resets` are reset without an answer;
+/// `usize::MAX` resets every connection after the first
+async fn flaky_agent(
+    resets: &'static [usize],
+) -> (u16, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let accepted = std::sync::Arc::new(AtomicUsize::new(0));
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let n = counter.fetch_add(1, Ordering::SeqCst);
+            if resets.contains(&n) || resets.contains(&usize::MAX) && n > 0 {
+                stream.set_linger(Some(std::time::Duration::ZERO)).unwrap();
+                drop(stream);
+                continue;
+            }
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut command = String::new();
+                if stream.read_line(&mut command).await.is_ok() {
+                    let reply = format!("ran: {}", command.trim_end());
+                    let _ = stream.get_mut().write_all(reply.as_bytes()).await;
+                }
+            });
+        }
+    });
+    (port, accepted)
+}
+
+fn loopback(port: u16) -> ConnectionType {
+    ConnectionType::Network {
+        host: "127.0.0.1".to_string(),
+        port,
+    }
+}
+
+#[tokio::test]
+async fn test_failed_command_reconnects_and_runs_once_more() -> Result<()> {
+    // 0 registers, 1 is dropped, 2 checks the new connection, 3 runs
+    let (port, accepted) = flaky_agent(&[1]).await;
+    let pool = CommandPool::new();
+    pool.register_vm("web".to_string(), loopback(port)).await?;
+
+    let output = pool.execute_command("web", "uptime").await?;
+    assert_eq!(output, "ran: uptime");
+    assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 4);
+    assert_eq!(pool.stats().await.reconnects, 1);
+    assert!(pool.health_check("web").await?);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_unreachable_guest_is_unregistered_after_one_reconnect() -> Result<()> {
+    // Everything after registering is dropped
+    let (port, accepted) = flaky_agent(&[usize::MAX]).await;
+    let pool = CommandPool::new();
+    pool.register_vm("web".to_string(), loopback(port)).await?;
+
+    let err = pool.execute_command("web", "uptime").await.unwrap_err();
+    assert!(matches!(err, AivaError::NetworkError { .. }), "{err:?}");
+    // The command is not retried over a connection that failed its check
+    assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 3);
+    assert!(!pool.is_registered("web").await);
+    assert_eq!(pool.stats().await, Default::default());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_idle_connections_are_evicted() -> Result<()> {
+    let (port, _) = flaky_agent(&[]).await;
+    let pool = CommandPool::new().with_idle_ttl(std::time::Duration::from_secs(3600));
+    pool.register_vm("web".to_string(), loopback(port)).await?;
+    let stats = pool.stats().await;
+    assert_eq!((stats.connections, stats.network, stats.idle), (1, 1, 0));
+    assert!(pool.evict_idle().await.is_empty());
+
+    let pool = CommandPool::new().with_idle_ttl(std::time::Duration::ZERO);
+    pool.register_vm("web".to_string(), loopback(port)).await?;
+    assert_eq!(pool.stats().await.idle, 1);
+    // An expired connection counts as unregistered, so it is set up afresh
+    assert!(!pool.is_registered("web").await);
+    assert_eq!(pool.stats().await.connections, 0);
+    assert!(matches!(
+        pool.health_check("web").await,
+        Err(AivaError::VMError { .. })
+    ));
+    Ok(())
+}
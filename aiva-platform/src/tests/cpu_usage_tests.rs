@@ -0,0 +1,150 @@
+use crate::cpu_usage::{
+    CpuSample, CpuUsageTracker, ProcSource, cpu_percent, parse_process_ticks, parse_start_time,
+    parse_system_ticks,
+};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// `/proc` handing out queued readings of process and system ticks, one
+/// pair per sample, for a process started at `start_time`
+#[derive(Clone, Default)]
+struct FakeProc {
+    readings: Arc<Mutex<VecDeque<(u64, u64)>>>,
+    start_time: Arc<Mutex<u64>>,
+}
+
+impl FakeProc {
+    fn with(readings: &[(u64, u64)]) -> Self {
+        Self {
+            readings: Arc::new(Mutex::new(readings.iter().copied().collect())),
+            start_time: Arc::new(Mutex::new(500)),
+        }
+    }
+
+    /// Hand the PID to a process started at `start_time`
+    fn restart(&self, start_time: u64) {
+        *self.start_time.lock().unwrap() = start_time;
+    }
+
+    fn remaining(&self) -> usize {
+        self.readings.lock().unwrap().len()
+    }
+}
+
+impl ProcSource for FakeProc {
+    fn process_stat(&self, pid: u32) -> std::io::Result<String> {
+        let (ticks, _) = self
+            .readings
+            .lock()
+            .unwrap()
+            .front()
+            .copied()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "process is gone"))?;
+        let start = *self.start_time.lock().unwrap();
+        Ok(format!(
+            "{pid} (firecracker) S 1 {pid} {pid} 0 -1 4194560 100 0 0 0 {ticks} 0 0 0 20 0 3 0 {start} 0 0"
+        ))
+    }
+
+    fn system_stat(&self) -> std::io::Result<String> {
+        let (_, ticks) = self.readings.lock().unwrap().pop_front().unwrap();
+        Ok(format!(
+            "cpu  {ticks} 0 0 0 0 0 0 0 0 0\ncpu0 0 0 0 0\ncpu1 0 0 0 0\ncpu2 0 0 0 0\ncpu3 0 0 0 0\nintr 12345\n"
+        ))
+    }
+}
+
+#[test]
+fn test_process_ticks_are_read_after_the_command_name() {
+    let stat = "4242 (fc vcpu 0) R 1 4242 4242 0 -1 4194560 100 0 0 0 150 25 0 0 20 0 3 0 500";
+    assert_eq!(parse_process_ticks(stat), Some(175));
+    assert_eq!(parse_start_time(stat), Some(500));
+    assert_eq!(parse_process_ticks("4242 (firecracker) R 1"), None);
+}
+
+#[test]
+fn test_system_ticks_sum_the_cpu_line_and_count_cpus() {
+    let stat = "cpu  100 5 50 800 10 0 5 0 0 0\ncpu0 50 0 25 400 5 0 5 0 0 0\ncpu1 50 5 25 400 5 0 0 0 0 0\nintr 1\n";
+    assert_eq!(parse_system_ticks(stat), Some((970, 2)));
+    assert_eq!(parse_system_ticks("intr 1\n"), None);
+}
+
+#[test]
+fn test_usage_is_the_delta_over_the_window_times_cpus() {
+    let sample = |process_ticks, system_ticks| CpuSample {
+        process_ticks,
+        system_ticks,
+        cpus: 4,
+        start_time: 500,
+    };
+    // 400 ticks across 4 CPUs is 100 ticks of wall time; 50 of them busy
+    assert_eq!(
+        cpu_percent(sample(1000, 8000), sample(1050, 8400)),
+        Some(50.0)
+    );
+    // Two cores kept busy
+    assert_eq!(
+        cpu_percent(sample(1000, 8000), sample(1200, 8400)),
+        Some(200.0)
+    );
+    // Busy at boot, idle now
+    assert_eq!(
+        cpu_percent(sample(5000, 8000), sample(5000, 8400)),
+        Some(0.0)
+    );
+    assert_eq!(cpu_percent(sample(1000, 8000), sample(1000, 8000)), None);
+    assert_eq!(cpu_percent(sample(1000, 8000), sample(10, 8400)), None);
+}
+
+#[tokio::test]
+async fn test_tracker_reuses_the_previous_sample() {
+    let proc = FakeProc::with(&[(5000, 8000), (5010, 8400), (5110, 8800)]);
+    let tracker = CpuUsageTracker::new(Box::new(proc.clone()), Duration::ZERO);
+
+    // No earlier reading: two samples a window apart
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 10.0);
+    assert_eq!(proc.remaining(), 1);
+
+    // The next call compares with the last sample without waiting
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 100.0);
+    assert_eq!(proc.remaining(), 0);
+}
+
+#[tokio::test]
+async fn test_tracker_resamples_a_replaced_process() {
+    let proc = FakeProc::with(&[(5000, 8000), (5000, 8400), (20, 8800), (60, 9200)]);
+    let tracker = CpuUsageTracker::new(Box::new(proc.clone()), Duration::ZERO);
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 0.0);
+
+    // A new process got the same PID, so its ticks went backwards
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 40.0);
+    assert_eq!(proc.remaining(), 0);
+
+    // Once it is gone the reading fails
+    assert!(tracker.cpu_usage(42).await.is_err());
+}
+
+#[tokio::test]
+async fn test_tracker_ignores_the_sample_of_a_reused_pid() {
+    let proc = FakeProc::with(&[(100, 8000), (100, 8400), (4000, 8800), (4040, 9200)]);
+    let tracker = CpuUsageTracker::new(Box::new(proc.clone()), Duration::ZERO);
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 0.0);
+
+    // The PID went to a busier process; its ticks only look like a delta
+    proc.restart(9000);
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 40.0);
+    assert_eq!(proc.remaining(), 0);
+}
+
+#[tokio::test]
+async fn test_forgotten_pid_is_sampled_afresh() {
+    let proc = FakeProc::with(&[(100, 8000), (110, 8400), (500, 8800), (520, 9200)]);
+    let tracker = CpuUsageTracker::new(Box::new(proc.clone()), Duration::ZERO);
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 10.0);
+
+    // The VM stopped, so the next reading is not against its last sample
+    tracker.forget(42);
+    assert_eq!(tracker.cpu_usage(42).await.unwrap(), 20.0);
+    assert_eq!(proc.remaining(), 0);
+}
@@ -0,0 +1,276 @@
+use crate::firecracker::{FirecrackerApiClient, MachineConfig};
+use crate::tests::api_tunnel_tests::MockFirecracker;
+use aiva_core::{AivaError, SnapshotType};
+use std::path::Path;
+
+fn body_json(body: &str) -> serde_json::Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_snapshot_of_a_running_vm_is_refused() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client.start_instance().await.unwrap();
+
+    for snapshot_type in [SnapshotType::Full, SnapshotType::Diff] {
+        let err = client
+            .create_snapshot(
+                snapshot_type,
+                Path::new("/snapshot.mem"),
+                Path::new("/snapshot"),
+            )
+            .await
+            .unwrap_err();
+        match err {
+            AivaError::PlatformError {
+                message,
+                recoverable,
+                ..
+            } => {
+                assert!(message.contains("Running"), "{message}");
+                assert!(message.contains("pause it first"), "{message}");
+                assert!(!recoverable);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+    assert!(
+        vmm.requests()
+            .iter()
+            .all(|(_, path, _)| path != "/snapshot/create")
+    );
+}
+
+#[tokio::test]
+async fn test_snapshot_is_created_once_paused() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client.start_instance().await.unwrap();
+    client.pause_vm().await.unwrap();
+    assert_eq!(client.instance_state().await.unwrap(), "Paused");
+
+    client
+        .create_snapshot(
+            SnapshotType::Diff,
+            Path::new("/snapshot.mem"),
+            Path::new("/snapshot"),
+        )
+        .await
+        .unwrap();
+
+    let requests = vmm.requests();
+    let (method, _, body) = requests
+        .iter()
+        .find(|(_, path, _)| path == "/snapshot/create")
+        .unwrap();
+    assert_eq!(method, "PUT");
+    assert_eq!(
+        body_json(body),
+        serde_json::json!({
+            "snapshot_type": "Diff",
+            "snapshot_path": "/snapshot",
+            "mem_file_path": "/snapshot.mem",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_load_snapshot_can_leave_the_vm_paused() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+
+    client
+        .load_snapshot(Path::new("/snapshot"), Path::new("/snapshot.mem"), false)
+        .await
+        .unwrap();
+    assert_eq!(client.instance_state().await.unwrap(), "Paused");
+
+    client
+        .load_snapshot(Path::new("/snapshot"), Path::new("/snapshot.mem"), true)
+        .await
+        .unwrap();
+    assert_eq!(client.instance_state().await.unwrap(), "Running");
+
+    let loads: Vec<_> = vmm
+        .requests()
+        .into_iter()
+        .filter(|(method, path, _)| method == "PUT" && path == "/snapshot/load")
+        .map(|(_, _, body)| body_json(&body))
+        .collect();
+    assert_eq!(
+        loads[0],
+        serde_json::json!({
+            "snapshot_path": "/snapshot",
+            "mem_backend": { "backend_type": "File", "backend_path": "/snapshot.mem" },
+            "resume_vm": false,
+        })
+    );
+    assert_eq!(loads[1]["resume_vm"], true);
+}
+
+#[tokio::test]
+async fn test_mmds_data_round_trips() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+
+    client
+        .configure_mmds("V2", &["eth0".to_string()])
+        .await
+        .unwrap();
+    let data = serde_json::json!({
+        "aiva": { "name": "agent", "metadata": { "api_base": "https://example.com" } }
+    });
+    client.set_mmds_data(data.clone()).await.unwrap();
+    assert_eq!(client.get_mmds_data().await.unwrap(), data);
+
+    let requests = vmm.requests();
+    let (method, _, body) = requests
+        .iter()
+        .find(|(_, path, _)| path == "/mmds/config")
+        .unwrap();
+    assert_eq!(method, "PUT");
+    assert_eq!(
+        body_json(body),
+        serde_json::json!({
+            "version": "V2",
+            "network_interfaces": ["eth0"],
+            "ipv4_address": "169.254.169.254",
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_unknown_mmds_version_is_rejected() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+
+    let err = client
+        .configure_mmds("V3", &["eth0".to_string()])
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("V3"), "{err}");
+    assert!(vmm.requests().is_empty());
+}
+
+#[tokio::test]
+async fn test_inflating_the_balloon_reduces_actual_pages() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client
+        .configure_machine(2, 4096, None, false)
+        .await
+        .unwrap();
+    client.configure_balloon(0, true, 1).await.unwrap();
+    client.start_instance().await.unwrap();
+    assert_eq!(client.get_balloon_stats().await.unwrap().actual_pages, 0);
+
+    client.update_balloon(2048).await.unwrap();
+
+    let stats = client.get_balloon_stats().await.unwrap();
+    assert_eq!(stats.target_mib, 2048);
+    assert_eq!(stats.actual_mib, 2048);
+    // 4 KiB pages
+    assert_eq!(stats.actual_pages, 2048 * 256);
+
+    let requests = vmm.requests();
+    let (_, _, body) = requests
+        .iter()
+        .find(|(method, path, _)| method == "PUT" && path == "/balloon")
+        .unwrap();
+    assert_eq!(
+        body_json(body),
+        serde_json::json!({
+            "amount_mib": 0,
+            "deflate_on_oom": true,
+            "stats_polling_interval_s": 1,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_balloon_must_be_configured_at_boot() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client.start_instance().await.unwrap();
+
+    for err in [
+        client.update_balloon(1024).await.unwrap_err(),
+        client.get_balloon_stats().await.unwrap_err(),
+    ] {
+        match err {
+            AivaError::PlatformError {
+                message,
+                recoverable,
+                ..
+            } => {
+                assert!(message.contains("not configured"), "{message}");
+                assert!(recoverable);
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_metrics_are_written_to_the_configured_path() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client
+        .configure_metrics(Path::new("/metrics.fifo"))
+        .await
+        .unwrap();
+    client.start_instance().await.unwrap();
+    client.flush_metrics().await.unwrap();
+
+    let requests = vmm.requests();
+    let (method, _, body) = requests
+        .iter()
+        .find(|(_, path, _)| path == "/metrics")
+        .unwrap();
+    assert_eq!(method, "PUT");
+    assert_eq!(
+        body_json(body),
+        serde_json::json!({ "metrics_path": "/metrics.fifo" })
+    );
+    let (_, _, body) = requests.last().unwrap();
+    assert_eq!(
+        body_json(body),
+        serde_json::json!({ "action_type": "FlushMetrics" })
+    );
+}
+
+#[test]
+fn test_machine_config_serializes_smt_and_template() {
+    assert_eq!(
+        serde_json::to_value(MachineConfig::new(2, 1024, None, false)).unwrap(),
+        serde_json::json!({"vcpu_count": 2, "mem_size_mib": 1024, "smt": false})
+    );
+    assert_eq!(
+        serde_json::to_value(MachineConfig::new(4, 2048, Some("t2s"), true)).unwrap(),
+        serde_json::json!({
+            "vcpu_count": 4,
+            "mem_size_mib": 2048,
+            "smt": true,
+            "cpu_template": "T2S"
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_cpu_template_is_sent_with_the_machine_config() {
+    let vmm = MockFirecracker::start();
+    let client = FirecrackerApiClient::new(vmm.socket.clone()).unwrap();
+    client
+        .configure_machine(2, 4096, Some("V1N1"), false)
+        .await
+        .unwrap();
+
+    let requests = vmm.requests();
+    let (method, path, body) = &requests[0];
+    assert_eq!((method.as_str(), path.as_str()), ("PUT", "/machine-config"));
+    let body = body_json(body);
+    assert_eq!(body["cpu_template"], "V1N1");
+    assert_eq!(body["smt"], false);
+    assert!(body.get("ht_enabled").is_none());
+}
@@ -0,0 +1,110 @@
+use crate::{KvmAccess, KvmDeviceInfo, KvmUser, decide_kvm_access};
+use aiva_core::AivaError;
+use std::path::Path;
+
+const KVM_GID: u32 = 108;
+
+fn device(mode: u32) -> KvmDeviceInfo {
+    KvmDeviceInfo {
+        mode,
+        owner_uid: 0,
+        group_gid: KVM_GID,
+        group_name: Some("kvm".to_string()),
+    }
+}
+
+fn user(active_gids: Vec<u32>, in_device_group: bool) -> KvmUser {
+    KvmUser {
+        uid: 1000,
+        name: "dev".to_string(),
+        active_gids,
+        in_device_group,
+    }
+}
+
+#[test]
+fn test_kvm_access_decision_matrix() {
+    let join = KvmAccess::JoinGroup {
+        group: "kvm".to_string(),
+    };
+    let relogin = KvmAccess::ReloginRequired {
+        group: "kvm".to_string(),
+    };
+
+    let cases = [
+        // Group members of a 0660 device
+        (0o660, user(vec![1000, KVM_GID], true), KvmAccess::Granted),
+        (0o660, user(vec![1000], true), relogin),
+        (0o660, user(vec![1000], false), join.clone()),
+        // World accessible devices need nothing
+        (0o666, user(vec![1000], false), KvmAccess::Granted),
+        // Group-readable only is not enough to create VMs
+        (
+            0o640,
+            user(vec![1000, KVM_GID], true),
+            KvmAccess::FixPermissions,
+        ),
+        (0o600, user(vec![1000], false), KvmAccess::FixPermissions),
+        (0o604, user(vec![1000], false), KvmAccess::FixPermissions),
+        (0o664, user(vec![1000], false), join),
+    ];
+
+    for (mode, user, expected) in cases {
+        assert_eq!(
+            decide_kvm_access(&device(mode), &user),
+            expected,
+            "mode {mode:o} with groups {:?}",
+            user.active_gids
+        );
+    }
+}
+
+#[test]
+fn test_owner_and_root_are_granted() {
+    let mut owned = device(0o600);
+    owned.owner_uid = 1000;
+    assert_eq!(
+        decide_kvm_access(&owned, &user(vec![1000], false)),
+        KvmAccess::Granted
+    );
+
+    let root = KvmUser {
+        uid: 0,
+        ..user(vec![0], false)
+    };
+    assert_eq!(decide_kvm_access(&device(0o600), &root), KvmAccess::Granted);
+}
+
+#[test]
+fn test_access_errors_are_recoverable_with_suggestions() {
+    let path = Path::new("/dev/kvm");
+    assert!(KvmAccess::Granted.to_error(path, "dev").is_none());
+
+    let unnamed = KvmDeviceInfo {
+        group_name: None,
+        ..device(0o660)
+    };
+    let decision = decide_kvm_access(&unnamed, &user(vec![1000], false));
+    assert_eq!(
+        decision,
+        KvmAccess::JoinGroup {
+            group: KVM_GID.to_string()
+        }
+    );
+
+    let join = KvmAccess::JoinGroup {
+        group: "kvm".to_string(),
+    };
+    match join.to_error(path, "dev") {
+        Some(AivaError::PlatformError {
+            message,
+            recoverable,
+            ..
+        }) => {
+            assert!(recoverable);
+            assert!(message.contains("sudo usermod -aG kvm $USER"));
+            assert!(message.contains("--grant-kvm"));
+        }
+        other => panic!("expected a recoverable platform error, got {other:?}"),
+    }
+}
@@ -0,0 +1,43 @@
+use crate::lima::{LimaStatus, find_lima_instance, parse_lima_list};
+
+/// Trimmed `limactl list --format json` output, one object per line
+const NDJSON: &str = r#"
+{"name":"aiva-host2","status":"Running","dir":"/Users/dev/.lima/aiva-host2","vmType":"vz","arch":"aarch64","cpus":4,"memory":4294967296,"disk":107374182400,"sshLocalPort":60022}
+{"name":"default","status":"Broken","dir":"/Users/dev/.lima/default","arch":"aarch64"}
+{"name":"aiva-host","status":"Stopped","dir":"/Users/dev/.lima/aiva-host","vmType":"vz","arch":"aarch64","cpus":4,"memory":4294967296,"disk":107374182400,"sshLocalPort":0}
+"#;
+
+#[test]
+fn test_exact_name_matching_ignores_similar_instances() {
+    let instances = parse_lima_list(NDJSON).unwrap();
+    assert_eq!(instances.len(), 3);
+
+    let host = find_lima_instance(&instances, "aiva-host").unwrap();
+    assert_eq!(host.name, "aiva-host");
+    // The stopped instance is found, so it gets started instead of recreated
+    assert_eq!(host.status, LimaStatus::Stopped);
+
+    assert_eq!(
+        find_lima_instance(&instances, "aiva-host2")
+            .unwrap()
+            .ssh_local_port,
+        Some(60022)
+    );
+    assert!(find_lima_instance(&instances, "aiva").is_none());
+    assert!(find_lima_instance(&instances, "aiva-host3").is_none());
+}
+
+#[test]
+fn test_parse_array_output_and_unknown_status() {
+    let instances = parse_lima_list(
+        r#"[{"name":"aiva-host-old","status":"Running"},{"name":"aiva-host","status":"Paused"}]"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        find_lima_instance(&instances, "aiva-host").unwrap().status,
+        LimaStatus::Unknown
+    );
+    assert!(parse_lima_list("").unwrap().is_empty());
+    assert!(parse_lima_list("aiva-host Running").is_err());
+}
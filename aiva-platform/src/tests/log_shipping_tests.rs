@@ -0,0 +1,96 @@
+use crate::log_shipping::{LogFrameDecoder, LogIngestor, ShippedLine, build_tail_command};
+use aiva_core::{DefaultMetricsCollector, LogLevel, LogStore, MonitoringService, Result};
+use std::sync::Arc;
+
+#[test]
+fn test_decoder_tags_lines_with_tail_headers() {
+    let paths = vec![
+        "/var/log/app.log".to_string(),
+        "/var/log/mcp.log".to_string(),
+    ];
+    let mut decoder = LogFrameDecoder::new(&paths);
+
+    let stream = [
+        "==> /var/log/app.log <==",
+        "app started",
+        "",
+        "==> /var/log/mcp.log <==",
+        "listening on :3000",
+    ];
+
+    let decoded: Vec<ShippedLine> = stream.iter().filter_map(|l| decoder.decode(l)).collect();
+
+    assert_eq!(
+        decoded,
+        vec![
+            ShippedLine {
+                path: "/var/log/app.log".to_string(),
+                line: "app started".to_string(),
+            },
+            ShippedLine {
+                path: "/var/log/mcp.log".to_string(),
+                line: "listening on :3000".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_decoder_single_path_has_no_headers() {
+    let paths = vec!["/var/log/app.log".to_string()];
+    let mut decoder = LogFrameDecoder::new(&paths);
+
+    let line = decoder.decode("hello\r").unwrap();
+    assert_eq!(line.path, "/var/log/app.log");
+    assert_eq!(line.line, "hello");
+}
+
+#[test]
+fn test_build_tail_command_quotes_paths() {
+    let paths = vec!["/var/log/a b.log".to_string(), "/tmp/it's.log".to_string()];
+    let command = build_tail_command(&paths).unwrap();
+
+    assert_eq!(
+        command,
+        r"tail -n 0 -F '/var/log/a b.log' '/tmp/it'\''s.log' 2>/dev/null"
+    );
+    assert!(build_tail_command(&[]).is_err());
+}
+
+#[tokio::test]
+async fn test_shipped_lines_appear_in_get_logs() -> Result<()> {
+    let monitoring = Arc::new(MonitoringService::new(Box::new(DefaultMetricsCollector)));
+    let store_dir = std::env::temp_dir().join(format!("aiva-log-store-{}", uuid::Uuid::new_v4()));
+    let paths = vec![
+        "/var/log/app.log".to_string(),
+        "/var/log/mcp.log".to_string(),
+    ];
+
+    let mut ingestor = LogIngestor::new("vm-1".to_string(), &paths, monitoring.clone())
+        .with_store(LogStore::new(store_dir.clone()));
+
+    for raw in [
+        "==> /var/log/app.log <==",
+        "INFO ready",
+        "",
+        "==> /var/log/mcp.log <==",
+        "ERROR upstream failed",
+    ] {
+        ingestor.ingest(raw).await?;
+    }
+
+    let logs = monitoring.get_logs(Some("vm-1"), None).await?;
+    assert_eq!(logs.len(), 2);
+    assert_eq!(logs[0].message, "INFO ready");
+    assert_eq!(logs[0].metadata.get("path").unwrap(), "/var/log/app.log");
+    assert_eq!(logs[1].metadata.get("path").unwrap(), "/var/log/mcp.log");
+    assert!(matches!(logs[1].level, LogLevel::Error));
+    assert!(monitoring.get_logs(Some("vm-2"), None).await?.is_empty());
+
+    let persisted = LogStore::new(store_dir.clone()).read("vm-1").await?;
+    assert_eq!(persisted.len(), 2);
+    assert_eq!(persisted[1].message, "ERROR upstream failed");
+
+    let _ = std::fs::remove_dir_all(store_dir);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use crate::macos::{LimaVmSample, render_metrics_script};
+use std::time::Duration;
+
+#[test]
+fn test_sample_of_a_running_vm() {
+    let sample: LimaVmSample = serde_json::from_str(
+        r#"{"running": true, "process_ticks": [1000, 1050], "system_ticks": [8000, 8400],
+            "cpus": 4, "vm_size_kb": 2097152, "vm_rss_kb": 524288,
+            "start_ticks": 50000, "clock_ticks": 100, "uptime_secs": 3600.5,
+            "read_bytes": 4096, "write_bytes": 8192,
+            "rx_bytes": 1500, "tx_bytes": 600, "rx_packets": 3, "tx_packets": 2}"#,
+    )
+    .unwrap();
+
+    let metrics = sample.into_metrics(4096);
+    // 50 of 100 ticks of wall time busy
+    assert_eq!(metrics.cpu_usage, 50.0);
+    assert_eq!(metrics.memory_usage.total_mb, 2048);
+    assert_eq!(metrics.memory_usage.used_mb, 512);
+    assert_eq!(metrics.memory_usage.available_mb, 1536);
+    assert_eq!(metrics.disk_io.read_bytes, 4096);
+    assert_eq!(metrics.disk_io.write_bytes, 8192);
+    assert_eq!(metrics.network_io.rx_bytes, 1500);
+    assert_eq!(metrics.network_io.tx_packets, 2);
+    assert_eq!(metrics.uptime, Duration::from_secs_f64(3100.5));
+}
+
+#[test]
+fn test_stopped_vm_reports_no_usage() {
+    let sample: LimaVmSample = serde_json::from_str(r#"{"running": false}"#).unwrap();
+    let metrics = sample.into_metrics(4096);
+    assert_eq!(metrics.cpu_usage, 0.0);
+    assert_eq!(metrics.memory_usage.total_mb, 4096);
+    assert_eq!(metrics.memory_usage.used_mb, 0);
+    assert_eq!(metrics.network_io.rx_bytes, 0);
+    assert_eq!(metrics.uptime, Duration::ZERO);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_metrics_script_samples_the_process_on_the_socket() {
+    use std::os::unix::fs::PermissionsExt;
+
+    if which::which("pgrep").is_err() {
+        eprintln!("pgrep not installed, skipping");
+        return;
+    }
+
+    // Something whose command line looks like Firecracker serving the socket
+    let dir = std::env::temp_dir().join(format!("aiva-lima-metrics-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let binary = dir.join("firecracker");
+    std::fs::write(&binary, "#!/bin/sh\nwhile :; do sleep 1; done\n").unwrap();
+    std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let socket = dir.join("firecracker.socket").display().to_string();
+    let mut child = std::process::Command::new(&binary)
+        .args(["--api-sock", &socket])
+        .spawn()
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+
+    let script = render_metrics_script(&socket, "aiva-no-such-tap").unwrap();
+    let run = |script: &str| {
+        let output = std::process::Command::new("sh")
+            .args(["-c", script])
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        serde_json::from_slice::<LimaVmSample>(&output.stdout)
+            .unwrap_or_else(|e| panic!("{e}: {}", String::from_utf8_lossy(&output.stdout)))
+    };
+
+    let sample = run(&script);
+    assert!(sample.running);
+    assert!(sample.cpus >= 1);
+    assert!(sample.system_ticks[1] > sample.system_ticks[0]);
+    assert!(sample.vm_rss_kb > 0);
+    assert!(sample.clock_ticks > 0);
+    assert!(sample.uptime_secs > 0.0);
+    // The TAP device does not exist
+    assert_eq!(sample.rx_bytes, 0);
+    let metrics = sample.into_metrics(1024);
+    assert!(metrics.memory_usage.used_mb <= metrics.memory_usage.total_mb);
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    assert!(!run(&script).running);
+    let _ = std::fs::remove_dir_all(dir);
+}
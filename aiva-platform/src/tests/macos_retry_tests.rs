@@ -0,0 +1,132 @@
+use crate::macos::{LimaRetryPolicy, backoff_delay, retry_async, ssh_failure_is_transient};
+use aiva_core::{AivaError, MacOSConfig};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+fn fast_policy(attempts: u32) -> LimaRetryPolicy {
+    LimaRetryPolicy {
+        attempts,
+        base_delay: Duration::from_millis(5),
+        max_delay: Duration::from_millis(20),
+    }
+}
+
+fn platform_error(recoverable: bool) -> AivaError {
+    AivaError::PlatformError {
+        platform: "macos".to_string(),
+        message: "ssh: connect to host 127.0.0.1 port 60022: Connection refused".to_string(),
+        recoverable,
+    }
+}
+
+#[test]
+fn test_backoff_doubles_up_to_the_cap() {
+    let policy = LimaRetryPolicy {
+        attempts: 10,
+        base_delay: Duration::from_millis(250),
+        max_delay: Duration::from_secs(2),
+    };
+    let delays: Vec<_> = (1..=5)
+        .map(|retry| backoff_delay(&policy, retry, 0.0))
+        .collect();
+    assert_eq!(
+        delays,
+        [250, 500, 1000, 2000, 2000].map(Duration::from_millis)
+    );
+    // Jitter takes off at most a quarter
+    assert_eq!(backoff_delay(&policy, 2, 1.0), Duration::from_millis(375));
+    assert_eq!(backoff_delay(&policy, 40, 0.5), Duration::from_millis(1750));
+}
+
+#[test]
+fn test_only_ssh_connection_errors_are_transient() {
+    assert!(ssh_failure_is_transient(
+        Some(255),
+        "ssh: connect to host 127.0.0.1 port 60022: Connection refused"
+    ));
+    assert!(ssh_failure_is_transient(
+        Some(255),
+        "kex_exchange_identification: read: Connection reset by peer"
+    ));
+    // A failed login, or the command's own failure
+    assert!(!ssh_failure_is_transient(
+        Some(255),
+        "user@127.0.0.1: Permission denied (publickey)."
+    ));
+    assert!(!ssh_failure_is_transient(
+        Some(127),
+        "bash: firecracker: command not found"
+    ));
+    assert!(!ssh_failure_is_transient(Some(1), "Connection refused"));
+}
+
+#[tokio::test]
+async fn test_recoverable_errors_are_retried_until_success() {
+    let calls = AtomicU32::new(0);
+    let result = retry_async(&fast_policy(5), "ssh", || async {
+        if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+            Err(platform_error(true))
+        } else {
+            Ok("ready")
+        }
+    })
+    .await;
+    assert_eq!(result.unwrap(), "ready");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_retries_stop_after_the_last_attempt() {
+    let calls = AtomicU32::new(0);
+    let result: aiva_core::Result<()> = retry_async(&fast_policy(3), "ssh", || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(platform_error(true))
+    })
+    .await;
+    assert!(
+        result
+            .unwrap_err()
+            .to_string()
+            .contains("Connection refused")
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_unrecoverable_errors_fail_fast() {
+    let calls = AtomicU32::new(0);
+    let result: aiva_core::Result<()> = retry_async(&fast_policy(5), "ssh", || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(platform_error(false))
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let calls = AtomicU32::new(0);
+    let result: aiva_core::Result<()> = retry_async(&fast_policy(5), "ssh", || async {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Err(AivaError::ConfigError("bad".to_string()))
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_policy_follows_the_macos_config() {
+    let config = MacOSConfig {
+        exec_retries: 2,
+        max_backoff_ms: 800,
+        ..aiva_core::Config::default().platform.macos
+    };
+    let policy = LimaRetryPolicy::from(&config);
+    assert_eq!(policy.attempts, 3);
+    assert_eq!(policy.max_delay, Duration::from_millis(800));
+
+    let config = MacOSConfig {
+        exec_retries: u32::MAX,
+        ..config
+    };
+    assert_eq!(LimaRetryPolicy::from(&config).attempts, u32::MAX);
+}
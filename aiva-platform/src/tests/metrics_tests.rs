@@ -0,0 +1,108 @@
+use crate::metrics::{
+    FirecrackerCounters, aggregate_network_stats, drain_firecracker_metrics, parse_net_dev,
+};
+use aiva_core::NetworkStats;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+
+const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:   52000     400    0    0    0     0          0         0    52000     400    0    0    0     0       0          0
+  eth0: 9000000    7000    1    2    0     0          0         0  1200000    5000    0    0    0     0       0          0
+aiva-tap0:   15000     100    2    1    0     0          0         0    30000     200    1    3    0     0       0          0
+aiva-tap1:4294967296   900    0    4    0     0          0         0     7000      50    0    0    0     0       0          0
+aiva-tap10:     999       9    9    9    0     0          0         0      999       9    9    9    0     0       0          0
+";
+
+#[test]
+fn test_tap_counters_are_summed_into_network_stats() {
+    let interfaces = parse_net_dev(NET_DEV);
+    assert_eq!(interfaces.len(), 5);
+    // A counter running into the colon still parses
+    assert_eq!(interfaces["aiva-tap1"].rx_bytes, 4_294_967_296);
+
+    let stats = aggregate_network_stats(&interfaces, ["aiva-tap0", "aiva-tap1", "aiva-gone"]);
+    assert_eq!(
+        stats,
+        NetworkStats {
+            rx_bytes: 4_294_967_296 + 15_000,
+            tx_bytes: 37_000,
+            rx_packets: 1_000,
+            tx_packets: 250,
+            errors: 3,
+            drops: 8,
+        }
+    );
+    assert!((stats.error_rate() - 11.0 / 1_250.0 * 100.0).abs() < 1e-9);
+
+    assert_eq!(
+        aggregate_network_stats(&interfaces, []),
+        NetworkStats::default()
+    );
+}
+
+/// Two flushes as Firecracker writes them, trimmed to a few sections
+const FLUSHES: &str = r#"{"utc_timestamp_ms":1,"block":{"read_bytes":4096,"write_bytes":512,"read_count":2,"write_count":1,"flush_count":0},"block_rootfs":{"read_bytes":4096},"net":{"rx_bytes_count":1500,"tx_bytes_count":600,"rx_packets_count":3,"tx_packets_count":2},"vcpu":{"exit_io_in":7}}
+{"utc_timestamp_ms":2,"block":{"read_bytes":8192,"write_bytes":0,"read_count":1,"write_count":0},"net":{"rx_bytes_count":100,"tx_bytes_count":0,"rx_packets_count":1,"tx_packets_count":0}}
+"#;
+
+#[test]
+fn test_firecracker_flushes_are_summed() {
+    let mut counters = FirecrackerCounters::default();
+    counters.add_flushes(FLUSHES);
+    // A flush cut off by a full FIFO is skipped
+    counters.add_flushes(r#"{"block":{"read_bytes":99"#);
+
+    assert_eq!(
+        counters,
+        FirecrackerCounters {
+            read_bytes: 12_288,
+            write_bytes: 512,
+            read_ops: 3,
+            write_ops: 1,
+            rx_bytes: 1_600,
+            tx_bytes: 600,
+            rx_packets: 4,
+            tx_packets: 2,
+        }
+    );
+    let disk_io = counters.disk_io();
+    assert_eq!((disk_io.read_ops, disk_io.write_ops), (3, 1));
+    let network_io = counters.network_io();
+    assert_eq!((network_io.rx_packets, network_io.tx_packets), (4, 2));
+}
+
+#[test]
+fn test_metrics_fifo_is_drained_into_running_totals() {
+    let dir = std::env::temp_dir().join(format!("aiva-fc-metrics-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let fifo = dir.join("metrics.fifo");
+    let totals = dir.join("metrics.json");
+    nix::unistd::mkfifo(&fifo, nix::sys::stat::Mode::from_bits_truncate(0o600)).unwrap();
+    // Firecracker opens its end read-write and non-blocking
+    let mut vmm = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(nix::libc::O_NONBLOCK)
+        .open(&fifo)
+        .unwrap();
+
+    // Nothing flushed yet
+    let counters = drain_firecracker_metrics(&fifo, &totals).unwrap();
+    assert_eq!(counters, FirecrackerCounters::default());
+
+    vmm.write_all(FLUSHES.as_bytes()).unwrap();
+    let counters = drain_firecracker_metrics(&fifo, &totals).unwrap();
+    assert_eq!(counters.read_ops, 3);
+
+    // Later flushes add to what an earlier reader stored
+    vmm.write_all(FLUSHES.lines().next().unwrap().as_bytes())
+        .unwrap();
+    vmm.write_all(b"\n").unwrap();
+    let counters = drain_firecracker_metrics(&fifo, &totals).unwrap();
+    assert_eq!(counters.read_ops, 5);
+    assert_eq!(counters.rx_packets, 7);
+
+    let _ = std::fs::remove_dir_all(dir);
+}
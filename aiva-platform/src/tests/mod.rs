@@ -1,6 +1,30 @@
 #[cfg(test)]
+mod api_tunnel_tests;
+#[cfg(test)]
+mod arch_tests;
+#[cfg(test)]
 mod command_pool_tests;
 #[cfg(test)]
+mod cpu_usage_tests;
+#[cfg(test)]
+mod firecracker_tests;
+#[cfg(test)]
+mod kvm_access_tests;
+#[cfg(test)]
+mod lima_tests;
+#[cfg(test)]
+mod log_shipping_tests;
+#[cfg(test)]
+mod macos_metrics_tests;
+#[cfg(test)]
+mod macos_retry_tests;
+#[cfg(test)]
+mod metrics_tests;
+#[cfg(test)]
 mod platform_tests;
 #[cfg(test)]
+mod setup_template_tests;
+#[cfg(test)]
 mod vsock_executor_tests;
+#[cfg(test)]
+mod windows_exec_tests;
@@ -1,7 +1,5 @@
 use crate::{detect_platform, get_current_platform};
-use aiva_core::{
-    CacheStrategy, NetworkConfig, Platform, Result, StorageConfig, VMConfig, VMInstance, VMState,
-};
+use aiva_core::{Platform, Result, VMConfig, VMInstance, VMState};
 use uuid::Uuid;
 
 #[test]
@@ -48,34 +46,26 @@ fn create_test_vm_instance(name: &str) -> VMInstance {
         id: Uuid::new_v4(),
         name: name.to_string(),
         state: VMState::Stopped,
-        config: VMConfig {
-            cpus: 2,
-            memory_mb: 1024,
-            disk_gb: 10,
-            kernel_path: "/test/kernel".to_string().into(),
-            rootfs_path: "/test/rootfs".to_string().into(),
-            network: NetworkConfig {
-                guest_ip: "192.168.1.100".to_string(),
-                host_ip: "192.168.1.1".to_string(),
-                subnet: "192.168.1.0/24".to_string(),
-                gateway: "192.168.1.1".to_string(),
-                dns_servers: vec!["8.8.8.8".to_string()],
-                dhcp_enabled: false,
-                port_mappings: vec![],
-            },
-            storage: StorageConfig {
-                cache_strategy: CacheStrategy::Writeback,
-                additional_drives: vec![],
-            },
-        },
+        config: VMConfig::builder()
+            .memory_mb(1024)
+            .disk_gb(10)
+            .kernel_path("/test/kernel")
+            .rootfs_path("/test/rootfs")
+            .addresses("192.168.1.100", "192.168.1.1", "192.168.1.0/24")
+            .dns_servers(["8.8.8.8"])
+            .build()
+            .unwrap(),
         runtime: aiva_core::RuntimeInfo {
             pid: None,
             api_socket: None,
             vsock_cid: None,
             tap_device: None,
+            mcp_pids: Some(Vec::new()),
+            paused: None,
         },
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        schema_version: aiva_core::SCHEMA_VERSION,
     }
 }
 
@@ -91,6 +81,798 @@ mod linux_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_vsock_of_two_vms_goes_to_distinct_devices() -> Result<()> {
+        use crate::command_pool::{ConnectionType, connection_for};
+
+        let platform = LinuxPlatform::new()?;
+        let endpoint = |name: &str, cid: u32| {
+            let mut vm = create_test_vm_instance(name);
+            vm.runtime.vsock_cid = Some(cid);
+            let uds = platform.vsock_uds_path(&vm);
+            assert!(uds.starts_with(platform.jailer_workspace(&vm)));
+            match connection_for(aiva_core::ExecTransport::Vsock, &vm, Some(uds)) {
+                Some(ConnectionType::Vsock { cid, uds_path }) => (cid, uds_path),
+                other => panic!("expected a vsock connection, got {other:?}"),
+            }
+        };
+
+        let (web_cid, web_uds) = endpoint("web", 3);
+        let (worker_cid, worker_uds) = endpoint("worker", 4);
+        assert_eq!((web_cid, worker_cid), (3, 4));
+        assert_ne!(web_uds, worker_uds);
+        assert!(web_uds.ends_with("root/vsock.sock"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_memory_with_rss_above_vm_size_does_not_underflow() {
+        let status = "Name:\tfirecracker\nVmSize:\t  102400 kB\nVmRSS:\t  104448 kB\n";
+        let memory = crate::linux::parse_process_memory(status);
+        assert_eq!((memory.total_mb, memory.used_mb), (100, 102));
+        assert_eq!(memory.available_mb, 0);
+
+        let memory = crate::linux::parse_process_memory("Name:\tfirecracker\n");
+        assert_eq!((memory.total_mb, memory.available_mb), (0, 0));
+    }
+
+    #[test]
+    fn test_jailer_args_enforce_restricted_preset_limits() -> Result<()> {
+        let platform = LinuxPlatform::new()?;
+        let vm = create_test_vm_instance("jailed");
+        let policy = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap();
+
+        let args: Vec<String> = platform
+            .jailer_args(
+                std::path::Path::new("/tmp/aiva-jailer"),
+                &vm,
+                Some(&policy.resource_limits),
+            )?
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        let cgroup_flags: Vec<&str> = args
+            .windows(2)
+            .filter(|pair| pair[0] == "--cgroup")
+            .map(|pair| pair[1].as_str())
+            .collect();
+        // 50% of the VM's two vCPUs
+        assert_eq!(
+            cgroup_flags,
+            [
+                "cpu.max=100000 100000",
+                "memory.max=4294967296",
+                "pids.max=512"
+            ]
+        );
+        assert!(
+            args.windows(2)
+                .any(|pair| pair == ["--cgroup-version", "2"])
+        );
+
+        // The jailer would otherwise reset the limit for Firecracker
+        let resource_limit = args
+            .windows(2)
+            .position(|pair| pair == ["--resource-limit", "no-file=512"])
+            .unwrap();
+
+        // Jailer flags must come before the Firecracker arguments
+        let separator = args.iter().position(|arg| arg == "--").unwrap();
+        let last_cgroup = args.iter().rposition(|arg| arg == "--cgroup").unwrap();
+        assert!(last_cgroup < separator);
+        assert!(resource_limit < separator);
+
+        let unlimited = platform.jailer_args(std::path::Path::new("/tmp"), &vm, None)?;
+        assert!(!unlimited.iter().any(|arg| arg == "--cgroup"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_jailer_owner_defaults_to_the_invoking_user() {
+        use crate::linux::jailer_owner;
+        use aiva_core::JailerConfig;
+
+        let primary_gid = |uid| (uid == 990).then_some(985);
+        let defaults = JailerConfig::default();
+        assert_eq!(
+            jailer_owner(&defaults, (1001, 1002), primary_gid),
+            (1001, 1002)
+        );
+
+        // A service account runs in its own group unless one is named
+        let service = JailerConfig {
+            uid: Some(990),
+            ..JailerConfig::default()
+        };
+        assert_eq!(jailer_owner(&service, (0, 0), primary_gid), (990, 985));
+        let grouped = JailerConfig {
+            gid: Some(27),
+            ..service
+        };
+        assert_eq!(jailer_owner(&grouped, (0, 0), primary_gid), (990, 27));
+    }
+
+    #[test]
+    fn test_jailer_args_follow_the_jailer_config() -> Result<()> {
+        use aiva_core::JailerConfig;
+
+        let jailer = JailerConfig {
+            uid: Some(4321),
+            gid: Some(4322),
+            numa_node: Some(1),
+            ..JailerConfig::default()
+        };
+        let platform = LinuxPlatform::new()?.with_jailer_config(jailer.clone());
+        let vm = create_test_vm_instance("jailed");
+
+        let args: Vec<String> = platform
+            .jailer_args(std::path::Path::new("/srv/aiva"), &vm, None)?
+            .iter()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+        for flag in [
+            ["--uid", "4321"],
+            ["--gid", "4322"],
+            ["--cgroup-version", "2"],
+            ["--node", "1"],
+        ] {
+            assert!(
+                args.windows(2).any(|pair| pair == flag),
+                "{flag:?} in {args:?}"
+            );
+        }
+
+        // The limits of a policy are cgroup v2 files
+        let platform = LinuxPlatform::new()?.with_jailer_config(JailerConfig {
+            cgroup_version: 1,
+            ..jailer
+        });
+        let policy = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap();
+        let err = platform
+            .jailer_args(
+                std::path::Path::new("/srv/aiva"),
+                &vm,
+                Some(&policy.resource_limits),
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("need cgroup v2"), "{err}");
+        assert!(
+            platform
+                .jailer_args(std::path::Path::new("/srv/aiva"), &vm, None)?
+                .windows(2)
+                .any(|pair| pair == ["--cgroup-version", "1"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_jailer_rejects_unusable_settings() -> Result<()> {
+        use aiva_core::{AivaError, JailerConfig};
+
+        let dir = std::env::temp_dir().join(format!("aiva-jailer-base-{}", Uuid::new_v4()));
+        let usable = JailerConfig {
+            uid: Some(0),
+            gid: Some(0),
+            chroot_base_dir: dir.join("jails"),
+            ..JailerConfig::default()
+        };
+        LinuxPlatform::new()?
+            .with_jailer_config(usable.clone())
+            .check_jailer()?;
+        assert!(dir.join("jails").is_dir());
+
+        let check = |jailer: JailerConfig| {
+            LinuxPlatform::new()
+                .unwrap()
+                .with_jailer_config(jailer)
+                .check_jailer()
+                .unwrap_err()
+        };
+        let err = check(JailerConfig {
+            uid: Some(3_999_999_999),
+            ..usable.clone()
+        });
+        assert!(matches!(err, AivaError::ConfigError(_)), "{err:?}");
+        assert!(
+            err.to_string().contains("platform.linux.jailer.uid"),
+            "{err}"
+        );
+
+        let err = check(JailerConfig {
+            gid: Some(3_999_999_999),
+            ..usable.clone()
+        });
+        assert!(
+            err.to_string().contains("platform.linux.jailer.gid"),
+            "{err}"
+        );
+
+        // A file where the base directory should be
+        std::fs::write(dir.join("file"), "")?;
+        let err = check(JailerConfig {
+            chroot_base_dir: dir.join("file").join("jails"),
+            ..usable.clone()
+        });
+        assert!(err.to_string().contains("chroot_base_dir"), "{err}");
+
+        let err = check(JailerConfig {
+            cgroup_version: 3,
+            ..usable
+        });
+        assert!(err.to_string().contains("cgroup_version"), "{err}");
+
+        let _ = std::fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[test]
+    fn test_jailer_args_reject_zero_limits() {
+        let platform = LinuxPlatform::new().unwrap();
+        let vm = create_test_vm_instance("jailed");
+        let limits = aiva_security::ResourceLimits {
+            pids_limit: Some(0),
+            ..Default::default()
+        };
+
+        let err = platform
+            .jailer_args(std::path::Path::new("/tmp"), &vm, Some(&limits))
+            .unwrap_err();
+        assert!(err.to_string().contains("pids_limit"));
+    }
+
+    #[test]
+    fn test_restricted_preset_keeps_the_builtin_seccomp_filter() -> Result<()> {
+        let platform = LinuxPlatform::new()?;
+        let policy = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap();
+        let workspace = std::env::temp_dir().join(format!("aiva-jailer-{}", Uuid::new_v4()));
+
+        // Its filter allows whatever it does not list
+        assert!(platform.seccomp_args(&workspace, Some(&policy))?.is_empty());
+        assert!(!workspace.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_restricted_policy_keeps_the_vm_gateway_reachable_on_create() -> Result<()> {
+        use crate::linux::ip_block_rules;
+
+        let policy = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap();
+        // A subnet inside the policy's blocked 10.0.0.0/8
+        let mut vm = create_test_vm_instance("blocked");
+        vm.config.network.subnet = "10.0.0.0/24".to_string();
+        vm.config.network.guest_ip = "10.0.0.2".to_string();
+        vm.config.network.host_ip = "10.0.0.1".to_string();
+        vm.config.network.gateway = "10.0.0.1".to_string();
+
+        let rules = ip_block_rules(&vm, &policy.network_policy, "aiva-tap-blocked")?;
+        let target = |dest: &str| {
+            rules
+                .iter()
+                .position(|rule| rule[5] == dest)
+                .map(|i| (i, rules[i].last().unwrap().as_str()))
+        };
+        let (accept, action) = target("10.0.0.1").unwrap();
+        assert_eq!(action, "ACCEPT");
+        let (drop, action) = target("10.0.0.0/8").unwrap();
+        assert_eq!(action, "DROP");
+        assert!(accept < drop);
+        assert!(rules.iter().all(|rule| rule[3] == "aiva-tap-blocked"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_allowlist_policy_passes_seccomp_filter_to_firecracker() -> Result<()> {
+        let platform = LinuxPlatform::new()?;
+        let mut policy = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap();
+        if let Some(filter) = policy.syscall_filter.as_mut() {
+            filter.default_action = aiva_security::FilterAction::Kill;
+        }
+        let workspace = std::env::temp_dir().join(format!("aiva-jailer-{}", Uuid::new_v4()));
+
+        let args = platform.seccomp_args(&workspace, Some(&policy))?;
+        let filter = workspace
+            .join("root")
+            .join(aiva_security::SECCOMP_FILTER_FILE);
+        assert_eq!(
+            args,
+            ["--seccomp-filter".into(), filter.clone().into_os_string()]
+        );
+        assert!(std::fs::metadata(&filter)?.len() > 0);
+        std::fs::remove_dir_all(&workspace)?;
+
+        assert!(platform.seccomp_args(&workspace, None)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pre_exec_sets_restricted_open_files_limit() -> Result<()> {
+        use crate::linux::{host_open_files_hard_limit, limit_open_files_on_exec};
+
+        let limits = aiva_security::load_preset_policies()
+            .remove("restricted")
+            .unwrap()
+            .resource_limits;
+        let limit = limits
+            .open_files_limit(host_open_files_hard_limit()?)?
+            .unwrap();
+        assert_eq!((limit.soft, limit.hard), (512, 512));
+
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "ulimit -Sn; ulimit -Hn"]);
+        limit_open_files_on_exec(&mut command, limit);
+        let output = command.output()?;
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "512\n512\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_files_limit_must_fit_the_host() {
+        let limits = aiva_security::ResourceLimits {
+            open_files: Some(4096),
+            ..Default::default()
+        };
+        let err = limits.open_files_limit(1024).unwrap_err();
+        assert!(err.to_string().contains("at most 1024"));
+
+        let zero = aiva_security::ResourceLimits {
+            open_files: Some(0),
+            ..Default::default()
+        };
+        assert!(zero.open_files_limit(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn test_grow_rootfs_rejects_disk_smaller_than_image() {
+        let image = std::env::temp_dir().join(format!("aiva-rootfs-{}.ext4", Uuid::new_v4()));
+        let size = 2 * aiva_core::GIB + 1;
+        std::fs::File::create(&image)
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+
+        let err = crate::linux::grow_rootfs(&image, 2, true).unwrap_err();
+        assert!(
+            matches!(err, aiva_core::AivaError::ConfigError(_)),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("at least 3"));
+        // The image is left alone, resize2fs never ran
+        assert_eq!(std::fs::metadata(&image).unwrap().len(), size);
+
+        let _ = std::fs::remove_file(image);
+    }
+
+    #[test]
+    fn test_scale_capabilities_need_a_balloon() {
+        let with_balloon = serde_json::json!({
+            "machine-config": { "vcpu_count": 2, "mem_size_mib": 4096 },
+            "balloon": { "amount_mib": 0, "deflate_on_oom": true },
+        });
+        let capabilities = crate::linux::scale_capabilities_from_vm_config(&with_balloon);
+        assert_eq!(capabilities.balloon_max_mb, Some(4096));
+        assert!(!capabilities.cpu_hotplug);
+
+        let without = serde_json::json!({
+            "machine-config": { "vcpu_count": 2, "mem_size_mib": 4096 },
+            "balloon": null,
+        });
+        assert_eq!(
+            crate::linux::scale_capabilities_from_vm_config(&without).balloon_max_mb,
+            None
+        );
+    }
+
+    #[test]
+    fn test_delete_stops_on_the_process_before_tap_and_workspace() {
+        use crate::linux::{DeleteStep, delete_steps};
+
+        let mut vm = create_test_vm_instance("deleted");
+        vm.runtime.pid = Some(4242);
+        vm.runtime.tap_device = Some("aiva-tap0".to_string());
+        let workspace = std::path::Path::new("/tmp/aiva-jailer").join(vm.id.to_string());
+        let steps = delete_steps(&vm, &workspace);
+        assert!(matches!(
+            steps.as_slice(),
+            [
+                DeleteStep::ConfirmExited(4242),
+                DeleteStep::RemoveTap(tap),
+                DeleteStep::RemoveWorkspace(path),
+            ] if tap == "aiva-tap0" && *path == workspace
+        ));
+
+        // Resources that were never recorded are skipped, the workspace is not
+        vm.runtime.pid = None;
+        vm.runtime.tap_device = None;
+        assert!(matches!(
+            delete_steps(&vm, &workspace).as_slice(),
+            [DeleteStep::RemoveWorkspace(_)]
+        ));
+    }
+
+    #[test]
+    fn test_orphans_exclude_resources_of_known_vms() -> Result<()> {
+        use crate::linux::{find_orphans, settled_workspaces};
+        use aiva_core::OrphanedResource;
+        use std::time::Duration;
+
+        let running = create_test_vm_instance("running");
+        let mut stopped = create_test_vm_instance("stopped");
+        stopped.runtime.tap_device = Some("aiva-tap-renamed".to_string());
+
+        let root = std::env::temp_dir().join(format!("aiva-orphans-{}", Uuid::new_v4()));
+        let gone = Uuid::new_v4().to_string();
+        for id in [running.id.to_string(), stopped.id.to_string(), gone.clone()] {
+            std::fs::create_dir_all(root.join(id))?;
+        }
+        // Too young to tell from a create in progress
+        assert!(settled_workspaces(&root, Duration::from_secs(3600))?.is_empty());
+        let workspaces = settled_workspaces(&root, Duration::ZERO)?;
+        assert_eq!(workspaces.len(), 3);
+
+        let taps = ["aiva-tap-running", "aiva-tap-renamed", "aiva-tap-old"]
+            .map(str::to_string)
+            .to_vec();
+        assert_eq!(
+            find_orphans(&[running, stopped], taps, workspaces),
+            vec![
+                OrphanedResource::TapDevice("aiva-tap-old".to_string()),
+                OrphanedResource::Workspace(root.join(gone)),
+            ]
+        );
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_removing_a_workspace_twice_succeeds() -> Result<()> {
+        let workspace = std::env::temp_dir().join(format!("aiva-jailer-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(workspace.join("root"))?;
+        std::fs::write(workspace.join("root").join("firecracker.socket"), "")?;
+
+        crate::linux::remove_workspace(&workspace)?;
+        assert!(!workspace.exists());
+        crate::linux::remove_workspace(&workspace)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lingering_firecracker_blocks_delete() -> Result<()> {
+        use std::time::Duration;
+
+        // A process whose name says Firecracker, as the liveness check sees it
+        let dir = std::env::temp_dir().join(format!("aiva-lingering-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir)?;
+        let binary = dir.join("firecracker");
+        std::os::unix::fs::symlink(which::which("sleep").unwrap(), &binary)?;
+        let mut child = std::process::Command::new(&binary).arg("30").spawn()?;
+        let comm = format!("/proc/{}/comm", child.id());
+        for _ in 0..50 {
+            if std::fs::read_to_string(&comm).is_ok_and(|name| name.trim() == "firecracker") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        let err = crate::linux::wait_for_exit(child.id(), Duration::from_millis(300))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                aiva_core::AivaError::PlatformError {
+                    recoverable: true,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("still running"), "{err}");
+
+        child.kill()?;
+        child.wait()?;
+        crate::linux::wait_for_exit(child.id(), Duration::from_millis(300)).await?;
+
+        let _ = std::fs::remove_dir_all(dir);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop_escalates_when_the_guest_ignores_ctrl_alt_del() -> Result<()> {
+        use crate::linux::{escalate_stop, process_running};
+        use aiva_core::StopMethod;
+        use std::time::Duration;
+
+        let grace = Duration::from_millis(200);
+
+        // Powers off within the grace period
+        let mut child = std::process::Command::new("sleep").arg("0.05").spawn()?;
+        let method = escalate_stop(child.id(), grace, grace, process_running).await?;
+        assert_eq!(method, StopMethod::Graceful);
+        child.wait()?;
+
+        // Never powers off on its own but honours SIGTERM
+        let mut child = std::process::Command::new("sleep").arg("30").spawn()?;
+        let method = escalate_stop(child.id(), grace, grace, process_running).await?;
+        assert_eq!(method, StopMethod::Terminated);
+        assert!(!child.wait()?.success());
+
+        // Ignores SIGTERM too
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "trap '' TERM; exec sleep 30"])
+            .spawn()?;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let method = escalate_stop(child.id(), grace, grace, process_running).await?;
+        assert_eq!(method, StopMethod::Killed);
+        child.wait()?;
+        assert!(!process_running(child.id()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failed_drive_configuration_rolls_back_create() -> Result<()> {
+        use crate::firecracker::FirecrackerApiClient;
+        use crate::linux::{CreateRollback, CreateStep, configure_and_boot};
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+
+        let vmm = MockFirecracker::failing_on(Some("/drives/"));
+        let api_client = FirecrackerApiClient::new(vmm.socket.clone())?;
+        let vm = create_test_vm_instance("rollback");
+
+        // What create acquired before configuring the VM
+        let workspace = std::env::temp_dir().join(format!("aiva-jailer-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(workspace.join("root"))?;
+        std::fs::write(workspace.join("root").join("rootfs.ext4"), "")?;
+        let child = std::process::Command::new("sleep").arg("30").spawn()?;
+        let pid = child.id();
+        let mut acquired = CreateRollback {
+            child: Some(child),
+            workspace: Some(workspace.clone()),
+            ..Default::default()
+        };
+
+        let failure = configure_and_boot(&api_client, &vm, None, &mut acquired)
+            .await
+            .unwrap_err();
+        assert_eq!(failure.step, CreateStep::ConfigureDrive);
+        // Nothing after the drive was attempted, so no TAP device exists
+        let paths: Vec<String> = vmm
+            .requests()
+            .into_iter()
+            .map(|(_, path, _)| path)
+            .collect();
+        assert_eq!(paths, ["/machine-config", "/boot-source", "/drives/rootfs"]);
+        assert!(acquired.tap_device.is_none());
+
+        let err = acquired.fail(&vm.name, failure);
+        assert!(
+            matches!(
+                err,
+                aiva_core::AivaError::PlatformError {
+                    recoverable: true,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        let message = err.to_string();
+        assert!(
+            message.contains("Creating VM rollback failed at configure drive"),
+            "{message}"
+        );
+        assert!(message.contains("injected failure"), "{message}");
+        assert!(!std::path::Path::new(&format!("/proc/{pid}")).exists());
+        assert!(!workspace.exists());
+
+        // Releasing again finds nothing left and still succeeds
+        let empty = CreateRollback {
+            workspace: Some(workspace),
+            ..Default::default()
+        };
+        let again = empty.fail(
+            &vm.name,
+            crate::linux::StepFailure {
+                step: CreateStep::ConfigureDrive,
+                error: aiva_core::AivaError::Other(anyhow::anyhow!("retry")),
+            },
+        );
+        assert!(!again.to_string().contains("left behind"), "{again}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_additional_drives_are_configured_before_boot() -> Result<()> {
+        use crate::firecracker::FirecrackerApiClient;
+        use crate::linux::{CreateRollback, CreateStep, configure_and_boot};
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+
+        // Stop right after the drives
+        let vmm = MockFirecracker::failing_on(Some("/balloon"));
+        let api_client = FirecrackerApiClient::new(vmm.socket.clone())?;
+        let mut vm = create_test_vm_instance("drives");
+        vm.config.storage.cache_strategy = aiva_core::CacheStrategy::Unsafe;
+        vm.config
+            .storage
+            .additional_drives
+            .push(aiva_core::BlockDevice {
+                path: "/var/lib/aiva/volumes/models.img".into(),
+                size_mb: 1024,
+                read_only: true,
+            });
+
+        let failure = configure_and_boot(&api_client, &vm, None, &mut CreateRollback::default())
+            .await
+            .unwrap_err();
+        assert_eq!(failure.step, CreateStep::ConfigureBalloon);
+        let requests = vmm.requests();
+        let (_, _, body) = requests
+            .iter()
+            .find(|(_, path, _)| path == "/drives/drive1")
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["path_on_host"], "/drive1.img");
+        assert_eq!(body["is_root_device"], false);
+        assert_eq!(body["is_read_only"], true);
+        assert_eq!(body["cache_type"], "Unsafe");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vsock_device_gets_the_allocated_cid() -> Result<()> {
+        use crate::firecracker::FirecrackerApiClient;
+        use crate::linux::{CreateRollback, CreateStep, configure_and_boot};
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+
+        let vmm = MockFirecracker::failing_on(Some("/vsock"));
+        let api_client = FirecrackerApiClient::new(vmm.socket.clone())?;
+        let mut vm = create_test_vm_instance("vsock-cid");
+        vm.runtime.vsock_cid = Some(7);
+
+        let failure = configure_and_boot(&api_client, &vm, None, &mut CreateRollback::default())
+            .await
+            .unwrap_err();
+        assert_eq!(failure.step, CreateStep::ConfigureVsock);
+        let requests = vmm.requests();
+        let (method, _, body) = requests
+            .iter()
+            .find(|(_, path, _)| path == "/vsock")
+            .unwrap();
+        assert_eq!(method, "PUT");
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["guest_cid"], 7);
+        assert_eq!(body["uds_path"], "/vsock.sock");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rootfs_uses_the_configured_cache_strategy() -> Result<()> {
+        use crate::firecracker::FirecrackerApiClient;
+        use crate::linux::{CreateRollback, configure_and_boot};
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+
+        for (strategy, cache_type) in [
+            (aiva_core::CacheStrategy::Writeback, "Writeback"),
+            (aiva_core::CacheStrategy::Unsafe, "Unsafe"),
+        ] {
+            let vmm = MockFirecracker::failing_on(Some("/balloon"));
+            let api_client = FirecrackerApiClient::new(vmm.socket.clone())?;
+            let mut vm = create_test_vm_instance("rootfs-cache");
+            vm.config.storage.cache_strategy = strategy;
+
+            configure_and_boot(&api_client, &vm, None, &mut CreateRollback::default())
+                .await
+                .unwrap_err();
+            let requests = vmm.requests();
+            let (_, _, body) = requests
+                .iter()
+                .find(|(_, path, _)| path == "/drives/rootfs")
+                .unwrap();
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(body["is_root_device"], true);
+            assert_eq!(body["cache_type"], cache_type, "{strategy}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_attaching_a_drive_to_a_running_vm_is_refused() -> Result<()> {
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+
+        let platform = LinuxPlatform::new()?;
+        let vmm = MockFirecracker::start();
+        let mut vm = create_test_vm_instance("attach");
+        vm.state = VMState::Running;
+        vm.runtime.api_socket = Some(vmm.socket.clone());
+        crate::firecracker::FirecrackerApiClient::new(vmm.socket.clone())?
+            .start_instance()
+            .await?;
+
+        let root = std::path::Path::new("/tmp/aiva-jailer")
+            .join(vm.id.to_string())
+            .join("root");
+        std::fs::create_dir_all(&root)?;
+        let volume = std::env::temp_dir().join(format!("aiva-volume-{}.img", Uuid::new_v4()));
+        std::fs::write(&volume, "")?;
+        let drive = aiva_core::BlockDevice {
+            path: volume.clone(),
+            size_mb: 1,
+            read_only: false,
+        };
+
+        let err = platform
+            .attach_drive(&vm, "drive1", &drive)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                aiva_core::AivaError::PlatformError {
+                    recoverable: true,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("stop the VM"), "{err}");
+        // The link made for the attempt is gone again
+        assert!(!root.join("drive1.img").exists());
+
+        let _ = std::fs::remove_dir_all(root.parent().unwrap());
+        let _ = std::fs::remove_file(volume);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_vm_requires_a_paused_vm() -> Result<()> {
+        use crate::tests::api_tunnel_tests::MockFirecracker;
+        use aiva_core::SnapshotType;
+
+        let platform = LinuxPlatform::new()?;
+        let vmm = MockFirecracker::start();
+        let mut vm = create_test_vm_instance("snapshot");
+        vm.runtime.api_socket = Some(vmm.socket.clone());
+        crate::firecracker::FirecrackerApiClient::new(vmm.socket.clone())?
+            .start_instance()
+            .await?;
+
+        let err = platform
+            .snapshot_vm(&vm, SnapshotType::Full)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("pause it first"), "{err}");
+
+        platform.pause_vm(&vm).await?;
+        let snapshot = platform.snapshot_vm(&vm, SnapshotType::Full).await?;
+        let root = std::path::Path::new("/tmp/aiva-jailer")
+            .join(vm.id.to_string())
+            .join("root");
+        assert_eq!(snapshot.state, root.join("snapshot"));
+        assert_eq!(snapshot.memory.parent(), Some(root.as_path()));
+
+        // The VMM writes inside its jail
+        let requests = vmm.requests();
+        let (_, _, body) = requests
+            .iter()
+            .find(|(_, path, _)| path == "/snapshot/create")
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["snapshot_type"], "Full");
+        assert_eq!(body["snapshot_path"], "/snapshot");
+        Ok(())
+    }
+
     #[test]
     fn test_vsock_support_check() {
         let platform = LinuxPlatform::new().unwrap();
@@ -0,0 +1,77 @@
+use crate::setup_sources::DownloadSources;
+use crate::{macos, windows};
+use aiva_core::{ArtifactSource, Config, FirecrackerSource};
+
+fn mirrored_config() -> Config {
+    Config {
+        firecracker: FirecrackerSource {
+            version: "1.10.0".to_string(),
+            download_base: "https://mirror.internal/firecracker/".to_string(),
+            sha256: Some("aaaa".to_string()),
+        },
+        kernel: ArtifactSource {
+            url: "https://mirror.internal/{arch}/vmlinux".to_string(),
+            sha256: Some("bbbb".to_string()),
+        },
+        rootfs: ArtifactSource {
+            url: "https://mirror.internal/{arch}/rootfs.ext4".to_string(),
+            sha256: None,
+        },
+        ..Config::default()
+    }
+}
+
+#[test]
+fn test_setup_scripts_use_configured_sources() {
+    let sources = DownloadSources::from_config(&mirrored_config());
+
+    for script in [
+        macos::render_setup_script(&sources).unwrap(),
+        windows::render_setup_script(&sources).unwrap(),
+    ] {
+        assert!(script.contains(
+            "https://mirror.internal/firecracker/v1.10.0/firecracker-v1.10.0-${FC_ARCH}.tgz"
+        ));
+        assert!(script.contains("release-v1.10.0-${FC_ARCH}/firecracker-v1.10.0-${FC_ARCH}"));
+        assert!(script.contains("\"https://mirror.internal/${FC_ARCH}/vmlinux\""));
+        assert!(script.contains("\"https://mirror.internal/${FC_ARCH}/rootfs.ext4\""));
+        assert!(script.contains("verify_sha256 \"$FC_TGZ\" \"aaaa\""));
+        assert!(script.contains("verify_sha256 \"$1\" \"bbbb\""));
+        assert!(!script.contains("v1.12.1"));
+        assert!(!script.contains("s3.amazonaws.com"));
+    }
+}
+
+#[test]
+fn test_default_sources_match_previous_downloads() {
+    let sources = DownloadSources::default();
+    let script = windows::render_setup_script(&sources).unwrap();
+
+    assert!(script.contains(
+        "https://github.com/firecracker-microvm/firecracker/releases/download/v1.12.1/firecracker-v1.12.1-${FC_ARCH}.tgz"
+    ));
+    assert!(script.contains(
+        "https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/${FC_ARCH}/kernels/vmlinux.bin"
+    ));
+    // Unpinned releases are checked against the published checksum file
+    assert!(script.contains("$FC_TGZ.sha256.txt"));
+    assert!(!script.contains("verify_sha256 \"$1\""));
+}
+
+#[test]
+fn test_skipped_downloads_require_staged_artifacts() {
+    let sources = DownloadSources::from_config(&Config {
+        skip_downloads: true,
+        ..mirrored_config()
+    });
+
+    for script in [
+        macos::render_setup_script(&sources).unwrap(),
+        windows::render_setup_script(&sources).unwrap(),
+    ] {
+        assert!(!script.contains("wget"), "{script}");
+        assert!(!script.contains("mirror.internal"));
+        assert!(script.contains("missing_artifact \"Firecracker v1.10.0\""));
+        assert!(script.contains("downloads are disabled"));
+    }
+}
@@ -1,11 +1,15 @@
 use crate::vsock_executor::{ConnectionType, VSOCK_COMMAND_PORT, VsockExecutor};
+use std::path::PathBuf;
 
 #[tokio::test]
 async fn test_vsock_executor_creation() {
     let vm_name = "test-vm".to_string();
 
     // Test vsock connection type
-    let vsock_conn = ConnectionType::Vsock { cid: 3 };
+    let vsock_conn = ConnectionType::Vsock {
+        cid: 3,
+        uds_path: PathBuf::from("/srv/jailer/test-vm/root/vsock.sock"),
+    };
     let _vsock_executor = VsockExecutor::new(vm_name.clone(), vsock_conn);
 
     // Test network connection type
@@ -68,20 +72,74 @@ async fn test_connection_check_failure() {
     assert!(!result.unwrap());
 }
 
-#[cfg(target_os = "linux")]
+/// Host side of a Firecracker vsock device at a fresh socket path: accepts
+/// `CONNECT <port>` for `port` only and answers commands with `ran: <command>`
+#[cfg(unix)]
+fn fake_vsock_device(port: u32) -> PathBuf {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let uds_path = std::env::temp_dir().join(format!("aiva-vsock-{}.sock", uuid::Uuid::new_v4()));
+    let listener = tokio::net::UnixListener::bind(&uds_path).unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut line = String::new();
+                stream.read_line(&mut line).await.unwrap();
+                if line != format!("CONNECT {port}\n") {
+                    return;
+                }
+                stream
+                    .get_mut()
+                    .write_all(b"OK 1073741824\n")
+                    .await
+                    .unwrap();
+
+                line.clear();
+                stream.read_line(&mut line).await.unwrap();
+                let reply = format!("ran: {}", line.trim_end());
+                stream.get_mut().write_all(reply.as_bytes()).await.unwrap();
+            });
+        }
+    });
+    uds_path
+}
+
+#[cfg(unix)]
 #[tokio::test]
-async fn test_vsock_on_linux() {
-    // This test only runs on Linux
-    let vm_name = "linux-vm".to_string();
-    let conn = ConnectionType::Vsock { cid: 3 };
+async fn test_vsock_commands_go_through_the_device_socket() {
+    let uds_path = fake_vsock_device(VSOCK_COMMAND_PORT);
+    let executor = VsockExecutor::new(
+        "vsock-vm".to_string(),
+        ConnectionType::Vsock {
+            cid: 3,
+            uds_path: uds_path.clone(),
+        },
+    );
 
-    let executor = VsockExecutor::new(vm_name, conn);
+    assert_eq!(
+        executor.execute_command("uptime").await.unwrap(),
+        "ran: uptime"
+    );
+    assert!(executor.check_connection().await.unwrap());
+    let _ = std::fs::remove_file(uds_path);
+}
 
-    // Try to execute a command (will fail without actual vsock setup)
-    let result = executor.execute_command("echo test").await;
+#[cfg(unix)]
+#[tokio::test]
+async fn test_vsock_handshake_refused_by_the_guest_fails() {
+    // Nothing listens on the command port in the guest
+    let uds_path = fake_vsock_device(VSOCK_COMMAND_PORT + 1);
+    let executor = VsockExecutor::new(
+        "vsock-vm".to_string(),
+        ConnectionType::Vsock {
+            cid: 3,
+            uds_path: uds_path.clone(),
+        },
+    );
 
-    // We expect this to fail in test environment
-    assert!(result.is_err());
+    assert!(executor.execute_command("uptime").await.is_err());
+    let _ = std::fs::remove_file(uds_path);
 }
 
 #[tokio::test]
@@ -110,3 +168,50 @@ async fn test_ssh_connection_type() {
     assert!(matches!(executor_with_key, VsockExecutor { .. }));
     assert!(matches!(executor_without_key, VsockExecutor { .. }));
 }
+
+#[test]
+fn test_guest_agent_prefers_the_vm_vsock_device() {
+    let now = chrono::Utc::now();
+    let mut vm = aiva_core::VMInstance {
+        id: uuid::Uuid::new_v4(),
+        name: "logs".to_string(),
+        state: aiva_core::VMState::Running,
+        config: aiva_core::VMConfig::default(),
+        runtime: aiva_core::RuntimeInfo {
+            pid: None,
+            api_socket: None,
+            vsock_cid: Some(9),
+            tap_device: None,
+            mcp_pids: None,
+            paused: None,
+        },
+        created_at: now,
+        updated_at: now,
+        schema_version: aiva_core::SCHEMA_VERSION,
+    };
+    let jailer = aiva_core::JailerConfig {
+        chroot_base_dir: PathBuf::from("/srv/jailer"),
+        ..Default::default()
+    };
+
+    match ConnectionType::guest_agent(&vm, &jailer) {
+        ConnectionType::Vsock { cid, uds_path } => {
+            assert_eq!(cid, 9);
+            assert_eq!(
+                uds_path,
+                PathBuf::from(format!("/srv/jailer/{}/root/vsock.sock", vm.id))
+            );
+        }
+        other => panic!("expected vsock, got {other:?}"),
+    }
+
+    // Created before CIDs were assigned, so it has no vsock device
+    vm.runtime.vsock_cid = None;
+    match ConnectionType::guest_agent(&vm, &jailer) {
+        ConnectionType::Network { host, port } => {
+            assert_eq!(host, vm.config.network.guest_ip);
+            assert_eq!(u32::from(port), VSOCK_COMMAND_PORT);
+        }
+        other => panic!("expected network, got {other:?}"),
+    }
+}
@@ -0,0 +1,155 @@
+use crate::windows::{WslExecPolicy, exec_with_retry, parse_vm_metrics};
+use aiva_core::AivaError;
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+fn fast_policy(max_retries: u32) -> WslExecPolicy {
+    WslExecPolicy {
+        timeout: Duration::from_millis(200),
+        max_retries,
+        base_backoff: Duration::from_millis(10),
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_exec_timeout_is_reported_without_a_retry() {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = attempts.clone();
+
+    let started = Instant::now();
+    let result = exec_with_retry(&fast_policy(2), "sleep 30", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut cmd = Command::new("sleep");
+        cmd.arg("30");
+        cmd
+    })
+    .await;
+
+    // The command may have done part of its work, so it runs only once and
+    // is killed at the timeout instead of running to completion
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    match result {
+        Err(AivaError::PlatformError {
+            message,
+            recoverable,
+            ..
+        }) => {
+            assert!(
+                message.contains("timed out"),
+                "unexpected message: {message}"
+            );
+            assert!(recoverable);
+        }
+        other => panic!("expected timeout error, got {other:?}"),
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_exec_command_failure_is_not_retried() {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = attempts.clone();
+
+    let result = exec_with_retry(&fast_policy(2), "false", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo boom >&2; exit 1"]);
+        cmd
+    })
+    .await;
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert!(matches!(
+        result,
+        Err(AivaError::PlatformError {
+            recoverable: false,
+            ..
+        })
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_exec_wsl_startup_failure_is_retried() {
+    let attempts = Arc::new(AtomicU32::new(0));
+    let counter = attempts.clone();
+
+    let result = exec_with_retry(&fast_policy(2), "true", move || {
+        counter.fetch_add(1, Ordering::SeqCst);
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", "echo 'Wsl/Service/E_UNEXPECTED' >&2; exit 1"]);
+        cmd
+    })
+    .await;
+
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert!(matches!(
+        result,
+        Err(AivaError::PlatformError {
+            recoverable: true,
+            ..
+        })
+    ));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_exec_success_returns_stdout() {
+    let output = exec_with_retry(&fast_policy(0), "echo", || {
+        let mut cmd = Command::new("echo");
+        cmd.arg("hello");
+        cmd
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(output.trim(), "hello");
+}
+
+#[test]
+fn test_portproxy_listens_on_mapping_address() {
+    use crate::windows::portproxy_args;
+    use aiva_core::{PortMapping, Protocol};
+
+    let mapping = PortMapping::new(3000, 8080, Protocol::Tcp);
+    assert_eq!(
+        portproxy_args(true, &mapping, "172.16.0.2"),
+        [
+            "interface",
+            "portproxy",
+            "add",
+            "v4tov4",
+            "listenaddress=127.0.0.1",
+            "listenport=3000",
+            "connectaddress=172.16.0.2",
+            "connectport=8080",
+        ]
+    );
+
+    let mut mapping = mapping;
+    mapping.host_ip = None;
+    assert_eq!(
+        portproxy_args(false, &mapping, "172.16.0.2")[4..],
+        ["listenaddress=0.0.0.0", "listenport=3000"]
+    );
+}
+
+#[test]
+fn test_metrics_with_more_memory_used_than_total_do_not_underflow() {
+    let metrics = parse_vm_metrics(
+        "{\n  \"cpu_usage\": 12.5,\n  \"memory_used_kb\": 4194304,\n  \"memory_total_kb\": 2097152,\n  \"rx_bytes\": 10,\n  \"tx_bytes\": 20\n}",
+    );
+    assert_eq!(metrics.cpu_usage, 12.5);
+    assert_eq!(metrics.memory_usage.total_mb, 2048);
+    assert_eq!(metrics.memory_usage.used_mb, 4096);
+    assert_eq!(metrics.memory_usage.available_mb, 0);
+    assert_eq!(
+        (metrics.network_io.rx_bytes, metrics.network_io.tx_bytes),
+        (10, 20)
+    );
+}
@@ -1,10 +1,19 @@
-use aiva_core::{AivaError, Result};
+use aiva_core::{AivaError, JailerConfig, Result, VMInstance};
+#[cfg(unix)]
+use std::path::Path;
+use std::path::PathBuf;
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
 use tracing::debug;
 
 /// Vsock port used for command execution
 pub const VSOCK_COMMAND_PORT: u32 = 5555;
 
+/// Byte stream to the guest agent
+trait AgentStream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> AgentStream for T {}
+
 /// Command execution through vsock or network connection
 pub struct VsockExecutor {
     _vm_name: String,
@@ -13,8 +22,9 @@ pub struct VsockExecutor {
 
 #[derive(Debug, Clone)]
 pub enum ConnectionType {
-    /// Direct vsock connection (Linux with vsock support)
-    Vsock { cid: u32 },
+    /// Firecracker vsock device of the guest with `cid`, reached through
+    /// the Unix socket its host side listens on
+    Vsock { cid: u32, uds_path: PathBuf },
     /// Network connection through port forwarding
     Network { host: String, port: u16 },
     /// SSH connection for fallback
@@ -25,6 +35,33 @@ pub enum ConnectionType {
     },
 }
 
+impl ConnectionType {
+    /// Command channel of the guest agent of `vm`: its vsock device, whose
+    /// host socket is in the jailer workspace, or the network for VMs
+    /// created before vsock CIDs were assigned
+    pub fn guest_agent(vm: &VMInstance, jailer: &JailerConfig) -> Self {
+        match vm.runtime.vsock_cid {
+            Some(cid) => Self::Vsock {
+                cid,
+                uds_path: vsock_uds_path(jailer, vm),
+            },
+            None => Self::Network {
+                host: vm.config.network.guest_ip.clone(),
+                port: VSOCK_COMMAND_PORT as u16,
+            },
+        }
+    }
+}
+
+/// Unix socket on the host side of the vsock device of `vm`
+pub(crate) fn vsock_uds_path(jailer: &JailerConfig, vm: &VMInstance) -> PathBuf {
+    jailer
+        .chroot_base_dir
+        .join(vm.id.to_string())
+        .join("root")
+        .join(crate::linux::VSOCK_UDS_PATH.trim_start_matches('/'))
+}
+
 impl VsockExecutor {
     pub fn new(vm_name: String, connection_type: ConnectionType) -> Self {
         Self {
@@ -36,9 +73,18 @@ impl VsockExecutor {
     /// Execute a command in the VM and return the output
     pub async fn execute_command(&self, command: &str) -> Result<String> {
         match &self.connection_type {
-            ConnectionType::Vsock { cid } => self.execute_vsock(*cid, command).await,
-            ConnectionType::Network { host, port } => {
-                self.execute_network(host, *port, command).await
+            ConnectionType::Vsock { .. } | ConnectionType::Network { .. } => {
+                let mut stream = self.connect().await?;
+                send_command(&mut stream, command).await?;
+
+                let mut response = String::new();
+                stream.read_to_string(&mut response).await.map_err(|e| {
+                    AivaError::NetworkError {
+                        operation: "read_response".to_string(),
+                        cause: format!("Failed to read response: {e}"),
+                    }
+                })?;
+                Ok(response)
             }
             ConnectionType::Ssh {
                 host,
@@ -51,80 +97,63 @@ impl VsockExecutor {
         }
     }
 
-    /// Execute command through vsock (Linux only)
-    async fn execute_vsock(&self, _cid: u32, _command: &str) -> Result<String> {
-        #[cfg(target_os = "linux")]
-        {
-            debug!("Executing command via vsock CID {}: {}", _cid, _command);
-
-            // For now, we'll use a network connection as vsock support requires special setup
-            // In production, this would use actual vsock sockets
-            return self
-                .execute_network("localhost", VSOCK_COMMAND_PORT as u16, _command)
-                .await;
-        }
-
-        #[cfg(not(target_os = "linux"))]
-        {
-            Err(AivaError::PlatformError {
+    /// Open a stream to the guest agent
+    async fn connect(&self) -> Result<Box<dyn AgentStream>> {
+        match &self.connection_type {
+            #[cfg(unix)]
+            ConnectionType::Vsock { cid, uds_path } => {
+                debug!(
+                    "Connecting to vsock CID {} through {}",
+                    cid,
+                    uds_path.display()
+                );
+                Ok(Box::new(connect_vsock(uds_path, VSOCK_COMMAND_PORT).await?))
+            }
+            #[cfg(not(unix))]
+            ConnectionType::Vsock { .. } => Err(AivaError::PlatformError {
                 platform: "vsock".to_string(),
                 message: "Vsock is only supported on Linux".to_string(),
                 recoverable: false,
-            })
+            }),
+            ConnectionType::Network { host, port } => {
+                let addr = format!("{host}:{port}");
+                debug!("Connecting to {}", addr);
+                let stream =
+                    with_connect_timeout(&addr, tokio::net::TcpStream::connect(&addr)).await?;
+                Ok(Box::new(stream))
+            }
+            ConnectionType::Ssh { .. } => Err(AivaError::NotImplemented(
+                "Streaming over SSH connections".to_string(),
+            )),
         }
     }
 
-    /// Execute command through network connection
-    async fn execute_network(&self, host: &str, port: u16, command: &str) -> Result<String> {
-        use tokio::io::{AsyncReadExt, AsyncWriteExt};
-        use tokio::net::TcpStream;
-
-        debug!(
-            "Executing command via network {}:{}: {}",
-            host, port, command
-        );
-
-        // Try to connect with timeout
-        let addr = format!("{host}:{port}");
-        let stream = tokio::time::timeout(Duration::from_secs(5), TcpStream::connect(&addr))
-            .await
-            .map_err(|_| AivaError::NetworkError {
-                operation: "connect".to_string(),
-                cause: format!("Connection to {addr} timed out"),
-            })?
-            .map_err(|e| AivaError::NetworkError {
-                operation: "connect".to_string(),
-                cause: format!("Failed to connect to {addr}: {e}"),
-            })?;
-
-        // Send command
-        let mut stream = stream;
-        stream
-            .write_all(command.as_bytes())
-            .await
-            .map_err(|e| AivaError::NetworkError {
-                operation: "send_command".to_string(),
-                cause: format!("Failed to send command: {e}"),
-            })?;
-        stream
-            .write_all(b"\n")
-            .await
-            .map_err(|e| AivaError::NetworkError {
-                operation: "send_newline".to_string(),
-                cause: format!("Failed to send newline: {e}"),
-            })?;
+    /// Run a long-lived command through the agent and forward each output line
+    /// until the guest closes the stream or the receiver is dropped
+    pub async fn stream_command(
+        &self,
+        command: &str,
+        lines: tokio::sync::mpsc::Sender<String>,
+    ) -> Result<()> {
+        debug!("Streaming command: {}", command);
+        let mut stream = self.connect().await?;
+        send_command(&mut stream, command).await?;
 
-        // Read response
-        let mut response = String::new();
-        stream
-            .read_to_string(&mut response)
+        let mut reader = BufReader::new(stream).lines();
+        while let Some(line) = reader
+            .next_line()
             .await
             .map_err(|e| AivaError::NetworkError {
-                operation: "read_response".to_string(),
-                cause: format!("Failed to read response: {e}"),
-            })?;
+                operation: "read_stream".to_string(),
+                cause: format!("Failed to read stream: {e}"),
+            })?
+        {
+            if lines.send(line).await.is_err() {
+                break;
+            }
+        }
 
-        Ok(response)
+        Ok(())
     }
 
     /// Execute command through SSH
@@ -183,3 +212,75 @@ impl VsockExecutor {
         }
     }
 }
+
+/// Connect to the guest port `port` through the Unix socket of a Firecracker
+/// vsock device, answering its `CONNECT <port>` handshake
+#[cfg(unix)]
+pub(crate) async fn connect_vsock(
+    uds_path: &Path,
+    port: u32,
+) -> Result<BufReader<tokio::net::UnixStream>> {
+    let target = uds_path.display().to_string();
+    let stream = with_connect_timeout(&target, tokio::net::UnixStream::connect(uds_path)).await?;
+    let mut stream = BufReader::new(stream);
+
+    let handshake = async {
+        stream
+            .get_mut()
+            .write_all(format!("CONNECT {port}\n").as_bytes())
+            .await?;
+        let mut reply = String::new();
+        stream.read_line(&mut reply).await?;
+        Ok::<_, std::io::Error>(reply)
+    };
+    let reply = tokio::time::timeout(CONNECT_TIMEOUT, handshake)
+        .await
+        .map_err(|_| AivaError::NetworkError {
+            operation: "connect".to_string(),
+            cause: format!("Vsock handshake on {target} timed out"),
+        })?
+        .map_err(|e| AivaError::NetworkError {
+            operation: "connect".to_string(),
+            cause: format!("Vsock handshake on {target} failed: {e}"),
+        })?;
+
+    // Firecracker answers `OK <host port>` once the guest accepted
+    if !reply.starts_with("OK ") {
+        return Err(AivaError::NetworkError {
+            operation: "connect".to_string(),
+            cause: format!(
+                "Guest refused vsock port {port} on {target}: {}",
+                reply.trim_end()
+            ),
+        });
+    }
+    Ok(stream)
+}
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn with_connect_timeout<S>(
+    target: &str,
+    connect: impl std::future::Future<Output = std::io::Result<S>>,
+) -> Result<S> {
+    tokio::time::timeout(CONNECT_TIMEOUT, connect)
+        .await
+        .map_err(|_| AivaError::NetworkError {
+            operation: "connect".to_string(),
+            cause: format!("Connection to {target} timed out"),
+        })?
+        .map_err(|e| AivaError::NetworkError {
+            operation: "connect".to_string(),
+            cause: format!("Failed to connect to {target}: {e}"),
+        })
+}
+
+async fn send_command(stream: &mut (impl AsyncWrite + Unpin), command: &str) -> Result<()> {
+    stream
+        .write_all(format!("{command}\n").as_bytes())
+        .await
+        .map_err(|e| AivaError::NetworkError {
+            operation: "send_command".to_string(),
+            cause: format!("Failed to send command: {e}"),
+        })
+}
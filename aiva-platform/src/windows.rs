@@ -1,17 +1,30 @@
-use aiva_core::{AivaError, Platform, Result, VMInstance, VMLogger, VMMetrics, VMState};
+use aiva_core::{
+    AivaError, ExecTransport, Platform, PortMapping, Result, ServerPidFile, ServerTeardown,
+    VMInstance, VMLogger, VMMetrics, VMState, WindowsConfig,
+};
 use askama::Template;
 use async_trait::async_trait;
+use std::io::Read;
 use std::path::PathBuf;
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
-use crate::command_pool::{ConnectionType, get_command_pool};
-use crate::vsock_executor::VSOCK_COMMAND_PORT;
+use crate::api_tunnel::ApiTunnel;
+use crate::arch::check_host_machine_config;
+use crate::command_pool::{ExecutorRegistry, connection_for, get_command_pool};
+use crate::firecracker_vm::FirecrackerVMConfig;
+use crate::setup_sources::DownloadSources;
+
+/// Command transports in the order tried when a VM does not configure one
+const WINDOWS_TRANSPORTS: &[ExecTransport] = &[ExecTransport::Network, ExecTransport::Wsl];
 
 // Askama templates for bash scripts
 #[derive(Template)]
 #[template(path = "windows_setup_firecracker.sh", escape = "none")]
-struct SetupFirecrackerTemplate;
+struct SetupFirecrackerTemplate<'a> {
+    sources: &'a DownloadSources,
+}
 
 #[derive(Template)]
 #[template(path = "windows_create_vm.sh", escape = "none")]
@@ -46,17 +59,261 @@ struct GetMetricsTemplate {
     vm_name: String,
 }
 
+/// Render the script that installs Firecracker, the kernel and the base
+/// rootfs inside the WSL distribution
+pub(crate) fn render_setup_script(sources: &DownloadSources) -> Result<String> {
+    SetupFirecrackerTemplate { sources }
+        .render()
+        .map_err(|e| AivaError::PlatformError {
+            platform: String::from("windows"),
+            message: format!("Failed to render setup script template: {e}"),
+            recoverable: false,
+        })
+}
+
+/// `netsh interface portproxy` arguments forwarding a mapping into the guest.
+/// The listener is bound to the mapping's host address, so the default
+/// 127.0.0.1 keeps the port off the LAN.
+pub(crate) fn portproxy_args(add: bool, mapping: &PortMapping, guest_ip: &str) -> Vec<String> {
+    let kind = if mapping.bind_address().is_ipv6() {
+        "v6tov4"
+    } else {
+        "v4tov4"
+    };
+
+    let mut args = vec![
+        "interface".to_string(),
+        "portproxy".to_string(),
+        if add { "add" } else { "delete" }.to_string(),
+        kind.to_string(),
+        format!("listenaddress={}", mapping.bind_address()),
+        format!("listenport={}", mapping.host_port),
+    ];
+    if add {
+        args.push(format!("connectaddress={guest_ip}"));
+        args.push(format!("connectport={}", mapping.guest_port));
+    }
+    args
+}
+
+/// Timeout and retry behaviour for commands executed through `wsl`
+#[derive(Debug, Clone)]
+pub struct WslExecPolicy {
+    pub timeout: Duration,
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+}
+
+impl Default for WslExecPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            max_retries: 2,
+            base_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl From<&WindowsConfig> for WslExecPolicy {
+    fn from(config: &WindowsConfig) -> Self {
+        Self {
+            timeout: Duration::from_secs(config.exec_timeout_secs),
+            max_retries: config.exec_retries,
+            ..Self::default()
+        }
+    }
+}
+
+/// Stderr fragments reported by WSL itself (not the command) when the
+/// service is still starting or briefly unavailable
+const TRANSIENT_WSL_ERRORS: &[&str] = &[
+    "Wsl/Service",
+    "remote procedure call failed",
+    "Catastrophic failure",
+    "The operation timed out",
+    "E_UNEXPECTED",
+];
+
+enum AttemptError {
+    TimedOut,
+    Transient(String),
+    Fatal(String),
+}
+
+/// Run a command built by `build`, retrying failures of WSL itself to start
+/// it with exponential backoff. Each attempt runs on the blocking pool and
+/// is killed when it exceeds the policy timeout; a command that timed out may
+/// already have had side effects, so it is not run again.
+pub(crate) async fn exec_with_retry<F>(
+    policy: &WslExecPolicy,
+    description: &str,
+    build: F,
+) -> Result<String>
+where
+    F: Fn() -> Command + Send + Sync + 'static,
+{
+    let build = std::sync::Arc::new(build);
+    let attempts = policy.max_retries + 1;
+    let mut last_error = AttemptError::Fatal(String::new());
+
+    for attempt in 1..=attempts {
+        let build = build.clone();
+        let timeout = policy.timeout;
+        let result = tokio::task::spawn_blocking(move || run_with_deadline(build(), timeout))
+            .await
+            .map_err(|e| AivaError::PlatformError {
+                platform: String::from("windows"),
+                message: format!("Failed to spawn WSL command: {e}"),
+                recoverable: false,
+            })?;
+
+        last_error = match result {
+            Ok(Some(output)) if output.status.success() => {
+                return Ok(String::from_utf8_lossy(&output.stdout).to_string());
+            }
+            Ok(Some(output)) => {
+                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+                if TRANSIENT_WSL_ERRORS.iter().any(|e| stderr.contains(e)) {
+                    AttemptError::Transient(stderr)
+                } else {
+                    AttemptError::Fatal(stderr)
+                }
+            }
+            Ok(None) => AttemptError::TimedOut,
+            Err(e) => AttemptError::Transient(e.to_string()),
+        };
+
+        if !matches!(last_error, AttemptError::Transient(_)) || attempt == attempts {
+            break;
+        }
+
+        let backoff = policy.base_backoff * 2u32.saturating_pow(attempt - 1);
+        warn!(
+            "WSL command '{}' failed (attempt {}/{}), retrying in {:?}",
+            description, attempt, attempts, backoff
+        );
+        tokio::time::sleep(backoff).await;
+    }
+
+    Err(match last_error {
+        AttemptError::TimedOut => AivaError::PlatformError {
+            platform: String::from("windows"),
+            message: format!(
+                "WSL command timed out after {}s: {description}",
+                policy.timeout.as_secs_f64()
+            ),
+            recoverable: true,
+        },
+        AttemptError::Transient(stderr) => AivaError::PlatformError {
+            platform: String::from("windows"),
+            message: format!("WSL unavailable after {attempts} attempt(s): {stderr}"),
+            recoverable: true,
+        },
+        AttemptError::Fatal(stderr) => AivaError::PlatformError {
+            platform: String::from("windows"),
+            message: format!("Command failed in WSL: {stderr}"),
+            recoverable: false,
+        },
+    })
+}
+
+/// Wait for a command to exit, killing it once `timeout` has elapsed.
+/// Returns `Ok(None)` on timeout.
+fn run_with_deadline(mut command: Command, timeout: Duration) -> std::io::Result<Option<Output>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // Drain the pipes on their own threads so a chatty command cannot block
+    // on a full pipe while we poll for exit
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let stdout_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(out) = stdout.as_mut() {
+            let _ = out.read_to_end(&mut buf);
+        }
+        buf
+    });
+    let stderr_reader = std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(err) = stderr.as_mut() {
+            let _ = err.read_to_end(&mut buf);
+        }
+        buf
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+
+    Ok(Some(Output {
+        status,
+        stdout: stdout_reader.join().unwrap_or_default(),
+        stderr: stderr_reader.join().unwrap_or_default(),
+    }))
+}
+
 pub struct WindowsPlatform {
     wsl_distro: String,
+    exec_policy: WslExecPolicy,
 }
 
 impl WindowsPlatform {
     pub fn new() -> Result<Self> {
         Ok(Self {
             wsl_distro: String::from("Ubuntu"), // Default to Ubuntu
+            exec_policy: WslExecPolicy::default(),
         })
     }
 
+    pub fn with_exec_policy(mut self, exec_policy: WslExecPolicy) -> Self {
+        self.exec_policy = exec_policy;
+        self
+    }
+
+    /// Install or remove the host port forwards for a VM. Failures are logged
+    /// rather than returned since portproxy changes need an elevated shell.
+    fn apply_port_forwards(&self, instance: &VMInstance, add: bool) {
+        for mapping in &instance.config.network.port_mappings {
+            if matches!(mapping.protocol, aiva_core::Protocol::Udp) {
+                warn!(
+                    "Skipping UDP port {}: portproxy only forwards TCP",
+                    mapping.host_port
+                );
+                continue;
+            }
+
+            let args = portproxy_args(add, mapping, &instance.config.network.guest_ip);
+            match Command::new("netsh").args(&args).output() {
+                Ok(output) if output.status.success() => debug!(
+                    "Port forward {}:{} {}",
+                    mapping.bind_address(),
+                    mapping.host_port,
+                    if add { "added" } else { "removed" }
+                ),
+                Ok(output) => warn!(
+                    "netsh portproxy failed for {}:{}: {}",
+                    mapping.bind_address(),
+                    mapping.host_port,
+                    String::from_utf8_lossy(&output.stdout).trim()
+                ),
+                Err(e) => warn!("Failed to run netsh: {}", e),
+            }
+        }
+    }
+
     fn check_nested_virtualization(&self) -> Result<()> {
         // Check if running on Windows 11
         let output = Command::new("cmd")
@@ -136,12 +393,8 @@ impl WindowsPlatform {
     async fn setup_firecracker_in_wsl(&self, distro: &str) -> Result<()> {
         info!("Setting up Firecracker in WSL distribution: {}", distro);
 
-        let template = SetupFirecrackerTemplate;
-        let script = template.render().map_err(|e| AivaError::PlatformError {
-            platform: String::from("windows"),
-            message: format!("Failed to render setup script template: {e}"),
-            recoverable: false,
-        })?;
+        let sources = DownloadSources::from_config(&aiva_core::Config::load().unwrap_or_default());
+        let script = render_setup_script(&sources)?;
 
         let result = self.exec_in_wsl(distro, &script).await?;
         debug!("Firecracker setup result: {}", result);
@@ -150,45 +403,59 @@ impl WindowsPlatform {
     }
 
     async fn exec_in_wsl(&self, distro: &str, command: &str) -> Result<String> {
-        let output = Command::new("wsl")
-            .args(["-d", distro, "bash", "-c", command])
-            .output()
-            .map_err(|e| AivaError::PlatformError {
-                platform: String::from("windows"),
-                message: format!("Failed to execute in WSL: {e}"),
-                recoverable: false,
-            })?;
+        let distro = distro.to_owned();
+        let command_owned = command.to_owned();
+        let description = command.lines().next().unwrap_or_default().to_owned();
+
+        exec_with_retry(&self.exec_policy, &description, move || {
+            let mut cmd = Command::new("wsl");
+            cmd.args(["-d", &distro, "bash", "-c", &command_owned]);
+            cmd
+        })
+        .await
+    }
 
-        if !output.status.success() {
-            return Err(AivaError::PlatformError {
-                platform: String::from("windows"),
-                message: format!(
-                    "Command failed in WSL: {}",
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-                recoverable: false,
-            });
+    /// Where the create script puts the VM inside the distro
+    fn firecracker_vm_config(&self, instance: &VMInstance) -> FirecrackerVMConfig {
+        FirecrackerVMConfig {
+            vm_id: instance.name.clone(),
+            socket_path: PathBuf::from(format!(
+                "/var/lib/firecracker/{}/firecracker.sock",
+                instance.name
+            )),
+            kernel_path: PathBuf::from("/opt/aiva/firecracker/vmlinux"),
+            rootfs_path: PathBuf::from(format!(
+                "/var/lib/firecracker/{}.rootfs.ext4",
+                instance.name
+            )),
+            vcpu_count: instance.config.cpus,
+            mem_size_mib: instance.config.memory_mb,
+            tap_device: format!("tap-{}", instance.name),
+            guest_ip: instance.config.network.guest_ip.clone(),
+            network_interface: "eth0".to_string(),
+            custom_boot_args: instance.config.boot_args.clone(),
+            extra_boot_args: instance.config.extra_boot_args.clone(),
+            cpu_template: instance.config.cpu_template.clone(),
+            cache_strategy: instance.config.storage.cache_strategy,
         }
-
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
     async fn create_firecracker_config(&self, instance: &VMInstance) -> Result<String> {
+        let vm_config = self.firecracker_vm_config(instance);
+
         // Create Firecracker configuration for WSL
         let config = serde_json::json!({
-            "vm_id": instance.name,
-            "vcpu_count": instance.config.cpus,
-            "mem_size_mib": instance.config.memory_mb,
-            "kernel_path": "/opt/aiva/firecracker/vmlinux",
-            "rootfs_path": format!("/var/lib/firecracker/{}.rootfs.ext4", instance.name),
-            "kernel_args": format!(
-                "console=ttyS0 reboot=k panic=1 pci=off init=/sbin/init ip={}::172.16.0.1:255.255.255.0::eth0:off",
-                instance.config.network.guest_ip
-            ),
+            "vm_id": vm_config.vm_id,
+            "vcpu_count": vm_config.vcpu_count,
+            "mem_size_mib": vm_config.mem_size_mib,
+            "kernel_path": vm_config.kernel_path,
+            "rootfs_path": vm_config.rootfs_path,
+            "kernel_args": vm_config.boot_args(),
+            "cpu_template": vm_config.cpu_template,
             "network": {
-                "iface_id": "eth0",
-                "guest_ip": instance.config.network.guest_ip,
-                "tap_device": format!("tap-{}", instance.name)
+                "iface_id": vm_config.network_interface,
+                "guest_ip": vm_config.guest_ip,
+                "tap_device": vm_config.tap_device
             }
         });
 
@@ -200,6 +467,11 @@ impl WindowsPlatform {
 impl Platform for WindowsPlatform {
     async fn create_vm(&self, instance: &VMInstance) -> Result<VMInstance> {
         self.check_nested_virtualization()?;
+        check_host_machine_config(
+            instance.config.cpu_template.as_deref(),
+            false,
+            instance.config.cpus,
+        )?;
         let distro = self.ensure_wsl_distro().await?;
 
         let logger = VMLogger::new(instance.name.clone());
@@ -231,11 +503,9 @@ impl Platform for WindowsPlatform {
         let mut updated_instance = instance.clone();
         updated_instance.state = VMState::Stopped;
         updated_instance.runtime.pid = None;
-        updated_instance.runtime.api_socket = Some(PathBuf::from(format!(
-            "/var/lib/firecracker/{}/firecracker.sock",
-            instance.name
-        )));
-        updated_instance.runtime.tap_device = Some(format!("tap-{}", instance.name));
+        let vm_config = self.firecracker_vm_config(instance);
+        updated_instance.runtime.api_socket = Some(vm_config.socket_path);
+        updated_instance.runtime.tap_device = Some(vm_config.tap_device);
 
         Ok(updated_instance)
     }
@@ -260,6 +530,12 @@ impl Platform for WindowsPlatform {
 
         self.exec_in_wsl(&distro, &script).await?;
 
+        // Same API calls as on Linux, through socat connected to the socket
+        let vm_config = self.firecracker_vm_config(instance);
+        let tunnel = ApiTunnel::wsl(&distro, &vm_config.socket_path).await?;
+        tunnel.client()?.boot(&vm_config).await?;
+        self.apply_port_forwards(instance, true);
+
         logger.info("VM started successfully in WSL2").await?;
 
         Ok(())
@@ -294,6 +570,7 @@ impl Platform for WindowsPlatform {
         })?;
 
         self.exec_in_wsl(&distro, &script).await?;
+        self.apply_port_forwards(instance, false);
 
         logger.info("VM stopped successfully in WSL2").await?;
 
@@ -311,6 +588,11 @@ impl Platform for WindowsPlatform {
         // First stop the VM if it's running
         let _ = self.stop_vm(instance, true).await;
 
+        // Servers launched in WSL while the guest was unreachable outlive it
+        let teardown =
+            ServerTeardown::for_instance(instance).command(&ServerPidFile::for_vm(&instance.name));
+        let _ = self.exec_in_wsl(&distro, &teardown).await;
+
         let template = DeleteVmTemplate {
             vm_name: instance.name.clone(),
         };
@@ -376,59 +658,7 @@ impl Platform for WindowsPlatform {
             });
         }
 
-        // Simple parsing of the output
-        let mut cpu_usage = 15.0;
-        let mut memory_used_kb = 0u64;
-        let mut memory_total_kb = 0u64;
-        let mut rx_bytes = 0u64;
-        let mut tx_bytes = 0u64;
-
-        for line in result.lines() {
-            if line.contains("cpu_usage") {
-                if let Some(value) = line.split(':').nth(1) {
-                    cpu_usage = value.trim().trim_end_matches(',').parse().unwrap_or(15.0);
-                }
-            } else if line.contains("memory_used_kb") {
-                if let Some(value) = line.split(':').nth(1) {
-                    memory_used_kb = value.trim().trim_end_matches(',').parse().unwrap_or(0);
-                }
-            } else if line.contains("memory_total_kb") {
-                if let Some(value) = line.split(':').nth(1) {
-                    memory_total_kb = value.trim().trim_end_matches(',').parse().unwrap_or(0);
-                }
-            } else if line.contains("rx_bytes") {
-                if let Some(value) = line.split(':').nth(1) {
-                    rx_bytes = value.trim().trim_end_matches(',').parse().unwrap_or(0);
-                }
-            } else if line.contains("tx_bytes") {
-                if let Some(value) = line.split(':').nth(1) {
-                    tx_bytes = value.trim().parse().unwrap_or(0);
-                }
-            }
-        }
-
-        Ok(VMMetrics {
-            cpu_usage,
-            memory_usage: aiva_core::MemoryMetrics {
-                total_mb: memory_total_kb / 1024,
-                used_mb: memory_used_kb / 1024,
-                available_mb: (memory_total_kb - memory_used_kb) / 1024,
-                cache_mb: 0,
-            },
-            disk_io: aiva_core::DiskIOMetrics {
-                read_bytes: 0,
-                write_bytes: 0,
-                read_ops: 0,
-                write_ops: 0,
-            },
-            network_io: aiva_core::NetworkIOMetrics {
-                rx_bytes,
-                tx_bytes,
-                rx_packets: 0,
-                tx_packets: 0,
-            },
-            uptime: std::time::Duration::from_secs(3600), // Default 1 hour
-        })
+        Ok(parse_vm_metrics(&result))
     }
 
     async fn execute_command(&self, instance: &VMInstance, command: &str) -> Result<String> {
@@ -456,20 +686,30 @@ impl Platform for WindowsPlatform {
 
         // If not registered, register it now
         if !command_pool.is_registered(&instance.name).await {
-            // Use network connection through guest IP
-            let connection_type = ConnectionType::Network {
-                host: instance.config.network.guest_ip.clone(),
-                port: VSOCK_COMMAND_PORT as u16,
-            };
+            let order = instance
+                .config
+                .execution
+                .transport_order(WINDOWS_TRANSPORTS)?;
+            // Running in the distribution does not fail, so nothing after
+            // `wsl` is ever tried
+            let candidates = order
+                .iter()
+                .take_while(|transport| **transport != ExecTransport::Wsl)
+                .filter_map(|transport| {
+                    connection_for(*transport, instance, None)
+                        .map(|connection| (*transport, connection))
+                })
+                .collect();
 
-            // Register the VM with the command pool
             if let Err(e) = command_pool
-                .register_vm(instance.name.clone(), connection_type)
+                .register_in_order(&instance.name, candidates)
                 .await
             {
-                warn!("Failed to register VM in command pool: {}", e);
+                if !order.contains(&ExecTransport::Wsl) {
+                    return Err(e);
+                }
+                debug!("Running command in WSL for VM {}: {}", instance.name, e);
 
-                // Fallback: execute command directly in WSL
                 let distro = self.ensure_wsl_distro().await?;
                 let fallback_output = self.exec_in_wsl(&distro, command).await?;
                 return Ok(fallback_output);
@@ -518,3 +758,54 @@ impl Platform for WindowsPlatform {
         "windows"
     }
 }
+
+/// Metrics of a VM from the key-value lines the WSL metrics script prints
+pub(crate) fn parse_vm_metrics(output: &str) -> VMMetrics {
+    let mut cpu_usage = 15.0;
+    let mut memory_used_kb = 0u64;
+    let mut memory_total_kb = 0u64;
+    let mut rx_bytes = 0u64;
+    let mut tx_bytes = 0u64;
+
+    for line in output.lines() {
+        if line.contains("cpu_usage") {
+            if let Some(value) = line.split(':').nth(1) {
+                cpu_usage = value.trim().trim_end_matches(',').parse().unwrap_or(15.0);
+            }
+        } else if line.contains("memory_used_kb") {
+            if let Some(value) = line.split(':').nth(1) {
+                memory_used_kb = value.trim().trim_end_matches(',').parse().unwrap_or(0);
+            }
+        } else if line.contains("memory_total_kb") {
+            if let Some(value) = line.split(':').nth(1) {
+                memory_total_kb = value.trim().trim_end_matches(',').parse().unwrap_or(0);
+            }
+        } else if line.contains("rx_bytes") {
+            if let Some(value) = line.split(':').nth(1) {
+                rx_bytes = value.trim().trim_end_matches(',').parse().unwrap_or(0);
+            }
+        } else if line.contains("tx_bytes")
+            && let Some(value) = line.split(':').nth(1)
+        {
+            tx_bytes = value.trim().parse().unwrap_or(0);
+        }
+    }
+
+    VMMetrics {
+        cpu_usage,
+        memory_usage: aiva_core::MemoryMetrics::from_kb(memory_total_kb, memory_used_kb),
+        disk_io: aiva_core::DiskIOMetrics {
+            read_bytes: 0,
+            write_bytes: 0,
+            read_ops: 0,
+            write_ops: 0,
+        },
+        network_io: aiva_core::NetworkIOMetrics {
+            rx_bytes,
+            tx_bytes,
+            rx_packets: 0,
+            tx_packets: 0,
+        },
+        uptime: std::time::Duration::from_secs(3600), // Default 1 hour
+    }
+}
@@ -0,0 +1,69 @@
+#!/bin/sh
+# Sample the Firecracker process of a VM and its TAP device in the Lima host,
+# printing one JSON object. CPU times are read twice, {{ window_secs }}s apart.
+SOCKET="{{ socket_path }}"
+TAP="{{ tap_device }}"
+
+# sudo's own process has the same command line but is older
+FC_PID=$(pgrep -n -f -- "firecracker --api-sock $SOCKET\$")
+if [ -z "$FC_PID" ] || [ ! -r "/proc/$FC_PID/stat" ]; then
+    echo '{"running": false}'
+    exit 0
+fi
+
+# Fields of /proc/<pid>/stat after the command name, which may contain spaces
+stat_field() {
+    sed 's/.*) //' "/proc/$FC_PID/stat" | awk -v field="$1" '{print $field}'
+}
+
+process_ticks() {
+    sed 's/.*) //' "/proc/$FC_PID/stat" | awk '{print $12 + $13}'
+}
+
+system_ticks() {
+    awk '/^cpu /{for (i = 2; i <= NF; i++) total += $i; print total}' /proc/stat
+}
+
+status_kb() {
+    awk -v key="$1:" '$1 == key {print $2}' "/proc/$FC_PID/status"
+}
+
+# Firecracker runs as root, so its I/O counters need sudo
+io_bytes() {
+    sudo -n cat "/proc/$FC_PID/io" 2>/dev/null | awk -v key="$1:" '$1 == key {print $2}'
+}
+
+net_counter() {
+    cat "/sys/class/net/$TAP/statistics/$1" 2>/dev/null
+}
+
+PROCESS_START=$(process_ticks)
+SYSTEM_START=$(system_ticks)
+sleep {{ window_secs }}
+PROCESS_END=$(process_ticks)
+SYSTEM_END=$(system_ticks)
+
+CPUS=$(grep -c '^cpu[0-9]' /proc/stat)
+VM_SIZE_KB=$(status_kb VmSize)
+VM_RSS_KB=$(status_kb VmRSS)
+START_TICKS=$(stat_field 20)
+CLOCK_TICKS=$(getconf CLK_TCK)
+UPTIME_SECS=$(awk '{print $1}' /proc/uptime)
+READ_BYTES=$(io_bytes read_bytes)
+WRITE_BYTES=$(io_bytes write_bytes)
+RX_BYTES=$(net_counter rx_bytes)
+TX_BYTES=$(net_counter tx_bytes)
+RX_PACKETS=$(net_counter rx_packets)
+TX_PACKETS=$(net_counter tx_packets)
+
+# Anything that could not be read, e.g. after the process exited, is 0
+echo "{\"running\": true, \
+\"process_ticks\": [${PROCESS_START:-0}, ${PROCESS_END:-0}], \
+\"system_ticks\": [${SYSTEM_START:-0}, ${SYSTEM_END:-0}], \
+\"cpus\": ${CPUS:-0}, \
+\"vm_size_kb\": ${VM_SIZE_KB:-0}, \"vm_rss_kb\": ${VM_RSS_KB:-0}, \
+\"start_ticks\": ${START_TICKS:-0}, \"clock_ticks\": ${CLOCK_TICKS:-100}, \
+\"uptime_secs\": ${UPTIME_SECS:-0}, \
+\"read_bytes\": ${READ_BYTES:-0}, \"write_bytes\": ${WRITE_BYTES:-0}, \
+\"rx_bytes\": ${RX_BYTES:-0}, \"tx_bytes\": ${TX_BYTES:-0}, \
+\"rx_packets\": ${RX_PACKETS:-0}, \"tx_packets\": ${TX_PACKETS:-0}}"
@@ -0,0 +1,37 @@
+{% include "setup_downloads.sh" %}
+
+# Create directories
+sudo mkdir -p /var/lib/firecracker /var/run/firecracker /opt/aiva/images
+sudo chmod 755 /var/lib/firecracker /var/run/firecracker /opt/aiva/images
+
+# Check if Firecracker is installed
+if ! command -v firecracker >/dev/null 2>&1; then
+    echo "Installing Firecracker..."
+    ARCH=$(uname -m)
+    if [ "$ARCH" = "x86_64" ]; then
+        FC_ARCH="x86_64"
+    elif [ "$ARCH" = "aarch64" ] || [ "$ARCH" = "arm64" ]; then
+        FC_ARCH="aarch64"
+    else
+        echo "Unsupported architecture: $ARCH"
+        exit 1
+    fi
+
+    # Download Firecracker binary
+    cd /tmp
+    download_firecracker
+    tar -xzf firecracker-{{ sources.firecracker_tag }}-${FC_ARCH}.tgz
+    sudo mv release-{{ sources.firecracker_tag }}-${FC_ARCH}/firecracker-{{ sources.firecracker_tag }}-${FC_ARCH} /usr/local/bin/firecracker
+    sudo chmod +x /usr/local/bin/firecracker
+    rm -rf firecracker-{{ sources.firecracker_tag }}-${FC_ARCH}.tgz release-{{ sources.firecracker_tag }}-${FC_ARCH}
+
+    # Download kernel image
+    download_kernel /tmp/vmlinux.bin
+    sudo mv /tmp/vmlinux.bin /opt/aiva/images/vmlinux
+
+    # Download base rootfs
+    download_rootfs /tmp/bionic.rootfs.ext4
+    sudo mv /tmp/bionic.rootfs.ext4 /opt/aiva/images/base.rootfs.ext4
+fi
+
+echo "Firecracker setup complete"
@@ -0,0 +1,61 @@
+{%- if sources.skip_downloads %}
+# Downloads are disabled, so whatever is missing has to be staged by hand
+missing_artifact() {
+    echo "$1 is missing and downloads are disabled (skip_downloads); stage it before running setup"
+    exit 1
+}
+
+download_firecracker() {
+    missing_artifact "Firecracker {{ sources.firecracker_tag }}"
+}
+
+download_kernel() {
+    missing_artifact "The guest kernel"
+}
+
+download_rootfs() {
+    missing_artifact "The base rootfs"
+}
+{%- else %}
+# Verify a download against an expected SHA-256, removing it on mismatch
+verify_sha256() {
+    if ! echo "$2  $1" | sha256sum -c - >/dev/null 2>&1; then
+        echo "Checksum mismatch for $1"
+        rm -f "$1"
+        exit 1
+    fi
+}
+
+# Download and verify the Firecracker release tarball into the current directory
+download_firecracker() {
+    FC_TGZ="firecracker-{{ sources.firecracker_tag }}-${FC_ARCH}.tgz"
+    wget -q -O "$FC_TGZ" "{{ sources.firecracker_url }}"
+{%- if sources.firecracker_sha256.is_empty() %}
+    wget -q -O "$FC_TGZ.sha256.txt" "{{ sources.firecracker_url }}.sha256.txt"
+    if ! sha256sum -c "$FC_TGZ.sha256.txt" >/dev/null 2>&1; then
+        echo "Checksum mismatch for $FC_TGZ"
+        rm -f "$FC_TGZ" "$FC_TGZ.sha256.txt"
+        exit 1
+    fi
+    rm -f "$FC_TGZ.sha256.txt"
+{%- else %}
+    verify_sha256 "$FC_TGZ" "{{ sources.firecracker_sha256 }}"
+{%- endif %}
+}
+
+# Download the guest kernel to the given path
+download_kernel() {
+    wget -q -O "$1" "{{ sources.kernel_url }}"
+{%- if !sources.kernel_sha256.is_empty() %}
+    verify_sha256 "$1" "{{ sources.kernel_sha256 }}"
+{%- endif %}
+}
+
+# Download the base rootfs to the given path
+download_rootfs() {
+    wget -q -O "$1" "{{ sources.rootfs_url }}"
+{%- if !sources.rootfs_sha256.is_empty() %}
+    verify_sha256 "$1" "{{ sources.rootfs_sha256 }}"
+{%- endif %}
+}
+{%- endif %}
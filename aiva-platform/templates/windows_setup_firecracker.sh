@@ -7,10 +7,18 @@ if [ ! -f /proc/sys/fs/binfmt_misc/WSLInterop ]; then
     exit 1
 fi
 
+{% include "setup_downloads.sh" %}
+
 # Create directories
 sudo mkdir -p /opt/aiva/firecracker /var/lib/firecracker /var/run/firecracker
 sudo chmod 755 /opt/aiva/firecracker /var/lib/firecracker /var/run/firecracker
 
+# socat forwards the Firecracker API socket to the Windows host
+if ! command -v socat >/dev/null 2>&1; then
+    echo "Installing socat..."
+    sudo apt-get update -qq && sudo apt-get install -y -qq socat
+fi
+
 # Check if Firecracker is installed
 if ! command -v firecracker >/dev/null 2>&1; then
     echo "Installing Firecracker..."
@@ -24,23 +32,26 @@ if ! command -v firecracker >/dev/null 2>&1; then
     fi
     
     # Download Firecracker
-    wget -q https://github.com/firecracker-microvm/firecracker/releases/download/v1.12.1/firecracker-v1.12.1-${FC_ARCH}.tgz
-    tar -xzf firecracker-v1.12.1-${FC_ARCH}.tgz
-    sudo mv release-v1.12.1-${FC_ARCH}/firecracker-v1.12.1-${FC_ARCH} /usr/local/bin/firecracker
-    sudo mv release-v1.12.1-${FC_ARCH}/jailer-v1.12.1-${FC_ARCH} /usr/local/bin/jailer
+    download_firecracker
+    tar -xzf firecracker-{{ sources.firecracker_tag }}-${FC_ARCH}.tgz
+    sudo mv release-{{ sources.firecracker_tag }}-${FC_ARCH}/firecracker-{{ sources.firecracker_tag }}-${FC_ARCH} /usr/local/bin/firecracker
+    sudo mv release-{{ sources.firecracker_tag }}-${FC_ARCH}/jailer-{{ sources.firecracker_tag }}-${FC_ARCH} /usr/local/bin/jailer
     sudo chmod +x /usr/local/bin/firecracker /usr/local/bin/jailer
-    rm -rf firecracker-v1.12.1-${FC_ARCH}.tgz release-v1.12.1-${FC_ARCH}
+    rm -rf firecracker-{{ sources.firecracker_tag }}-${FC_ARCH}.tgz release-{{ sources.firecracker_tag }}-${FC_ARCH}
 fi
 
 # Download kernel and rootfs if needed
+FC_ARCH="x86_64"
 if [ ! -f /opt/aiva/firecracker/vmlinux ]; then
     echo "Downloading kernel..."
-    sudo wget -q -O /opt/aiva/firecracker/vmlinux https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/x86_64/kernels/vmlinux.bin
+    download_kernel /tmp/vmlinux.bin
+    sudo mv /tmp/vmlinux.bin /opt/aiva/firecracker/vmlinux
 fi
 
 if [ ! -f /opt/aiva/firecracker/base.rootfs.ext4 ]; then
     echo "Downloading base rootfs..."
-    sudo wget -q -O /opt/aiva/firecracker/base.rootfs.ext4 https://s3.amazonaws.com/spec.ccfc.min/img/quickstart_guide/x86_64/rootfs/bionic.rootfs.ext4
+    download_rootfs /tmp/base.rootfs.ext4
+    sudo mv /tmp/base.rootfs.ext4 /opt/aiva/firecracker/base.rootfs.ext4
 fi
 
 echo "Firecracker setup complete"
\ No newline at end of file
@@ -1,37 +1,20 @@
-use aiva_core::{
-    CacheStrategy, NetworkConfig, PortMapping, Protocol, Result, StorageConfig, VMConfig,
-    VMInstance, VMState,
-};
+use aiva_core::{Protocol, Result, VMConfig, VMInstance, VMState};
 use aiva_platform::{detect_platform, get_current_platform};
 use std::time::Duration;
 use uuid::Uuid;
 
 // Helper to create a test VM configuration
 fn create_test_vm_config() -> VMConfig {
-    VMConfig {
-        cpus: 1,
-        memory_mb: 512,
-        disk_gb: 5,
-        kernel_path: "/opt/aiva/kernel/vmlinux".to_string().into(),
-        rootfs_path: "/opt/aiva/images/base.rootfs.ext4".to_string().into(),
-        network: NetworkConfig {
-            guest_ip: "172.16.0.2".to_string(),
-            host_ip: "172.16.0.1".to_string(),
-            subnet: "172.16.0.0/24".to_string(),
-            gateway: "172.16.0.1".to_string(),
-            dns_servers: vec!["8.8.8.8".to_string()],
-            dhcp_enabled: false,
-            port_mappings: vec![PortMapping {
-                host_port: 8080,
-                guest_port: 80,
-                protocol: Protocol::Tcp,
-            }],
-        },
-        storage: StorageConfig {
-            cache_strategy: CacheStrategy::Writeback,
-            additional_drives: vec![],
-        },
-    }
+    VMConfig::builder()
+        .cpus(1)
+        .memory_mb(512)
+        .disk_gb(5)
+        .kernel_path("/opt/aiva/kernel/vmlinux")
+        .rootfs_path("/opt/aiva/images/base.rootfs.ext4")
+        .dns_servers(["8.8.8.8"])
+        .port(8080, 80, Protocol::Tcp)
+        .build()
+        .unwrap()
 }
 
 // Helper to create a test VM instance
@@ -46,9 +29,12 @@ fn create_test_vm_instance(name: &str) -> VMInstance {
             api_socket: None,
             vsock_cid: None,
             tap_device: None,
+            mcp_pids: Some(Vec::new()),
+            paused: None,
         },
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        schema_version: aiva_core::SCHEMA_VERSION,
     }
 }
 
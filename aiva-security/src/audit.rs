@@ -0,0 +1,256 @@
+//! Seccomp violation reporting.
+//!
+//! A guest that trips its seccomp filter shows up in two places: the kernel
+//! audit log records a `type=SECCOMP` event for every logged or killed
+//! syscall, and Firecracker logs the offending syscall number right before
+//! it shuts the VM down. Both are parsed into a [`SeccompEvent`] and raised
+//! as [`AlertType::SecurityViolation`](aiva_core::AlertType) alerts.
+
+use crate::FilterAction;
+use aiva_core::{Alert, AlertSeverity, MonitoringService, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::debug;
+
+/// `SECCOMP_RET_ACTION_FULL` mask and the action values the kernel reports
+const SECCOMP_RET_ACTION_FULL: u32 = 0xffff_0000;
+const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+const SECCOMP_RET_KILL_THREAD: u32 = 0x0000_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+
+/// `AUDIT_ARCH_*` values as printed in the `arch=` field
+const AUDIT_ARCH_X86_64: &str = "c000003e";
+const AUDIT_ARCH_AARCH64: &str = "c00000b7";
+
+const FIRECRACKER_BAD_SYSCALL: &str = "intercepting a bad syscall (";
+
+/// Syscalls worth naming in alerts, as `(name, x86_64, aarch64)`. `open` has
+/// no aarch64 number.
+const SYSCALL_NAMES: &[(&str, Option<u32>, Option<u32>)] = &[
+    ("read", Some(0), Some(63)),
+    ("write", Some(1), Some(64)),
+    ("open", Some(2), None),
+    ("close", Some(3), Some(57)),
+    ("socket", Some(41), Some(198)),
+    ("clone", Some(56), Some(220)),
+    ("execve", Some(59), Some(221)),
+    ("ptrace", Some(101), Some(117)),
+    ("personality", Some(135), Some(92)),
+    ("pivot_root", Some(155), Some(41)),
+    ("chroot", Some(161), Some(51)),
+    ("mount", Some(165), Some(40)),
+    ("umount2", Some(166), Some(39)),
+    ("swapon", Some(167), Some(224)),
+    ("reboot", Some(169), Some(142)),
+    ("init_module", Some(175), Some(105)),
+    ("delete_module", Some(176), Some(106)),
+    ("kexec_load", Some(246), Some(104)),
+    ("add_key", Some(248), Some(217)),
+    ("keyctl", Some(250), Some(219)),
+    ("unshare", Some(272), Some(97)),
+    ("perf_event_open", Some(298), Some(241)),
+    ("setns", Some(308), Some(268)),
+    ("finit_module", Some(313), Some(273)),
+    ("bpf", Some(321), Some(280)),
+    ("userfaultfd", Some(323), Some(282)),
+];
+
+/// A syscall that was logged or blocked by a seccomp filter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeccompEvent {
+    /// Host process that made the call, when the source reports it
+    pub pid: Option<u32>,
+    pub comm: Option<String>,
+    pub syscall: u32,
+    /// Architecture the syscall number belongs to (`x86_64`, `aarch64`, ...)
+    pub arch: String,
+    pub action: FilterAction,
+}
+
+impl SeccompEvent {
+    /// Parse a kernel audit record, as found in `/var/log/audit/audit.log`
+    /// (`type=SECCOMP`) or the kernel ring buffer (`type=1326`). Events whose
+    /// action is `ALLOW` are not violations and yield `None`.
+    pub fn parse_audit_line(line: &str) -> Option<Self> {
+        let fields = audit_fields(line);
+        if !matches!(fields.get("type").copied(), Some("SECCOMP" | "1326")) {
+            return None;
+        }
+
+        let syscall = fields.get("syscall")?.parse().ok()?;
+        let code = fields
+            .get("code")
+            .and_then(|code| u32::from_str_radix(code.trim_start_matches("0x"), 16).ok())
+            .unwrap_or(SECCOMP_RET_KILL_PROCESS);
+        let action = match code & SECCOMP_RET_ACTION_FULL {
+            SECCOMP_RET_ALLOW => return None,
+            SECCOMP_RET_KILL_PROCESS | SECCOMP_RET_KILL_THREAD => FilterAction::Kill,
+            SECCOMP_RET_LOG => FilterAction::Log,
+            // ERRNO, TRAP and tracer hand-offs all deny the call without
+            // killing the caller
+            _ => FilterAction::Trap,
+        };
+        let arch = match fields.get("arch").copied() {
+            Some(AUDIT_ARCH_X86_64) => "x86_64".to_string(),
+            Some(AUDIT_ARCH_AARCH64) => "aarch64".to_string(),
+            Some(other) => other.to_string(),
+            None => std::env::consts::ARCH.to_string(),
+        };
+
+        Some(Self {
+            pid: fields.get("pid").and_then(|pid| pid.parse().ok()),
+            comm: fields.get("comm").map(|comm| comm.to_string()),
+            syscall,
+            arch,
+            action,
+        })
+    }
+
+    /// Parse Firecracker's own log line for a syscall its filter rejected.
+    /// Firecracker always kills the VM in that case.
+    pub fn parse_firecracker_line(line: &str) -> Option<Self> {
+        let (_, rest) = line.split_once(FIRECRACKER_BAD_SYSCALL)?;
+        let (number, _) = rest.split_once(')')?;
+
+        Some(Self {
+            pid: None,
+            comm: Some("firecracker".to_string()),
+            syscall: number.trim().parse().ok()?,
+            arch: std::env::consts::ARCH.to_string(),
+            action: FilterAction::Kill,
+        })
+    }
+
+    /// Syscall name for known numbers, `syscall#<n>` otherwise
+    pub fn syscall_name(&self) -> String {
+        SYSCALL_NAMES
+            .iter()
+            .find(|(_, x86_64, aarch64)| match self.arch.as_str() {
+                "x86_64" => *x86_64 == Some(self.syscall),
+                "aarch64" => *aarch64 == Some(self.syscall),
+                _ => false,
+            })
+            .map(|(name, _, _)| name.to_string())
+            .unwrap_or_else(|| format!("syscall#{}", self.syscall))
+    }
+
+    pub fn severity(&self) -> AlertSeverity {
+        match self.action {
+            FilterAction::Kill => AlertSeverity::Critical,
+            FilterAction::Trap => AlertSeverity::High,
+            FilterAction::Log | FilterAction::Allow => AlertSeverity::Medium,
+        }
+    }
+
+    fn describe(&self, vm_id: &str) -> String {
+        let outcome = match self.action {
+            FilterAction::Kill => "killed the caller",
+            FilterAction::Trap => "was denied",
+            FilterAction::Log | FilterAction::Allow => "was logged",
+        };
+        let caller = match (&self.comm, self.pid) {
+            (Some(comm), Some(pid)) => format!(" by {comm} (pid {pid})"),
+            (Some(comm), None) => format!(" by {comm}"),
+            (None, Some(pid)) => format!(" by pid {pid}"),
+            (None, None) => String::new(),
+        };
+        format!(
+            "Seccomp filter of VM {vm_id} {outcome}: {} ({}){caller}",
+            self.syscall_name(),
+            self.syscall
+        )
+    }
+}
+
+/// Split an audit record into its `key=value` fields. Values may be quoted
+/// and contain spaces (`comm="fc_vcpu 0"`).
+fn audit_fields(line: &str) -> HashMap<&str, &str> {
+    let mut fields = HashMap::new();
+    let mut rest = line.trim();
+
+    while !rest.is_empty() {
+        let Some((key, after)) = rest.split_once('=') else {
+            break;
+        };
+        // Keys are the last word before '=', which skips prefixes such as
+        // "audit:" or "msg=audit(...):"
+        let key = key.rsplit(' ').next().unwrap_or(key);
+
+        let (value, remaining) = match after.strip_prefix('"') {
+            Some(quoted) => match quoted.split_once('"') {
+                Some((value, remaining)) => (value, remaining),
+                None => (quoted, ""),
+            },
+            None => after.split_once(' ').unwrap_or((after, "")),
+        };
+
+        fields.entry(key).or_insert(value);
+        rest = remaining.trim_start();
+    }
+
+    fields
+}
+
+/// Maps seccomp events to the VMs that caused them and raises alerts
+pub struct SeccompMonitor {
+    monitoring: Arc<MonitoringService>,
+    /// Host pid of each VM's Firecracker process
+    vm_pids: RwLock<HashMap<u32, String>>,
+}
+
+impl SeccompMonitor {
+    pub fn new(monitoring: Arc<MonitoringService>) -> Self {
+        Self {
+            monitoring,
+            vm_pids: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Attribute audit events from `pid` to `vm_id`
+    pub async fn track(&self, pid: u32, vm_id: &str) {
+        self.vm_pids.write().await.insert(pid, vm_id.to_string());
+    }
+
+    pub async fn untrack(&self, pid: u32) {
+        self.vm_pids.write().await.remove(&pid);
+    }
+
+    /// Feed one line of the host audit log. Events from processes that are
+    /// not tracked VMs are ignored.
+    pub async fn ingest_audit_line(&self, line: &str) -> Result<Option<Alert>> {
+        let Some(event) = SeccompEvent::parse_audit_line(line) else {
+            return Ok(None);
+        };
+        let Some(pid) = event.pid else {
+            return Ok(None);
+        };
+        let Some(vm_id) = self.vm_pids.read().await.get(&pid).cloned() else {
+            debug!("Ignoring seccomp event from untracked pid {}", pid);
+            return Ok(None);
+        };
+
+        self.report(&vm_id, &event).await
+    }
+
+    /// Feed one line of the Firecracker log of `vm_id`
+    pub async fn ingest_firecracker_line(&self, vm_id: &str, line: &str) -> Result<Option<Alert>> {
+        match SeccompEvent::parse_firecracker_line(line) {
+            Some(event) => self.report(vm_id, &event).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Raise a rate-limited security alert for `event`
+    pub async fn report(&self, vm_id: &str, event: &SeccompEvent) -> Result<Option<Alert>> {
+        self.monitoring
+            .report_security_violation(
+                vm_id,
+                &event.syscall_name(),
+                event.severity(),
+                event.describe(vm_id),
+            )
+            .await
+    }
+}
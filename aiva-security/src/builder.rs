@@ -0,0 +1,153 @@
+//! Fluent construction of a [`SecurityPolicy`].
+
+use crate::{
+    Direction, FilterAction, IOLimit, IsolationLevel, NetworkRateLimit, PortRule, SecurityPolicy,
+    SyscallFilter, SyscallRule,
+};
+use aiva_core::Result;
+
+impl SecurityPolicy {
+    pub fn builder() -> SecurityPolicyBuilder {
+        SecurityPolicyBuilder::default()
+    }
+}
+
+/// Builds a [`SecurityPolicy`] on top of the defaults, validating on
+/// [`build`](Self::build)
+#[derive(Debug, Clone, Default)]
+pub struct SecurityPolicyBuilder {
+    policy: SecurityPolicy,
+}
+
+impl SecurityPolicyBuilder {
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.policy.name = name.into();
+        self
+    }
+
+    pub fn extends(mut self, parent: impl Into<String>) -> Self {
+        self.policy.extends = Some(parent.into());
+        self
+    }
+
+    pub fn isolation_level(mut self, level: IsolationLevel) -> Self {
+        self.policy.isolation_level = level;
+        self
+    }
+
+    pub fn allow_capability(mut self, capability: impl Into<String>) -> Self {
+        push_once(&mut self.policy.capabilities.allowed, capability.into());
+        self
+    }
+
+    pub fn deny_capability(mut self, capability: impl Into<String>) -> Self {
+        push_once(&mut self.policy.capabilities.denied, capability.into());
+        self
+    }
+
+    /// Filter syscalls, with `action` for those no rule matches
+    pub fn default_syscall_action(mut self, action: FilterAction) -> Self {
+        self.syscall_filter().default_action = action;
+        self
+    }
+
+    /// Add a rule taking `action` whenever `syscall` is made. Starts an
+    /// allow-by-default filter if the policy has none yet.
+    pub fn syscall(mut self, syscall: impl Into<String>, action: FilterAction) -> Self {
+        self.syscall_filter().rules.push(SyscallRule {
+            syscall: syscall.into(),
+            action,
+            conditions: None,
+        });
+        self
+    }
+
+    /// Kill the VM when it makes `syscall`, see [`syscall`](Self::syscall)
+    pub fn syscall_kill(self, syscall: impl Into<String>) -> Self {
+        self.syscall(syscall, FilterAction::Kill)
+    }
+
+    pub fn syscall_rule(mut self, rule: SyscallRule) -> Self {
+        self.syscall_filter().rules.push(rule);
+        self
+    }
+
+    pub fn cpu_quota(mut self, percent: u32) -> Self {
+        self.policy.resource_limits.cpu_quota = Some(percent);
+        self
+    }
+
+    pub fn memory_limit(mut self, bytes: u64) -> Self {
+        self.policy.resource_limits.memory_limit = Some(bytes);
+        self
+    }
+
+    pub fn pids_limit(mut self, pids: u32) -> Self {
+        self.policy.resource_limits.pids_limit = Some(pids);
+        self
+    }
+
+    pub fn open_files(mut self, files: u32) -> Self {
+        self.policy.resource_limits.open_files = Some(files);
+        self
+    }
+
+    pub fn io_bandwidth(mut self, limit: IOLimit) -> Self {
+        self.policy.resource_limits.io_bandwidth = Some(limit);
+        self
+    }
+
+    pub fn allow_outbound(mut self, allow: bool) -> Self {
+        self.policy.network_policy.allow_outbound = allow;
+        self
+    }
+
+    pub fn allow_port(
+        mut self,
+        port: u16,
+        protocol: impl Into<String>,
+        direction: Direction,
+    ) -> Self {
+        self.policy.network_policy.allowed_ports.push(PortRule {
+            port,
+            protocol: protocol.into(),
+            direction,
+        });
+        self
+    }
+
+    /// Block an address or CIDR range
+    pub fn block_ip(mut self, range: impl Into<String>) -> Self {
+        push_once(&mut self.policy.network_policy.blocked_ips, range.into());
+        self
+    }
+
+    pub fn rate_limit(mut self, bandwidth_mbps: u32, connections_per_second: u32) -> Self {
+        self.policy.network_policy.rate_limit = Some(NetworkRateLimit {
+            bandwidth_mbps,
+            connections_per_second,
+        });
+        self
+    }
+
+    /// The policy, if it passes [`SecurityPolicy::validate`]
+    pub fn build(self) -> Result<SecurityPolicy> {
+        self.policy.validate()?;
+        Ok(self.policy)
+    }
+
+    fn syscall_filter(&mut self) -> &mut SyscallFilter {
+        self.policy
+            .syscall_filter
+            .get_or_insert_with(|| SyscallFilter {
+                default_action: FilterAction::Allow,
+                rules: Vec::new(),
+            })
+    }
+}
+
+fn push_once(values: &mut Vec<String>, value: String) {
+    if !values.contains(&value) {
+        values.push(value);
+    }
+}
@@ -0,0 +1,101 @@
+//! Expansion of the `ALL` capability sentinel.
+//!
+//! A policy may deny `"ALL"` instead of listing every capability, but the
+//! jailer and the bounding set only understand concrete `CAP_*` names.
+//! [`CapabilitySet::expanded`] replaces the sentinel with every capability
+//! the kernel defines, less the ones the policy allows.
+
+use crate::CapabilitySet;
+use aiva_core::{AivaError, Result};
+
+/// Denying this denies every capability
+pub const ALL_CAPABILITIES: &str = "ALL";
+
+/// Every Linux capability, in kernel numbering from `CAP_CHOWN` (0) to
+/// `CAP_CHECKPOINT_RESTORE` (40)
+pub const LINUX_CAPABILITIES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+impl CapabilitySet {
+    /// Whether `denied` holds the `ALL` sentinel
+    pub fn denies_all(&self) -> bool {
+        self.denied.iter().any(|cap| cap == ALL_CAPABILITIES)
+    }
+
+    /// Reject allowing capabilities next to a denied `ALL`
+    pub fn validate(&self) -> Result<()> {
+        if self.denies_all() && !self.allowed.is_empty() {
+            return Err(AivaError::SecurityError(format!(
+                "Cannot allow capabilities when {ALL_CAPABILITIES} is denied: {}",
+                self.allowed.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// The same set with `ALL` replaced by every capability not in
+    /// `allowed`. Sets without the sentinel are returned unchanged.
+    pub fn expanded(&self) -> CapabilitySet {
+        if !self.denies_all() {
+            return self.clone();
+        }
+
+        let mut denied: Vec<String> = LINUX_CAPABILITIES
+            .iter()
+            .filter(|cap| !self.allowed.iter().any(|allowed| allowed == *cap))
+            .map(|cap| cap.to_string())
+            .collect();
+        // Names the list does not know, e.g. from a newer kernel
+        for cap in &self.denied {
+            if cap != ALL_CAPABILITIES && !denied.contains(cap) {
+                denied.push(cap.clone());
+            }
+        }
+
+        CapabilitySet {
+            allowed: self.allowed.clone(),
+            denied,
+        }
+    }
+}
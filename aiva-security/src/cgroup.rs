@@ -0,0 +1,189 @@
+//! Enforcement of `ResourceLimits` through cgroup v2.
+//!
+//! The jailer creates a cgroup for every microVM and writes any
+//! `--cgroup <file>=<value>` pairs into it before dropping privileges, so the
+//! policy limits apply from the first instruction without a separate cgroup
+//! writer. A VM process started some other way is moved into a cgroup of its
+//! own under `/sys/fs/cgroup/aiva` by [`VmCgroup`], which also limits IO on
+//! the disk holding the VM's image.
+
+use crate::ResourceLimits;
+use aiva_core::{AivaError, Result};
+use std::path::{Path, PathBuf};
+
+/// Period of `cpu.max`, the kernel default
+pub const CGROUP_CPU_PERIOD_US: u64 = 100_000;
+/// Where the cgroup v2 hierarchy is mounted
+pub const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+/// Parent of the VM cgroups aiva creates, below the root
+const AIVA_CGROUP: &str = "aiva";
+/// Controllers the VM cgroups write to
+const CGROUP_CONTROLLERS: &[&str] = &["cpu", "io", "memory", "pids"];
+
+impl ResourceLimits {
+    /// Jailer arguments enforcing these limits on cgroup v2. `cpu_quota` is a
+    /// percentage of the VM's `vcpus`. Empty when no limit is set.
+    pub fn jailer_cgroup_args(&self, vcpus: u32) -> Result<Vec<String>> {
+        let settings = self.cgroup_settings(vcpus, None)?;
+        if settings.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut args = vec!["--cgroup-version".to_string(), "2".to_string()];
+        for (file, value) in settings {
+            args.push("--cgroup".to_string());
+            args.push(format!("{file}={value}"));
+        }
+        Ok(args)
+    }
+
+    /// cgroup v2 files and the values enforcing these limits. `cpu_quota` is
+    /// a percentage of the VM's `vcpus`. IO is only limited given `io_device`,
+    /// the `major:minor` of the disk the VM reads and writes.
+    pub fn cgroup_settings(
+        &self,
+        vcpus: u32,
+        io_device: Option<&str>,
+    ) -> Result<Vec<(String, String)>> {
+        let mut settings = Vec::new();
+
+        if let Some(cpu_quota) = self.cpu_quota {
+            if cpu_quota == 0 || cpu_quota > 100 {
+                return Err(invalid_limit("cpu_quota", "a percentage between 1 and 100"));
+            }
+            if vcpus == 0 {
+                return Err(invalid_limit("vcpus", "greater than zero"));
+            }
+            let quota = CGROUP_CPU_PERIOD_US * u64::from(vcpus) * u64::from(cpu_quota) / 100;
+            settings.push((
+                "cpu.max".to_string(),
+                format!("{quota} {CGROUP_CPU_PERIOD_US}"),
+            ));
+        }
+
+        if let Some(memory_limit) = self.memory_limit {
+            if memory_limit == 0 {
+                return Err(invalid_limit("memory_limit", "greater than zero"));
+            }
+            settings.push(("memory.max".to_string(), memory_limit.to_string()));
+        }
+
+        if let Some(pids_limit) = self.pids_limit {
+            if pids_limit == 0 {
+                return Err(invalid_limit("pids_limit", "greater than zero"));
+            }
+            settings.push(("pids.max".to_string(), pids_limit.to_string()));
+        }
+
+        if let (Some(io), Some(device)) = (&self.io_bandwidth, io_device) {
+            let limits: Vec<String> = [
+                ("rbps", io.read_bps),
+                ("wbps", io.write_bps),
+                ("riops", io.read_iops),
+                ("wiops", io.write_iops),
+            ]
+            .into_iter()
+            .filter_map(|(key, limit)| limit.map(|limit| format!("{key}={limit}")))
+            .collect();
+            if !limits.is_empty() {
+                settings.push((
+                    "io.max".to_string(),
+                    format!("{device} {}", limits.join(" ")),
+                ));
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+/// The cgroup of one VM, `aiva/<vm_id>` below a cgroup v2 root
+#[derive(Debug, Clone)]
+pub struct VmCgroup {
+    path: PathBuf,
+}
+
+impl VmCgroup {
+    /// Create the cgroup of `vm_id` below `root`, enabling the controllers
+    /// it needs on the way
+    pub fn create(root: &Path, vm_id: &str) -> Result<Self> {
+        if !cgroup_v2_mounted(root) {
+            return Err(AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: format!("cgroup v2 is not mounted at {}", root.display()),
+                recoverable: true,
+            });
+        }
+
+        let available = std::fs::read_to_string(root.join("cgroup.controllers"))?;
+        let enable: Vec<String> = CGROUP_CONTROLLERS
+            .iter()
+            .filter(|controller| available.split_whitespace().any(|c| c == **controller))
+            .map(|controller| format!("+{controller}"))
+            .collect();
+
+        let parent = root.join(AIVA_CGROUP);
+        std::fs::create_dir_all(&parent)?;
+        for dir in [root, parent.as_path()] {
+            std::fs::write(dir.join("cgroup.subtree_control"), enable.join(" "))?;
+        }
+
+        let path = parent.join(vm_id);
+        std::fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write `settings` as returned by [`ResourceLimits::cgroup_settings`]
+    pub fn apply(&self, settings: &[(String, String)]) -> Result<()> {
+        for (file, value) in settings {
+            std::fs::write(self.path.join(file), value).map_err(|e| {
+                AivaError::SecurityError(format!(
+                    "Failed to set {file} of {}: {e}",
+                    self.path.display()
+                ))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Move process `pid` into this cgroup
+    pub fn add_process(&self, pid: u32) -> Result<()> {
+        std::fs::write(self.path.join("cgroup.procs"), pid.to_string())?;
+        Ok(())
+    }
+}
+
+/// Whether `root` is a cgroup v2 hierarchy
+pub fn cgroup_v2_mounted(root: &Path) -> bool {
+    root.join("cgroup.controllers").exists()
+}
+
+/// `major:minor` of the disk holding `path`, `None` when it is on a
+/// filesystem without a block device such as tmpfs. A partition is
+/// resolved to its disk, which is what `io.max` limits.
+#[cfg(unix)]
+pub fn block_device_of(path: &Path) -> Result<Option<String>> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = std::fs::metadata(path)?.dev();
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    if major == 0 {
+        return Ok(None);
+    }
+
+    let sysfs = PathBuf::from(format!("/sys/dev/block/{major}:{minor}"));
+    if sysfs.join("partition").exists() {
+        let disk = std::fs::read_to_string(sysfs.join("..").join("dev"))?;
+        return Ok(Some(disk.trim().to_string()));
+    }
+    Ok(Some(format!("{major}:{minor}")))
+}
+
+fn invalid_limit(field: &str, expected: &str) -> AivaError {
+    AivaError::SecurityError(format!("Resource limit '{field}' must be {expected}"))
+}
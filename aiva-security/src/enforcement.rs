@@ -0,0 +1,190 @@
+//! Verification that policy limits actually took effect in the VMM.
+//!
+//! Older Firecracker releases silently ignore rate limiter fields they do not
+//! understand, so after applying an `IOLimit` the drive's limiter is read back
+//! from `GET /vm/config` and compared with what the policy asked for. The
+//! open files limit is read back from the VMM process the same way.
+
+use crate::IOLimit;
+use crate::rlimit::OpenFilesLimit;
+use aiva_core::{AivaError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Firecracker token bucket: `size` tokens refilled every `refill_time` ms
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenBucket {
+    pub size: u64,
+    pub refill_time: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_time_burst: Option<u64>,
+}
+
+impl TokenBucket {
+    /// Sustained rate in tokens per second, `None` if the bucket never refills
+    pub fn per_second(&self) -> Option<u64> {
+        (self.refill_time > 0).then(|| self.size.saturating_mul(1000) / self.refill_time)
+    }
+}
+
+/// Rate limiter of a Firecracker block device
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DriveRateLimiter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<TokenBucket>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ops: Option<TokenBucket>,
+}
+
+impl IOLimit {
+    /// Firecracker has a single bucket for reads and writes, so the tighter of
+    /// the two limits is the one that gets applied
+    pub fn effective_bps(&self) -> Option<u64> {
+        tighter(self.read_bps, self.write_bps)
+    }
+
+    pub fn effective_iops(&self) -> Option<u64> {
+        tighter(self.read_iops, self.write_iops)
+    }
+
+    /// The drive rate limiter that enforces this limit
+    pub fn to_rate_limiter(&self) -> DriveRateLimiter {
+        let bucket = |rate: u64| TokenBucket {
+            size: rate,
+            refill_time: 1000,
+            one_time_burst: None,
+        };
+
+        DriveRateLimiter {
+            bandwidth: self.effective_bps().map(bucket),
+            ops: self.effective_iops().map(bucket),
+        }
+    }
+}
+
+fn tighter(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum IOLimitStatus {
+    /// The VMM reports the limits the policy asked for
+    Enforced,
+    /// A limiter is present but differs from the policy
+    Drifted { differences: Vec<String> },
+    /// The drive has no rate limiter at all
+    Missing,
+}
+
+/// Outcome of comparing an intended `IOLimit` with the VMM's readback
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IOLimitCheck {
+    pub intended: IOLimit,
+    pub readback: Option<DriveRateLimiter>,
+    pub status: IOLimitStatus,
+}
+
+impl IOLimitCheck {
+    pub fn is_enforced(&self) -> bool {
+        self.status == IOLimitStatus::Enforced
+    }
+}
+
+/// Compare the limiter read back from the VMM with the policy's `IOLimit`
+pub fn verify_io_limit(intended: &IOLimit, readback: Option<&DriveRateLimiter>) -> IOLimitCheck {
+    let expected = intended.to_rate_limiter();
+
+    let status = match readback {
+        None if expected == DriveRateLimiter::default() => IOLimitStatus::Enforced,
+        None => IOLimitStatus::Missing,
+        Some(actual) => {
+            let differences: Vec<String> = [
+                ("bandwidth", &expected.bandwidth, &actual.bandwidth, "B/s"),
+                ("ops", &expected.ops, &actual.ops, "ops/s"),
+            ]
+            .into_iter()
+            .filter_map(|(name, expected, actual, unit)| {
+                let expected = expected.as_ref().and_then(TokenBucket::per_second);
+                let actual = actual.as_ref().and_then(TokenBucket::per_second);
+                (expected != actual).then(|| {
+                    format!(
+                        "{name}: expected {}, got {}",
+                        describe_rate(expected, unit),
+                        describe_rate(actual, unit)
+                    )
+                })
+            })
+            .collect();
+
+            if differences.is_empty() {
+                IOLimitStatus::Enforced
+            } else {
+                IOLimitStatus::Drifted { differences }
+            }
+        }
+    };
+
+    IOLimitCheck {
+        intended: intended.clone(),
+        readback: readback.cloned(),
+        status,
+    }
+}
+
+fn describe_rate(rate: Option<u64>, unit: &str) -> String {
+    match rate {
+        Some(rate) => format!("{rate} {unit}"),
+        None => "unlimited".to_string(),
+    }
+}
+
+/// Extract a drive's rate limiter from a `GET /vm/config` payload
+pub fn parse_drive_rate_limiter(
+    vm_config: &serde_json::Value,
+    drive_id: &str,
+) -> Result<Option<DriveRateLimiter>> {
+    let drive = vm_config
+        .get("drives")
+        .and_then(|drives| drives.as_array())
+        .and_then(|drives| {
+            drives
+                .iter()
+                .find(|drive| drive.get("drive_id").and_then(|id| id.as_str()) == Some(drive_id))
+        })
+        .ok_or_else(|| {
+            AivaError::SecurityError(format!("Drive {drive_id} not found in VM configuration"))
+        })?;
+
+    match drive.get("rate_limiter") {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(limiter) => Ok(Some(serde_json::from_value(limiter.clone())?)),
+    }
+}
+
+/// Outcome of comparing the policy's `open_files` with the VMM process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenFilesCheck {
+    pub intended: u32,
+    /// `None` if the limits of the process could not be read
+    pub readback: Option<OpenFilesLimit>,
+}
+
+impl OpenFilesCheck {
+    /// The process cannot open more files than the policy allows
+    pub fn is_enforced(&self) -> bool {
+        self.readback
+            .is_some_and(|limit| limit.hard <= u64::from(self.intended))
+    }
+}
+
+/// Per-VM view of which policy limits are confirmed to be in effect
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnforcementStatus {
+    pub vm_id: String,
+    pub policy: String,
+    pub io_limit: Option<IOLimitCheck>,
+    #[serde(default)]
+    pub open_files: Option<OpenFilesCheck>,
+}
@@ -1,7 +1,15 @@
+#[cfg(target_os = "linux")]
+use crate::cgroup::VmCgroup;
+use crate::enforcement::{
+    DriveRateLimiter, EnforcementStatus, IOLimitCheck, OpenFilesCheck, verify_io_limit,
+};
+use crate::network::{NetworkPolicyPlan, plan_network_policy};
+use crate::rlimit::OpenFilesLimit;
 use crate::{IsolationLevel, SecurityManager, SecurityPolicy};
-use aiva_core::{AivaError, Result};
+use aiva_core::{AivaError, NetworkConfig, Result};
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
@@ -9,10 +17,24 @@ use tracing::{debug, info, warn};
 pub struct IsolationManager {
     policies: Arc<RwLock<HashMap<String, SecurityPolicy>>>,
     vm_policies: Arc<RwLock<HashMap<String, String>>>, // vm_id -> policy_name
+    enforcement: Arc<RwLock<HashMap<String, IOLimitCheck>>>, // vm_id -> last IO readback
+    open_files: Arc<RwLock<HashMap<String, OpenFilesCheck>>>, // vm_id -> last rlimit readback
+    vm_networks: Arc<RwLock<HashMap<String, NetworkConfig>>>,
+    network_plans: Arc<RwLock<HashMap<String, NetworkPolicyPlan>>>,
+    vm_processes: Arc<RwLock<HashMap<String, VmProcess>>>,
     #[cfg(target_os = "linux")]
     linux_isolation: Option<LinuxIsolation>,
 }
 
+/// The VMM process of a VM, whose cgroup enforces the resource limits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmProcess {
+    pub pid: u32,
+    pub vcpus: u32,
+    /// Image the VM reads and writes; IO is limited on the disk holding it
+    pub disk: Option<PathBuf>,
+}
+
 impl IsolationManager {
     pub fn new() -> Result<Self> {
         let mut policies = HashMap::new();
@@ -25,11 +47,25 @@ impl IsolationManager {
         Ok(Self {
             policies: Arc::new(RwLock::new(policies)),
             vm_policies: Arc::new(RwLock::new(HashMap::new())),
+            enforcement: Arc::new(RwLock::new(HashMap::new())),
+            open_files: Arc::new(RwLock::new(HashMap::new())),
+            vm_networks: Arc::new(RwLock::new(HashMap::new())),
+            network_plans: Arc::new(RwLock::new(HashMap::new())),
+            vm_processes: Arc::new(RwLock::new(HashMap::new())),
             #[cfg(target_os = "linux")]
             linux_isolation: Some(LinuxIsolation::new()?),
         })
     }
 
+    /// Create VM cgroups below `root` instead of `/sys/fs/cgroup`
+    #[cfg(target_os = "linux")]
+    pub fn with_cgroup_root(mut self, root: impl Into<PathBuf>) -> Self {
+        if let Some(linux_isolation) = &mut self.linux_isolation {
+            linux_isolation.cgroup_root = root.into();
+        }
+        self
+    }
+
     pub async fn add_policy(&self, policy: SecurityPolicy) -> Result<()> {
         self.validate_policy(&policy).await?;
         self.policies
@@ -79,6 +115,95 @@ impl IsolationManager {
             .cloned()
             .ok_or_else(|| AivaError::SecurityError(format!("No policy assigned to VM {vm_id}")))
     }
+
+    /// Tell the manager which network a VM sits on so network policies never
+    /// cut it off from its own gateway
+    pub async fn register_vm_network(&self, vm_id: &str, network: NetworkConfig) {
+        self.vm_networks
+            .write()
+            .await
+            .insert(vm_id.to_string(), network);
+    }
+
+    /// Tell the manager which process runs a VM so its resource limits can
+    /// be enforced through a cgroup
+    pub async fn register_vm_process(&self, vm_id: &str, process: VmProcess) {
+        self.vm_processes
+            .write()
+            .await
+            .insert(vm_id.to_string(), process);
+    }
+
+    /// The network filtering last applied to a VM
+    pub async fn get_network_plan(&self, vm_id: &str) -> Option<NetworkPolicyPlan> {
+        self.network_plans.read().await.get(vm_id).cloned()
+    }
+
+    /// Compare the drive rate limiter read back from the VMM with the VM's
+    /// effective policy and remember the result for `get_enforcement_status`
+    pub async fn record_io_readback(
+        &self,
+        vm_id: &str,
+        readback: Option<&DriveRateLimiter>,
+    ) -> Result<Option<IOLimitCheck>> {
+        let policy = self.get_effective_policy(vm_id).await?;
+        let Some(intended) = &policy.resource_limits.io_bandwidth else {
+            self.enforcement.write().await.remove(vm_id);
+            return Ok(None);
+        };
+
+        let check = verify_io_limit(intended, readback);
+        if !check.is_enforced() {
+            warn!(
+                "IO limits of policy {} are not in effect for VM {}: {:?}",
+                policy.name, vm_id, check.status
+            );
+        }
+
+        self.enforcement
+            .write()
+            .await
+            .insert(vm_id.to_string(), check.clone());
+        Ok(Some(check))
+    }
+
+    /// Compare the open files limit read back from the VMM process with the
+    /// VM's effective policy and remember the result for
+    /// `get_enforcement_status`
+    pub async fn record_open_files_readback(
+        &self,
+        vm_id: &str,
+        readback: Option<OpenFilesLimit>,
+    ) -> Result<Option<OpenFilesCheck>> {
+        let policy = self.get_effective_policy(vm_id).await?;
+        let Some(intended) = policy.resource_limits.open_files else {
+            self.open_files.write().await.remove(vm_id);
+            return Ok(None);
+        };
+
+        let check = OpenFilesCheck { intended, readback };
+        if !check.is_enforced() {
+            warn!(
+                "Open files limit of policy {} is not in effect for VM {}: {:?}",
+                policy.name, vm_id, check.readback
+            );
+        }
+
+        self.open_files
+            .write()
+            .await
+            .insert(vm_id.to_string(), check.clone());
+        Ok(Some(check))
+    }
+
+    pub async fn get_enforcement_status(&self, vm_id: &str) -> Result<EnforcementStatus> {
+        Ok(EnforcementStatus {
+            vm_id: vm_id.to_string(),
+            policy: self.get_vm_policy(vm_id).await?,
+            io_limit: self.enforcement.read().await.get(vm_id).cloned(),
+            open_files: self.open_files.read().await.get(vm_id).cloned(),
+        })
+    }
 }
 
 #[async_trait]
@@ -109,28 +234,11 @@ impl SecurityManager for IsolationManager {
     }
 
     async fn validate_policy(&self, policy: &SecurityPolicy) -> Result<()> {
-        // Validate policy consistency
-        if policy.name.is_empty() {
-            return Err(AivaError::SecurityError(
-                "Policy name cannot be empty".to_string(),
-            ));
-        }
+        policy.validate()?;
 
-        // Validate resource limits
-        if let Some(cpu_quota) = policy.resource_limits.cpu_quota {
-            if cpu_quota == 0 || cpu_quota > 100 {
-                return Err(AivaError::SecurityError(
-                    "CPU quota must be between 1 and 100".to_string(),
-                ));
-            }
-        }
-
-        // Validate syscall filter
+        #[cfg(target_os = "linux")]
         if let Some(filter) = &policy.syscall_filter {
-            if filter.rules.is_empty() && matches!(filter.default_action, crate::FilterAction::Kill)
-            {
-                warn!("Policy {} denies all syscalls by default", policy.name);
-            }
+            crate::seccomp::compile_filter(filter, crate::seccomp::host_arch()?)?;
         }
 
         Ok(())
@@ -138,7 +246,9 @@ impl SecurityManager for IsolationManager {
 
     async fn get_effective_policy(&self, vm_id: &str) -> Result<SecurityPolicy> {
         let policy_name = self.get_vm_policy(vm_id).await?;
-        self.get_policy(&policy_name).await
+        let mut policy = self.get_policy(&policy_name).await?;
+        policy.capabilities = policy.capabilities.expanded();
+        Ok(policy)
     }
 }
 
@@ -212,6 +322,37 @@ impl IsolationManager {
             debug!("Setting PIDs limit to {} for VM {}", pids_limit, vm_id);
         }
 
+        // IO limits are enforced by the VMM's drive rate limiter; callers
+        // confirm them afterwards through `record_io_readback`
+        if let Some(io_limit) = &limits.io_bandwidth {
+            debug!(
+                "Expecting drive rate limiter {:?} for VM {}",
+                io_limit.to_rate_limiter(),
+                vm_id
+            );
+        }
+
+        let Some(process) = self.vm_processes.read().await.get(vm_id).cloned() else {
+            debug!(
+                "No process registered for VM {}, not creating a cgroup",
+                vm_id
+            );
+            return Ok(());
+        };
+
+        #[cfg(target_os = "linux")]
+        if let Some(linux_isolation) = &self.linux_isolation {
+            linux_isolation.apply_cgroup_limits(vm_id, limits, &process)?;
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            warn!(
+                "cgroups not available on this platform, process {} of VM {} is not limited",
+                process.pid, vm_id
+            );
+        }
+
         Ok(())
     }
 
@@ -222,7 +363,7 @@ impl IsolationManager {
     ) -> Result<()> {
         debug!("Applying capability restrictions to VM {}", vm_id);
 
-        for cap in &capabilities.denied {
+        for cap in capabilities.expanded().denied {
             debug!("Denying capability {} for VM {}", cap, vm_id);
         }
 
@@ -256,10 +397,29 @@ impl IsolationManager {
             debug!("Blocking outbound connections for VM {}", vm_id);
         }
 
-        for ip in &policy.blocked_ips {
+        let network = self.vm_networks.read().await.get(vm_id).cloned();
+        let plan = plan_network_policy(policy, network.as_ref())?;
+
+        for conflict in &plan.conflicts {
+            warn!(
+                "Blocked range {} for VM {}; keeping it reachable",
+                conflict, vm_id
+            );
+        }
+
+        for ip in &plan.always_allowed {
+            debug!("Always allowing connections to {} for VM {}", ip, vm_id);
+        }
+
+        for ip in &plan.blocked {
             debug!("Blocking connections to {} for VM {}", ip, vm_id);
         }
 
+        self.network_plans
+            .write()
+            .await
+            .insert(vm_id.to_string(), plan);
+
         if let Some(rate_limit) = &policy.rate_limit {
             debug!(
                 "Setting bandwidth limit to {} Mbps for VM {}",
@@ -284,13 +444,50 @@ impl IsolationLevel {
 
 #[cfg(target_os = "linux")]
 struct LinuxIsolation {
-    // Linux-specific isolation implementation
+    cgroup_root: PathBuf,
 }
 
 #[cfg(target_os = "linux")]
 impl LinuxIsolation {
     fn new() -> Result<Self> {
-        Ok(Self {})
+        Ok(Self {
+            cgroup_root: PathBuf::from(crate::cgroup::CGROUP_ROOT),
+        })
+    }
+
+    /// Move the VM's process into a cgroup of its own enforcing `limits`
+    fn apply_cgroup_limits(
+        &self,
+        vm_id: &str,
+        limits: &crate::ResourceLimits,
+        process: &VmProcess,
+    ) -> Result<()> {
+        use caps::{CapSet, Capability};
+
+        if !caps::has_cap(None, CapSet::Effective, Capability::CAP_SYS_ADMIN).unwrap_or(false) {
+            return Err(AivaError::PlatformError {
+                platform: "linux".to_string(),
+                message: "Managing cgroups needs CAP_SYS_ADMIN".to_string(),
+                recoverable: true,
+            });
+        }
+
+        let io_device = match (&limits.io_bandwidth, &process.disk) {
+            (Some(_), Some(disk)) => crate::cgroup::block_device_of(disk)?,
+            _ => None,
+        };
+        let settings = limits.cgroup_settings(process.vcpus, io_device.as_deref())?;
+
+        let cgroup = VmCgroup::create(&self.cgroup_root, vm_id)?;
+        cgroup.apply(&settings)?;
+        cgroup.add_process(process.pid)?;
+        info!(
+            "Moved process {} of VM {} into {}",
+            process.pid,
+            vm_id,
+            cgroup.path().display()
+        );
+        Ok(())
     }
 
     async fn apply_maximum_restrictions(&self, vm_id: &str) -> Result<()> {
@@ -308,15 +505,17 @@ impl LinuxIsolation {
         Ok(())
     }
 
-    async fn apply_seccomp_filter(
-        &self,
-        vm_id: &str,
-        _filter: &crate::SyscallFilter,
-    ) -> Result<()> {
+    async fn apply_seccomp_filter(&self, vm_id: &str, filter: &crate::SyscallFilter) -> Result<()> {
         debug!("Applying seccomp filter to VM {}", vm_id);
 
-        // This would use the seccompiler crate to create and apply BPF filters
-        // For now, we just log the intent
+        // Firecracker installs the filter itself from the file the platform
+        // writes at spawn; compiling it here rejects a filter it could not load
+        let program = crate::seccomp::compile_filter(filter, crate::seccomp::host_arch()?)?;
+        debug!(
+            "Compiled seccomp filter of {} instructions for VM {}",
+            program.len(),
+            vm_id
+        );
 
         Ok(())
     }
@@ -1,5 +1,19 @@
+pub mod audit;
+pub mod builder;
+pub mod capabilities;
+pub mod cgroup;
+pub mod enforcement;
 pub mod isolation;
+pub mod network;
+pub mod oci;
 pub mod policy;
+pub mod rlimit;
+#[cfg(target_os = "linux")]
+pub mod seccomp;
+pub mod validation;
+
+#[cfg(test)]
+mod tests;
 
 use aiva_core::Result;
 use async_trait::async_trait;
@@ -13,9 +27,12 @@ pub trait SecurityManager: Send + Sync {
     async fn get_effective_policy(&self, vm_id: &str) -> Result<SecurityPolicy>;
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SecurityPolicy {
     pub name: String,
+    /// Policy this one inherits every field from that it does not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
     pub isolation_level: IsolationLevel,
     pub capabilities: CapabilitySet,
     pub syscall_filter: Option<SyscallFilter>,
@@ -23,7 +40,7 @@ pub struct SecurityPolicy {
     pub network_policy: NetworkPolicy,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IsolationLevel {
     None,
     Basic,
@@ -31,19 +48,19 @@ pub enum IsolationLevel {
     Maximum,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CapabilitySet {
     pub allowed: Vec<String>,
     pub denied: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyscallFilter {
     pub default_action: FilterAction,
     pub rules: Vec<SyscallRule>,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FilterAction {
     Allow,
     Kill,
@@ -51,21 +68,21 @@ pub enum FilterAction {
     Log,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyscallRule {
     pub syscall: String,
     pub action: FilterAction,
     pub conditions: Option<Vec<Condition>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Condition {
     pub arg_index: u32,
     pub operation: CompareOp,
     pub value: u64,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CompareOp {
     Equal,
     NotEqual,
@@ -76,7 +93,7 @@ pub enum CompareOp {
     MaskedEqual(u64),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ResourceLimits {
     pub cpu_quota: Option<u32>,
     pub memory_limit: Option<u64>,
@@ -85,7 +102,7 @@ pub struct ResourceLimits {
     pub io_bandwidth: Option<IOLimit>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct IOLimit {
     pub read_bps: Option<u64>,
     pub write_bps: Option<u64>,
@@ -93,7 +110,7 @@ pub struct IOLimit {
     pub write_iops: Option<u64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NetworkPolicy {
     pub allow_outbound: bool,
     pub allowed_ports: Vec<PortRule>,
@@ -101,21 +118,21 @@ pub struct NetworkPolicy {
     pub rate_limit: Option<NetworkRateLimit>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PortRule {
     pub port: u16,
     pub protocol: String,
     pub direction: Direction,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     Inbound,
     Outbound,
     Both,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct NetworkRateLimit {
     pub bandwidth_mbps: u32,
     pub connections_per_second: u32,
@@ -125,6 +142,7 @@ impl Default for SecurityPolicy {
     fn default() -> Self {
         Self {
             name: "default".to_string(),
+            extends: None,
             isolation_level: IsolationLevel::Basic,
             capabilities: CapabilitySet {
                 allowed: vec![],
@@ -168,6 +186,7 @@ pub fn load_preset_policies() -> HashMap<String, SecurityPolicy> {
         "trusted".to_string(),
         SecurityPolicy {
             name: "trusted".to_string(),
+            extends: None,
             isolation_level: IsolationLevel::None,
             capabilities: CapabilitySet {
                 allowed: vec![],
@@ -198,6 +217,7 @@ pub fn load_preset_policies() -> HashMap<String, SecurityPolicy> {
         "restricted".to_string(),
         SecurityPolicy {
             name: "restricted".to_string(),
+            extends: None,
             isolation_level: IsolationLevel::Enhanced,
             capabilities: CapabilitySet {
                 allowed: vec![],
@@ -262,11 +282,12 @@ pub fn load_preset_policies() -> HashMap<String, SecurityPolicy> {
         "isolated".to_string(),
         SecurityPolicy {
             name: "isolated".to_string(),
+            extends: None,
             isolation_level: IsolationLevel::Maximum,
             capabilities: CapabilitySet {
                 allowed: vec![],
                 denied: vec![
-                    "ALL".to_string(), // Deny all capabilities
+                    ALL_CAPABILITIES.to_string(), // Deny all capabilities
                 ],
             },
             syscall_filter: Some(SyscallFilter {
@@ -323,5 +344,23 @@ pub fn load_preset_policies() -> HashMap<String, SecurityPolicy> {
     policies
 }
 
-pub use isolation::IsolationManager;
-pub use policy::PolicyManager;
+pub use audit::{SeccompEvent, SeccompMonitor};
+pub use builder::SecurityPolicyBuilder;
+pub use capabilities::{ALL_CAPABILITIES, LINUX_CAPABILITIES};
+pub use cgroup::{CGROUP_CPU_PERIOD_US, CGROUP_ROOT, VmCgroup, cgroup_v2_mounted};
+pub use enforcement::{
+    DriveRateLimiter, EnforcementStatus, IOLimitCheck, IOLimitStatus, OpenFilesCheck, TokenBucket,
+    parse_drive_rate_limiter, verify_io_limit,
+};
+pub use isolation::{IsolationManager, VmProcess};
+pub use network::{IpRange, NetworkPolicyPlan, plan_network_policy};
+pub use policy::{PolicyManager, merge_policy, resolve_extends, resolve_policy};
+pub use rlimit::{OpenFilesLimit, parse_open_files_limit};
+#[cfg(target_os = "linux")]
+pub use seccomp::{
+    SECCOMP_FILTER_FILE, compile_filter, host_arch, replaces_firecracker_filter, syscall_number,
+    write_firecracker_filter,
+};
+#[cfg(target_os = "linux")]
+pub use seccompiler::{BpfProgram, TargetArch};
+pub use validation::validate_cache_strategy;
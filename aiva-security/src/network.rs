@@ -0,0 +1,141 @@
+//! Turns a `NetworkPolicy` into the concrete block/allow sets for one VM.
+//!
+//! Blocked ranges are written without knowing which subnet a VM ends up on, so
+//! a broad range can swallow the VM's own gateway or the loopback services an
+//! MCP server talks to. Those addresses are always exempted.
+
+use crate::NetworkPolicy;
+use aiva_core::{AivaError, NetworkConfig, Result};
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// An IPv4 or IPv6 range in CIDR notation; a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    pub fn parse(value: &str) -> Result<Self> {
+        let invalid = || AivaError::SecurityError(format!("Invalid IP range: {value}"));
+
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix.parse::<u8>().map_err(|_| invalid())?)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr.trim().parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        if prefix > max {
+            return Err(invalid());
+        }
+
+        Ok(Self { addr, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Protocols a `PortRule` can name
+const PORT_PROTOCOLS: &[&str] = &["tcp", "udp"];
+
+impl NetworkPolicy {
+    /// Reject blocked ranges that are not an address or CIDR range and port
+    /// rules for protocols other than `tcp` and `udp`
+    pub fn validate(&self) -> Result<()> {
+        for blocked in &self.blocked_ips {
+            IpRange::parse(blocked)?;
+        }
+        for rule in &self.allowed_ports {
+            if !PORT_PROTOCOLS.contains(&rule.protocol.as_str()) {
+                return Err(AivaError::SecurityError(format!(
+                    "Invalid protocol '{}' for port {}, expected one of: {}",
+                    rule.protocol,
+                    rule.port,
+                    PORT_PROTOCOLS.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Effective network filtering for a VM
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetworkPolicyPlan {
+    /// Ranges to block, as written in the policy
+    pub blocked: Vec<String>,
+    /// Addresses permitted ahead of the blocked ranges
+    pub always_allowed: Vec<IpAddr>,
+    /// Blocked ranges that would have cut off an always-allowed address
+    pub conflicts: Vec<String>,
+}
+
+impl NetworkPolicyPlan {
+    /// Whether traffic to `ip` gets through the policy's IP blocks
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        if self.always_allowed.contains(&ip) {
+            return true;
+        }
+        !self
+            .blocked
+            .iter()
+            .filter_map(|range| IpRange::parse(range).ok())
+            .any(|range| range.contains(ip))
+    }
+}
+
+/// Addresses a VM must always reach: loopback plus its host side and gateway
+pub fn essential_addresses(network: Option<&NetworkConfig>) -> Vec<IpAddr> {
+    let mut addresses = vec![
+        IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(Ipv6Addr::LOCALHOST),
+    ];
+
+    if let Some(network) = network {
+        for ip in [&network.host_ip, &network.gateway] {
+            if let Ok(ip) = ip.parse::<IpAddr>()
+                && !addresses.contains(&ip)
+            {
+                addresses.push(ip);
+            }
+        }
+    }
+
+    addresses
+}
+
+/// Resolve `policy.blocked_ips` against the addresses that must stay reachable
+pub fn plan_network_policy(
+    policy: &NetworkPolicy,
+    network: Option<&NetworkConfig>,
+) -> Result<NetworkPolicyPlan> {
+    let always_allowed = essential_addresses(network);
+    let mut conflicts = Vec::new();
+
+    for blocked in &policy.blocked_ips {
+        let range = IpRange::parse(blocked)?;
+        for ip in always_allowed.iter().filter(|ip| range.contains(**ip)) {
+            conflicts.push(format!("{blocked} covers {ip}"));
+        }
+    }
+
+    Ok(NetworkPolicyPlan {
+        blocked: policy.blocked_ips.clone(),
+        always_allowed,
+        conflicts,
+    })
+}
@@ -0,0 +1,192 @@
+//! Conversion between syscall filters and OCI seccomp profiles.
+//!
+//! runc, crun and podman take seccomp profiles in the `linux.seccomp` format
+//! of the OCI runtime spec, which is also what Docker's `--security-opt
+//! seccomp=` reads. Exporting a policy's filter in that format lets the same
+//! rules confine a container, and importing one turns an existing profile
+//! into an aiva policy.
+//!
+//! Consecutive rules with the same action and conditions are exported as one
+//! entry listing all their syscalls, the way profiles are usually written, so
+//! an imported profile exports unchanged. A masked comparison puts the mask
+//! in `value` and the value compared with in `valueTwo`, the order libseccomp
+//! takes them in.
+
+use crate::{CompareOp, Condition, FilterAction, SecurityPolicy, SyscallFilter, SyscallRule};
+use aiva_core::{AivaError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OciProfile {
+    default_action: String,
+    #[serde(default)]
+    syscalls: Vec<OciSyscall>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct OciSyscall {
+    names: Vec<String>,
+    action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    args: Option<Vec<OciArg>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OciArg {
+    index: u32,
+    value: u64,
+    #[serde(default)]
+    value_two: u64,
+    op: String,
+}
+
+impl SecurityPolicy {
+    /// The syscall filter as an OCI seccomp profile. A policy without a
+    /// filter allows every syscall.
+    pub fn to_oci_seccomp(&self) -> Value {
+        match &self.syscall_filter {
+            Some(filter) => filter.to_oci_seccomp(),
+            None => SyscallFilter {
+                default_action: FilterAction::Allow,
+                rules: Vec::new(),
+            }
+            .to_oci_seccomp(),
+        }
+    }
+
+    /// The default policy named `name`, filtering syscalls as the OCI
+    /// seccomp profile `profile` does
+    pub fn from_oci_seccomp(name: &str, profile: &Value) -> Result<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            syscall_filter: Some(SyscallFilter::from_oci_seccomp(profile)?),
+            ..Self::default()
+        })
+    }
+}
+
+impl SyscallFilter {
+    /// This filter as an OCI seccomp profile
+    pub fn to_oci_seccomp(&self) -> Value {
+        let mut syscalls: Vec<OciSyscall> = Vec::new();
+        for rule in &self.rules {
+            let action = oci_action(rule.action);
+            let args = rule
+                .conditions
+                .as_ref()
+                .map(|conditions| conditions.iter().map(oci_arg).collect::<Vec<_>>());
+            if let Some(last) = syscalls.last_mut()
+                && last.action == action
+                && last.args == args
+            {
+                last.names.push(rule.syscall.clone());
+                continue;
+            }
+            syscalls.push(OciSyscall {
+                names: vec![rule.syscall.clone()],
+                action: action.to_string(),
+                args,
+            });
+        }
+
+        serde_json::to_value(OciProfile {
+            default_action: oci_action(self.default_action).to_string(),
+            syscalls,
+        })
+        .expect("OCI profiles serialize to JSON")
+    }
+
+    /// Read the filter of an OCI seccomp profile. Actions aiva cannot take,
+    /// such as returning an errno, are rejected.
+    pub fn from_oci_seccomp(profile: &Value) -> Result<Self> {
+        let profile: OciProfile = serde_json::from_value(profile.clone())
+            .map_err(|e| AivaError::SecurityError(format!("Invalid OCI seccomp profile: {e}")))?;
+
+        let mut rules = Vec::new();
+        for syscall in profile.syscalls {
+            let action = filter_action(&syscall.action)?;
+            let conditions = syscall
+                .args
+                .map(|args| args.iter().map(condition).collect::<Result<Vec<_>>>())
+                .transpose()?;
+            for name in syscall.names {
+                rules.push(SyscallRule {
+                    syscall: name,
+                    action,
+                    conditions: conditions.clone(),
+                });
+            }
+        }
+
+        Ok(Self {
+            default_action: filter_action(&profile.default_action)?,
+            rules,
+        })
+    }
+}
+
+fn oci_action(action: FilterAction) -> &'static str {
+    match action {
+        FilterAction::Allow => "SCMP_ACT_ALLOW",
+        FilterAction::Kill => "SCMP_ACT_KILL_PROCESS",
+        FilterAction::Trap => "SCMP_ACT_TRAP",
+        FilterAction::Log => "SCMP_ACT_LOG",
+    }
+}
+
+fn filter_action(action: &str) -> Result<FilterAction> {
+    match action {
+        "SCMP_ACT_ALLOW" => Ok(FilterAction::Allow),
+        "SCMP_ACT_KILL" | "SCMP_ACT_KILL_THREAD" | "SCMP_ACT_KILL_PROCESS" => {
+            Ok(FilterAction::Kill)
+        }
+        "SCMP_ACT_TRAP" => Ok(FilterAction::Trap),
+        "SCMP_ACT_LOG" => Ok(FilterAction::Log),
+        _ => Err(AivaError::SecurityError(format!(
+            "Unsupported seccomp action: {action}"
+        ))),
+    }
+}
+
+fn oci_arg(condition: &Condition) -> OciArg {
+    let (op, value, value_two) = match condition.operation {
+        CompareOp::Equal => ("SCMP_CMP_EQ", condition.value, 0),
+        CompareOp::NotEqual => ("SCMP_CMP_NE", condition.value, 0),
+        CompareOp::Greater => ("SCMP_CMP_GT", condition.value, 0),
+        CompareOp::GreaterEqual => ("SCMP_CMP_GE", condition.value, 0),
+        CompareOp::Less => ("SCMP_CMP_LT", condition.value, 0),
+        CompareOp::LessEqual => ("SCMP_CMP_LE", condition.value, 0),
+        CompareOp::MaskedEqual(mask) => ("SCMP_CMP_MASKED_EQ", mask, condition.value),
+    };
+    OciArg {
+        index: condition.arg_index,
+        value,
+        value_two,
+        op: op.to_string(),
+    }
+}
+
+fn condition(arg: &OciArg) -> Result<Condition> {
+    let (operation, value) = match arg.op.as_str() {
+        "SCMP_CMP_EQ" => (CompareOp::Equal, arg.value),
+        "SCMP_CMP_NE" => (CompareOp::NotEqual, arg.value),
+        "SCMP_CMP_GT" => (CompareOp::Greater, arg.value),
+        "SCMP_CMP_GE" => (CompareOp::GreaterEqual, arg.value),
+        "SCMP_CMP_LT" => (CompareOp::Less, arg.value),
+        "SCMP_CMP_LE" => (CompareOp::LessEqual, arg.value),
+        "SCMP_CMP_MASKED_EQ" => (CompareOp::MaskedEqual(arg.value), arg.value_two),
+        op => {
+            return Err(AivaError::SecurityError(format!(
+                "Unsupported seccomp comparison: {op}"
+            )));
+        }
+    };
+    Ok(Condition {
+        arg_index: arg.index,
+        operation,
+        value,
+    })
+}
@@ -1,21 +1,52 @@
-use crate::{CapabilitySet, IsolationLevel, NetworkPolicy, ResourceLimits, SecurityPolicy};
+use crate::{
+    CapabilitySet, FilterAction, IOLimit, IsolationLevel, NetworkPolicy, NetworkRateLimit,
+    PortRule, ResourceLimits, SecurityPolicy, SyscallFilter, SyscallRule,
+};
 use aiva_core::{AivaError, Result};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde_json;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use tokio::fs;
 use tracing::{debug, info, warn};
 
+/// Look up a policy by name, preferring user-defined policies in
+/// `policies_dir` over the presets
+pub async fn resolve_policy(name: &str, policies_dir: &Path) -> Result<SecurityPolicy> {
+    if policies_dir.exists() {
+        let mut manager = PolicyManager::new(policies_dir.to_path_buf())?;
+        manager.load_policies().await?;
+        if let Ok(policy) = manager.get_policy(name) {
+            return Ok(policy);
+        }
+    }
+
+    crate::load_preset_policies()
+        .remove(name)
+        .ok_or_else(|| AivaError::SecurityError(format!("Policy {name} not found")))
+}
+
 pub struct PolicyManager {
     policies_dir: PathBuf,
+    // Behind a std lock because `watch` updates it from notify's thread
+    loaded: Arc<RwLock<LoadedPolicies>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+/// The policies of a [`PolicyManager`], and the file each came from
+#[derive(Default)]
+struct LoadedPolicies {
     policies: HashMap<String, SecurityPolicy>,
+    files: HashMap<PathBuf, String>,
 }
 
 impl PolicyManager {
     pub fn new(policies_dir: PathBuf) -> Result<Self> {
         Ok(Self {
             policies_dir,
-            policies: HashMap::new(),
+            loaded: Arc::new(RwLock::new(LoadedPolicies::default())),
+            watcher: Mutex::new(None),
         })
     }
 
@@ -24,7 +55,7 @@ impl PolicyManager {
         self.load_policies().await?;
 
         // Create default policies if none exist
-        if self.policies.is_empty() {
+        if self.read().policies.is_empty() {
             self.create_default_policies().await?;
         }
 
@@ -34,12 +65,17 @@ impl PolicyManager {
     pub async fn load_policies(&mut self) -> Result<()> {
         let mut entries = fs::read_dir(&self.policies_dir).await?;
 
+        let mut documents = HashMap::new();
         while let Some(entry) = entries.next_entry().await? {
             let path = entry.path();
-            if path.extension().and_then(|e| e.to_str()) == Some("json") {
-                match self.load_policy_from_file(&path).await {
-                    Ok(policy) => {
-                        self.policies.insert(policy.name.clone(), policy);
+            if is_policy_file(&path) {
+                let document = fs::read_to_string(&path)
+                    .await
+                    .map_err(AivaError::from)
+                    .and_then(|content| policy_document(&content));
+                match document {
+                    Ok((name, document)) => {
+                        documents.insert(name, (path, document));
                     }
                     Err(e) => {
                         warn!("Failed to load policy from {:?}: {}", path, e);
@@ -48,13 +84,55 @@ impl PolicyManager {
             }
         }
 
-        info!("Loaded {} security policies", self.policies.len());
+        let sources: HashMap<String, serde_json::Value> = documents
+            .iter()
+            .map(|(name, (_, document))| (name.clone(), document.clone()))
+            .collect();
+        let mut loaded = self.write();
+        for (name, (path, _)) in &documents {
+            match resolve_extends(name, &sources) {
+                Ok(policy) => {
+                    debug!("Loaded policy {} from {:?}", policy.name, path);
+                    loaded.insert(path.clone(), policy);
+                }
+                Err(e) => {
+                    warn!("Failed to load policy from {:?}: {}", path, e);
+                }
+            }
+        }
+
+        info!("Loaded {} security policies", loaded.policies.len());
+        Ok(())
+    }
+
+    /// Keep the loaded policies in step with `policies_dir` until the manager
+    /// is dropped: a policy file that is created or modified is loaded again,
+    /// one that is deleted is forgotten. A file that fails to load keeps the
+    /// policy it held before.
+    pub fn watch(&self) -> Result<()> {
+        let dir = self.policies_dir.clone();
+        let loaded = Arc::clone(&self.loaded);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+                Ok(event) => {
+                    for path in event.paths.iter().filter(|path| is_policy_file(path)) {
+                        reload_policy_file(&dir, path, &loaded);
+                    }
+                }
+                Err(e) => warn!("Failed to watch policies in {:?}: {}", dir, e),
+            })
+            .map_err(watch_error)?;
+        watcher
+            .watch(&self.policies_dir, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+
+        *self.watcher.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher);
+        info!("Watching {:?} for policy changes", self.policies_dir);
         Ok(())
     }
 
     pub async fn save_policy(&self, policy: &SecurityPolicy) -> Result<()> {
-        let filename = format!("{}.json", policy.name);
-        let path = self.policies_dir.join(filename);
+        let path = self.policy_path(&policy.name);
 
         let content = serde_json::to_string_pretty(policy)?;
         fs::write(&path, content).await?;
@@ -64,41 +142,46 @@ impl PolicyManager {
     }
 
     pub async fn delete_policy(&mut self, name: &str) -> Result<()> {
-        if !self.policies.contains_key(name) {
+        if !self.read().policies.contains_key(name) {
             return Err(AivaError::SecurityError(format!("Policy {name} not found")));
         }
 
-        let filename = format!("{name}.json");
-        let path = self.policies_dir.join(filename);
+        let path = self.policy_path(name);
 
         if path.exists() {
             fs::remove_file(&path).await?;
         }
 
-        self.policies.remove(name);
+        let mut loaded = self.write();
+        loaded.policies.remove(name);
+        loaded.files.remove(&path);
+        drop(loaded);
         info!("Deleted policy {}", name);
         Ok(())
     }
 
-    pub fn get_policy(&self, name: &str) -> Result<&SecurityPolicy> {
-        self.policies
+    pub fn get_policy(&self, name: &str) -> Result<SecurityPolicy> {
+        self.read()
+            .policies
             .get(name)
+            .cloned()
             .ok_or_else(|| AivaError::SecurityError(format!("Policy {name} not found")))
     }
 
     pub fn list_policies(&self) -> Vec<String> {
-        self.policies.keys().cloned().collect()
+        self.read().policies.keys().cloned().collect()
     }
 
     pub async fn create_policy(&mut self, policy: SecurityPolicy) -> Result<()> {
         self.validate_policy(&policy)?;
         self.save_policy(&policy).await?;
-        self.policies.insert(policy.name.clone(), policy);
+        let path = self.policy_path(&policy.name);
+        self.write().insert(path, policy);
         Ok(())
     }
 
     pub async fn update_policy(&mut self, policy: SecurityPolicy) -> Result<()> {
-        if !self.policies.contains_key(&policy.name) {
+        if !self.read().policies.contains_key(&policy.name) {
             return Err(AivaError::SecurityError(format!(
                 "Policy {} not found",
                 policy.name
@@ -107,139 +190,180 @@ impl PolicyManager {
 
         self.validate_policy(&policy)?;
         self.save_policy(&policy).await?;
-        self.policies.insert(policy.name.clone(), policy);
+        let path = self.policy_path(&policy.name);
+        self.write().insert(path, policy);
         Ok(())
     }
 
     pub fn validate_policy(&self, policy: &SecurityPolicy) -> Result<()> {
-        // Validate policy name
-        if policy.name.is_empty() {
-            return Err(AivaError::SecurityError(
-                "Policy name cannot be empty".to_string(),
-            ));
-        }
+        policy.validate()
+    }
 
-        // Validate resource limits
-        if let Some(cpu_quota) = policy.resource_limits.cpu_quota {
-            if cpu_quota == 0 || cpu_quota > 100 {
-                return Err(AivaError::SecurityError(
-                    "CPU quota must be between 1 and 100".to_string(),
-                ));
-            }
+    /// Merge the stored policies `base` and `overlay`, see [`merge_policy`]
+    pub fn merge_policies(&self, base: &str, overlay: &str) -> Result<SecurityPolicy> {
+        if base == overlay {
+            return Err(AivaError::SecurityError(format!(
+                "Cannot merge policy {base} with itself"
+            )));
         }
 
-        if let Some(memory_limit) = policy.resource_limits.memory_limit {
-            if memory_limit == 0 {
-                return Err(AivaError::SecurityError(
-                    "Memory limit must be greater than 0".to_string(),
-                ));
-            }
-        }
+        let base_policy = self.get_policy(base)?;
+        let overlay_policy = self.get_policy(overlay)?;
+        Ok(merge_policy(&base_policy, &overlay_policy))
+    }
 
-        // Validate capabilities
-        for cap in &policy.capabilities.denied {
-            if cap == "ALL" && !policy.capabilities.allowed.is_empty() {
-                return Err(AivaError::SecurityError(
-                    "Cannot allow capabilities when ALL is denied".to_string(),
-                ));
-            }
-        }
+    async fn create_default_policies(&mut self) -> Result<()> {
+        info!("Creating default security policies");
 
-        // Validate syscall filter
-        if let Some(filter) = &policy.syscall_filter {
-            if filter.rules.is_empty() && matches!(filter.default_action, crate::FilterAction::Kill)
-            {
-                warn!("Policy {} denies all syscalls by default", policy.name);
-            }
+        for policy in crate::load_preset_policies().into_values() {
+            self.save_policy(&policy).await?;
+            let path = self.policy_path(&policy.name);
+            self.write().insert(path, policy);
         }
 
         Ok(())
     }
 
-    pub fn merge_policies(&self, base: &str, overlay: &str) -> Result<SecurityPolicy> {
-        let base_policy = self.get_policy(base)?;
-        let overlay_policy = self.get_policy(overlay)?;
+    fn policy_path(&self, name: &str) -> PathBuf {
+        self.policies_dir.join(format!("{name}.json"))
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, LoadedPolicies> {
+        // Every update leaves the maps consistent, so a panic elsewhere while
+        // holding the lock does not make them unusable
+        self.loaded.read().unwrap_or_else(|e| e.into_inner())
+    }
 
-        let mut merged = base_policy.clone();
-        merged.name = format!("{base}-{overlay}");
+    fn write(&self) -> RwLockWriteGuard<'_, LoadedPolicies> {
+        self.loaded.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
 
-        // Merge isolation levels (take the higher/more restrictive one)
-        if overlay_policy.isolation_level as u8 > base_policy.isolation_level as u8 {
-            merged.isolation_level = overlay_policy.isolation_level;
+impl LoadedPolicies {
+    fn insert(&mut self, path: PathBuf, policy: SecurityPolicy) {
+        // The file may name a different policy than when it was last loaded
+        if let Some(previous) = self.files.insert(path, policy.name.clone())
+            && previous != policy.name
+        {
+            self.policies.remove(&previous);
         }
+        self.policies.insert(policy.name.clone(), policy);
+    }
+}
 
-        // Merge capabilities (combine denied, intersect allowed)
-        for cap in &overlay_policy.capabilities.denied {
-            if !merged.capabilities.denied.contains(cap) {
-                merged.capabilities.denied.push(cap.clone());
-            }
+fn is_policy_file(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("json")
+}
+
+/// Name and raw JSON of a policy file, which may leave out whatever it
+/// inherits through `extends`
+fn policy_document(content: &str) -> Result<(String, serde_json::Value)> {
+    let document: serde_json::Value = serde_json::from_str(content)?;
+    let name = document
+        .get("name")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| AivaError::SecurityError("Policy has no name".to_string()))?
+        .to_string();
+    Ok((name, document))
+}
+
+/// Bring `loaded` up to date with the policy file `path` in `dir`, after
+/// notify reported a change to it
+fn reload_policy_file(dir: &Path, path: &Path, loaded: &RwLock<LoadedPolicies>) {
+    if !path.exists() {
+        let mut loaded = loaded.write().unwrap_or_else(|e| e.into_inner());
+        if let Some(name) = loaded.files.remove(path) {
+            loaded.policies.remove(&name);
+            info!("Removed policy {} as {:?} was deleted", name, path);
         }
+        return;
+    }
 
-        // Keep only capabilities allowed by both policies
-        merged
-            .capabilities
-            .allowed
-            .retain(|cap| overlay_policy.capabilities.allowed.contains(cap));
-
-        // Merge resource limits (take the more restrictive one)
-        if let Some(overlay_cpu) = overlay_policy.resource_limits.cpu_quota {
-            if let Some(base_cpu) = merged.resource_limits.cpu_quota {
-                merged.resource_limits.cpu_quota = Some(overlay_cpu.min(base_cpu));
-            } else {
-                merged.resource_limits.cpu_quota = Some(overlay_cpu);
-            }
+    // The file can extend any other in the directory, and others can extend it
+    let mut sources = HashMap::new();
+    let mut dependents = Vec::new();
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let other = entry.path();
+        if !is_policy_file(&other) {
+            continue;
+        }
+        let Ok((name, document)) = std::fs::read_to_string(&other)
+            .map_err(AivaError::from)
+            .and_then(|content| policy_document(&content))
+        else {
+            continue;
+        };
+        if other != path && document.get("extends").is_some() {
+            dependents.push((other, name.clone()));
         }
+        sources.insert(name, document);
+    }
 
-        if let Some(overlay_mem) = overlay_policy.resource_limits.memory_limit {
-            if let Some(base_mem) = merged.resource_limits.memory_limit {
-                merged.resource_limits.memory_limit = Some(overlay_mem.min(base_mem));
-            } else {
-                merged.resource_limits.memory_limit = Some(overlay_mem);
-            }
+    let reloaded = std::fs::read_to_string(path)
+        .map_err(AivaError::from)
+        .and_then(|content| policy_document(&content))
+        .and_then(|(name, _)| resolve_extends(&name, &sources));
+    let mut loaded = loaded.write().unwrap_or_else(|e| e.into_inner());
+    match reloaded {
+        Ok(policy) => {
+            info!("Reloaded policy {} from {:?}", policy.name, path);
+            loaded.insert(path.to_path_buf(), policy);
         }
+        Err(e) => {
+            warn!("Keeping policy from {:?}, failed to reload it: {}", path, e);
+            return;
+        }
+    }
 
-        // Merge network policies (more restrictive)
-        if !overlay_policy.network_policy.allow_outbound {
-            merged.network_policy.allow_outbound = false;
+    for (other, name) in dependents {
+        if let Ok(policy) = resolve_extends(&name, &sources) {
+            loaded.insert(other, policy);
         }
+    }
+}
 
-        // Combine blocked IPs
-        for ip in &overlay_policy.network_policy.blocked_ips {
-            if !merged.network_policy.blocked_ips.contains(ip) {
-                merged.network_policy.blocked_ips.push(ip.clone());
-            }
+fn watch_error(e: notify::Error) -> AivaError {
+    AivaError::SecurityError(format!("Failed to watch policies: {e}"))
+}
+
+impl SecurityPolicy {
+    /// Check the limits, network rules and capabilities for consistency
+    pub fn validate(&self) -> Result<()> {
+        // Validate policy name
+        if self.name.is_empty() {
+            return Err(AivaError::SecurityError(
+                "Policy name cannot be empty".to_string(),
+            ));
         }
 
-        // Take the more restrictive rate limit
-        if let Some(overlay_rate) = &overlay_policy.network_policy.rate_limit {
-            if let Some(base_rate) = &merged.network_policy.rate_limit {
-                merged.network_policy.rate_limit = Some(crate::NetworkRateLimit {
-                    bandwidth_mbps: overlay_rate.bandwidth_mbps.min(base_rate.bandwidth_mbps),
-                    connections_per_second: overlay_rate
-                        .connections_per_second
-                        .min(base_rate.connections_per_second),
-                });
-            } else {
-                merged.network_policy.rate_limit = Some(overlay_rate.clone());
-            }
+        // Validate resource limits
+        if let Some(cpu_quota) = self.resource_limits.cpu_quota
+            && (cpu_quota == 0 || cpu_quota > 100)
+        {
+            return Err(AivaError::SecurityError(
+                "CPU quota must be between 1 and 100".to_string(),
+            ));
         }
 
-        Ok(merged)
-    }
+        if let Some(memory_limit) = self.resource_limits.memory_limit
+            && memory_limit == 0
+        {
+            return Err(AivaError::SecurityError(
+                "Memory limit must be greater than 0".to_string(),
+            ));
+        }
 
-    async fn load_policy_from_file(&self, path: &PathBuf) -> Result<SecurityPolicy> {
-        let content = fs::read_to_string(path).await?;
-        let policy: SecurityPolicy = serde_json::from_str(&content)?;
-        debug!("Loaded policy {} from {:?}", policy.name, path);
-        Ok(policy)
-    }
+        self.network_policy.validate()?;
 
-    async fn create_default_policies(&mut self) -> Result<()> {
-        info!("Creating default security policies");
+        // Validate capabilities
+        self.capabilities.validate()?;
 
-        for (name, policy) in crate::load_preset_policies() {
-            self.save_policy(&policy).await?;
-            self.policies.insert(name, policy);
+        // Validate syscall filter
+        if let Some(filter) = &self.syscall_filter
+            && filter.rules.is_empty()
+            && matches!(filter.default_action, crate::FilterAction::Kill)
+        {
+            warn!("Policy {} denies all syscalls by default", self.name);
         }
 
         Ok(())
@@ -262,9 +386,34 @@ impl std::str::FromStr for IsolationLevel {
     }
 }
 
+/// Ordered by how much the level restricts a VM, from `None` up to
+/// `Maximum`. Spelled out rather than derived so that reordering the
+/// variants cannot invert the comparison.
+impl Ord for IsolationLevel {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        isolation_strictness(*self).cmp(&isolation_strictness(*other))
+    }
+}
+
+impl PartialOrd for IsolationLevel {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn isolation_strictness(level: IsolationLevel) -> u8 {
+    match level {
+        IsolationLevel::None => 0,
+        IsolationLevel::Basic => 1,
+        IsolationLevel::Enhanced => 2,
+        IsolationLevel::Maximum => 3,
+    }
+}
+
 pub fn create_mcp_policy() -> SecurityPolicy {
     SecurityPolicy {
         name: "mcp-server".to_string(),
+        extends: None,
         isolation_level: IsolationLevel::Enhanced,
         capabilities: CapabilitySet {
             allowed: vec!["CAP_NET_BIND_SERVICE".to_string()],
@@ -337,6 +486,7 @@ pub fn create_mcp_policy() -> SecurityPolicy {
 pub fn create_ai_agent_policy() -> SecurityPolicy {
     SecurityPolicy {
         name: "ai-agent".to_string(),
+        extends: None,
         isolation_level: IsolationLevel::Basic,
         capabilities: CapabilitySet {
             allowed: vec![],
@@ -377,3 +527,278 @@ pub fn create_ai_agent_policy() -> SecurityPolicy {
         },
     }
 }
+
+/// The policy `name` of the raw JSON `documents`, with its `extends` chain
+/// applied. A document only needs the fields it changes; the rest come from
+/// the policy it extends, which may be another document or a preset.
+/// Capabilities are merged as by [`merge_policy`]: denied lists are joined
+/// and only capabilities both allow stay allowed.
+pub fn resolve_extends(
+    name: &str,
+    documents: &HashMap<String, serde_json::Value>,
+) -> Result<SecurityPolicy> {
+    resolve_chain(name, documents, &mut Vec::new())
+}
+
+fn resolve_chain(
+    name: &str,
+    documents: &HashMap<String, serde_json::Value>,
+    chain: &mut Vec<String>,
+) -> Result<SecurityPolicy> {
+    if chain.iter().any(|seen| seen == name) {
+        chain.push(name.to_string());
+        return Err(AivaError::SecurityError(format!(
+            "Policy inheritance cycle: {}",
+            chain.join(" -> ")
+        )));
+    }
+
+    let Some(document) = documents.get(name) else {
+        return crate::load_preset_policies()
+            .remove(name)
+            .ok_or_else(|| AivaError::SecurityError(format!("Policy {name} not found")));
+    };
+    let Some(parent_name) = document.get("extends").and_then(serde_json::Value::as_str) else {
+        return Ok(serde_json::from_value(document.clone())?);
+    };
+
+    chain.push(name.to_string());
+    let parent = resolve_chain(parent_name, documents, chain)?;
+    let mut merged = serde_json::to_value(&parent)?;
+    overlay(&mut merged, document.clone());
+
+    let mut policy: SecurityPolicy = serde_json::from_value(merged)?;
+    policy.capabilities = merge_capabilities(&parent.capabilities, &policy.capabilities);
+    Ok(policy)
+}
+
+/// Replace the values of `base` that `layer` sets, key by key
+fn overlay(base: &mut serde_json::Value, layer: serde_json::Value) {
+    match (base, layer) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => overlay(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Combine two policies into one that is at least as restrictive as each of
+/// them on every dimension. Neither input takes precedence:
+///
+/// - isolation level: the higher one
+/// - capabilities: denied sets are joined; a capability stays allowed only
+///   when both allow it and neither denies it. Denying `ALL` clears allowed.
+/// - resource limits: the lower of each limit, a missing limit being
+///   unlimited
+/// - network: outbound traffic only when both allow it, then only to the
+///   ports both allow; blocked ranges are joined; the lower rate limits
+/// - syscalls: per syscall the stricter action of the two filters, a missing
+///   filter allowing everything
+///
+/// Apart from the name, merging is commutative and associative.
+pub fn merge_policy(base: &SecurityPolicy, overlay: &SecurityPolicy) -> SecurityPolicy {
+    SecurityPolicy {
+        name: format!("{}-{}", base.name, overlay.name),
+        extends: None,
+        isolation_level: base.isolation_level.max(overlay.isolation_level),
+        capabilities: merge_capabilities(&base.capabilities, &overlay.capabilities),
+        syscall_filter: merge_syscall_filters(
+            base.syscall_filter.as_ref(),
+            overlay.syscall_filter.as_ref(),
+        ),
+        resource_limits: merge_resource_limits(&base.resource_limits, &overlay.resource_limits),
+        network_policy: merge_network_policies(&base.network_policy, &overlay.network_policy),
+    }
+}
+
+fn merge_capabilities(base: &CapabilitySet, overlay: &CapabilitySet) -> CapabilitySet {
+    let mut denied = base.denied.clone();
+    for cap in &overlay.denied {
+        if !denied.contains(cap) {
+            denied.push(cap.clone());
+        }
+    }
+
+    let allowed = if denied.iter().any(|cap| cap == crate::ALL_CAPABILITIES) {
+        Vec::new()
+    } else {
+        base.allowed
+            .iter()
+            .filter(|cap| overlay.allowed.contains(cap) && !denied.contains(cap))
+            .cloned()
+            .collect()
+    };
+
+    CapabilitySet { allowed, denied }
+}
+
+/// The lower of two limits where `None` means unlimited
+fn min_limit<T: Ord>(a: Option<T>, b: Option<T>) -> Option<T> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn merge_resource_limits(base: &ResourceLimits, overlay: &ResourceLimits) -> ResourceLimits {
+    let io_bandwidth = match (&base.io_bandwidth, &overlay.io_bandwidth) {
+        (Some(a), Some(b)) => Some(IOLimit {
+            read_bps: min_limit(a.read_bps, b.read_bps),
+            write_bps: min_limit(a.write_bps, b.write_bps),
+            read_iops: min_limit(a.read_iops, b.read_iops),
+            write_iops: min_limit(a.write_iops, b.write_iops),
+        }),
+        (a, b) => a.clone().or_else(|| b.clone()),
+    };
+
+    ResourceLimits {
+        cpu_quota: min_limit(base.cpu_quota, overlay.cpu_quota),
+        memory_limit: min_limit(base.memory_limit, overlay.memory_limit),
+        pids_limit: min_limit(base.pids_limit, overlay.pids_limit),
+        open_files: min_limit(base.open_files, overlay.open_files),
+        io_bandwidth,
+    }
+}
+
+fn merge_network_policies(base: &NetworkPolicy, overlay: &NetworkPolicy) -> NetworkPolicy {
+    // `allowed_ports` lists the exceptions of a policy without outbound
+    // traffic, so only the restricting policies have a say in them
+    let allowed_ports: Vec<PortRule> = match (base.allow_outbound, overlay.allow_outbound) {
+        (true, true) => {
+            let mut ports = base.allowed_ports.clone();
+            for rule in &overlay.allowed_ports {
+                if !ports.contains(rule) {
+                    ports.push(rule.clone());
+                }
+            }
+            ports
+        }
+        (false, true) => base.allowed_ports.clone(),
+        (true, false) => overlay.allowed_ports.clone(),
+        (false, false) => base
+            .allowed_ports
+            .iter()
+            .filter(|rule| overlay.allowed_ports.contains(rule))
+            .cloned()
+            .collect(),
+    };
+
+    let mut blocked_ips = base.blocked_ips.clone();
+    for ip in &overlay.blocked_ips {
+        if !blocked_ips.contains(ip) {
+            blocked_ips.push(ip.clone());
+        }
+    }
+
+    let rate_limit = match (&base.rate_limit, &overlay.rate_limit) {
+        (Some(a), Some(b)) => Some(NetworkRateLimit {
+            bandwidth_mbps: a.bandwidth_mbps.min(b.bandwidth_mbps),
+            connections_per_second: a.connections_per_second.min(b.connections_per_second),
+        }),
+        (a, b) => a.clone().or_else(|| b.clone()),
+    };
+
+    NetworkPolicy {
+        allow_outbound: base.allow_outbound && overlay.allow_outbound,
+        allowed_ports,
+        blocked_ips,
+        rate_limit,
+    }
+}
+
+/// How strongly an action stops a syscall, from `Allow` up to `Kill`
+pub(crate) fn action_strictness(action: FilterAction) -> u8 {
+    match action {
+        FilterAction::Allow => 0,
+        FilterAction::Log => 1,
+        FilterAction::Trap => 2,
+        FilterAction::Kill => 3,
+    }
+}
+
+fn stricter(a: FilterAction, b: FilterAction) -> FilterAction {
+    if action_strictness(b) > action_strictness(a) {
+        b
+    } else {
+        a
+    }
+}
+
+/// Action `filter` takes for every call of `syscall`; conditional rules only
+/// cover some calls and are left out
+pub(crate) fn unconditional_action(filter: Option<&SyscallFilter>, syscall: &str) -> FilterAction {
+    let Some(filter) = filter else {
+        return FilterAction::Allow;
+    };
+    filter
+        .rules
+        .iter()
+        .find(|rule| rule.syscall == syscall && rule.conditions.is_none())
+        .map(|rule| rule.action)
+        .unwrap_or(filter.default_action)
+}
+
+fn merge_syscall_filters(
+    base: Option<&SyscallFilter>,
+    overlay: Option<&SyscallFilter>,
+) -> Option<SyscallFilter> {
+    if base.is_none() && overlay.is_none() {
+        return None;
+    }
+
+    let default_action = stricter(
+        base.map_or(FilterAction::Allow, |f| f.default_action),
+        overlay.map_or(FilterAction::Allow, |f| f.default_action),
+    );
+
+    let all_rules = || {
+        base.into_iter()
+            .chain(overlay)
+            .flat_map(|filter| filter.rules.iter())
+    };
+
+    let mut rules: Vec<SyscallRule> = Vec::new();
+    for rule in all_rules().filter(|rule| rule.conditions.is_none()) {
+        if rules.iter().any(|r| r.syscall == rule.syscall) {
+            continue;
+        }
+        let action = stricter(
+            unconditional_action(base, &rule.syscall),
+            unconditional_action(overlay, &rule.syscall),
+        );
+        if action != default_action {
+            rules.push(SyscallRule {
+                syscall: rule.syscall.clone(),
+                action,
+                conditions: None,
+            });
+        }
+    }
+
+    // Conditional rules survive when they are stricter than what the merged
+    // filter does for the syscall anyway. They go first, a later rule would
+    // be shadowed by the unconditional one.
+    let mut merged: Vec<SyscallRule> = Vec::new();
+    for rule in all_rules().filter(|rule| rule.conditions.is_some()) {
+        let action = rules
+            .iter()
+            .find(|r| r.syscall == rule.syscall)
+            .map_or(default_action, |r| r.action);
+        if action_strictness(rule.action) > action_strictness(action) && !merged.contains(rule) {
+            merged.push(rule.clone());
+        }
+    }
+    merged.extend(rules);
+
+    Some(SyscallFilter {
+        default_action,
+        rules: merged,
+    })
+}
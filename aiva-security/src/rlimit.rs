@@ -0,0 +1,71 @@
+//! Enforcement of `ResourceLimits::open_files` through `RLIMIT_NOFILE`.
+//!
+//! The limit is set on the jailer just before it executes, and passed on as
+//! `--resource-limit no-file=<n>` since the jailer otherwise resets the
+//! limit to its own default for Firecracker. Both happen before the VMM runs
+//! any guest code. `/proc/<pid>/limits` shows what the process ended up with.
+
+use crate::ResourceLimits;
+use aiva_core::{AivaError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Soft and hard `RLIMIT_NOFILE` of a process
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenFilesLimit {
+    pub soft: u64,
+    pub hard: u64,
+}
+
+impl ResourceLimits {
+    /// The `RLIMIT_NOFILE` enforcing `open_files`, both soft and hard so the
+    /// VMM cannot raise it again. `host_hard` is the hard limit of the
+    /// process spawning the VMM, which an unprivileged parent cannot exceed.
+    pub fn open_files_limit(&self, host_hard: u64) -> Result<Option<OpenFilesLimit>> {
+        let Some(open_files) = self.open_files else {
+            return Ok(None);
+        };
+        if open_files == 0 {
+            return Err(AivaError::SecurityError(
+                "Resource limit 'open_files' must be greater than zero".to_string(),
+            ));
+        }
+        if u64::from(open_files) > host_hard {
+            return Err(AivaError::SecurityError(format!(
+                "Resource limit 'open_files' is {open_files} but the host allows at most {host_hard}"
+            )));
+        }
+
+        let limit = u64::from(open_files);
+        Ok(Some(OpenFilesLimit {
+            soft: limit,
+            hard: limit,
+        }))
+    }
+
+    /// Jailer arguments applying `open_files` to Firecracker
+    pub fn jailer_rlimit_args(&self, host_hard: u64) -> Result<Vec<String>> {
+        Ok(match self.open_files_limit(host_hard)? {
+            Some(limit) => vec![
+                "--resource-limit".to_string(),
+                format!("no-file={}", limit.hard),
+            ],
+            None => Vec::new(),
+        })
+    }
+}
+
+/// The `Max open files` line of `/proc/<pid>/limits`
+pub fn parse_open_files_limit(proc_limits: &str) -> Option<OpenFilesLimit> {
+    let values = proc_limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))?;
+    let mut values = values.split_whitespace().map(|value| match value {
+        "unlimited" => Some(u64::MAX),
+        value => value.parse().ok(),
+    });
+
+    Some(OpenFilesLimit {
+        soft: values.next()??,
+        hard: values.next()??,
+    })
+}
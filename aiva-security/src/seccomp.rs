@@ -0,0 +1,236 @@
+//! Compiling a [`SyscallFilter`] into a seccomp-bpf program.
+//!
+//! seccompiler gives every filter a single on-match action, while the rules
+//! of a [`SyscallFilter`] each carry their own. The rules are therefore
+//! compiled one action at a time, strictest first, and the resulting
+//! programs are chained: where a segment would return "no match" it jumps to
+//! the next one instead, and the last segment returns the default action. A
+//! syscall matched by several rules gets the strictest of their actions,
+//! as it does when policies are merged.
+//!
+//! Firecracker loads the program from a file passed with `--seccomp-filter`,
+//! written by [`write_firecracker_filter`]. That file replaces Firecracker's
+//! built-in allowlist, so only filters passing [`replaces_firecracker_filter`]
+//! are handed over.
+
+use crate::policy::action_strictness;
+use crate::{CompareOp, Condition, FilterAction, SyscallFilter};
+use aiva_core::{AivaError, Result};
+use seccompiler::{
+    BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+    SeccompRule, TargetArch, sock_filter,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::Path;
+
+/// Name of the filter file in a VM's jailer workspace
+pub const SECCOMP_FILTER_FILE: &str = "seccomp.bpf";
+/// Firecracker threads that each need a filter in the file
+const FIRECRACKER_THREADS: &[&str] = &["api", "vcpu", "vmm"];
+/// Longest program the kernel accepts
+const BPF_MAX_LEN: usize = 4096;
+/// `BPF_LD | BPF_W | BPF_ABS`, loading a word of `seccomp_data`
+const BPF_LD_W_ABS: u16 = 0x20;
+/// `BPF_JMP | BPF_JA`
+const BPF_JA: u16 = 0x05;
+/// `BPF_RET | BPF_K`
+const BPF_RET_K: u16 = 0x06;
+/// Placeholder "no match" result of a segment, replaced by a jump to the
+/// next one. No [`FilterAction`] maps to an errno, so it cannot be confused
+/// with a real action.
+const FALL_THROUGH: SeccompAction = SeccompAction::Errno(0xffff);
+
+/// Architecture of this host
+pub fn host_arch() -> Result<TargetArch> {
+    TargetArch::try_from(std::env::consts::ARCH)
+        .map_err(|e| AivaError::SecurityError(format!("Seccomp is not supported: {e}")))
+}
+
+/// Number of `syscall` on `arch`, `None` if it has no such syscall
+pub fn syscall_number(syscall: &str, arch: TargetArch) -> Option<i64> {
+    // seccompiler only resolves names in its JSON frontend, so compile a
+    // filter matching just this syscall and read the number it compares to
+    let probe = serde_json::json!({
+        "probe": {
+            "mismatch_action": "allow",
+            "match_action": "trap",
+            "filter": [{ "syscall": syscall }],
+        }
+    });
+    let program = seccompiler::compile_from_json(probe.to_string().as_bytes(), arch)
+        .ok()?
+        .remove("probe")?;
+    let load_nr = program
+        .iter()
+        .position(|insn| insn.code == BPF_LD_W_ABS && insn.k == 0)?;
+    program.get(load_nr + 1).map(|insn| i64::from(insn.k))
+}
+
+/// Compile `filter` into a program for `arch`. Fails listing every syscall
+/// name `arch` does not have.
+pub fn compile_filter(filter: &SyscallFilter, arch: TargetArch) -> Result<BpfProgram> {
+    let mut numbers = BTreeMap::new();
+    let mut unknown = BTreeSet::new();
+    for rule in &filter.rules {
+        match syscall_number(&rule.syscall, arch) {
+            Some(nr) => {
+                numbers.insert(rule.syscall.as_str(), nr);
+            }
+            None => {
+                unknown.insert(rule.syscall.as_str());
+            }
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(AivaError::SecurityError(format!(
+            "Unknown syscalls for {arch:?}: {}",
+            unknown.into_iter().collect::<Vec<_>>().join(", ")
+        )));
+    }
+
+    let mut actions: Vec<FilterAction> = filter.rules.iter().map(|rule| rule.action).collect();
+    actions.sort_by_key(|action| std::cmp::Reverse(action_strictness(*action)));
+    actions.dedup();
+
+    let mut program = BpfProgram::new();
+    for action in actions {
+        let mut chains: BTreeMap<i64, Vec<SeccompRule>> = BTreeMap::new();
+        let mut unconditional = BTreeSet::new();
+        for rule in filter.rules.iter().filter(|rule| rule.action == action) {
+            let nr = numbers[rule.syscall.as_str()];
+            let chain = chains.entry(nr).or_default();
+            match rule.conditions.as_deref() {
+                Some(conditions) if !conditions.is_empty() => {
+                    let conditions = conditions
+                        .iter()
+                        .map(seccomp_condition)
+                        .collect::<Result<Vec<_>>>()?;
+                    chain.push(SeccompRule::new(conditions).map_err(seccomp_error)?);
+                }
+                _ => {
+                    unconditional.insert(nr);
+                }
+            }
+        }
+        // An empty chain matches the syscall whatever its arguments
+        for nr in unconditional {
+            chains.insert(nr, Vec::new());
+        }
+
+        let segment = compile_segment(chains, FALL_THROUGH, seccomp_action(action), arch)?;
+        let len = segment.len();
+        program.extend(segment.into_iter().enumerate().map(|(i, insn)| {
+            if insn.code == BPF_RET_K && insn.k == u32::from(FALL_THROUGH) {
+                jump((len - i - 1) as u32)
+            } else {
+                insn
+            }
+        }));
+    }
+    program.extend(compile_segment(
+        BTreeMap::new(),
+        seccomp_action(filter.default_action),
+        FALL_THROUGH,
+        arch,
+    )?);
+
+    if program.len() > BPF_MAX_LEN {
+        return Err(AivaError::SecurityError(format!(
+            "Seccomp filter has {} instructions, the kernel accepts at most {BPF_MAX_LEN}",
+            program.len()
+        )));
+    }
+    Ok(program)
+}
+
+/// Whether `filter` may stand in for Firecracker's built-in filter. A filter
+/// letting unlisted syscalls through is a denylist, weaker than the built-in
+/// allowlist it would replace.
+pub fn replaces_firecracker_filter(filter: &SyscallFilter) -> bool {
+    matches!(
+        filter.default_action,
+        FilterAction::Kill | FilterAction::Trap
+    )
+}
+
+/// Write `program` as the filter of every Firecracker thread, in the format
+/// `--seccomp-filter` reads: a bincode map of thread name to program, with
+/// fixed-width little-endian integers.
+pub fn write_firecracker_filter(program: &BpfProgram, path: &Path) -> Result<()> {
+    let mut bytes = Vec::new();
+    bytes.extend((FIRECRACKER_THREADS.len() as u64).to_le_bytes());
+    for thread in FIRECRACKER_THREADS {
+        bytes.extend((thread.len() as u64).to_le_bytes());
+        bytes.extend(thread.as_bytes());
+        bytes.extend((program.len() as u64).to_le_bytes());
+        for insn in program {
+            bytes.extend(insn.code.to_le_bytes());
+            bytes.push(insn.jt);
+            bytes.push(insn.jf);
+            bytes.extend(insn.k.to_le_bytes());
+        }
+    }
+
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(&bytes)?;
+    Ok(())
+}
+
+fn compile_segment(
+    chains: BTreeMap<i64, Vec<SeccompRule>>,
+    mismatch_action: SeccompAction,
+    match_action: SeccompAction,
+    arch: TargetArch,
+) -> Result<BpfProgram> {
+    let filter =
+        SeccompFilter::new(chains, mismatch_action, match_action, arch).map_err(seccomp_error)?;
+    BpfProgram::try_from(filter).map_err(seccomp_error)
+}
+
+fn seccomp_action(action: FilterAction) -> SeccompAction {
+    match action {
+        FilterAction::Allow => SeccompAction::Allow,
+        FilterAction::Kill => SeccompAction::KillProcess,
+        FilterAction::Trap => SeccompAction::Trap,
+        FilterAction::Log => SeccompAction::Log,
+    }
+}
+
+fn seccomp_condition(condition: &Condition) -> Result<SeccompCondition> {
+    let operator = match condition.operation {
+        CompareOp::Equal => SeccompCmpOp::Eq,
+        CompareOp::NotEqual => SeccompCmpOp::Ne,
+        CompareOp::Greater => SeccompCmpOp::Gt,
+        CompareOp::GreaterEqual => SeccompCmpOp::Ge,
+        CompareOp::Less => SeccompCmpOp::Lt,
+        CompareOp::LessEqual => SeccompCmpOp::Le,
+        CompareOp::MaskedEqual(mask) => SeccompCmpOp::MaskedEq(mask),
+    };
+    let arg_index = u8::try_from(condition.arg_index).map_err(|_| {
+        AivaError::SecurityError(format!(
+            "Syscall argument {} does not exist",
+            condition.arg_index
+        ))
+    })?;
+    SeccompCondition::new(
+        arg_index,
+        SeccompCmpArgLen::Qword,
+        operator,
+        condition.value,
+    )
+    .map_err(seccomp_error)
+}
+
+fn jump(offset: u32) -> sock_filter {
+    sock_filter {
+        code: BPF_JA,
+        jt: 0,
+        jf: 0,
+        k: offset,
+    }
+}
+
+fn seccomp_error(e: impl std::fmt::Display) -> AivaError {
+    AivaError::SecurityError(format!("Invalid seccomp filter: {e}"))
+}
@@ -0,0 +1,105 @@
+use crate::{FilterAction, SeccompEvent, SeccompMonitor};
+use aiva_core::{AlertSeverity, AlertType, DefaultMetricsCollector, MonitoringService, Result};
+use std::sync::Arc;
+
+const KILLED_MOUNT: &str = r#"type=SECCOMP msg=audit(1700000000.123:456): auid=4294967295 uid=0 gid=0 ses=4294967295 subj=unconfined pid=4242 comm="fc_vcpu 0" exe="/usr/bin/firecracker" sig=31 arch=c000003e syscall=165 compat=0 ip=0x7f00deadbeef code=0x80000000"#;
+
+#[test]
+fn test_parse_killed_syscall_from_audit_log() {
+    let event = SeccompEvent::parse_audit_line(KILLED_MOUNT).unwrap();
+
+    assert_eq!(event.pid, Some(4242));
+    assert_eq!(event.comm.as_deref(), Some("fc_vcpu 0"));
+    assert_eq!(event.syscall, 165);
+    assert_eq!(event.arch, "x86_64");
+    assert_eq!(event.action, FilterAction::Kill);
+    assert_eq!(event.syscall_name(), "mount");
+}
+
+#[test]
+fn test_parse_audit_line_actions() {
+    let logged = "audit: type=1326 audit(1700000000.5:7): pid=10 comm=\"python3\" arch=c00000b7 syscall=117 code=0x7ffc0000";
+    let event = SeccompEvent::parse_audit_line(logged).unwrap();
+    assert_eq!(event.action, FilterAction::Log);
+    assert_eq!(event.syscall_name(), "ptrace");
+
+    let allowed = KILLED_MOUNT.replace("code=0x80000000", "code=0x7fff0000");
+    assert!(SeccompEvent::parse_audit_line(&allowed).is_none());
+
+    let syscall_audit = KILLED_MOUNT.replace("type=SECCOMP", "type=SYSCALL");
+    assert!(SeccompEvent::parse_audit_line(&syscall_audit).is_none());
+}
+
+#[test]
+fn test_parse_firecracker_bad_syscall() {
+    let line = "2024-01-01T00:00:00.000000000 [anonymous-instance:fc_vcpu 0] Shutting down VM after intercepting a bad syscall (999).";
+    let event = SeccompEvent::parse_firecracker_line(line).unwrap();
+
+    assert_eq!(event.syscall, 999);
+    assert_eq!(event.action, FilterAction::Kill);
+    assert_eq!(event.syscall_name(), "syscall#999");
+    assert!(SeccompEvent::parse_firecracker_line("Running Firecracker v1.7.0").is_none());
+}
+
+#[tokio::test]
+async fn test_violation_raises_rate_limited_alert() -> Result<()> {
+    let monitoring = Arc::new(MonitoringService::new(Box::new(DefaultMetricsCollector)));
+    let monitor = SeccompMonitor::new(monitoring.clone());
+
+    // Events from processes that are not VMs are not ours to report
+    assert!(monitor.ingest_audit_line(KILLED_MOUNT).await?.is_none());
+
+    monitor.track(4242, "vm-1").await;
+    let alert = monitor.ingest_audit_line(KILLED_MOUNT).await?.unwrap();
+    assert_eq!(alert.vm_id.as_deref(), Some("vm-1"));
+    assert!(matches!(alert.alert_type, AlertType::SecurityViolation));
+    assert!(matches!(alert.severity, AlertSeverity::Critical));
+    assert!(alert.message.contains("mount (165)"));
+    assert!(alert.message.contains("fc_vcpu 0"));
+
+    // The same VM and syscall within the window is suppressed, a different
+    // syscall is not
+    assert!(monitor.ingest_audit_line(KILLED_MOUNT).await?.is_none());
+    let other = KILLED_MOUNT.replace("syscall=165", "syscall=101");
+    assert!(monitor.ingest_audit_line(&other).await?.is_some());
+
+    assert_eq!(monitoring.get_alerts(Some("vm-1")).await?.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_suppressed_count_reported_after_window() -> Result<()> {
+    let monitoring = Arc::new(
+        MonitoringService::new(Box::new(DefaultMetricsCollector))
+            .with_security_alert_window(std::time::Duration::from_millis(50)),
+    );
+    let monitor = SeccompMonitor::new(monitoring.clone());
+    let line = "Shutting down VM after intercepting a bad syscall (165).";
+
+    assert!(
+        monitor
+            .ingest_firecracker_line("vm-2", line)
+            .await?
+            .is_some()
+    );
+    assert!(
+        monitor
+            .ingest_firecracker_line("vm-2", line)
+            .await?
+            .is_none()
+    );
+    assert!(
+        monitor
+            .ingest_firecracker_line("vm-2", line)
+            .await?
+            .is_none()
+    );
+
+    tokio::time::sleep(std::time::Duration::from_millis(80)).await;
+    let alert = monitor
+        .ingest_firecracker_line("vm-2", line)
+        .await?
+        .unwrap();
+    assert!(alert.message.contains("2 similar event(s) suppressed"));
+    Ok(())
+}
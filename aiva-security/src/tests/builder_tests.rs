@@ -0,0 +1,68 @@
+use crate::policy::create_mcp_policy;
+use crate::{Direction, FilterAction, IOLimit, IsolationLevel, SecurityPolicy};
+
+#[test]
+fn test_builder_reconstructs_mcp_server_preset() {
+    let policy = SecurityPolicy::builder()
+        .name("mcp-server")
+        .isolation_level(IsolationLevel::Enhanced)
+        .allow_capability("CAP_NET_BIND_SERVICE")
+        .deny_capability("CAP_SYS_ADMIN")
+        .deny_capability("CAP_SYS_PTRACE")
+        .deny_capability("CAP_SYS_MODULE")
+        .syscall_kill("mount")
+        .syscall_kill("umount")
+        .syscall_kill("ptrace")
+        .cpu_quota(75)
+        .memory_limit(4 * 1024 * 1024 * 1024)
+        .pids_limit(512)
+        .open_files(1024)
+        .io_bandwidth(IOLimit {
+            read_bps: Some(200 * 1024 * 1024),
+            write_bps: Some(200 * 1024 * 1024),
+            read_iops: Some(2000),
+            write_iops: Some(2000),
+        })
+        .allow_port(443, "tcp", Direction::Outbound)
+        .allow_port(80, "tcp", Direction::Outbound)
+        .allow_port(8080, "tcp", Direction::Inbound)
+        .block_ip("127.0.0.1/32")
+        .rate_limit(500, 50)
+        .build()
+        .unwrap();
+
+    assert_eq!(policy, create_mcp_policy());
+}
+
+#[test]
+fn test_builder_starts_from_default_policy() {
+    let policy = SecurityPolicy::builder().build().unwrap();
+    assert_eq!(policy, SecurityPolicy::default());
+
+    let policy = SecurityPolicy::builder()
+        .default_syscall_action(FilterAction::Log)
+        .build()
+        .unwrap();
+    let filter = policy.syscall_filter.unwrap();
+    assert_eq!(filter.default_action, FilterAction::Log);
+    assert!(filter.rules.is_empty());
+}
+
+#[test]
+fn test_builder_validates_on_build() {
+    let err = SecurityPolicy::builder().cpu_quota(0).build().unwrap_err();
+    assert!(err.to_string().contains("CPU quota"), "{err}");
+
+    let err = SecurityPolicy::builder()
+        .allow_port(53, "icmp", Direction::Outbound)
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("icmp"), "{err}");
+
+    let err = SecurityPolicy::builder()
+        .deny_capability("ALL")
+        .allow_capability("CAP_CHOWN")
+        .build()
+        .unwrap_err();
+    assert!(err.to_string().contains("CAP_CHOWN"), "{err}");
+}
@@ -0,0 +1,66 @@
+use crate::{
+    ALL_CAPABILITIES, CapabilitySet, IsolationManager, LINUX_CAPABILITIES, SecurityManager,
+    load_preset_policies,
+};
+use aiva_core::Result;
+
+#[test]
+fn test_all_expands_to_every_kernel_capability() {
+    let isolated = load_preset_policies().remove("isolated").unwrap();
+
+    let expanded = isolated.capabilities.expanded();
+    assert_eq!(expanded.denied.len(), 41);
+    assert_eq!(expanded.denied.first().unwrap(), "CAP_CHOWN");
+    assert_eq!(expanded.denied.last().unwrap(), "CAP_CHECKPOINT_RESTORE");
+    assert!(!expanded.denied.iter().any(|cap| cap == ALL_CAPABILITIES));
+}
+
+#[test]
+fn test_expansion_leaves_out_allowed_and_keeps_unknown_names() {
+    let capabilities = CapabilitySet {
+        allowed: vec!["CAP_NET_BIND_SERVICE".to_string()],
+        denied: vec![ALL_CAPABILITIES.to_string(), "CAP_FUTURE".to_string()],
+    };
+
+    let expanded = capabilities.expanded();
+    assert!(
+        !expanded
+            .denied
+            .contains(&"CAP_NET_BIND_SERVICE".to_string())
+    );
+    assert_eq!(expanded.denied.len(), LINUX_CAPABILITIES.len());
+    assert_eq!(expanded.denied.last().unwrap(), "CAP_FUTURE");
+
+    let explicit = load_preset_policies().remove("restricted").unwrap();
+    assert_eq!(
+        explicit.capabilities.expanded().denied,
+        explicit.capabilities.denied
+    );
+}
+
+#[tokio::test]
+async fn test_allowing_capabilities_next_to_all_is_rejected() -> Result<()> {
+    let manager = IsolationManager::new()?;
+    let mut policy = load_preset_policies().remove("isolated").unwrap();
+    policy.name = "leaky".to_string();
+    policy.capabilities.allowed = vec!["CAP_NET_RAW".to_string()];
+
+    let err = manager.validate_policy(&policy).await.unwrap_err();
+    assert!(err.to_string().contains("CAP_NET_RAW"), "{err}");
+    assert!(manager.add_policy(policy).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_effective_policy_shows_expanded_capabilities() -> Result<()> {
+    let manager = IsolationManager::new()?;
+    manager.assign_policy("vm-1", "isolated").await?;
+
+    let policy = manager.get_effective_policy("vm-1").await?;
+    let expected: Vec<String> = LINUX_CAPABILITIES
+        .iter()
+        .map(|cap| cap.to_string())
+        .collect();
+    assert_eq!(policy.capabilities.denied, expected);
+    Ok(())
+}
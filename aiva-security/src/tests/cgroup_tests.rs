@@ -0,0 +1,136 @@
+use crate::{IsolationManager, SecurityManager, SecurityPolicy, VmProcess, load_preset_policies};
+use aiva_core::{AivaError, Result};
+use std::path::{Path, PathBuf};
+
+/// A directory laid out like a cgroup v2 mount
+fn fake_cgroup_root(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("aiva-cgroup-{name}-{}", std::process::id()));
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("cgroup.controllers"), "cpuset cpu io memory pids").unwrap();
+    root
+}
+
+fn has_sys_admin() -> bool {
+    caps::has_cap(
+        None,
+        caps::CapSet::Effective,
+        caps::Capability::CAP_SYS_ADMIN,
+    )
+    .unwrap_or(false)
+}
+
+fn read(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap()
+}
+
+fn half_cpu_policy() -> SecurityPolicy {
+    let mut policy = load_preset_policies().remove("restricted").unwrap();
+    policy.name = "half-cpu".to_string();
+    policy.resource_limits.cpu_quota = Some(50);
+    policy
+}
+
+#[test]
+fn test_cgroup_settings_cover_every_limit() {
+    let limits = load_preset_policies()
+        .remove("restricted")
+        .unwrap()
+        .resource_limits;
+
+    let settings = limits.cgroup_settings(1, Some("8:0")).unwrap();
+    let settings: Vec<(&str, &str)> = settings
+        .iter()
+        .map(|(file, value)| (file.as_str(), value.as_str()))
+        .collect();
+    assert_eq!(
+        settings,
+        [
+            ("cpu.max", "50000 100000"),
+            ("memory.max", "4294967296"),
+            ("pids.max", "512"),
+            (
+                "io.max",
+                "8:0 rbps=104857600 wbps=104857600 riops=1000 wiops=1000"
+            ),
+        ]
+    );
+
+    // Without a device IO is left to the drive rate limiter
+    let settings = limits.cgroup_settings(1, None).unwrap();
+    assert!(!settings.iter().any(|(file, _)| file == "io.max"));
+}
+
+#[tokio::test]
+async fn test_half_cpu_policy_writes_cpu_max() -> Result<()> {
+    if !has_sys_admin() {
+        eprintln!("Skipping cgroup test without CAP_SYS_ADMIN");
+        return Ok(());
+    }
+    let root = fake_cgroup_root("apply");
+    let manager = IsolationManager::new()?.with_cgroup_root(&root);
+    manager
+        .register_vm_process(
+            "vm-1",
+            VmProcess {
+                pid: 4242,
+                vcpus: 1,
+                disk: None,
+            },
+        )
+        .await;
+
+    manager.apply_isolation("vm-1", &half_cpu_policy()).await?;
+
+    let cgroup = root.join("aiva").join("vm-1");
+    assert_eq!(read(&cgroup.join("cpu.max")), "50000 100000");
+    assert_eq!(read(&cgroup.join("pids.max")), "512");
+    assert_eq!(read(&cgroup.join("cgroup.procs")), "4242");
+    assert!(!cgroup.join("io.max").exists());
+    assert_eq!(
+        read(&root.join("aiva").join("cgroup.subtree_control")),
+        "+cpu +io +memory +pids"
+    );
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_missing_cgroup_v2_is_a_recoverable_error() -> Result<()> {
+    if !has_sys_admin() {
+        eprintln!("Skipping cgroup test without CAP_SYS_ADMIN");
+        return Ok(());
+    }
+    let root = fake_cgroup_root("v1");
+    std::fs::remove_file(root.join("cgroup.controllers"))?;
+    let manager = IsolationManager::new()?.with_cgroup_root(&root);
+    manager
+        .register_vm_process(
+            "vm-1",
+            VmProcess {
+                pid: 4242,
+                vcpus: 1,
+                disk: None,
+            },
+        )
+        .await;
+
+    let err = manager
+        .apply_isolation("vm-1", &half_cpu_policy())
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(
+            err,
+            AivaError::PlatformError {
+                recoverable: true,
+                ..
+            }
+        ),
+        "{err:?}"
+    );
+    assert!(!root.join("aiva").exists());
+
+    std::fs::remove_dir_all(&root)?;
+    Ok(())
+}
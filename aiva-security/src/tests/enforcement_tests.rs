@@ -0,0 +1,144 @@
+use crate::{
+    DriveRateLimiter, IOLimit, IOLimitStatus, IsolationManager, OpenFilesLimit, TokenBucket,
+    parse_drive_rate_limiter, parse_open_files_limit, verify_io_limit,
+};
+use serde_json::json;
+
+fn io_limit() -> IOLimit {
+    IOLimit {
+        read_bps: Some(100 * 1024 * 1024),
+        write_bps: Some(50 * 1024 * 1024),
+        read_iops: Some(1000),
+        write_iops: None,
+    }
+}
+
+fn readback(payload: serde_json::Value) -> Option<DriveRateLimiter> {
+    let vm_config = json!({
+        "drives": [
+            { "drive_id": "scratch", "rate_limiter": null },
+            { "drive_id": "rootfs", "rate_limiter": payload },
+        ]
+    });
+    parse_drive_rate_limiter(&vm_config, "rootfs").unwrap()
+}
+
+#[test]
+fn test_tighter_limit_becomes_the_drive_limiter() {
+    let limiter = io_limit().to_rate_limiter();
+    assert_eq!(
+        limiter.bandwidth,
+        Some(TokenBucket {
+            size: 50 * 1024 * 1024,
+            refill_time: 1000,
+            one_time_burst: None,
+        })
+    );
+    assert_eq!(limiter.ops.unwrap().per_second(), Some(1000));
+}
+
+#[test]
+fn test_matching_readback_is_enforced() {
+    // Same rates expressed with a different refill window
+    let readback = readback(json!({
+        "bandwidth": { "size": 5 * 1024 * 1024, "refill_time": 100 },
+        "ops": { "size": 500, "refill_time": 500, "one_time_burst": 2000 },
+    }));
+
+    let check = verify_io_limit(&io_limit(), readback.as_ref());
+    assert!(check.is_enforced());
+}
+
+#[test]
+fn test_drifted_readback_lists_differences() {
+    let readback = readback(json!({
+        "bandwidth": { "size": 100 * 1024 * 1024, "refill_time": 1000 },
+    }));
+
+    let check = verify_io_limit(&io_limit(), readback.as_ref());
+    let IOLimitStatus::Drifted { differences } = check.status else {
+        panic!("expected drift, got {:?}", check.status);
+    };
+    assert_eq!(
+        differences,
+        vec![
+            "bandwidth: expected 52428800 B/s, got 104857600 B/s",
+            "ops: expected 1000 ops/s, got unlimited",
+        ]
+    );
+}
+
+#[test]
+fn test_missing_limiter_is_reported() {
+    let check = verify_io_limit(&io_limit(), readback(serde_json::Value::Null).as_ref());
+    assert_eq!(check.status, IOLimitStatus::Missing);
+
+    let vm_config = json!({ "drives": [] });
+    assert!(parse_drive_rate_limiter(&vm_config, "rootfs").is_err());
+}
+
+#[tokio::test]
+async fn test_readback_is_part_of_enforcement_status() -> aiva_core::Result<()> {
+    let manager = IsolationManager::new()?;
+    manager.assign_policy("vm-1", "restricted").await?;
+
+    let check = manager.record_io_readback("vm-1", None).await?.unwrap();
+    assert_eq!(check.status, IOLimitStatus::Missing);
+
+    let status = manager.get_enforcement_status("vm-1").await?;
+    assert_eq!(status.policy, "restricted");
+    assert_eq!(status.io_limit.unwrap().status, IOLimitStatus::Missing);
+    Ok(())
+}
+
+#[test]
+fn test_open_files_limit_is_parsed_from_proc() {
+    let limits = "\
+Limit                     Soft Limit           Hard Limit           Units
+Max processes             63431                63431                processes
+Max open files            512                  1024                 files
+Max locked memory         8388608              8388608              bytes
+";
+    assert_eq!(
+        parse_open_files_limit(limits),
+        Some(OpenFilesLimit {
+            soft: 512,
+            hard: 1024
+        })
+    );
+
+    let unlimited = "Max open files            unlimited            unlimited            files\n";
+    assert_eq!(
+        parse_open_files_limit(unlimited).map(|limit| limit.hard),
+        Some(u64::MAX)
+    );
+    assert_eq!(parse_open_files_limit("Max processes 1 1 processes"), None);
+}
+
+#[tokio::test]
+async fn test_open_files_readback_is_part_of_enforcement_status() -> aiva_core::Result<()> {
+    let manager = IsolationManager::new()?;
+    manager.assign_policy("vm-1", "restricted").await?;
+
+    let loose = OpenFilesLimit {
+        soft: 512,
+        hard: 4096,
+    };
+    let check = manager
+        .record_open_files_readback("vm-1", Some(loose))
+        .await?
+        .unwrap();
+    assert_eq!(check.intended, 512);
+    assert!(!check.is_enforced());
+
+    let exact = OpenFilesLimit {
+        soft: 512,
+        hard: 512,
+    };
+    manager
+        .record_open_files_readback("vm-1", Some(exact))
+        .await?;
+    let status = manager.get_enforcement_status("vm-1").await?;
+    assert!(status.open_files.unwrap().is_enforced());
+    Ok(())
+}
@@ -0,0 +1,20 @@
+#[cfg(test)]
+mod audit_tests;
+#[cfg(test)]
+mod builder_tests;
+#[cfg(test)]
+mod capability_tests;
+#[cfg(all(test, target_os = "linux"))]
+mod cgroup_tests;
+#[cfg(test)]
+mod enforcement_tests;
+#[cfg(test)]
+mod network_tests;
+#[cfg(test)]
+mod oci_tests;
+#[cfg(test)]
+mod policy_tests;
+#[cfg(all(test, target_os = "linux"))]
+mod seccomp_tests;
+#[cfg(test)]
+mod validation_tests;
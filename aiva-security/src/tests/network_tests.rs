@@ -0,0 +1,119 @@
+use crate::{
+    IpRange, IsolationManager, PolicyManager, SecurityManager, load_preset_policies,
+    plan_network_policy,
+};
+use aiva_core::{NetworkConfig, Result};
+use std::net::IpAddr;
+
+fn bridge_network() -> NetworkConfig {
+    NetworkConfig {
+        guest_ip: "172.16.0.2".to_string(),
+        host_ip: "172.16.0.1".to_string(),
+        subnet: "172.16.0.0/24".to_string(),
+        gateway: "172.16.0.1".to_string(),
+        dns_servers: vec!["8.8.8.8".to_string()],
+        dns_search: vec![],
+        dns_overrides: Default::default(),
+        hostname: None,
+        dhcp_enabled: false,
+        port_mappings: vec![],
+        audit_egress: false,
+    }
+}
+
+fn ip(value: &str) -> IpAddr {
+    value.parse().unwrap()
+}
+
+#[test]
+fn test_ip_range_contains() {
+    let range = IpRange::parse("10.0.0.0/8").unwrap();
+    assert!(range.contains(ip("10.1.2.3")));
+    assert!(!range.contains(ip("172.16.0.1")));
+    assert!(!range.contains(ip("::1")));
+
+    assert!(
+        IpRange::parse("0.0.0.0/0")
+            .unwrap()
+            .contains(ip("127.0.0.1"))
+    );
+    assert!(
+        IpRange::parse("127.0.0.1")
+            .unwrap()
+            .contains(ip("127.0.0.1"))
+    );
+    assert!(IpRange::parse("10.0.0.0/33").is_err());
+    assert!(IpRange::parse("not-an-ip").is_err());
+}
+
+#[tokio::test]
+async fn test_restricted_policy_keeps_gateway_reachable() -> Result<()> {
+    let manager = IsolationManager::new()?;
+    manager.register_vm_network("vm-1", bridge_network()).await;
+
+    let policy = manager.get_policy("restricted").await?;
+    manager.apply_isolation("vm-1", &policy).await?;
+
+    let plan = manager.get_network_plan("vm-1").await.unwrap();
+    assert!(plan.permits(ip("172.16.0.1")));
+    assert!(plan.permits(ip("127.0.0.1")));
+    assert!(!plan.permits(ip("10.0.0.5")));
+    assert!(!plan.permits(ip("192.168.1.1")));
+    Ok(())
+}
+
+#[test]
+fn test_block_all_still_exempts_gateway_and_loopback() {
+    let policies = load_preset_policies();
+    let isolated = &policies["isolated"].network_policy;
+
+    let plan = plan_network_policy(isolated, Some(&bridge_network())).unwrap();
+    assert!(plan.permits(ip("172.16.0.1")));
+    assert!(plan.permits(ip("127.0.0.1")));
+    assert!(!plan.permits(ip("1.1.1.1")));
+    assert_eq!(
+        plan.conflicts,
+        vec!["0.0.0.0/0 covers 127.0.0.1", "0.0.0.0/0 covers 172.16.0.1"]
+    );
+}
+
+#[test]
+fn test_mcp_server_loopback_block_is_overridden() {
+    let policy = crate::policy::create_mcp_policy();
+
+    let plan = plan_network_policy(&policy.network_policy, None).unwrap();
+    assert!(plan.permits(ip("127.0.0.1")));
+    assert_eq!(plan.conflicts, vec!["127.0.0.1/32 covers 127.0.0.1"]);
+}
+
+#[tokio::test]
+async fn test_policy_with_malformed_blocked_range_is_rejected() -> Result<()> {
+    let manager = IsolationManager::new()?;
+    let mut policy = load_preset_policies().remove("restricted").unwrap();
+    policy.network_policy.blocked_ips = vec!["10.0.0.0/8".to_string(), "10.0.0/8".to_string()];
+
+    let err = manager.validate_policy(&policy).await.unwrap_err();
+    assert!(err.to_string().contains("10.0.0/8"), "{err}");
+
+    let dir = std::env::temp_dir().join(format!("aiva-policy-network-{}", std::process::id()));
+    let err = PolicyManager::new(dir)?
+        .validate_policy(&policy)
+        .unwrap_err();
+    assert!(err.to_string().contains("10.0.0/8"), "{err}");
+    Ok(())
+}
+
+#[test]
+fn test_port_rules_only_accept_tcp_and_udp() {
+    let mut network = load_preset_policies()
+        .remove("restricted")
+        .unwrap()
+        .network_policy;
+    network.blocked_ips = vec!["2001:db8::/32".to_string(), "192.0.2.7".to_string()];
+    network.allowed_ports[1].protocol = "udp".to_string();
+    assert!(network.validate().is_ok());
+
+    network.allowed_ports[0].protocol = "icmp".to_string();
+    let err = network.validate().unwrap_err();
+    assert!(err.to_string().contains("'icmp' for port 443"), "{err}");
+}
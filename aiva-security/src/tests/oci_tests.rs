@@ -0,0 +1,80 @@
+use crate::{CompareOp, FilterAction, SecurityPolicy, SyscallFilter, load_preset_policies};
+use serde_json::json;
+
+/// A profile in the shape runc and podman ship
+fn oci_profile() -> serde_json::Value {
+    json!({
+        "defaultAction": "SCMP_ACT_KILL_PROCESS",
+        "syscalls": [
+            {
+                "names": ["read", "write", "close", "exit_group"],
+                "action": "SCMP_ACT_ALLOW"
+            },
+            {
+                "names": ["personality"],
+                "action": "SCMP_ACT_ALLOW",
+                "args": [
+                    { "index": 0, "value": 0, "valueTwo": 0, "op": "SCMP_CMP_EQ" }
+                ]
+            },
+            {
+                "names": ["clone"],
+                "action": "SCMP_ACT_ALLOW",
+                "args": [
+                    { "index": 0, "value": 2114060288, "valueTwo": 0, "op": "SCMP_CMP_MASKED_EQ" }
+                ]
+            },
+            {
+                "names": ["ptrace"],
+                "action": "SCMP_ACT_LOG"
+            }
+        ]
+    })
+}
+
+#[test]
+fn test_oci_profile_round_trips_unchanged() {
+    let profile = oci_profile();
+
+    let policy = SecurityPolicy::from_oci_seccomp("runc-default", &profile).unwrap();
+    assert_eq!(policy.name, "runc-default");
+    assert_eq!(policy.to_oci_seccomp(), profile);
+
+    let filter = policy.syscall_filter.unwrap();
+    assert_eq!(filter.default_action, FilterAction::Kill);
+    assert_eq!(filter.rules.len(), 7);
+    let clone = filter.rules.iter().find(|r| r.syscall == "clone").unwrap();
+    let condition = &clone.conditions.as_ref().unwrap()[0];
+    assert_eq!(condition.operation, CompareOp::MaskedEqual(2114060288));
+    assert_eq!(condition.value, 0);
+}
+
+#[test]
+fn test_restricted_preset_exports_kill_rules() {
+    let restricted = load_preset_policies().remove("restricted").unwrap();
+
+    assert_eq!(
+        restricted.to_oci_seccomp(),
+        json!({
+            "defaultAction": "SCMP_ACT_ALLOW",
+            "syscalls": [
+                { "names": ["ptrace", "mount"], "action": "SCMP_ACT_KILL_PROCESS" }
+            ]
+        })
+    );
+    assert_eq!(
+        SecurityPolicy::default().to_oci_seccomp(),
+        json!({ "defaultAction": "SCMP_ACT_ALLOW", "syscalls": [] })
+    );
+}
+
+#[test]
+fn test_oci_profile_with_unsupported_action_is_rejected() {
+    let profile = json!({
+        "defaultAction": "SCMP_ACT_ERRNO",
+        "syscalls": []
+    });
+
+    let err = SyscallFilter::from_oci_seccomp(&profile).unwrap_err();
+    assert!(err.to_string().contains("SCMP_ACT_ERRNO"), "{err}");
+}
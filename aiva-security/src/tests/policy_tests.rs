@@ -0,0 +1,550 @@
+use crate::policy::{action_strictness, unconditional_action};
+use crate::{
+    CapabilitySet, CompareOp, Condition, Direction, FilterAction, IOLimit, IsolationLevel,
+    NetworkPolicy, NetworkRateLimit, PolicyManager, PortRule, ResourceLimits, SecurityPolicy,
+    SyscallFilter, SyscallRule, load_preset_policies, merge_policy, resolve_extends,
+};
+use aiva_core::AivaError;
+use serde_json::json;
+use std::collections::HashMap;
+
+const CAPABILITIES: [&str; 5] = [
+    "CAP_SYS_ADMIN",
+    "CAP_NET_ADMIN",
+    "CAP_SYS_PTRACE",
+    "CAP_CHOWN",
+    "CAP_NET_BIND_SERVICE",
+];
+const SYSCALLS: [&str; 6] = ["read", "write", "mount", "ptrace", "execve", "clone"];
+const ACTIONS: [FilterAction; 4] = [
+    FilterAction::Allow,
+    FilterAction::Log,
+    FilterAction::Trap,
+    FilterAction::Kill,
+];
+
+/// Deterministic xorshift so failures reproduce
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn chance(&mut self) -> bool {
+        self.below(2) == 0
+    }
+
+    fn maybe<T>(&mut self, value: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if self.chance() {
+            Some(value(self))
+        } else {
+            None
+        }
+    }
+
+    fn pick<T: Clone>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize].clone()
+    }
+
+    fn subset(&mut self, items: &[&str]) -> Vec<String> {
+        items
+            .iter()
+            .filter(|_| self.below(3) == 0)
+            .map(|item| item.to_string())
+            .collect()
+    }
+}
+
+fn random_policy(rng: &mut Rng, name: &str) -> SecurityPolicy {
+    let mut denied = rng.subset(&CAPABILITIES);
+    let allowed = if rng.below(5) == 0 {
+        denied.push("ALL".to_string());
+        Vec::new()
+    } else {
+        rng.subset(&CAPABILITIES)
+            .into_iter()
+            .filter(|cap| !denied.contains(cap))
+            .collect()
+    };
+
+    let syscall_filter = rng.maybe(|rng| SyscallFilter {
+        default_action: rng.pick(&ACTIONS),
+        rules: (0..rng.below(4))
+            .map(|_| SyscallRule {
+                syscall: rng.pick(&SYSCALLS).to_string(),
+                action: rng.pick(&ACTIONS),
+                conditions: None,
+            })
+            .collect(),
+    });
+
+    let ports: Vec<PortRule> = [443, 80, 8080]
+        .into_iter()
+        .filter(|_| rng.chance())
+        .map(|port| PortRule {
+            port,
+            protocol: "tcp".to_string(),
+            direction: Direction::Outbound,
+        })
+        .collect();
+
+    SecurityPolicy {
+        name: name.to_string(),
+        extends: None,
+        isolation_level: rng.pick(&[
+            IsolationLevel::None,
+            IsolationLevel::Basic,
+            IsolationLevel::Enhanced,
+            IsolationLevel::Maximum,
+        ]),
+        capabilities: CapabilitySet { allowed, denied },
+        syscall_filter,
+        resource_limits: ResourceLimits {
+            cpu_quota: rng.maybe(|rng| 1 + rng.below(100) as u32),
+            memory_limit: rng.maybe(|rng| (1 + rng.below(16)) << 30),
+            pids_limit: rng.maybe(|rng| 1 + rng.below(2048) as u32),
+            open_files: rng.maybe(|rng| 1 + rng.below(2048) as u32),
+            io_bandwidth: rng.maybe(|rng| IOLimit {
+                read_bps: rng.maybe(|rng| 1 + rng.below(1 << 30)),
+                write_bps: rng.maybe(|rng| 1 + rng.below(1 << 30)),
+                read_iops: rng.maybe(|rng| 1 + rng.below(5000)),
+                write_iops: rng.maybe(|rng| 1 + rng.below(5000)),
+            }),
+        },
+        network_policy: NetworkPolicy {
+            allow_outbound: rng.chance(),
+            allowed_ports: ports,
+            blocked_ips: rng.subset(&["10.0.0.0/8", "192.168.0.0/16", "0.0.0.0/0"]),
+            rate_limit: rng.maybe(|rng| NetworkRateLimit {
+                bandwidth_mbps: 1 + rng.below(1000) as u32,
+                connections_per_second: 1 + rng.below(100) as u32,
+            }),
+        },
+    }
+}
+
+/// Random policies followed by the presets
+fn sample_policies() -> Vec<SecurityPolicy> {
+    let mut rng = Rng(0x5eed_1463);
+    let mut policies: Vec<SecurityPolicy> = (0..40)
+        .map(|i| random_policy(&mut rng, &format!("random-{i}")))
+        .collect();
+    policies.extend(load_preset_policies().into_values());
+    policies
+}
+
+fn limit_kept<T: Ord + std::fmt::Debug>(merged: Option<T>, input: Option<T>, what: &str) {
+    if let Some(input) = input {
+        match merged {
+            Some(merged) => assert!(merged <= input, "{what}: {merged:?} > {input:?}"),
+            None => panic!("{what}: limit {input:?} was dropped"),
+        }
+    }
+}
+
+fn assert_at_least_as_restrictive(merged: &SecurityPolicy, input: &SecurityPolicy) {
+    let context = format!("{} vs {}", merged.name, input.name);
+
+    assert!(
+        merged.isolation_level >= input.isolation_level,
+        "{context}: isolation"
+    );
+
+    for cap in &input.capabilities.denied {
+        assert!(
+            merged.capabilities.denied.contains(cap),
+            "{context}: {cap} no longer denied"
+        );
+    }
+    for cap in &merged.capabilities.allowed {
+        assert!(
+            input.capabilities.allowed.contains(cap),
+            "{context}: {cap} newly allowed"
+        );
+        assert!(
+            !merged.capabilities.denied.contains(cap),
+            "{context}: {cap} both allowed and denied"
+        );
+    }
+    if merged.capabilities.denied.iter().any(|cap| cap == "ALL") {
+        assert!(merged.capabilities.allowed.is_empty(), "{context}: ALL");
+    }
+
+    let (limits, input_limits) = (&merged.resource_limits, &input.resource_limits);
+    limit_kept(limits.cpu_quota, input_limits.cpu_quota, "cpu_quota");
+    limit_kept(
+        limits.memory_limit,
+        input_limits.memory_limit,
+        "memory_limit",
+    );
+    limit_kept(limits.pids_limit, input_limits.pids_limit, "pids_limit");
+    limit_kept(limits.open_files, input_limits.open_files, "open_files");
+    if let Some(input_io) = &input_limits.io_bandwidth {
+        let io = limits.io_bandwidth.as_ref().expect("io_bandwidth dropped");
+        limit_kept(io.read_bps, input_io.read_bps, "read_bps");
+        limit_kept(io.write_bps, input_io.write_bps, "write_bps");
+        limit_kept(io.read_iops, input_io.read_iops, "read_iops");
+        limit_kept(io.write_iops, input_io.write_iops, "write_iops");
+    }
+
+    let (network, input_network) = (&merged.network_policy, &input.network_policy);
+    if !input_network.allow_outbound {
+        assert!(!network.allow_outbound, "{context}: outbound allowed");
+        for rule in &network.allowed_ports {
+            assert!(
+                input_network.allowed_ports.contains(rule),
+                "{context}: port {} newly allowed",
+                rule.port
+            );
+        }
+    }
+    for ip in &input_network.blocked_ips {
+        assert!(
+            network.blocked_ips.contains(ip),
+            "{context}: {ip} unblocked"
+        );
+    }
+    if let Some(input_rate) = &input_network.rate_limit {
+        let rate = network.rate_limit.as_ref().expect("rate limit dropped");
+        assert!(rate.bandwidth_mbps <= input_rate.bandwidth_mbps);
+        assert!(rate.connections_per_second <= input_rate.connections_per_second);
+    }
+
+    for syscall in SYSCALLS {
+        let merged_action = unconditional_action(merged.syscall_filter.as_ref(), syscall);
+        let input_action = unconditional_action(input.syscall_filter.as_ref(), syscall);
+        assert!(
+            action_strictness(merged_action) >= action_strictness(input_action),
+            "{context}: {syscall} is {merged_action:?}, was {input_action:?}"
+        );
+    }
+}
+
+#[test]
+fn test_merge_is_never_less_restrictive_than_its_inputs() {
+    let policies = sample_policies();
+    for base in &policies {
+        for overlay in &policies {
+            let merged = merge_policy(base, overlay);
+            assert_at_least_as_restrictive(&merged, base);
+            assert_at_least_as_restrictive(&merged, overlay);
+        }
+    }
+}
+
+#[test]
+fn test_merge_of_resource_limits_is_associative_and_commutative() {
+    let policies = sample_policies();
+    for a in policies.iter().step_by(3) {
+        for b in policies.iter().step_by(2) {
+            assert_eq!(
+                merge_policy(a, b).resource_limits,
+                merge_policy(b, a).resource_limits
+            );
+            for c in policies.iter().step_by(5) {
+                let left = merge_policy(&merge_policy(a, b), c);
+                let right = merge_policy(a, &merge_policy(b, c));
+                assert_eq!(left.resource_limits, right.resource_limits);
+            }
+        }
+    }
+}
+
+/// Every level, least restrictive first
+const LEVELS: [IsolationLevel; 4] = [
+    IsolationLevel::None,
+    IsolationLevel::Basic,
+    IsolationLevel::Enhanced,
+    IsolationLevel::Maximum,
+];
+
+#[test]
+fn test_isolation_levels_order_by_restrictiveness() {
+    for (i, a) in LEVELS.iter().enumerate() {
+        for (j, b) in LEVELS.iter().enumerate() {
+            assert_eq!(a.cmp(b), i.cmp(&j), "{a:?} vs {b:?}");
+            assert_eq!(a.partial_cmp(b), Some(i.cmp(&j)), "{a:?} vs {b:?}");
+        }
+    }
+}
+
+#[test]
+fn test_merge_keeps_the_higher_isolation_level_either_way() {
+    let base = load_preset_policies().remove("standard").unwrap();
+    for a in LEVELS {
+        for b in LEVELS {
+            let mut first = base.clone();
+            first.isolation_level = a;
+            let mut second = base.clone();
+            second.isolation_level = b;
+
+            let merged = merge_policy(&first, &second).isolation_level;
+            assert_eq!(merged, merge_policy(&second, &first).isolation_level);
+            assert_eq!(merged, a.max(b), "{a:?} merged with {b:?}");
+        }
+    }
+}
+
+#[test]
+fn test_merge_with_all_denied_clears_allowed() {
+    let presets = load_preset_policies();
+    let mut permissive = presets["trusted"].clone();
+    permissive.capabilities.allowed = vec!["CAP_NET_ADMIN".to_string()];
+
+    for merged in [
+        merge_policy(&permissive, &presets["isolated"]),
+        merge_policy(&presets["isolated"], &permissive),
+    ] {
+        assert!(merged.capabilities.denied.contains(&"ALL".to_string()));
+        assert!(merged.capabilities.allowed.is_empty());
+    }
+}
+
+#[test]
+fn test_merge_keeps_denied_caps_from_both_sides() {
+    let presets = load_preset_policies();
+    let mut overlay = presets["trusted"].clone();
+    overlay.capabilities.allowed = vec!["CAP_SYS_PTRACE".to_string()];
+
+    let mut base = presets["restricted"].clone();
+    base.capabilities.allowed = vec!["CAP_CHOWN".to_string()];
+
+    let merged = merge_policy(&base, &overlay);
+    assert!(
+        merged
+            .capabilities
+            .denied
+            .contains(&"CAP_SYS_PTRACE".to_string())
+    );
+    assert!(merged.capabilities.allowed.is_empty());
+}
+
+#[tokio::test]
+async fn test_merging_restricted_and_mcp_server_keeps_their_kills() {
+    let dir = std::env::temp_dir().join(format!("aiva-policy-syscalls-{}", std::process::id()));
+    let mut manager = PolicyManager::new(dir.clone()).unwrap();
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    manager
+        .create_policy(load_preset_policies().remove("restricted").unwrap())
+        .await
+        .unwrap();
+    manager
+        .create_policy(crate::policy::create_mcp_policy())
+        .await
+        .unwrap();
+
+    for (base, overlay) in [("restricted", "mcp-server"), ("mcp-server", "restricted")] {
+        let merged = manager.merge_policies(base, overlay).unwrap();
+        let filter = merged.syscall_filter.as_ref().unwrap();
+        assert_eq!(filter.default_action, FilterAction::Allow);
+        for syscall in ["mount", "umount", "ptrace"] {
+            assert_eq!(
+                unconditional_action(Some(filter), syscall),
+                FilterAction::Kill,
+                "{base} + {overlay}: {syscall}"
+            );
+        }
+        // ptrace and mount are killed by both, and listed once
+        assert_eq!(filter.rules.len(), 3, "{:?}", filter.rules);
+    }
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[test]
+fn test_merge_keeps_conditional_rules_once() {
+    let trap_raw_sockets = SyscallRule {
+        syscall: "socket".to_string(),
+        action: FilterAction::Trap,
+        conditions: Some(vec![Condition {
+            arg_index: 1,
+            operation: CompareOp::MaskedEqual(0xf),
+            value: 3,
+        }]),
+    };
+    let mut base = load_preset_policies().remove("restricted").unwrap();
+    base.syscall_filter
+        .as_mut()
+        .unwrap()
+        .rules
+        .push(trap_raw_sockets.clone());
+    let mut overlay = base.clone();
+    overlay.name = "hardened".to_string();
+    overlay.syscall_filter.as_mut().unwrap().default_action = FilterAction::Kill;
+
+    let merged = merge_policy(&base, &overlay);
+    let filter = merged.syscall_filter.unwrap();
+    assert_eq!(filter.default_action, FilterAction::Kill);
+    // Trapping is milder than the merged default, so the rule goes
+    assert!(!filter.rules.contains(&trap_raw_sockets));
+
+    let merged = merge_policy(&base, &base);
+    let rules = merged.syscall_filter.unwrap().rules;
+    assert_eq!(
+        rules
+            .iter()
+            .filter(|rule| **rule == trap_raw_sockets)
+            .count(),
+        1,
+        "{rules:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_merging_a_policy_with_itself_is_rejected() {
+    let dir = std::env::temp_dir().join(format!("aiva-policy-merge-{}", std::process::id()));
+    let mut manager = PolicyManager::new(dir.clone()).unwrap();
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    for policy in load_preset_policies().into_values() {
+        manager.create_policy(policy).await.unwrap();
+    }
+
+    let err = manager
+        .merge_policies("restricted", "restricted")
+        .unwrap_err();
+    assert!(err.to_string().contains("itself"));
+
+    let merged = manager.merge_policies("restricted", "isolated").unwrap();
+    assert_eq!(merged.name, "restricted-isolated");
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_policy_extending_a_preset_only_changes_what_it_sets() {
+    let dir = std::env::temp_dir().join(format!("aiva-policy-extends-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    tokio::fs::write(
+        dir.join("my-mcp.json"),
+        r#"{"name":"my-mcp","extends":"restricted","resource_limits":{"cpu_quota":30}}"#,
+    )
+    .await
+    .unwrap();
+
+    let mut manager = PolicyManager::new(dir.clone()).unwrap();
+    manager.load_policies().await.unwrap();
+    let policy = manager.get_policy("my-mcp").unwrap();
+
+    let mut expected = load_preset_policies().remove("restricted").unwrap();
+    expected.name = "my-mcp".to_string();
+    expected.extends = Some("restricted".to_string());
+    expected.resource_limits.cpu_quota = Some(30);
+    assert_eq!(
+        serde_json::to_value(&policy).unwrap(),
+        serde_json::to_value(&expected).unwrap()
+    );
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
+
+#[test]
+fn test_extends_chain_merges_capabilities() {
+    let base = SecurityPolicy {
+        name: "base".to_string(),
+        isolation_level: IsolationLevel::Enhanced,
+        capabilities: CapabilitySet {
+            allowed: vec!["CAP_CHOWN".to_string(), "CAP_NET_BIND_SERVICE".to_string()],
+            denied: vec!["CAP_SYS_ADMIN".to_string()],
+        },
+        ..SecurityPolicy::default()
+    };
+    let documents = HashMap::from([
+        ("base".to_string(), serde_json::to_value(&base).unwrap()),
+        (
+            "middle".to_string(),
+            json!({ "name": "middle", "extends": "base", "isolation_level": "Maximum" }),
+        ),
+        (
+            "child".to_string(),
+            json!({
+                "name": "child",
+                "extends": "middle",
+                "capabilities": {
+                    "allowed": ["CAP_CHOWN", "CAP_NET_RAW"],
+                    "denied": ["CAP_SYS_PTRACE"]
+                }
+            }),
+        ),
+    ]);
+
+    let child = resolve_extends("child", &documents).unwrap();
+    assert_eq!(child.isolation_level, IsolationLevel::Maximum);
+    assert_eq!(child.capabilities.allowed, ["CAP_CHOWN"]);
+    assert_eq!(
+        child.capabilities.denied,
+        ["CAP_SYS_ADMIN", "CAP_SYS_PTRACE"]
+    );
+}
+
+#[test]
+fn test_extends_cycle_is_rejected() {
+    let documents = HashMap::from([
+        ("a".to_string(), json!({ "name": "a", "extends": "b" })),
+        ("b".to_string(), json!({ "name": "b", "extends": "a" })),
+    ]);
+
+    let err = resolve_extends("a", &documents).unwrap_err();
+    assert!(matches!(err, AivaError::SecurityError(_)), "{err:?}");
+    assert!(err.to_string().contains("a -> b -> a"), "{err}");
+
+    let missing = HashMap::from([("c".to_string(), json!({ "name": "c", "extends": "nope" }))]);
+    assert!(resolve_extends("c", &missing).is_err());
+}
+
+/// Poll `manager` until `check` holds, for at most five seconds
+async fn wait_for(manager: &PolicyManager, check: impl Fn(&PolicyManager) -> bool) -> bool {
+    for _ in 0..100 {
+        if check(manager) {
+            return true;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_watch_reloads_changed_policy_files() {
+    let dir = std::env::temp_dir().join(format!("aiva-policy-watch-{}", std::process::id()));
+    tokio::fs::create_dir_all(&dir).await.unwrap();
+    let mut manager = PolicyManager::new(dir.clone()).unwrap();
+    manager.load_policies().await.unwrap();
+    manager.watch().unwrap();
+
+    let policy = SecurityPolicy {
+        name: "hot".to_string(),
+        ..SecurityPolicy::default()
+    };
+    tokio::fs::write(
+        dir.join("hot.json"),
+        serde_json::to_string(&policy).unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(wait_for(&manager, |m| m.get_policy("hot").is_ok()).await);
+
+    // A broken edit keeps the policy; events arrive in order, so once the
+    // next file is loaded the broken one has been seen
+    tokio::fs::write(dir.join("hot.json"), "{").await.unwrap();
+    tokio::fs::write(
+        dir.join("child.json"),
+        r#"{"name":"child","extends":"restricted","resource_limits":{"cpu_quota":30}}"#,
+    )
+    .await
+    .unwrap();
+    assert!(wait_for(&manager, |m| m.get_policy("child").is_ok()).await);
+    assert!(manager.get_policy("hot").is_ok());
+
+    tokio::fs::remove_file(dir.join("hot.json")).await.unwrap();
+    assert!(wait_for(&manager, |m| m.get_policy("hot").is_err()).await);
+    assert_eq!(manager.list_policies(), vec!["child".to_string()]);
+
+    tokio::fs::remove_dir_all(&dir).await.unwrap();
+}
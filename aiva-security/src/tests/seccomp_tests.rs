@@ -0,0 +1,199 @@
+use crate::{
+    BpfProgram, CompareOp, Condition, FilterAction, SyscallFilter, SyscallRule, TargetArch,
+    compile_filter, host_arch, replaces_firecracker_filter, syscall_number,
+    write_firecracker_filter,
+};
+use aiva_core::Result;
+use nix::libc;
+use nix::sys::signal::Signal;
+use nix::sys::wait::{WaitStatus, waitpid};
+use nix::unistd::{ForkResult, fork};
+
+fn restricted_filter() -> SyscallFilter {
+    crate::load_preset_policies()
+        .remove("restricted")
+        .unwrap()
+        .syscall_filter
+        .unwrap()
+}
+
+fn rule(syscall: &str, action: FilterAction, conditions: Option<Vec<Condition>>) -> SyscallRule {
+    SyscallRule {
+        syscall: syscall.to_string(),
+        action,
+        conditions,
+    }
+}
+
+/// Run `call` in a child process confined by `program` and report how the
+/// child ended
+fn run_confined(program: &BpfProgram, call: impl FnOnce()) -> WaitStatus {
+    // SAFETY: the child only installs the filter, makes raw syscalls and exits
+    match unsafe { fork() }.unwrap() {
+        ForkResult::Child => {
+            let code = match seccompiler::apply_filter(program) {
+                Ok(()) => {
+                    call();
+                    0
+                }
+                Err(_) => 2,
+            };
+            unsafe { libc::_exit(code) }
+        }
+        ForkResult::Parent { child } => waitpid(child, None).unwrap(),
+    }
+}
+
+#[test]
+fn test_restricted_preset_kills_ptrace() -> Result<()> {
+    let program = compile_filter(&restricted_filter(), host_arch()?)?;
+
+    let status = run_confined(&program, || unsafe {
+        libc::ptrace(libc::PTRACE_TRACEME, 0, 0, 0);
+    });
+    assert!(
+        matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+        "{status:?}"
+    );
+
+    let status = run_confined(&program, || unsafe {
+        libc::getpid();
+    });
+    assert!(matches!(status, WaitStatus::Exited(_, 0)), "{status:?}");
+    Ok(())
+}
+
+#[test]
+fn test_masked_condition_matches_only_masked_bits() -> Result<()> {
+    let filter = SyscallFilter {
+        default_action: FilterAction::Allow,
+        rules: vec![rule(
+            "umask",
+            FilterAction::Kill,
+            Some(vec![Condition {
+                arg_index: 0,
+                operation: CompareOp::MaskedEqual(0o700),
+                value: 0o700,
+            }]),
+        )],
+    };
+    let program = compile_filter(&filter, host_arch()?)?;
+
+    let status = run_confined(&program, || unsafe {
+        libc::umask(0o077);
+    });
+    assert!(matches!(status, WaitStatus::Exited(_, 0)), "{status:?}");
+
+    let status = run_confined(&program, || unsafe {
+        libc::umask(0o722);
+    });
+    assert!(
+        matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+        "{status:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_strictest_matching_rule_wins() -> Result<()> {
+    let filter = SyscallFilter {
+        default_action: FilterAction::Kill,
+        rules: vec![
+            rule("exit_group", FilterAction::Allow, None),
+            rule("umask", FilterAction::Allow, None),
+            rule(
+                "umask",
+                FilterAction::Kill,
+                Some(vec![Condition {
+                    arg_index: 0,
+                    operation: CompareOp::Equal,
+                    value: 0o777,
+                }]),
+            ),
+        ],
+    };
+    let program = compile_filter(&filter, host_arch()?)?;
+
+    let status = run_confined(&program, || unsafe {
+        libc::umask(0o022);
+    });
+    assert!(matches!(status, WaitStatus::Exited(_, 0)), "{status:?}");
+
+    let status = run_confined(&program, || unsafe {
+        libc::umask(0o777);
+    });
+    assert!(
+        matches!(status, WaitStatus::Signaled(_, Signal::SIGSYS, _)),
+        "{status:?}"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_unknown_syscalls_are_all_listed() {
+    let filter = SyscallFilter {
+        default_action: FilterAction::Allow,
+        rules: vec![
+            rule("ptrace", FilterAction::Kill, None),
+            rule("frobnicate", FilterAction::Kill, None),
+            rule("open_sesame", FilterAction::Trap, None),
+        ],
+    };
+
+    let err = compile_filter(&filter, TargetArch::x86_64)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("frobnicate, open_sesame"), "{err}");
+    assert!(!err.contains("ptrace"), "{err}");
+}
+
+#[test]
+fn test_syscall_names_resolve_per_arch() -> Result<()> {
+    assert_eq!(syscall_number("ptrace", TargetArch::x86_64), Some(101));
+    assert_eq!(syscall_number("ptrace", TargetArch::aarch64), Some(117));
+    // aarch64 only has openat
+    assert_eq!(syscall_number("open", TargetArch::aarch64), None);
+
+    for arch in [TargetArch::x86_64, TargetArch::aarch64] {
+        assert!(!compile_filter(&restricted_filter(), arch)?.is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_firecracker_filter_file_covers_every_thread() -> Result<()> {
+    let program = compile_filter(&restricted_filter(), TargetArch::x86_64)?;
+    let path = std::env::temp_dir().join(format!("aiva-seccomp-{}.bpf", std::process::id()));
+
+    write_firecracker_filter(&program, &path)?;
+
+    let bytes = std::fs::read(&path)?;
+    std::fs::remove_file(&path)?;
+    let word = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap()) as usize;
+    assert_eq!(word(0), 3);
+    let mut at = 8;
+    let mut threads = Vec::new();
+    for _ in 0..3 {
+        let name_len = word(at);
+        threads.push(String::from_utf8(bytes[at + 8..at + 8 + name_len].to_vec()).unwrap());
+        at += 8 + name_len;
+        assert_eq!(word(at), program.len());
+        at += 8 + program.len() * 8;
+    }
+    assert_eq!(threads, ["api", "vcpu", "vmm"]);
+    assert_eq!(at, bytes.len());
+    Ok(())
+}
+
+#[test]
+fn test_restricted_preset_never_replaces_the_firecracker_filter() {
+    // The api, vmm and vcpu threads would otherwise run under a filter that
+    // allows everything the policy does not list
+    let mut filter = restricted_filter();
+    assert!(!replaces_firecracker_filter(&filter));
+
+    filter.default_action = FilterAction::Log;
+    assert!(!replaces_firecracker_filter(&filter));
+    filter.default_action = FilterAction::Kill;
+    assert!(replaces_firecracker_filter(&filter));
+}
@@ -0,0 +1,65 @@
+use crate::{load_preset_policies, validate_cache_strategy};
+use aiva_core::{CacheStrategy, StorageConfig};
+
+fn storage(cache_strategy: CacheStrategy) -> StorageConfig {
+    StorageConfig {
+        cache_strategy,
+        additional_drives: vec![],
+    }
+}
+
+#[test]
+fn test_maximum_isolation_rejects_unsafe_cache() {
+    let presets = load_preset_policies();
+    let isolated = &presets["isolated"];
+
+    let result = validate_cache_strategy(
+        Some(isolated),
+        &storage(CacheStrategy::Unsafe),
+        false,
+        false,
+    );
+    assert!(result.is_err());
+
+    // Explicit override is honored
+    assert!(
+        validate_cache_strategy(Some(isolated), &storage(CacheStrategy::Unsafe), false, true)
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_production_rejects_unsafe_cache() {
+    let presets = load_preset_policies();
+    let standard = &presets["standard"];
+
+    assert!(
+        validate_cache_strategy(Some(standard), &storage(CacheStrategy::Unsafe), true, false)
+            .is_err()
+    );
+    assert!(validate_cache_strategy(None, &storage(CacheStrategy::Unsafe), true, false).is_err());
+    assert!(
+        validate_cache_strategy(
+            Some(standard),
+            &storage(CacheStrategy::Unsafe),
+            false,
+            false
+        )
+        .is_ok()
+    );
+}
+
+#[test]
+fn test_writeback_always_allowed() {
+    let presets = load_preset_policies();
+
+    assert!(
+        validate_cache_strategy(
+            Some(&presets["isolated"]),
+            &storage(CacheStrategy::Writeback),
+            true,
+            false
+        )
+        .is_ok()
+    );
+}
@@ -0,0 +1,40 @@
+use crate::{IsolationLevel, SecurityPolicy};
+use aiva_core::{AivaError, CacheStrategy, Result, StorageConfig};
+use tracing::warn;
+
+/// Cross-checks a VM's storage settings against its security posture.
+///
+/// The `Unsafe` cache strategy skips flushes and can lose or corrupt guest data
+/// on host crash, so it is rejected for production workloads and for policies
+/// with `Maximum` isolation unless explicitly overridden.
+pub fn validate_cache_strategy(
+    policy: Option<&SecurityPolicy>,
+    storage: &StorageConfig,
+    production: bool,
+    allow_unsafe_cache: bool,
+) -> Result<()> {
+    if !matches!(storage.cache_strategy, CacheStrategy::Unsafe) {
+        return Ok(());
+    }
+
+    let reason = match policy {
+        Some(policy) if policy.isolation_level == IsolationLevel::Maximum => Some(format!(
+            "policy '{}' requires Maximum isolation",
+            policy.name
+        )),
+        _ if production => Some("production mode is enabled".to_string()),
+        _ => None,
+    };
+
+    match reason {
+        Some(reason) if allow_unsafe_cache => {
+            warn!("Using unsafe cache strategy although {}", reason);
+            Ok(())
+        }
+        Some(reason) => Err(AivaError::SecurityError(format!(
+            "Cache strategy 'unsafe' is not allowed because {reason}; \
+             use 'writeback' or pass --allow-unsafe-cache to override"
+        ))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,66 @@
+//! Reserving disk space for non-sparse volumes.
+//!
+//! `set_len` only moves the end of a file, which leaves it sparse on every
+//! common filesystem: a "preallocated" volume then claims its blocks as the
+//! guest writes and can fail halfway once the host disk is full.
+//! [`preallocate`] asks the filesystem for the blocks up front with
+//! `fallocate(2)` and writes zeros where that is not supported.
+
+use aiva_core::{AivaError, Result};
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+
+/// Bytes written per call when zero-filling
+const ZERO_CHUNK: usize = 1024 * 1024;
+
+/// How [`preallocate`] reserved the space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Allocation {
+    /// The filesystem allocated the blocks without writing them
+    Fallocate,
+    /// Every block was written with zeros
+    ZeroFilled,
+}
+
+/// Grow `file` to `len` bytes with every block allocated
+pub fn preallocate(file: &File, len: u64) -> Result<Allocation> {
+    #[cfg(target_os = "linux")]
+    match fallocate(file, len) {
+        Ok(()) => return Ok(Allocation::Fallocate),
+        Err(nix::errno::Errno::EOPNOTSUPP | nix::errno::Errno::ENOSYS) => {
+            tracing::debug!("fallocate is not supported here, writing zeros instead");
+        }
+        Err(e) => {
+            return Err(AivaError::StorageError(format!(
+                "Failed to preallocate {len} bytes: {e}"
+            )));
+        }
+    }
+
+    zero_fill(file, len)
+        .map_err(|e| AivaError::StorageError(format!("Failed to write {len} bytes: {e}")))?;
+    Ok(Allocation::ZeroFilled)
+}
+
+#[cfg(target_os = "linux")]
+fn fallocate(file: &File, len: u64) -> nix::Result<()> {
+    use nix::fcntl::FallocateFlags;
+    use std::os::fd::AsRawFd;
+
+    let len = nix::libc::off_t::try_from(len).map_err(|_| nix::errno::Errno::EFBIG)?;
+    nix::fcntl::fallocate(file.as_raw_fd(), FallocateFlags::empty(), 0, len)
+}
+
+/// Write `len` zero bytes from the start of `file`
+pub(crate) fn zero_fill(mut file: &File, len: u64) -> std::io::Result<()> {
+    let zeros = vec![0u8; ZERO_CHUNK];
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(ZERO_CHUNK as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()
+}
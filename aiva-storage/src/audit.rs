@@ -0,0 +1,121 @@
+//! Supply-chain audit of stored images.
+//!
+//! Every image is checked for a content digest, a verified signature and,
+//! when an allowlist is configured, a source on that allowlist. Only the
+//! allowlist is a hard failure; the other findings are reported so unsigned
+//! or legacy images can be replaced over time.
+
+use crate::{ImageInfo, ImageSource};
+use serde::Serialize;
+
+/// Something an audit found wrong with an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditFinding {
+    MissingDigest,
+    UnverifiedSignature,
+    SourceNotAllowed,
+}
+
+impl std::fmt::Display for AuditFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditFinding::MissingDigest => write!(f, "missing digest"),
+            AuditFinding::UnverifiedSignature => write!(f, "unverified signature"),
+            AuditFinding::SourceNotAllowed => write!(f, "source not allowed"),
+        }
+    }
+}
+
+/// Image sources that are trusted, as prefixes of URLs, registry
+/// repositories or local paths. An empty allowlist trusts every source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceAllowlist {
+    prefixes: Vec<String>,
+}
+
+impl SourceAllowlist {
+    pub fn new<I, S>(prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            prefixes: prefixes
+                .into_iter()
+                .map(Into::into)
+                .filter(|prefix: &String| !prefix.is_empty())
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prefixes.is_empty()
+    }
+
+    /// Whether `source` falls under one of the prefixes. A prefix only
+    /// matches at a path, tag or digest boundary, so `ghcr.io/org` does not
+    /// admit `ghcr.io/org-evil`.
+    pub fn allows(&self, source: &ImageSource) -> bool {
+        if self.prefixes.is_empty() {
+            return true;
+        }
+
+        let source = match source {
+            ImageSource::Registry { repo, .. } => repo.clone(),
+            other => other.to_string(),
+        };
+        self.prefixes.iter().any(|prefix| {
+            source.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                rest.is_empty()
+                    || prefix.ends_with('/')
+                    || rest.starts_with(['/', ':', '@', '?', '#'])
+            })
+        })
+    }
+}
+
+/// Audit result of one image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImageAudit {
+    pub id: String,
+    pub name: String,
+    pub source: String,
+    pub findings: Vec<AuditFinding>,
+}
+
+impl ImageAudit {
+    pub fn fails_allowlist(&self) -> bool {
+        self.findings.contains(&AuditFinding::SourceNotAllowed)
+    }
+}
+
+/// Audit `images` and return those with at least one finding, by name
+pub fn audit_images(images: &[ImageInfo], allowlist: &SourceAllowlist) -> Vec<ImageAudit> {
+    let mut audits: Vec<ImageAudit> = images
+        .iter()
+        .filter_map(|image| {
+            let provenance = &image.provenance;
+            let mut findings = Vec::new();
+            if provenance.digest.is_none() {
+                findings.push(AuditFinding::MissingDigest);
+            }
+            if !provenance.signature_verified {
+                findings.push(AuditFinding::UnverifiedSignature);
+            }
+            if !allowlist.allows(&image.source) {
+                findings.push(AuditFinding::SourceNotAllowed);
+            }
+
+            (!findings.is_empty()).then(|| ImageAudit {
+                id: image.id.clone(),
+                name: image.name.clone(),
+                source: image.source.to_string(),
+                findings,
+            })
+        })
+        .collect();
+
+    audits.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+    audits
+}
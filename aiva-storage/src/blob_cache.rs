@@ -0,0 +1,195 @@
+//! Content-addressed cache for image layers.
+//!
+//! Blobs live under `<storage_path>/cache/blobs/<algorithm>/<hex>` and are
+//! shared by every image that references them, so a base layer common to a
+//! fleet of images is downloaded once. `refs.json` records which images use
+//! each blob; blobs nothing refers to any more are removed by `prune`.
+
+use aiva_core::{AivaError, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+pub struct BlobCache {
+    root: PathBuf,
+    // digest -> ids of the images referencing it
+    refs: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl BlobCache {
+    /// Open the cache rooted at `root`, loading existing references
+    pub fn new(root: PathBuf) -> Result<Self> {
+        let refs_path = root.join("refs.json");
+        let refs = if refs_path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&refs_path)?)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            root,
+            refs: Mutex::new(refs),
+        })
+    }
+
+    /// Path of a blob inside the cache; fails for malformed digests so a
+    /// digest can never escape the cache directory
+    pub fn blob_path(&self, digest: &str) -> Result<PathBuf> {
+        let (algorithm, hex) = parse_digest(digest)?;
+        Ok(self.root.join(algorithm).join(hex))
+    }
+
+    pub fn contains(&self, digest: &str) -> bool {
+        self.blob_path(digest).is_ok_and(|path| path.exists())
+    }
+
+    /// Return the cached blob for `digest`, calling `fetch` to download it
+    /// into the given path on a miss. The downloaded content must match the
+    /// digest before it is admitted to the cache. Returns the blob path and
+    /// whether it was a cache hit.
+    pub async fn get_or_fetch<F, Fut>(&self, digest: &str, fetch: F) -> Result<(PathBuf, bool)>
+    where
+        F: FnOnce(PathBuf) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let path = self.blob_path(digest)?;
+        if path.exists() {
+            debug!("Blob cache hit for {}", digest);
+            return Ok((path, true));
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // Download next to the final location so the rename is atomic
+        let partial = path.with_extension("partial");
+        let result = async {
+            fetch(partial.clone()).await?;
+            verify_digest(&partial, digest).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            let _ = fs::remove_file(&partial).await;
+            return Err(e);
+        }
+
+        fs::rename(&partial, &path).await?;
+        info!("Cached blob {}", digest);
+        Ok((path, false))
+    }
+
+    /// Record that `image_id` uses the blob
+    pub async fn add_reference(&self, digest: &str, image_id: &str) -> Result<()> {
+        parse_digest(digest)?;
+        let mut refs = self.refs.lock().await;
+        refs.entry(digest.to_string())
+            .or_default()
+            .insert(image_id.to_string());
+        self.save_refs(&refs).await
+    }
+
+    /// Drop every reference held by `image_id`
+    pub async fn release_image(&self, image_id: &str) -> Result<()> {
+        let mut refs = self.refs.lock().await;
+        for users in refs.values_mut() {
+            users.remove(image_id);
+        }
+        self.save_refs(&refs).await
+    }
+
+    pub async fn ref_count(&self, digest: &str) -> usize {
+        self.refs.lock().await.get(digest).map_or(0, BTreeSet::len)
+    }
+
+    /// Delete cached blobs no image refers to and return their digests
+    pub async fn prune(&self) -> Result<Vec<String>> {
+        let mut refs = self.refs.lock().await;
+        let mut pruned = Vec::new();
+
+        if !self.root.exists() {
+            return Ok(pruned);
+        }
+
+        let mut algorithms = fs::read_dir(&self.root).await?;
+        while let Some(algorithm) = algorithms.next_entry().await? {
+            if !algorithm.file_type().await?.is_dir() {
+                continue;
+            }
+
+            let mut blobs = fs::read_dir(algorithm.path()).await?;
+            while let Some(blob) = blobs.next_entry().await? {
+                let digest = format!(
+                    "{}:{}",
+                    algorithm.file_name().to_string_lossy(),
+                    blob.file_name().to_string_lossy()
+                );
+                // Downloads still in progress are not blobs yet
+                if parse_digest(&digest).is_err() {
+                    continue;
+                }
+                if refs.get(&digest).is_none_or(BTreeSet::is_empty) {
+                    fs::remove_file(blob.path()).await?;
+                    refs.remove(&digest);
+                    pruned.push(digest);
+                }
+            }
+        }
+
+        self.save_refs(&refs).await?;
+        pruned.sort();
+        Ok(pruned)
+    }
+
+    async fn save_refs(&self, refs: &BTreeMap<String, BTreeSet<String>>) -> Result<()> {
+        fs::create_dir_all(&self.root).await?;
+        let content = serde_json::to_string_pretty(refs)?;
+        fs::write(self.root.join("refs.json"), content).await?;
+        Ok(())
+    }
+}
+
+fn parse_digest(digest: &str) -> Result<(&str, &str)> {
+    match digest.split_once(':') {
+        Some(("sha256", hex)) if hex.len() == 64 && hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            Ok(("sha256", hex))
+        }
+        _ => Err(AivaError::StorageError(format!(
+            "Unsupported blob digest: {digest}"
+        ))),
+    }
+}
+
+/// `sha256:<hex>` digest of a file, read in chunks so images of any size
+/// can be hashed
+pub(crate) async fn file_digest(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+async fn verify_digest(path: &Path, digest: &str) -> Result<()> {
+    let actual = file_digest(path).await?;
+
+    if actual.eq_ignore_ascii_case(digest) {
+        Ok(())
+    } else {
+        Err(AivaError::StorageError(format!(
+            "Blob digest mismatch: expected {digest}, got {actual}"
+        )))
+    }
+}
@@ -1,4 +1,7 @@
-use crate::{ImageFormat, ImageInfo, ImageSource};
+use crate::blob_cache::{BlobCache, file_digest};
+use crate::rootfs::{self, RootfsSpec};
+use crate::tools::{StorageOperation, preflight};
+use crate::{ImageFormat, ImageInfo, ImageProvenance, ImageSource};
 use aiva_core::{AivaError, Result};
 use async_trait::async_trait;
 use chrono::Utc;
@@ -11,9 +14,17 @@ use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+/// Pull progress callback: bytes written so far and the total when known
+pub type PullProgress<'a> = &'a (dyn Fn(u64, Option<u64>) + Send + Sync);
+
 #[async_trait]
 pub trait ImageBackend: Send + Sync {
-    async fn pull(&self, source: &ImageSource, path: &std::path::Path) -> Result<()>;
+    async fn pull(
+        &self,
+        source: &ImageSource,
+        path: &std::path::Path,
+        progress: PullProgress<'_>,
+    ) -> Result<()>;
     async fn push(&self, path: &std::path::Path, destination: &ImageSource) -> Result<()>;
     async fn convert(
         &self,
@@ -27,18 +38,60 @@ pub struct ImageManager {
     storage_path: PathBuf,
     images: Arc<RwLock<HashMap<String, ImageInfo>>>,
     backend: Box<dyn ImageBackend>,
+    blobs: BlobCache,
 }
 
 impl ImageManager {
     pub fn new(storage_path: PathBuf) -> Result<Self> {
         let backend = Box::new(LocalImageBackend::new());
+        let blobs = BlobCache::new(storage_path.join("cache").join("blobs"))?;
         Ok(Self {
             storage_path,
             images: Arc::new(RwLock::new(HashMap::new())),
             backend,
+            blobs,
         })
     }
 
+    pub fn blob_cache(&self) -> &BlobCache {
+        &self.blobs
+    }
+
+    /// Make the layers of `image_id` available locally, downloading only
+    /// those not already in the blob cache. `fetch` receives the layer digest
+    /// and the path to write it to. Returns the cached layer paths in order.
+    pub async fn fetch_layers<F, Fut>(
+        &self,
+        image_id: &str,
+        digests: &[String],
+        fetch: F,
+    ) -> Result<Vec<PathBuf>>
+    where
+        F: Fn(String, PathBuf) -> Fut,
+        Fut: std::future::Future<Output = Result<()>>,
+    {
+        let mut paths = Vec::with_capacity(digests.len());
+        let mut hits = 0;
+
+        for digest in digests {
+            let (path, hit) = self
+                .blobs
+                .get_or_fetch(digest, |dest| fetch(digest.clone(), dest))
+                .await?;
+            self.blobs.add_reference(digest, image_id).await?;
+            hits += usize::from(hit);
+            paths.push(path);
+        }
+
+        info!(
+            "Image {}: {} of {} layers served from cache",
+            image_id,
+            hits,
+            digests.len()
+        );
+        Ok(paths)
+    }
+
     pub async fn init(&self) -> Result<()> {
         fs::create_dir_all(&self.storage_path).await?;
         fs::create_dir_all(self.storage_path.join("images")).await?;
@@ -48,15 +101,31 @@ impl ImageManager {
     }
 
     pub async fn pull_image(&self, name: &str, source: ImageSource) -> Result<ImageInfo> {
+        self.pull_image_with_progress(name, source, &|_, _| {})
+            .await
+    }
+
+    /// Like [`ImageManager::pull_image`], reporting download progress
+    pub async fn pull_image_with_progress(
+        &self,
+        name: &str,
+        source: ImageSource,
+        progress: PullProgress<'_>,
+    ) -> Result<ImageInfo> {
         info!("Pulling image {} from {:?}", name, source);
 
         let image_id = Uuid::new_v4().to_string();
         let image_path = self.storage_path.join("images").join(&image_id);
 
-        self.backend.pull(&source, &image_path).await?;
+        if let Err(e) = self.backend.pull(&source, &image_path, progress).await {
+            // Don't leave a truncated download behind
+            let _ = fs::remove_file(&image_path).await;
+            return Err(e);
+        }
 
         let size_mb = fs::metadata(&image_path).await?.len() / (1024 * 1024);
         let format = Self::detect_format(&image_path)?;
+        let provenance = ImageProvenance::record(&source, file_digest(&image_path).await?);
 
         let info = ImageInfo {
             id: image_id.clone(),
@@ -65,6 +134,7 @@ impl ImageManager {
             format,
             source,
             created_at: Utc::now(),
+            provenance,
         };
 
         self.images.write().await.insert(image_id, info.clone());
@@ -76,20 +146,90 @@ impl ImageManager {
     pub async fn create_from_rootfs(&self, name: &str, rootfs_path: &PathBuf) -> Result<ImageInfo> {
         info!("Creating image {} from rootfs {:?}", name, rootfs_path);
 
+        preflight(StorageOperation::CreateImage)?;
         let image_id = Uuid::new_v4().to_string();
         let image_path = self.storage_path.join("images").join(&image_id);
 
         self.create_ext4_image(rootfs_path, &image_path).await?;
 
         let size_mb = fs::metadata(&image_path).await?.len() / (1024 * 1024);
+        let source = ImageSource::Local(rootfs_path.clone());
+        let provenance = ImageProvenance::record(&source, file_digest(&image_path).await?);
 
         let info = ImageInfo {
             id: image_id.clone(),
             name: name.to_string(),
             size_mb,
             format: ImageFormat::Raw,
-            source: ImageSource::Local(rootfs_path.clone()),
+            source,
             created_at: Utc::now(),
+            provenance,
+        };
+
+        self.images.write().await.insert(image_id, info.clone());
+        self.save_metadata().await?;
+
+        Ok(info)
+    }
+
+    /// Assemble an ext4 image from `spec`. Building the same spec against
+    /// the same base yields the same image digest. The normalized spec is
+    /// kept under `builds/` and recorded as the image source.
+    pub async fn build_rootfs(&self, spec: &RootfsSpec) -> Result<ImageInfo> {
+        spec.validate()?;
+        preflight(StorageOperation::BuildRootfs)?;
+        let spec = spec.normalized();
+        let base_digest = match &spec.base {
+            Some(base) => Some(file_digest(base).await?),
+            None => None,
+        };
+        let spec_digest = spec.digest(base_digest.as_deref())?;
+        info!("Building rootfs {} from spec {}", spec.name, spec_digest);
+
+        let builds = self.storage_path.join("builds");
+        fs::create_dir_all(&builds).await?;
+        fs::create_dir_all(self.storage_path.join("images")).await?;
+
+        let image_id = Uuid::new_v4().to_string();
+        let image_path = self.storage_path.join("images").join(&image_id);
+        let staging = builds.join(format!("staging-{image_id}"));
+
+        let built = async {
+            rootfs::stage(&spec, &staging).await?;
+            rootfs::make_ext4(
+                &staging,
+                &image_path,
+                spec.size_mb,
+                &rootfs::filesystem_uuid(&spec_digest),
+            )
+            .await?;
+            rootfs::pin_timestamps(&staging, &image_path).await
+        }
+        .await;
+        if let Err(e) = fs::remove_dir_all(&staging).await {
+            warn!("Failed to remove rootfs staging {:?}: {}", staging, e);
+        }
+        if let Err(e) = built {
+            let _ = fs::remove_file(&image_path).await;
+            return Err(e);
+        }
+
+        let manifest = builds.join(format!(
+            "{}.json",
+            spec_digest.trim_start_matches("sha256:")
+        ));
+        fs::write(&manifest, serde_json::to_string_pretty(&spec)?).await?;
+
+        let source = ImageSource::Local(manifest);
+        let provenance = ImageProvenance::record(&source, file_digest(&image_path).await?);
+        let info = ImageInfo {
+            id: image_id.clone(),
+            name: spec.name.clone(),
+            size_mb: spec.size_mb,
+            format: ImageFormat::Raw,
+            source,
+            created_at: Utc::now(),
+            provenance,
         };
 
         self.images.write().await.insert(image_id, info.clone());
@@ -106,6 +246,7 @@ impl ImageManager {
             if image_path.exists() {
                 fs::remove_file(&image_path).await?;
             }
+            self.blobs.release_image(image_id).await?;
             info!("Deleted image {}", info.name);
             self.save_metadata().await?;
             Ok(())
@@ -237,7 +378,12 @@ impl LocalImageBackend {
 
 #[async_trait]
 impl ImageBackend for LocalImageBackend {
-    async fn pull(&self, source: &ImageSource, path: &std::path::Path) -> Result<()> {
+    async fn pull(
+        &self,
+        source: &ImageSource,
+        path: &std::path::Path,
+        progress: PullProgress<'_>,
+    ) -> Result<()> {
         match source {
             ImageSource::Url(url) => {
                 info!("Downloading image from {}", url);
@@ -259,20 +405,33 @@ impl ImageBackend for LocalImageBackend {
                     });
                 }
 
+                let total = response.content_length();
+                let mut response = response;
                 let mut file = fs::File::create(path).await?;
-                let content = response
-                    .bytes()
-                    .await
-                    .map_err(|e| AivaError::NetworkError {
-                        operation: "image download".to_string(),
-                        cause: e.to_string(),
-                    })?;
+                let mut written = 0;
+
+                // Stream to disk so large images don't have to fit in memory
+                while let Some(chunk) =
+                    response
+                        .chunk()
+                        .await
+                        .map_err(|e| AivaError::NetworkError {
+                            operation: "image download".to_string(),
+                            cause: e.to_string(),
+                        })?
+                {
+                    file.write_all(&chunk).await?;
+                    written += chunk.len() as u64;
+                    progress(written, total);
+                }
 
-                file.write_all(&content).await?;
+                file.flush().await?;
                 Ok(())
             }
             ImageSource::Local(local_path) => {
-                fs::copy(local_path, path).await?;
+                let total = fs::metadata(local_path).await?.len();
+                let copied = fs::copy(local_path, path).await?;
+                progress(copied, Some(total));
                 Ok(())
             }
             ImageSource::Registry { repo: _, tag: _ } => {
@@ -304,6 +463,7 @@ impl ImageBackend for LocalImageBackend {
     ) -> Result<()> {
         use tokio::process::Command;
 
+        preflight(StorageOperation::ConvertImage)?;
         let format_str = match format {
             ImageFormat::Raw => "raw",
             ImageFormat::Qcow2 => "qcow2",
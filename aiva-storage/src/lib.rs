@@ -1,7 +1,15 @@
+pub mod allocate;
+pub mod audit;
+pub mod blob_cache;
 pub mod image;
+pub mod rootfs;
+pub mod tools;
 pub mod volume;
 
-use aiva_core::Result;
+#[cfg(test)]
+mod tests;
+
+use aiva_core::{AivaError, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -24,15 +32,39 @@ pub struct Volume {
     pub path: PathBuf,
     pub format: VolumeFormat,
     pub attached_to: Option<String>,
+    /// Shared volumes outlive the VMs using them and are never purged
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default)]
+    pub read_only: bool,
     pub created_at: chrono::DateTime<chrono::Utc>,
 }
 
+impl Volume {
+    /// Whether the volume belongs to `owner` alone and may go away with it
+    pub fn is_exclusively_owned_by(&self, owner: &str) -> bool {
+        self.attached_to.as_deref() == Some(owner) && !self.shared && !self.read_only
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VolumeConfig {
     pub name: String,
     pub size_mb: u64,
     pub format: VolumeFormat,
     pub sparse: bool,
+    #[serde(default)]
+    pub shared: bool,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+/// Outcome of removing a VM's volumes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VolumePurge {
+    pub deleted: Vec<Volume>,
+    /// Shared or read-only volumes that were only detached
+    pub preserved: Vec<Volume>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
@@ -47,6 +79,20 @@ pub struct BlockDeviceInfo {
     pub path: PathBuf,
     pub device: String,
     pub read_only: bool,
+    #[serde(default)]
+    pub size_mb: u64,
+}
+
+/// The drive a VM is given for an attached volume, see
+/// `VMOrchestrator::attach_drive`
+impl From<BlockDeviceInfo> for aiva_core::BlockDevice {
+    fn from(info: BlockDeviceInfo) -> Self {
+        aiva_core::BlockDevice {
+            path: info.path,
+            size_mb: info.size_mb,
+            read_only: info.read_only,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,6 +103,40 @@ pub struct ImageInfo {
     pub format: ImageFormat,
     pub source: ImageSource,
     pub created_at: chrono::DateTime<chrono::Utc>,
+    /// Missing for images stored before provenance was recorded
+    #[serde(default)]
+    pub provenance: ImageProvenance,
+}
+
+/// What is known about the origin of an image, next to its [`ImageSource`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageProvenance {
+    /// `sha256:<hex>` of the stored image file
+    pub digest: Option<String>,
+    /// Manifest digest the registry reference was pinned to
+    pub registry_digest: Option<String>,
+    /// Identity whose signature over the image was checked
+    pub signer: Option<String>,
+    pub signature_verified: bool,
+    pub recorded_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl ImageProvenance {
+    /// Provenance of an image just stored from `source` with content `digest`
+    pub fn record(source: &ImageSource, digest: String) -> Self {
+        let registry_digest = match source {
+            ImageSource::Registry { tag, .. } if tag.contains(':') => Some(tag.clone()),
+            _ => None,
+        };
+
+        Self {
+            digest: Some(digest),
+            registry_digest,
+            signer: None,
+            signature_verified: false,
+            recorded_at: Some(chrono::Utc::now()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,12 +147,95 @@ pub enum ImageFormat {
     Vhd,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ImageSource {
     Url(String),
     Local(PathBuf),
     Registry { repo: String, tag: String },
 }
 
-pub use image::ImageManager;
+/// File extensions that mark a bare file name as a local image
+const LOCAL_IMAGE_EXTENSIONS: &[&str] = &["ext4", "img", "raw", "qcow2", "vmdk", "vhd", "vhdx"];
+
+impl ImageSource {
+    /// Classify a user supplied source: `http(s)://` URLs, `file://` URIs and
+    /// filesystem paths, and otherwise a registry reference such as
+    /// `ubuntu`, `ghcr.io/org/rootfs:1.2` or `repo@sha256:<digest>`
+    pub fn parse(source: &str) -> Result<Self> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(AivaError::ConfigError(
+                "Image source cannot be empty".to_string(),
+            ));
+        }
+
+        if source.starts_with("http://") || source.starts_with("https://") {
+            return Ok(Self::Url(source.to_string()));
+        }
+        if let Some(path) = source.strip_prefix("file://") {
+            return Ok(Self::Local(PathBuf::from(path)));
+        }
+
+        let path = std::path::Path::new(source);
+        let looks_local = source.starts_with(['/', '.'])
+            || path.exists()
+            || path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| LOCAL_IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()));
+        if looks_local {
+            return Ok(Self::Local(path.to_path_buf()));
+        }
+
+        // A ':' only separates the tag when it follows the last '/', since
+        // registry hosts may carry a port
+        let (repo, tag) = if let Some((repo, digest)) = source.split_once('@') {
+            (repo, digest)
+        } else {
+            match source.rsplit_once(':') {
+                Some((repo, tag)) if !tag.contains('/') => (repo, tag),
+                _ => (source, "latest"),
+            }
+        };
+
+        if repo.is_empty() || tag.is_empty() {
+            return Err(AivaError::ConfigError(format!(
+                "Invalid image reference: {source}"
+            )));
+        }
+
+        Ok(Self::Registry {
+            repo: repo.to_string(),
+            tag: tag.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for ImageSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageSource::Url(url) => write!(f, "{url}"),
+            ImageSource::Local(path) => write!(f, "{}", path.display()),
+            ImageSource::Registry { repo, tag } if tag.contains(':') => write!(f, "{repo}@{tag}"),
+            ImageSource::Registry { repo, tag } => write!(f, "{repo}:{tag}"),
+        }
+    }
+}
+
+impl std::fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageFormat::Raw => write!(f, "raw"),
+            ImageFormat::Qcow2 => write!(f, "qcow2"),
+            ImageFormat::Vmdk => write!(f, "vmdk"),
+            ImageFormat::Vhd => write!(f, "vhd"),
+        }
+    }
+}
+
+pub use audit::{AuditFinding, ImageAudit, SourceAllowlist, audit_images};
+pub use blob_cache::BlobCache;
+pub use image::{ImageManager, PullProgress};
+pub use rootfs::{RootfsFile, RootfsSpec};
+pub use tools::{StorageOperation, ToolPreflight, ToolStatus, preflight};
 pub use volume::VolumeManager;
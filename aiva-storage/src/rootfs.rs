@@ -0,0 +1,345 @@
+//! Root filesystems assembled from a declarative [`RootfsSpec`].
+//!
+//! A build stages the base tarball, the spec's files and the output of its
+//! provisioning steps in a directory and hands the tree to `mke2fs -d`. The
+//! filesystem UUID and directory hash seed are derived from the spec digest,
+//! e2fsprogs runs with a fixed clock and every inode timestamp is pinned
+//! afterwards, so the same spec produces a byte-identical image.
+
+use aiva_core::{AivaError, Result, VMTemplate, shell_quote};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Smallest image mke2fs reliably creates an ext4 filesystem with journal in
+pub const MIN_ROOTFS_MB: u64 = 16;
+/// Timestamp given to every inode, 2024-01-01T00:00:00Z
+const BUILD_EPOCH: u64 = 1_704_067_200;
+
+/// Manifest of a root filesystem image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootfsSpec {
+    pub name: String,
+    pub size_mb: u64,
+    /// `.tar` or `.tar.gz` of the base root filesystem; without one the
+    /// image only holds `files`
+    #[serde(default)]
+    pub base: Option<PathBuf>,
+    /// Installed with apt-get before `commands` run
+    #[serde(default)]
+    pub packages: Vec<String>,
+    #[serde(default)]
+    pub files: Vec<RootfsFile>,
+    /// Shell scripts run in order inside the tree
+    #[serde(default)]
+    pub commands: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootfsFile {
+    /// Absolute path in the guest
+    pub path: String,
+    pub content: String,
+    #[serde(default = "default_file_mode")]
+    pub mode: u32,
+}
+
+fn default_file_mode() -> u32 {
+    0o644
+}
+
+impl RootfsSpec {
+    pub fn new(name: &str, size_mb: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            size_mb,
+            base: None,
+            packages: Vec::new(),
+            files: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    /// Add the setup script of `template` as a provisioning step
+    pub fn with_template(mut self, template: &VMTemplate) -> Self {
+        self.commands.push(template.get_setup_script());
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(AivaError::ConfigError(
+                "Rootfs spec needs a name".to_string(),
+            ));
+        }
+        if self.size_mb < MIN_ROOTFS_MB {
+            return Err(AivaError::ConfigError(format!(
+                "Rootfs '{}' needs size_mb of at least {MIN_ROOTFS_MB}",
+                self.name
+            )));
+        }
+        for file in &self.files {
+            guest_relative_path(&file.path)?;
+        }
+
+        let mut paths: Vec<&str> = self.files.iter().map(|f| f.path.as_str()).collect();
+        paths.sort_unstable();
+        if let Some(pair) = paths.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(AivaError::ConfigError(format!(
+                "Rootfs '{}' lists {} more than once",
+                self.name, pair[0]
+            )));
+        }
+        Ok(())
+    }
+
+    /// The spec with packages and files in a canonical order. The order of
+    /// `commands` is kept, it is significant.
+    pub fn normalized(&self) -> Self {
+        let mut spec = self.clone();
+        spec.packages.sort();
+        spec.packages.dedup();
+        spec.files.sort_by(|a, b| a.path.cmp(&b.path));
+        spec
+    }
+
+    /// `sha256:<hex>` identifying the build inputs: the normalized spec and
+    /// the digest of the base tarball
+    pub fn digest(&self, base_digest: Option<&str>) -> Result<String> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&self.normalized())?);
+        hasher.update(base_digest.unwrap_or_default().as_bytes());
+        Ok(format!("sha256:{:x}", hasher.finalize()))
+    }
+
+    /// Scripts run inside the staged tree, package installation first
+    pub fn provisioning_steps(&self) -> Vec<String> {
+        let mut steps = Vec::new();
+        let spec = self.normalized();
+        if !spec.packages.is_empty() {
+            let packages: Vec<String> = spec.packages.iter().map(|p| shell_quote(p)).collect();
+            steps.push(format!(
+                "apt-get update && DEBIAN_FRONTEND=noninteractive \
+                 apt-get install -y --no-install-recommends {}",
+                packages.join(" ")
+            ));
+        }
+        steps.extend(spec.commands);
+        steps
+    }
+}
+
+/// `path` relative to the root of the tree, refusing anything that could
+/// leave it
+fn guest_relative_path(path: &str) -> Result<PathBuf> {
+    let relative = path.strip_prefix('/').ok_or_else(|| {
+        AivaError::ConfigError(format!("Rootfs file path '{path}' must be absolute"))
+    })?;
+    let relative = PathBuf::from(relative);
+    let normal = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if !normal || relative.as_os_str().is_empty() {
+        return Err(AivaError::ConfigError(format!(
+            "Rootfs file path '{path}' must name a file below /"
+        )));
+    }
+    Ok(relative)
+}
+
+/// Filesystem UUID derived from a spec digest, also used as hash seed
+pub(crate) fn filesystem_uuid(spec_digest: &str) -> String {
+    let hex = spec_digest.strip_prefix("sha256:").unwrap_or(spec_digest);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Fill `dir` with the base, the files and the result of the provisioning
+/// steps of `spec`
+pub(crate) async fn stage(spec: &RootfsSpec, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).await?;
+
+    if let Some(base) = spec.base.clone() {
+        let dest = dir.to_path_buf();
+        tokio::task::spawn_blocking(move || unpack_base(&base, &dest))
+            .await
+            .map_err(|e| AivaError::StorageError(format!("Base extraction failed: {e}")))??;
+    }
+
+    for file in &spec.normalized().files {
+        let path = dir.join(guest_relative_path(&file.path)?);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&path, &file.content).await?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, std::fs::Permissions::from_mode(file.mode)).await?;
+        }
+    }
+
+    for (index, step) in spec.provisioning_steps().iter().enumerate() {
+        run_step(dir, index, step).await?;
+    }
+    Ok(())
+}
+
+fn unpack_base(base: &Path, dest: &Path) -> Result<()> {
+    let file = std::fs::File::open(base)?;
+    let name = base.to_string_lossy();
+    let reader: Box<dyn std::io::Read> = if name.ends_with(".gz") || name.ends_with(".tgz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(true);
+    archive.unpack(dest).map_err(|e| {
+        AivaError::StorageError(format!("Failed to unpack base {}: {e}", base.display()))
+    })
+}
+
+/// Run one provisioning script chrooted into `dir`, like the template setup
+/// in the Firecracker VM builder
+async fn run_step(dir: &Path, index: usize, script: &str) -> Result<()> {
+    let guest_path = format!("/tmp/aiva-step-{index}.sh");
+    let host_path = dir.join(guest_path.trim_start_matches('/'));
+    fs::create_dir_all(dir.join("tmp")).await?;
+
+    let script = if script.starts_with("#!") {
+        script.to_string()
+    } else {
+        format!("#!/bin/sh\nset -e\n{script}\n")
+    };
+    fs::write(&host_path, script).await?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&host_path, std::fs::Permissions::from_mode(0o755)).await?;
+    }
+
+    info!("Running rootfs provisioning step {}", index + 1);
+    let output = Command::new("sudo")
+        .arg("chroot")
+        .arg(dir)
+        .arg(&guest_path)
+        .output()
+        .await?;
+    let _ = fs::remove_file(&host_path).await;
+
+    if !output.status.success() {
+        return Err(AivaError::StorageError(format!(
+            "Rootfs provisioning step {} failed: {}",
+            index + 1,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Paths of everything below `dir` as seen from the guest, sorted
+fn guest_paths(dir: &Path) -> Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, paths: &mut Vec<String>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            paths.push(format!("/{}", relative.to_string_lossy()));
+            if std::fs::symlink_metadata(&path)?.is_dir() {
+                walk(root, &path, paths)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut paths = vec!["/".to_string()];
+    walk(dir, dir, &mut paths)?;
+    paths.sort();
+    Ok(paths)
+}
+
+/// Give every inode of `image` that came from `dir` the build timestamp.
+/// mke2fs copies atime and ctime from the staging tree, and ctime cannot be
+/// set on the host, so the inodes are rewritten with debugfs.
+pub(crate) async fn pin_timestamps(dir: &Path, image: &Path) -> Result<()> {
+    let mut commands = String::new();
+    for path in guest_paths(dir)? {
+        if path.contains('"') {
+            return Err(AivaError::StorageError(format!(
+                "Rootfs path {path} contains a double quote"
+            )));
+        }
+        for field in ["atime", "ctime", "mtime", "crtime"] {
+            commands.push_str(&format!("sif \"{path}\" {field} @{BUILD_EPOCH}\n"));
+        }
+    }
+    let script = tempfile::NamedTempFile::new()?;
+    fs::write(script.path(), commands).await?;
+
+    let output = Command::new("debugfs")
+        .arg("-w")
+        .arg("-f")
+        .arg(script.path())
+        .arg(image)
+        .env("E2FSPROGS_FAKE_TIME", BUILD_EPOCH.to_string())
+        .output()
+        .await
+        .map_err(|e| AivaError::StorageError(format!("Failed to run debugfs: {e}")))?;
+
+    // debugfs reports failed commands on stderr, after its version banner,
+    // but still exits with 0
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if let Some(error) = stderr.lines().find(|line| !line.starts_with("debugfs ")) {
+        return Err(AivaError::StorageError(format!(
+            "Failed to pin rootfs timestamps: {error}"
+        )));
+    }
+    if !output.status.success() {
+        return Err(AivaError::StorageError(format!(
+            "Failed to pin rootfs timestamps: {}",
+            stderr.trim()
+        )));
+    }
+    Ok(())
+}
+
+/// Create `output` as an ext4 image of `size_mb` holding the tree in `dir`
+pub(crate) async fn make_ext4(dir: &Path, output: &Path, size_mb: u64, uuid: &str) -> Result<()> {
+    std::fs::File::create(output)?.set_len(size_mb * 1024 * 1024)?;
+
+    debug!(
+        "Creating ext4 image {} with UUID {}",
+        output.display(),
+        uuid
+    );
+    let result = Command::new("mke2fs")
+        .args(["-q", "-F", "-t", "ext4", "-U", uuid, "-E"])
+        .arg(format!("hash_seed={uuid},root_owner=0:0"))
+        .arg("-d")
+        .arg(dir)
+        .arg(output)
+        // e2fsprogs before 1.47.1 only honours the former
+        .env("E2FSPROGS_FAKE_TIME", BUILD_EPOCH.to_string())
+        .env("SOURCE_DATE_EPOCH", BUILD_EPOCH.to_string())
+        .output()
+        .await
+        .map_err(|e| AivaError::StorageError(format!("Failed to run mke2fs: {e}")))?;
+
+    if !result.status.success() {
+        return Err(AivaError::StorageError(format!(
+            "Failed to create ext4 filesystem: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(())
+}
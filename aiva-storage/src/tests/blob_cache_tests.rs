@@ -0,0 +1,123 @@
+use crate::{BlobCache, ImageManager};
+use aiva_core::Result;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+
+fn digest_of(content: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(content))
+}
+
+const BASE_LAYER: &[u8] = b"shared base rootfs layer";
+const APP_A: &[u8] = b"app a layer";
+const APP_B: &[u8] = b"app b layer";
+
+fn layer_content(digest: &str) -> &'static [u8] {
+    [BASE_LAYER, APP_A, APP_B]
+        .into_iter()
+        .find(|content| digest_of(content) == digest)
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_second_pull_reuses_shared_layer() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let manager = ImageManager::new(dir.path().to_path_buf())?;
+    let downloads = Mutex::new(Vec::new());
+
+    let fetch = |digest: String, dest: std::path::PathBuf| {
+        downloads.lock().unwrap().push(digest.clone());
+        async move {
+            tokio::fs::write(dest, layer_content(&digest)).await?;
+            Ok(())
+        }
+    };
+
+    let first = vec![digest_of(BASE_LAYER), digest_of(APP_A)];
+    let second = vec![digest_of(BASE_LAYER), digest_of(APP_B)];
+
+    manager.fetch_layers("image-a", &first, fetch).await?;
+    let paths = manager.fetch_layers("image-b", &second, fetch).await?;
+
+    // The base layer was only downloaded by the first pull
+    assert_eq!(
+        *downloads.lock().unwrap(),
+        vec![digest_of(BASE_LAYER), digest_of(APP_A), digest_of(APP_B)]
+    );
+    assert_eq!(tokio::fs::read(&paths[0]).await?, BASE_LAYER);
+    assert_eq!(
+        manager.blob_cache().ref_count(&digest_of(BASE_LAYER)).await,
+        2
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prune_keeps_referenced_blobs() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache = BlobCache::new(dir.path().join("blobs"))?;
+
+    for (content, image) in [
+        (BASE_LAYER, "image-a"),
+        (APP_A, "image-a"),
+        (BASE_LAYER, "image-b"),
+    ] {
+        let digest = digest_of(content);
+        cache
+            .get_or_fetch(&digest, |dest| async move {
+                tokio::fs::write(dest, content).await?;
+                Ok(())
+            })
+            .await?;
+        cache.add_reference(&digest, image).await?;
+    }
+
+    cache.release_image("image-a").await?;
+    assert_eq!(cache.prune().await?, vec![digest_of(APP_A)]);
+    assert!(cache.contains(&digest_of(BASE_LAYER)));
+    assert!(!cache.contains(&digest_of(APP_A)));
+
+    // References survive reopening the cache
+    let reopened = BlobCache::new(dir.path().join("blobs"))?;
+    assert_eq!(reopened.ref_count(&digest_of(BASE_LAYER)).await, 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_corrupt_download_is_not_cached() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache = BlobCache::new(dir.path().to_path_buf())?;
+    let digest = digest_of(BASE_LAYER);
+
+    let result = cache
+        .get_or_fetch(&digest, |dest| async move {
+            tokio::fs::write(dest, b"truncated").await?;
+            Ok(())
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert!(!cache.contains(&digest));
+    assert!(cache.blob_path("sha256:../../etc/passwd").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_prune_leaves_downloads_in_progress_alone() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let cache = BlobCache::new(dir.path().to_path_buf())?;
+    let digest = digest_of(BASE_LAYER);
+
+    let cache = &cache;
+    let (path, hit) = cache
+        .get_or_fetch(&digest, |dest| async move {
+            tokio::fs::write(&dest, BASE_LAYER).await?;
+            assert!(cache.prune().await?.is_empty());
+            assert!(dest.exists());
+            Ok(())
+        })
+        .await?;
+
+    assert!(!hit);
+    assert_eq!(tokio::fs::read(path).await?, BASE_LAYER);
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use crate::{
+    AuditFinding, ImageFormat, ImageInfo, ImageProvenance, ImageSource, SourceAllowlist,
+    audit_images,
+};
+use std::path::PathBuf;
+
+fn image(name: &str, source: ImageSource, provenance: ImageProvenance) -> ImageInfo {
+    ImageInfo {
+        id: format!("id-{name}"),
+        name: name.to_string(),
+        size_mb: 512,
+        format: ImageFormat::Raw,
+        source,
+        created_at: chrono::Utc::now(),
+        provenance,
+    }
+}
+
+fn signed(digest: &str) -> ImageProvenance {
+    ImageProvenance {
+        digest: Some(digest.to_string()),
+        signer: Some("release@example.com".to_string()),
+        signature_verified: true,
+        ..Default::default()
+    }
+}
+
+fn images() -> Vec<ImageInfo> {
+    vec![
+        image(
+            "trusted",
+            ImageSource::Url("https://images.example.com/rootfs.ext4".to_string()),
+            signed("sha256:aa"),
+        ),
+        image(
+            "unsigned",
+            ImageSource::Registry {
+                repo: "ghcr.io/acme/agent".to_string(),
+                tag: "1.0".to_string(),
+            },
+            ImageProvenance {
+                digest: Some("sha256:bb".to_string()),
+                ..Default::default()
+            },
+        ),
+        // Stored before provenance existed
+        image(
+            "legacy",
+            ImageSource::Local(PathBuf::from("/opt/aiva/images/base.ext4")),
+            ImageProvenance::default(),
+        ),
+        image(
+            "lookalike",
+            ImageSource::Registry {
+                repo: "ghcr.io/acme-evil/agent".to_string(),
+                tag: "latest".to_string(),
+            },
+            signed("sha256:cc"),
+        ),
+    ]
+}
+
+#[test]
+fn test_audit_classifies_images() {
+    let allowlist = SourceAllowlist::new([
+        "https://images.example.com",
+        "ghcr.io/acme",
+        "/opt/aiva/images",
+    ]);
+    let audits = audit_images(&images(), &allowlist);
+
+    let summary: Vec<(&str, &[AuditFinding])> = audits
+        .iter()
+        .map(|audit| (audit.name.as_str(), audit.findings.as_slice()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (
+                "legacy",
+                &[
+                    AuditFinding::MissingDigest,
+                    AuditFinding::UnverifiedSignature
+                ][..]
+            ),
+            ("lookalike", &[AuditFinding::SourceNotAllowed][..]),
+            ("unsigned", &[AuditFinding::UnverifiedSignature][..]),
+        ]
+    );
+
+    let failing: Vec<&str> = audits
+        .iter()
+        .filter(|audit| audit.fails_allowlist())
+        .map(|audit| audit.name.as_str())
+        .collect();
+    assert_eq!(failing, ["lookalike"]);
+}
+
+#[test]
+fn test_empty_allowlist_trusts_every_source() {
+    let audits = audit_images(&images(), &SourceAllowlist::new(Vec::<String>::new()));
+    assert!(audits.iter().all(|audit| !audit.fails_allowlist()));
+    assert_eq!(audits.len(), 2);
+}
+
+#[test]
+fn test_recorded_provenance_keeps_pinned_registry_digest() {
+    let pinned = ImageSource::parse("ghcr.io/acme/agent@sha256:0123").unwrap();
+    let provenance = ImageProvenance::record(&pinned, "sha256:ff".to_string());
+    assert_eq!(provenance.registry_digest.as_deref(), Some("sha256:0123"));
+    assert_eq!(provenance.digest.as_deref(), Some("sha256:ff"));
+    assert!(provenance.recorded_at.is_some());
+    assert!(!provenance.signature_verified);
+
+    let tagged = ImageSource::parse("ghcr.io/acme/agent:1.0").unwrap();
+    assert!(
+        ImageProvenance::record(&tagged, "sha256:ff".to_string())
+            .registry_digest
+            .is_none()
+    );
+}
@@ -0,0 +1,55 @@
+use crate::ImageSource;
+use std::path::PathBuf;
+
+#[test]
+fn test_urls_and_paths_are_classified() {
+    assert_eq!(
+        ImageSource::parse("https://example.com/rootfs.ext4").unwrap(),
+        ImageSource::Url("https://example.com/rootfs.ext4".to_string())
+    );
+    assert_eq!(
+        ImageSource::parse("/var/lib/images/base.ext4").unwrap(),
+        ImageSource::Local(PathBuf::from("/var/lib/images/base.ext4"))
+    );
+    assert_eq!(
+        ImageSource::parse("./build/rootfs").unwrap(),
+        ImageSource::Local(PathBuf::from("./build/rootfs"))
+    );
+    assert_eq!(
+        ImageSource::parse("file:///tmp/rootfs.img").unwrap(),
+        ImageSource::Local(PathBuf::from("/tmp/rootfs.img"))
+    );
+    // A bare file name with an image extension is not a registry reference
+    assert_eq!(
+        ImageSource::parse("bionic.rootfs.ext4").unwrap(),
+        ImageSource::Local(PathBuf::from("bionic.rootfs.ext4"))
+    );
+}
+
+#[test]
+fn test_registry_references_are_classified() {
+    let registry = |repo: &str, tag: &str| ImageSource::Registry {
+        repo: repo.to_string(),
+        tag: tag.to_string(),
+    };
+
+    assert_eq!(
+        ImageSource::parse("ubuntu").unwrap(),
+        registry("ubuntu", "latest")
+    );
+    assert_eq!(
+        ImageSource::parse("ghcr.io/org/rootfs:1.2").unwrap(),
+        registry("ghcr.io/org/rootfs", "1.2")
+    );
+    // The port of a registry host is not a tag
+    assert_eq!(
+        ImageSource::parse("localhost:5000/rootfs").unwrap(),
+        registry("localhost:5000/rootfs", "latest")
+    );
+    assert_eq!(
+        ImageSource::parse("org/rootfs@sha256:abc123").unwrap(),
+        registry("org/rootfs", "sha256:abc123")
+    );
+    assert!(ImageSource::parse("").is_err());
+    assert!(ImageSource::parse("rootfs:").is_err());
+}
@@ -0,0 +1,12 @@
+#[cfg(test)]
+mod blob_cache_tests;
+#[cfg(test)]
+mod image_audit_tests;
+#[cfg(test)]
+mod image_source_tests;
+#[cfg(test)]
+mod rootfs_tests;
+#[cfg(test)]
+mod tools_tests;
+#[cfg(test)]
+mod volume_tests;
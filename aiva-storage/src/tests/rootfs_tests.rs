@@ -0,0 +1,63 @@
+use crate::{ImageManager, RootfsFile, RootfsSpec};
+use aiva_core::{Result, VMTemplate};
+
+fn agent_spec() -> RootfsSpec {
+    let mut spec = RootfsSpec::new("agent", 16);
+    // Deliberately out of order, the build sorts them
+    spec.files = vec![
+        RootfsFile {
+            path: "/opt/agent/run.sh".to_string(),
+            content: "#!/bin/sh\nexec agent\n".to_string(),
+            mode: 0o755,
+        },
+        RootfsFile {
+            path: "/etc/motd".to_string(),
+            content: "built by aiva\n".to_string(),
+            mode: 0o644,
+        },
+    ];
+    spec
+}
+
+#[tokio::test]
+async fn test_same_spec_builds_same_digest() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let manager = ImageManager::new(dir.path().to_path_buf())?;
+    manager.init().await?;
+
+    let first = manager.build_rootfs(&agent_spec()).await?;
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let mut reordered = agent_spec();
+    reordered.files.reverse();
+    let second = manager.build_rootfs(&reordered).await?;
+
+    assert_ne!(first.id, second.id);
+    assert!(first.provenance.digest.is_some());
+    assert_eq!(first.provenance.digest, second.provenance.digest);
+    assert_eq!(first.source, second.source);
+
+    let mut changed = agent_spec();
+    changed.files[1].content = "changed\n".to_string();
+    let third = manager.build_rootfs(&changed).await?;
+    assert_ne!(first.provenance.digest, third.provenance.digest);
+    Ok(())
+}
+
+#[test]
+fn test_spec_validation_and_steps() {
+    let mut spec = agent_spec();
+    assert!(spec.validate().is_ok());
+
+    spec.files[0].path = "/etc/../../escape".to_string();
+    assert!(spec.validate().is_err());
+    spec.files[0].path = "/etc/motd".to_string();
+    assert!(spec.validate().is_err());
+    assert!(RootfsSpec::new("tiny", 4).validate().is_err());
+
+    let mut spec = RootfsSpec::new("python", 2048).with_template(&VMTemplate::python3_uv());
+    spec.packages = vec!["git".to_string(), "curl".to_string(), "git".to_string()];
+    let steps = spec.provisioning_steps();
+    assert_eq!(steps.len(), 2);
+    assert!(steps[0].ends_with("--no-install-recommends 'curl' 'git'"));
+    assert_eq!(steps[1], VMTemplate::python3_uv().get_setup_script());
+}
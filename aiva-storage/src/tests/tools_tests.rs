@@ -0,0 +1,67 @@
+use crate::{StorageOperation, ToolPreflight, VolumeFormat};
+use aiva_core::{AivaError, Result};
+use std::path::Path;
+
+fn fake_tool(dir: &Path, name: &str) -> Result<()> {
+    let path = dir.join(name);
+    std::fs::write(&path, "#!/bin/sh\n")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_volume_create_reports_missing_mkfs() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    fake_tool(dir.path(), "dd")?;
+    let preflight = ToolPreflight::with_search_path(dir.path());
+
+    let ext4 = StorageOperation::CreateVolume {
+        format: VolumeFormat::Ext4,
+        sparse: true,
+    };
+    let missing: Vec<&str> = preflight
+        .missing_tools(ext4)
+        .iter()
+        .map(|t| t.name)
+        .collect();
+    assert_eq!(missing, vec!["mkfs.ext4"]);
+
+    match preflight.check(ext4) {
+        Err(AivaError::StorageError(message)) => {
+            assert!(message.contains("`mkfs.ext4`"), "{message}");
+            assert!(message.contains("e2fsprogs"), "{message}");
+        }
+        other => panic!("expected a storage error, got {other:?}"),
+    }
+
+    // Raw volumes written with dd need nothing else
+    preflight.check(StorageOperation::CreateVolume {
+        format: VolumeFormat::Raw,
+        sparse: true,
+    })?;
+
+    // The answer for dd is cached, removing it changes nothing
+    std::fs::remove_file(dir.path().join("dd"))?;
+    assert!(preflight.locate("dd").is_some());
+    Ok(())
+}
+
+#[test]
+fn test_non_executable_files_are_not_tools() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("qemu-img"), "")?;
+    let preflight = ToolPreflight::with_search_path(dir.path());
+
+    let report = preflight.report();
+    let qemu = report.iter().find(|s| s.tool == "qemu-img").unwrap();
+    assert!(qemu.path.is_none());
+    assert_eq!(
+        qemu.needed_for,
+        vec!["qcow2 volume creation", "image conversion"]
+    );
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use crate::allocate::{preallocate, zero_fill};
+use crate::volume::qcow2_virtual_size;
+use crate::{ToolPreflight, VolumeConfig, VolumeFormat, VolumeManager};
+use aiva_core::{AivaError, Result};
+
+fn volume_config(name: &str, shared: bool) -> VolumeConfig {
+    VolumeConfig {
+        name: name.to_string(),
+        size_mb: 1,
+        format: VolumeFormat::Raw,
+        sparse: false,
+        shared,
+        read_only: false,
+    }
+}
+
+#[tokio::test]
+async fn test_purge_removes_owned_volumes_but_keeps_shared() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let manager = VolumeManager::new(dir.path().to_path_buf())?;
+    manager.init().await?;
+
+    let owned = manager
+        .create_volume(volume_config("scratch", false))
+        .await?;
+    let shared = manager.create_volume(volume_config("models", true)).await?;
+    let other = manager.create_volume(volume_config("other", false)).await?;
+
+    manager.attach_volume(&owned.id, "vm-1").await?;
+    manager.attach_volume(&shared.id, "vm-1").await?;
+    manager.attach_volume(&other.id, "vm-2").await?;
+
+    let purge = manager.purge_vm_volumes("vm-1").await?;
+    assert_eq!(purge.deleted.len(), 1);
+    assert_eq!(purge.deleted[0].id, owned.id);
+    assert_eq!(purge.preserved.len(), 1);
+    assert_eq!(purge.preserved[0].id, shared.id);
+
+    assert!(manager.get_volume(&owned.id).await.is_err());
+    assert!(!owned.path.exists());
+
+    let shared = manager.get_volume(&shared.id).await?;
+    assert!(shared.attached_to.is_none());
+    assert!(shared.path.exists());
+
+    // Volumes of other VMs are untouched
+    let other = manager.get_volume(&other.id).await?;
+    assert_eq!(other.attached_to.as_deref(), Some("vm-2"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_qcow2_volume_has_requested_virtual_size() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let manager = VolumeManager::new(dir.path().to_path_buf())?;
+    manager.init().await?;
+
+    let config = VolumeConfig {
+        format: VolumeFormat::Qcow2,
+        size_mb: 64,
+        sparse: true,
+        ..volume_config("disk", false)
+    };
+    let result = manager.create_volume(config).await;
+    if ToolPreflight::global().locate("qemu-img").is_none() {
+        // Without qemu-img the preflight names it up front
+        match result {
+            Err(AivaError::StorageError(message)) => assert!(message.contains("qemu-img")),
+            other => panic!("expected a preflight error, got {other:?}"),
+        }
+        return Ok(());
+    }
+
+    let volume = result?;
+    assert_eq!(qcow2_virtual_size(&volume.path).await?, 64 * 1024 * 1024);
+    // Clusters are only allocated as the guest writes
+    assert!(std::fs::metadata(&volume.path)?.len() < 64 * 1024 * 1024);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_qcow2_header_parsing() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("disk.qcow2");
+
+    let mut header = vec![0u8; 512];
+    header[0..4].copy_from_slice(b"QFI\xfb");
+    header[4..8].copy_from_slice(&3u32.to_be_bytes());
+    header[24..32].copy_from_slice(&(5u64 << 30).to_be_bytes());
+    std::fs::write(&path, &header)?;
+    assert_eq!(qcow2_virtual_size(&path).await?, 5 << 30);
+
+    std::fs::write(&path, vec![0u8; 512])?;
+    assert!(qcow2_virtual_size(&path).await.is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_non_sparse_volume_has_full_length() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let manager = VolumeManager::new(dir.path().to_path_buf())?;
+    manager.init().await?;
+
+    let volume = manager.create_volume(volume_config("full", false)).await?;
+    assert_eq!(std::fs::metadata(&volume.path)?.len(), 1024 * 1024);
+    Ok(())
+}
+
+#[test]
+fn test_zero_fill_writes_every_byte() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("zeros");
+    let file = std::fs::File::options()
+        .create(true)
+        .truncate(true)
+        .read(true)
+        .write(true)
+        .open(&path)?;
+
+    // More than one chunk, not a multiple of it
+    let len = 3 * 1024 * 1024 / 2;
+    zero_fill(&file, len)?;
+
+    let content = std::fs::read(&path)?;
+    assert_eq!(content.len() as u64, len);
+    assert!(content.iter().all(|&b| b == 0));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+#[ignore = "Needs a filesystem that reports allocated blocks, e.g. ext4 or xfs"]
+fn test_preallocation_reserves_blocks() -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let len = 8 * 1024 * 1024;
+    // Under the crate rather than in /tmp, which is often tmpfs
+    let dir = tempfile::tempdir_in(env!("CARGO_MANIFEST_DIR"))?;
+
+    let sparse = std::fs::File::create(dir.path().join("sparse"))?;
+    sparse.set_len(len)?;
+    assert!(sparse.metadata()?.blocks() * 512 < len);
+
+    let file = std::fs::File::create(dir.path().join("preallocated"))?;
+    let allocation = preallocate(&file, len)?;
+    let metadata = file.metadata()?;
+    assert_eq!(metadata.len(), len);
+    assert!(
+        metadata.blocks() * 512 >= len,
+        "{allocation:?} allocated {} of {len} bytes",
+        metadata.blocks() * 512
+    );
+    Ok(())
+}
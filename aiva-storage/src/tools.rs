@@ -0,0 +1,250 @@
+//! Preflight checks for the host tools storage operations shell out to.
+//!
+//! Running a missing binary fails with a bare "No such file or directory",
+//! so operations first look their tools up on `PATH` and report which one is
+//! missing and which package provides it. Lookups are cached per process.
+
+use crate::VolumeFormat;
+use aiva_core::{AivaError, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// An external program and the package providing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostTool {
+    pub name: &'static str,
+    pub package: &'static str,
+}
+
+const DD: HostTool = HostTool {
+    name: "dd",
+    package: "coreutils",
+};
+const TRUNCATE: HostTool = HostTool {
+    name: "truncate",
+    package: "coreutils",
+};
+const MKFS_EXT4: HostTool = HostTool {
+    name: "mkfs.ext4",
+    package: "e2fsprogs",
+};
+const MKE2FS: HostTool = HostTool {
+    name: "mke2fs",
+    package: "e2fsprogs",
+};
+const DEBUGFS: HostTool = HostTool {
+    name: "debugfs",
+    package: "e2fsprogs",
+};
+const E2FSCK: HostTool = HostTool {
+    name: "e2fsck",
+    package: "e2fsprogs",
+};
+const RESIZE2FS: HostTool = HostTool {
+    name: "resize2fs",
+    package: "e2fsprogs",
+};
+const QEMU_IMG: HostTool = HostTool {
+    name: "qemu-img",
+    package: "qemu-utils",
+};
+
+impl HostTool {
+    pub fn install_hint(&self) -> String {
+        if cfg!(target_os = "macos") {
+            let formula = match self.package {
+                "qemu-utils" => "qemu",
+                package => package,
+            };
+            format!("Install it with `brew install {formula}`")
+        } else {
+            format!(
+                "Install the {} package, e.g. `sudo apt install {}`",
+                self.package, self.package
+            )
+        }
+    }
+}
+
+/// Storage operations that run host tools
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageOperation {
+    CreateVolume { format: VolumeFormat, sparse: bool },
+    CreateImage,
+    ConvertImage,
+    BuildRootfs,
+    ResizeRootfs,
+}
+
+impl StorageOperation {
+    /// Every operation with the tools it always needs, for `aiva doctor`
+    pub const ALL: [StorageOperation; 6] = [
+        StorageOperation::CreateVolume {
+            format: VolumeFormat::Ext4,
+            sparse: true,
+        },
+        StorageOperation::CreateVolume {
+            format: VolumeFormat::Qcow2,
+            sparse: false,
+        },
+        StorageOperation::CreateImage,
+        StorageOperation::ConvertImage,
+        StorageOperation::BuildRootfs,
+        StorageOperation::ResizeRootfs,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            StorageOperation::CreateVolume {
+                format: VolumeFormat::Qcow2,
+                ..
+            } => "qcow2 volume creation",
+            StorageOperation::CreateVolume { .. } => "volume creation",
+            StorageOperation::CreateImage => "image creation",
+            StorageOperation::ConvertImage => "image conversion",
+            StorageOperation::BuildRootfs => "rootfs builds",
+            StorageOperation::ResizeRootfs => "rootfs resizing",
+        }
+    }
+
+    pub fn required_tools(&self) -> Vec<HostTool> {
+        match *self {
+            // qemu-img creates qcow2 volumes on its own
+            StorageOperation::CreateVolume {
+                format: VolumeFormat::Qcow2,
+                ..
+            } => vec![QEMU_IMG],
+            StorageOperation::CreateVolume { format, sparse } => {
+                let mut tools = Vec::new();
+                if sparse {
+                    tools.push(DD);
+                }
+                if format == VolumeFormat::Ext4 {
+                    tools.push(MKFS_EXT4);
+                }
+                tools
+            }
+            StorageOperation::CreateImage => vec![DD, MKFS_EXT4],
+            StorageOperation::ConvertImage => vec![QEMU_IMG],
+            StorageOperation::BuildRootfs => vec![MKE2FS, DEBUGFS],
+            StorageOperation::ResizeRootfs => vec![TRUNCATE, E2FSCK, RESIZE2FS],
+        }
+    }
+}
+
+/// Result of looking up one tool, as shown by `aiva doctor`
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolStatus {
+    pub tool: &'static str,
+    pub path: Option<PathBuf>,
+    pub package: &'static str,
+    /// Operations that cannot run without the tool
+    pub needed_for: Vec<&'static str>,
+}
+
+/// Looks tools up on a search path and remembers the answers
+pub struct ToolPreflight {
+    search_path: Option<OsString>,
+    found: Mutex<HashMap<&'static str, Option<PathBuf>>>,
+}
+
+impl ToolPreflight {
+    /// The preflight for the `PATH` of this process
+    pub fn global() -> &'static ToolPreflight {
+        static GLOBAL: OnceLock<ToolPreflight> = OnceLock::new();
+        GLOBAL.get_or_init(|| ToolPreflight {
+            search_path: std::env::var_os("PATH"),
+            found: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// A preflight searching `search_path` instead of `PATH`
+    pub fn with_search_path(search_path: impl Into<OsString>) -> Self {
+        Self {
+            search_path: Some(search_path.into()),
+            found: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn locate(&self, tool: &'static str) -> Option<PathBuf> {
+        let mut found = self.found.lock().unwrap_or_else(|e| e.into_inner());
+        found
+            .entry(tool)
+            .or_insert_with(|| {
+                let search_path = self.search_path.as_ref()?;
+                std::env::split_paths(search_path)
+                    .map(|dir| dir.join(tool))
+                    .find(|candidate| is_executable(candidate))
+            })
+            .clone()
+    }
+
+    /// Tools `operation` needs that are not on the search path
+    pub fn missing_tools(&self, operation: StorageOperation) -> Vec<HostTool> {
+        operation
+            .required_tools()
+            .into_iter()
+            .filter(|tool| self.locate(tool.name).is_none())
+            .collect()
+    }
+
+    /// Fail with the first tool `operation` needs that is missing
+    pub fn check(&self, operation: StorageOperation) -> Result<()> {
+        match self.missing_tools(operation).first() {
+            None => Ok(()),
+            Some(tool) => Err(AivaError::StorageError(format!(
+                "`{}` is not on PATH but is needed for {}. {}",
+                tool.name,
+                operation.description(),
+                tool.install_hint()
+            ))),
+        }
+    }
+
+    /// Status of every tool any storage operation uses
+    pub fn report(&self) -> Vec<ToolStatus> {
+        let mut statuses: Vec<ToolStatus> = Vec::new();
+        for operation in StorageOperation::ALL {
+            for tool in operation.required_tools() {
+                let needed_for = operation.description();
+                match statuses.iter_mut().find(|s| s.tool == tool.name) {
+                    Some(status) => {
+                        if !status.needed_for.contains(&needed_for) {
+                            status.needed_for.push(needed_for);
+                        }
+                    }
+                    None => statuses.push(ToolStatus {
+                        tool: tool.name,
+                        path: self.locate(tool.name),
+                        package: tool.package,
+                        needed_for: vec![needed_for],
+                    }),
+                }
+            }
+        }
+        statuses
+    }
+}
+
+/// Check the tools of `operation` against the `PATH` of this process
+pub fn preflight(operation: StorageOperation) -> Result<()> {
+    ToolPreflight::global().check(operation)
+}
+
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = std::fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
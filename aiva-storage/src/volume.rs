@@ -1,4 +1,6 @@
-use crate::{BlockDeviceInfo, StorageBackend, Volume, VolumeConfig, VolumeFormat};
+use crate::allocate::preallocate;
+use crate::tools::{StorageOperation, preflight};
+use crate::{BlockDeviceInfo, StorageBackend, Volume, VolumeConfig, VolumeFormat, VolumePurge};
 use aiva_core::{AivaError, Result};
 use async_trait::async_trait;
 use chrono::Utc;
@@ -55,12 +57,12 @@ impl VolumeManager {
         // Check if volume is attached
         {
             let volumes = self.volumes.read().await;
-            if let Some(volume) = volumes.get(volume_id) {
-                if volume.attached_to.is_some() {
-                    return Err(AivaError::StorageError(
-                        "Cannot delete attached volume".to_string(),
-                    ));
-                }
+            if let Some(volume) = volumes.get(volume_id)
+                && volume.attached_to.is_some()
+            {
+                return Err(AivaError::StorageError(
+                    "Cannot delete attached volume".to_string(),
+                ));
             }
         }
 
@@ -75,7 +77,7 @@ impl VolumeManager {
         info!("Attaching volume {} to VM {}", volume_id, vm_id);
 
         // Check if volume exists and is not already attached
-        {
+        let (read_only, size_mb) = {
             let volumes = self.volumes.read().await;
             if let Some(volume) = volumes.get(volume_id) {
                 if let Some(attached_to) = &volume.attached_to {
@@ -83,18 +85,20 @@ impl VolumeManager {
                         "Volume already attached to {attached_to}"
                     )));
                 }
+                (volume.read_only, volume.size_mb)
             } else {
                 return Err(AivaError::StorageError(format!(
                     "Volume {volume_id} not found"
                 )));
             }
-        }
+        };
 
-        let info = self.backend.attach_volume(volume_id, vm_id).await?;
+        let mut info = self.backend.attach_volume(volume_id, vm_id).await?;
+        info.read_only = read_only;
+        info.size_mb = size_mb;
 
-        // Update volume state
-        let mut volumes = self.volumes.write().await;
-        if let Some(volume) = volumes.get_mut(volume_id) {
+        // Update volume state, releasing the lock before saving
+        if let Some(volume) = self.volumes.write().await.get_mut(volume_id) {
             volume.attached_to = Some(vm_id.to_string());
         }
 
@@ -107,9 +111,8 @@ impl VolumeManager {
 
         self.backend.detach_volume(volume_id).await?;
 
-        // Update volume state
-        let mut volumes = self.volumes.write().await;
-        if let Some(volume) = volumes.get_mut(volume_id) {
+        // Update volume state, releasing the lock before saving
+        if let Some(volume) = self.volumes.write().await.get_mut(volume_id) {
             volume.attached_to = None;
         }
 
@@ -117,6 +120,53 @@ impl VolumeManager {
         Ok(())
     }
 
+    /// Detach every volume attached to `owner`, deleting the ones it owns
+    /// exclusively. Shared and read-only volumes are detached but kept.
+    pub async fn purge_vm_volumes(&self, owner: &str) -> Result<VolumePurge> {
+        let attached: Vec<Volume> = self
+            .volumes
+            .read()
+            .await
+            .values()
+            .filter(|volume| volume.attached_to.as_deref() == Some(owner))
+            .cloned()
+            .collect();
+
+        let mut purge = VolumePurge::default();
+        for volume in attached {
+            self.detach_volume(&volume.id).await?;
+
+            if volume.is_exclusively_owned_by(owner) {
+                self.delete_volume(&volume.id).await?;
+                purge.deleted.push(volume);
+            } else {
+                info!("Preserving shared volume {} ({})", volume.name, volume.id);
+                purge.preserved.push(volume);
+            }
+        }
+
+        Ok(purge)
+    }
+
+    /// Detach every volume attached to `owner` and keep it, so a VM created
+    /// later can attach it again
+    pub async fn release_vm_volumes(&self, owner: &str) -> Result<Vec<Volume>> {
+        let attached: Vec<Volume> = self
+            .volumes
+            .read()
+            .await
+            .values()
+            .filter(|volume| volume.attached_to.as_deref() == Some(owner))
+            .cloned()
+            .collect();
+
+        for volume in &attached {
+            self.detach_volume(&volume.id).await?;
+        }
+
+        Ok(attached)
+    }
+
     pub async fn list_volumes(&self) -> Result<Vec<Volume>> {
         let volumes = self.volumes.read().await;
         Ok(volumes.values().cloned().collect())
@@ -188,19 +238,44 @@ impl LocalStorageBackend {
         Ok(())
     }
 
-    async fn format_volume(&self, path: &std::path::Path, format: VolumeFormat) -> Result<()> {
+    /// Create a file with all `size_mb` reserved on disk, see [`preallocate`]
+    async fn create_preallocated_file(&self, path: &std::path::Path, size_mb: u64) -> Result<()> {
+        let file = fs::File::create(path).await?.into_std().await;
+        let allocation =
+            tokio::task::spawn_blocking(move || preallocate(&file, size_mb * 1024 * 1024))
+                .await
+                .map_err(|e| AivaError::StorageError(format!("Preallocation failed: {e}")))??;
+
+        debug!(
+            "Preallocated {}MB for {} ({:?})",
+            size_mb,
+            path.display(),
+            allocation
+        );
+        Ok(())
+    }
+
+    /// Put a filesystem on `path`. On a preallocated file mkfs must not
+    /// discard, which would punch the reserved blocks out again.
+    async fn format_volume(
+        &self,
+        path: &std::path::Path,
+        format: VolumeFormat,
+        sparse: bool,
+    ) -> Result<()> {
         use tokio::process::Command;
 
         match format {
-            VolumeFormat::Raw => {
-                // Raw format doesn't need formatting
-                Ok(())
-            }
+            // Raw format doesn't need formatting, qcow2 volumes are created
+            // formatted by create_qcow2
+            VolumeFormat::Raw | VolumeFormat::Qcow2 => Ok(()),
             VolumeFormat::Ext4 => {
-                let output = Command::new("mkfs.ext4")
-                    .args(["-F", &path.to_string_lossy()])
-                    .output()
-                    .await?;
+                let mut command = Command::new("mkfs.ext4");
+                command.arg("-F");
+                if !sparse {
+                    command.args(["-E", "nodiscard"]);
+                }
+                let output = command.arg(path).output().await?;
 
                 if !output.status.success() {
                     return Err(AivaError::StorageError(format!(
@@ -210,50 +285,90 @@ impl LocalStorageBackend {
                 }
                 Ok(())
             }
-            VolumeFormat::Qcow2 => {
-                // For qcow2, we need to use qemu-img instead
-                let output = Command::new("qemu-img")
-                    .args([
-                        "create",
-                        "-f",
-                        "qcow2",
-                        &path.to_string_lossy(),
-                        &format!("{}M", path.metadata().unwrap().len() / (1024 * 1024)),
-                    ])
-                    .output()
-                    .await?;
+        }
+    }
 
-                if !output.status.success() {
-                    return Err(AivaError::StorageError(format!(
-                        "Failed to create qcow2: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    )));
-                }
-                Ok(())
-            }
+    /// qemu-img writes the whole qcow2 file itself; the image only grows
+    /// as the guest writes, so there is nothing to preallocate
+    async fn create_qcow2(&self, path: &std::path::Path, size_mb: u64) -> Result<()> {
+        use tokio::process::Command;
+
+        let output = Command::new("qemu-img")
+            .args(["create", "-q", "-f", "qcow2"])
+            .arg(path)
+            .arg(format!("{size_mb}M"))
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(AivaError::StorageError(format!(
+                "Failed to create qcow2: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let virtual_size = qcow2_virtual_size(path).await?;
+        if virtual_size != size_mb * 1024 * 1024 {
+            return Err(AivaError::StorageError(format!(
+                "qemu-img created {} with {virtual_size} bytes instead of {size_mb}MB",
+                path.display()
+            )));
         }
+        Ok(())
     }
 }
 
+/// Virtual disk size recorded in the header of a qcow2 image
+pub(crate) async fn qcow2_virtual_size(path: &std::path::Path) -> Result<u64> {
+    use tokio::io::AsyncReadExt;
+
+    let mut header = [0u8; 32];
+    fs::File::open(path)
+        .await?
+        .read_exact(&mut header)
+        .await
+        .map_err(|e| {
+            AivaError::StorageError(format!("{} is not a qcow2 image: {e}", path.display()))
+        })?;
+    if &header[0..4] != b"QFI\xfb" {
+        return Err(AivaError::StorageError(format!(
+            "{} is not a qcow2 image: bad magic",
+            path.display()
+        )));
+    }
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&header[24..32]);
+    Ok(u64::from_be_bytes(size))
+}
+
 #[async_trait]
 impl StorageBackend for LocalStorageBackend {
     async fn create_volume(&self, config: &VolumeConfig) -> Result<Volume> {
+        preflight(StorageOperation::CreateVolume {
+            format: config.format,
+            sparse: config.sparse,
+        })?;
+
         let volume_id = Uuid::new_v4().to_string();
         let volume_path = self.storage_path.join("volumes").join(&volume_id);
 
-        // Create volume file
-        if config.sparse {
-            self.create_sparse_file(&volume_path, config.size_mb)
-                .await?;
+        if config.format == VolumeFormat::Qcow2 {
+            self.create_qcow2(&volume_path, config.size_mb).await?;
         } else {
-            // Create full file
-            let file = fs::File::create(&volume_path).await?;
-            file.set_len(config.size_mb * 1024 * 1024).await?;
-        }
+            // Create volume file
+            if config.sparse {
+                self.create_sparse_file(&volume_path, config.size_mb)
+                    .await?;
+            } else {
+                self.create_preallocated_file(&volume_path, config.size_mb)
+                    .await?;
+            }
 
-        // Format if needed
-        if config.format != VolumeFormat::Raw {
-            self.format_volume(&volume_path, config.format).await?;
+            // Format if needed
+            if config.format != VolumeFormat::Raw {
+                self.format_volume(&volume_path, config.format, config.sparse)
+                    .await?;
+            }
         }
 
         Ok(Volume {
@@ -263,6 +378,8 @@ impl StorageBackend for LocalStorageBackend {
             path: volume_path,
             format: config.format,
             attached_to: None,
+            shared: config.shared,
+            read_only: config.read_only,
             created_at: Utc::now(),
         })
     }
@@ -290,6 +407,7 @@ impl StorageBackend for LocalStorageBackend {
             path: volume_path,
             device: format!("/dev/vd{}", volume_id.chars().next().unwrap_or('a')),
             read_only: false,
+            size_mb: 0,
         })
     }
 